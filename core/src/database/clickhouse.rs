@@ -50,6 +50,12 @@ impl Display for ClickhouseFormat {
     }
 }
 
+/// Body sent along with the query, gzip bodies are sent with `Content-Encoding: gzip`
+enum InsertBody {
+    Plain(String),
+    Gzip(Vec<u8>),
+}
+
 #[derive(Clone)]
 pub struct ClickhouseHttp {
     pub url: String,
@@ -139,7 +145,7 @@ impl ClickhouseHttp {
         Ok(data)
     }
 
-    async fn do_execute(&self, sql: &str, body: Option<String>) -> Result<String> {
+    async fn do_execute(&self, sql: &str, body: Option<InsertBody>) -> Result<String> {
         let response = self.execute_inner(sql, body, None).await?;
         let status = response.status();
         let text = response.text().await.map_err(HttpTransportError::Reqwest)?;
@@ -155,7 +161,7 @@ impl ClickhouseHttp {
     async fn execute_inner(
         &self,
         sql: &str,
-        body: Option<String>,
+        body: Option<InsertBody>,
         output_format: Option<ClickhouseFormat>,
     ) -> Result<Response> {
         let http_client_builder = reqwest::Client::builder().danger_accept_invalid_certs(true);
@@ -206,10 +212,13 @@ impl ClickhouseHttp {
             .headers(headers.into_inner());
 
         // Send query as body if body is None
-        let req = if let Some(body) = body {
-            req.query(&[("query", sql.as_str())]).body(body)
-        } else {
-            req.body(sql)
+        let req = match body {
+            Some(InsertBody::Plain(body)) => req.query(&[("query", sql.as_str())]).body(body),
+            Some(InsertBody::Gzip(payload)) => req
+                .query(&[("query", sql.as_str())])
+                .header(reqwest::header::CONTENT_ENCODING, "gzip")
+                .body(payload),
+            None => req.body(sql),
         };
 
         let response = req
@@ -265,7 +274,20 @@ impl DatabaseTransport for ClickhouseHttp {
             .join(",");
         let query = format!("INSERT INTO {table_name}({columns_str}) FORMAT JSONCompactEachRow");
 
-        self.do_execute(&query, Some(body)).await
+        self.do_execute(&query, Some(InsertBody::Plain(body))).await
+    }
+
+    async fn insert_gzip(
+        &self,
+        table_name: &str,
+        columns: &[&str],
+        payload: Vec<u8>,
+    ) -> Result<String> {
+        let columns_str = columns.join(",");
+        let query = format!("INSERT INTO {table_name}({columns_str}) FORMAT JSONCompactEachRow");
+
+        self.do_execute(&query, Some(InsertBody::Gzip(payload)))
+            .await
     }
 
     async fn execute_binary(&self, sql: &str) -> Result<Vec<u8>> {
@@ -356,3 +378,45 @@ impl DatabaseTransport for ClickhouseHttp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::otel::batch::{SpanBatch, TraceCompression};
+    use crate::test_utils::recording_server;
+    use flate2::read::GzDecoder;
+    use serde_json::json;
+    use std::io::Read;
+
+    #[tokio::test]
+    async fn test_gzip_insert_is_sent_compressed() {
+        let (url, request) = recording_server("text/plain", String::new()).await;
+        let mut transport = ClickhouseHttp::root();
+        transport.with_url(&url);
+
+        let rows = vec![
+            vec![json!(1), json!("openai")],
+            vec![json!(2), json!("anthropic")],
+        ];
+        let SpanBatch::Gzip { payload, .. } =
+            SpanBatch::encode(rows, TraceCompression::Gzip).unwrap()
+        else {
+            panic!("Expected gzip batch");
+        };
+        transport
+            .insert_gzip("langdb.traces", &["id", "provider"], payload)
+            .await
+            .unwrap();
+
+        let request = request.await.unwrap();
+        let headers_end = request.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let headers = String::from_utf8_lossy(&request[..headers_end]).to_lowercase();
+        assert!(headers.contains("content-encoding: gzip"));
+
+        let mut body = String::new();
+        GzDecoder::new(&request[headers_end + 4..])
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(body, "[1,\"openai\"]\n[2,\"anthropic\"]\n");
+    }
+}
@@ -27,6 +27,13 @@ pub trait DatabaseTransport: Send + Sync + DatabaseTransportClone + 'static {
         columns: &[&str],
         body: Vec<Vec<Value>>,
     ) -> Result<String>;
+    /// Inserts gzip compressed `JSONCompactEachRow` rows
+    async fn insert_gzip(
+        &self,
+        table_name: &str,
+        columns: &[&str],
+        payload: Vec<u8>,
+    ) -> Result<String>;
     async fn query_stream(
         &self,
         sql: &str,
@@ -1,4 +1,5 @@
-use crate::events::{JsonValue, RecordResult, SPAN_OPENAI};
+use crate::events::bodies::BodyRecordingMode;
+use crate::events::{JsonValue, SPAN_OPENAI};
 use crate::model::error::ModelError;
 use crate::model::openai::openai_client;
 use crate::model::types::LLMFinishEvent;
@@ -8,6 +9,7 @@ use crate::model::types::ModelFinishReason;
 use crate::model::CredentialsIdent;
 use crate::types::credentials::ApiKeyCredentials;
 use crate::types::embed::OpenAiEmbeddingParams;
+use crate::types::engine::ExecutionOptions;
 use crate::types::gateway::{CompletionModelUsage, Input, CreateEmbeddingResponse as GatewayEmbeddingResponse};
 use crate::GatewayError;
 use crate::GatewayResult;
@@ -59,7 +61,7 @@ impl OpenAIEmbed {
         credentials: Option<&ApiKeyCredentials>,
         endpoint: Option<&str>,
     ) -> Result<Self, ModelError> {
-        let client = openai_client(credentials, endpoint)?;
+        let client = openai_client(credentials, endpoint, &ExecutionOptions::default())?;
 
         let credentials_ident = credentials
             .map(|_c| CredentialsIdent::Own)
@@ -95,16 +97,17 @@ impl OpenAIEmbed {
         let mut response = async move {
             let result = self.client.embeddings().create(request).await;
 
-            let _ = result
-                .as_ref()
-                .map(|response| serde_json::to_value(response).unwrap())
-                .as_ref()
-                .map(JsonValue)
-                .record();
+            if let Err(e) = &result {
+                Span::current().record("error", e.to_string());
+            }
 
             let response = result.map_err(|e| ModelError::CustomError(e.to_string()))?;
 
             let span = Span::current();
+            span.record(
+                "output",
+                BodyRecordingMode::current().apply(&serde_json::to_string(&response)?),
+            );
             let usage = response.usage.clone();
             span.record(
                 "usage",
@@ -131,6 +134,9 @@ impl OpenAIEmbed {
                     finish_reason: ModelFinishReason::Stop,
                     tool_calls: vec![],
                     credentials_ident: self.credentials_ident.clone(),
+                    logprobs: None,
+                    service_tier: None,
+                    response_id: None,
                 }),
             )))
             .await
@@ -153,8 +159,8 @@ impl Embed for OpenAIEmbed {
         input_text: Input,
         tx: Option<tokio::sync::mpsc::Sender<Option<ModelEvent>>>,
     ) -> GatewayResult<crate::types::gateway::CreateEmbeddingResponse> {
-        let input = serde_json::to_string(&input_text)?;
-        let call_span = tracing::info_span!(target: target!("embedding"), SPAN_OPENAI, input = input, output = field::Empty, ttft = field::Empty, error = field::Empty, usage = field::Empty);
+        let input = BodyRecordingMode::current().apply(&serde_json::to_string(&input_text)?);
+        let call_span = tracing::info_span!(target: target!("embedding"), SPAN_OPENAI, input = input.as_deref(), output = field::Empty, ttft = field::Empty, error = field::Empty, usage = field::Empty);
 
         // Input -> EmbeddingInput
         let embedding_input = match input_text {
@@ -168,7 +174,7 @@ impl Embed for OpenAIEmbed {
         // 转换为主流程类型
         let data = response.data.into_iter().map(|e| crate::types::gateway::EmbeddingData {
             object: e.object,
-            embedding: e.embedding,
+            embedding: e.embedding.into(),
             index: e.index as u32,
         }).collect();
         Ok(GatewayEmbeddingResponse {
@@ -179,6 +185,7 @@ impl Embed for OpenAIEmbed {
                 prompt_tokens: response.usage.prompt_tokens,
                 total_tokens: response.usage.total_tokens,
             },
+            errors: vec![],
         })
     }
 
@@ -1,3 +1,4 @@
+use crate::model::error::ModelError;
 use crate::model::ollama::OllamaModel;
 use crate::types::engine::OllamaModelParams;
 use crate::model::CredentialsIdent;
@@ -12,6 +13,7 @@ use serde_json::Value;
 use tracing::{Span};
 use async_trait::async_trait;
 use crate::embed_mod::Embed;
+use crate::events::bodies::BodyRecordingMode;
 use crate::model::ModelInstance;
 use std::pin::Pin;
 
@@ -27,21 +29,21 @@ impl OllamaEmbed {
         params: OllamaModelParams,
         credentials: Option<&ApiKeyCredentials>,
         endpoint: Option<&str>,
-    ) -> Self {
+    ) -> Result<Self, ModelError> {
         let model = OllamaModel::new(
             params.clone(),
             Default::default(),
             credentials.cloned(),
             endpoint.map(|s| s.to_string()),
-        );
+        )?;
         let credentials_ident = credentials
             .map(|_c| CredentialsIdent::Own)
             .unwrap_or(CredentialsIdent::Langdb);
-        Self {
+        Ok(Self {
             params,
             model,
             credentials_ident,
-        }
+        })
     }
 
     async fn execute(
@@ -67,6 +69,9 @@ impl OllamaEmbed {
                     finish_reason: ModelFinishReason::Stop,
                     tool_calls: vec![],
                     credentials_ident: self.credentials_ident.clone(),
+                    logprobs: None,
+                    service_tier: None,
+                    response_id: None,
                 }),
             )))
             .await
@@ -90,13 +95,14 @@ impl Embed for OllamaEmbed {
                 arr.into_iter().next().ok_or_else(|| GatewayError::CustomError("Ollama embedding only supports String input".to_string()))?
             }
         };
-        let call_span = tracing::info_span!("embedding_ollama", input = &input);
+        let recorded_input = BodyRecordingMode::current().apply(&input);
+        let call_span = tracing::info_span!("embedding_ollama", input = recorded_input.as_deref());
         let (embedding, usage) = self.execute(input, call_span.clone(), tx.as_ref()).await?;
         let model_name = self.model.get_model_name();
         // Ollama 只返回一个 embedding
         let data = vec![EmbeddingData {
             object: "embedding".to_string(),
-            embedding,
+            embedding: embedding.into(),
             index: 0,
         }];
         Ok(GatewayEmbeddingResponse {
@@ -107,6 +113,7 @@ impl Embed for OllamaEmbed {
                 prompt_tokens: usage.prompt_tokens,
                 total_tokens: usage.total_tokens,
             },
+            errors: vec![],
         })
     }
 
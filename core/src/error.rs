@@ -3,9 +3,10 @@ use crate::model::error::ModelError;
 use crate::model::mcp::McpServerError;
 use crate::model::types::ModelEvent;
 use crate::types::guardrails::GuardError;
-use actix_web::http::header::ContentType;
+use actix_web::http::header::{ContentType, RETRY_AFTER};
 use actix_web::http::StatusCode;
 use actix_web::HttpResponse;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
 
@@ -27,6 +28,8 @@ pub enum GatewayError {
     ModelError(#[from] Box<ModelError>),
     #[error("Tool call id not found in request")]
     ToolCallIdNotFound,
+    #[error("Tool Not Found {0}")]
+    ToolNotFound(String),
     #[error(transparent)]
     ReqwestError(#[from] reqwest::Error),
     #[error(transparent)]
@@ -41,6 +44,80 @@ pub enum GatewayError {
     McpServerError(#[from] Box<McpServerError>),
     #[error(transparent)]
     SendError(#[from] Box<tokio::sync::mpsc::error::SendError<Option<ModelEvent>>>),
+    #[error("Rate limited by {provider}")]
+    RateLimited {
+        provider: String,
+        /// Seconds the provider asked to wait before retrying
+        retry_after: Option<u64>,
+    },
+    #[error("{message}")]
+    ProviderError {
+        provider: String,
+        class: ErrorClass,
+        message: String,
+    },
+}
+
+/// Kind of failure a provider call ended with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// The provider rejected the request itself, e.g. with a 400
+    ClientError,
+    /// The provider failed or couldn't be reached, e.g. a 503 or a refused connection
+    ServerError,
+    /// The provider answered with a 429
+    RateLimit,
+    /// The provider didn't answer in time
+    Timeout,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            429 => ErrorClass::RateLimit,
+            408 | 504 => ErrorClass::Timeout,
+            400..=499 => ErrorClass::ClientError,
+            500..=599 => ErrorClass::ServerError,
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+impl GatewayError {
+    /// Error for a 429 response of `provider`, keeping the delay of its `Retry-After` header
+    pub fn rate_limited(provider: &str, headers: &reqwest::header::HeaderMap) -> Self {
+        GatewayError::RateLimited {
+            provider: provider.to_string(),
+            retry_after: headers
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse().ok()),
+        }
+    }
+
+    /// Error for a failed response of `provider` with HTTP `status`
+    pub fn provider_status(provider: &str, status: u16, message: impl ToString) -> Self {
+        GatewayError::ProviderError {
+            provider: provider.to_string(),
+            class: ErrorClass::from_status(status),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            GatewayError::RateLimited { .. } => ErrorClass::RateLimit,
+            GatewayError::ProviderError { class, .. } => *class,
+            GatewayError::ModelError(e) if matches!(e.as_ref(), ModelError::Timeout(_)) => {
+                ErrorClass::Timeout
+            }
+            GatewayError::ReqwestError(e) if e.is_timeout() => ErrorClass::Timeout,
+            GatewayError::ReqwestError(e) if e.is_connect() => ErrorClass::ServerError,
+            _ => ErrorClass::Other,
+        }
+    }
 }
 
 impl From<ModelError> for GatewayError {
@@ -66,6 +143,16 @@ impl actix_web::error::ResponseError for GatewayError {
         tracing::error!("API error: {:?}", self);
         match self {
             GatewayError::GuardError(e) => e.error_response(),
+            GatewayError::RateLimited { retry_after, .. } => {
+                let mut response = HttpResponse::build(self.status_code());
+                response.insert_header(ContentType::json());
+                if let Some(seconds) = retry_after {
+                    response.insert_header((RETRY_AFTER, seconds.to_string()));
+                }
+                response.json(json!({
+                    "error": self.to_string(),
+                }))
+            }
             e => {
                 let json_error = json!({
                     "error": e.to_string(),
@@ -80,10 +167,55 @@ impl actix_web::error::ResponseError for GatewayError {
 
     fn status_code(&self) -> StatusCode {
         match self {
-            GatewayError::GuardError(GuardError::GuardNotPassed(_, _)) => {
+            GatewayError::GuardError(GuardError::GuardNotPassed(..)) => {
                 GuardValidationFailed::status_code()
             }
+            GatewayError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+
+    #[test]
+    fn test_rate_limited_response() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, "30".parse().unwrap());
+        let error = GatewayError::rate_limited("openai", &headers);
+        assert!(matches!(
+            &error,
+            GatewayError::RateLimited { provider, retry_after: Some(30) } if provider == "openai"
+        ));
+
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "30");
+
+        let error = GatewayError::rate_limited("openai", &reqwest::header::HeaderMap::new());
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+    }
+
+    #[test]
+    fn test_error_class() {
+        let class = |status| GatewayError::provider_status("openai", status, "failed").class();
+        assert_eq!(class(400), ErrorClass::ClientError);
+        assert_eq!(class(404), ErrorClass::ClientError);
+        assert_eq!(class(429), ErrorClass::RateLimit);
+        assert_eq!(class(503), ErrorClass::ServerError);
+        assert_eq!(class(504), ErrorClass::Timeout);
+        assert_eq!(
+            GatewayError::from(ModelError::Timeout(30)).class(),
+            ErrorClass::Timeout
+        );
+        assert_eq!(
+            GatewayError::CustomError("failed".to_string()).class(),
+            ErrorClass::Other
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::future::Future;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+tokio::task_local! {
+    static CURRENT_MODE: BodyRecordingMode;
+}
+
+/// How request and response bodies are recorded on spans
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyRecordingMode {
+    /// The full request and response
+    #[default]
+    Full,
+    /// A sha256 digest, enough to tell identical bodies apart
+    Hashed,
+    /// Nothing
+    Off,
+}
+
+impl BodyRecordingMode {
+    /// Value recorded on the span for `body`, if any
+    pub fn apply(&self, body: &str) -> Option<String> {
+        match self {
+            BodyRecordingMode::Full => Some(body.to_string()),
+            BodyRecordingMode::Hashed => {
+                let digest = Sha256::digest(body.as_bytes());
+                let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+                Some(format!("sha256:{hex}"))
+            }
+            BodyRecordingMode::Off => None,
+        }
+    }
+
+    /// Runs `future` with `self` as the [`BodyRecordingMode::current`] mode
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT_MODE.scope(self, future).await
+    }
+
+    /// Mode of the model call in progress, provider spans record their bodies with it.
+    /// Outside of a [`BodyRecordingMode::scope`] bodies are recorded in full.
+    pub fn current() -> Self {
+        CURRENT_MODE.try_with(|mode| *mode).unwrap_or_default()
+    }
+}
+
+/// Present in app data to change how request and response bodies are recorded in traces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodyRecording {
+    #[serde(default)]
+    pub mode: BodyRecordingMode,
+    /// Share of requests recorded with `mode`, the rest are recorded as with `off`
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+impl Default for BodyRecording {
+    fn default() -> Self {
+        Self {
+            mode: BodyRecordingMode::default(),
+            sample_rate: default_sample_rate(),
+        }
+    }
+}
+
+impl BodyRecording {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err(format!(
+                "sample_rate must be between 0 and 1, got {}",
+                self.sample_rate
+            ));
+        }
+        Ok(())
+    }
+
+    /// Mode used for a single request
+    pub fn sample(&self) -> BodyRecordingMode {
+        if self.sample_rate >= 1.0 || rand::thread_rng().gen_bool(self.sample_rate.max(0.0)) {
+            self.mode
+        } else {
+            BodyRecordingMode::Off
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::SpanFields;
+    use std::collections::HashMap;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn recorded(mode: BodyRecordingMode) -> HashMap<String, String> {
+        let fields = SpanFields::default();
+        let subscriber = tracing_subscriber::registry().with(fields.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let input = mode.apply(r#"{"messages":"What is the capital of France?"}"#);
+            let span = tracing::info_span!(
+                "model_call",
+                input = input.as_deref(),
+                output = tracing::field::Empty
+            );
+            span.record("output", mode.apply("Paris"));
+        });
+
+        fields.of("model_call").unwrap()
+    }
+
+    #[test]
+    fn test_full_bodies_are_recorded() {
+        let fields = recorded(BodyRecordingMode::Full);
+        assert_eq!(
+            fields["input"],
+            r#"{"messages":"What is the capital of France?"}"#
+        );
+        assert_eq!(fields["output"], "Paris");
+    }
+
+    #[test]
+    fn test_hashed_bodies_are_recorded() {
+        let fields = recorded(BodyRecordingMode::Hashed);
+        assert!(!fields["input"].contains("France"));
+        assert_eq!(
+            fields["output"],
+            "sha256:5dd272b4f316b776a7b8e3d0894b37e1e42be3d5d3b204b8a5836cc50597a6b1"
+        );
+    }
+
+    #[test]
+    fn test_bodies_are_not_recorded() {
+        let fields = recorded(BodyRecordingMode::Off);
+        assert!(!fields.contains_key("input"));
+        assert!(!fields.contains_key("output"));
+    }
+
+    #[test]
+    fn test_sampling() {
+        let never = BodyRecording {
+            mode: BodyRecordingMode::Full,
+            sample_rate: 0.0,
+        };
+        let always = BodyRecording {
+            mode: BodyRecordingMode::Hashed,
+            sample_rate: 1.0,
+        };
+
+        for _ in 0..100 {
+            assert_eq!(never.sample(), BodyRecordingMode::Off);
+            assert_eq!(always.sample(), BodyRecordingMode::Hashed);
+        }
+        assert!(BodyRecording {
+            sample_rate: 1.5,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
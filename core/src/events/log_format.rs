@@ -0,0 +1,148 @@
+use std::fmt;
+
+use opentelemetry::baggage::BaggageExt;
+use opentelemetry::trace::{TraceContextExt, TraceId};
+use opentelemetry::Context;
+use tracing::{Event, Subscriber};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::otel::trace_id_uuid;
+
+/// Event formatter that prefixes every log line emitted during a request with the
+/// request's `trace_id` and `tenant`
+pub struct CorrelatedFormat<F> {
+    inner: F,
+}
+
+impl<F> CorrelatedFormat<F> {
+    pub fn new(inner: F) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, N, F> FormatEvent<S, N> for CorrelatedFormat<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        // The closest span exported to OpenTelemetry carries the trace, otherwise fall back
+        // to the context attached to the request future
+        let span_context = ctx.event_scope(event).and_then(|mut scope| {
+            scope.find_map(|span| {
+                span.extensions().get::<OtelData>().map(|data| {
+                    let trace_id = data
+                        .builder
+                        .trace_id
+                        .unwrap_or_else(|| data.parent_cx.span().span_context().trace_id());
+                    (trace_id, data.parent_cx.clone())
+                })
+            })
+        });
+        let (trace_id, cx) = span_context.unwrap_or_else(|| {
+            let cx = Context::current();
+            (cx.span().span_context().trace_id(), cx)
+        });
+
+        if trace_id != TraceId::INVALID {
+            write!(writer, "trace_id={} ", trace_id_uuid(trace_id))?;
+        }
+        if let Some(tenant) = cx.baggage().get("langdb.tenant") {
+            write!(writer, "tenant={tenant} ")?;
+        }
+        self.inner.format_event(ctx, writer, event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use std::sync::{Arc, Mutex};
+    use tracing::level_filters::LevelFilter;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_logs_carry_trace_id_and_tenant() {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(crate::events::layer(
+                "langdb::user_tracing",
+                LevelFilter::INFO,
+                provider.tracer("test"),
+            ))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(move || writer.clone())
+                    .event_format(CorrelatedFormat::new(
+                        tracing_subscriber::fmt::format().compact(),
+                    )),
+            );
+
+        let trace_id = tracing::subscriber::with_default(subscriber, || {
+            let _cx = Context::current()
+                .with_baggage(vec![KeyValue::new("langdb.tenant", "acme")])
+                .attach();
+            let span =
+                tracing::info_span!(target: "langdb::user_tracing::api_invoke", "api_invoke");
+            let _entered = span.enter();
+            tracing::info!("calling model");
+
+            span.context().span().span_context().trace_id()
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains(&format!("trace_id={}", trace_id_uuid(trace_id))));
+        assert!(logs.contains("tenant=acme"));
+        assert!(logs.contains("calling model"));
+    }
+
+    #[test]
+    fn test_logs_without_request() {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .event_format(CorrelatedFormat::new(
+                    tracing_subscriber::fmt::format().compact(),
+                )),
+        );
+
+        tracing::subscriber::with_default(subscriber, || tracing::info!("starting server"));
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("starting server"));
+        assert!(!logs.contains("trace_id="));
+    }
+}
@@ -6,8 +6,11 @@ use serde_json::Value;
 use valuable::{Listable, Mappable, Valuable, Visit};
 use std::fmt;
 
+pub mod bodies;
 mod layer;
+mod log_format;
 pub use layer::{config, layer, RecordResult, UuidIdGenerator};
+pub use log_format::CorrelatedFormat;
 
 pub const SPAN_QUERY: &str = "query";
 
@@ -41,23 +44,46 @@ pub const SPAN_GUARD_EVAULATION: &str = "guard_evaluation";
 
 pub const SPAN_VIRTUAL_MODEL: &str = "virtual_model";
 
+/// Baggage entries always copied onto span attributes
+pub const DEFAULT_BAGGAGE_KEYS: [&str; 4] = [
+    "langdb.parent_trace_id",
+    "langdb.run_id",
+    "langdb.label",
+    "langdb.request_id",
+];
+
+/// Baggage keys must be non-empty HTTP tokens (RFC 7230)
+pub fn validate_baggage_key(key: &str) -> Result<(), String> {
+    let is_token_char = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+    if key.is_empty() || !key.chars().all(is_token_char) {
+        return Err(format!("Invalid baggage key: {key:?}"));
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
-pub struct BaggageSpanProcessor<const N: usize> {
-    keys: [&'static str; N],
+pub struct BaggageSpanProcessor {
+    keys: Vec<String>,
 }
 
-impl<const N: usize> BaggageSpanProcessor<N> {
-    pub const fn new(keys: [&'static str; N]) -> Self {
-        Self { keys }
+impl BaggageSpanProcessor {
+    pub fn new<I, K>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+        }
     }
 }
 
-impl<const N: usize> SpanProcessor for BaggageSpanProcessor<N> {
+impl SpanProcessor for BaggageSpanProcessor {
     fn on_start(&self, span: &mut opentelemetry_sdk::trace::Span, cx: &opentelemetry::Context) {
-        for key in self.keys {
-            let value = cx.baggage().get(key);
+        for key in &self.keys {
+            let value = cx.baggage().get(key.as_str());
             if let Some(value) = value {
-                span.set_attribute(opentelemetry::KeyValue::new(key, value.clone()));
+                span.set_attribute(opentelemetry::KeyValue::new(key.clone(), value.clone()));
             }
         }
     }
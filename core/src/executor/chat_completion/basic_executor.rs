@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use crate::events::bodies::BodyRecordingMode;
 use crate::model::types::ModelEvent;
 use crate::model::types::{LLMFinishEvent, ToolStartEvent, ModelEventType};
 use crate::types::gateway::ChatCompletionMessage;
@@ -9,8 +10,8 @@ use crate::{
     model::ModelInstance,
     types::{
         gateway::{
-            ChatCompletionChoice, ChatCompletionRequest, ChatCompletionResponse,
-            ChatCompletionUsage,
+            ChatCompletionChoice, ChatCompletionLogprobs, ChatCompletionRequest,
+            ChatCompletionResponse, ChatCompletionUsage,
         },
         threads::Message,
     },
@@ -33,6 +34,17 @@ pub struct BasicCacheContext {
     pub cached_response: Option<ChatCompletionMessage>,
 }
 
+/// Logprobs of the completion, only returned when the request asked for them
+fn requested_logprobs(
+    request: &ChatCompletionRequest,
+    finish_event: Option<&LLMFinishEvent>,
+) -> Option<ChatCompletionLogprobs> {
+    if !request.logprobs.unwrap_or(false) {
+        return None;
+    }
+    finish_event.and_then(|e| e.logprobs.clone())
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn execute(
     request: ChatCompletionRequest,
@@ -45,7 +57,7 @@ pub async fn execute(
     input_vars: HashMap<String, serde_json::Value>,
     cache_context: BasicCacheContext,
 ) -> Result<ChatCompletionResponse, GatewayApiError> {
-    let (inner_tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(4096);
+    let (inner_tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(tx.max_capacity());
 
     // Create a channel for capturing LLMFinishEvent if none is provided
     let (finish_tx, finish_rx) = tokio::sync::oneshot::channel();
@@ -63,9 +75,11 @@ pub async fn execute(
 
             // Forward the event
             if let Some(sender) = &cache_context.events_sender {
-                sender.send(event.clone()).await.unwrap();
+                if let Err(e) = sender.send(event.clone()).await {
+                    tracing::warn!("Failed to forward event to response cache: {e}");
+                }
             }
-            let _ = tx.try_send(event);
+            let _ = tx.send(event).await;
         }
 
         // Send the captured finish event
@@ -85,11 +99,14 @@ pub async fn execute(
     let finish_reason = match (&response.tool_calls, &response.content) {
         (Some(_), _) => {
             let calls = serde_json::to_string(&response.tool_calls).unwrap();
-            span.record("response", &calls);
+            span.record("response", BodyRecordingMode::current().apply(&calls));
             Ok("tool_calls".to_string())
         }
         (None, Some(c)) => {
-            span.record("response", &c.as_string());
+            span.record(
+                "response",
+                BodyRecordingMode::current().apply(&c.as_string()),
+            );
             Ok("stop".to_string())
         }
         _ => Err(GatewayApiError::GatewayError(GatewayError::CustomError(
@@ -108,6 +125,12 @@ pub async fn execute(
         }
     };
 
+    let logprobs = requested_logprobs(&request, u.as_ref());
+    let service_tier = u.as_ref().and_then(|u| u.service_tier.clone());
+    let id = u
+        .as_ref()
+        .and_then(|u| u.response_id.clone())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
     let model_usage = u.and_then(|u| u.usage);
     let is_cache_used = model_usage.as_ref().map(|u| u.is_cache_used);
     let usage: ChatCompletionUsage = match model_usage {
@@ -123,7 +146,7 @@ pub async fn execute(
     };
 
     let response = ChatCompletionResponse {
-        id: Uuid::new_v4().to_string(),
+        id,
         object: "chat.completion".to_string(),
         created: chrono::Utc::now().timestamp(),
         model: request.model.clone(),
@@ -131,9 +154,11 @@ pub async fn execute(
             index: 0,
             message: response.clone(),
             finish_reason: Some(finish_reason.clone()),
+            logprobs,
         }],
         usage, // <-- 这里写入真实 usage
         is_cache_used,
+        service_tier,
     };
 
     Ok(response)
@@ -151,7 +176,7 @@ pub async fn execute_with_tags(
     input_vars: HashMap<String, serde_json::Value>,
     cache_context: BasicCacheContext,
 ) -> Result<ChatCompletionResponse, GatewayApiError> {
-    let (inner_tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(4096);
+    let (inner_tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(tx.max_capacity());
     
     // Create a channel for capturing LLMFinishEvent if none is provided
     let (finish_tx, finish_rx) = tokio::sync::oneshot::channel();
@@ -168,9 +193,11 @@ pub async fn execute_with_tags(
             }
             
             if let Some(sender) = &cache_context.events_sender {
-                sender.send(event.clone()).await.unwrap();
+                if let Err(e) = sender.send(event.clone()).await {
+                    tracing::warn!("Failed to forward event to response cache: {e}");
+                }
             }
-            let _ = tx.try_send(event);
+            let _ = tx.send(event).await;
         }
         
         // Send the captured finish event
@@ -190,11 +217,14 @@ pub async fn execute_with_tags(
     let finish_reason = match (&response.tool_calls, &response.content) {
         (Some(_), _) => {
             let calls = serde_json::to_string(&response.tool_calls).unwrap();
-            span.record("response", &calls);
+            span.record("response", BodyRecordingMode::current().apply(&calls));
             Ok("tool_calls".to_string())
         }
         (None, Some(c)) => {
-            span.record("response", &c.as_string());
+            span.record(
+                "response",
+                BodyRecordingMode::current().apply(&c.as_string()),
+            );
             Ok("stop".to_string())
         }
         _ => Err(GatewayApiError::GatewayError(GatewayError::CustomError(
@@ -213,6 +243,12 @@ pub async fn execute_with_tags(
         }
     };
     
+    let logprobs = requested_logprobs(&request, u.as_ref());
+    let service_tier = u.as_ref().and_then(|u| u.service_tier.clone());
+    let id = u
+        .as_ref()
+        .and_then(|u| u.response_id.clone())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
     let model_usage = u.and_then(|u| u.usage);
     let is_cache_used = model_usage.as_ref().map(|u| u.is_cache_used);
     let usage: ChatCompletionUsage = match model_usage {
@@ -229,7 +265,7 @@ pub async fn execute_with_tags(
 
     // 构造 ChatCompletionResponse
     let chat_response = ChatCompletionResponse {
-        id: Uuid::new_v4().to_string(),
+        id,
         object: "chat.completion".to_string(),
         created: chrono::Utc::now().timestamp(),
         model: request.model.clone(),
@@ -237,9 +273,56 @@ pub async fn execute_with_tags(
             index: 0,
             message: response.clone(),
             finish_reason: Some(finish_reason.clone()),
+            logprobs,
         }],
         usage, // Use the captured usage info
         is_cache_used,
+        service_tier,
     };
     Ok(chat_response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::types::ModelFinishReason;
+    use crate::model::CredentialsIdent;
+    use crate::types::gateway::ChatCompletionTokenLogprob;
+
+    fn finish_event() -> LLMFinishEvent {
+        LLMFinishEvent {
+            provider_name: "openai".to_string(),
+            model_name: "gpt-4o-mini".to_string(),
+            output: Some("Paris".to_string()),
+            usage: None,
+            finish_reason: ModelFinishReason::Stop,
+            tool_calls: vec![],
+            credentials_ident: CredentialsIdent::Own,
+            logprobs: Some(ChatCompletionLogprobs {
+                content: vec![ChatCompletionTokenLogprob {
+                    token: "Paris".to_string(),
+                    logprob: -0.01,
+                    bytes: None,
+                    top_logprobs: vec![],
+                }],
+            }),
+            service_tier: None,
+            response_id: None,
+        }
+    }
+
+    #[test]
+    fn test_logprobs_only_returned_when_requested() {
+        let finish_event = finish_event();
+        let request = |logprobs| ChatCompletionRequest {
+            logprobs,
+            ..Default::default()
+        };
+
+        let logprobs = requested_logprobs(&request(Some(true)), Some(&finish_event)).unwrap();
+        assert_eq!(logprobs.content[0].token, "Paris");
+        assert!(requested_logprobs(&request(Some(false)), Some(&finish_event)).is_none());
+        assert!(requested_logprobs(&request(None), Some(&finish_event)).is_none());
+        assert!(requested_logprobs(&request(Some(true)), None).is_none());
+    }
+}
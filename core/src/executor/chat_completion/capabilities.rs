@@ -0,0 +1,172 @@
+use async_openai::types::ResponseFormat;
+
+use crate::models::ModelMetadata;
+use crate::pricing::estimate::estimate_usage;
+use crate::types::engine::ModelTools;
+use crate::types::gateway::{ChatCompletionContent, ChatCompletionRequest, ContentType};
+use crate::GatewayApiError;
+
+fn has_images(request: &ChatCompletionRequest) -> bool {
+    request
+        .messages
+        .iter()
+        .any(|message| match &message.content {
+            Some(ChatCompletionContent::Content(parts)) => parts
+                .iter()
+                .any(|part| matches!(part.r#type, ContentType::ImageUrl)),
+            _ => false,
+        })
+}
+
+fn requests_json(request: &ChatCompletionRequest) -> bool {
+    matches!(
+        request.response_format,
+        Some(ResponseFormat::JsonObject | ResponseFormat::JsonSchema { .. })
+    )
+}
+
+/// Features used by `request` that `model` does not support. `tools` are the request
+/// and MCP tools passed to the model.
+pub fn unsupported_features(
+    request: &ChatCompletionRequest,
+    tools: &ModelTools,
+    model: &ModelMetadata,
+) -> Vec<&'static str> {
+    let mut features = Vec::new();
+
+    if !tools.0.is_empty() && !model.supports_tools() {
+        features.push("tools");
+    }
+    if has_images(request) && !model.supports_vision() {
+        features.push("vision");
+    }
+    if requests_json(request) && !model.supports_json() {
+        features.push("json");
+    }
+
+    let max_context = model.max_context();
+    if max_context > 0 && estimate_usage(request).total_tokens > max_context {
+        features.push("context size");
+    }
+
+    features
+}
+
+/// Fails with every feature of `request` that `model` does not support
+pub fn check_capabilities(
+    request: &ChatCompletionRequest,
+    tools: &ModelTools,
+    model: &ModelMetadata,
+) -> Result<(), GatewayApiError> {
+    let features = unsupported_features(request, tools, model);
+    if features.is_empty() {
+        return Ok(());
+    }
+
+    Err(GatewayApiError::UnsupportedFeatures {
+        model: request.model.clone(),
+        features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Limits, ModelIOFormats};
+    use crate::types::engine::ModelTool;
+    use crate::types::gateway::{ChatCompletionMessage, Content, ImageUrl};
+
+    fn vision_request() -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: "openai/gpt-3.5-turbo".to_string(),
+            messages: vec![ChatCompletionMessage {
+                role: "user".to_string(),
+                content: Some(ChatCompletionContent::Content(vec![
+                    Content {
+                        r#type: ContentType::Text,
+                        text: Some("What is in this image?".to_string()),
+                        image_url: None,
+                        audio: None,
+                    },
+                    Content {
+                        r#type: ContentType::ImageUrl,
+                        text: None,
+                        image_url: Some(ImageUrl {
+                            url: "https://example.com/image.jpg".to_string(),
+                        }),
+                        audio: None,
+                    },
+                ])),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn text_model() -> ModelMetadata {
+        ModelMetadata {
+            model: "gpt-3.5-turbo".to_string(),
+            model_provider: "openai".to_string(),
+            input_formats: vec![ModelIOFormats::Text],
+            limits: Limits::new(16385),
+            supports_vision: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_vision_request_on_text_model_is_rejected() {
+        let err =
+            check_capabilities(&vision_request(), &ModelTools(vec![]), &text_model()).unwrap_err();
+        assert!(matches!(
+            &err,
+            GatewayApiError::UnsupportedFeatures { features, .. } if features == &vec!["vision"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Model openai/gpt-3.5-turbo does not support: vision"
+        );
+
+        let vision_model = ModelMetadata {
+            supports_vision: Some(true),
+            ..text_model()
+        };
+        assert!(check_capabilities(&vision_request(), &ModelTools(vec![]), &vision_model).is_ok());
+    }
+
+    #[test]
+    fn test_features_are_assumed_without_metadata() {
+        // Most models in models.yaml list no capabilities and only text inputs
+        let model = ModelMetadata {
+            supports_vision: None,
+            ..text_model()
+        };
+        assert!(model.capabilities.is_empty());
+        let tools = ModelTools(vec![ModelTool {
+            name: "get_weather".to_string(),
+            description: None,
+            passed_args: vec![],
+        }]);
+
+        assert!(check_capabilities(&vision_request(), &tools, &model).is_ok());
+    }
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let model = ModelMetadata {
+            supports_vision: Some(true),
+            supports_json: Some(false),
+            max_context: Some(1),
+            ..text_model()
+        };
+        let request = ChatCompletionRequest {
+            response_format: Some(ResponseFormat::JsonObject),
+            ..vision_request()
+        };
+
+        assert_eq!(
+            unsupported_features(&request, &ModelTools(vec![]), &model),
+            vec!["json", "context size"]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use async_openai::types::ResponseFormat;
+
+use crate::executor::chat_completion::response_schema::enforced_schema;
+use crate::models::ModelMetadata;
+use crate::types::gateway::{ChatCompletionContent, ChatCompletionRequest, ChatCompletionResponse};
+
+/// Marks that markdown code fences around structured outputs are stripped before the
+/// completion is returned
+#[derive(Debug, Clone, Copy)]
+pub struct StripCodeFences;
+
+/// Whether the request asks for JSON, itself or through the model's response schema
+pub fn is_structured_output(request: &ChatCompletionRequest, model: &ModelMetadata) -> bool {
+    matches!(
+        request.response_format,
+        Some(ResponseFormat::JsonObject | ResponseFormat::JsonSchema { .. })
+    ) || enforced_schema(request, model).is_some()
+}
+
+/// Content of a reply wrapped in a single markdown code block, e.g. "```json\n{}\n```",
+/// without the fences and the surrounding whitespace. Returns `None` when there is nothing
+/// to strip.
+pub fn strip_code_fences(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    let stripped = match trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+    {
+        // Several code blocks are left alone
+        Some(inner) if !inner.contains("```") => {
+            // The language tag runs until the end of the first line
+            match inner.split_once('\n') {
+                Some((tag, body))
+                    if tag
+                        .trim()
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '_') =>
+                {
+                    body.trim()
+                }
+                _ => inner.trim(),
+            }
+        }
+        _ => trimmed,
+    };
+
+    (stripped != text).then(|| stripped.to_string())
+}
+
+/// Strips the code fences of the text content of every choice. Returns true if any content
+/// was changed.
+pub fn strip_response_code_fences(response: &mut ChatCompletionResponse) -> bool {
+    let mut stripped_any = false;
+    for choice in response.choices.iter_mut() {
+        if let Some(ChatCompletionContent::Text(text)) = &choice.message.content {
+            if let Some(stripped) = strip_code_fences(text) {
+                choice.message.content = Some(ChatCompletionContent::Text(stripped));
+                stripped_any = true;
+            }
+        }
+    }
+    stripped_any
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fenced_json_is_unwrapped() {
+        assert_eq!(
+            strip_code_fences("```json\n{\"city\": \"Paris\"}\n```").as_deref(),
+            Some("{\"city\": \"Paris\"}")
+        );
+        assert_eq!(
+            strip_code_fences("\n  ```\n[1, 2]\n```  \n").as_deref(),
+            Some("[1, 2]")
+        );
+        assert_eq!(
+            strip_code_fences("```{\"city\": \"Paris\"}```").as_deref(),
+            Some("{\"city\": \"Paris\"}")
+        );
+        assert_eq!(
+            strip_code_fences("  {\"city\": \"Paris\"}\n").as_deref(),
+            Some("{\"city\": \"Paris\"}")
+        );
+    }
+
+    #[test]
+    fn test_plain_json_is_untouched() {
+        assert!(strip_code_fences("{\"city\": \"Paris\"}").is_none());
+        assert!(strip_code_fences("{\"code\": \"```rust\\nfn main() {}\\n```\"}").is_none());
+        assert!(strip_code_fences("```json\n{}\n```\nand\n```json\n[]\n```").is_none());
+    }
+
+    #[test]
+    fn test_only_structured_outputs_qualify() {
+        let model = ModelMetadata::default();
+        let request = |response_format| ChatCompletionRequest {
+            response_format,
+            ..Default::default()
+        };
+
+        assert!(is_structured_output(
+            &request(Some(ResponseFormat::JsonObject)),
+            &model
+        ));
+        assert!(!is_structured_output(
+            &request(Some(ResponseFormat::Text)),
+            &model
+        ));
+        assert!(!is_structured_output(&request(None), &model));
+
+        let model = ModelMetadata {
+            response_schema: Some(serde_json::json!({"type": "object"})),
+            ..Default::default()
+        };
+        assert!(is_structured_output(&request(None), &model));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::types::gateway::{ChatCompletionContent, ChatCompletionResponse};
+
+/// Attempts to fix common mistakes in model generated JSON: trailing commas, unquoted keys,
+/// single quoted strings, python literals and missing closing brackets.
+/// Returns `None` when the input is already valid or cannot be repaired.
+pub fn repair_json(input: &str) -> Option<String> {
+    let trimmed = input.trim();
+    if serde_json::from_str::<serde_json::Value>(trimmed).is_ok() {
+        return None;
+    }
+
+    let repaired = repair(trimmed);
+    serde_json::from_str::<serde_json::Value>(&repaired)
+        .ok()
+        .filter(|v| v.is_object() || v.is_array())
+        .map(|_| repaired)
+}
+
+/// Repairs the text content of every choice. Returns true if any content was changed.
+pub fn repair_response_json(response: &mut ChatCompletionResponse) -> bool {
+    let mut repaired_any = false;
+    for choice in response.choices.iter_mut() {
+        if let Some(ChatCompletionContent::Text(text)) = &choice.message.content {
+            if let Some(repaired) = repair_json(text) {
+                choice.message.content = Some(ChatCompletionContent::Text(repaired));
+                repaired_any = true;
+            }
+        }
+    }
+    repaired_any
+}
+
+fn repair(input: &str) -> String {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut out = String::with_capacity(input.len());
+    let mut closers: Vec<char> = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' | '\'' => {
+                i = copy_string(&chars, i, &mut out);
+                continue;
+            }
+            '{' => closers.push('}'),
+            '[' => closers.push(']'),
+            '}' | ']' => {
+                remove_trailing_comma(&mut out);
+                closers.pop();
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                {
+                    i += 1;
+                }
+                let word = chars[start..i].iter().collect::<String>();
+                let next = chars[i..].iter().find(|c| !c.is_whitespace());
+                if next == Some(&':') {
+                    out.push('"');
+                    out.push_str(&word);
+                    out.push('"');
+                } else {
+                    out.push_str(match word.as_str() {
+                        "True" => "true",
+                        "False" => "false",
+                        "None" => "null",
+                        w => w,
+                    });
+                }
+                continue;
+            }
+            _ => {}
+        }
+        out.push(c);
+        i += 1;
+    }
+
+    remove_trailing_comma(&mut out);
+    while let Some(closer) = closers.pop() {
+        out.push(closer);
+    }
+
+    out
+}
+
+/// Copies a quoted string starting at `start` as a double quoted JSON string, returning the
+/// index after its closing quote. Unterminated strings are closed.
+fn copy_string(chars: &[char], start: usize, out: &mut String) -> usize {
+    let quote = chars[start];
+    let mut i = start + 1;
+    out.push('"');
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                if quote == '\'' && chars[i + 1] == '\'' {
+                    out.push('\'');
+                } else {
+                    out.push(c);
+                    out.push(chars[i + 1]);
+                }
+                i += 2;
+                continue;
+            }
+            c if c == quote => {
+                out.push('"');
+                return i + 1;
+            }
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    out.push('"');
+    i
+}
+
+fn remove_trailing_comma(out: &mut String) {
+    let trimmed_len = out.trim_end().len();
+    if out[..trimmed_len].ends_with(',') {
+        out.truncate(trimmed_len - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn repaired_value(input: &str) -> serde_json::Value {
+        serde_json::from_str(&repair_json(input).expect("Should be repaired")).unwrap()
+    }
+
+    #[test]
+    fn test_repair_trailing_commas() {
+        assert_eq!(
+            repaired_value(r#"{"a": 1, "b": [1, 2, 3,],}"#),
+            json!({"a": 1, "b": [1, 2, 3]})
+        );
+    }
+
+    #[test]
+    fn test_repair_unquoted_keys() {
+        assert_eq!(
+            repaired_value(r#"{name: "John", age: 30, is_admin: false}"#),
+            json!({"name": "John", "age": 30, "is_admin": false})
+        );
+    }
+
+    #[test]
+    fn test_repair_single_quotes_and_python_literals() {
+        assert_eq!(
+            repaired_value(r#"{'name': 'O\'Neil "Jr"', 'active': True, 'manager': None}"#),
+            json!({"name": "O'Neil \"Jr\"", "active": true, "manager": null})
+        );
+    }
+
+    #[test]
+    fn test_repair_truncated() {
+        assert_eq!(
+            repaired_value(r#"{"items": [{"id": 1}, {"id": 2"#),
+            json!({"items": [{"id": 1}, {"id": 2}]})
+        );
+    }
+
+    #[test]
+    fn test_valid_json_is_untouched() {
+        assert_eq!(repair_json(r#"{"a": [1, 2]}"#), None);
+    }
+
+    #[test]
+    fn test_unrepairable() {
+        assert_eq!(repair_json("The answer is: {not json at all ::: }"), None);
+        assert_eq!(repair_json("plain text answer"), None);
+    }
+}
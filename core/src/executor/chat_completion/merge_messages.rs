@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::gateway::{ChatCompletionContent, ChatCompletionMessage, Content, ContentType};
+
+/// Providers rejecting consecutive messages of the same role
+const MERGING_PROVIDERS: &[&str] = &["anthropic", "bedrock"];
+
+/// Per provider switch for merging consecutive same-role messages before dispatch.
+/// Providers that aren't listed merge only when they reject such messages.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeConsecutiveMessages(pub HashMap<String, bool>);
+
+impl MergeConsecutiveMessages {
+    pub fn enabled_for(&self, provider: &str) -> bool {
+        self.0
+            .get(provider)
+            .copied()
+            .unwrap_or_else(|| MERGING_PROVIDERS.contains(&provider))
+    }
+}
+
+fn into_parts(content: ChatCompletionContent) -> Vec<Content> {
+    match content {
+        ChatCompletionContent::Text(text) => vec![Content {
+            r#type: ContentType::Text,
+            text: Some(text),
+            image_url: None,
+            audio: None,
+        }],
+        ChatCompletionContent::Content(parts) => parts,
+    }
+}
+
+fn merge_content(
+    first: Option<ChatCompletionContent>,
+    second: Option<ChatCompletionContent>,
+) -> Option<ChatCompletionContent> {
+    match (first, second) {
+        (Some(ChatCompletionContent::Text(a)), Some(ChatCompletionContent::Text(b))) => {
+            Some(ChatCompletionContent::Text(format!("{a}\n\n{b}")))
+        }
+        (Some(a), Some(b)) => {
+            let mut parts = into_parts(a);
+            parts.extend(into_parts(b));
+            Some(ChatCompletionContent::Content(parts))
+        }
+        (a, b) => a.or(b),
+    }
+}
+
+/// Tool calls and tool results are paired by id, so they are never merged
+fn can_merge(message: &ChatCompletionMessage) -> bool {
+    message.tool_calls.is_none() && message.tool_call_id.is_none() && message.role != "tool"
+}
+
+/// Merges adjacent messages of the same role by concatenating their content
+pub fn merge_consecutive_messages(
+    messages: Vec<ChatCompletionMessage>,
+) -> Vec<ChatCompletionMessage> {
+    let mut merged: Vec<ChatCompletionMessage> = Vec::with_capacity(messages.len());
+    for message in messages {
+        match merged.last_mut() {
+            Some(last) if last.role == message.role && can_merge(last) && can_merge(&message) => {
+                last.content = merge_content(last.content.take(), message.content);
+            }
+            _ => merged.push(message),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(role: &str, content: &str) -> ChatCompletionMessage {
+        ChatCompletionMessage::new_text(role.to_string(), content.to_string())
+    }
+
+    #[test]
+    fn test_consecutive_user_messages_are_merged() {
+        let messages = vec![
+            text("system", "Be brief."),
+            text("user", "Hi."),
+            text("user", "What is the capital of France?"),
+            text("assistant", "Paris"),
+        ];
+
+        let config = MergeConsecutiveMessages::default();
+        assert!(config.enabled_for("anthropic"));
+        assert!(config.enabled_for("bedrock"));
+
+        let merged = merge_consecutive_messages(messages);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[1].role, "user");
+        assert_eq!(
+            merged[1].content,
+            Some(ChatCompletionContent::Text(
+                "Hi.\n\nWhat is the capital of France?".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_providers_without_merging_are_left_alone() {
+        let config = MergeConsecutiveMessages::default();
+        assert!(!config.enabled_for("openai"));
+        assert!(!config.enabled_for("gemini"));
+
+        let config = MergeConsecutiveMessages(HashMap::from([
+            ("openai".to_string(), true),
+            ("anthropic".to_string(), false),
+        ]));
+        assert!(config.enabled_for("openai"));
+        assert!(!config.enabled_for("anthropic"));
+        assert!(config.enabled_for("bedrock"));
+    }
+
+    #[test]
+    fn test_tool_results_are_not_merged() {
+        let tool_result = |id: &str| ChatCompletionMessage {
+            role: "tool".to_string(),
+            content: Some(ChatCompletionContent::Text("42".to_string())),
+            tool_call_id: Some(id.to_string()),
+            ..Default::default()
+        };
+        let messages = vec![tool_result("call_1"), tool_result("call_2")];
+
+        assert_eq!(merge_consecutive_messages(messages).len(), 2);
+    }
+
+    #[test]
+    fn test_text_and_parts_are_merged_into_parts() {
+        let image = ChatCompletionMessage {
+            role: "user".to_string(),
+            content: Some(ChatCompletionContent::Content(vec![Content {
+                r#type: ContentType::ImageUrl,
+                text: None,
+                image_url: None,
+                audio: None,
+            }])),
+            ..Default::default()
+        };
+        let merged = merge_consecutive_messages(vec![text("user", "Describe this"), image]);
+
+        assert_eq!(merged.len(), 1);
+        let Some(ChatCompletionContent::Content(parts)) = &merged[0].content else {
+            panic!("Expected content parts");
+        };
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].text.as_deref(), Some("Describe this"));
+        assert_eq!(parts[1].r#type, ContentType::ImageUrl);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::error::GatewayError;
 use crate::executor::chat_completion::basic_executor::BasicCacheContext;
+use crate::executor::chat_completion::capabilities::check_capabilities;
 use crate::executor::chat_completion::stream_executor::{stream_chunks, StreamCacheContext};
 use crate::handler::{find_model_by_full_name, ModelEventWithDetails};
 use crate::llm_gateway::message_mapper::MessageMapper;
@@ -11,18 +12,21 @@ use crate::model::types::ModelEvent;
 use crate::model::types::ModelEventType;
 use crate::model::{ModelInstance, ResponseCacheState};
 use crate::models::ModelMetadata;
+use crate::pricing::estimate::check_request_cost;
+use crate::types::credentials::Credentials;
 use crate::types::engine::{
     CompletionModelDefinition, CompletionModelParams, ExecutionOptions, Model, ModelTool,
     ModelTools, ModelType, Prompt,
 };
 use crate::types::gateway::{
-    ChatCompletionMessage, ChatCompletionRequestWithTools, ChatCompletionResponse, Extra,
+    ChatCompletionDelta, ChatCompletionMessage, ChatCompletionRequestWithTools,
+    ChatCompletionResponse, CompletionModelUsage, Extra, ServerTools,
 };
 use crate::GatewayApiError;
 
 use either::Either::{self, Left, Right};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use tracing::Span;
@@ -30,13 +34,175 @@ use tracing_futures::Instrument;
 use uuid::Uuid;
 
 use super::context::ExecutorContext;
+use super::provider_keys::RotatedKeyModel;
 use super::{get_key_credentials, use_langdb_proxy};
-use crate::executor::chat_completion::stream_wrapper::ChatCompletionStream;
+use crate::executor::chat_completion::code_fences::{
+    is_structured_output, strip_response_code_fences,
+};
+use crate::executor::chat_completion::json_repair::repair_response_json;
+use crate::executor::chat_completion::merge_messages::merge_consecutive_messages;
+use crate::executor::chat_completion::parallel_tool_calls::{
+    keep_first_tool_call, with_single_tool_call,
+};
+use crate::executor::chat_completion::response_schema::{
+    apply_response_schema, enforced_schema, response_schema, validate_response,
+};
+use crate::executor::chat_completion::stop_sequences::apply_stop_sequences;
+use crate::executor::chat_completion::stream_schema::with_schema_validation;
+use crate::executor::chat_completion::stream_wrapper::{
+    with_stream_usage, wrap_stream, ChatCompletionStream,
+};
+use crate::executor::chat_completion::tool_limit::apply_tool_limit;
 
 pub mod basic_executor;
+pub mod capabilities;
+pub mod code_fences;
+pub mod json_repair;
+pub mod merge_messages;
+pub mod parallel_tool_calls;
+pub mod prompt_limit;
+pub mod response_aggregator;
+pub mod response_cache;
+pub mod response_schema;
 pub mod routed_executor;
+pub mod stop_sequences;
 pub mod stream_executor;
+pub mod stream_schema;
 pub mod stream_wrapper;
+pub mod tool_limit;
+pub mod transforms;
+
+/// Marks that every request has to carry a `user` identifier
+#[derive(Debug, Clone, Copy)]
+pub struct RequireUser;
+
+/// Capacity of the channels model events pass through. Producers wait for room once a
+/// channel is full, so a slow client throttles the provider stream instead of having it
+/// buffered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EventChannelCapacity(pub usize);
+
+impl Default for EventChannelCapacity {
+    fn default() -> Self {
+        Self(1000)
+    }
+}
+
+/// Answers streaming requests for providers that can't stream with a single chunk
+/// built from a non-streaming call, instead of failing them
+#[derive(Debug, Clone, Copy)]
+pub struct StreamFallback;
+
+/// Whether a streaming request for `model` has to be served by a non-streaming call
+fn needs_stream_fallback(
+    model: &ModelMetadata,
+    is_stream: bool,
+    stream_fallback: bool,
+) -> Result<bool, GatewayApiError> {
+    let provider = &model.inference_provider.provider;
+    if !is_stream || provider.supports_streaming() {
+        return Ok(false);
+    }
+    if !stream_fallback {
+        return Err(GatewayApiError::StreamingNotSupported(provider.to_string()));
+    }
+    Ok(true)
+}
+
+/// Streams a complete response as a single chunk
+fn single_chunk_stream(response: ChatCompletionResponse) -> ChatCompletionStream {
+    let usage = CompletionModelUsage {
+        input_tokens: response.usage.prompt_tokens as u32,
+        output_tokens: response.usage.completion_tokens as u32,
+        total_tokens: response.usage.total_tokens as u32,
+        ..Default::default()
+    };
+    let event = match response.choices.into_iter().next() {
+        Some(choice) => {
+            let delta = ChatCompletionDelta {
+                role: Some(choice.message.role),
+                content: choice.message.content.and_then(|c| c.as_string()),
+                tool_calls: choice.message.tool_calls,
+                index: 0,
+            };
+            (Some(delta), Some(usage), choice.finish_reason)
+        }
+        None => (None, Some(usage), None),
+    };
+
+    wrap_stream(futures::stream::once(async move { Ok(event) }))
+}
+
+/// Returns the request's `user`, or a random id when users aren't required
+pub(crate) fn resolve_user(
+    user: Option<&String>,
+    require_user: bool,
+) -> Result<String, GatewayApiError> {
+    match user {
+        Some(user) if !(require_user && user.trim().is_empty()) => Ok(user.clone()),
+        _ if require_user => Err(GatewayApiError::MissingUser),
+        _ => Ok(Uuid::new_v4().to_string()),
+    }
+}
+
+/// Most key-value pairs a request's `metadata` may hold
+const MAX_METADATA_PAIRS: usize = 16;
+/// Longest metadata key, in characters
+const MAX_METADATA_KEY_LENGTH: usize = 64;
+/// Longest metadata value, in characters
+const MAX_METADATA_VALUE_LENGTH: usize = 512;
+
+/// Tags recorded on the traces of a request: the `x-tags` of the request, plus its `metadata`
+/// for the keys `x-tags` doesn't set
+fn request_tags(
+    tags: &HashMap<String, String>,
+    metadata: Option<&HashMap<String, String>>,
+) -> Result<HashMap<String, String>, GatewayApiError> {
+    let Some(metadata) = metadata else {
+        return Ok(tags.clone());
+    };
+    if metadata.len() > MAX_METADATA_PAIRS {
+        return Err(GatewayApiError::InvalidMetadata(format!(
+            "{} pairs, at most {MAX_METADATA_PAIRS} are allowed",
+            metadata.len()
+        )));
+    }
+    for (key, value) in metadata {
+        if key.is_empty() || key.chars().count() > MAX_METADATA_KEY_LENGTH {
+            return Err(GatewayApiError::InvalidMetadata(format!(
+                "key {key:?} must have 1 to {MAX_METADATA_KEY_LENGTH} characters"
+            )));
+        }
+        if value.chars().count() > MAX_METADATA_VALUE_LENGTH {
+            return Err(GatewayApiError::InvalidMetadata(format!(
+                "value of {key:?} is longer than {MAX_METADATA_VALUE_LENGTH} characters"
+            )));
+        }
+    }
+
+    let mut merged = metadata.clone();
+    merged.extend(tags.clone());
+    Ok(merged)
+}
+
+/// Adds the tools of the MCP servers, skipping the ones disabled for the request
+fn register_mcp_tools(
+    mcp_tools: Vec<ServerTools>,
+    disabled_tools: &[String],
+    tools_map: &mut HashMap<String, Box<dyn Tool>>,
+    request_tools: &mut Vec<ModelTool>,
+) {
+    for server_tools in mcp_tools {
+        for tool in server_tools.tools {
+            if disabled_tools.contains(&tool.name()) {
+                continue;
+            }
+            tools_map.insert(tool.name(), Box::new(tool.clone()) as Box<dyn Tool>);
+            request_tools.push(tool.into());
+        }
+    }
+}
 
 pub async fn execute<T: Serialize + DeserializeOwned + Debug + Clone>(
     request_with_tools: &ChatCompletionRequestWithTools<T>,
@@ -52,6 +218,19 @@ pub async fn execute<T: Serialize + DeserializeOwned + Debug + Clone>(
     GatewayApiError,
 > {
     let span = Span::current();
+    let mut request_with_tools = request_with_tools.clone();
+    executor_context
+        .transforms
+        .transform_request(&mut request_with_tools.request)?;
+    let request_with_tools = &request_with_tools;
+    let user = resolve_user(
+        request_with_tools.request.user.as_ref(),
+        executor_context.require_user,
+    )?;
+    let tags = request_tags(
+        &executor_context.tags,
+        request_with_tools.request.metadata.as_ref(),
+    )?;
 
     let mut request_tools = vec![];
     let mut tools_map = HashMap::new();
@@ -77,14 +256,18 @@ pub async fn execute<T: Serialize + DeserializeOwned + Debug + Clone>(
         None => Vec::new(),
     };
 
-    for server_tools in mcp_tools {
-        for tool in server_tools.tools {
-            tools_map.insert(tool.name(), Box::new(tool.clone()) as Box<dyn Tool>);
-            request_tools.push(tool.into());
-        }
+    register_mcp_tools(
+        mcp_tools,
+        &request_with_tools.disabled_tools,
+        &mut tools_map,
+        &mut request_tools,
+    );
+    if let Some(limit) = executor_context.tool_limit {
+        apply_tool_limit(limit, &mut tools_map, &mut request_tools)?;
     }
 
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(1000);
+    let (tx, mut rx) =
+        tokio::sync::mpsc::channel::<Option<ModelEvent>>(executor_context.event_channel_capacity);
 
     let tools = ModelTools(request_tools);
 
@@ -120,13 +303,33 @@ pub async fn execute<T: Serialize + DeserializeOwned + Debug + Clone>(
 
     let mut request = request_with_tools.request.clone();
     let llm_model = find_model_by_full_name(&request.model, &executor_context.provided_models)?;
+    if let Some(ceiling) = executor_context.request_cost_ceiling {
+        check_request_cost(
+            &**executor_context.cost_calculator,
+            &llm_model,
+            &request,
+            ceiling,
+            &executor_context.currency,
+        )
+        .await?;
+    }
+    let stream_fallback = needs_stream_fallback(
+        &llm_model,
+        request.stream.unwrap_or(false),
+        executor_context.stream_fallback,
+    )?;
+    if stream_fallback {
+        request.stream = Some(false);
+    }
+    let provider_name = llm_model.inference_provider.provider.to_string();
+    if executor_context
+        .merge_consecutive_messages
+        .enabled_for(&provider_name)
+    {
+        request.messages = merge_consecutive_messages(request.messages);
+    }
     request.model = llm_model.inference_provider.model_name.clone();
 
-    let user: String = request
-        .user
-        .as_ref()
-        .map_or(Uuid::new_v4().to_string(), |v| v.clone());
-
     let mut messages = vec![];
 
     for message in &request.messages {
@@ -203,35 +406,112 @@ pub async fn execute<T: Serialize + DeserializeOwned + Debug + Clone>(
         .as_ref()
         .and_then(|e| e.variables.clone())
         .unwrap_or_default();
+    let include_usage = request_with_tools
+        .request
+        .stream_options
+        .as_ref()
+        .is_some_and(|options| options.include_usage);
+    let single_tool_call = request_with_tools.request.parallel_tool_calls == Some(false)
+        && !llm_model
+            .inference_provider
+            .provider
+            .supports_parallel_tool_calls();
     if is_stream {
+        let stream_schema = match &request_with_tools.extra {
+            Some(Extra {
+                validate_stream: true,
+                ..
+            }) => response_schema(&request_with_tools.request, &llm_model).cloned(),
+            _ => None,
+        };
         Ok(Left(
             stream_chunks(
                 resolved_model_context.completion_model_definition,
                 resolved_model_context.model_instance,
                 messages.clone(),
                 executor_context.callbackhandler.clone().into(),
-                executor_context.tags.clone(),
+                tags,
                 input_vars,
                 stream_cache_context,
+                executor_context.event_channel_capacity,
             )
             .instrument(span)
-            .await,
+            .await
+            .and_then(|stream| match stream_schema {
+                Some(schema) => with_schema_validation(stream, schema),
+                None => Ok(stream),
+            })
+            .map(|stream| {
+                let stream = if single_tool_call {
+                    with_single_tool_call(stream)
+                } else {
+                    stream
+                };
+                with_stream_usage(stream, include_usage)
+            }),
         ))
     } else {
         let result = basic_executor::execute(
             request,
             resolved_model_context.model_instance,
             messages.clone(),
-            executor_context.tags.clone(),
+            tags,
             tx,
             span.clone(),
             Some(handle),
             input_vars,
             basic_cache_context,
         )
-        .instrument(span)
+        .instrument(span.clone())
         .await;
 
+        let result = if executor_context.strip_code_fences
+            && is_structured_output(&request_with_tools.request, &llm_model)
+        {
+            result.map(|mut response| {
+                strip_response_code_fences(&mut response);
+                response
+            })
+        } else {
+            result
+        };
+
+        let result = match &request_with_tools.extra {
+            Some(Extra {
+                repair_json: true, ..
+            }) => result.map(|mut response| {
+                if repair_response_json(&mut response) {
+                    span.record("json_repaired", true);
+                }
+                response
+            }),
+            _ => result,
+        };
+
+        let result = match enforced_schema(&request_with_tools.request, &llm_model) {
+            Some(schema) => result.and_then(|response| {
+                validate_response(&response, schema)?;
+                Ok(response)
+            }),
+            None => result,
+        };
+
+        let result = if single_tool_call {
+            result.map(|mut response| {
+                keep_first_tool_call(&mut response);
+                response
+            })
+        } else {
+            result
+        };
+
+        let result = result.and_then(|mut response| {
+            executor_context
+                .transforms
+                .transform_response(&mut response)?;
+            Ok(response)
+        });
+
         // if let Ok(completion_response) = &result {
         //     let ChatCompletionResponse { choices, .. } = completion_response;
         //     for choice in choices {
@@ -246,6 +526,12 @@ pub async fn execute<T: Serialize + DeserializeOwned + Debug + Clone>(
         //     }
         // }
 
+        if stream_fallback {
+            return Ok(Left(result.map(|response| {
+                with_stream_usage(single_chunk_stream(response), include_usage)
+            })));
+        }
+
         Ok(Right(result))
     }
 }
@@ -264,22 +550,30 @@ pub async fn resolve_model_instance<T: Serialize + DeserializeOwned + Debug + Cl
 ) -> Result<ResolvedModelContext, GatewayApiError> {
     let llm_model =
         find_model_by_full_name(&request.request.model, &executor_context.provided_models)?;
+    check_capabilities(&request.request, &tools, &llm_model)?;
     let (key_credentials, llm_model) = use_langdb_proxy(executor_context, llm_model.clone());
 
+    let provider_name = llm_model.inference_provider.provider.to_string();
     let key = get_key_credentials(
         key_credentials.as_ref(),
         executor_context.providers_config.as_ref(),
-        &llm_model.inference_provider.provider.to_string(),
+        &provider_name,
     );
+    // Keys rotated over hear back when the provider rate limits them, so they get skipped
+    let rotating_keys = match (&key_credentials, &key) {
+        (None, Some(Credentials::ApiKey(key))) => executor_context
+            .providers_config
+            .as_ref()
+            .and_then(|p| p.rotating_keys(&provider_name))
+            .map(|keys| (keys.clone(), key.api_key.clone())),
+        _ => None,
+    };
     let provider_specific = request.provider_specific.clone();
-    let execution_options = request
-        .max_retries
-        .map(|retries| ExecutionOptions {
-            max_retries: Some(retries),
-        })
-        .unwrap_or_default();
+    let execution_options = resolve_execution_options(request, &llm_model);
 
-    let request = request.request.clone();
+    let mut request = request.request.clone();
+    apply_response_schema(&mut request, &llm_model);
+    apply_stop_sequences(&mut request, &llm_model);
 
     let engine = Provider::get_completion_engine_for_model(
         &llm_model,
@@ -297,7 +591,7 @@ pub async fn resolve_model_instance<T: Serialize + DeserializeOwned + Debug + Cl
         model_params: HashMap::new(),
         tools: tools.clone(),
         model_type: ModelType::Completions,
-        response_schema: None,
+        response_schema: llm_model.response_schema.as_ref().map(|s| s.to_string()),
         credentials: key,
     };
 
@@ -330,6 +624,10 @@ pub async fn resolve_model_instance<T: Serialize + DeserializeOwned + Debug + Cl
     )
     .await
     .map_err(|e| GatewayApiError::CustomError(e.to_string()))?;
+    let model_instance: Box<dyn ModelInstance> = match rotating_keys {
+        Some((keys, api_key)) => Box::new(RotatedKeyModel::new(model_instance, keys, api_key)),
+        None => model_instance,
+    };
 
     Ok(ResolvedModelContext {
         completion_model_definition,
@@ -339,6 +637,19 @@ pub async fn resolve_model_instance<T: Serialize + DeserializeOwned + Debug + Cl
     })
 }
 
+/// Request level options override the defaults configured for the model
+pub fn resolve_execution_options<T>(
+    request: &ChatCompletionRequestWithTools<T>,
+    llm_model: &ModelMetadata,
+) -> ExecutionOptions {
+    ExecutionOptions {
+        max_retries: request.max_retries,
+        max_tool_call_iterations: request.max_tool_call_iterations,
+        ..Default::default()
+    }
+    .or(llm_model.execution_options.clone().unwrap_or_default())
+}
+
 pub struct ResolvedModelContext {
     pub completion_model_definition: CompletionModelDefinition,
     pub model_instance: Box<dyn ModelInstance>,
@@ -361,6 +672,19 @@ pub async fn execute_with_tags<T: Serialize + DeserializeOwned + Debug + Clone>(
     GatewayApiError,
 > {
     let span = Span::current();
+    let mut request_with_tools = request_with_tools.clone();
+    executor_context
+        .transforms
+        .transform_request(&mut request_with_tools.request)?;
+    let request_with_tools = &request_with_tools;
+    let user = resolve_user(
+        request_with_tools.request.user.as_ref(),
+        executor_context.require_user,
+    )?;
+    let tags = request_tags(
+        &executor_context.tags,
+        request_with_tools.request.metadata.as_ref(),
+    )?;
 
     let mut request_tools = vec![];
     let mut tools_map = HashMap::new();
@@ -386,14 +710,18 @@ pub async fn execute_with_tags<T: Serialize + DeserializeOwned + Debug + Clone>(
         None => Vec::new(),
     };
 
-    for server_tools in mcp_tools {
-        for tool in server_tools.tools {
-            tools_map.insert(tool.name(), Box::new(tool.clone()) as Box<dyn Tool>);
-            request_tools.push(tool.into());
-        }
+    register_mcp_tools(
+        mcp_tools,
+        &request_with_tools.disabled_tools,
+        &mut tools_map,
+        &mut request_tools,
+    );
+    if let Some(limit) = executor_context.tool_limit {
+        apply_tool_limit(limit, &mut tools_map, &mut request_tools)?;
     }
 
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(1000);
+    let (tx, mut rx) =
+        tokio::sync::mpsc::channel::<Option<ModelEvent>>(executor_context.event_channel_capacity);
 
     let tools = ModelTools(request_tools);
 
@@ -429,13 +757,33 @@ pub async fn execute_with_tags<T: Serialize + DeserializeOwned + Debug + Clone>(
 
     let mut request = request_with_tools.request.clone();
     let llm_model = find_model_by_full_name(&request.model, &executor_context.provided_models)?;
+    if let Some(ceiling) = executor_context.request_cost_ceiling {
+        check_request_cost(
+            &**executor_context.cost_calculator,
+            &llm_model,
+            &request,
+            ceiling,
+            &executor_context.currency,
+        )
+        .await?;
+    }
+    let stream_fallback = needs_stream_fallback(
+        &llm_model,
+        request.stream.unwrap_or(false),
+        executor_context.stream_fallback,
+    )?;
+    if stream_fallback {
+        request.stream = Some(false);
+    }
+    let provider_name = llm_model.inference_provider.provider.to_string();
+    if executor_context
+        .merge_consecutive_messages
+        .enabled_for(&provider_name)
+    {
+        request.messages = merge_consecutive_messages(request.messages);
+    }
     request.model = llm_model.inference_provider.model_name.clone();
 
-    let user: String = request
-        .user
-        .as_ref()
-        .map_or(Uuid::new_v4().to_string(), |v| v.clone());
-
     let mut messages = vec![];
 
     for message in &request.messages {
@@ -512,35 +860,112 @@ pub async fn execute_with_tags<T: Serialize + DeserializeOwned + Debug + Clone>(
         .as_ref()
         .and_then(|e| e.variables.clone())
         .unwrap_or_default();
+    let include_usage = request_with_tools
+        .request
+        .stream_options
+        .as_ref()
+        .is_some_and(|options| options.include_usage);
+    let single_tool_call = request_with_tools.request.parallel_tool_calls == Some(false)
+        && !llm_model
+            .inference_provider
+            .provider
+            .supports_parallel_tool_calls();
     if is_stream {
+        let stream_schema = match &request_with_tools.extra {
+            Some(Extra {
+                validate_stream: true,
+                ..
+            }) => response_schema(&request_with_tools.request, &llm_model).cloned(),
+            _ => None,
+        };
         Ok(Left(
             stream_chunks(
                 resolved_model_context.completion_model_definition,
                 resolved_model_context.model_instance,
                 messages.clone(),
                 executor_context.callbackhandler.clone().into(),
-                executor_context.tags.clone(),
+                tags,
                 input_vars,
                 stream_cache_context,
+                executor_context.event_channel_capacity,
             )
             .instrument(span)
-            .await,
+            .await
+            .and_then(|stream| match stream_schema {
+                Some(schema) => with_schema_validation(stream, schema),
+                None => Ok(stream),
+            })
+            .map(|stream| {
+                let stream = if single_tool_call {
+                    with_single_tool_call(stream)
+                } else {
+                    stream
+                };
+                with_stream_usage(stream, include_usage)
+            }),
         ))
     } else {
         let result = basic_executor::execute_with_tags(
             request,
             resolved_model_context.model_instance,
             messages.clone(),
-            executor_context.tags.clone(),
+            tags,
             tx,
             span.clone(),
             Some(handle),
             input_vars,
             basic_cache_context,
         )
-        .instrument(span)
+        .instrument(span.clone())
         .await;
 
+        let result = if executor_context.strip_code_fences
+            && is_structured_output(&request_with_tools.request, &llm_model)
+        {
+            result.map(|mut response| {
+                strip_response_code_fences(&mut response);
+                response
+            })
+        } else {
+            result
+        };
+
+        let result = match &request_with_tools.extra {
+            Some(Extra {
+                repair_json: true, ..
+            }) => result.map(|mut response| {
+                if repair_response_json(&mut response) {
+                    span.record("json_repaired", true);
+                }
+                response
+            }),
+            _ => result,
+        };
+
+        let result = match enforced_schema(&request_with_tools.request, &llm_model) {
+            Some(schema) => result.and_then(|response| {
+                validate_response(&response, schema)?;
+                Ok(response)
+            }),
+            None => result,
+        };
+
+        let result = if single_tool_call {
+            result.map(|mut response| {
+                keep_first_tool_call(&mut response);
+                response
+            })
+        } else {
+            result
+        };
+
+        let result = result.and_then(|mut response| {
+            executor_context
+                .transforms
+                .transform_response(&mut response)?;
+            Ok(response)
+        });
+
         // if let Ok(completion_response) = &result {
         //     let ChatCompletionResponse { choices, .. } = completion_response;
         //     for choice in choices {
@@ -555,6 +980,187 @@ pub async fn execute_with_tags<T: Serialize + DeserializeOwned + Debug + Clone>(
         //     }
         // }
 
+        if stream_fallback {
+            return Ok(Left(result.map(|response| {
+                with_stream_usage(single_chunk_stream(response), include_usage)
+            })));
+        }
+
         Ok(Right(result))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::InferenceProvider;
+    use crate::types::gateway::{ChatCompletionChoice, ChatCompletionUsage};
+    use crate::types::provider::InferenceModelProvider;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_user_is_generated_by_default() {
+        let user = resolve_user(None, false).unwrap();
+        assert!(Uuid::parse_str(&user).is_ok());
+
+        let user = resolve_user(Some(&"alice".to_string()), false).unwrap();
+        assert_eq!(user, "alice");
+    }
+
+    #[test]
+    fn test_user_is_required() {
+        assert!(matches!(
+            resolve_user(None, true),
+            Err(GatewayApiError::MissingUser)
+        ));
+        assert!(matches!(
+            resolve_user(Some(&" ".to_string()), true),
+            Err(GatewayApiError::MissingUser)
+        ));
+
+        let user = resolve_user(Some(&"alice".to_string()), true).unwrap();
+        assert_eq!(user, "alice");
+    }
+
+    #[test]
+    fn test_metadata_is_merged_into_tags() {
+        let tags = HashMap::from([("team".to_string(), "search".to_string())]);
+        let metadata = HashMap::from([
+            ("conversation_id".to_string(), "c-42".to_string()),
+            ("team".to_string(), "ads".to_string()),
+        ]);
+
+        let merged = request_tags(&tags, Some(&metadata)).unwrap();
+        assert_eq!(merged["conversation_id"], "c-42");
+        // x-tags take precedence
+        assert_eq!(merged["team"], "search");
+        assert_eq!(request_tags(&tags, None).unwrap(), tags);
+    }
+
+    #[test]
+    fn test_metadata_size_is_limited() {
+        let too_many = (0..=MAX_METADATA_PAIRS)
+            .map(|i| (format!("key{i}"), "value".to_string()))
+            .collect::<HashMap<_, _>>();
+        let long_key = HashMap::from([("k".repeat(MAX_METADATA_KEY_LENGTH + 1), String::new())]);
+        let long_value = HashMap::from([(
+            "feature".to_string(),
+            "v".repeat(MAX_METADATA_VALUE_LENGTH + 1),
+        )]);
+
+        for metadata in [too_many, long_key, long_value] {
+            assert!(matches!(
+                request_tags(&HashMap::new(), Some(&metadata)),
+                Err(GatewayApiError::InvalidMetadata(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_disabled_mcp_tools_are_skipped() {
+        let server_tools: ServerTools = serde_json::from_value(serde_json::json!({
+            "definition": {"type": "in-memory", "name": "langdb"},
+            "tools": [
+                [
+                    {"name": "search", "description": "Searches the web", "inputSchema": {"type": "object"}},
+                    {"type": "in-memory", "name": "langdb"}
+                ],
+                [
+                    {"name": "delete_records", "description": "Deletes records", "inputSchema": {"type": "object"}},
+                    {"type": "in-memory", "name": "langdb"}
+                ]
+            ]
+        }))
+        .unwrap();
+        let request: ChatCompletionRequestWithTools<()> =
+            serde_json::from_value(serde_json::json!({
+                "model": "openai/gpt-4o-mini",
+                "messages": [],
+                "disabled_tools": ["delete_records"]
+            }))
+            .unwrap();
+
+        let mut tools_map = HashMap::new();
+        let mut request_tools = vec![];
+        register_mcp_tools(
+            vec![server_tools],
+            &request.disabled_tools,
+            &mut tools_map,
+            &mut request_tools,
+        );
+
+        assert!(tools_map.contains_key("search"));
+        assert!(!tools_map.contains_key("delete_records"));
+        assert_eq!(
+            request_tools
+                .iter()
+                .map(|t| t.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["search"]
+        );
+    }
+
+    fn model(provider: InferenceModelProvider) -> ModelMetadata {
+        ModelMetadata {
+            inference_provider: InferenceProvider {
+                provider,
+                model_name: "llama3".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_streaming_unsupported_is_rejected() {
+        let ollama = model(InferenceModelProvider::OllamaApi);
+        assert!(matches!(
+            needs_stream_fallback(&ollama, true, false),
+            Err(GatewayApiError::StreamingNotSupported(provider)) if provider == "ollama_api"
+        ));
+        assert!(!needs_stream_fallback(&ollama, false, false).unwrap());
+
+        let openai = model(InferenceModelProvider::OpenAI);
+        assert!(!needs_stream_fallback(&openai, true, false).unwrap());
+        assert!(!needs_stream_fallback(&openai, true, true).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_stream_fallback_wraps_response() {
+        let ollama = model(InferenceModelProvider::OllamaApi);
+        assert!(needs_stream_fallback(&ollama, true, true).unwrap());
+
+        let response = ChatCompletionResponse {
+            id: "chatcmpl-1".to_string(),
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: "llama3".to_string(),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatCompletionMessage::new_text(
+                    "assistant".to_string(),
+                    "Hello!".to_string(),
+                ),
+                finish_reason: Some("stop".to_string()),
+                logprobs: None,
+            }],
+            usage: ChatCompletionUsage {
+                prompt_tokens: 5,
+                completion_tokens: 2,
+                total_tokens: 7,
+                cost: 0.0,
+            },
+            is_cache_used: None,
+            service_tier: None,
+        };
+
+        let events: Vec<_> = single_chunk_stream(response).collect().await;
+        assert_eq!(events.len(), 1);
+        let (delta, usage, finish_reason) = events.into_iter().next().unwrap().unwrap();
+        let delta = delta.unwrap();
+        assert_eq!(delta.role.as_deref(), Some("assistant"));
+        assert_eq!(delta.content.as_deref(), Some("Hello!"));
+        assert_eq!(usage.unwrap().total_tokens, 7);
+        assert_eq!(finish_reason.as_deref(), Some("stop"));
+    }
+}
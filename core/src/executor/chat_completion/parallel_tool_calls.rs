@@ -0,0 +1,143 @@
+use futures::StreamExt;
+
+use crate::executor::chat_completion::stream_wrapper::{wrap_stream, ChatCompletionStream};
+use crate::types::gateway::ChatCompletionResponse;
+
+/// Keeps only the first tool call of every choice, for providers that can't be asked
+/// to avoid parallel tool calls
+pub fn keep_first_tool_call(response: &mut ChatCompletionResponse) {
+    for choice in &mut response.choices {
+        if let Some(tool_calls) = &mut choice.message.tool_calls {
+            tool_calls.truncate(1);
+        }
+    }
+}
+
+/// Drops every streamed tool call that isn't the first one. Deltas left without content,
+/// usage or a finish reason are dropped as well
+pub fn with_single_tool_call(stream: ChatCompletionStream) -> ChatCompletionStream {
+    let mut first_id: Option<String> = None;
+    wrap_stream(stream.filter_map(move |event| {
+        let event = match event {
+            Ok((Some(mut delta), usage, finish_reason)) if delta.tool_calls.is_some() => {
+                let tool_calls: Vec<_> = delta
+                    .tool_calls
+                    .take()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|tool_call| {
+                        first_id.get_or_insert_with(|| tool_call.id.clone()) == &tool_call.id
+                    })
+                    .collect();
+                if !tool_calls.is_empty() {
+                    delta.tool_calls = Some(tool_calls);
+                }
+
+                if delta.tool_calls.is_none()
+                    && delta.content.is_none()
+                    && usage.is_none()
+                    && finish_reason.is_none()
+                {
+                    None
+                } else {
+                    Some(Ok((Some(delta), usage, finish_reason)))
+                }
+            }
+            event => Some(event),
+        };
+        futures::future::ready(event)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gateway::{
+        ChatCompletionChoice, ChatCompletionDelta, ChatCompletionMessage, ChatCompletionUsage,
+        FunctionCall, ToolCall,
+    };
+
+    fn tool_call(index: usize, id: &str) -> ToolCall {
+        ToolCall {
+            index: Some(index),
+            id: id.to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "get_weather".to_string(),
+                arguments: format!("{{\"city\": \"{id}\"}}"),
+            },
+        }
+    }
+
+    fn tool_call_delta(tool_calls: Vec<ToolCall>) -> ChatCompletionDelta {
+        ChatCompletionDelta {
+            role: Some("assistant".to_string()),
+            content: None,
+            tool_calls: Some(tool_calls),
+            index: 0,
+        }
+    }
+
+    #[test]
+    fn test_response_keeps_first_tool_call() {
+        let mut response = ChatCompletionResponse {
+            id: "msg_1".to_string(),
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: "claude-3-5-sonnet".to_string(),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatCompletionMessage {
+                    role: "assistant".to_string(),
+                    tool_calls: Some(vec![tool_call(0, "call_1"), tool_call(1, "call_2")]),
+                    ..Default::default()
+                },
+                finish_reason: Some("tool_calls".to_string()),
+                logprobs: None,
+            }],
+            usage: ChatCompletionUsage::default(),
+            is_cache_used: None,
+            service_tier: None,
+        };
+
+        keep_first_tool_call(&mut response);
+
+        let tool_calls = response.choices[0].message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls.len(), 1);
+        assert_eq!(tool_calls[0].id, "call_1");
+    }
+
+    #[tokio::test]
+    async fn test_stream_keeps_first_tool_call() {
+        let events = vec![
+            Ok((
+                Some(tool_call_delta(vec![tool_call(0, "call_1")])),
+                None,
+                None,
+            )),
+            Ok((
+                Some(tool_call_delta(vec![tool_call(0, "call_2")])),
+                None,
+                None,
+            )),
+            Ok((
+                Some(tool_call_delta(vec![
+                    tool_call(0, "call_1"),
+                    tool_call(1, "call_2"),
+                ])),
+                None,
+                Some("tool_calls".to_string()),
+            )),
+        ];
+        let stream = wrap_stream(futures::stream::iter(events));
+
+        let events: Vec<_> = with_single_tool_call(stream).collect().await;
+        assert_eq!(events.len(), 2);
+        for event in events {
+            let (delta, _, _) = event.unwrap();
+            let tool_calls = delta.unwrap().tool_calls.unwrap();
+            assert_eq!(tool_calls.len(), 1);
+            assert_eq!(tool_calls[0].id, "call_1");
+        }
+    }
+}
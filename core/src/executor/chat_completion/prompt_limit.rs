@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pricing::estimate::estimate_tokens;
+use crate::types::gateway::{ChatCompletionContent, ChatCompletionMessage};
+use crate::GatewayApiError;
+
+/// Caps on the size of a prompt. Tokens are estimated at about four characters per token.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptSizeLimit {
+    #[serde(default)]
+    pub max_characters: Option<usize>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
+/// Rejects requests whose prompt is larger than allowed before they are sent to a provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptLimit {
+    #[serde(flatten)]
+    pub default: PromptSizeLimit,
+    /// Limits replacing the default for a model, keyed by its full name, e.g. `openai/gpt-4o`
+    #[serde(default)]
+    pub models: HashMap<String, PromptSizeLimit>,
+}
+
+impl PromptLimit {
+    fn for_model(&self, model: &str) -> PromptSizeLimit {
+        self.models.get(model).copied().unwrap_or(self.default)
+    }
+}
+
+/// Text of the messages sent as the prompt, images and audio excluded
+fn prompt_text(messages: &[ChatCompletionMessage]) -> String {
+    let mut text = String::new();
+    for message in messages {
+        match &message.content {
+            Some(ChatCompletionContent::Text(content)) => text.push_str(content),
+            Some(ChatCompletionContent::Content(parts)) => {
+                for part in parts.iter().filter_map(|part| part.text.as_ref()) {
+                    text.push_str(part);
+                }
+            }
+            None => {}
+        }
+        for tool_call in message.tool_calls.iter().flatten() {
+            text.push_str(&tool_call.function.arguments);
+        }
+    }
+    text
+}
+
+/// Fails when the prompt of a request to `model` is over the limit for it
+pub fn check_prompt_size(
+    limit: &PromptLimit,
+    model: &str,
+    messages: &[ChatCompletionMessage],
+) -> Result<(), GatewayApiError> {
+    let limit = limit.for_model(model);
+    let text = prompt_text(messages);
+
+    if let Some(max_characters) = limit.max_characters {
+        let characters = text.chars().count();
+        if characters > max_characters {
+            return Err(GatewayApiError::PromptTooLarge {
+                size: characters,
+                limit: max_characters,
+                unit: "characters",
+            });
+        }
+    }
+
+    if let Some(max_tokens) = limit.max_tokens {
+        let tokens = estimate_tokens(&text);
+        if tokens > max_tokens {
+            return Err(GatewayApiError::PromptTooLarge {
+                size: tokens as usize,
+                limit: max_tokens as usize,
+                unit: "tokens",
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(text: &str) -> Vec<ChatCompletionMessage> {
+        vec![
+            ChatCompletionMessage::new_text("system".to_string(), "Be brief.".to_string()),
+            ChatCompletionMessage::new_text("user".to_string(), text.to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_prompt_over_limit_is_rejected() {
+        let limit = PromptLimit {
+            default: PromptSizeLimit {
+                max_characters: Some(100),
+                max_tokens: Some(10),
+            },
+            models: HashMap::from([(
+                "openai/gpt-4o".to_string(),
+                PromptSizeLimit {
+                    max_characters: Some(1000),
+                    max_tokens: None,
+                },
+            )]),
+        };
+
+        // 9 + 22 characters, 8 tokens
+        let short = messages("What is 2 + 2 equal to");
+        assert!(check_prompt_size(&limit, "openai/gpt-4o-mini", &short).is_ok());
+
+        let long = messages(&"a".repeat(60));
+        assert!(matches!(
+            check_prompt_size(&limit, "openai/gpt-4o-mini", &long),
+            Err(GatewayApiError::PromptTooLarge {
+                size: 18,
+                limit: 10,
+                unit: "tokens"
+            })
+        ));
+
+        let longer = messages(&"a".repeat(200));
+        assert!(matches!(
+            check_prompt_size(&limit, "openai/gpt-4o-mini", &longer),
+            Err(GatewayApiError::PromptTooLarge {
+                size: 209,
+                limit: 100,
+                unit: "characters"
+            })
+        ));
+
+        // The model's own limit replaces the default
+        assert!(check_prompt_size(&limit, "openai/gpt-4o", &longer).is_ok());
+    }
+
+    #[test]
+    fn test_no_limit_accepts_any_prompt() {
+        let longer = messages(&"a".repeat(10_000));
+        assert!(check_prompt_size(&PromptLimit::default(), "openai/gpt-4o", &longer).is_ok());
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+
+use uuid::Uuid;
+
+use crate::model::types::{LLMFinishEvent, ModelEventType, ModelFinishReason, ModelToolCall};
+use crate::types::gateway::{
+    ChatCompletionChoice, ChatCompletionContent, ChatCompletionMessage, ChatCompletionResponse,
+    ChatCompletionUsage, FunctionCall, ToolCall,
+};
+
+/// Assembles the events of a streamed completion into the response a non-streaming call
+/// would have returned
+#[derive(Default)]
+pub struct ResponseAggregator {
+    /// Text of every choice, by index
+    content: BTreeMap<u32, String>,
+    tool_calls: Vec<ModelToolCall>,
+    finish: Option<LLMFinishEvent>,
+}
+
+impl ResponseAggregator {
+    pub fn push(&mut self, event: &ModelEventType) {
+        match event {
+            ModelEventType::LlmContent(content) => self
+                .content
+                .entry(content.index)
+                .or_default()
+                .push_str(&content.content),
+            ModelEventType::ToolStart(tool) => self.tool_calls.push(ModelToolCall {
+                tool_id: tool.tool_id.clone(),
+                tool_name: tool.tool_name.clone(),
+                input: tool.input.clone(),
+            }),
+            ModelEventType::LlmStop(finish) => self.finish = Some(finish.clone()),
+            _ => {}
+        }
+    }
+
+    /// The assembled response, `None` until the stream has finished
+    pub fn finish(self) -> Option<ChatCompletionResponse> {
+        let finish = self.finish?;
+
+        let tool_calls = if finish.tool_calls.is_empty() {
+            self.tool_calls
+        } else {
+            finish.tool_calls
+        };
+        let tool_calls = (!tool_calls.is_empty()).then(|| {
+            tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(index, tc)| ToolCall {
+                    index: Some(index),
+                    id: tc.tool_id,
+                    r#type: "function".into(),
+                    function: FunctionCall {
+                        name: tc.tool_name,
+                        arguments: tc.input,
+                    },
+                })
+                .collect::<Vec<_>>()
+        });
+        let mut choices_content = self.content;
+        let first_content = choices_content.remove(&0).unwrap_or_default();
+        let content = (tool_calls.is_none() || !first_content.is_empty())
+            .then_some(ChatCompletionContent::Text(first_content));
+
+        let finish_reason = match (&finish.finish_reason, &tool_calls) {
+            (ModelFinishReason::Stop, Some(_)) => ModelFinishReason::ToolCalls.to_string(),
+            (reason, _) => reason.to_string(),
+        };
+        let usage = finish
+            .usage
+            .as_ref()
+            .map(|u| ChatCompletionUsage {
+                prompt_tokens: u.input_tokens as i32,
+                completion_tokens: u.output_tokens as i32,
+                total_tokens: u.total_tokens as i32,
+                cost: 0.0,
+            })
+            .unwrap_or_default();
+
+        let mut choices = vec![ChatCompletionChoice {
+            index: 0,
+            message: ChatCompletionMessage {
+                role: "assistant".to_string(),
+                content,
+                tool_calls,
+                ..Default::default()
+            },
+            finish_reason: Some(finish_reason),
+            logprobs: finish.logprobs,
+        }];
+        // Other choices only carry text
+        choices.extend(
+            choices_content
+                .into_iter()
+                .map(|(index, text)| ChatCompletionChoice {
+                    index: index as i32,
+                    message: ChatCompletionMessage::new_text("assistant".to_string(), text),
+                    finish_reason: Some(finish.finish_reason.to_string()),
+                    logprobs: None,
+                }),
+        );
+
+        Some(ChatCompletionResponse {
+            id: finish
+                .response_id
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            object: "chat.completion".to_string(),
+            created: chrono::Utc::now().timestamp(),
+            model: finish.model_name,
+            choices,
+            usage,
+            is_cache_used: finish.usage.map(|u| u.is_cache_used),
+            service_tier: finish.service_tier,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::chat_completion::basic_executor::{self, BasicCacheContext};
+    use crate::model::cached::CachedModel;
+    use crate::model::types::{LLMContentEvent, ModelEvent};
+    use crate::model::{CredentialsIdent, ModelInstance};
+    use crate::types::gateway::{ChatCompletionRequest, CompletionModelUsage};
+    use std::collections::HashMap;
+    use tracing::Span;
+
+    fn events() -> Vec<ModelEvent> {
+        let span = Span::none();
+        let mut events = ["The capital ", "of France ", "is Paris."]
+            .into_iter()
+            .map(|content| {
+                ModelEvent::new(
+                    &span,
+                    ModelEventType::LlmContent(LLMContentEvent {
+                        content: content.to_string(),
+                        index: 0,
+                    }),
+                )
+            })
+            .collect::<Vec<_>>();
+        events.push(ModelEvent::new(
+            &span,
+            ModelEventType::LlmStop(LLMFinishEvent {
+                provider_name: "openai".to_string(),
+                model_name: "gpt-4o".to_string(),
+                output: Some("The capital of France is Paris.".to_string()),
+                usage: Some(CompletionModelUsage {
+                    input_tokens: 12,
+                    output_tokens: 8,
+                    total_tokens: 20,
+                    ..Default::default()
+                }),
+                finish_reason: ModelFinishReason::Stop,
+                tool_calls: vec![],
+                credentials_ident: CredentialsIdent::Own,
+                logprobs: None,
+                service_tier: None,
+                response_id: Some("chatcmpl-upstream".to_string()),
+            }),
+        ));
+        events
+    }
+
+    fn mock() -> Box<dyn ModelInstance> {
+        Box::new(CachedModel::new(
+            events(),
+            Some(ChatCompletionMessage::new_text(
+                "assistant".to_string(),
+                "The capital of France is Paris.".to_string(),
+            )),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_streamed_response_matches_non_streaming() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        mock()
+            .stream(HashMap::new(), tx, vec![], HashMap::new())
+            .await
+            .unwrap();
+        let mut aggregator = ResponseAggregator::default();
+        while let Some(Some(event)) = rx.recv().await {
+            aggregator.push(&event.event);
+        }
+        let streamed = aggregator.finish().unwrap();
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let invoked = basic_executor::execute(
+            ChatCompletionRequest {
+                model: "gpt-4o".to_string(),
+                ..Default::default()
+            },
+            mock(),
+            vec![],
+            HashMap::new(),
+            tx,
+            Span::none(),
+            None,
+            HashMap::new(),
+            BasicCacheContext::default(),
+        )
+        .await
+        .unwrap();
+
+        let comparable = |response: &ChatCompletionResponse| {
+            let mut value = serde_json::to_value(response).unwrap();
+            value["id"] = serde_json::Value::Null;
+            value["created"] = serde_json::Value::Null;
+            (value, response.is_cache_used)
+        };
+        assert_eq!(comparable(&streamed), comparable(&invoked));
+    }
+
+    #[tokio::test]
+    async fn test_upstream_response_id_is_used() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        mock()
+            .stream(HashMap::new(), tx, vec![], HashMap::new())
+            .await
+            .unwrap();
+        let mut aggregator = ResponseAggregator::default();
+        while let Some(Some(event)) = rx.recv().await {
+            aggregator.push(&event.event);
+        }
+        assert_eq!(aggregator.finish().unwrap().id, "chatcmpl-upstream");
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let invoked = basic_executor::execute(
+            ChatCompletionRequest {
+                model: "gpt-4o".to_string(),
+                ..Default::default()
+            },
+            mock(),
+            vec![],
+            HashMap::new(),
+            tx,
+            Span::none(),
+            None,
+            HashMap::new(),
+            BasicCacheContext::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(invoked.id, "chatcmpl-upstream");
+    }
+
+    #[test]
+    fn test_tool_calls_are_assembled() {
+        let mut aggregator = ResponseAggregator::default();
+        aggregator.push(&ModelEventType::LlmStop(LLMFinishEvent {
+            provider_name: "openai".to_string(),
+            model_name: "gpt-4o".to_string(),
+            output: None,
+            usage: None,
+            finish_reason: ModelFinishReason::ToolCalls,
+            tool_calls: vec![ModelToolCall {
+                tool_id: "call_1".to_string(),
+                tool_name: "search".to_string(),
+                input: r#"{"query":"Paris"}"#.to_string(),
+            }],
+            credentials_ident: CredentialsIdent::Own,
+            logprobs: None,
+            service_tier: None,
+            response_id: None,
+        }));
+
+        let response = aggregator.finish().unwrap();
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason.as_deref(), Some("tool_calls"));
+        assert!(choice.message.content.is_none());
+        let tool_calls = choice.message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls[0].id, "call_1");
+        assert_eq!(tool_calls[0].function.name, "search");
+    }
+
+    #[test]
+    fn test_unfinished_stream_has_no_response() {
+        let mut aggregator = ResponseAggregator::default();
+        aggregator.push(&ModelEventType::LlmContent(LLMContentEvent {
+            content: "The capital".to_string(),
+            index: 0,
+        }));
+        assert!(aggregator.finish().is_none());
+    }
+}
@@ -0,0 +1,605 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::executor::chat_completion::basic_executor::BasicCacheContext;
+use crate::executor::chat_completion::stream_executor::StreamCacheContext;
+use crate::executor::context::ExecutorContext;
+use crate::model::types::{ModelEvent, ModelEventType};
+use crate::model::ResponseCacheState;
+use crate::types::credentials::credential_fingerprint;
+use crate::types::gateway::{ChatCompletionMessage, ChatCompletionRequestWithTools};
+
+/// Header that makes a request skip the response cache when set to `true`
+pub const CACHE_BYPASS_HEADER: &str = "x-cache-bypass";
+
+/// Events and response of a finished completion, replayed by `CachedModel` on a hit
+#[derive(Clone)]
+pub struct CachedCompletion {
+    pub events: Vec<ModelEvent>,
+    /// Only set by non-streaming completions
+    pub response: Option<ChatCompletionMessage>,
+}
+
+/// Expiration and size limits of the response cache
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    /// Seconds after which a cached completion expires
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Completions kept at most, the least recently used one is evicted to make room
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_ttl_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_max_entries() -> usize {
+    10_000
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_ttl_secs(),
+            max_entries: default_max_entries(),
+        }
+    }
+}
+
+struct CacheEntry {
+    completion: CachedCompletion,
+    expires_at: Instant,
+    /// Tick of the last insert or hit, the key of the entry in `Entries::recency`
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<String, CacheEntry>,
+    /// Keys ordered by their last use, least recently used first
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Entries {
+    fn get(&mut self, key: &str, now: Instant) -> Option<CachedCompletion> {
+        let entry = self.entries.get_mut(key)?;
+        if entry.expires_at <= now {
+            self.remove(key);
+            return None;
+        }
+        self.tick += 1;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(self.tick, key.to_string());
+        entry.last_used = self.tick;
+        Some(entry.completion.clone())
+    }
+
+    fn insert(
+        &mut self,
+        key: String,
+        completion: CachedCompletion,
+        expires_at: Instant,
+        max_entries: usize,
+    ) {
+        self.remove(&key);
+        while self.entries.len() >= max_entries {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                completion,
+                expires_at,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+/// In-memory store of the completions of requests that set `extra.cache`, keyed by the
+/// request. Streaming and non-streaming requests share entries. Entries expire after the
+/// configured TTL and the least recently used one is evicted once the cache is full.
+#[derive(Clone)]
+pub struct ResponseCache {
+    entries: Arc<Mutex<Entries>>,
+    ttl: Duration,
+    max_entries: usize,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(&ResponseCacheConfig::default())
+    }
+}
+
+impl ResponseCache {
+    pub fn new(config: &ResponseCacheConfig) -> Self {
+        Self::with_limits(Duration::from_secs(config.ttl_secs), config.max_entries)
+    }
+
+    pub fn with_limits(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            ttl,
+            max_entries,
+        }
+    }
+
+    /// Key of `request` made in `executor_context`. Requests of other tenants, or made with
+    /// other provider credentials, never share an entry.
+    pub fn key<T: Serialize>(
+        request: &ChatCompletionRequestWithTools<T>,
+        executor_context: &ExecutorContext,
+    ) -> String {
+        let mut value = serde_json::to_value(request).unwrap_or_default();
+        if let Some(object) = value.as_object_mut() {
+            object.remove("stream");
+        }
+        let credentials = executor_context
+            .key_credentials
+            .as_ref()
+            .and_then(|credentials| serde_json::to_string(credentials).ok())
+            .map(|credentials| credential_fingerprint(&credentials));
+        serde_json::json!({
+            "tenant": executor_context.tenant,
+            "credentials": credentials,
+            "request": value,
+        })
+        .to_string()
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedCompletion> {
+        self.entries.lock().unwrap().get(key, Instant::now())
+    }
+
+    /// Stores `completion` until the cache TTL, or `ttl` when it is shorter, has passed
+    pub fn insert(&self, key: String, completion: CachedCompletion, ttl: Option<Duration>) {
+        let ttl = ttl.map_or(self.ttl, |ttl| ttl.min(self.ttl));
+        self.entries.lock().unwrap().insert(
+            key,
+            completion,
+            Instant::now() + ttl,
+            self.max_entries,
+        );
+    }
+
+    /// Cache contexts for executing `request`. On a hit they replay the cached
+    /// completion, on a miss they record the completion into the cache once it finishes.
+    pub fn contexts<T: Serialize>(
+        &self,
+        request: &ChatCompletionRequestWithTools<T>,
+        executor_context: &ExecutorContext,
+    ) -> (ResponseCacheState, StreamCacheContext, BasicCacheContext) {
+        let key = Self::key(request, executor_context);
+        let stream = request.request.stream.unwrap_or(false);
+        let ttl = request
+            .extra
+            .as_ref()
+            .and_then(|e| e.cache.as_ref())
+            .and_then(|options| options.expiration_time)
+            .map(|secs| Duration::from_secs(secs.into()));
+
+        if let Some(cached) = self.get(&key) {
+            if stream {
+                let context = StreamCacheContext {
+                    cached_events: Some(cached.events),
+                    ..Default::default()
+                };
+                return (
+                    ResponseCacheState::Hit,
+                    context,
+                    BasicCacheContext::default(),
+                );
+            }
+            if let Some(response) = cached.response {
+                let context = BasicCacheContext {
+                    cached_events: Some(cached.events),
+                    cached_response: Some(response),
+                    ..Default::default()
+                };
+                return (
+                    ResponseCacheState::Hit,
+                    StreamCacheContext::default(),
+                    context,
+                );
+            }
+        }
+
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::channel(4096);
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let mut events = vec![];
+            while let Some(event) = events_rx.recv().await {
+                events.extend(event);
+            }
+            let response = response_rx.await.ok();
+
+            let finished = events
+                .iter()
+                .any(|e| matches!(e.event, ModelEventType::LlmStop(_)));
+            if finished && (stream || response.is_some()) {
+                cache.insert(key, CachedCompletion { events, response }, ttl);
+            }
+        });
+
+        if stream {
+            let context = StreamCacheContext {
+                events_sender: Some(events_tx),
+                ..Default::default()
+            };
+            (
+                ResponseCacheState::Miss,
+                context,
+                BasicCacheContext::default(),
+            )
+        } else {
+            let context = BasicCacheContext {
+                events_sender: Some(events_tx),
+                response_sender: Some(response_tx),
+                ..Default::default()
+            };
+            (
+                ResponseCacheState::Miss,
+                StreamCacheContext::default(),
+                context,
+            )
+        }
+    }
+}
+
+/// Cache state and contexts of `request`, which only uses the cache when it sets
+/// `extra.cache`. Requests bypassing the cache always call the model and are reported
+/// as a `MISS`.
+pub fn cache_contexts<T: Serialize>(
+    request: &ChatCompletionRequestWithTools<T>,
+    executor_context: &ExecutorContext,
+) -> (
+    Option<ResponseCacheState>,
+    StreamCacheContext,
+    BasicCacheContext,
+) {
+    let options = request.extra.as_ref().and_then(|e| e.cache.as_ref());
+    match (executor_context.response_cache.as_ref(), options) {
+        (Some(_), Some(options)) if options.bypass || bypass_requested(executor_context) => (
+            Some(ResponseCacheState::Miss),
+            StreamCacheContext::default(),
+            BasicCacheContext::default(),
+        ),
+        (Some(cache), Some(_)) => {
+            let (state, stream, basic) = cache.contexts(request, executor_context);
+            (Some(state), stream, basic)
+        }
+        _ => (
+            None,
+            StreamCacheContext::default(),
+            BasicCacheContext::default(),
+        ),
+    }
+}
+
+fn bypass_requested(executor_context: &ExecutorContext) -> bool {
+    executor_context
+        .headers
+        .get(CACHE_BYPASS_HEADER)
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::chat_completion::routed_executor::RoutedExecutor;
+    use crate::executor::context::ExecutorContext;
+    use crate::handler::{AvailableModels, CallbackHandlerFn};
+    use crate::models::{InferenceProvider, ModelMetadata};
+    use crate::otel::TraceMap;
+    use crate::routing::RoutingStrategy;
+    use crate::test_utils::{FreeModels, NoGuards};
+    use crate::types::cache::ResponseCacheOptions;
+    use crate::types::credentials::{ApiKeyCredentials, Credentials};
+    use crate::types::gateway::{ChatCompletionRequest, Extra};
+    use crate::types::provider::InferenceModelProvider;
+    use actix_web::{web, App, HttpMessage, HttpResponse, HttpServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn completion(calls: web::Data<AtomicUsize>) -> HttpResponse {
+        calls.fetch_add(1, Ordering::SeqCst);
+        HttpResponse::Ok().json(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Paris"},
+                "finish_reason": "stop",
+                "logprobs": null
+            }],
+            "usage": {"prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10}
+        }))
+    }
+
+    /// Starts a provider answering with `completion`, returning its address
+    fn start_provider(calls: web::Data<AtomicUsize>) -> std::net::SocketAddr {
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(calls.clone())
+                .route("/v1/chat/completions", web::post().to(completion))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        addr
+    }
+
+    /// Context of a request sent through `req` to the provider at `addr`
+    fn context_for(
+        addr: std::net::SocketAddr,
+        cache: &ResponseCache,
+        req: actix_web::test::TestRequest,
+    ) -> ExecutorContext {
+        let model = ModelMetadata {
+            model: "gpt-4o-mini".to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: InferenceProvider {
+                provider: InferenceModelProvider::OpenAI,
+                model_name: "gpt-4o-mini".to_string(),
+                endpoint: Some(format!("http://{addr}/v1")),
+            },
+            ..Default::default()
+        };
+        let req = req.app_data(cache.clone()).to_http_request();
+        req.extensions_mut()
+            .insert(Credentials::ApiKey(ApiKeyCredentials {
+                api_key: "test-key".to_string(),
+            }));
+        ExecutorContext::new(
+            CallbackHandlerFn(None),
+            Arc::new(Box::new(FreeModels)),
+            AvailableModels(vec![model]),
+            &req,
+            Arc::new(Box::new(NoGuards)),
+        )
+        .unwrap()
+    }
+
+    fn cached_request() -> ChatCompletionRequestWithTools<RoutingStrategy> {
+        ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: "openai/gpt-4o-mini".to_string(),
+                messages: vec![ChatCompletionMessage::new_text(
+                    "user".to_string(),
+                    "What is the capital of France?".to_string(),
+                )],
+                ..Default::default()
+            },
+            extra: Some(Extra {
+                user: None,
+                guards: vec![],
+                cache: Some(ResponseCacheOptions::default()),
+                variables: None,
+                repair_json: false,
+                validate_stream: false,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Waits for the completion of `request`, written to the cache in the background
+    async fn wait_for_entry(
+        cache: &ResponseCache,
+        request: &ChatCompletionRequestWithTools<RoutingStrategy>,
+        executor_context: &ExecutorContext,
+    ) {
+        let key = ResponseCache::key(request, executor_context);
+        for _ in 0..50 {
+            if cache.get(&key).is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_cache_state_header() {
+        let calls = web::Data::new(AtomicUsize::new(0));
+        let addr = start_provider(calls.clone());
+        let cache = ResponseCache::default();
+        let executor_context = context_for(addr, &cache, actix_web::test::TestRequest::default());
+        let request = cached_request();
+        let traces = TraceMap::new();
+
+        let response = RoutedExecutor::new(request.clone())
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get("x-cache").unwrap(), "MISS");
+        wait_for_entry(&cache, &request, &executor_context).await;
+
+        let response = RoutedExecutor::new(request.clone())
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get("x-cache").unwrap(), "HIT");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Requests without cache options don't report a cache state
+        let uncached = ChatCompletionRequestWithTools {
+            extra: None,
+            ..request
+        };
+        let response = RoutedExecutor::new(uncached)
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        assert!(response.headers().get("x-cache").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_cache_bypass() {
+        let calls = web::Data::new(AtomicUsize::new(0));
+        let addr = start_provider(calls.clone());
+        let cache = ResponseCache::default();
+        let executor_context = context_for(addr, &cache, actix_web::test::TestRequest::default());
+        let request = cached_request();
+        let traces = TraceMap::new();
+
+        RoutedExecutor::new(request.clone())
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        wait_for_entry(&cache, &request, &executor_context).await;
+
+        let bypassing_context = context_for(
+            addr,
+            &cache,
+            actix_web::test::TestRequest::default().insert_header((CACHE_BYPASS_HEADER, "true")),
+        );
+        let response = RoutedExecutor::new(request.clone())
+            .execute(&bypassing_context, &traces, None)
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get("x-cache").unwrap(), "MISS");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let mut bypassing_request = request.clone();
+        if let Some(Extra {
+            cache: Some(options),
+            ..
+        }) = bypassing_request.extra.as_mut()
+        {
+            options.bypass = true;
+        }
+        let response = RoutedExecutor::new(bypassing_request.clone())
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get("x-cache").unwrap(), "MISS");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Bypassing requests don't write to the cache
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(cache
+            .get(&ResponseCache::key(&bypassing_request, &executor_context))
+            .is_none());
+
+        let response = RoutedExecutor::new(request)
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get("x-cache").unwrap(), "HIT");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[actix_web::test]
+    async fn test_entries_are_not_shared_across_tenants() {
+        let calls = web::Data::new(AtomicUsize::new(0));
+        let addr = start_provider(calls.clone());
+        let cache = ResponseCache::default();
+        let executor_context = ExecutorContext {
+            tenant: Some("acme".to_string()),
+            ..context_for(addr, &cache, actix_web::test::TestRequest::default())
+        };
+        let request = cached_request();
+        let traces = TraceMap::new();
+
+        RoutedExecutor::new(request.clone())
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        wait_for_entry(&cache, &request, &executor_context).await;
+
+        let other_tenant = ExecutorContext {
+            tenant: Some("globex".to_string()),
+            ..executor_context.clone()
+        };
+        let other_key = ExecutorContext {
+            key_credentials: Some(Credentials::ApiKey(ApiKeyCredentials {
+                api_key: "other-key".to_string(),
+            })),
+            ..executor_context.clone()
+        };
+        for executor_context in [&other_tenant, &other_key] {
+            let response = RoutedExecutor::new(request.clone())
+                .execute(executor_context, &traces, None)
+                .await
+                .unwrap();
+            assert_eq!(response.headers().get("x-cache").unwrap(), "MISS");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let response = RoutedExecutor::new(request)
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get("x-cache").unwrap(), "HIT");
+    }
+
+    fn completion(content: &str) -> CachedCompletion {
+        CachedCompletion {
+            events: vec![],
+            response: Some(ChatCompletionMessage::new_text(
+                "assistant".to_string(),
+                content.to_string(),
+            )),
+        }
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let cache = ResponseCache::with_limits(Duration::from_millis(50), 10);
+        cache.insert("paris".to_string(), completion("Paris"), None);
+        // A shorter request expiration wins over the cache TTL
+        cache.insert(
+            "rome".to_string(),
+            completion("Rome"),
+            Some(Duration::from_millis(10)),
+        );
+        assert!(cache.get("paris").is_some());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(cache.get("paris").is_some());
+        assert!(cache.get("rome").is_none());
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(cache.get("paris").is_none());
+    }
+
+    #[test]
+    fn test_least_recently_used_entry_is_evicted() {
+        let cache = ResponseCache::with_limits(Duration::from_secs(60), 2);
+        cache.insert("paris".to_string(), completion("Paris"), None);
+        cache.insert("rome".to_string(), completion("Rome"), None);
+        // Reading makes `paris` the most recently used entry
+        assert!(cache.get("paris").is_some());
+
+        cache.insert("madrid".to_string(), completion("Madrid"), None);
+        assert!(cache.get("rome").is_none());
+        assert!(cache.get("paris").is_some());
+        assert!(cache.get("madrid").is_some());
+
+        // Replacing an entry doesn't evict another one
+        cache.insert("madrid".to_string(), completion("Madrid"), None);
+        assert!(cache.get("paris").is_some());
+        assert!(cache.get("madrid").is_some());
+    }
+}
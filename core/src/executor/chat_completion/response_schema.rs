@@ -0,0 +1,170 @@
+use async_openai::types::{ResponseFormat, ResponseFormatJsonSchema};
+use jsonschema::{Draft, Validator};
+use serde_json::Value;
+
+use crate::models::ModelMetadata;
+use crate::types::gateway::{ChatCompletionContent, ChatCompletionRequest, ChatCompletionResponse};
+use crate::GatewayApiError;
+
+const RESPONSE_SCHEMA_NAME: &str = "response";
+
+/// The schema of `model` enforced for `request`. Requests bringing their own
+/// `response_format` are left alone.
+pub fn enforced_schema<'a>(
+    request: &ChatCompletionRequest,
+    model: &'a ModelMetadata,
+) -> Option<&'a Value> {
+    match request.response_format {
+        Some(_) => None,
+        None => model.response_schema.as_ref(),
+    }
+}
+
+/// Sets the structured output format of `request` to the model's response schema
+pub fn apply_response_schema(request: &mut ChatCompletionRequest, model: &ModelMetadata) {
+    if let Some(schema) = enforced_schema(request, model) {
+        request.response_format = Some(ResponseFormat::JsonSchema {
+            json_schema: ResponseFormatJsonSchema {
+                description: None,
+                name: RESPONSE_SCHEMA_NAME.to_string(),
+                schema: Some(schema.clone()),
+                strict: Some(true),
+            },
+        });
+    }
+}
+
+/// The schema a response to `request` has to match: the request's own JSON schema
+/// response format, else the schema enforced by `model`
+pub fn response_schema<'a>(
+    request: &'a ChatCompletionRequest,
+    model: &'a ModelMetadata,
+) -> Option<&'a Value> {
+    match &request.response_format {
+        Some(ResponseFormat::JsonSchema { json_schema }) => json_schema.schema.as_ref(),
+        _ => enforced_schema(request, model),
+    }
+}
+
+pub fn schema_validator(schema: &Value) -> Result<Validator, GatewayApiError> {
+    Validator::options()
+        .with_draft(Draft::Draft7)
+        .build(schema)
+        .map_err(|e| GatewayApiError::ResponseSchemaViolation(format!("invalid schema: {e}")))
+}
+
+/// Checks that `text` is JSON matching the schema of `validator`
+pub fn validate_content(validator: &Validator, text: &str) -> Result<(), GatewayApiError> {
+    let value = serde_json::from_str::<Value>(text)
+        .map_err(|e| GatewayApiError::ResponseSchemaViolation(e.to_string()))?;
+    validator
+        .validate(&value)
+        .map_err(|e| GatewayApiError::ResponseSchemaViolation(e.to_string()))
+}
+
+/// Checks that the text content of every choice is JSON matching `schema`
+pub fn validate_response(
+    response: &ChatCompletionResponse,
+    schema: &Value,
+) -> Result<(), GatewayApiError> {
+    let validator = schema_validator(schema)?;
+
+    for choice in &response.choices {
+        let Some(ChatCompletionContent::Text(text)) = &choice.message.content else {
+            continue;
+        };
+        validate_content(&validator, text)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gateway::{ChatCompletionChoice, ChatCompletionMessage, ChatCompletionUsage};
+    use serde_json::json;
+
+    fn model() -> ModelMetadata {
+        ModelMetadata {
+            response_schema: Some(json!({
+                "type": "object",
+                "properties": {"city": {"type": "string"}},
+                "required": ["city"]
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn response(content: &str) -> ChatCompletionResponse {
+        ChatCompletionResponse {
+            id: "chatcmpl-1".to_string(),
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: "gpt-4o".to_string(),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatCompletionMessage::new_text(
+                    "assistant".to_string(),
+                    content.to_string(),
+                ),
+                finish_reason: Some("stop".to_string()),
+                logprobs: None,
+            }],
+            usage: ChatCompletionUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+                cost: 0.0,
+            },
+            is_cache_used: None,
+            service_tier: None,
+        }
+    }
+
+    #[test]
+    fn test_schema_is_applied_to_request() {
+        let model = model();
+        let mut request = ChatCompletionRequest::default();
+        apply_response_schema(&mut request, &model);
+
+        match &request.response_format {
+            Some(ResponseFormat::JsonSchema { json_schema }) => {
+                assert_eq!(json_schema.schema.as_ref(), model.response_schema.as_ref());
+                assert_eq!(json_schema.strict, Some(true));
+            }
+            f => panic!("Unexpected response format: {f:?}"),
+        }
+        assert!(enforced_schema(&request, &model).is_none());
+    }
+
+    #[test]
+    fn test_request_format_takes_precedence() {
+        let mut request = ChatCompletionRequest {
+            response_format: Some(ResponseFormat::JsonObject),
+            ..Default::default()
+        };
+        apply_response_schema(&mut request, &model());
+
+        assert!(matches!(
+            request.response_format,
+            Some(ResponseFormat::JsonObject)
+        ));
+    }
+
+    #[test]
+    fn test_response_is_validated() {
+        let model = model();
+        let schema = model.response_schema.as_ref().unwrap();
+
+        assert!(validate_response(&response(r#"{"city": "Paris"}"#), schema).is_ok());
+        assert!(matches!(
+            validate_response(&response(r#"{"country": "France"}"#), schema),
+            Err(GatewayApiError::ResponseSchemaViolation(_))
+        ));
+        assert!(matches!(
+            validate_response(&response("Paris"), schema),
+            Err(GatewayApiError::ResponseSchemaViolation(_))
+        ));
+    }
+}
@@ -1,23 +1,36 @@
 use crate::executor::chat_completion::basic_executor::BasicCacheContext;
+use crate::executor::chat_completion::response_cache::cache_contexts;
 use crate::executor::context::ExecutorContext;
 use crate::handler::chat::map_sso_event;
 use crate::routing::RoutingStrategy;
 use crate::usage::InMemoryStorage;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 
 use crate::executor::chat_completion::execute;
+use crate::executor::chat_completion::prompt_limit::check_prompt_size;
 use crate::routing::RouteStrategy;
-use crate::types::gateway::ChatCompletionRequestWithTools;
+use crate::types::gateway::{
+    ChatCompletionChoice, ChatCompletionDelta, ChatCompletionMessage,
+    ChatCompletionRequestWithTools, ChatCompletionResponse, DynamicRouter,
+};
+use crate::types::guardrails::GuardError;
 
+use crate::error::ErrorClass;
 use crate::GatewayError;
-use actix_web::HttpResponse;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, HttpResponseBuilder, ResponseError};
 use bytes::Bytes;
 use either::Either::{Left, Right};
 use futures::StreamExt;
 use futures::TryStreamExt;
+use serde::Serialize;
 
+use crate::executor::chat_completion::stream_wrapper::{
+    with_keepalive, ChatCompletionStream, KeepAliveConfig,
+};
 use crate::executor::chat_completion::StreamCacheContext;
 use thiserror::Error;
 
@@ -31,13 +44,11 @@ use tracing_opentelemetry::OpenTelemetrySpanExt as _;
 use crate::handler::find_model_by_full_name;
 
 use crate::otel::{trace_id_uuid, TraceMap};
-use crate::routing::LlmRouter;
+use crate::routing::{LlmRouter, RouterError, Targets};
 use crate::GatewayApiError;
 
-use crate::events::JsonValue;
 use crate::events::SPAN_REQUEST_ROUTING;
 use tracing::field;
-use valuable::Valuable;
 
 #[derive(Error, Debug)]
 pub enum RoutedExecutorError {
@@ -46,6 +57,198 @@ pub enum RoutedExecutorError {
 
     #[error("Failed serializing merged request with target: {0}")]
     FailedToSerializeMergedRequestResult(serde_json::Error),
+
+    #[error("All router targets failed: {}", describe(attempts))]
+    AllTargetsFailed { attempts: Vec<TargetAttempt> },
+}
+
+/// Error a router target returned
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetAttempt {
+    pub model: String,
+    pub class: ErrorClass,
+    pub error: String,
+    /// Status the error maps to, only used for the status of the response
+    #[serde(skip)]
+    pub status: u16,
+}
+
+fn describe(attempts: &[TargetAttempt]) -> String {
+    attempts
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl std::fmt::Display for TargetAttempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.model, self.error)
+    }
+}
+
+impl RoutedExecutorError {
+    /// Status of the response, which is the one of the last target tried when all failed
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            RoutedExecutorError::AllTargetsFailed { attempts } => attempts
+                .last()
+                .and_then(|a| StatusCode::from_u16(a.status).ok())
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// `request_routing` span of a routed request, recording the outcome of every target tried
+struct RoutingTrace {
+    span: Span,
+    attempts: Vec<serde_json::Value>,
+    failures: Vec<TargetAttempt>,
+}
+
+impl RoutingTrace {
+    fn new(span: Span) -> Self {
+        Self {
+            span,
+            attempts: vec![],
+            failures: vec![],
+        }
+    }
+
+    fn record<T>(&mut self, model: &str, result: &Result<T, GatewayApiError>) {
+        self.attempts.push(match result {
+            Ok(_) => serde_json::json!({"model": model, "status": "success"}),
+            Err(e) => {
+                self.failures.push(TargetAttempt {
+                    model: model.to_string(),
+                    class: e.error_class(),
+                    error: e.to_string(),
+                    status: e.status_code().as_u16(),
+                });
+                serde_json::json!({"model": model, "status": "failed", "error": e.to_string()})
+            }
+        });
+        self.span.record(
+            "attempts",
+            serde_json::to_string(&self.attempts).unwrap_or_default(),
+        );
+    }
+}
+
+/// Error returned once no target is left to try. When several targets were tried, it
+/// lists what each of them returned rather than only the last error.
+fn exhausted(routing: Option<RoutingTrace>, error: GatewayApiError) -> GatewayApiError {
+    match routing {
+        Some(routing) if routing.failures.len() > 1 => RoutedExecutorError::AllTargetsFailed {
+            attempts: routing.failures,
+        }
+        .into(),
+        _ => error,
+    }
+}
+
+/// Whether `error` of a target moves the request on to the next one
+fn fails_over(failover_on: &Option<Vec<ErrorClass>>, error: &GatewayApiError) -> bool {
+    match failover_on {
+        Some(classes) => classes.contains(&error.error_class()),
+        None => true,
+    }
+}
+
+/// Parent span of the model calls made for a target
+fn target_span(routing: &Option<RoutingTrace>) -> Span {
+    routing
+        .as_ref()
+        .map_or_else(Span::current, |routing| routing.span.clone())
+}
+
+/// Output returned in place of a guard error, empty unless the blocked output may be returned
+fn blocked_output(error: &GatewayApiError) -> Option<String> {
+    match error {
+        GatewayApiError::GatewayError(GatewayError::GuardError(GuardError::GuardNotPassed(
+            _,
+            _,
+            blocked_content,
+        ))) => Some(blocked_content.clone().unwrap_or_default()),
+        _ => None,
+    }
+}
+
+/// Completion standing in for one a guard blocked, so OpenAI clients see a `content_filter`
+/// finish reason instead of an error. Carries the blocked output when it may be returned.
+fn blocked_completion(model: &str, error: &GatewayApiError) -> Option<ChatCompletionResponse> {
+    let content = blocked_output(error)?;
+
+    Some(ChatCompletionResponse {
+        id: uuid::Uuid::new_v4().to_string(),
+        object: "chat.completion".to_string(),
+        created: chrono::Utc::now().timestamp(),
+        model: model.to_string(),
+        choices: vec![ChatCompletionChoice {
+            index: 0,
+            message: ChatCompletionMessage::new_text("assistant".to_string(), content),
+            finish_reason: Some("content_filter".to_string()),
+            logprobs: None,
+        }],
+        usage: Default::default(),
+        is_cache_used: None,
+        service_tier: None,
+    })
+}
+
+/// Replaces a guard error with a `content_filter` completion when `blocked_as_content_filter`
+/// is enabled
+fn filter_blocked(
+    executor_context: &ExecutorContext,
+    model: &str,
+    response: Result<ChatCompletionResponse, GatewayApiError>,
+) -> Result<ChatCompletionResponse, GatewayApiError> {
+    match response {
+        Err(e) if executor_context.blocked_as_content_filter => {
+            blocked_completion(model, &e).ok_or(e)
+        }
+        response => response,
+    }
+}
+
+/// Ends a stream a guard blocked with a `content_filter` chunk in place of the guard error,
+/// when `blocked_as_content_filter` is enabled
+fn filter_blocked_stream(
+    executor_context: &ExecutorContext,
+    stream: Result<ChatCompletionStream, GatewayApiError>,
+) -> Result<ChatCompletionStream, GatewayApiError> {
+    if !executor_context.blocked_as_content_filter {
+        return stream;
+    }
+    let stream = match stream {
+        Err(e) if blocked_output(&e).is_none() => return Err(e),
+        stream => stream.unwrap_or_else(|e| Box::pin(futures::stream::iter([Err(e)]))),
+    };
+
+    Ok(Box::pin(stream.scan(false, |blocked, event| {
+        let event = match event {
+            _ if *blocked => return futures::future::ready(None),
+            Err(e) => match blocked_output(&e) {
+                Some(content) => {
+                    *blocked = true;
+                    Ok((
+                        Some(ChatCompletionDelta {
+                            role: Some("assistant".to_string()),
+                            content: Some(content),
+                            tool_calls: None,
+                            index: 0,
+                        }),
+                        None,
+                        Some("content_filter".to_string()),
+                    ))
+                }
+                None => Err(e),
+            },
+            event => event,
+        };
+        futures::future::ready(Some(event))
+    })))
 }
 
 pub struct RoutedExecutor {
@@ -57,15 +260,121 @@ impl RoutedExecutor {
         Self { request }
     }
 
+    /// Rejects an oversized prompt before any target is tried, by the limit of the
+    /// requested model or router
+    fn check_prompt_size(&self, executor_context: &ExecutorContext) -> Result<(), GatewayApiError> {
+        match &executor_context.prompt_limit {
+            Some(limit) => check_prompt_size(
+                limit,
+                &self.request.request.model,
+                &self.request.request.messages,
+            ),
+            None => Ok(()),
+        }
+    }
+
     pub async fn execute(
         &self,
         executor_context: &ExecutorContext,
         traces: &TraceMap,
         memory_storage: Option<Arc<Mutex<InMemoryStorage>>>,
     ) -> Result<HttpResponse, GatewayApiError> {
-        let span = Span::current();
+        self.check_prompt_size(executor_context)?;
+        Self::run_targets(
+            self.request.clone(),
+            executor_context,
+            &memory_storage,
+            |request, router_span| async move {
+                Self::execute_request(&request, executor_context, traces, router_span).await
+            },
+        )
+        .await
+    }
+
+    pub async fn execute_with_tags(
+        &self,
+        executor_context: &ExecutorContext,
+        traces: &TraceMap,
+        memory_storage: Option<Arc<Mutex<InMemoryStorage>>>,
+        tags: HashMap<String, String>,
+    ) -> Result<HttpResponse, GatewayApiError> {
+        self.check_prompt_size(executor_context)?;
+        Self::run_targets(
+            self.request.clone(),
+            executor_context,
+            &memory_storage,
+            |request, router_span| {
+                // 传递 tags 到 execute_request
+                let tags = tags.clone();
+                async move {
+                    Self::execute_request_with_tags(
+                        &request,
+                        executor_context,
+                        traces,
+                        tags,
+                        router_span,
+                    )
+                    .await
+                }
+            },
+        )
+        .await
+    }
 
-        let mut targets = vec![(self.request.clone(), None)];
+    /// Runs the request without streaming and returns the completion, trying router
+    /// targets in order like [`RoutedExecutor::execute`]. Used for calls the gateway
+    /// makes on its own behalf, e.g. LLM judge guards.
+    pub async fn complete(
+        &self,
+        executor_context: &ExecutorContext,
+        memory_storage: Option<Arc<Mutex<InMemoryStorage>>>,
+    ) -> Result<ChatCompletionResponse, GatewayApiError> {
+        let mut request = self.request.clone();
+        request.request.stream = Some(false);
+        Self::run_targets(
+            request,
+            executor_context,
+            &memory_storage,
+            |request, router_span| async move {
+                match execute(
+                    &request,
+                    executor_context,
+                    router_span,
+                    StreamCacheContext::default(),
+                    BasicCacheContext::default(),
+                )
+                .instrument(Span::current())
+                .await
+                {
+                    Ok(Right(response)) => response,
+                    Ok(Left(_)) => Err(GatewayApiError::CustomError(
+                        "Unexpected streaming response".to_string(),
+                    )),
+                    Err(e) => Err(e),
+                }
+            },
+        )
+        .await
+    }
+
+    /// Runs `request` with `run`, or when it names a router, each target the router resolves
+    /// to in order until one succeeds. Errors outside the router's `failover_on` classes end
+    /// the request right away.
+    async fn run_targets<T, F, Fut>(
+        request: ChatCompletionRequestWithTools<RoutingStrategy>,
+        executor_context: &ExecutorContext,
+        memory_storage: &Option<Arc<Mutex<InMemoryStorage>>>,
+        run: F,
+    ) -> Result<T, GatewayApiError>
+    where
+        F: Fn(ChatCompletionRequestWithTools<RoutingStrategy>, Span) -> Fut,
+        Fut: Future<Output = Result<T, GatewayApiError>>,
+    {
+        let requested_model = request.request.model.clone();
+        let mut targets = vec![(request, None)];
+        let mut routing = None;
+        let mut failover_on = None;
+        let mut last_error = None;
 
         while let Some((mut request, target)) = targets.pop() {
             if let Some(t) = target {
@@ -74,170 +383,121 @@ impl RoutedExecutor {
             }
 
             if let Some(router) = &request.router {
-                let router_name = request
-                    .request
-                    .model
-                    .split('/')
-                    .next_back()
-                    .expect("Model name should not be empty")
-                    .to_string();
-                span.record("router_name", &router_name);
-
-                let span = tracing::info_span!(
-                    target: "langdb::user_tracing::request_routing",
-                    SPAN_REQUEST_ROUTING,
-                    router_name = router_name,
-                    before = JsonValue(&serde_json::to_value(&request.request)?).as_value(),
-                    after = field::Empty
-                );
-
-                let llm_router = LlmRouter {
-                    name: router.name.clone().unwrap_or("dynamic".to_string()),
-                    strategy: router.strategy.clone(),
-                    targets: router.targets.clone(),
-                    metrics_duration: None,
-                };
-
-                let metrics = match &memory_storage {
-                    Some(storage) => {
-                        let guard = storage.lock().await;
-                        guard.get_all_counters().await
-                    }
-                    None => BTreeMap::new(),
-                };
-
-                let executor_result = llm_router
-                    .route(
-                        request.request.clone(),
-                        &executor_context.provided_models,
-                        executor_context.headers.clone(),
-                        metrics,
-                    )
-                    .instrument(span.clone())
-                    .await;
-
-                match executor_result {
-                    Ok(executor_result) => {
-                        for t in executor_result.iter().rev() {
-                            targets.push((request.clone(), Some(t.clone())));
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Router error: {}, route ignored", e);
-                    }
+                let (routed, span) =
+                    Self::route(&request, router, executor_context, memory_storage).await?;
+                routing = Some(RoutingTrace::new(span));
+                failover_on = router.failover_on.clone();
+                for t in routed.iter().rev() {
+                    targets.push((request.clone(), Some(t.clone())));
                 }
             } else {
-                let result = Self::execute_request(&request, executor_context, traces).await;
+                let model = request.request.model.clone();
+                let result = executor_context
+                    .body_recording
+                    .scope(run(request, target_span(&routing)))
+                    .await;
+                if let Some(routing) = &mut routing {
+                    routing.record(&model, &result);
+                }
 
                 match result {
                     Ok(response) => return Ok(response),
+                    Err(err) if targets.is_empty() => return Err(exhausted(routing, err)),
+                    Err(err) if !fails_over(&failover_on, &err) => return Err(err),
                     Err(err) => {
-                        if targets.is_empty() {
-                            return Err(err);
-                        } else {
-                            tracing::warn!(
-                                "Error executing request: {:?}, so moving to next target",
-                                err
-                            );
-                        }
+                        tracing::warn!(
+                            "Error executing request: {:?}, so moving to next target",
+                            err
+                        );
+                        last_error = Some(err);
                     }
                 }
             }
         }
 
-        unreachable!()
+        // Only reached when a router resolved no targets
+        match last_error {
+            Some(err) => Err(exhausted(routing, err)),
+            None => Err(RouterError::NoTargets(requested_model).into()),
+        }
     }
 
-    pub async fn execute_with_tags(
-        &self,
+    /// Resolves the targets `router` selects for the request, along with the routing span
+    /// the attempts of those targets are recorded on
+    async fn route(
+        request: &ChatCompletionRequestWithTools<RoutingStrategy>,
+        router: &DynamicRouter<RoutingStrategy>,
         executor_context: &ExecutorContext,
-        traces: &TraceMap,
-        memory_storage: Option<Arc<Mutex<InMemoryStorage>>>,
-        tags: HashMap<String, String>,
-    ) -> Result<HttpResponse, GatewayApiError> {
-        let span = Span::current();
-        let mut targets = vec![(self.request.clone(), None)];
-        while let Some((mut request, target)) = targets.pop() {
-            if let Some(t) = target {
-                request.router = None;
-                request = Self::merge_request_with_target(&request, &t)?;
+        memory_storage: &Option<Arc<Mutex<InMemoryStorage>>>,
+    ) -> Result<(Targets, Span), GatewayApiError> {
+        let router_name = request
+            .request
+            .model
+            .split('/')
+            .next_back()
+            .expect("Model name should not be empty")
+            .to_string();
+        Span::current().record("router_name", &router_name);
+
+        let before = executor_context
+            .body_recording
+            .apply(&serde_json::to_string(&request.request)?);
+        let span = tracing::info_span!(
+            target: "langdb::user_tracing::request_routing",
+            SPAN_REQUEST_ROUTING,
+            router_name = router_name,
+            before = before.as_deref(),
+            after = field::Empty,
+            attempts = field::Empty
+        );
+
+        let llm_router = LlmRouter {
+            name: router.name.clone().unwrap_or("dynamic".to_string()),
+            strategy: router.strategy.clone(),
+            targets: router.targets.clone(),
+            metrics_duration: None,
+        };
+
+        let metrics = match memory_storage {
+            Some(storage) => {
+                let guard = storage.lock().await;
+                guard.get_all_counters().await
             }
-            if let Some(router) = &request.router {
-                let router_name = request
-                    .request
-                    .model
-                    .split('/')
-                    .next_back()
-                    .expect("Model name should not be empty")
-                    .to_string();
-                span.record("router_name", &router_name);
-                let span = tracing::info_span!(
-                    target: "langdb::user_tracing::request_routing",
-                    SPAN_REQUEST_ROUTING,
-                    router_name = router_name,
-                    before = JsonValue(&serde_json::to_value(&request.request)?).as_value(),
-                    after = field::Empty
-                );
-                let llm_router = LlmRouter {
-                    name: router.name.clone().unwrap_or("dynamic".to_string()),
-                    strategy: router.strategy.clone(),
-                    targets: router.targets.clone(),
-                    metrics_duration: None,
-                };
-                let metrics = match &memory_storage {
-                    Some(storage) => {
-                        let guard = storage.lock().await;
-                        guard.get_all_counters().await
-                    }
-                    None => BTreeMap::new(),
-                };
-                let executor_result = llm_router
-                    .route(
-                        request.request.clone(),
-                        &executor_context.provided_models,
-                        executor_context.headers.clone(),
-                        metrics,
-                    )
-                    .instrument(span.clone())
-                    .await;
-                match executor_result {
-                    Ok(executor_result) => {
-                        for t in executor_result.iter().rev() {
-                            targets.push((request.clone(), Some(t.clone())));
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Router error: {}, route ignored", e);
-                    }
-                }
-            } else {
-                // 传递 tags 到 execute_request
-                let result = Self::execute_request_with_tags(&request, executor_context, traces, tags.clone()).await;
-                match result {
-                    Ok(response) => return Ok(response),
-                    Err(err) => {
-                        if targets.is_empty() {
-                            return Err(err);
-                        } else {
-                            tracing::warn!(
-                                "Error executing request: {:?}, so moving to next target",
-                                err
-                            );
-                        }
-                    }
-                }
+            None => BTreeMap::new(),
+        };
+
+        let executor_result = llm_router
+            .route(
+                request.request.clone(),
+                &executor_context.provided_models,
+                executor_context.headers.clone(),
+                metrics,
+            )
+            .instrument(span.clone())
+            .await;
+
+        match executor_result {
+            Ok(targets) => Ok((targets, span)),
+            Err(e) => {
+                tracing::error!("Router error: {}, route ignored", e);
+                Ok((vec![], span))
             }
         }
-        unreachable!()
     }
 
     async fn execute_request(
         request: &ChatCompletionRequestWithTools<RoutingStrategy>,
         executor_context: &ExecutorContext,
         traces: &TraceMap,
+        router_span: Span,
     ) -> Result<HttpResponse, GatewayApiError> {
         let span = tracing::Span::current();
-        span.record("request", &serde_json::to_string(&request)?);
+        span.record(
+            "request",
+            executor_context
+                .body_recording
+                .apply(&serde_json::to_string(&request)?),
+        );
         let trace_id = span.context().span().span_context().trace_id();
         traces
             .entry(trace_id)
@@ -252,12 +512,14 @@ impl RoutedExecutor {
             llm_model.inference_provider.provider,
             llm_model.inference_provider.endpoint
         );
+        let (cache_state, stream_cache_context, basic_cache_context) =
+            cache_contexts(request, executor_context);
         let response = execute(
             request,
             executor_context,
-            span.clone(),
-            StreamCacheContext::default(),
-            BasicCacheContext::default(),
+            router_span,
+            stream_cache_context,
+            basic_cache_context,
         )
         .instrument(span.clone())
         .await?;
@@ -270,43 +532,25 @@ impl RoutedExecutor {
                 "X-Provider-Name",
                 llm_model.inference_provider.provider.to_string(),
             ));
+        if let Some(cache_state) = cache_state {
+            builder.insert_header(("X-Cache", cache_state.to_string()));
+        }
 
         match response {
             Left(result_stream) => {
-                let stream = result_stream?.map_err(|e| {
-                    GatewayApiError::GatewayError(GatewayError::CustomError(e.to_string()))
-                });
-
-                // Pin the stream to heap
-                let mut stream = Box::pin(stream);
-
-                // Check first element for error
-                let first = match stream.as_mut().next().await {
-                    Some(Ok(delta)) => delta,
-                    Some(Err(e)) => {
-                        return Err(e);
-                    }
-                    None => {
-                        return Err(GatewayApiError::GatewayError(GatewayError::CustomError(
-                            "Empty response from model".to_string(),
-                        )));
-                    }
-                };
-
-                let model_name = model_name.clone();
-                let result = futures::stream::once(async { Ok(first) })
-                    .chain(stream)
-                    .then(move |delta| {
-                        let model_name = model_name.clone();
-                        async move { map_sso_event(delta, model_name) }
-                    })
-                    .chain(futures::stream::once(async {
-                        Ok::<_, GatewayApiError>(Bytes::from("data: [DONE]\n\n"))
-                    }));
-
-                Ok(builder.content_type("text/event-stream").streaming(result))
+                Self::stream_response(
+                    builder,
+                    filter_blocked_stream(executor_context, result_stream)?,
+                    model_name,
+                    executor_context.keep_alive.as_ref(),
+                )
+                .await
             }
-            Right(completions_response) => Ok(builder.json(completions_response?)),
+            Right(completions_response) => Ok(builder.json(filter_blocked(
+                executor_context,
+                &model_name,
+                completions_response,
+            )?)),
         }
     }
 
@@ -315,9 +559,15 @@ impl RoutedExecutor {
         executor_context: &ExecutorContext,
         traces: &TraceMap,
         tags: HashMap<String, String>,
+        router_span: Span,
     ) -> Result<HttpResponse, GatewayApiError> {
         let span = tracing::Span::current();
-        span.record("request", &serde_json::to_string(&request)?);
+        span.record(
+            "request",
+            executor_context
+                .body_recording
+                .apply(&serde_json::to_string(&request)?),
+        );
         let trace_id = span.context().span().span_context().trace_id();
         traces
             .entry(trace_id)
@@ -333,12 +583,14 @@ impl RoutedExecutor {
             llm_model.inference_provider.endpoint
         );
         // 传递 tags 给 execute_with_tags
+        let (cache_state, stream_cache_context, basic_cache_context) =
+            cache_contexts(request, executor_context);
         let response = crate::executor::chat_completion::execute_with_tags(
             request,
             executor_context,
-            span.clone(),
-            StreamCacheContext::default(),
-            BasicCacheContext::default(),
+            router_span,
+            stream_cache_context,
+            basic_cache_context,
             Some(tags),
         )
         .instrument(span.clone())
@@ -352,43 +604,84 @@ impl RoutedExecutor {
                 "X-Provider-Name",
                 llm_model.inference_provider.provider.to_string(),
             ));
+        if let Some(cache_state) = cache_state {
+            builder.insert_header(("X-Cache", cache_state.to_string()));
+        }
 
         match response {
             Left(result_stream) => {
-                let stream = result_stream?.map_err(|e| {
-                    GatewayApiError::GatewayError(GatewayError::CustomError(e.to_string()))
-                });
+                Self::stream_response(
+                    builder,
+                    filter_blocked_stream(executor_context, result_stream)?,
+                    model_name,
+                    executor_context.keep_alive.as_ref(),
+                )
+                .await
+            }
+            Right(completions_response) => Ok(builder.json(filter_blocked(
+                executor_context,
+                &model_name,
+                completions_response,
+            )?)),
+        }
+    }
 
-                // Pin the stream to heap
-                let mut stream = Box::pin(stream);
+    async fn stream_response(
+        builder: &mut HttpResponseBuilder,
+        result_stream: ChatCompletionStream,
+        model_name: String,
+        keep_alive: Option<&KeepAliveConfig>,
+    ) -> Result<HttpResponse, GatewayApiError> {
+        let stream = result_stream
+            .map_err(|e| GatewayApiError::GatewayError(GatewayError::CustomError(e.to_string())));
 
-                // Check first element for error
-                let first = match stream.as_mut().next().await {
-                    Some(Ok(delta)) => delta,
-                    Some(Err(e)) => {
-                        return Err(e);
-                    }
-                    None => {
-                        return Err(GatewayApiError::GatewayError(GatewayError::CustomError(
-                            "Empty response from model".to_string(),
-                        )));
+        // Pin the stream to heap
+        let mut stream = Box::pin(stream);
+
+        // With keep-alive enabled the response is started right away so keep-alive comments
+        // cover the wait for the first token, a first error is then sent as an SSE error event
+        let first = match keep_alive {
+            Some(_) => None,
+            // Check first element for error
+            None => match stream.as_mut().next().await {
+                Some(Ok(delta)) => Some(delta),
+                Some(Err(e)) => {
+                    return Err(e);
+                }
+                None => {
+                    return Err(GatewayApiError::GatewayError(GatewayError::CustomError(
+                        "Empty response from model".to_string(),
+                    )));
+                }
+            },
+        };
+
+        // An error ends the stream with an error event in place of `[DONE]`, so clients can
+        // tell a failed stream from a completed one
+        let result = futures::stream::iter(first.map(Ok))
+            .chain(stream)
+            .map(Some)
+            .chain(futures::stream::once(async { None }))
+            .scan(false, move |failed, delta| {
+                let frame = match delta {
+                    _ if *failed => None,
+                    Some(delta) => {
+                        *failed = delta.is_err();
+                        Some(map_sso_event(delta, model_name.clone()))
                     }
+                    None => Some(Ok(Bytes::from("data: [DONE]\n\n"))),
                 };
+                futures::future::ready(frame)
+            });
 
-                let model_name = model_name.clone();
-                let result = futures::stream::once(async { Ok(first) })
-                    .chain(stream)
-                    .then(move |delta| {
-                        let model_name = model_name.clone();
-                        async move { map_sso_event(delta, model_name) }
-                    })
-                    .chain(futures::stream::once(async {
-                        Ok::<_, GatewayApiError>(Bytes::from("data: [DONE]\n\n"))
-                    }));
-
-                Ok(builder.content_type("text/event-stream").streaming(result))
-            },
-            Right(completions_response) => Ok(builder.json(completions_response?)),
+        let builder = builder.content_type("text/event-stream");
+        match keep_alive {
+            Some(keep_alive) => Ok(builder.streaming(with_keepalive(
+                result,
+                keep_alive.interval(),
+                keep_alive.disable_after_data,
+            ))),
+            None => Ok(builder.streaming(result)),
         }
     }
 
@@ -412,3 +705,629 @@ impl RoutedExecutor {
             .map_err(RoutedExecutorError::FailedToDeserializeRequestResult)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::SPAN_MODEL_CALL;
+    use crate::handler::{AvailableModels, CallbackHandlerFn};
+    use crate::models::{InferenceProvider, ModelMetadata};
+    use crate::test_utils::{FreeModels, NoGuards, SpanFields};
+    use crate::types::credentials::{ApiKeyCredentials, Credentials};
+    use crate::types::gateway::{ChatCompletionMessage, ChatCompletionRequest};
+    use crate::types::guardrails::service::GuardrailsEvaluator;
+    use crate::types::guardrails::{GuardResult, GuardStage};
+    use crate::types::provider::InferenceModelProvider;
+    use actix_web::{web, App, HttpMessage, HttpServer};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    async fn completion() -> HttpResponse {
+        HttpResponse::Ok().json(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Paris"},
+                "finish_reason": "stop",
+                "logprobs": null
+            }],
+            "usage": {"prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10}
+        }))
+    }
+
+    fn openai_model(name: &str, endpoint: String) -> ModelMetadata {
+        ModelMetadata {
+            model: name.to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: InferenceProvider {
+                provider: InferenceModelProvider::OpenAI,
+                model_name: name.to_string(),
+                endpoint: Some(endpoint),
+            },
+            ..Default::default()
+        }
+    }
+
+    async fn provider_error(status: u16, r#type: &str) -> HttpResponse {
+        HttpResponse::build(actix_web::http::StatusCode::from_u16(status).unwrap()).json(
+            serde_json::json!({
+                "error": {"message": "failed", "type": r#type, "param": null, "code": null}
+            }),
+        )
+    }
+
+    /// Starts an OpenAI compatible provider and returns its address. Besides completions
+    /// under `/v1`, it answers 400 under `/bad/v1` and 503 under `/down/v1`.
+    fn provider() -> std::net::SocketAddr {
+        let server = HttpServer::new(|| {
+            App::new()
+                .route("/v1/chat/completions", web::post().to(completion))
+                .route(
+                    "/bad/v1/chat/completions",
+                    web::post().to(|| provider_error(400, "invalid_request_error")),
+                )
+                .route(
+                    "/down/v1/chat/completions",
+                    web::post().to(|| provider_error(503, "server_error")),
+                )
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        addr
+    }
+
+    fn executor_context(models: Vec<ModelMetadata>) -> ExecutorContext {
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        req.extensions_mut()
+            .insert(Credentials::ApiKey(ApiKeyCredentials {
+                api_key: "test-key".to_string(),
+            }));
+        ExecutorContext::new(
+            CallbackHandlerFn(None),
+            Arc::new(Box::new(FreeModels)),
+            AvailableModels(models),
+            &req,
+            Arc::new(Box::new(NoGuards)),
+        )
+        .unwrap()
+    }
+
+    fn fallback_request(
+        targets: &[&str],
+        failover_on: Option<Vec<ErrorClass>>,
+    ) -> ChatCompletionRequestWithTools<RoutingStrategy> {
+        ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: "router/fallback".to_string(),
+                messages: vec![ChatCompletionMessage::new_text(
+                    "user".to_string(),
+                    "What is the capital of France?".to_string(),
+                )],
+                ..Default::default()
+            },
+            router: Some(DynamicRouter {
+                strategy: RoutingStrategy::Fallback,
+                targets: targets
+                    .iter()
+                    .map(|model| HashMap::from([("model".to_string(), serde_json::json!(model))]))
+                    .collect(),
+                name: Some("fallback".to_string()),
+                failover_on,
+            }),
+            max_retries: Some(1),
+            ..Default::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_routing_span_records_fallback_attempts() {
+        let spans = SpanFields::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+        let addr = provider();
+        // Nothing listens on a released port, so both of these targets fail right away
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let unreachable = format!("http://{}/v1", closed.local_addr().unwrap());
+        drop(closed);
+        let executor_context = executor_context(vec![
+            openai_model("down-1", unreachable.clone()),
+            openai_model("down-2", unreachable),
+            openai_model("gpt-4o-mini", format!("http://{addr}/v1")),
+        ]);
+        let request = fallback_request(
+            &["openai/down-1", "openai/down-2", "openai/gpt-4o-mini"],
+            None,
+        );
+
+        RoutedExecutor::new(request)
+            .complete(&executor_context, None)
+            .await
+            .unwrap();
+
+        let spans = spans.named(SPAN_REQUEST_ROUTING);
+        assert_eq!(spans.len(), 1);
+        let routing = &spans[0];
+        let attempts: Vec<serde_json::Value> = serde_json::from_str(&routing["attempts"]).unwrap();
+        let outcomes: Vec<(&str, &str)> = attempts
+            .iter()
+            .map(|a| (a["model"].as_str().unwrap(), a["status"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("openai/down-1", "failed"),
+                ("openai/down-2", "failed"),
+                ("openai/gpt-4o-mini", "success"),
+            ]
+        );
+        assert!(attempts[0]["error"].is_string());
+    }
+
+    #[actix_web::test]
+    async fn test_router_without_targets_is_an_error() {
+        let executor_context = executor_context(vec![]);
+        let result = RoutedExecutor::new(fallback_request(&[], None))
+            .complete(&executor_context, None)
+            .await;
+        match result {
+            Err(e @ GatewayApiError::RouteError(RouterError::NoTargets(_))) => {
+                assert_eq!(e.status_code(), StatusCode::BAD_REQUEST);
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_metadata_is_recorded_on_model_call() {
+        let spans = SpanFields::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+        let addr = provider();
+        let executor_context = executor_context(vec![openai_model(
+            "gpt-4o-mini",
+            format!("http://{addr}/v1"),
+        )]);
+        let mut request = fallback_request(&["openai/gpt-4o-mini"], None);
+        request.request.metadata = Some(HashMap::from([
+            ("conversation_id".to_string(), "c-42".to_string()),
+            ("feature".to_string(), "search".to_string()),
+        ]));
+
+        RoutedExecutor::new(request)
+            .complete(&executor_context, None)
+            .await
+            .unwrap();
+
+        let spans = spans.named(SPAN_MODEL_CALL);
+        assert_eq!(spans.len(), 1);
+        let tags = &spans[0]["tags"];
+        assert!(tags.contains("conversation_id") && tags.contains("c-42"));
+        assert!(tags.contains("feature") && tags.contains("search"));
+    }
+
+    #[actix_web::test]
+    async fn test_failover_on_error_classes() {
+        let addr = provider();
+        let executor_context = executor_context(vec![
+            openai_model("bad", format!("http://{addr}/bad/v1")),
+            openai_model("down", format!("http://{addr}/down/v1")),
+            openai_model("gpt-4o-mini", format!("http://{addr}/v1")),
+        ]);
+        let failover_on = Some(vec![
+            ErrorClass::ServerError,
+            ErrorClass::Timeout,
+            ErrorClass::RateLimit,
+        ]);
+
+        // A rejected request would be rejected by every target
+        let request = fallback_request(&["openai/bad", "openai/gpt-4o-mini"], failover_on.clone());
+        let error = RoutedExecutor::new(request)
+            .complete(&executor_context, None)
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_class(), ErrorClass::ClientError);
+
+        let request = fallback_request(&["openai/down", "openai/gpt-4o-mini"], failover_on);
+        RoutedExecutor::new(request)
+            .complete(&executor_context, None)
+            .await
+            .unwrap();
+
+        // Without failover_on every error moves on to the next target
+        let request = fallback_request(&["openai/bad", "openai/gpt-4o-mini"], None);
+        RoutedExecutor::new(request)
+            .complete(&executor_context, None)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_anthropic_server_errors_fail_over() {
+        // clust can't be pointed at a local server, the errors are built from its messages
+        let failover_on = Some(vec![ErrorClass::ServerError, ErrorClass::Timeout]);
+        let overloaded = crate::model::anthropic::api_err(
+            r#"API error: 529 <unknown status code> {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        );
+        assert!(fails_over(&failover_on, &overloaded.into()));
+
+        let rejected = crate::model::anthropic::api_err(
+            r#"API error: 400 Bad Request {"type":"error","error":{"type":"invalid_request_error","message":"max_tokens: Field required"}}"#,
+        );
+        assert!(!fails_over(&failover_on, &rejected.into()));
+    }
+
+    #[actix_web::test]
+    async fn test_all_targets_failed_lists_attempts() {
+        let addr = provider();
+        let executor_context = executor_context(vec![
+            openai_model("bad", format!("http://{addr}/bad/v1")),
+            openai_model("down", format!("http://{addr}/down/v1")),
+        ]);
+        let request = fallback_request(&["openai/bad", "openai/down"], None);
+
+        let error = RoutedExecutor::new(request)
+            .complete(&executor_context, None)
+            .await
+            .unwrap_err();
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let attempts: Vec<(&str, &str)> = body["attempts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|a| (a["model"].as_str().unwrap(), a["class"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            attempts,
+            vec![
+                ("openai/bad", "client_error"),
+                ("openai/down", "server_error")
+            ]
+        );
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("All router targets failed: openai/bad: "));
+    }
+
+    /// Stream of a model that sends one chunk and then fails
+    fn failing_stream() -> ChatCompletionStream {
+        let delta = crate::types::gateway::ChatCompletionDelta {
+            role: Some("assistant".to_string()),
+            content: Some("The capital".to_string()),
+            tool_calls: None,
+            index: 0,
+        };
+        crate::executor::chat_completion::stream_wrapper::wrap_stream(futures::stream::iter(vec![
+            Ok((Some(delta.clone()), None, None)),
+            Err(GatewayApiError::CustomError(
+                "upstream connection reset".to_string(),
+            )),
+            Ok((Some(delta), None, Some("stop".to_string()))),
+        ]))
+    }
+
+    #[actix_web::test]
+    async fn test_stream_error_ends_with_error_event() {
+        let response = RoutedExecutor::stream_response(
+            &mut HttpResponse::Ok(),
+            failing_stream(),
+            "gpt-4o".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let frames = body
+            .split("\n\n")
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| frame.strip_prefix("data: ").unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), 2);
+        let chunk: serde_json::Value = serde_json::from_str(frames[0]).unwrap();
+        assert_eq!(chunk["choices"][0]["delta"]["content"], "The capital");
+
+        let error: serde_json::Value = serde_json::from_str(frames[1]).unwrap();
+        assert!(error["error"]["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("upstream connection reset"));
+        assert_eq!(error["error"]["type"], "server_error");
+        assert!(error["error"]["param"].is_null());
+        assert!(error["error"]["code"].is_null());
+        assert!(!body.contains("[DONE]"));
+    }
+
+    /// Frames streamed for a model whose first event takes longer than the keep-alive interval
+    async fn slow_first_event_frames(
+        event: Result<crate::handler::chat::SSOChatEvent, GatewayApiError>,
+    ) -> Vec<String> {
+        let stream = crate::executor::chat_completion::stream_wrapper::wrap_stream(
+            futures::stream::once(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+                event
+            }),
+        );
+        let keep_alive = KeepAliveConfig {
+            interval_secs: 1,
+            disable_after_data: false,
+        };
+        let response = RoutedExecutor::stream_response(
+            &mut HttpResponse::Ok(),
+            stream,
+            "gpt-4o".to_string(),
+            Some(&keep_alive),
+        )
+        .await
+        .unwrap();
+
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .split_inclusive("\n\n")
+            .map(|frame| frame.to_string())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_keepalive_is_sent_before_first_chunk() {
+        use crate::executor::chat_completion::stream_wrapper::KEEP_ALIVE_FRAME;
+
+        let delta = crate::types::gateway::ChatCompletionDelta {
+            role: Some("assistant".to_string()),
+            content: Some("Paris".to_string()),
+            tool_calls: None,
+            index: 0,
+        };
+        let frames = slow_first_event_frames(Ok((Some(delta), None, None))).await;
+        assert_eq!(frames[0], KEEP_ALIVE_FRAME);
+        assert!(frames[1].contains("\"content\":\"Paris\""));
+        assert_eq!(frames.last().unwrap(), "data: [DONE]\n\n");
+
+        let frames = slow_first_event_frames(Err(GatewayApiError::CustomError(
+            "upstream connection reset".to_string(),
+        )))
+        .await;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], KEEP_ALIVE_FRAME);
+        let error: serde_json::Value =
+            serde_json::from_str(frames[1].trim_end().strip_prefix("data: ").unwrap()).unwrap();
+        assert!(error["error"]["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("upstream connection reset"));
+    }
+
+    #[actix_web::test]
+    async fn test_oversized_prompt_is_rejected_before_dispatch() {
+        use crate::executor::chat_completion::prompt_limit::{PromptLimit, PromptSizeLimit};
+
+        let addr = provider();
+        // Nothing listens on a released port, a dispatched request would fail to connect
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let unreachable = format!("http://{}/v1", closed.local_addr().unwrap());
+        drop(closed);
+        let mut executor_context = executor_context(vec![
+            openai_model("gpt-4o-mini", format!("http://{addr}/v1")),
+            openai_model("gpt-4o", unreachable),
+        ]);
+        executor_context.prompt_limit = Some(PromptLimit {
+            default: PromptSizeLimit {
+                max_characters: Some(100),
+                max_tokens: None,
+            },
+            models: HashMap::new(),
+        });
+        let traces = TraceMap::new();
+        let request = |model: &str, content: String| ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: model.to_string(),
+                messages: vec![ChatCompletionMessage::new_text("user".to_string(), content)],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let error = RoutedExecutor::new(request("openai/gpt-4o", "a".repeat(101)))
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            GatewayApiError::PromptTooLarge {
+                size: 101,
+                limit: 100,
+                unit: "characters"
+            }
+        ));
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+
+        let response = RoutedExecutor::new(request(
+            "openai/gpt-4o-mini",
+            "What is the capital of France?".to_string(),
+        ))
+        .execute(&executor_context, &traces, None)
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Evaluator failing every guard of a stage
+    struct Block(GuardStage);
+
+    #[async_trait::async_trait]
+    impl GuardrailsEvaluator for Block {
+        async fn evaluate(
+            &self,
+            _messages: &[ChatCompletionMessage],
+            _guard_id: &str,
+            _executor_context: &ExecutorContext,
+            _parameters: Option<&serde_json::Value>,
+            guard_stage: &GuardStage,
+        ) -> Result<GuardResult, String> {
+            Ok(GuardResult::Boolean {
+                passed: guard_stage != &self.0,
+                confidence: None,
+            })
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_blocked_completion_modes() {
+        use crate::types::gateway::{Extra, GuardOrName};
+
+        let addr = provider();
+        let mut executor_context = executor_context(vec![openai_model(
+            "gpt-4o-mini",
+            format!("http://{addr}/v1"),
+        )]);
+        executor_context.evaluator_service = Arc::new(Box::new(Block(GuardStage::Output)));
+        let traces = TraceMap::new();
+        let executor = RoutedExecutor::new(ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: "openai/gpt-4o-mini".to_string(),
+                messages: vec![ChatCompletionMessage::new_text(
+                    "user".to_string(),
+                    "What is the capital of France?".to_string(),
+                )],
+                ..Default::default()
+            },
+            extra: Some(Extra {
+                user: None,
+                guards: vec![GuardOrName::GuardId("no-geography".to_string())],
+                cache: None,
+                variables: None,
+                repair_json: false,
+                validate_stream: false,
+            }),
+            ..Default::default()
+        });
+
+        // By default the client gets the guard error
+        let error = executor
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            GatewayApiError::GatewayError(GatewayError::GuardError(GuardError::GuardNotPassed(
+                guard_id,
+                ..
+            ))) if guard_id == "no-geography"
+        ));
+
+        executor_context.blocked_as_content_filter = true;
+        let completion = |response: HttpResponse| async move {
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = actix_web::body::to_bytes(response.into_body())
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let body = completion(
+            executor
+                .execute(&executor_context, &traces, None)
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(body["object"], "chat.completion");
+        assert_eq!(body["model"], "openai/gpt-4o-mini");
+        assert_eq!(body["choices"][0]["finish_reason"], "content_filter");
+        assert_eq!(body["choices"][0]["message"]["role"], "assistant");
+        assert_eq!(body["choices"][0]["message"]["content"], "");
+
+        // The blocked output is only included when it may be returned
+        executor_context.return_blocked_output = true;
+        let body = completion(
+            executor
+                .execute(&executor_context, &traces, None)
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(body["choices"][0]["finish_reason"], "content_filter");
+        assert_eq!(body["choices"][0]["message"]["content"], "Paris");
+    }
+
+    #[actix_web::test]
+    async fn test_blocked_stream_ends_with_content_filter() {
+        use crate::types::gateway::{Extra, GuardOrName};
+
+        let addr = provider();
+        let mut executor_context = executor_context(vec![openai_model(
+            "gpt-4o-mini",
+            format!("http://{addr}/v1"),
+        )]);
+        executor_context.evaluator_service = Arc::new(Box::new(Block(GuardStage::Input)));
+        let traces = TraceMap::new();
+        let executor = RoutedExecutor::new(ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: "openai/gpt-4o-mini".to_string(),
+                messages: vec![ChatCompletionMessage::new_text(
+                    "user".to_string(),
+                    "What is the capital of France?".to_string(),
+                )],
+                stream: Some(true),
+                ..Default::default()
+            },
+            extra: Some(Extra {
+                user: None,
+                guards: vec![GuardOrName::GuardId("no-geography".to_string())],
+                cache: None,
+                variables: None,
+                repair_json: false,
+                validate_stream: false,
+            }),
+            ..Default::default()
+        });
+
+        assert!(executor
+            .execute(&executor_context, &traces, None)
+            .await
+            .is_err());
+
+        executor_context.blocked_as_content_filter = true;
+        let response = executor
+            .execute(&executor_context, &traces, None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let frames = body
+            .split("\n\n")
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| frame.strip_prefix("data: ").unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), 2);
+        let chunk: serde_json::Value = serde_json::from_str(frames[0]).unwrap();
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+        assert_eq!(chunk["choices"][0]["finish_reason"], "content_filter");
+        assert_eq!(chunk["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(frames[1], "[DONE]");
+    }
+}
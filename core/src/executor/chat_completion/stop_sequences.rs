@@ -0,0 +1,110 @@
+use crate::models::ModelMetadata;
+use crate::types::gateway::ChatCompletionRequest;
+
+/// Adds the stop sequences configured for `model` to the request's `stop`. Duplicates are
+/// dropped and the result is capped to what the provider accepts, configured stops first.
+pub fn apply_stop_sequences(request: &mut ChatCompletionRequest, model: &ModelMetadata) {
+    if model.stop_sequences.is_empty() && request.stop.is_none() {
+        return;
+    }
+
+    let mut stop: Vec<String> = vec![];
+    for sequence in model
+        .stop_sequences
+        .iter()
+        .chain(request.stop.iter().flatten())
+    {
+        if !stop.contains(sequence) {
+            stop.push(sequence.clone());
+        }
+    }
+
+    if let Some(max) = model.inference_provider.provider.max_stop_sequences() {
+        if stop.len() > max {
+            tracing::warn!(
+                "Request has {} stop sequences, only the first {max} are sent",
+                stop.len()
+            );
+            stop.truncate(max);
+        }
+    }
+
+    request.stop = (!stop.is_empty()).then_some(stop);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::InferenceProvider;
+    use crate::types::provider::InferenceModelProvider;
+
+    fn model(provider: InferenceModelProvider, stop_sequences: &[&str]) -> ModelMetadata {
+        ModelMetadata {
+            model: "llama-3-8b".to_string(),
+            inference_provider: InferenceProvider {
+                provider,
+                model_name: "llama-3-8b".to_string(),
+                endpoint: None,
+            },
+            stop_sequences: stop_sequences.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn chat_request(stop: Option<&[&str]>) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: "llama-3-8b".to_string(),
+            stop: stop.map(|stop| stop.iter().map(|s| s.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_config_stops_are_merged_and_capped() {
+        let model = model(InferenceModelProvider::OpenAI, &["<|eot_id|>", "</s>"]);
+
+        let mut request = chat_request(Some(&["\n\n", "</s>"]));
+        apply_stop_sequences(&mut request, &model);
+        assert_eq!(
+            request.stop,
+            Some(vec![
+                "<|eot_id|>".to_string(),
+                "</s>".to_string(),
+                "\n\n".to_string()
+            ])
+        );
+
+        // OpenAI accepts at most 4 stop sequences
+        let mut request = chat_request(Some(&["a", "b", "c"]));
+        apply_stop_sequences(&mut request, &model);
+        assert_eq!(
+            request.stop,
+            Some(vec![
+                "<|eot_id|>".to_string(),
+                "</s>".to_string(),
+                "a".to_string(),
+                "b".to_string()
+            ])
+        );
+
+        let mut request = chat_request(None);
+        apply_stop_sequences(&mut request, &model);
+        assert_eq!(
+            request.stop,
+            Some(vec!["<|eot_id|>".to_string(), "</s>".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_request_without_configured_stops() {
+        let model = model(InferenceModelProvider::Ollama, &[]);
+
+        let mut request = chat_request(None);
+        apply_stop_sequences(&mut request, &model);
+        assert_eq!(request.stop, None);
+
+        let mut request = chat_request(Some(&["a", "b", "c", "d", "e", "a"]));
+        apply_stop_sequences(&mut request, &model);
+        assert_eq!(request.stop.unwrap().len(), 5);
+    }
+}
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::events::bodies::BodyRecordingMode;
 use crate::model::types::LLMFinishEvent;
 use crate::model::types::ModelEvent;
-use futures::future::join;
+use futures::future::{join, AbortHandle, Abortable};
 use futures::StreamExt;
 use futures::TryStreamExt;
 
@@ -22,6 +23,7 @@ use tokio_stream::wrappers::ReceiverStream;
 use tracing::Span;
 use tracing_futures::Instrument;
 
+use super::response_aggregator::ResponseAggregator;
 use super::stream_wrapper::wrap_stream;
 use crate::executor::chat_completion::ChatCompletionStream;
 use crate::handler::{CallbackHandlerFn, ModelEventWithDetails};
@@ -35,6 +37,9 @@ pub struct StreamCacheContext {
     pub cached_events: Option<Vec<ModelEvent>>,
 }
 
+/// Streams the completion of `model`. Events pass through channels holding at most
+/// `channel_capacity` events, the model waits for room once a slow client fills them
+#[allow(clippy::too_many_arguments)]
 pub async fn stream_chunks(
     completion_model_definition: CompletionModelDefinition,
     model: Box<dyn ModelInstance>,
@@ -43,6 +48,7 @@ pub async fn stream_chunks(
     tags: HashMap<String, String>,
     input_vars: HashMap<String, serde_json::Value>,
     cached_context: StreamCacheContext,
+    channel_capacity: usize,
 ) -> Result<ChatCompletionStream, GatewayApiError> {
     let parent_definition =
         ParentDefinition::CompletionModel(Box::new(completion_model_definition.clone()));
@@ -53,45 +59,72 @@ pub async fn stream_chunks(
     };
 
     let db_model = model_options.definition.get_db_model();
-    let (outer_tx, rx) = tokio::sync::mpsc::channel(4096);
+    let (outer_tx, rx) = tokio::sync::mpsc::channel(channel_capacity);
+    // Task locals don't reach the spawned task
+    let body_recording = BodyRecordingMode::current();
 
     tokio::spawn(
         async move {
-            let (tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(4096);
+            let (tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(channel_capacity);
+            let (model_handle, model_registration) = AbortHandle::new_pair();
             let forward_fut = async {
                 let mut assistant_msg = String::new();
-                while let Some(Some(mut msg)) = rx.recv().await {
+                let mut aggregator = ResponseAggregator::default();
+                loop {
+                    let msg = tokio::select! {
+                        msg = rx.recv() => msg,
+                        // The stream was dropped (client disconnect or guard block), cancel
+                        // the model together with any tool calls it is waiting for
+                        _ = outer_tx.closed() => {
+                            model_handle.abort();
+                            break;
+                        }
+                    };
+                    let Some(Some(mut msg)) = msg else {
+                        break;
+                    };
                     if let ModelEventType::LlmContent(event) = &mut msg.event {
                         assistant_msg.push_str(event.content.as_str());
                     }
+                    aggregator.push(&msg.event);
 
                     callback_handler.on_message(ModelEventWithDetails::new(
                         msg.clone(),
                         Some(db_model.clone()),
                     ));
-                    let e = outer_tx.send(Ok(msg)).await;
-                    match e {
-                        Ok(_) => {}
-                        Err(e) => {
-                            tracing::error!("Error in sending message: {e}");
-                        }
+                    if outer_tx.send(Ok(msg)).await.is_err() {
+                        model_handle.abort();
+                        break;
                     }
                 }
 
                 let span = Span::current();
-                span.record("response", assistant_msg.clone());
+                span.record("response", body_recording.apply(&assistant_msg));
+
+                if let Some(response) = aggregator.finish() {
+                    callback_handler.on_message(ModelEventWithDetails::new(
+                        ModelEvent::new(&span, ModelEventType::LlmResponse(Box::new(response))),
+                        Some(db_model.clone()),
+                    ));
+                }
             };
 
-            let result_fut = model
-                .stream(input_vars, tx, messages, tags)
-                .instrument(Span::current());
+            let result_fut = Abortable::new(
+                model
+                    .stream(input_vars, tx, messages, tags)
+                    .instrument(Span::current()),
+                model_registration,
+            );
 
             let (result, _) = join(result_fut, forward_fut).await;
+            let Ok(result) = result else {
+                tracing::debug!("Stream was dropped, model execution cancelled");
+                return;
+            };
             if let Err(e) = result {
-                outer_tx
-                    .send(Err(GatewayApiError::GatewayError(e)))
-                    .await
-                    .unwrap();
+                if let Err(e) = outer_tx.send(Err(GatewayApiError::GatewayError(e))).await {
+                    tracing::error!("Error in sending message: {e}");
+                }
             }
         }
         .in_current_span(),
@@ -103,7 +136,9 @@ pub async fn stream_chunks(
             async move {
                 if let Ok(event) = &e {
                     if let Some(events_sender) = events_sender {
-                        events_sender.send(Some(event.clone())).await.unwrap();
+                        if let Err(e) = events_sender.send(Some(event.clone())).await {
+                            tracing::warn!("Failed to forward event to response cache: {e}");
+                        }
                     }
                 }
 
@@ -129,6 +164,7 @@ pub async fn stream_chunks(
                             role: Some("assistant".to_string()),
                             content: Some(content.content),
                             tool_calls: None,
+                            index: content.index,
                         }),
                         None,
                         None,
@@ -146,6 +182,7 @@ pub async fn stream_chunks(
                                     arguments: tool_call.input.clone(),
                                 },
                             }]),
+                            index: 0,
                         }),
                         None,
                         None,
@@ -175,6 +212,7 @@ pub async fn stream_chunks(
                                         })
                                         .collect(),
                                 ),
+                                index: 0,
                             }),
                             _ => None,
                         };
@@ -195,3 +233,337 @@ pub async fn stream_chunks(
 
     Ok(wrap_stream(event_stream))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::chat::map_sso_event;
+    use crate::model::handler::handle_tool_call;
+    use crate::model::tools::Tool;
+    use crate::model::types::{LLMContentEvent, ModelToolCall};
+    use crate::types::engine::{
+        CompletionEngineParams, CompletionModelParams, Model, ModelTools, ModelType, Prompt,
+    };
+    use crate::types::gateway::ChatCompletionMessage;
+    use crate::types::gateway::FunctionParameters;
+    use crate::GatewayResult;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// Streams `events` content events, counting the ones sent
+    struct ChattyModel {
+        events: usize,
+        sent: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl ModelInstance for ChattyModel {
+        async fn invoke(
+            &self,
+            _input_vars: HashMap<String, serde_json::Value>,
+            _tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<ChatCompletionMessage> {
+            unimplemented!()
+        }
+
+        async fn stream(
+            &self,
+            _input_vars: HashMap<String, serde_json::Value>,
+            tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<()> {
+            for i in 0..self.events {
+                let event = ModelEvent::new(
+                    &Span::none(),
+                    ModelEventType::LlmContent(LLMContentEvent {
+                        content: format!("{i} "),
+                        index: 0,
+                    }),
+                );
+                if tx.send(Some(event)).await.is_err() {
+                    break;
+                }
+                self.sent.fetch_add(1, Ordering::SeqCst);
+            }
+            Ok(())
+        }
+    }
+
+    /// Streams two choices, alternating between them
+    struct TwoChoiceModel;
+
+    #[async_trait::async_trait]
+    impl ModelInstance for TwoChoiceModel {
+        async fn invoke(
+            &self,
+            _input_vars: HashMap<String, serde_json::Value>,
+            _tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<ChatCompletionMessage> {
+            unimplemented!()
+        }
+
+        async fn stream(
+            &self,
+            _input_vars: HashMap<String, serde_json::Value>,
+            tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<()> {
+            for (index, content) in [(0, "Paris"), (1, "Lyon"), (0, " is"), (1, " is")] {
+                let event = ModelEvent::new(
+                    &Span::none(),
+                    ModelEventType::LlmContent(LLMContentEvent {
+                        content: content.to_string(),
+                        index,
+                    }),
+                );
+                tx.send(Some(event))
+                    .await
+                    .map_err(|e| crate::GatewayError::CustomError(e.to_string()))?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Sets its flag when dropped
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Takes far longer than any test, recording whether it started, finished or was dropped
+    #[derive(Clone, Default)]
+    struct SlowTool {
+        started: Arc<AtomicBool>,
+        finished: Arc<AtomicBool>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl Tool for SlowTool {
+        fn name(&self) -> String {
+            "slow_search".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Searches for a very long time".to_string()
+        }
+
+        fn get_function_parameters(&self) -> Option<FunctionParameters> {
+            None
+        }
+
+        async fn run(
+            &self,
+            _input: HashMap<String, serde_json::Value>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<serde_json::Value> {
+            let _guard = DropFlag(self.dropped.clone());
+            self.started.store(true, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            self.finished.store(true, Ordering::SeqCst);
+            Ok(serde_json::json!({"results": []}))
+        }
+    }
+
+    /// Streams some content, then calls `slow_search`
+    struct ToolCallingModel {
+        tools: HashMap<String, Box<dyn Tool>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ModelInstance for ToolCallingModel {
+        async fn invoke(
+            &self,
+            _input_vars: HashMap<String, serde_json::Value>,
+            _tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<ChatCompletionMessage> {
+            unimplemented!()
+        }
+
+        async fn stream(
+            &self,
+            _input_vars: HashMap<String, serde_json::Value>,
+            tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            tags: HashMap<String, String>,
+        ) -> GatewayResult<()> {
+            let event = ModelEvent::new(
+                &Span::none(),
+                ModelEventType::LlmContent(LLMContentEvent {
+                    content: "Searching".to_string(),
+                    index: 0,
+                }),
+            );
+            tx.send(Some(event))
+                .await
+                .map_err(|e| crate::GatewayError::CustomError(e.to_string()))?;
+            let tool_call = ModelToolCall {
+                tool_id: "call_1".to_string(),
+                tool_name: "slow_search".to_string(),
+                input: "{}".to_string(),
+            };
+            handle_tool_call(&tool_call, &self.tools, &tx, tags).await?;
+            Ok(())
+        }
+    }
+
+    fn definition() -> CompletionModelDefinition {
+        CompletionModelDefinition {
+            name: "openai/gpt-4o-mini".to_string(),
+            model_params: CompletionModelParams {
+                engine: CompletionEngineParams::OpenAi {
+                    params: Default::default(),
+                    execution_options: Default::default(),
+                    credentials: None,
+                    endpoint: None,
+                },
+                provider_name: "openai".to_string(),
+                prompt_name: None,
+            },
+            prompt: Prompt::empty(),
+            tools: ModelTools::default(),
+            db_model: Model {
+                name: "openai/gpt-4o-mini".to_string(),
+                description: None,
+                provider_name: "openai".to_string(),
+                prompt_name: None,
+                model_params: HashMap::new(),
+                tools: ModelTools::default(),
+                model_type: ModelType::Completions,
+                response_schema: None,
+                credentials: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_throttles_model() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let model = ChattyModel {
+            events: 50,
+            sent: sent.clone(),
+        };
+        let mut stream = stream_chunks(
+            definition(),
+            Box::new(model),
+            vec![],
+            Arc::new(CallbackHandlerFn(None)),
+            HashMap::new(),
+            HashMap::new(),
+            StreamCacheContext::default(),
+            2,
+        )
+        .await
+        .unwrap();
+
+        stream.next().await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // Only what fits into the channels is produced ahead of the client
+        assert!(sent.load(Ordering::SeqCst) < 10);
+
+        let mut received = 1;
+        while let Some(event) = stream.next().await {
+            event.unwrap();
+            received += 1;
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(received, 50);
+        assert_eq!(sent.load(Ordering::SeqCst), 50);
+    }
+
+    #[tokio::test]
+    async fn test_dropping_stream_cancels_tool_calls() {
+        let tool = SlowTool::default();
+        let model = ToolCallingModel {
+            tools: HashMap::from([(
+                "slow_search".to_string(),
+                Box::new(tool.clone()) as Box<dyn Tool>,
+            )]),
+        };
+        let mut stream = stream_chunks(
+            definition(),
+            Box::new(model),
+            vec![],
+            Arc::new(CallbackHandlerFn(None)),
+            HashMap::new(),
+            HashMap::new(),
+            StreamCacheContext::default(),
+            8,
+        )
+        .await
+        .unwrap();
+
+        stream.next().await.unwrap().unwrap();
+        while !tool.started.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        drop(stream);
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !tool.dropped.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("the tool call should be cancelled");
+        assert!(!tool.finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_chunks_carry_choice_index() {
+        let stream = stream_chunks(
+            definition(),
+            Box::new(TwoChoiceModel),
+            vec![],
+            Arc::new(CallbackHandlerFn(None)),
+            HashMap::new(),
+            HashMap::new(),
+            StreamCacheContext::default(),
+            16,
+        )
+        .await
+        .unwrap();
+
+        let chunks = stream
+            .map(|event| map_sso_event(event, "gpt-4o-mini".to_string()).unwrap())
+            .map(|frame| {
+                let frame = String::from_utf8(frame.to_vec()).unwrap();
+                serde_json::from_str::<serde_json::Value>(frame.trim_start_matches("data: "))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>()
+            .await;
+        let choices = chunks
+            .iter()
+            .map(|chunk| {
+                let choice = &chunk["choices"][0];
+                (choice["index"].clone(), choice["delta"]["content"].clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            choices,
+            [
+                (0.into(), "Paris".into()),
+                (1.into(), "Lyon".into()),
+                (0.into(), " is".into()),
+                (1.into(), " is".into())
+            ]
+        );
+        // The index stays on the choice, not in the delta
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk["choices"][0]["delta"].get("index").is_none()));
+    }
+}
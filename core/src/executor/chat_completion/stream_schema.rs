@@ -0,0 +1,236 @@
+use futures::StreamExt;
+use jsonschema::Validator;
+use serde_json::Value;
+
+use crate::executor::chat_completion::json_repair::repair_json;
+use crate::executor::chat_completion::response_schema::{schema_validator, validate_content};
+use crate::executor::chat_completion::stream_wrapper::{wrap_stream, ChatCompletionStream};
+use crate::GatewayApiError;
+
+/// Validates streamed JSON content against a response schema while it arrives
+pub struct StreamingSchemaValidator {
+    schema: Value,
+    validator: Validator,
+    content: String,
+}
+
+impl StreamingSchemaValidator {
+    pub fn new(schema: Value) -> Result<Self, GatewayApiError> {
+        let validator = schema_validator(&schema)?;
+        Ok(Self {
+            schema,
+            validator,
+            content: String::new(),
+        })
+    }
+
+    /// Adds a chunk of content, failing as soon as the content so far can't be completed
+    /// into JSON matching the schema
+    pub fn push(&mut self, chunk: &str) -> Result<(), GatewayApiError> {
+        self.content.push_str(chunk);
+        let content = self.content.trim_start();
+        if content.is_empty() {
+            return Ok(());
+        }
+
+        // Structured outputs are objects, anything else can't become one
+        if !content.starts_with(['{', '[']) {
+            return Err(violation("output is not a JSON object or array"));
+        }
+
+        // Incomplete input, such as a half written literal, is checked once more arrives
+        let partial = serde_json::from_str::<Value>(content)
+            .ok()
+            .or_else(|| repair_json(content).and_then(|r| serde_json::from_str(&r).ok()));
+        match partial.and_then(|partial| structural_violation(&partial, &self.schema, "")) {
+            Some(e) => Err(violation(&e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Validates the complete content
+    pub fn finish(&self) -> Result<(), GatewayApiError> {
+        validate_content(&self.validator, &self.content)
+    }
+}
+
+fn violation(message: &str) -> GatewayApiError {
+    GatewayApiError::ResponseSchemaViolation(format!("streamed {message}"))
+}
+
+fn matches_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        // A partial number may still get a fraction, so integers are checked once complete
+        "number" | "integer" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Checks the parts of a partial document more content can't change: value types, and
+/// properties an object doesn't allow. Strings, numbers and missing properties may still
+/// be completed, so `required` and value constraints are left to the final validation.
+fn structural_violation(value: &Value, schema: &Value, path: &str) -> Option<String> {
+    let types = match schema.get("type") {
+        Some(Value::String(ty)) => vec![ty.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    if !types.is_empty() && !types.iter().any(|ty| matches_type(value, ty)) {
+        return Some(format!(
+            "value at '{path}' is not of type {}",
+            types.join(" or ")
+        ));
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+            object.iter().find_map(|(key, value)| {
+                let path = format!("{path}/{key}");
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(schema) => structural_violation(value, schema, &path),
+                    None if closed => Some(format!("property '{path}' is not allowed")),
+                    None => None,
+                }
+            })
+        }
+        Value::Array(items) => {
+            let schema = schema.get("items").filter(|items| items.is_object())?;
+            items
+                .iter()
+                .enumerate()
+                .find_map(|(i, item)| structural_violation(item, schema, &format!("{path}/{i}")))
+        }
+        _ => None,
+    }
+}
+
+/// Ends `stream` with a schema violation as soon as its content can no longer match `schema`,
+/// and validates the complete content once it finishes
+pub fn with_schema_validation(
+    stream: ChatCompletionStream,
+    schema: Value,
+) -> Result<ChatCompletionStream, GatewayApiError> {
+    let validator = StreamingSchemaValidator::new(schema)?;
+    Ok(wrap_stream(stream.scan(
+        Some(validator),
+        |validator, event| {
+            let Some(current) = validator else {
+                return futures::future::ready(None);
+            };
+
+            let result = match &event {
+                Ok((delta, _, finish_reason)) => {
+                    let content = delta.as_ref().and_then(|delta| delta.content.as_deref());
+                    let pushed = content.map_or(Ok(()), |content| current.push(content));
+                    match finish_reason {
+                        Some(_) => pushed.and_then(|_| current.finish()),
+                        None => pushed,
+                    }
+                }
+                Err(_) => Ok(()),
+            };
+
+            let event = match result {
+                Ok(()) => event,
+                Err(e) => {
+                    *validator = None;
+                    Err(e)
+                }
+            };
+            futures::future::ready(Some(event))
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gateway::ChatCompletionDelta;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "city": {"type": "string"},
+                "population": {"type": "integer"}
+            },
+            "required": ["city", "population"],
+            "additionalProperties": false
+        })
+    }
+
+    fn chunk(content: &str) -> Result<crate::handler::chat::SSOChatEvent, GatewayApiError> {
+        Ok((
+            Some(ChatCompletionDelta {
+                role: Some("assistant".to_string()),
+                content: Some(content.to_string()),
+                tool_calls: None,
+                index: 0,
+            }),
+            None,
+            None,
+        ))
+    }
+
+    #[test]
+    fn test_partial_output_is_checked() {
+        let mut validator = StreamingSchemaValidator::new(schema()).unwrap();
+        for chunk in [
+            "{\"ci",
+            "ty\": \"Par",
+            "is\", \"popu",
+            "lation\": 21",
+            "00000}",
+        ] {
+            validator.push(chunk).unwrap();
+        }
+        validator.finish().unwrap();
+
+        let mut validator = StreamingSchemaValidator::new(schema()).unwrap();
+        validator.push("{\"city\": \"Paris\", ").unwrap();
+        assert!(validator.push("\"country\": ").is_ok());
+        assert!(matches!(
+            validator.push("\"FR\""),
+            Err(GatewayApiError::ResponseSchemaViolation(_))
+        ));
+
+        let mut validator = StreamingSchemaValidator::new(schema()).unwrap();
+        assert!(validator.push("Sure! Here is").is_err());
+
+        // Missing properties only fail once the output is complete
+        let mut validator = StreamingSchemaValidator::new(schema()).unwrap();
+        validator.push("{\"city\": \"Paris\"}").unwrap();
+        assert!(validator.finish().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_violating_stream_is_aborted() {
+        let events = vec![
+            chunk("{\"city\": "),
+            chunk("[\"Paris\"]"),
+            chunk(", \"population\": 2100000}"),
+            Ok((None, None, Some("stop".to_string()))),
+        ];
+        let stream = wrap_stream(futures::stream::iter(events));
+
+        let events: Vec<_> = with_schema_validation(stream, schema())
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(events.len(), 2);
+        assert!(events[0].is_ok());
+        assert!(matches!(
+            &events[1],
+            Err(GatewayApiError::ResponseSchemaViolation(e)) if e.contains("/city")
+        ));
+    }
+}
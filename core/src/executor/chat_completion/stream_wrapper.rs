@@ -1,7 +1,13 @@
 use crate::handler::chat::SSOChatEvent;
 use crate::GatewayApiError;
-use futures::Stream;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::pin::Pin;
+use std::time::Duration;
+
+/// SSE comment frame, ignored by clients but keeps intermediaries from closing idle connections
+pub const KEEP_ALIVE_FRAME: &str = ": keep-alive\n\n";
 
 /// Type alias for the concrete stream type returned by chat completion functions
 pub type ChatCompletionStream =
@@ -14,3 +20,213 @@ where
 {
     Box::pin(stream)
 }
+
+/// Moves the usage of the final event into a usage-only chunk with empty choices when
+/// `include_usage` is set, as OpenAI's `stream_options` does, and drops it otherwise
+pub fn with_stream_usage(
+    stream: ChatCompletionStream,
+    include_usage: bool,
+) -> ChatCompletionStream {
+    wrap_stream(stream.flat_map(move |event| {
+        let events = match event {
+            Ok((delta, Some(usage), finish_reason)) => match (include_usage, delta) {
+                (true, Some(delta)) => vec![
+                    Ok((Some(delta), None, finish_reason)),
+                    Ok((None, Some(usage), None)),
+                ],
+                (true, None) => vec![Ok((None, Some(usage), finish_reason))],
+                (false, delta) => vec![Ok((delta, None, finish_reason))],
+            },
+            event => vec![event],
+        };
+        futures::stream::iter(events)
+    }))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeepAliveConfig {
+    /// Seconds without any data after which a keep-alive comment is sent
+    pub interval_secs: u64,
+    /// Stop sending keep-alive comments once the first chunk has been sent
+    #[serde(default)]
+    pub disable_after_data: bool,
+}
+
+impl KeepAliveConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_secs == 0 {
+            return Err("sse_keepalive.interval_secs must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// Interleaves SSE keep-alive comments into an event stream whenever it has been idle for `interval`
+pub fn with_keepalive<S, E>(
+    stream: S,
+    interval: Duration,
+    disable_after_data: bool,
+) -> impl Stream<Item = Result<Bytes, E>> + Send
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Send + 'static,
+{
+    futures::stream::unfold(
+        (Box::pin(stream), false),
+        move |(mut stream, data_sent)| async move {
+            if data_sent && disable_after_data {
+                return stream.next().await.map(|item| (item, (stream, true)));
+            }
+
+            match tokio::time::timeout(interval, stream.next()).await {
+                Ok(Some(item)) => Some((item, (stream, true))),
+                Ok(None) => None,
+                Err(_) => Some((
+                    Ok(Bytes::from_static(KEEP_ALIVE_FRAME.as_bytes())),
+                    (stream, data_sent),
+                )),
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::chat::map_sso_event;
+    use crate::types::gateway::{ChatCompletionDelta, CompletionModelUsage};
+
+    fn slow_model_stream() -> impl Stream<Item = Result<Bytes, GatewayApiError>> + Send {
+        futures::stream::once(async {
+            tokio::time::sleep(Duration::from_millis(350)).await;
+            Ok(Bytes::from("data: {\"id\":\"1\"}\n\n"))
+        })
+        .chain(futures::stream::once(async {
+            tokio::time::sleep(Duration::from_millis(350)).await;
+            Ok(Bytes::from("data: {\"id\":\"2\"}\n\n"))
+        }))
+    }
+
+    fn frames(items: Vec<Result<Bytes, GatewayApiError>>) -> Vec<String> {
+        items
+            .into_iter()
+            .map(|i| String::from_utf8(i.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    fn finished_stream() -> ChatCompletionStream {
+        let delta = ChatCompletionDelta {
+            role: Some("assistant".to_string()),
+            content: Some("Paris".to_string()),
+            tool_calls: None,
+            index: 0,
+        };
+        let usage = CompletionModelUsage {
+            input_tokens: 9,
+            output_tokens: 1,
+            total_tokens: 10,
+            ..Default::default()
+        };
+        wrap_stream(futures::stream::iter(vec![
+            Ok((Some(delta), None, None)),
+            Ok((None, Some(usage), Some("stop".to_string()))),
+        ]))
+    }
+
+    async fn chunks(include_usage: bool) -> Vec<serde_json::Value> {
+        with_stream_usage(finished_stream(), include_usage)
+            .map(|event| map_sso_event(event, "gpt-4o".to_string()).unwrap())
+            .filter(|frame| futures::future::ready(!frame.is_empty()))
+            .map(|frame| {
+                let frame = String::from_utf8(frame.to_vec()).unwrap();
+                serde_json::from_str(frame.trim().trim_start_matches("data: ")).unwrap()
+            })
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_final_chunk_carries_usage_when_requested() {
+        let chunks = chunks(true).await;
+        let last = chunks.last().unwrap();
+        assert_eq!(last["choices"], serde_json::json!([]));
+        assert_eq!(last["usage"]["prompt_tokens"], 9);
+        assert_eq!(last["usage"]["total_tokens"], 10);
+
+        let chunks = chunks(false).await;
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks.iter().all(|chunk| chunk["usage"].is_null()));
+    }
+
+    #[tokio::test]
+    async fn test_usage_is_split_from_tool_call_delta() {
+        let delta = ChatCompletionDelta {
+            role: Some("assistant".to_string()),
+            content: None,
+            tool_calls: None,
+            index: 0,
+        };
+        let stream = wrap_stream(futures::stream::iter(vec![Ok((
+            Some(delta),
+            Some(CompletionModelUsage::default()),
+            Some("tool_calls".to_string()),
+        ))]));
+
+        let events = with_stream_usage(stream, true).collect::<Vec<_>>().await;
+        assert_eq!(events.len(), 2);
+        let (delta, usage, finish_reason) = events[0].as_ref().unwrap();
+        assert!(delta.is_some() && usage.is_none());
+        assert_eq!(finish_reason.as_deref(), Some("tool_calls"));
+        let (delta, usage, _) = events[1].as_ref().unwrap();
+        assert!(delta.is_none() && usage.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_emitted_during_gap() {
+        let items = with_keepalive(slow_model_stream(), Duration::from_millis(100), false)
+            .collect::<Vec<_>>()
+            .await;
+        let frames = frames(items);
+
+        let first_data = frames.iter().position(|f| f.starts_with("data:")).unwrap();
+        assert!(first_data >= 2);
+        assert!(frames[..first_data].iter().all(|f| f == KEEP_ALIVE_FRAME));
+
+        let data = frames
+            .iter()
+            .filter(|f| f.starts_with("data:"))
+            .collect::<Vec<_>>();
+        assert_eq!(data.len(), 2);
+        assert!(frames
+            .iter()
+            .skip(first_data + 1)
+            .any(|f| f == KEEP_ALIVE_FRAME));
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_disabled_after_data() {
+        let items = with_keepalive(slow_model_stream(), Duration::from_millis(100), true)
+            .collect::<Vec<_>>()
+            .await;
+        let frames = frames(items);
+
+        let first_data = frames.iter().position(|f| f.starts_with("data:")).unwrap();
+        assert!(first_data >= 2);
+        assert_eq!(frames.len(), first_data + 2);
+        assert!(frames[first_data + 1].starts_with("data:"));
+    }
+
+    #[test]
+    fn test_keepalive_interval_must_be_positive() {
+        let config = |interval_secs| KeepAliveConfig {
+            interval_secs,
+            disable_after_data: false,
+        };
+        assert!(config(0).validate().is_err());
+        assert!(config(15).validate().is_ok());
+    }
+}
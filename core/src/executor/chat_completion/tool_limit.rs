@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::tools::Tool;
+use crate::types::engine::ModelTool;
+use crate::GatewayApiError;
+
+/// What to do with a request carrying more tools than allowed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolLimitStrategy {
+    /// Reject the request
+    #[default]
+    Error,
+    /// Send only the first `max_tools` tools, request tools before MCP ones
+    Truncate,
+}
+
+/// Caps the number of tools sent to the provider
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ToolLimit {
+    pub max_tools: usize,
+    #[serde(default)]
+    pub strategy: ToolLimitStrategy,
+}
+
+/// Enforces `limit` on the tools of a request
+pub fn apply_tool_limit(
+    limit: ToolLimit,
+    tools_map: &mut HashMap<String, Box<dyn Tool>>,
+    request_tools: &mut Vec<ModelTool>,
+) -> Result<(), GatewayApiError> {
+    let count = request_tools.len();
+    if count <= limit.max_tools {
+        return Ok(());
+    }
+
+    match limit.strategy {
+        ToolLimitStrategy::Error => Err(GatewayApiError::TooManyTools {
+            count,
+            limit: limit.max_tools,
+        }),
+        ToolLimitStrategy::Truncate => {
+            tracing::warn!(
+                "Request has {count} tools, only the first {} are sent",
+                limit.max_tools
+            );
+            for tool in request_tools.drain(limit.max_tools..) {
+                tools_map.remove(&tool.name);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::tools::GatewayTool;
+
+    fn tools(count: usize) -> (HashMap<String, Box<dyn Tool>>, Vec<ModelTool>) {
+        let mut tools_map = HashMap::new();
+        let mut request_tools = vec![];
+        for i in 0..count {
+            let name = format!("tool_{i}");
+            let def = serde_json::from_value(serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": name,
+                    "description": null,
+                    "parameters": {"type": "object", "properties": {}}
+                }
+            }))
+            .unwrap();
+            tools_map.insert(name.clone(), Box::new(GatewayTool { def }) as Box<dyn Tool>);
+            request_tools.push(ModelTool {
+                name,
+                description: None,
+                passed_args: vec![],
+            });
+        }
+        (tools_map, request_tools)
+    }
+
+    #[test]
+    fn test_tools_over_limit_are_rejected() {
+        let limit = ToolLimit {
+            max_tools: 3,
+            strategy: ToolLimitStrategy::Error,
+        };
+
+        let (mut tools_map, mut request_tools) = tools(3);
+        assert!(apply_tool_limit(limit, &mut tools_map, &mut request_tools).is_ok());
+
+        let (mut tools_map, mut request_tools) = tools(4);
+        assert!(matches!(
+            apply_tool_limit(limit, &mut tools_map, &mut request_tools),
+            Err(GatewayApiError::TooManyTools { count: 4, limit: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_tools_over_limit_are_truncated() {
+        let limit = ToolLimit {
+            max_tools: 3,
+            strategy: ToolLimitStrategy::Truncate,
+        };
+
+        let (mut tools_map, mut request_tools) = tools(5);
+        apply_tool_limit(limit, &mut tools_map, &mut request_tools).unwrap();
+
+        assert_eq!(
+            request_tools
+                .iter()
+                .map(|tool| tool.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["tool_0", "tool_1", "tool_2"]
+        );
+        assert_eq!(tools_map.len(), 3);
+        assert!(!tools_map.contains_key("tool_3"));
+    }
+}
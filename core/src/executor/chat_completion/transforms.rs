@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use crate::types::gateway::{ChatCompletionRequest, ChatCompletionResponse};
+use crate::GatewayApiError;
+
+/// Mutates chat completion requests before they are sent to the provider
+pub trait RequestTransform: Send + Sync {
+    fn transform(&self, request: &mut ChatCompletionRequest) -> Result<(), GatewayApiError>;
+}
+
+/// Mutates non-streaming chat completion responses before they are returned
+pub trait ResponseTransform: Send + Sync {
+    fn transform(&self, response: &mut ChatCompletionResponse) -> Result<(), GatewayApiError>;
+}
+
+/// Request and response transforms, applied in the order they were added
+#[derive(Clone, Default)]
+pub struct TransformPipeline {
+    request: Vec<Arc<dyn RequestTransform>>,
+    response: Vec<Arc<dyn ResponseTransform>>,
+}
+
+impl std::fmt::Debug for TransformPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransformPipeline")
+            .field("request", &self.request.len())
+            .field("response", &self.response.len())
+            .finish()
+    }
+}
+
+impl TransformPipeline {
+    pub fn with_request_transform(mut self, transform: Arc<dyn RequestTransform>) -> Self {
+        self.request.push(transform);
+        self
+    }
+
+    pub fn with_response_transform(mut self, transform: Arc<dyn ResponseTransform>) -> Self {
+        self.response.push(transform);
+        self
+    }
+
+    pub fn transform_request(
+        &self,
+        request: &mut ChatCompletionRequest,
+    ) -> Result<(), GatewayApiError> {
+        self.request
+            .iter()
+            .try_for_each(|transform| transform.transform(request))
+    }
+
+    pub fn transform_response(
+        &self,
+        response: &mut ChatCompletionResponse,
+    ) -> Result<(), GatewayApiError> {
+        self.response
+            .iter()
+            .try_for_each(|transform| transform.transform(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::gateway::{
+        ChatCompletionChoice, ChatCompletionContent, ChatCompletionMessage, ChatCompletionUsage,
+    };
+
+    /// Puts a system message in front of every request
+    struct InjectSystemMessage(&'static str);
+
+    impl RequestTransform for InjectSystemMessage {
+        fn transform(&self, request: &mut ChatCompletionRequest) -> Result<(), GatewayApiError> {
+            request.messages.insert(
+                0,
+                ChatCompletionMessage::new_text("system".to_string(), self.0.to_string()),
+            );
+            Ok(())
+        }
+    }
+
+    /// Drops the service tier responses were served with
+    struct RedactServiceTier;
+
+    impl ResponseTransform for RedactServiceTier {
+        fn transform(&self, response: &mut ChatCompletionResponse) -> Result<(), GatewayApiError> {
+            response.service_tier = None;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_request_transform_injects_system_message() {
+        let pipeline = TransformPipeline::default()
+            .with_request_transform(Arc::new(InjectSystemMessage("Answer in French.")));
+        let mut request = ChatCompletionRequest {
+            model: "openai/gpt-4o-mini".to_string(),
+            messages: vec![ChatCompletionMessage::new_text(
+                "user".to_string(),
+                "What is the capital of Italy?".to_string(),
+            )],
+            ..Default::default()
+        };
+
+        pipeline.transform_request(&mut request).unwrap();
+
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role, "system");
+        assert_eq!(
+            request.messages[0].content,
+            Some(ChatCompletionContent::Text("Answer in French.".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_response_transform_redacts_field() {
+        let pipeline =
+            TransformPipeline::default().with_response_transform(Arc::new(RedactServiceTier));
+        let mut response = ChatCompletionResponse {
+            id: "chatcmpl-1".to_string(),
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: "gpt-4o-mini".to_string(),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatCompletionMessage::new_text(
+                    "assistant".to_string(),
+                    "Rome".to_string(),
+                ),
+                finish_reason: Some("stop".to_string()),
+                logprobs: None,
+            }],
+            usage: ChatCompletionUsage::default(),
+            is_cache_used: None,
+            service_tier: Some("default".to_string()),
+        };
+
+        pipeline.transform_response(&mut response).unwrap();
+
+        let body = serde_json::to_value(&response).unwrap();
+        assert!(body.get("service_tier").is_none());
+        assert_eq!(body["choices"][0]["message"]["content"], "Rome");
+    }
+}
@@ -1,8 +1,25 @@
+use crate::events::bodies::BodyRecordingMode;
+use crate::executor::chat_completion::code_fences::StripCodeFences;
+use crate::executor::chat_completion::merge_messages::MergeConsecutiveMessages;
+use crate::executor::chat_completion::prompt_limit::PromptLimit;
+use crate::executor::chat_completion::response_cache::ResponseCache;
+use crate::executor::chat_completion::stream_wrapper::KeepAliveConfig;
+use crate::executor::chat_completion::tool_limit::ToolLimit;
+use crate::executor::chat_completion::transforms::TransformPipeline;
+use crate::executor::chat_completion::{EventChannelCapacity, RequireUser, StreamFallback};
+use crate::pricing::currency::CurrencyConverter;
+use crate::pricing::estimate::RequestCostCeiling;
 use crate::types::guardrails::service::GuardrailsEvaluator;
+use crate::types::guardrails::{BlockedAsContentFilter, ReturnBlockedOutput};
 use crate::{
     error::GatewayError,
-    handler::{extract_tags, AvailableModels, CallbackHandlerFn},
-    types::{credentials::Credentials, gateway::CostCalculator},
+    handler::{body_recording, extract_tags, AvailableModels, CallbackHandlerFn},
+    types::{
+        credentials::{CredentialFingerprinting, Credentials},
+        gateway::CostCalculator,
+        provider::BedrockConfig,
+        GatewayTenant,
+    },
 };
 use actix_web::{HttpMessage, HttpRequest};
 use std::{collections::HashMap, sync::Arc};
@@ -17,8 +34,28 @@ pub struct ExecutorContext {
     pub tags: HashMap<String, String>,
     pub headers: HashMap<String, String>,
     pub key_credentials: Option<Credentials>,
+    /// Name of the tenant the request was authenticated as
+    pub tenant: Option<String>,
     pub providers_config: Option<ProvidersConfig>,
     pub evaluator_service: Arc<Box<dyn GuardrailsEvaluator>>,
+    pub bedrock_config: Option<BedrockConfig>,
+    pub keep_alive: Option<KeepAliveConfig>,
+    pub credential_fingerprinting: Option<CredentialFingerprinting>,
+    pub request_cost_ceiling: Option<RequestCostCeiling>,
+    pub currency: CurrencyConverter,
+    pub require_user: bool,
+    pub return_blocked_output: bool,
+    pub blocked_as_content_filter: bool,
+    pub stream_fallback: bool,
+    pub strip_code_fences: bool,
+    /// How bodies of this request are recorded, sampled once per request
+    pub body_recording: BodyRecordingMode,
+    pub response_cache: Option<ResponseCache>,
+    pub merge_consecutive_messages: MergeConsecutiveMessages,
+    pub tool_limit: Option<ToolLimit>,
+    pub prompt_limit: Option<PromptLimit>,
+    pub transforms: TransformPipeline,
+    pub event_channel_capacity: usize,
 }
 
 // Implement Send + Sync since all fields are Send + Sync
@@ -41,7 +78,40 @@ impl ExecutorContext {
             .collect();
 
         let key_credentials = req.extensions().get::<Credentials>().cloned();
+        let tenant = req
+            .extensions()
+            .get::<GatewayTenant>()
+            .map(|tenant| tenant.name.clone());
         let providers_config = req.app_data::<ProvidersConfig>().cloned();
+        let bedrock_config = req.app_data::<BedrockConfig>().cloned();
+        let keep_alive = req.app_data::<KeepAliveConfig>().cloned();
+        let credential_fingerprinting = req.app_data::<CredentialFingerprinting>().cloned();
+        let request_cost_ceiling = req.app_data::<RequestCostCeiling>().cloned();
+        let currency = req
+            .app_data::<CurrencyConverter>()
+            .cloned()
+            .unwrap_or_default();
+        let require_user = req.app_data::<RequireUser>().is_some();
+        let return_blocked_output = req.app_data::<ReturnBlockedOutput>().is_some();
+        let blocked_as_content_filter = req.app_data::<BlockedAsContentFilter>().is_some();
+        let stream_fallback = req.app_data::<StreamFallback>().is_some();
+        let strip_code_fences = req.app_data::<StripCodeFences>().is_some();
+        let body_recording = body_recording(req);
+        let response_cache = req.app_data::<ResponseCache>().cloned();
+        let merge_consecutive_messages = req
+            .app_data::<MergeConsecutiveMessages>()
+            .cloned()
+            .unwrap_or_default();
+        let tool_limit = req.app_data::<ToolLimit>().cloned();
+        let prompt_limit = req.app_data::<PromptLimit>().cloned();
+        let transforms = req
+            .app_data::<TransformPipeline>()
+            .cloned()
+            .unwrap_or_default();
+        let EventChannelCapacity(event_channel_capacity) = req
+            .app_data::<EventChannelCapacity>()
+            .copied()
+            .unwrap_or_default();
 
         Ok(Self {
             callbackhandler,
@@ -50,8 +120,26 @@ impl ExecutorContext {
             tags,
             headers,
             key_credentials,
+            tenant,
             providers_config,
             evaluator_service,
+            bedrock_config,
+            keep_alive,
+            credential_fingerprinting,
+            request_cost_ceiling,
+            currency,
+            require_user,
+            return_blocked_output,
+            blocked_as_content_filter,
+            stream_fallback,
+            strip_code_fences,
+            body_recording,
+            response_cache,
+            merge_consecutive_messages,
+            tool_limit,
+            prompt_limit,
+            transforms,
+            event_channel_capacity,
         })
     }
 }
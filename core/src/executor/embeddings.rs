@@ -1,21 +1,28 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, LazyLock};
 
 use crate::embed_mod::Embed;
 use crate::embed_mod::OpenAIEmbed;
 use crate::embed_mod_ollama::OllamaEmbed;
 use crate::error::GatewayError;
-use crate::model::types::ModelEvent;
+use crate::model::types::{LLMFinishEvent, ModelEvent, ModelEventType, ModelFinishReason};
+use crate::model::CredentialsIdent;
 use crate::models::ModelMetadata;
+use crate::types::credentials::credential_fingerprint;
 use crate::types::credentials::ApiKeyCredentials;
 use crate::types::credentials::Credentials;
-use actix_web::HttpRequest;
 use tracing::Span;
 
 use crate::types::embed::OpenAiEmbeddingParams;
 use crate::types::{
     engine::{Model, ModelTools, ModelType, OllamaModelParams},
-    gateway::{CreateEmbeddingRequest, CreateEmbeddingResponse},
+    gateway::{
+        CompletionModelUsage, CreateEmbeddingRequest, CreateEmbeddingResponse, EmbeddingInputError,
+        EmbeddingUsage, Input,
+    },
 };
+use futures::StreamExt;
 use tracing_futures::Instrument;
 
 use crate::handler::{CallbackHandlerFn, ModelEventWithDetails};
@@ -29,7 +36,7 @@ pub async fn handle_embeddings_invoke(
     callback_handler: &CallbackHandlerFn,
     llm_model: &ModelMetadata,
     key_credentials: Option<&Credentials>,
-    req: HttpRequest,
+    providers_config: Option<&ProvidersConfig>,
     tags: HashMap<String, String>,
 ) -> Result<CreateEmbeddingResponse, GatewayError> {
     // 从 tags 获取 tenant_id
@@ -37,6 +44,7 @@ pub async fn handle_embeddings_invoke(
     let span = Span::current();
     span.record("tenant_id", &tenant_id);
     request.model = llm_model.inference_provider.model_name.clone();
+    request.dimensions = request.dimensions.or(llm_model.dimensions);
 
     let params = OpenAiEmbeddingParams {
         model: Some(llm_model.model.clone()),
@@ -70,11 +78,10 @@ pub async fn handle_embeddings_invoke(
         }
     });
 
-    let providers_config = req.app_data::<ProvidersConfig>().cloned();
     let mut custom_endpoint = llm_model.inference_provider.endpoint.clone();
     let key = match get_key_credentials(
         key_credentials,
-        providers_config.as_ref(),
+        providers_config,
         &llm_model.inference_provider.provider.to_string(),
     ) {
         Some(Credentials::ApiKey(key)) => Some(key),
@@ -87,6 +94,15 @@ pub async fn handle_embeddings_invoke(
         }
         _ => None,
     };
+    // OpenAI-compatible providers fall back to their own key, as chat completions do
+    let key = key.or_else(|| match &llm_model.inference_provider.provider {
+        InferenceModelProvider::Proxy(provider) => {
+            std::env::var(format!("LANGDB_{}_API_KEY", provider.to_uppercase()))
+                .ok()
+                .map(|api_key| ApiKeyCredentials { api_key })
+        }
+        _ => None,
+    });
 
     let _provider_name = &llm_model.inference_provider.provider.to_string();
     // Provider selection: instantiate the correct Embed implementation
@@ -108,19 +124,452 @@ pub async fn handle_embeddings_invoke(
                 logit_bias: None,
                 user: None,
                 n: None,
+                dimensions: request.dimensions,
             };
             Box::new(OllamaEmbed::new(
                 params,
                 key.as_ref(),
                 custom_endpoint.as_deref(),
-            ))
+            )?)
         }
         _ => Box::new(OpenAIEmbed::new(params, key.as_ref(), custom_endpoint.as_deref())?)
     };
     
+    let flight_key = EmbeddingFlightKey {
+        provider: llm_model.inference_provider.provider.to_string(),
+        model: llm_model.model.clone(),
+        endpoint: custom_endpoint.clone(),
+        credentials: key.as_ref().map(|key| credential_fingerprint(&key.api_key)),
+        dimensions: request.dimensions,
+        input: serde_json::to_string(&request.input)?,
+    };
+    let credentials_ident = match key {
+        Some(_) => CredentialsIdent::Own,
+        None => CredentialsIdent::Langdb,
+    };
+    let shared_tx = tx.clone();
+
     // 调用 embedding API
-    embed
-        .invoke(request.input.clone(), Some(tx.clone()))
-        .instrument(span.clone())
+    let call_span = span.clone();
+    let (result, shared) = coalesce(flight_key, async move {
+        match request.input {
+            Input::Array(inputs) if inputs.len() > 1 => {
+                embed_inputs(embed.as_ref(), inputs, tx)
+                    .instrument(call_span)
+                    .await
+            }
+            input => embed.invoke(input, Some(tx)).instrument(call_span).await,
+        }
+    })
+    .await;
+
+    // A shared call reported its usage to the request that made it, the others report
+    // the same usage as their own
+    if let (Ok(response), true) = (&result, shared) {
+        let event = ModelEvent::new(
+            &span,
+            ModelEventType::LlmStop(LLMFinishEvent {
+                provider_name: llm_model.inference_provider.provider.to_string(),
+                model_name: llm_model.model.clone(),
+                output: None,
+                usage: Some(CompletionModelUsage {
+                    input_tokens: response.usage.prompt_tokens,
+                    output_tokens: 0,
+                    total_tokens: response.usage.total_tokens,
+                    ..Default::default()
+                }),
+                finish_reason: ModelFinishReason::Stop,
+                tool_calls: vec![],
+                credentials_ident,
+                logprobs: None,
+                service_tier: None,
+                response_id: None,
+            }),
+        );
+        if let Err(e) = shared_tx.send(Some(event)).await {
+            tracing::warn!("Failed to report the usage of a shared embedding call: {e}");
+        }
+    }
+
+    result
+}
+
+/// What decides the response of an embedding request
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct EmbeddingFlightKey {
+    provider: String,
+    model: String,
+    endpoint: Option<String>,
+    /// Fingerprint of the provider key, a response is only shared with requests paying
+    /// with the same key
+    credentials: Option<String>,
+    dimensions: Option<u16>,
+    input: String,
+}
+
+type EmbeddingFlight = Arc<tokio::sync::OnceCell<Result<CreateEmbeddingResponse, String>>>;
+type EmbeddingFlights = parking_lot::Mutex<HashMap<EmbeddingFlightKey, EmbeddingFlight>>;
+
+/// Embedding requests being sent upstream, shared with identical requests arriving meanwhile
+static EMBEDDING_FLIGHTS: LazyLock<EmbeddingFlights> = LazyLock::new(Default::default);
+
+/// Runs `call` unless an identical request is already in flight, in which case that
+/// request's response is returned instead, together with whether it was shared
+async fn coalesce(
+    key: EmbeddingFlightKey,
+    call: impl Future<Output = Result<CreateEmbeddingResponse, GatewayError>>,
+) -> (Result<CreateEmbeddingResponse, GatewayError>, bool) {
+    let flight = EMBEDDING_FLIGHTS
+        .lock()
+        .entry(key.clone())
+        .or_default()
+        .clone();
+
+    let mut own_error = None;
+    let mut shared = true;
+    let result = flight
+        .get_or_init(|| async {
+            shared = false;
+            call.await.map_err(|e| {
+                let message = e.to_string();
+                own_error = Some(e);
+                message
+            })
+        })
+        .await
+        .clone();
+
+    {
+        let mut flights = EMBEDDING_FLIGHTS.lock();
+        if flights.get(&key).is_some_and(|f| Arc::ptr_eq(f, &flight)) {
+            flights.remove(&key);
+        }
+    }
+
+    let result = match (result, own_error) {
+        (_, Some(e)) => Err(e),
+        (Ok(response), None) => Ok(response),
+        (Err(message), None) => Err(GatewayError::CustomError(message)),
+    };
+    (result, shared)
+}
+
+/// Embedding calls made at once when the inputs of a failed batch are retried one by one
+const INPUT_RETRY_CONCURRENCY: usize = 8;
+
+/// Embeds `inputs` in a single call. When the provider rejects the batch, each input is
+/// embedded on its own so one bad input doesn't fail the others, and the inputs that still
+/// fail are reported in `errors`. Fails only when no input could be embedded.
+async fn embed_inputs(
+    embed: &dyn Embed,
+    inputs: Vec<String>,
+    tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+) -> Result<CreateEmbeddingResponse, GatewayError> {
+    let batch_error = match embed
+        .invoke(Input::Array(inputs.clone()), Some(tx.clone()))
+        .await
+    {
+        Ok(response) => return Ok(response),
+        Err(e) => e,
+    };
+    tracing::warn!("Embedding batch failed, embedding its inputs one by one: {batch_error}");
+
+    let results = futures::stream::iter(inputs)
+        .map(|input| embed.invoke(Input::String(input), Some(tx.clone())))
+        .buffered(INPUT_RETRY_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut response = CreateEmbeddingResponse {
+        object: "list".to_string(),
+        data: vec![],
+        model: String::new(),
+        usage: EmbeddingUsage {
+            prompt_tokens: 0,
+            total_tokens: 0,
+        },
+        errors: vec![],
+    };
+    for (index, result) in results.into_iter().enumerate() {
+        let index = index as u32;
+        match result {
+            Ok(single) => {
+                response.model = single.model;
+                response.usage.prompt_tokens += single.usage.prompt_tokens;
+                response.usage.total_tokens += single.usage.total_tokens;
+                response
+                    .data
+                    .extend(single.data.into_iter().map(|mut data| {
+                        data.index = index;
+                        data
+                    }));
+            }
+            Err(e) => response.errors.push(EmbeddingInputError {
+                index,
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    if response.data.is_empty() {
+        return Err(batch_error);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::InferenceProvider;
+    use crate::types::gateway::EmbeddingVector;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Embeds like OpenAI, which rejects a whole batch when one input is empty. Embeddings
+    /// start with the length of the input and have 2 values unless `dimensions` is set.
+    async fn embeddings(body: web::Json<serde_json::Value>) -> HttpResponse {
+        let inputs = match &body["input"] {
+            serde_json::Value::Array(inputs) => inputs.clone(),
+            input => vec![input.clone()],
+        };
+        if inputs.iter().any(|input| input.as_str() == Some("")) {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": {
+                    "message": "'$.input' is invalid",
+                    "type": "invalid_request_error",
+                    "param": null,
+                    "code": null
+                }
+            }));
+        }
+        let dimensions = body["dimensions"].as_u64().unwrap_or(2) as usize;
+        let data = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let mut embedding = vec![1.0; dimensions];
+                embedding[0] = input.as_str().unwrap().len() as f32;
+                serde_json::json!({"object": "embedding", "embedding": embedding, "index": index})
+            })
+            .collect::<Vec<_>>();
+        HttpResponse::Ok().json(serde_json::json!({
+            "object": "list",
+            "data": data,
+            "model": body["model"],
+            "usage": {"prompt_tokens": inputs.len(), "total_tokens": inputs.len()}
+        }))
+    }
+
+    fn embedding_model(endpoint: String) -> ModelMetadata {
+        ModelMetadata {
+            model: "text-embedding-3-small".to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: InferenceProvider {
+                provider: InferenceModelProvider::OpenAI,
+                model_name: "text-embedding-3-small".to_string(),
+                endpoint: Some(endpoint),
+            },
+            ..Default::default()
+        }
+    }
+
+    async fn embed(
+        model: &ModelMetadata,
+        inputs: &[&str],
+        dimensions: Option<u16>,
+    ) -> Result<CreateEmbeddingResponse, GatewayError> {
+        embed_with(
+            model,
+            inputs,
+            dimensions,
+            "sk-test",
+            &CallbackHandlerFn(None),
+        )
+        .await
+    }
+
+    async fn embed_with(
+        model: &ModelMetadata,
+        inputs: &[&str],
+        dimensions: Option<u16>,
+        api_key: &str,
+        callback_handler: &CallbackHandlerFn,
+    ) -> Result<CreateEmbeddingResponse, GatewayError> {
+        let request = CreateEmbeddingRequest {
+            model: "openai/text-embedding-3-small".to_string(),
+            input: Input::Array(inputs.iter().map(|i| i.to_string()).collect()),
+            user: None,
+            dimensions,
+            encoding_format: Default::default(),
+        };
+        handle_embeddings_invoke(
+            request,
+            callback_handler,
+            model,
+            Some(&Credentials::ApiKey(ApiKeyCredentials {
+                api_key: api_key.to_string(),
+            })),
+            None,
+            HashMap::new(),
+        )
+        .await
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_inputs_dont_fail_the_batch() {
+        let server =
+            HttpServer::new(|| App::new().route("/v1/embeddings", web::post().to(embeddings)))
+                .workers(1)
+                .bind("127.0.0.1:0")
+                .unwrap();
+        let model = embedding_model(format!("http://{}/v1", server.addrs()[0]));
+        actix_web::rt::spawn(server.run());
+
+        let response = embed(&model, &["refund", "", "hello there"], None)
+            .await
+            .unwrap();
+        let embedded = response
+            .data
+            .iter()
+            .map(|data| (data.index, data.embedding.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            embedded,
+            vec![(0, vec![6.0, 1.0].into()), (2, vec![11.0, 1.0].into())]
+        );
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0].index, 1);
+        assert!(response.errors[0].error.contains("'$.input' is invalid"));
+        assert_eq!(response.usage.total_tokens, 2);
+
+        // A valid batch is embedded in one call
+        let response = embed(&model, &["refund", "hello there"], None)
+            .await
+            .unwrap();
+        assert_eq!(response.data.len(), 2);
+        assert!(response.errors.is_empty());
+
+        assert!(embed(&model, &["", ""], None).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_model_default_dimensions() {
+        let server =
+            HttpServer::new(|| App::new().route("/v1/embeddings", web::post().to(embeddings)))
+                .workers(1)
+                .bind("127.0.0.1:0")
+                .unwrap();
+        let mut model = embedding_model(format!("http://{}/v1", server.addrs()[0]));
+        actix_web::rt::spawn(server.run());
+
+        let length = |response: CreateEmbeddingResponse| match &response.data[0].embedding {
+            EmbeddingVector::Float(values) => values.len(),
+            EmbeddingVector::Base64(_) => panic!("Expected float embedding"),
+        };
+
+        model.dimensions = Some(256);
+        let response = embed(&model, &["refund"], None).await.unwrap();
+        assert_eq!(length(response), 256);
+
+        // The request's dimensions take precedence
+        let response = embed(&model, &["refund"], Some(64)).await.unwrap();
+        assert_eq!(length(response), 64);
+
+        model.dimensions = None;
+        let response = embed(&model, &["refund"], None).await.unwrap();
+        assert_eq!(length(response), 2);
+    }
+
+    /// Embedding model of a slow provider, together with the number of calls it received
+    fn slow_embedding_model() -> (ModelMetadata, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let server_calls = calls.clone();
+        let server = HttpServer::new(move || {
+            let calls = server_calls.clone();
+            App::new().route(
+                "/v1/embeddings",
+                web::post().to(move |body: web::Json<serde_json::Value>| {
+                    let calls = calls.clone();
+                    async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        // Slow enough for all requests to arrive while the first is in flight
+                        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                        embeddings(body).await
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let model = embedding_model(format!("http://{}/v1", server.addrs()[0]));
+        actix_web::rt::spawn(server.run());
+        (model, calls)
+    }
+
+    #[actix_web::test]
+    async fn test_concurrent_identical_requests_share_one_call() {
+        let (model, calls) = slow_embedding_model();
+
+        let responses =
+            futures::future::join_all((0..5).map(|_| embed(&model, &["coalesced"], None))).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        for response in responses {
+            let response = response.unwrap();
+            assert_eq!(response.data.len(), 1);
+            assert_eq!(response.data[0].embedding, vec![9.0, 1.0].into());
+        }
+
+        // Once the call is done, the next identical request goes upstream again
+        embed(&model, &["coalesced"], None).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Different inputs aren't coalesced
+        futures::future::join_all([
+            embed(&model, &["coalesced"], None),
+            embed(&model, &["separate"], None),
+        ])
+        .await;
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[actix_web::test]
+    async fn test_requests_with_other_keys_are_not_coalesced() {
+        let (model, calls) = slow_embedding_model();
+        let callback_handler = CallbackHandlerFn(None);
+
+        futures::future::join_all([
+            embed_with(&model, &["keyed"], None, "sk-first", &callback_handler),
+            embed_with(&model, &["keyed"], None, "sk-second", &callback_handler),
+        ])
+        .await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_shared_calls_report_usage_per_request() {
+        let (model, calls) = slow_embedding_model();
+        let (events_tx, mut events_rx) = tokio::sync::broadcast::channel(64);
+        let callback_handler = CallbackHandlerFn(Some(events_tx));
+
+        futures::future::join_all(
+            (0..3).map(|_| embed_with(&model, &["reported"], None, "sk-test", &callback_handler)),
+        )
+        .await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Events reach the callback handler in the background
+        let mut usages = vec![];
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while usages.len() < 3 {
+                let event = events_rx.recv().await.unwrap();
+                if let ModelEventType::LlmStop(stop) = event.event.event {
+                    usages.push(stop.usage.unwrap());
+                }
+            }
+        })
         .await
+        .unwrap();
+        assert!(usages.iter().all(|usage| usage.input_tokens == 1));
+    }
 }
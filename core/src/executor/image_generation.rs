@@ -4,11 +4,13 @@ use std::sync::Arc;
 use crate::handler::CallbackHandlerFn;
 use crate::handler::ModelEventWithDetails;
 use crate::llm_gateway::provider::Provider;
-use crate::model::image_generation::initialize_image_generation;
-use crate::model::types::ModelEvent;
+use crate::model::image_generation::{initialize_image_generation, ImageGenerationModelInstance};
+use crate::model::types::{ImageGenerationFinishEvent, ModelEvent};
 use crate::models::ModelMetadata;
 use crate::types::engine::ImageGenerationModelDefinition;
-use crate::types::gateway::CreateImageRequest;
+use crate::types::gateway::{
+    CreateImageEditRequest, CreateImageRequest, CreateImageVariationRequest,
+};
 use crate::types::image::ImagesResponse;
 use crate::types::provider::InferenceModelProvider;
 use crate::GatewayError;
@@ -21,6 +23,7 @@ use crate::{
     },
 };
 use actix_web::HttpRequest;
+use tokio::task::JoinHandle;
 use tracing::Span;
 use tracing_futures::Instrument;
 
@@ -39,13 +42,118 @@ pub async fn handle_image_generation(
     let span = Span::current();
     request.model = llm_model.inference_provider.model_name.clone();
 
+    let (model, tx, handle) = init_image_model(
+        callback_handler,
+        llm_model,
+        key_credentials,
+        cost_calculator,
+        &req,
+    )
+    .await?;
+
+    let result = model
+        .create_new(&request, tx, tags.clone())
+        .instrument(span.clone())
+        .await?;
+
+    handle
+        .await
+        .map_err(|e| GatewayError::CustomError(e.to_string()))?;
+
+    Ok(result)
+}
+
+pub async fn handle_image_edit(
+    mut request: CreateImageEditRequest,
+    callback_handler: &CallbackHandlerFn,
+    llm_model: &ModelMetadata,
+    key_credentials: Option<&Credentials>,
+    cost_calculator: Arc<Box<dyn CostCalculator>>,
+    tags: HashMap<String, String>,
+    req: HttpRequest,
+) -> Result<ImagesResponse, GatewayError> {
+    let span = Span::current();
+    request.model = llm_model.inference_provider.model_name.clone();
+
+    let (model, tx, handle) = init_image_model(
+        callback_handler,
+        llm_model,
+        key_credentials,
+        cost_calculator,
+        &req,
+    )
+    .await?;
+
+    let result = model
+        .edit(&request, tx, tags.clone())
+        .instrument(span.clone())
+        .await?;
+
+    handle
+        .await
+        .map_err(|e| GatewayError::CustomError(e.to_string()))?;
+
+    Ok(result)
+}
+
+pub async fn handle_image_variation(
+    mut request: CreateImageVariationRequest,
+    callback_handler: &CallbackHandlerFn,
+    llm_model: &ModelMetadata,
+    key_credentials: Option<&Credentials>,
+    cost_calculator: Arc<Box<dyn CostCalculator>>,
+    tags: HashMap<String, String>,
+    req: HttpRequest,
+) -> Result<ImagesResponse, GatewayError> {
+    let span = Span::current();
+    request.model = llm_model.inference_provider.model_name.clone();
+
+    let (model, tx, handle) = init_image_model(
+        callback_handler,
+        llm_model,
+        key_credentials,
+        cost_calculator,
+        &req,
+    )
+    .await?;
+
+    let result = model
+        .create_variation(&request, tx, tags.clone())
+        .instrument(span.clone())
+        .await?;
+
+    handle
+        .await
+        .map_err(|e| GatewayError::CustomError(e.to_string()))?;
+
+    Ok(result)
+}
+
+type ImageModelWithEvents = (
+    Box<dyn ImageGenerationModelInstance>,
+    tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+    JoinHandle<Option<ImageGenerationFinishEvent>>,
+);
+
+/// Builds the image model for `llm_model` and spawns forwarding of its events to the callback handler
+async fn init_image_model(
+    callback_handler: &CallbackHandlerFn,
+    llm_model: &ModelMetadata,
+    key_credentials: Option<&Credentials>,
+    cost_calculator: Arc<Box<dyn CostCalculator>>,
+    req: &HttpRequest,
+) -> Result<ImageModelWithEvents, GatewayError> {
     let providers_config = req.app_data::<ProvidersConfig>().cloned();
     let key = get_key_credentials(
         key_credentials,
         providers_config.as_ref(),
         &llm_model.inference_provider.provider.to_string(),
     );
-    let engine = Provider::get_image_engine_for_model(llm_model, &request, key.as_ref())?;
+    let engine = Provider::get_image_engine_for_model(
+        llm_model,
+        &llm_model.inference_provider.model_name,
+        key.as_ref(),
+    )?;
 
     let api_provider_name = match &llm_model.inference_provider.provider {
         InferenceModelProvider::Proxy(provider) => provider.clone(),
@@ -70,7 +178,6 @@ pub async fn handle_image_generation(
         db_model: db_model.clone(),
     };
 
-    let cost_calculator = cost_calculator.clone();
     let callback_handler = callback_handler.clone();
     let (tx, mut rx) = tokio::sync::mpsc::channel::<Option<ModelEvent>>(1000);
 
@@ -92,20 +199,13 @@ pub async fn handle_image_generation(
     });
 
     let model = initialize_image_generation(
-        image_model_definition.clone(),
-        Some(cost_calculator.clone()),
+        image_model_definition,
+        Some(cost_calculator),
         llm_model.inference_provider.endpoint.as_deref(),
         Some(llm_model.model_provider.as_str()),
     )
     .await
     .map_err(|e| GatewayError::CustomError(e.to_string()))?;
 
-    let result = model
-        .create_new(&request, tx, tags.clone())
-        .instrument(span.clone())
-        .await?;
-
-    let _stop_event = handle.await.unwrap();
-
-    Ok(result)
+    Ok((model, tx, handle))
 }
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use context::ExecutorContext;
+use provider_keys::{ProviderCredentials, RotatingKeys};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -16,10 +17,28 @@ pub mod chat_completion;
 pub mod context;
 pub mod embeddings;
 pub mod image_generation;
+pub mod provider_keys;
 pub mod responses;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
-pub struct ProvidersConfig(pub HashMap<String, ApiKeyCredentials>);
+pub struct ProvidersConfig(pub HashMap<String, ProviderCredentials>);
+
+impl ProvidersConfig {
+    /// Key to use for the next request to `provider_name`
+    pub fn select_key(&self, provider_name: &str) -> Option<ApiKeyCredentials> {
+        self.0
+            .get(provider_name)
+            .and_then(ProviderCredentials::select)
+    }
+
+    /// Keys `provider_name` rotates over, when several are configured for it
+    pub fn rotating_keys(&self, provider_name: &str) -> Option<&RotatingKeys> {
+        match self.0.get(provider_name) {
+            Some(ProviderCredentials::Rotating(keys)) => Some(keys),
+            _ => None,
+        }
+    }
+}
 
 pub fn get_key_credentials(
     key_credentials: Option<&Credentials>,
@@ -30,9 +49,8 @@ pub fn get_key_credentials(
         Some(credentials) => Some(credentials.clone()),
         None => match providers_config {
             Some(providers_config) => providers_config
-                .0
-                .get(provider_name)
-                .map(|credentials| Credentials::ApiKey(credentials.clone())),
+                .select_key(provider_name)
+                .map(Credentials::ApiKey),
             None => None,
         },
     }
@@ -46,9 +64,9 @@ pub fn get_langdb_proxy_key(
         key_credentials,
         providers_config
             .as_ref()
-            .and_then(|p| p.0.get("langdb_proxy")),
+            .and_then(|p| p.select_key("langdb_proxy")),
     ) {
-        (None, Some(key)) => Some(Credentials::ApiKey(key.clone())),
+        (None, Some(key)) => Some(Credentials::ApiKey(key)),
         (credentials, _) => credentials,
     }
 }
@@ -62,10 +80,10 @@ pub fn use_langdb_proxy(
         executor_context
             .providers_config
             .as_ref()
-            .and_then(|p| p.0.get("langdb_proxy")),
+            .and_then(|p| p.select_key("langdb_proxy")),
     ) {
         (None, Some(key)) => (
-            Some(Credentials::ApiKey(key.clone())),
+            Some(Credentials::ApiKey(key)),
             Some(format!(
                 "{}/v1",
                 std::env::var("LANGDB_API_URL")
@@ -0,0 +1,371 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::model::types::ModelEvent;
+use crate::model::ModelInstance;
+use crate::types::credentials::ApiKeyCredentials;
+use crate::types::gateway::ChatCompletionMessage;
+use crate::types::threads::Message;
+use crate::{GatewayError, GatewayResult};
+
+/// Credentials configured for a provider: either one API key or several to spread requests across
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProviderCredentials {
+    Key(ApiKeyCredentials),
+    Rotating(RotatingKeys),
+}
+
+impl ProviderCredentials {
+    /// Key to use for the next request
+    pub fn select(&self) -> Option<ApiKeyCredentials> {
+        match self {
+            ProviderCredentials::Key(credentials) => Some(credentials.clone()),
+            ProviderCredentials::Rotating(keys) => keys.select(),
+        }
+    }
+}
+
+impl From<ApiKeyCredentials> for ProviderCredentials {
+    fn from(credentials: ApiKeyCredentials) -> Self {
+        ProviderCredentials::Key(credentials)
+    }
+}
+
+/// How the next key is picked among the keys of a provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySelection {
+    /// Keys take turns, each getting a share of requests proportional to its weight
+    #[default]
+    RoundRobin,
+    /// The key that served the fewest requests relative to its weight
+    LeastUsed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeightedKey {
+    pub api_key: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_cooldown_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Default)]
+struct KeyState {
+    current_weight: i64,
+    served: u64,
+    limited_until: Option<Instant>,
+}
+
+/// Several API keys of one provider. Keys the provider rate limited are skipped until their
+/// cooldown passes, unless every key is rate limited. Keys with a weight of 0 are skipped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RotatingKeys {
+    pub api_keys: Vec<WeightedKey>,
+    #[serde(default)]
+    pub selection: KeySelection,
+    /// Seconds a rate limited key is skipped when the provider doesn't say how long to wait
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    /// Shared by every clone, so all workers rotate over the same keys
+    #[serde(skip)]
+    state: Arc<Mutex<Vec<KeyState>>>,
+}
+
+impl RotatingKeys {
+    pub fn new(api_keys: Vec<WeightedKey>, selection: KeySelection) -> Self {
+        Self {
+            api_keys,
+            selection,
+            cooldown_secs: default_cooldown_secs(),
+            state: Default::default(),
+        }
+    }
+
+    fn weight(&self, index: usize) -> i64 {
+        self.api_keys[index].weight as i64
+    }
+
+    pub fn select(&self) -> Option<ApiKeyCredentials> {
+        self.select_at(Instant::now())
+    }
+
+    fn select_at(&self, now: Instant) -> Option<ApiKeyCredentials> {
+        if self.api_keys.is_empty() {
+            return None;
+        }
+
+        let mut state = self.state.lock();
+        state.resize_with(self.api_keys.len(), Default::default);
+
+        // Keys with a weight of 0 are drained and never picked
+        let weighted = (0..self.api_keys.len())
+            .filter(|&i| self.api_keys[i].weight > 0)
+            .collect::<Vec<_>>();
+        let available = weighted
+            .iter()
+            .copied()
+            .filter(|&i| state[i].limited_until.map_or(true, |until| until <= now))
+            .collect::<Vec<_>>();
+        let candidates = if available.is_empty() {
+            weighted
+        } else {
+            available
+        };
+
+        let chosen = match self.selection {
+            // Smooth weighted round-robin: heavier keys are picked more often, but spread out
+            KeySelection::RoundRobin => {
+                let total = candidates.iter().map(|&i| self.weight(i)).sum::<i64>();
+                for &i in &candidates {
+                    state[i].current_weight += self.weight(i);
+                }
+                let chosen = *candidates
+                    .iter()
+                    .max_by_key(|&&i| (state[i].current_weight, Reverse(i)))?;
+                state[chosen].current_weight -= total;
+                chosen
+            }
+            KeySelection::LeastUsed => *candidates.iter().min_by(|&&a, &&b| {
+                (state[a].served as i64 * self.weight(b))
+                    .cmp(&(state[b].served as i64 * self.weight(a)))
+            })?,
+        };
+        state[chosen].served += 1;
+
+        Some(ApiKeyCredentials {
+            api_key: self.api_keys[chosen].api_key.clone(),
+        })
+    }
+
+    /// Skips `api_key` for `retry_after` seconds, or the configured cooldown
+    pub fn report_rate_limited(&self, api_key: &str, retry_after: Option<u64>) {
+        self.report_rate_limited_at(api_key, retry_after, Instant::now())
+    }
+
+    fn report_rate_limited_at(&self, api_key: &str, retry_after: Option<u64>, now: Instant) {
+        let Some(index) = self.api_keys.iter().position(|k| k.api_key == api_key) else {
+            return;
+        };
+        let cooldown = Duration::from_secs(retry_after.unwrap_or(self.cooldown_secs));
+        let mut state = self.state.lock();
+        state.resize_with(self.api_keys.len(), Default::default);
+        state[index].limited_until = Some(now + cooldown);
+    }
+}
+
+/// Model called with one of the keys of `keys`, reporting the key when the provider rate limits it
+pub struct RotatedKeyModel {
+    inner: Box<dyn ModelInstance>,
+    keys: RotatingKeys,
+    api_key: String,
+}
+
+impl RotatedKeyModel {
+    pub fn new(inner: Box<dyn ModelInstance>, keys: RotatingKeys, api_key: String) -> Self {
+        Self {
+            inner,
+            keys,
+            api_key,
+        }
+    }
+
+    fn report_rate_limit<T>(&self, result: &GatewayResult<T>) {
+        if let Err(GatewayError::RateLimited { retry_after, .. }) = result {
+            self.keys.report_rate_limited(&self.api_key, *retry_after);
+        }
+    }
+}
+
+#[async_trait]
+impl ModelInstance for RotatedKeyModel {
+    async fn invoke(
+        &self,
+        input_vars: HashMap<String, Value>,
+        tx: mpsc::Sender<Option<ModelEvent>>,
+        previous_messages: Vec<Message>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ChatCompletionMessage> {
+        let result = self
+            .inner
+            .invoke(input_vars, tx, previous_messages, tags)
+            .await;
+        self.report_rate_limit(&result);
+        result
+    }
+
+    async fn stream(
+        &self,
+        input_vars: HashMap<String, Value>,
+        tx: mpsc::Sender<Option<ModelEvent>>,
+        previous_messages: Vec<Message>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<()> {
+        let result = self
+            .inner
+            .stream(input_vars, tx, previous_messages, tags)
+            .await;
+        self.report_rate_limit(&result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(selection: KeySelection, weights: &[(&str, u32)]) -> RotatingKeys {
+        RotatingKeys::new(
+            weights
+                .iter()
+                .map(|(api_key, weight)| WeightedKey {
+                    api_key: api_key.to_string(),
+                    weight: *weight,
+                })
+                .collect(),
+            selection,
+        )
+    }
+
+    fn picks(keys: &RotatingKeys, count: usize, now: Instant) -> Vec<String> {
+        (0..count)
+            .map(|_| keys.select_at(now).unwrap().api_key)
+            .collect()
+    }
+
+    #[test]
+    fn test_requests_rotate_across_keys() {
+        let now = Instant::now();
+        let keys = pool(KeySelection::RoundRobin, &[("a", 2), ("b", 1)]);
+        assert_eq!(picks(&keys, 6, now), ["a", "b", "a", "a", "b", "a"]);
+
+        // Clones share the rotation
+        let clone = keys.clone();
+        assert_eq!(picks(&clone, 3, now), ["a", "b", "a"]);
+
+        let keys = pool(KeySelection::LeastUsed, &[("a", 1), ("b", 1), ("c", 1)]);
+        assert_eq!(picks(&keys, 4, now), ["a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_rate_limited_key_is_skipped_until_cooldown() {
+        let now = Instant::now();
+        let keys = pool(KeySelection::RoundRobin, &[("a", 1), ("b", 1)]);
+
+        keys.report_rate_limited_at("a", Some(30), now);
+        assert_eq!(picks(&keys, 3, now), ["b", "b", "b"]);
+
+        let later = now + Duration::from_secs(31);
+        assert!(picks(&keys, 2, later).contains(&"a".to_string()));
+
+        // With every key rate limited requests still go out
+        keys.report_rate_limited_at("a", None, later);
+        keys.report_rate_limited_at("b", None, later);
+        assert_eq!(picks(&keys, 2, later).len(), 2);
+    }
+
+    #[test]
+    fn test_zero_weight_keys_are_drained() {
+        let now = Instant::now();
+        for selection in [KeySelection::RoundRobin, KeySelection::LeastUsed] {
+            let keys = pool(selection, &[("a", 0), ("b", 1), ("c", 1)]);
+            assert!(!picks(&keys, 6, now).contains(&"a".to_string()));
+
+            // Also when every other key is rate limited
+            keys.report_rate_limited_at("b", Some(30), now);
+            keys.report_rate_limited_at("c", Some(30), now);
+            assert!(!picks(&keys, 4, now).contains(&"a".to_string()));
+        }
+
+        let keys = pool(KeySelection::RoundRobin, &[("a", 0)]);
+        assert!(keys.select_at(now).is_none());
+    }
+
+    /// Model the provider always rate limits
+    struct RateLimitedModel;
+
+    #[async_trait]
+    impl ModelInstance for RateLimitedModel {
+        async fn invoke(
+            &self,
+            _input_vars: HashMap<String, Value>,
+            _tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<ChatCompletionMessage> {
+            Err(GatewayError::RateLimited {
+                provider: "openai".to_string(),
+                retry_after: Some(30),
+            })
+        }
+
+        async fn stream(
+            &self,
+            _input_vars: HashMap<String, Value>,
+            _tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<()> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_call_skips_its_key() {
+        let keys = pool(KeySelection::RoundRobin, &[("a", 1), ("b", 1)]);
+        let api_key = keys.select().unwrap().api_key;
+        assert_eq!(api_key, "a");
+
+        let model = RotatedKeyModel::new(Box::new(RateLimitedModel), keys.clone(), api_key);
+        let (tx, _rx) = mpsc::channel(16);
+        let result = model
+            .invoke(HashMap::new(), tx, vec![], HashMap::new())
+            .await;
+        assert!(matches!(result, Err(GatewayError::RateLimited { .. })));
+
+        assert_eq!(picks(&keys, 3, Instant::now()), ["b", "b", "b"]);
+    }
+
+    #[test]
+    fn test_single_and_multiple_keys_are_parsed() {
+        let providers: HashMap<String, ProviderCredentials> =
+            serde_json::from_value(serde_json::json!({
+                "openai": {"api_key": "sk-one"},
+                "anthropic": {
+                    "api_keys": [{"api_key": "sk-a", "weight": 3}, {"api_key": "sk-b"}],
+                    "selection": "least_used"
+                }
+            }))
+            .unwrap();
+
+        assert!(matches!(
+            &providers["openai"],
+            ProviderCredentials::Key(ApiKeyCredentials { api_key }) if api_key == "sk-one"
+        ));
+        let ProviderCredentials::Rotating(keys) = &providers["anthropic"] else {
+            panic!("expected several keys");
+        };
+        assert_eq!(keys.api_keys[0].weight, 3);
+        assert_eq!(keys.api_keys[1].weight, 1);
+        assert_eq!(keys.selection, KeySelection::LeastUsed);
+        assert_eq!(keys.cooldown_secs, 60);
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tokio::sync::Mutex;
+
+use crate::model::rate_limits::upstream_rate_limits;
+use crate::usage::InMemoryStorage;
+use crate::GatewayApiError;
+
+use super::model_access::extract_api_key;
+
+/// Key required by the admin endpoints. They reject every request when it isn't configured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminConfig {
+    pub api_key: String,
+}
+
+fn authorize(req: &HttpRequest) -> Result<(), GatewayApiError> {
+    match (req.app_data::<AdminConfig>(), extract_api_key(req)) {
+        (Some(config), Some(key))
+            if bool::from(key.as_bytes().ct_eq(config.api_key.as_bytes())) =>
+        {
+            Ok(())
+        }
+        _ => Err(GatewayApiError::Unauthorized),
+    }
+}
+
+fn storage(req: &HttpRequest) -> Result<Arc<Mutex<InMemoryStorage>>, GatewayApiError> {
+    req.app_data::<Arc<Mutex<InMemoryStorage>>>()
+        .cloned()
+        .ok_or_else(|| GatewayApiError::CustomError("Metrics are not collected".to_string()))
+}
+
+/// Returns the collected metrics per provider and model
+pub async fn get_metrics(req: HttpRequest) -> Result<HttpResponse, GatewayApiError> {
+    authorize(&req)?;
+    let metrics = storage(&req)?.lock().await.get_all_counters().await;
+
+    Ok(HttpResponse::Ok().json(metrics))
+}
+
+/// Returns the latest rate-limit headers sent by each provider
+pub async fn get_rate_limits(req: HttpRequest) -> Result<HttpResponse, GatewayApiError> {
+    authorize(&req)?;
+
+    Ok(HttpResponse::Ok().json(upstream_rate_limits().all()))
+}
+
+/// Clears the counters of a single model
+pub async fn reset_model_metrics(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, GatewayApiError> {
+    authorize(&req)?;
+    let (provider, model) = path.into_inner();
+    let removed = storage(&req)?.lock().await.reset_model(&provider, &model);
+    tracing::info!("Reset {removed} counters of {provider}/{model}");
+
+    Ok(HttpResponse::Ok().json(json!({ "removed": removed })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage::LimitPeriod;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+
+    async fn seeded_storage() -> Arc<Mutex<InMemoryStorage>> {
+        let storage = InMemoryStorage::new();
+        for identifier in ["openai:gpt-4o", "anthropic:claude-3-5-sonnet"] {
+            for key in ["requests", "input_tokens", "latency"] {
+                storage
+                    .increment_and_get_value(&LimitPeriod::Total, identifier, key, 10.0)
+                    .await;
+            }
+        }
+        Arc::new(Mutex::new(storage))
+    }
+
+    #[actix_web::test]
+    async fn test_reset_model_metrics() {
+        let storage = seeded_storage().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(storage.clone())
+                .app_data(AdminConfig {
+                    api_key: "admin-key".to_string(),
+                })
+                .route("/admin/metrics", web::get().to(get_metrics))
+                .route(
+                    "/admin/metrics/{provider}/{model}",
+                    web::delete().to(reset_model_metrics),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/metrics")
+            .insert_header(("Authorization", "Bearer admin-key"))
+            .to_request();
+        let metrics: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            metrics["openai"]["models"]["gpt-4o"]["metrics"]["total"]["requests"],
+            10.0
+        );
+
+        let req = test::TestRequest::delete()
+            .uri("/admin/metrics/openai/gpt-4o")
+            .insert_header(("x-api-key", "tenant-key"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(storage
+            .lock()
+            .await
+            .get_all_counters()
+            .await
+            .contains_key("openai"));
+
+        let req = test::TestRequest::delete()
+            .uri("/admin/metrics/openai/gpt-4o")
+            .insert_header(("x-api-key", "admin-key"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["removed"], 3);
+
+        let metrics = storage.lock().await.get_all_counters().await;
+        assert!(!metrics.contains_key("openai"));
+        assert!(metrics.contains_key("anthropic"));
+    }
+
+    #[actix_web::test]
+    async fn test_admin_key_is_required() {
+        let app = test::init_service(
+            App::new()
+                .app_data(seeded_storage().await)
+                .app_data(AdminConfig {
+                    api_key: "admin-key".to_string(),
+                })
+                .route("/admin/metrics", web::get().to(get_metrics)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/metrics")
+            .insert_header(("Authorization", "Bearer other-key"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/admin/metrics").to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_rate_limits_are_served() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-tokens", "4000".parse().unwrap());
+        upstream_rate_limits().record("admin-test", &headers);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(AdminConfig {
+                    api_key: "admin-key".to_string(),
+                })
+                .route("/admin/rate_limits", web::get().to(get_rate_limits)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/rate_limits")
+            .insert_header(("Authorization", "Bearer admin-key"))
+            .to_request();
+        let rate_limits: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            rate_limits["admin-test"]["x-ratelimit-remaining-tokens"],
+            "4000"
+        );
+    }
+}
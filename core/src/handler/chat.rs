@@ -2,13 +2,12 @@ use std::collections::HashMap;
 
 use crate::events::JsonValue;
 use crate::executor::context::ExecutorContext;
-use crate::routing::RoutingStrategy;
+use crate::routing::{apply_route_overrides, RoutingStrategy, ROUTER_HEADER, ROUTE_TARGET_HEADER};
 use crate::types::gateway::ChatCompletionRequestWithTools;
 use crate::types::gateway::CompletionModelUsage;
 use crate::types::gateway::Extra;
-use crate::types::guardrails::service::GuardrailsEvaluator;
 use crate::usage::InMemoryStorage;
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use bytes::Bytes;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -27,7 +26,10 @@ use crate::otel::TraceMap;
 use crate::GatewayApiError;
 
 use super::can_execute_llm_for_request;
+use super::model_access::check_request_model_access;
+use super::reload::{current_default_routers, current_evaluator};
 
+use crate::executor::chat_completion::resolve_user;
 use crate::executor::chat_completion::routed_executor::RoutedExecutor;
 
 pub type SSOChatEvent = (
@@ -42,9 +44,8 @@ pub async fn create_chat_completion(
     callback_handler: web::Data<CallbackHandlerFn>,
     traces: web::Data<TraceMap>,
     req: HttpRequest,
-    provided_models: web::Data<AvailableModels>,
+    provided_models: AvailableModels,
     cost_calculator: web::Data<Box<dyn CostCalculator>>,
-    evaluator_service: web::Data<Box<dyn GuardrailsEvaluator>>,
 ) -> Result<HttpResponse, GatewayApiError> {
     can_execute_llm_for_request(&req).await?;
 
@@ -60,6 +61,7 @@ pub async fn create_chat_completion(
         thread_id = tracing::field::Empty,
         message_id = tracing::field::Empty,
         user = tracing::field::Empty,
+        json_repaired = tracing::field::Empty,
         tenant_id = client_ip.clone(),
     ));
 
@@ -75,11 +77,13 @@ pub async fn create_chat_completion(
 
     let memory_storage = req.app_data::<Arc<Mutex<InMemoryStorage>>>().cloned();
 
-    let guardrails_evaluator_service = evaluator_service.clone().into_inner();
+    let guardrails_evaluator_service = current_evaluator(&req).ok_or_else(|| {
+        GatewayApiError::CustomError("Guardrails evaluator is not configured".to_string())
+    })?;
     let executor_context = ExecutorContext::new(
         callback_handler.get_ref().clone(),
         cost_calculator.into_inner(),
-        provided_models.get_ref().clone(),
+        provided_models,
         &req,
         guardrails_evaluator_service,
     )?;
@@ -88,13 +92,45 @@ pub async fn create_chat_completion(
     tags.insert("tenant_id".to_string(), client_ip);
 
     // 将 tags 传递给 executor
-    let executor = RoutedExecutor::new(request.clone());
+    let mut request = request.into_inner();
+    // Checked before routing, so a missing user is a 400 and not a failure of every target
+    resolve_user(request.request.user.as_ref(), executor_context.require_user)?;
+    let default_routers = current_default_routers(&req);
+    if let Some(default_routers) = &default_routers {
+        default_routers.apply(&mut request);
+    }
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+    apply_route_overrides(
+        &mut request,
+        default_routers.as_ref(),
+        header(ROUTER_HEADER),
+        header(ROUTE_TARGET_HEADER),
+    )?;
+    check_request_model_access(&req, &request, &executor_context.provided_models)?;
+    let executor = RoutedExecutor::new(request);
     executor
         .execute_with_tags(&executor_context, traces.get_ref(), memory_storage, tags)
         .instrument(span.clone())
         .await
 }
 
+/// OpenAI style error payload, sent as the last event of a stream that failed
+fn stream_error(e: &GatewayApiError) -> serde_json::Value {
+    let error_type = if e.status_code().is_client_error() {
+        "invalid_request_error"
+    } else {
+        "server_error"
+    };
+    serde_json::json!({
+        "error": {
+            "message": e.to_string(),
+            "type": error_type,
+            "param": null,
+            "code": null,
+        }
+    })
+}
+
 pub fn map_sso_event(
     delta: Result<SSOChatEvent, GatewayApiError>,
     model_name: String,
@@ -110,7 +146,7 @@ pub fn map_sso_event(
                 model: model_name.clone(),
                 choices: delta.as_ref().map_or(vec![], |d| {
                     vec![ChatCompletionChunkChoice {
-                        index: 0,
+                        index: d.index as i32,
                         delta: d.clone(),
                         finish_reason,
                         logprobs: None,
@@ -135,7 +171,7 @@ pub fn map_sso_event(
                 .unwrap_or_else(|e| format!("{{\"error\": \"Failed to serialize chunk: {e}\"}}"))
         }),
         Err(e) => Some(
-            serde_json::to_string(&HashMap::from([("error", e.to_string())]))
+            serde_json::to_string(&stream_error(&e))
                 .unwrap_or_else(|e| format!("{{\"error\": \"Failed to serialize chunk: {e}\"}}")),
         ),
     };
@@ -146,3 +182,51 @@ pub fn map_sso_event(
 
     Ok(Bytes::from(result))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::chat_completion::RequireUser;
+    use crate::test_utils::{FreeModels, NoGuards};
+    use crate::types::guardrails::service::GuardrailsEvaluator;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn test_missing_user_is_rejected() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(CallbackHandlerFn(None)))
+                .app_data(web::Data::new(TraceMap::new()))
+                .app_data(web::Data::new(AvailableModels(vec![])))
+                .app_data(web::Data::new(
+                    Box::new(FreeModels) as Box<dyn CostCalculator>
+                ))
+                .app_data(web::Data::new(
+                    Box::new(NoGuards) as Box<dyn GuardrailsEvaluator>
+                ))
+                .app_data(RequireUser)
+                .route("/chat/completions", web::post().to(create_chat_completion)),
+        )
+        .await;
+
+        for user in [None, Some(" ")] {
+            let mut body = serde_json::json!({
+                "model": "openai/gpt-4o-mini",
+                "messages": [{"role": "user", "content": "Hi"}],
+            });
+            if let Some(user) = user {
+                body["user"] = user.into();
+            }
+            let req = test::TestRequest::post()
+                .uri("/chat/completions")
+                .set_json(body)
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+            let body: serde_json::Value = test::read_body_json(res).await;
+            assert_eq!(body["error"], "The `user` field is required");
+        }
+    }
+}
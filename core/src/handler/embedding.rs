@@ -1,31 +1,36 @@
 use crate::executor::embeddings::handle_embeddings_invoke;
+use crate::executor::ProvidersConfig;
 use crate::types::credentials::Credentials;
 use actix_web::{web, HttpResponse};
 use actix_web::{HttpMessage, HttpRequest};
+use base64::Engine;
 use std::collections::HashMap;
 use tracing::Span;
 use tracing_futures::Instrument;
 
 use crate::types::gateway::{
     CreateEmbeddingRequest, CreateEmbeddingResponse, EmbeddingData, EmbeddingUsage,
+    EmbeddingVector, EncodingFormat,
 };
 
 use crate::handler::AvailableModels;
 use crate::handler::CallbackHandlerFn;
 use crate::GatewayApiError;
 
-use super::{can_execute_llm_for_request, find_model_by_full_name};
+use super::model_access::check_model_access;
+use super::{body_recording, can_execute_llm_for_request, find_model_by_full_name};
 
 pub async fn embeddings_handler(
     request: web::Json<CreateEmbeddingRequest>,
-    models: web::Data<AvailableModels>,
+    models: AvailableModels,
     callback_handler: web::Data<CallbackHandlerFn>,
     req: HttpRequest,
 ) -> Result<HttpResponse, GatewayApiError> {
     can_execute_llm_for_request(&req).await?;
     let request = request.into_inner();
-    let available_models = models.into_inner();
-    let llm_model = find_model_by_full_name(&request.model, &available_models)?;
+    let encoding_format = request.encoding_format.clone();
+    check_model_access(&req, &request.model, &models)?;
+    let llm_model = find_model_by_full_name(&request.model, &models)?;
     let key_credentials = req.extensions().get::<Credentials>().cloned();
 
     // 获取 client IP 并写入 tags
@@ -40,28 +45,33 @@ pub async fn embeddings_handler(
         message_id = tracing::field::Empty,
         tenant_id = client_ip.clone(),
     ));
-    span.record("request", &serde_json::to_string(&request)?);
+    let body_recording = body_recording(&req);
+    span.record(
+        "request",
+        body_recording.apply(&serde_json::to_string(&request)?),
+    );
 
     let mut tags = HashMap::new();
     tags.insert("tenant_id".to_string(), client_ip);
     // 将 tags 传递给 handle_embeddings_invoke
-    let result = handle_embeddings_invoke(
-        request,
-        callback_handler.get_ref(),
-        &llm_model,
-        key_credentials.as_ref(),
-        req,
-        tags,
-    )
-    .instrument(span)
-    .await?;
+    let result = body_recording
+        .scope(handle_embeddings_invoke(
+            request,
+            callback_handler.get_ref(),
+            &llm_model,
+            key_credentials.as_ref(),
+            req.app_data::<ProvidersConfig>(),
+            tags,
+        ))
+        .instrument(span)
+        .await?;
 
     let data = result
         .data
-        .iter()
+        .into_iter()
         .map(|v| EmbeddingData {
-            object: v.object.clone(),
-            embedding: v.embedding.clone(),
+            object: v.object,
+            embedding: encode_embedding(v.embedding, &encoding_format),
             index: v.index,
         })
         .collect();
@@ -80,5 +90,53 @@ pub async fn embeddings_handler(
                 prompt_tokens: result.usage.prompt_tokens,
                 total_tokens: result.usage.total_tokens,
             },
+            errors: result.errors,
         }))
 }
+
+/// Converts a float embedding to the requested encoding format
+pub fn encode_embedding(embedding: EmbeddingVector, format: &EncodingFormat) -> EmbeddingVector {
+    match (embedding, format) {
+        (EmbeddingVector::Float(values), EncodingFormat::Base64) => {
+            let bytes = values
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect::<Vec<u8>>();
+            EmbeddingVector::Base64(base64::engine::general_purpose::STANDARD.encode(bytes))
+        }
+        (embedding, _) => embedding,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_embedding_roundtrip() {
+        let values = vec![0.0, 1.5, -2.25, 0.0012345, f32::MAX];
+        let EmbeddingVector::Base64(encoded) =
+            encode_embedding(values.clone().into(), &EncodingFormat::Base64)
+        else {
+            panic!("Expected base64 embedding");
+        };
+
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        let decoded = bytes
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn test_float_embedding_untouched() {
+        let values = vec![0.5, -0.5];
+        assert_eq!(
+            encode_embedding(values.clone().into(), &EncodingFormat::Float),
+            EmbeddingVector::Float(values)
+        );
+    }
+}
@@ -1,22 +1,34 @@
-use crate::executor::image_generation::handle_image_generation;
+use crate::executor::image_generation::{
+    handle_image_edit, handle_image_generation, handle_image_variation,
+};
 use crate::handler::record_map_err;
 use crate::handler::AvailableModels;
 use crate::handler::CallbackHandlerFn;
-use crate::types::gateway::CreateImageRequest;
+use crate::models::ModelMetadata;
+use crate::types::gateway::{
+    CreateImageEditRequest, CreateImageRequest, CreateImageVariationRequest, ImageFile,
+    ImageQuality, ImageResponseFormat, ImageSize,
+};
+use crate::types::provider::{InferenceModelProvider, ModelPrice};
 use crate::types::{credentials::Credentials, gateway::CostCalculator};
 use crate::GatewayApiError;
+use actix_multipart::Multipart;
 use actix_web::HttpMessage;
 use actix_web::{web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use std::collections::HashMap;
 use tracing::Span;
 use tracing_futures::Instrument;
 
+use super::body_recording;
 use super::can_execute_llm_for_request;
 use super::extract_tags;
 use super::find_model_by_full_name;
+use super::model_access::check_model_access;
 
 pub async fn create_image(
     request: web::Json<CreateImageRequest>,
-    models: web::Data<AvailableModels>,
+    models: AvailableModels,
     req: HttpRequest,
     cost_calculator: web::Data<Box<dyn CostCalculator>>,
     callback_handler: web::Data<CallbackHandlerFn>,
@@ -24,8 +36,9 @@ pub async fn create_image(
     can_execute_llm_for_request(&req).await?;
 
     let request = request.into_inner();
-    let available_models = models.into_inner();
-    let llm_model = find_model_by_full_name(&request.model, &available_models)?;
+    check_model_access(&req, &request.model, &models)?;
+    let llm_model = find_model_by_full_name(&request.model, &models)?;
+    validate_image_request(&request, &llm_model)?;
 
     let span = Span::or_current(tracing::info_span!(
         target: "langdb::user_tracing::api_invoke",
@@ -35,23 +48,414 @@ pub async fn create_image(
         error = tracing::field::Empty,
         message_id = tracing::field::Empty,
     ));
-    span.record("request", &serde_json::to_string(&request)?);
+    let body_recording = body_recording(&req);
+    span.record(
+        "request",
+        body_recording.apply(&serde_json::to_string(&request)?),
+    );
 
     let tags = extract_tags(&req)?;
 
     let key = req.extensions().get::<Credentials>().cloned();
-    let result = handle_image_generation(
-        request,
-        callback_handler.get_ref(),
-        &llm_model,
-        key.as_ref(),
-        cost_calculator.into_inner(),
-        tags,
-        req,
-    )
-    .instrument(span.clone())
-    .await
-    .map_err(|e| record_map_err(e, span.clone()))?;
+    let result = body_recording
+        .scope(handle_image_generation(
+            request,
+            callback_handler.get_ref(),
+            &llm_model,
+            key.as_ref(),
+            cost_calculator.into_inner(),
+            tags,
+            req,
+        ))
+        .instrument(span.clone())
+        .await
+        .map_err(|e| record_map_err(e, span.clone()))?;
 
     Ok(HttpResponse::Ok().json(result))
 }
+
+pub async fn create_image_edit(
+    payload: Multipart,
+    models: AvailableModels,
+    req: HttpRequest,
+    cost_calculator: web::Data<Box<dyn CostCalculator>>,
+    callback_handler: web::Data<CallbackHandlerFn>,
+) -> Result<HttpResponse, GatewayApiError> {
+    can_execute_llm_for_request(&req).await?;
+
+    let mut form = ImageForm::read(payload).await?;
+    let request = CreateImageEditRequest {
+        image: form.required_file("image")?,
+        mask: form.files.remove("mask"),
+        prompt: form.required_text("prompt")?,
+        model: form.required_text("model")?,
+        n: form.n()?,
+        size: form.size()?,
+        response_format: form.response_format()?,
+        user: form.texts.remove("user"),
+    };
+    check_model_access(&req, &request.model, &models)?;
+    let llm_model = find_model_by_full_name(&request.model, &models)?;
+    check_image_inputs_supported(&llm_model)?;
+    validate_image_options(None, request.size.as_ref(), &llm_model)?;
+
+    let span = Span::or_current(tracing::info_span!(
+        target: "langdb::user_tracing::api_invoke",
+        "api_invoke",
+        request = tracing::field::Empty,
+        response = tracing::field::Empty,
+        error = tracing::field::Empty,
+        message_id = tracing::field::Empty,
+    ));
+    let body_recording = body_recording(&req);
+    span.record(
+        "request",
+        body_recording.apply(&serde_json::to_string(&request)?),
+    );
+
+    let tags = extract_tags(&req)?;
+
+    let key = req.extensions().get::<Credentials>().cloned();
+    let result = body_recording
+        .scope(handle_image_edit(
+            request,
+            callback_handler.get_ref(),
+            &llm_model,
+            key.as_ref(),
+            cost_calculator.into_inner(),
+            tags,
+            req,
+        ))
+        .instrument(span.clone())
+        .await
+        .map_err(|e| record_map_err(e, span.clone()))?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn create_image_variation(
+    payload: Multipart,
+    models: AvailableModels,
+    req: HttpRequest,
+    cost_calculator: web::Data<Box<dyn CostCalculator>>,
+    callback_handler: web::Data<CallbackHandlerFn>,
+) -> Result<HttpResponse, GatewayApiError> {
+    can_execute_llm_for_request(&req).await?;
+
+    let mut form = ImageForm::read(payload).await?;
+    let request = CreateImageVariationRequest {
+        image: form.required_file("image")?,
+        model: form.required_text("model")?,
+        n: form.n()?,
+        size: form.size()?,
+        response_format: form.response_format()?,
+        user: form.texts.remove("user"),
+    };
+    check_model_access(&req, &request.model, &models)?;
+    let llm_model = find_model_by_full_name(&request.model, &models)?;
+    check_image_inputs_supported(&llm_model)?;
+    validate_image_options(None, request.size.as_ref(), &llm_model)?;
+
+    let span = Span::or_current(tracing::info_span!(
+        target: "langdb::user_tracing::api_invoke",
+        "api_invoke",
+        request = tracing::field::Empty,
+        response = tracing::field::Empty,
+        error = tracing::field::Empty,
+        message_id = tracing::field::Empty,
+    ));
+    let body_recording = body_recording(&req);
+    span.record(
+        "request",
+        body_recording.apply(&serde_json::to_string(&request)?),
+    );
+
+    let tags = extract_tags(&req)?;
+
+    let key = req.extensions().get::<Credentials>().cloned();
+    let result = body_recording
+        .scope(handle_image_variation(
+            request,
+            callback_handler.get_ref(),
+            &llm_model,
+            key.as_ref(),
+            cost_calculator.into_inner(),
+            tags,
+            req,
+        ))
+        .instrument(span.clone())
+        .await
+        .map_err(|e| record_map_err(e, span.clone()))?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Upper bound for the whole multipart form, images and mask included
+const MAX_IMAGE_FORM_BYTES: usize = 25 * 1024 * 1024;
+
+/// Text fields and files of a multipart image request
+#[derive(Default)]
+struct ImageForm {
+    texts: HashMap<String, String>,
+    files: HashMap<String, ImageFile>,
+}
+
+impl ImageForm {
+    async fn read(mut payload: Multipart) -> Result<Self, GatewayApiError> {
+        let mut form = Self::default();
+        let mut read = 0;
+        while let Some(field) = payload.next().await {
+            let mut field = field.map_err(invalid_form)?;
+            let Some(name) = field.name().map(|n| n.to_string()) else {
+                continue;
+            };
+            let filename = field
+                .content_disposition()
+                .and_then(|cd| cd.get_filename())
+                .map(|f| f.to_string());
+
+            let mut content = vec![];
+            while let Some(chunk) = field.next().await {
+                let chunk = chunk.map_err(invalid_form)?;
+                read += chunk.len();
+                if read > MAX_IMAGE_FORM_BYTES {
+                    return Err(GatewayApiError::InvalidImageRequest(format!(
+                        "form exceeds {} MB",
+                        MAX_IMAGE_FORM_BYTES / (1024 * 1024)
+                    )));
+                }
+                content.extend_from_slice(&chunk);
+            }
+
+            match filename {
+                Some(filename) => {
+                    form.files.insert(name, ImageFile { filename, content });
+                }
+                None => {
+                    let text = String::from_utf8(content).map_err(invalid_form)?;
+                    form.texts.insert(name, text);
+                }
+            }
+        }
+        Ok(form)
+    }
+
+    fn required_text(&mut self, name: &str) -> Result<String, GatewayApiError> {
+        self.texts
+            .remove(name)
+            .ok_or_else(|| GatewayApiError::InvalidImageRequest(format!("{name} is required")))
+    }
+
+    fn required_file(&mut self, name: &str) -> Result<ImageFile, GatewayApiError> {
+        self.files
+            .remove(name)
+            .ok_or_else(|| GatewayApiError::InvalidImageRequest(format!("{name} file is required")))
+    }
+
+    fn n(&self) -> Result<Option<u8>, GatewayApiError> {
+        self.texts
+            .get("n")
+            .map(|n| n.parse::<u8>().map_err(invalid_form))
+            .transpose()
+    }
+
+    fn size(&self) -> Result<Option<ImageSize>, GatewayApiError> {
+        self.texts
+            .get("size")
+            .map(|size| {
+                serde_json::from_value::<ImageSize>(size.clone().into()).map_err(invalid_form)
+            })
+            .transpose()
+    }
+
+    fn response_format(&self) -> Result<Option<ImageResponseFormat>, GatewayApiError> {
+        self.texts
+            .get("response_format")
+            .map(|format| match format.as_str() {
+                "url" => Ok(ImageResponseFormat::Url),
+                "b64_json" => Ok(ImageResponseFormat::B64Json),
+                other => Err(GatewayApiError::InvalidImageRequest(format!(
+                    "unsupported response_format {other}"
+                ))),
+            })
+            .transpose()
+    }
+}
+
+fn invalid_form(e: impl ToString) -> GatewayApiError {
+    GatewayApiError::InvalidImageRequest(e.to_string())
+}
+
+/// Edits and variations upload images, which Ollama models cannot take
+fn check_image_inputs_supported(llm_model: &ModelMetadata) -> Result<(), GatewayApiError> {
+    match llm_model.inference_provider.provider {
+        InferenceModelProvider::Ollama | InferenceModelProvider::OllamaApi => {
+            Err(GatewayApiError::InvalidImageRequest(format!(
+                "image edits and variations are not supported by provider {}",
+                llm_model.inference_provider.provider
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks the requested quality and size against the ones priced for the model.
+/// Models without per type prices are not validated.
+pub fn validate_image_request(
+    request: &CreateImageRequest,
+    llm_model: &ModelMetadata,
+) -> Result<(), GatewayApiError> {
+    validate_image_options(request.quality.as_ref(), request.size.as_ref(), llm_model)
+}
+
+fn validate_image_options(
+    quality: Option<&ImageQuality>,
+    size: Option<&ImageSize>,
+    llm_model: &ModelMetadata,
+) -> Result<(), GatewayApiError> {
+    let ModelPrice::ImageGeneration(price) = &llm_model.price else {
+        return Ok(());
+    };
+    let Some(type_prices) = &price.type_prices else {
+        return Ok(());
+    };
+
+    let supported_sizes = match quality {
+        Some(quality) => {
+            let Some(sizes) = type_prices.get(&quality.to_string()) else {
+                let mut supported = type_prices.keys().cloned().collect::<Vec<_>>();
+                supported.sort();
+                return Err(GatewayApiError::InvalidImageRequest(format!(
+                    "quality {quality} is not supported by {}, supported: {}",
+                    llm_model.model,
+                    supported.join(", ")
+                )));
+            };
+            sizes.keys().cloned().collect::<Vec<_>>()
+        }
+        None => type_prices
+            .values()
+            .flat_map(|sizes| sizes.keys().cloned())
+            .collect(),
+    };
+
+    if let Some(size) = size {
+        let size = size.to_string();
+        if !supported_sizes.contains(&size) {
+            let mut supported = supported_sizes;
+            supported.sort();
+            supported.dedup();
+            return Err(GatewayApiError::InvalidImageRequest(format!(
+                "size {size} is not supported by {}, supported: {}",
+                llm_model.model,
+                supported.join(", ")
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::InferenceProvider;
+    use crate::types::provider::ImageGenerationPrice;
+    use actix_web::ResponseError;
+    use std::collections::HashMap;
+
+    fn dall_e_3() -> ModelMetadata {
+        ModelMetadata {
+            model: "dall-e-3".to_string(),
+            price: ModelPrice::ImageGeneration(ImageGenerationPrice {
+                type_prices: Some(HashMap::from([
+                    (
+                        "standard".to_string(),
+                        HashMap::from([
+                            ("1024x1024".to_string(), 0.04),
+                            ("1024x1792".to_string(), 0.08),
+                        ]),
+                    ),
+                    (
+                        "hd".to_string(),
+                        HashMap::from([("1024x1024".to_string(), 0.08)]),
+                    ),
+                ])),
+                mp_price: None,
+                valid_from: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn request(quality: Option<ImageQuality>, size: Option<ImageSize>) -> CreateImageRequest {
+        CreateImageRequest {
+            prompt: "A lighthouse at dawn".to_string(),
+            model: "openai/dall-e-3".to_string(),
+            n: None,
+            quality,
+            response_format: None,
+            size,
+            style: None,
+            user: None,
+        }
+    }
+
+    #[test]
+    fn test_supported_image_request() {
+        let model = dall_e_3();
+        assert!(validate_image_request(
+            &request(Some(ImageQuality::HD), Some(ImageSize::Size1024x1024)),
+            &model
+        )
+        .is_ok());
+        assert!(
+            validate_image_request(&request(None, Some(ImageSize::Size1024x1792)), &model).is_ok()
+        );
+        assert!(validate_image_request(&request(None, None), &model).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_image_size() {
+        let model = dall_e_3();
+        let err = validate_image_request(
+            &request(Some(ImageQuality::SD), Some(ImageSize::Size256x256)),
+            &model,
+        )
+        .unwrap_err();
+        assert_eq!(err.status_code(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        // 1024x1792 is only available in standard quality
+        let err = validate_image_request(
+            &request(Some(ImageQuality::HD), Some(ImageSize::Size1024x1792)),
+            &model,
+        )
+        .unwrap_err();
+        assert_eq!(err.status_code(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_edit_size_is_validated() {
+        let model = dall_e_3();
+        assert!(validate_image_options(None, Some(&ImageSize::Size1024x1792), &model).is_ok());
+        let err = validate_image_options(None, Some(&ImageSize::Size256x256), &model).unwrap_err();
+        assert_eq!(err.status_code(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_ollama_rejects_image_inputs() {
+        let model = ModelMetadata {
+            inference_provider: InferenceProvider {
+                provider: InferenceModelProvider::Ollama,
+                model_name: "llava".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let err = check_image_inputs_supported(&model).unwrap_err();
+        assert_eq!(err.status_code(), actix_web::http::StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("not supported by provider ollama"));
+
+        assert!(check_image_inputs_supported(&dall_e_3()).is_ok());
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::forward_ready;
+use actix_web::http::StatusCode;
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::handler::model_access::extract_api_key;
+use crate::types::GatewayTenant;
+
+pub const DEFAULT_TENANT: &str = "default";
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("API key is missing")]
+    MissingCredentials,
+
+    #[error("API key is invalid")]
+    InvalidCredentials,
+
+    #[error("{0}")]
+    Rejected(String),
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": self.to_string(),
+        }))
+    }
+}
+
+/// Resolves the tenant a request is made for. Requests it fails for are rejected with a 401
+/// before reaching any handler.
+pub trait AuthProvider: Send + Sync {
+    fn authenticate(&self, req: &HttpRequest) -> Result<GatewayTenant, AuthError>;
+}
+
+impl std::fmt::Debug for dyn AuthProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AuthProvider")
+    }
+}
+
+fn tenant(name: &str) -> GatewayTenant {
+    GatewayTenant {
+        name: name.to_string(),
+        project_slug: DEFAULT_TENANT.to_string(),
+    }
+}
+
+/// Accepts every request as the default tenant
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllowAll;
+
+impl AuthProvider for AllowAll {
+    fn authenticate(&self, _req: &HttpRequest) -> Result<GatewayTenant, AuthError> {
+        Ok(tenant(DEFAULT_TENANT))
+    }
+}
+
+/// Accepts requests carrying one of the configured keys in `Authorization: Bearer <key>`
+/// or `x-api-key`, as the tenant the key belongs to
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BearerKeyAuth {
+    /// Tenant names keyed by API key
+    pub keys: HashMap<String, String>,
+}
+
+impl AuthProvider for BearerKeyAuth {
+    fn authenticate(&self, req: &HttpRequest) -> Result<GatewayTenant, AuthError> {
+        let key = extract_api_key(req).ok_or(AuthError::MissingCredentials)?;
+        self.keys
+            .get(&key)
+            .map(|name| tenant(name))
+            .ok_or(AuthError::InvalidCredentials)
+    }
+}
+
+/// Authenticates requests with an `AuthProvider` and adds the tenant to the request
+/// extensions
+#[derive(Clone)]
+pub struct AuthMiddleware {
+    provider: Arc<dyn AuthProvider>,
+}
+
+impl AuthMiddleware {
+    pub fn new(provider: Arc<dyn AuthProvider>) -> Self {
+        Self { provider }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AuthMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuthMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthMiddlewareService {
+            service: service.into(),
+            provider: self.provider.clone(),
+        }))
+    }
+}
+
+pub struct AuthMiddlewareService<S> {
+    service: Rc<S>,
+    provider: Arc<dyn AuthProvider>,
+}
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T> + 'static>>;
+
+impl<S, B> Service<ServiceRequest> for AuthMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        match self.provider.authenticate(req.request()) {
+            Ok(tenant) => {
+                req.extensions_mut().insert(tenant);
+                Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) })
+            }
+            Err(e) => {
+                let res = req.into_response(e.error_response());
+                Box::pin(async move { Ok(res.map_into_right_body()) })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+
+    /// Accepts requests whose `x-team` header names a known team
+    struct TeamHeaderAuth;
+
+    impl AuthProvider for TeamHeaderAuth {
+        fn authenticate(&self, req: &HttpRequest) -> Result<GatewayTenant, AuthError> {
+            match req.headers().get("x-team").and_then(|v| v.to_str().ok()) {
+                Some(team @ ("search" | "billing")) => Ok(tenant(team)),
+                Some(team) => Err(AuthError::Rejected(format!("Unknown team {team}"))),
+                None => Err(AuthError::MissingCredentials),
+            }
+        }
+    }
+
+    async fn echo_tenant(req: HttpRequest) -> HttpResponse {
+        let tenant = req.extensions().get::<GatewayTenant>().cloned().unwrap();
+        HttpResponse::Ok().body(tenant.name)
+    }
+
+    #[actix_web::test]
+    async fn test_custom_provider() {
+        let app = test::init_service(
+            App::new()
+                .wrap(AuthMiddleware::new(Arc::new(TeamHeaderAuth)))
+                .route("/", web::get().to(echo_tenant)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("x-team", "search"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, "search");
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("x-team", "marketing"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_bearer_key_auth() {
+        let auth = BearerKeyAuth {
+            keys: HashMap::from([("sk-search".to_string(), "search".to_string())]),
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(AuthMiddleware::new(Arc::new(auth)))
+                .route("/", web::get().to(echo_tenant)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("authorization", "Bearer sk-search"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(test::read_body(res).await, "search");
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("authorization", "Bearer sk-other"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_allow_all_uses_default_tenant() {
+        let app = test::init_service(
+            App::new()
+                .wrap(AuthMiddleware::new(Arc::new(AllowAll)))
+                .route("/", web::get().to(echo_tenant)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(test::read_body(res).await, DEFAULT_TENANT);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::otel::AdditionalContext;
+use actix_web::dev::forward_ready;
+use actix_web::http::header::{HeaderName, InvalidHeaderName};
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use opentelemetry::baggage::BaggageExt;
+use opentelemetry::trace::FutureExt;
+use opentelemetry::{Context, KeyValue};
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// Copies the values of configured request headers into the request's `AdditionalContext`
+/// and its trace baggage, under the tag each header is mapped to
+#[derive(Debug, Clone, Default)]
+pub struct HeaderTagsMiddleware {
+    tags: Vec<(HeaderName, String)>,
+}
+
+impl HeaderTagsMiddleware {
+    /// `tags` maps request header names to the tags their values are recorded as
+    pub fn new(tags: &HashMap<String, String>) -> Result<Self, InvalidHeaderName> {
+        let tags = tags
+            .iter()
+            .map(|(header, tag)| Ok((HeaderName::try_from(header.as_str())?, tag.clone())))
+            .collect::<Result<Vec<_>, InvalidHeaderName>>()?;
+        Ok(Self { tags })
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HeaderTagsMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = HeaderTagsMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HeaderTagsMiddlewareService {
+            service: service.into(),
+            tags: self.tags.clone(),
+        }))
+    }
+}
+
+pub struct HeaderTagsMiddlewareService<S> {
+    service: Rc<S>,
+    tags: Vec<(HeaderName, String)>,
+}
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T> + 'static>>;
+
+impl<S, B> Service<ServiceRequest> for HeaderTagsMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        let values = self
+            .tags
+            .iter()
+            .filter_map(|(header, tag)| {
+                let value = req.headers().get(header)?.to_str().ok()?.trim();
+                (!value.is_empty()).then(|| (tag.clone(), value.to_string()))
+            })
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return Box::pin(service.call(req));
+        }
+
+        let mut additional_context = req
+            .extensions()
+            .get::<AdditionalContext>()
+            .cloned()
+            .unwrap_or_else(|| AdditionalContext::new(HashMap::new()));
+        additional_context.0.extend(values.iter().cloned());
+        req.extensions_mut().insert(additional_context);
+
+        let context = Context::current().with_baggage(
+            values
+                .into_iter()
+                .map(|(tag, value)| KeyValue::new(tag, value)),
+        );
+
+        Box::pin(async move { service.call(req).await }.with_context(context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpRequest, HttpResponse};
+
+    async fn echo_tags(req: HttpRequest) -> HttpResponse {
+        let baggage = Context::current()
+            .baggage()
+            .get("env")
+            .map(|v| v.to_string());
+        let context = req.extensions().get::<AdditionalContext>().cloned();
+        HttpResponse::Ok().json(serde_json::json!({
+            "baggage": baggage,
+            "context": context.map(|c| c.0),
+        }))
+    }
+
+    #[actix_web::test]
+    async fn test_configured_headers_become_tags() {
+        let tags = HashMap::from([
+            ("x-env".to_string(), "env".to_string()),
+            ("x-team".to_string(), "team".to_string()),
+        ]);
+        let app = test::init_service(
+            App::new()
+                .wrap(HeaderTagsMiddleware::new(&tags).unwrap())
+                .route("/", web::get().to(echo_tags)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("x-env", "staging"))
+            .insert_header(("x-label", "ignored"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body["baggage"], "staging");
+        assert_eq!(body["context"], serde_json::json!({"env": "staging"}));
+    }
+
+    #[test]
+    fn test_invalid_header_is_rejected() {
+        let tags = HashMap::from([("x env".to_string(), "env".to_string())]);
+        assert!(HeaderTagsMiddleware::new(&tags).is_err());
+    }
+}
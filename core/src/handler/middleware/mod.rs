@@ -1 +1,5 @@
+pub mod auth;
+pub mod header_tags;
 pub mod rate_limit;
+pub mod request_id;
+pub mod response_headers;
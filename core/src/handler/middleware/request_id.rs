@@ -0,0 +1,166 @@
+use actix_web::dev::forward_ready;
+use actix_web::http::header::{HeaderName, HeaderValue, InvalidHeaderName};
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use opentelemetry::baggage::BaggageExt;
+use opentelemetry::trace::FutureExt;
+use opentelemetry::{Context, KeyValue};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use uuid::Uuid;
+
+pub const DEFAULT_REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Baggage entry carrying the request id onto spans
+pub const REQUEST_ID_BAGGAGE_KEY: &str = "langdb.request_id";
+
+/// Id of the current request, available to handlers through the request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Takes the request id from the configured header, or generates one when it is
+/// missing, and echoes it back in the same response header
+#[derive(Debug, Clone)]
+pub struct RequestIdMiddleware {
+    header: HeaderName,
+}
+
+impl RequestIdMiddleware {
+    pub fn new(header: &str) -> Result<Self, InvalidHeaderName> {
+        Ok(Self {
+            header: HeaderName::try_from(header)?,
+        })
+    }
+}
+
+impl Default for RequestIdMiddleware {
+    fn default() -> Self {
+        Self {
+            header: HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddlewareService {
+            service: service.into(),
+            header: self.header.clone(),
+        }))
+    }
+}
+
+pub struct RequestIdMiddlewareService<S> {
+    service: Rc<S>,
+    header: HeaderName,
+}
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T> + 'static>>;
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let header = self.header.clone();
+
+        let request_id = req
+            .headers()
+            .get(&header)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        req.extensions_mut().insert(RequestId(request_id.clone()));
+
+        let context = Context::current().with_baggage(vec![KeyValue::new(
+            REQUEST_ID_BAGGAGE_KEY,
+            request_id.clone(),
+        )]);
+
+        Box::pin(
+            async move {
+                let mut res = service.call(req).await?;
+                if let Ok(value) = HeaderValue::from_str(&request_id) {
+                    res.headers_mut().insert(header, value);
+                }
+                Ok(res)
+            }
+            .with_context(context),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpRequest, HttpResponse};
+
+    async fn echo_request_id(req: HttpRequest) -> HttpResponse {
+        let request_id = req.extensions().get::<RequestId>().cloned().unwrap();
+        HttpResponse::Ok().body(request_id.0)
+    }
+
+    #[actix_web::test]
+    async fn test_provided_request_id_is_echoed() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestIdMiddleware::default())
+                .route("/", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("x-request-id", "req-123"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.headers().get("x-request-id").unwrap(), "req-123");
+        assert_eq!(test::read_body(res).await, "req-123");
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_is_generated() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestIdMiddleware::new("x-correlation-id").unwrap())
+                .route("/", web::get().to(echo_request_id)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+
+        let header = res
+            .headers()
+            .get("x-correlation-id")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(Uuid::parse_str(&header).is_ok());
+        assert_eq!(test::read_body(res).await, header.as_str());
+    }
+}
@@ -0,0 +1,184 @@
+use actix_web::dev::forward_ready;
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use serde::{Deserialize, Serialize};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Headers removed from responses before they reach the client. Names are case-insensitive,
+/// a trailing `*` matches any header with that prefix.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ResponseHeadersConfig {
+    /// Headers always removed
+    #[serde(default)]
+    pub strip: Vec<String>,
+    /// When set, `x-` headers not in this list are removed as well
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+impl ResponseHeadersConfig {
+    fn is_stripped(&self, name: &str) -> bool {
+        if self.strip.iter().any(|p| matches(p, name)) {
+            return true;
+        }
+        match &self.allow {
+            Some(allow) => name.starts_with("x-") && !allow.iter().any(|p| matches(p, name)),
+            None => false,
+        }
+    }
+}
+
+/// Strips the configured headers from responses. Provider rate-limit headers are recorded by
+/// the provider clients, see [`crate::model::rate_limits`].
+#[derive(Debug, Clone, Default)]
+pub struct ResponseHeadersMiddleware {
+    config: Arc<ResponseHeadersConfig>,
+}
+
+impl ResponseHeadersMiddleware {
+    pub fn new(config: ResponseHeadersConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ResponseHeadersMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ResponseHeadersMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ResponseHeadersMiddlewareService {
+            service: service.into(),
+            config: self.config.clone(),
+        }))
+    }
+}
+
+pub struct ResponseHeadersMiddlewareService<S> {
+    service: Rc<S>,
+    config: Arc<ResponseHeadersConfig>,
+}
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T> + 'static>>;
+
+impl<S, B> Service<ServiceRequest> for ResponseHeadersMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let config = self.config.clone();
+
+        Box::pin(async move {
+            let mut res = service.call(req).await?;
+
+            let stripped = res
+                .headers()
+                .keys()
+                .filter(|name| config.is_stripped(name.as_str()))
+                .cloned()
+                .collect::<Vec<_>>();
+            for name in stripped {
+                res.headers_mut().remove(name);
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn upstream() -> HttpResponse {
+        HttpResponse::Ok()
+            .insert_header(("X-Provider-Name", "openai"))
+            .insert_header(("X-Model-Name", "gpt-4o"))
+            .insert_header(("x-ratelimit-remaining-requests", "59"))
+            .insert_header(("x-ratelimit-remaining-tokens", "149000"))
+            .insert_header(("openai-organization", "org-123"))
+            .finish()
+    }
+
+    #[actix_web::test]
+    async fn test_configured_headers_are_stripped() {
+        let config = ResponseHeadersConfig {
+            strip: vec![
+                "x-ratelimit-*".to_string(),
+                "OpenAI-Organization".to_string(),
+            ],
+            allow: None,
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(ResponseHeadersMiddleware::new(config))
+                .route("/", web::get().to(upstream)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+
+        assert!(res
+            .headers()
+            .get("x-ratelimit-remaining-requests")
+            .is_none());
+        assert!(res.headers().get("x-ratelimit-remaining-tokens").is_none());
+        assert!(res.headers().get("openai-organization").is_none());
+        assert_eq!(res.headers().get("x-model-name").unwrap(), "gpt-4o");
+    }
+
+    #[actix_web::test]
+    async fn test_allow_list() {
+        let config = ResponseHeadersConfig {
+            strip: vec![],
+            allow: Some(vec!["x-model-name".to_string()]),
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(ResponseHeadersMiddleware::new(config))
+                .route("/", web::get().to(upstream)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+
+        assert_eq!(res.headers().get("x-model-name").unwrap(), "gpt-4o");
+        assert!(res.headers().get("x-provider-name").is_none());
+        assert!(res
+            .headers()
+            .get("x-ratelimit-remaining-requests")
+            .is_none());
+        // Only `x-` headers are subject to the allow list
+        assert_eq!(res.headers().get("openai-organization").unwrap(), "org-123");
+    }
+}
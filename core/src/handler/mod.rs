@@ -1,10 +1,16 @@
+pub mod admin;
 pub mod chat;
 pub mod embedding;
 pub mod image;
 pub mod middleware;
+pub mod model_access;
 pub mod models;
+pub mod reload;
 pub mod responses;
+pub mod usage;
 
+use crate::events::bodies::{BodyRecording, BodyRecordingMode};
+use crate::llm_gateway::presets::preset_model;
 use crate::model::types::ModelEvent;
 use crate::models::ModelMetadata;
 use crate::types::engine::Model;
@@ -49,12 +55,59 @@ pub fn find_model_by_full_name(
         None
     };
 
-    match llm_model {
+    match llm_model.or_else(|| preset_model(model_name)) {
         Some(model) => Ok(model),
-        None => Err(GatewayApiError::ModelError(Box::new(
-            ModelError::ModelNotFound(model_name.to_string()),
-        ))),
+        None => {
+            let error = match suggest_model_name(model_name, provided_models) {
+                Some(suggestion) => {
+                    ModelError::ModelNotFoundWithSuggestion(model_name.to_string(), suggestion)
+                }
+                None => ModelError::ModelNotFound(model_name.to_string()),
+            };
+            Err(GatewayApiError::ModelError(Box::new(error)))
+        }
+    }
+}
+
+/// Finds the configured model name closest to `model_name`, if it is close enough to be a typo
+fn suggest_model_name(model_name: &str, provided_models: &AvailableModels) -> Option<String> {
+    let model_name = model_name.to_lowercase();
+    let with_provider = model_name.contains('/');
+
+    provided_models
+        .0
+        .iter()
+        .map(|m| {
+            if with_provider {
+                format!("{}/{}", m.inference_provider.provider, m.model)
+            } else {
+                m.model.clone()
+            }
+        })
+        .map(|candidate| {
+            let distance = levenshtein(&model_name, &candidate.to_lowercase());
+            (candidate, distance)
+        })
+        .filter(|(candidate, distance)| *distance <= candidate.chars().count().div_ceil(3))
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(candidate, _)| candidate)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
     }
+
+    previous[b.len()]
 }
 
 // extract langdb-tags from headers, shoule be sth like this: tag1=value1&tag2=value2 => result should be a Map<String, String>
@@ -81,6 +134,13 @@ pub fn extract_tags(req: &HttpRequest) -> Result<HashMap<String, String>, Gatewa
     .unwrap_or_default())
 }
 
+/// How the bodies of `req` are recorded in traces, sampled once per request
+pub fn body_recording(req: &HttpRequest) -> BodyRecordingMode {
+    req.app_data::<BodyRecording>()
+        .map(BodyRecording::sample)
+        .unwrap_or_default()
+}
+
 pub fn record_map_err(
     e: impl Into<GatewayApiError> + ToString,
     span: tracing::Span,
@@ -119,6 +179,12 @@ pub struct DollarUsage {
     pub monthly_limit: Option<f64>,
     pub total: f64,
     pub total_limit: Option<f64>,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+fn default_currency() -> String {
+    crate::pricing::currency::USD.to_string()
 }
 
 #[async_trait::async_trait]
@@ -195,3 +261,75 @@ pub(crate) async fn can_execute_llm_for_request(req: &HttpRequest) -> Result<(),
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::model;
+    use crate::types::provider::InferenceModelProvider;
+
+    fn available_models() -> AvailableModels {
+        AvailableModels(vec![
+            model(InferenceModelProvider::OpenAI, "gpt-4o"),
+            model(InferenceModelProvider::OpenAI, "gpt-4o-mini"),
+            model(InferenceModelProvider::Anthropic, "claude-3-5-sonnet"),
+        ])
+    }
+
+    #[test]
+    fn test_model_not_found_suggestion() {
+        let err = find_model_by_full_name("opena/gpt-4o", &available_models()).unwrap_err();
+        match err {
+            GatewayApiError::ModelError(e) => match *e {
+                ModelError::ModelNotFoundWithSuggestion(name, suggestion) => {
+                    assert_eq!(name, "opena/gpt-4o");
+                    assert_eq!(suggestion, "openai/gpt-4o");
+                }
+                e => panic!("Unexpected error: {e}"),
+            },
+            e => panic!("Unexpected error: {e}"),
+        }
+
+        let err = find_model_by_full_name("gpt4o-mini", &available_models()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Model gpt4o-mini not found, did you mean gpt-4o-mini?"
+        );
+    }
+
+    #[test]
+    fn test_model_not_found_without_suggestion() {
+        let err =
+            find_model_by_full_name("mistral/totally-unrelated", &available_models()).unwrap_err();
+        match err {
+            GatewayApiError::ModelError(e) => {
+                assert!(matches!(*e, ModelError::ModelNotFound(_)))
+            }
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn test_preset_models_are_found() {
+        let model = find_model_by_full_name(
+            "fireworks/accounts/fireworks/models/deepseek-r1",
+            &available_models(),
+        )
+        .unwrap();
+        assert_eq!(
+            model.inference_provider.provider,
+            InferenceModelProvider::Proxy("fireworksai".to_string())
+        );
+        assert_eq!(
+            model.inference_provider.model_name,
+            "accounts/fireworks/models/deepseek-r1"
+        );
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("gpt-4o", "gpt-4o"), 0);
+    }
+}
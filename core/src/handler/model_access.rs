@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+
+use actix_web::{HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use crate::llm_gateway::presets::preset_model;
+use crate::models::ModelMetadata;
+use crate::types::gateway::ChatCompletionRequestWithTools;
+use crate::types::GatewayTenant;
+use crate::GatewayApiError;
+
+use super::{find_model_by_full_name, AvailableModels};
+
+/// Model patterns a single API key may access. Patterns support `*` and `?` wildcards.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ModelAccessRules {
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub denied_models: Vec<String>,
+}
+
+impl ModelAccessRules {
+    /// Deny patterns take precedence; an empty allowlist allows every model
+    pub fn is_allowed(&self, model: &str) -> bool {
+        if self.denied_models.iter().any(|p| glob_match(p, model)) {
+            return false;
+        }
+
+        self.allowed_models.is_empty() || self.allowed_models.iter().any(|p| glob_match(p, model))
+    }
+}
+
+/// Model access rules keyed by API key
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ApiKeysConfig(pub HashMap<String, ModelAccessRules>);
+
+impl ApiKeysConfig {
+    pub fn check_model_access(
+        &self,
+        api_key: Option<&str>,
+        model: &str,
+    ) -> Result<(), GatewayApiError> {
+        match api_key.and_then(|key| self.0.get(key)) {
+            Some(rules) if !rules.is_allowed(model) => {
+                Err(GatewayApiError::ModelNotAllowed(model.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Model access rules keyed by tenant name. Tenants without rules may use every model.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TenantModels(pub HashMap<String, ModelAccessRules>);
+
+impl TenantModels {
+    /// The models of `models` available to `tenant`, matched by their `provider/model` id
+    pub fn available_to(&self, tenant: &str, models: AvailableModels) -> AvailableModels {
+        match self.0.get(tenant) {
+            Some(rules) => AvailableModels(
+                models
+                    .0
+                    .into_iter()
+                    .filter(|model| rules.is_allowed(&model_id(model)))
+                    .collect(),
+            ),
+            None => models,
+        }
+    }
+}
+
+fn model_id(model: &ModelMetadata) -> String {
+    format!("{}/{}", model.inference_provider.provider, model.model)
+}
+
+/// Narrows `models` down to the ones available to the tenant of `req`
+pub fn tenant_models(req: &HttpRequest, models: AvailableModels) -> AvailableModels {
+    match (
+        req.app_data::<TenantModels>(),
+        req.extensions().get::<GatewayTenant>(),
+    ) {
+        (Some(config), Some(tenant)) => config.available_to(&tenant.name, models),
+        _ => models,
+    }
+}
+
+/// Checks `model` against the access rules of the caller. The rules are matched against the
+/// `provider/model` id the name resolves to, so bare names and `@variant` names can't slip past
+/// them.
+pub fn check_model_access(
+    req: &HttpRequest,
+    model: &str,
+    models: &AvailableModels,
+) -> Result<(), GatewayApiError> {
+    let resolved = find_model_by_full_name(model, models).ok();
+    let model = resolved.as_ref().map_or(model.to_string(), model_id);
+
+    if let Some(config) = req.app_data::<ApiKeysConfig>() {
+        config.check_model_access(extract_api_key(req).as_deref(), &model)?;
+    }
+
+    // Preset models are resolved without being listed, so tenant rules can't filter them out
+    if preset_model(&model).is_some() {
+        let rules = req.app_data::<TenantModels>().and_then(|config| {
+            let tenant = req.extensions().get::<GatewayTenant>()?.name.clone();
+            config.0.get(&tenant).cloned()
+        });
+        if rules.is_some_and(|rules| !rules.is_allowed(&model)) {
+            return Err(GatewayApiError::ModelNotAllowed(model));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks every model `request` can reach: the targets of its router, or its model when it
+/// isn't routed
+pub fn check_request_model_access<T>(
+    req: &HttpRequest,
+    request: &ChatCompletionRequestWithTools<T>,
+    models: &AvailableModels,
+) -> Result<(), GatewayApiError> {
+    match &request.router {
+        Some(router) => check_targets_access(req, &request.request.model, &router.targets, models),
+        None => check_model_access(req, &request.request.model, models),
+    }
+}
+
+/// Targets are merged into the request they route, so a target without a model runs the
+/// model of that request, and a target carrying a router of its own routes further
+fn check_targets_access(
+    req: &HttpRequest,
+    model: &str,
+    targets: &[HashMap<String, serde_json::Value>],
+    models: &AvailableModels,
+) -> Result<(), GatewayApiError> {
+    targets.iter().try_for_each(|target| {
+        let model = target
+            .get("model")
+            .and_then(|m| m.as_str())
+            .unwrap_or(model);
+        let nested = target
+            .get("router")
+            .and_then(|router| router.get("targets"))
+            .map(|targets| serde_json::from_value::<Vec<_>>(targets.clone()))
+            .transpose()?;
+        match nested {
+            Some(targets) => check_targets_access(req, model, &targets, models),
+            None => check_model_access(req, model, models),
+        }
+    })
+}
+
+/// Reads the API key from `Authorization: Bearer <key>` or `x-api-key`
+pub fn extract_api_key(req: &HttpRequest) -> Option<String> {
+    let headers = req.headers();
+    headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .map(|key| key.trim().to_string())
+}
+
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let value = value.to_lowercase().chars().collect::<Vec<_>>();
+
+    let (mut p, mut v) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, v));
+            p += 1;
+        } else if let Some((star_p, star_v)) = star {
+            p = star_p + 1;
+            v = star_v + 1;
+            star = Some((star_p, star_v + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::find_model_by_full_name;
+    use crate::handler::middleware::auth::{AuthMiddleware, BearerKeyAuth};
+    use crate::handler::models::list_gateway_models;
+    use crate::test_utils::model;
+    use crate::types::provider::InferenceModelProvider;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App, HttpResponse, ResponseError};
+    use std::sync::Arc;
+
+    fn config() -> ApiKeysConfig {
+        ApiKeysConfig(HashMap::from([
+            (
+                "key-a".to_string(),
+                ModelAccessRules {
+                    allowed_models: vec!["openai/*".to_string()],
+                    denied_models: vec!["openai/o1*".to_string()],
+                },
+            ),
+            (
+                "key-b".to_string(),
+                ModelAccessRules {
+                    allowed_models: vec!["anthropic/*".to_string()],
+                    denied_models: vec![],
+                },
+            ),
+        ]))
+    }
+
+    #[test]
+    fn test_allowed_model() {
+        let config = config();
+        assert!(config
+            .check_model_access(Some("key-a"), "openai/gpt-4o-mini")
+            .is_ok());
+        assert!(config
+            .check_model_access(Some("key-b"), "anthropic/claude-3-5-sonnet")
+            .is_ok());
+        // Keys without rules are not restricted
+        assert!(config
+            .check_model_access(Some("key-c"), "gemini/gemini-2.0-flash")
+            .is_ok());
+        assert!(config
+            .check_model_access(None, "gemini/gemini-2.0-flash")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_disallowed_model() {
+        let config = config();
+
+        let err = config
+            .check_model_access(Some("key-a"), "anthropic/claude-3-5-sonnet")
+            .unwrap_err();
+        assert_eq!(err.status_code(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let err = config
+            .check_model_access(Some("key-a"), "openai/o1-mini")
+            .unwrap_err();
+        assert_eq!(err.status_code(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let err = config
+            .check_model_access(Some("key-b"), "openai/gpt-4o")
+            .unwrap_err();
+        assert_eq!(err.status_code(), actix_web::http::StatusCode::FORBIDDEN);
+    }
+
+    async fn resolve_model(
+        req: HttpRequest,
+        models: AvailableModels,
+        path: web::Path<(String, String)>,
+    ) -> Result<HttpResponse, GatewayApiError> {
+        let (provider, name) = path.into_inner();
+        let name = format!("{provider}/{name}");
+        check_model_access(&req, &name, &models)?;
+        match find_model_by_full_name(&name, &models) {
+            Ok(model) => Ok(HttpResponse::Ok().body(model.model)),
+            Err(_) => Ok(HttpResponse::NotFound().finish()),
+        }
+    }
+
+    fn request(uri: &str, key: &str) -> test::TestRequest {
+        test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("x-api-key", key))
+    }
+
+    fn model_ids(models: serde_json::Value) -> Vec<String> {
+        models["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_tenant_models() {
+        let models = AvailableModels(vec![
+            model(InferenceModelProvider::OpenAI, "gpt-4o"),
+            model(InferenceModelProvider::OpenAI, "gpt-4o-mini"),
+            model(InferenceModelProvider::Anthropic, "claude-3-5-sonnet"),
+        ]);
+        let tenants = TenantModels(HashMap::from([
+            (
+                "search".to_string(),
+                ModelAccessRules {
+                    allowed_models: vec!["openai/*".to_string()],
+                    denied_models: vec!["openai/gpt-4o".to_string()],
+                },
+            ),
+            (
+                "billing".to_string(),
+                ModelAccessRules {
+                    allowed_models: vec!["anthropic/*".to_string()],
+                    denied_models: vec![],
+                },
+            ),
+        ]));
+        let auth = BearerKeyAuth {
+            keys: HashMap::from([
+                ("search-key".to_string(), "search".to_string()),
+                ("billing-key".to_string(), "billing".to_string()),
+                ("admin-key".to_string(), "admin".to_string()),
+            ]),
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(AuthMiddleware::new(Arc::new(auth)))
+                .app_data(web::Data::new(models))
+                .app_data(tenants)
+                .route("/models", web::get().to(list_gateway_models))
+                .route("/resolve/{provider}/{model}", web::get().to(resolve_model)),
+        )
+        .await;
+
+        let mut listed = vec![];
+        for key in ["search-key", "billing-key", "admin-key"] {
+            let req = request("/models", key).to_request();
+            listed.push(model_ids(test::call_and_read_body_json(&app, req).await));
+        }
+        assert_eq!(listed[0], vec!["openai/gpt-4o-mini"]);
+        assert_eq!(listed[1], vec!["anthropic/claude-3-5-sonnet"]);
+        // Tenants without rules see every model
+        assert_eq!(listed[2].len(), 3);
+
+        let cases = [
+            ("search-key", "openai/gpt-4o-mini", StatusCode::OK),
+            ("search-key", "openai/gpt-4o", StatusCode::NOT_FOUND),
+            (
+                "search-key",
+                "anthropic/claude-3-5-sonnet",
+                StatusCode::NOT_FOUND,
+            ),
+            ("billing-key", "anthropic/claude-3-5-sonnet", StatusCode::OK),
+            ("billing-key", "openai/gpt-4o-mini", StatusCode::NOT_FOUND),
+            // Preset models aren't listed but are still subject to the tenant's rules
+            ("billing-key", "together/llama-3", StatusCode::FORBIDDEN),
+            ("admin-key", "together/llama-3", StatusCode::OK),
+        ];
+        for (key, model, status) in cases {
+            let req = request(&format!("/resolve/{model}"), key).to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), status, "{key} requesting {model}");
+        }
+    }
+
+    #[test]
+    fn test_rules_match_resolved_model() {
+        let models = AvailableModels(vec![
+            model(InferenceModelProvider::OpenAI, "gpt-4o-mini"),
+            model(InferenceModelProvider::OpenAI, "o1-mini"),
+        ]);
+        let req = test::TestRequest::default()
+            .insert_header(("x-api-key", "key-a"))
+            .app_data(config())
+            .to_http_request();
+
+        assert!(check_model_access(&req, "gpt-4o-mini", &models).is_ok());
+        for name in ["o1-mini", "openai/o1-mini@latest", "O1-MINI"] {
+            assert!(
+                matches!(
+                    check_model_access(&req, name, &models),
+                    Err(GatewayApiError::ModelNotAllowed(model)) if model == "openai/o1-mini"
+                ),
+                "{name}"
+            );
+        }
+
+        let routed = |targets: &[&str]| ChatCompletionRequestWithTools::<serde_json::Value> {
+            request: crate::types::gateway::ChatCompletionRequest {
+                model: "router/fallback".to_string(),
+                ..Default::default()
+            },
+            router: Some(crate::types::gateway::DynamicRouter {
+                strategy: serde_json::json!({"type": "fallback"}),
+                targets: targets
+                    .iter()
+                    .map(|model| HashMap::from([("model".to_string(), serde_json::json!(model))]))
+                    .collect(),
+                name: None,
+                failover_on: None,
+            }),
+            ..Default::default()
+        };
+        assert!(check_request_model_access(&req, &routed(&["gpt-4o-mini"]), &models).is_ok());
+        assert!(matches!(
+            check_request_model_access(&req, &routed(&["gpt-4o-mini", "o1-mini"]), &models),
+            Err(GatewayApiError::ModelNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_targets_without_model_check_request_model() {
+        let models = AvailableModels(vec![
+            model(InferenceModelProvider::OpenAI, "gpt-4o-mini"),
+            model(InferenceModelProvider::OpenAI, "o1-mini"),
+        ]);
+        let req = test::TestRequest::default()
+            .insert_header(("x-api-key", "key-a"))
+            .app_data(config())
+            .to_http_request();
+        let routed = |model: &str, targets: serde_json::Value| {
+            serde_json::from_value::<ChatCompletionRequestWithTools<serde_json::Value>>(
+                serde_json::json!({
+                    "model": model,
+                    "messages": [],
+                    "router": {"type": "fallback", "targets": targets}
+                }),
+            )
+            .unwrap()
+        };
+
+        // The target only overrides parameters, so the request model is the one called
+        let request = routed("o1-mini", serde_json::json!([{"temperature": 0}]));
+        assert!(matches!(
+            check_request_model_access(&req, &request, &models),
+            Err(GatewayApiError::ModelNotAllowed(model)) if model == "openai/o1-mini"
+        ));
+        let request = routed("gpt-4o-mini", serde_json::json!([{"temperature": 0}]));
+        assert!(check_request_model_access(&req, &request, &models).is_ok());
+
+        // Routers nested in a target are checked too
+        let request = routed(
+            "gpt-4o-mini",
+            serde_json::json!([{"router": {"type": "fallback", "targets": [{"model": "o1-mini"}]}}]),
+        );
+        assert!(matches!(
+            check_request_model_access(&req, &request, &models),
+            Err(GatewayApiError::ModelNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("openai/*", "openai/gpt-4o"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("gpt-4?", "gpt-4o"));
+        assert!(glob_match("*/claude-*", "anthropic/claude-3-opus"));
+        assert!(!glob_match("openai/*", "anthropic/claude-3-opus"));
+        assert!(!glob_match("gpt-4?", "gpt-4o-mini"));
+    }
+}
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
-use crate::{models::ModelCapability, types::gateway::ChatModel};
-use actix_web::{web, HttpResponse};
+use crate::models::{ModelCapability, ModelMetadata};
+use crate::pricing::currency::CurrencyConverter;
+use crate::types::gateway::{ChatModel, ChatModelPricing};
+use crate::types::provider::ModelPrice;
+use actix_web::{HttpRequest, HttpResponse};
 use serde::Serialize;
 
 use crate::GatewayApiError;
@@ -14,13 +17,33 @@ pub struct ChatModelsResponse {
     pub data: Vec<ChatModel>,
 }
 
+/// Token prices of `model` in the configured currency. Image models are priced per image
+/// and have none.
+fn model_pricing(model: &ModelMetadata, currency: &CurrencyConverter) -> Option<ChatModelPricing> {
+    let (input, output) = match &model.price {
+        ModelPrice::Completion(price) => (price.per_input_token, Some(price.per_output_token)),
+        ModelPrice::Embedding(price) => (price.per_input_token, None),
+        ModelPrice::ImageGeneration(_) => return None,
+    };
+
+    Some(ChatModelPricing {
+        input_per_1m_tokens: currency.convert(input),
+        output_per_1m_tokens: output.map(|output| currency.convert(output)),
+        currency: currency.currency().to_string(),
+    })
+}
+
 pub async fn list_gateway_models(
-    models: web::Data<AvailableModels>,
+    models: AvailableModels,
+    req: HttpRequest,
 ) -> Result<HttpResponse, GatewayApiError> {
+    let currency = req
+        .app_data::<CurrencyConverter>()
+        .cloned()
+        .unwrap_or_default();
     let response = ChatModelsResponse {
         object: "list".to_string(),
         data: models
-            .into_inner()
             .0
             .iter()
             .map(|v| ChatModel {
@@ -28,6 +51,8 @@ pub async fn list_gateway_models(
                 object: "model".to_string(),
                 created: 1686935002,
                 owned_by: v.model_provider.to_string(),
+                context_window: Some(v.max_context()),
+                pricing: model_pricing(v, &currency),
             })
             .collect(),
     };
@@ -36,10 +61,9 @@ pub async fn list_gateway_models(
 }
 
 pub async fn list_gateway_models_capabilities(
-    models: web::Data<AvailableModels>,
+    models: AvailableModels,
 ) -> Result<HttpResponse, GatewayApiError> {
     let capabilities: HashMap<String, Vec<ModelCapability>> = models
-        .into_inner()
         .0
         .iter()
         .map(|model| (model.model.to_string(), model.capabilities.clone()))
@@ -49,7 +73,87 @@ pub async fn list_gateway_models_capabilities(
 }
 
 pub async fn list_gateway_pricing(
-    models: web::Data<AvailableModels>,
+    models: AvailableModels,
 ) -> Result<HttpResponse, GatewayApiError> {
-    Ok(HttpResponse::Ok().json(models.into_inner().0.clone()))
+    Ok(HttpResponse::Ok().json(models.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{InferenceProvider, Limits};
+    use crate::pricing::currency::CurrencyConfig;
+    use crate::types::provider::{CompletionModelPrice, InferenceModelProvider};
+    use actix_web::{test, web, App};
+
+    fn model(name: &str, context: u32, input: f64, output: f64) -> ModelMetadata {
+        ModelMetadata {
+            model: name.to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: InferenceProvider {
+                provider: InferenceModelProvider::OpenAI,
+                model_name: name.to_string(),
+                endpoint: None,
+            },
+            price: ModelPrice::Completion(CompletionModelPrice {
+                per_input_token: input,
+                per_output_token: output,
+                per_reasoning_token: None,
+                valid_from: None,
+            }),
+            limits: Limits::new(context),
+            ..Default::default()
+        }
+    }
+
+    async fn list_models(currency: CurrencyConverter) -> serde_json::Value {
+        let models = AvailableModels(vec![
+            model("gpt-4o", 128000, 2.5, 10.0),
+            model("gpt-4o-mini", 128000, 0.15, 0.6),
+        ]);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(models))
+                .app_data(currency)
+                .route("/models", web::get().to(list_gateway_models)),
+        )
+        .await;
+
+        test::call_and_read_body_json(&app, test::TestRequest::get().uri("/models").to_request())
+            .await
+    }
+
+    #[actix_web::test]
+    async fn test_models_include_context_window_and_pricing() {
+        let body = list_models(CurrencyConverter::usd()).await;
+
+        assert_eq!(body["data"][0]["id"], "openai/gpt-4o");
+        assert_eq!(body["data"][0]["context_window"], 128000);
+        assert_eq!(
+            body["data"][0]["pricing"],
+            serde_json::json!({
+                "input_per_1m_tokens": 2.5,
+                "output_per_1m_tokens": 10.0,
+                "currency": "USD"
+            })
+        );
+        assert_eq!(body["data"][1]["id"], "openai/gpt-4o-mini");
+        assert_eq!(body["data"][1]["pricing"]["input_per_1m_tokens"], 0.15);
+        assert_eq!(body["data"][1]["pricing"]["output_per_1m_tokens"], 0.6);
+    }
+
+    #[actix_web::test]
+    async fn test_model_pricing_uses_configured_currency() {
+        let currency = CurrencyConverter::new(&CurrencyConfig {
+            code: "eur".to_string(),
+            rate: Some(0.5),
+            rate_url: None,
+            refresh_interval_secs: 3600,
+        });
+        let body = list_models(currency).await;
+
+        assert_eq!(body["data"][0]["pricing"]["currency"], "EUR");
+        assert_eq!(body["data"][0]["pricing"]["input_per_1m_tokens"], 1.25);
+        assert_eq!(body["data"][0]["pricing"]["output_per_1m_tokens"], 5.0);
+    }
 }
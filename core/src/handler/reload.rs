@@ -0,0 +1,78 @@
+use std::future::{ready, Ready};
+use std::sync::Arc;
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use parking_lot::RwLock;
+
+use crate::routing::DefaultRouters;
+use crate::types::guardrails::service::GuardrailsEvaluator;
+
+use super::model_access::tenant_models;
+use super::AvailableModels;
+
+/// Models, routers and guards in effect at one point in time
+pub struct ReloadableSnapshot {
+    pub models: AvailableModels,
+    pub default_routers: Option<DefaultRouters>,
+    pub evaluator: Arc<Box<dyn GuardrailsEvaluator>>,
+}
+
+/// Configuration that can be replaced while the server is running. Requests take the
+/// snapshot current when they start and keep it until they finish.
+#[derive(Clone)]
+pub struct ReloadableState {
+    current: Arc<RwLock<Arc<ReloadableSnapshot>>>,
+}
+
+impl ReloadableState {
+    pub fn new(snapshot: ReloadableSnapshot) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(snapshot))),
+        }
+    }
+
+    pub fn current(&self) -> Arc<ReloadableSnapshot> {
+        self.current.read().clone()
+    }
+
+    pub fn swap(&self, snapshot: ReloadableSnapshot) {
+        *self.current.write() = Arc::new(snapshot);
+    }
+}
+
+/// Guardrails evaluator of the current snapshot, or the one registered as app data
+pub fn current_evaluator(req: &HttpRequest) -> Option<Arc<Box<dyn GuardrailsEvaluator>>> {
+    match req.app_data::<ReloadableState>() {
+        Some(state) => Some(state.current().evaluator.clone()),
+        None => req
+            .app_data::<web::Data<Box<dyn GuardrailsEvaluator>>>()
+            .map(|evaluator| evaluator.clone().into_inner()),
+    }
+}
+
+/// Default routers of the current snapshot, or the ones registered as app data
+pub fn current_default_routers(req: &HttpRequest) -> Option<DefaultRouters> {
+    match req.app_data::<ReloadableState>() {
+        Some(state) => state.current().default_routers.clone(),
+        None => req.app_data::<DefaultRouters>().cloned(),
+    }
+}
+
+impl FromRequest for AvailableModels {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let models = match req.app_data::<ReloadableState>() {
+            Some(state) => Some(state.current().models.clone()),
+            None => req
+                .app_data::<web::Data<AvailableModels>>()
+                .map(|models| models.get_ref().clone()),
+        }
+        .map(|models| tenant_models(req, models));
+
+        ready(models.ok_or_else(|| {
+            actix_web::error::ErrorInternalServerError("Available models are not configured")
+        }))
+    }
+}
@@ -0,0 +1,26 @@
+use actix_web::{HttpRequest, HttpResponse};
+
+use crate::pricing::currency::CurrencyConverter;
+use crate::GatewayApiError;
+
+use super::LimitCheckWrapper;
+
+/// Returns daily, monthly and total spend with the configured cost limits
+pub async fn get_usage(req: HttpRequest) -> Result<HttpResponse, GatewayApiError> {
+    let Some(Some(limit_checker)) = req.app_data::<Option<LimitCheckWrapper>>() else {
+        return Err(GatewayApiError::CustomError(
+            "Cost control is not configured".to_string(),
+        ));
+    };
+
+    let usage = limit_checker
+        .get_usage()
+        .await
+        .map_err(|e| GatewayApiError::CustomError(e.to_string()))?;
+    let usage = match req.app_data::<CurrencyConverter>() {
+        Some(converter) => converter.convert_usage(usage),
+        None => usage,
+    };
+
+    Ok(HttpResponse::Ok().json(usage))
+}
@@ -14,9 +14,11 @@ pub mod otel;
 pub mod pricing;
 pub mod responses;
 pub mod routing;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod types;
 
-use crate::error::GatewayError;
+use crate::error::{ErrorClass, GatewayError};
 use crate::types::gateway::CostCalculatorError;
 use actix_web::http::header::ContentType;
 use actix_web::http::StatusCode;
@@ -57,6 +59,46 @@ pub enum GatewayApiError {
     #[error("Token usage limit exceeded")]
     TokenUsageLimit,
 
+    #[error("Estimated request cost {estimated:.6} exceeds the per request limit of {limit}")]
+    RequestCostLimit { estimated: f64, limit: f64 },
+
+    #[error("The `user` field is required")]
+    MissingUser,
+
+    #[error("Provider {0} does not support streaming")]
+    StreamingNotSupported(String),
+
+    #[error("Request has {count} tools, at most {limit} are allowed")]
+    TooManyTools { count: usize, limit: usize },
+
+    #[error("Prompt has {size} {unit}, at most {limit} are allowed")]
+    PromptTooLarge {
+        size: usize,
+        limit: usize,
+        unit: &'static str,
+    },
+
+    #[error("Response does not match the model's response schema: {0}")]
+    ResponseSchemaViolation(String),
+
+    #[error("Model {0} is not allowed for this API key")]
+    ModelNotAllowed(String),
+
+    #[error("Admin API key is missing or invalid")]
+    Unauthorized,
+
+    #[error("Invalid image request: {0}")]
+    InvalidImageRequest(String),
+
+    #[error("Invalid metadata: {0}")]
+    InvalidMetadata(String),
+
+    #[error("Model {model} does not support: {}", features.join(", "))]
+    UnsupportedFeatures {
+        model: String,
+        features: Vec<&'static str>,
+    },
+
     #[error(transparent)]
     RouteError(#[from] routing::RouterError),
 
@@ -68,12 +110,23 @@ impl GatewayApiError {
     pub fn is_countable_error(&self) -> bool {
         !matches!(
             self,
-            GatewayApiError::GatewayError(GatewayError::GuardError(GuardError::GuardNotPassed(
-                _,
-                _
-            )))
+            GatewayApiError::GatewayError(GatewayError::GuardError(GuardError::GuardNotPassed(..)))
+        )
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(
+            self,
+            GatewayApiError::GatewayError(GatewayError::RateLimited { .. })
         )
     }
+
+    pub fn error_class(&self) -> ErrorClass {
+        match self {
+            GatewayApiError::GatewayError(e) => e.class(),
+            _ => ErrorClass::Other,
+        }
+    }
 }
 
 impl actix_web::error::ResponseError for GatewayApiError {
@@ -81,6 +134,14 @@ impl actix_web::error::ResponseError for GatewayApiError {
         tracing::error!("API error: {:?}", self);
         match self {
             GatewayApiError::GatewayError(e) => e.error_response(),
+            GatewayApiError::RoutedExecutorError(
+                e @ RoutedExecutorError::AllTargetsFailed { attempts },
+            ) => HttpResponse::build(e.status_code())
+                .insert_header(ContentType::json())
+                .json(json!({
+                    "error": e.to_string(),
+                    "attempts": attempts,
+                })),
             e => {
                 let json_error = json!({
                     "error": e.to_string(),
@@ -100,9 +161,25 @@ impl actix_web::error::ResponseError for GatewayApiError {
             GatewayApiError::CustomError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             GatewayApiError::CostCalculatorError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             GatewayApiError::ModelError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            GatewayApiError::RouteError(
+                routing::RouterError::UnknownRouter(_)
+                | routing::RouterError::UnknownTarget(_)
+                | routing::RouterError::NoTargets(_),
+            ) => StatusCode::BAD_REQUEST,
             GatewayApiError::RouteError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            GatewayApiError::RoutedExecutorError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            GatewayApiError::RoutedExecutorError(e) => e.status_code(),
             GatewayApiError::TokenUsageLimit => StatusCode::BAD_REQUEST,
+            GatewayApiError::RequestCostLimit { .. } => StatusCode::BAD_REQUEST,
+            GatewayApiError::MissingUser => StatusCode::BAD_REQUEST,
+            GatewayApiError::StreamingNotSupported(_) => StatusCode::BAD_REQUEST,
+            GatewayApiError::TooManyTools { .. } => StatusCode::BAD_REQUEST,
+            GatewayApiError::PromptTooLarge { .. } => StatusCode::BAD_REQUEST,
+            GatewayApiError::ResponseSchemaViolation(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            GatewayApiError::ModelNotAllowed(_) => StatusCode::FORBIDDEN,
+            GatewayApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            GatewayApiError::InvalidImageRequest(_) => StatusCode::BAD_REQUEST,
+            GatewayApiError::InvalidMetadata(_) => StatusCode::BAD_REQUEST,
+            GatewayApiError::UnsupportedFeatures { .. } => StatusCode::BAD_REQUEST,
         }
     }
 }
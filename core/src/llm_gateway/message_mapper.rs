@@ -49,7 +49,7 @@ impl MessageMapper {
                                             .to_string(),
                                     ))
                                 }
-                                ContentType::InputAudio => {
+                                ContentType::InputAudio | ContentType::Audio => {
                                     return Err(GatewayError::CustomError(
                                         "Input audio are not supported for system messages"
                                             .to_string(),
@@ -113,7 +113,7 @@ impl MessageMapper {
                                     .unwrap_or("".to_string()),
                                 additional_options: None,
                             },
-                            ContentType::InputAudio => {
+                            ContentType::InputAudio | ContentType::Audio => {
                                 let audio = c.audio.as_ref().ok_or(GatewayError::CustomError(
                                     "Audio data is empty".to_string(),
                                 ))?;
@@ -1,2 +1,3 @@
 pub mod message_mapper;
+pub mod presets;
 pub mod provider;
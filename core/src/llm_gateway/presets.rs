@@ -0,0 +1,248 @@
+use crate::models::{InferenceProvider, ModelMetadata};
+use crate::types::provider::InferenceModelProvider;
+
+/// OpenAI-compatible provider whose models can be used without being listed in `models.yaml`
+pub struct ProviderPreset {
+    /// Provider name, also used to look up its credentials
+    pub provider: &'static str,
+    /// Model name prefixes resolved to this provider
+    pub prefixes: &'static [&'static str],
+    /// Base URL of chat completions and embeddings
+    pub endpoint: &'static str,
+}
+
+pub const PRESETS: &[ProviderPreset] = &[
+    ProviderPreset {
+        provider: "togetherai",
+        prefixes: &["together", "togetherai"],
+        endpoint: "https://api.together.xyz/v1",
+    },
+    ProviderPreset {
+        provider: "fireworksai",
+        prefixes: &["fireworks", "fireworksai"],
+        endpoint: "https://api.fireworks.ai/inference/v1",
+    },
+];
+
+/// Preset serving models prefixed with `prefix`
+pub fn find_preset(prefix: &str) -> Option<&'static ProviderPreset> {
+    let prefix = prefix.to_lowercase();
+    PRESETS
+        .iter()
+        .find(|p| p.prefixes.contains(&prefix.as_str()))
+}
+
+/// Metadata of `together/...` and `fireworks/...` models. Their capabilities are unknown,
+/// so the provider decides which features it accepts.
+pub fn preset_model(model_name: &str) -> Option<ModelMetadata> {
+    let (prefix, name) = model_name.split_once('/')?;
+    let preset = find_preset(prefix)?;
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(ModelMetadata {
+        model: name.to_string(),
+        model_provider: preset.provider.to_string(),
+        inference_provider: InferenceProvider {
+            provider: InferenceModelProvider::Proxy(preset.provider.to_string()),
+            model_name: name.to_string(),
+            endpoint: Some(preset.endpoint.to_string()),
+        },
+        description: format!("{name} served by {}", preset.provider),
+        supports_tools: Some(true),
+        supports_vision: Some(true),
+        supports_json: Some(true),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_gateway::message_mapper::MessageMapper;
+    use crate::llm_gateway::provider::Provider;
+    use crate::model::proxy::OpenAISpecModel;
+    use crate::model::types::{ModelEvent, ModelEventType};
+    use crate::model::ModelInstance;
+    use crate::types::credentials::ApiKeyCredentials;
+    use crate::types::engine::{CompletionEngineParams, Prompt};
+    use crate::types::gateway::{
+        ChatCompletionContent, ChatCompletionMessage, CompletionModelUsage,
+    };
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_presets_resolve_to_endpoints() {
+        let model = preset_model("together/meta-llama/Llama-3.3-70B-Instruct-Turbo").unwrap();
+        assert_eq!(
+            model.inference_provider.provider,
+            InferenceModelProvider::Proxy("togetherai".to_string())
+        );
+        assert_eq!(
+            model.inference_provider.model_name,
+            "meta-llama/Llama-3.3-70B-Instruct-Turbo"
+        );
+        assert_eq!(
+            model.inference_provider.endpoint.as_deref(),
+            Some("https://api.together.xyz/v1")
+        );
+
+        let model =
+            preset_model("fireworks/accounts/fireworks/models/nomic-embed-text-v1.5").unwrap();
+        assert_eq!(
+            model.inference_provider.provider,
+            InferenceModelProvider::Proxy("fireworksai".to_string())
+        );
+        assert_eq!(
+            model.inference_provider.model_name,
+            "accounts/fireworks/models/nomic-embed-text-v1.5"
+        );
+        assert_eq!(
+            model.inference_provider.endpoint.as_deref(),
+            Some("https://api.fireworks.ai/inference/v1")
+        );
+
+        assert!(preset_model("fireworksai/accounts/fireworks/models/deepseek-r1").is_some());
+        assert!(preset_model("together/").is_none());
+        assert!(preset_model("openai/gpt-4o").is_none());
+    }
+
+    fn completion_body() -> serde_json::Value {
+        serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "meta-llama/Llama-3.3-70B-Instruct-Turbo",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Paris"},
+                "finish_reason": "stop"
+            }]
+        })
+    }
+
+    async fn mock_completions(
+        req: HttpRequest,
+        body: web::Json<serde_json::Value>,
+    ) -> HttpResponse {
+        assert_eq!(
+            req.headers().get("authorization").unwrap(),
+            "Bearer together-key"
+        );
+        assert_eq!(body["model"], "meta-llama/Llama-3.3-70B-Instruct-Turbo");
+        let mut response = completion_body();
+        response["usage"] =
+            serde_json::json!({"prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10});
+        HttpResponse::Ok().json(response)
+    }
+
+    async fn mock_completions_without_usage() -> HttpResponse {
+        HttpResponse::Ok().json(completion_body())
+    }
+
+    /// Sends a question to a together preset served by `addr` and returns the answer along
+    /// with the emitted events
+    async fn invoke_preset(
+        addr: std::net::SocketAddr,
+    ) -> (ChatCompletionMessage, Vec<Option<ModelEvent>>) {
+        let model = preset_model("together/meta-llama/Llama-3.3-70B-Instruct-Turbo").unwrap();
+        let engine = Provider::get_completion_engine_for_model(
+            &model,
+            &Default::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let CompletionEngineParams::Proxy { params, .. } = engine else {
+            panic!("Expected proxy params");
+        };
+        let instance = OpenAISpecModel::new(
+            params,
+            Some(&ApiKeyCredentials {
+                api_key: "together-key".to_string(),
+            }),
+            Default::default(),
+            Prompt::new("test".to_string(), String::new()),
+            HashMap::new(),
+            Some(&format!("http://{addr}/v1")),
+            &model.inference_provider.provider.to_string(),
+        )
+        .unwrap();
+
+        let message = MessageMapper::map_completions_message_to_langdb_message(
+            &ChatCompletionMessage::new_text(
+                "user".to_string(),
+                "What is the capital of France?".to_string(),
+            ),
+            &model.inference_provider.model_name,
+            "user",
+        )
+        .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let response = instance
+            .invoke(HashMap::new(), tx, vec![message], HashMap::new())
+            .await
+            .unwrap();
+
+        let mut events = vec![];
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        (response, events)
+    }
+
+    fn reported_usage(events: &[Option<ModelEvent>]) -> Option<CompletionModelUsage> {
+        events
+            .iter()
+            .flatten()
+            .find_map(|event| match &event.event {
+                ModelEventType::LlmStop(stop) => stop.usage.clone(),
+                _ => None,
+            })
+    }
+
+    #[actix_web::test]
+    async fn test_preset_completion_round_trip() {
+        let server = HttpServer::new(|| {
+            App::new().route("/v1/chat/completions", web::post().to(mock_completions))
+        })
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        let (response, events) = invoke_preset(addr).await;
+        let usage = reported_usage(&events).unwrap();
+        assert_eq!(usage.input_tokens, 9);
+        assert_eq!(usage.output_tokens, 1);
+
+        assert!(matches!(
+            response.content,
+            Some(ChatCompletionContent::Text(text)) if text == "Paris"
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_missing_usage_is_estimated() {
+        let server = HttpServer::new(|| {
+            App::new().route(
+                "/v1/chat/completions",
+                web::post().to(mock_completions_without_usage),
+            )
+        })
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        let (_, events) = invoke_preset(addr).await;
+        let usage = reported_usage(&events).expect("usage should be estimated");
+        assert!(usage.input_tokens > 0);
+        // "Paris" is five characters, about two tokens
+        assert_eq!(usage.output_tokens, 2);
+        assert_eq!(usage.total_tokens, usage.input_tokens + usage.output_tokens);
+    }
+}
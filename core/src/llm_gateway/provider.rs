@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use clust::messages::StopSequence;
 
 use crate::{
@@ -10,7 +8,7 @@ use crate::{
             AnthropicModelParams, BedrockModelParams, ClaudeModel, CompletionEngineParams,
             ExecutionOptions, GeminiModelParams, ImageGenerationEngineParams, OpenAiModelParams, OllamaModelParams,
         },
-        gateway::{ChatCompletionRequest, CreateImageRequest, ProviderSpecificRequest},
+        gateway::{ChatCompletionRequest, ProviderSpecificRequest},
         provider::{BedrockProvider, InferenceModelProvider},
     },
 };
@@ -27,22 +25,53 @@ impl Provider {
         provider_specific: Option<&ProviderSpecificRequest>,
         execution_options: Option<ExecutionOptions>,
     ) -> Result<CompletionEngineParams, GatewayError> {
+        if !matches!(
+            model.inference_provider.provider,
+            InferenceModelProvider::OpenAI | InferenceModelProvider::Proxy(_)
+        ) {
+            if request.service_tier.is_some() {
+                tracing::warn!(
+                    "service_tier is not supported by {}, ignoring it",
+                    model.inference_provider.provider
+                );
+            }
+            if request.modalities.is_some() || request.audio.is_some() {
+                tracing::warn!(
+                    "Audio output is not supported by {}, ignoring it",
+                    model.inference_provider.provider
+                );
+            }
+            if request.prediction.is_some() {
+                tracing::warn!(
+                    "prediction is not supported by {}, ignoring it",
+                    model.inference_provider.provider
+                );
+            }
+        }
+
         match model.inference_provider.provider {
             InferenceModelProvider::OpenAI | InferenceModelProvider::Proxy(_) => {
                 let params = OpenAiModelParams {
                     model: Some(model.inference_provider.model_name.clone()),
                     frequency_penalty: request.frequency_penalty,
                     logit_bias: request.logit_bias.clone(),
-                    logprobs: None,
-                    top_logprobs: None,
+                    logprobs: request.logprobs,
+                    top_logprobs: request.top_logprobs,
                     max_tokens: request.max_tokens,
                     presence_penalty: request.presence_penalty,
                     seed: request.seed,
+                    service_tier: request.service_tier.clone(),
+                    modalities: request.modalities.clone(),
+                    audio: request.audio.clone(),
+                    prediction: request.prediction.clone(),
+                    n: request.n,
+                    parallel_tool_calls: request.parallel_tool_calls,
                     stop: request.stop.clone(),
                     temperature: request.temperature,
                     top_p: request.top_p,
                     user: request.user.clone(),
                     response_format: request.response_format.clone(),
+                    extra_body: provider_specific.and_then(|ps| ps.extra_body.clone()),
                 };
                 let mut custom_endpoint = None;
                 let api_key_credentials = credentials.and_then(|cred| match cred {
@@ -91,7 +120,10 @@ impl Provider {
                         temperature: request.temperature,
                         top_p: request.top_p,
                         stop_sequences: request.stop.clone(),
-                        additional_parameters: HashMap::new(),
+                        additional_parameters: provider_specific
+                            .and_then(|ps| ps.extra_body.clone())
+                            .map(|extra_body| extra_body.into_iter().collect())
+                            .unwrap_or_default(),
                     },
                     provider,
                 })
@@ -135,6 +167,9 @@ impl Provider {
                                     budget_tokens: thinking.budget_tokens,
                                 })
                         }),
+                        disable_parallel_tool_use: provider_specific
+                            .and_then(|ps| ps.disable_parallel_tool_use)
+                            .or(request.parallel_tool_calls.map(|parallel| !parallel)),
                     },
                 })
             }
@@ -156,14 +191,13 @@ impl Provider {
                         presence_penalty: request.presence_penalty,
                         frequency_penalty: request.frequency_penalty,
                         seed: request.seed,
+                        response_logprobs: request.logprobs,
+                        logprobs: request.top_logprobs.map(i32::from),
                         // Not supported by request inteface
-                        // response_logprobs: request.response_logprobs,
-                        // logprobs: request.logprobs,
                         // top_k: request.top_k,
-                        response_logprobs: None,
-                        logprobs: None,
                         top_k: None,
                         response_format: request.response_format.clone(),
+                        extra_body: provider_specific.and_then(|ps| ps.extra_body.clone()),
                     },
                 })
             }
@@ -216,6 +250,7 @@ impl Provider {
                         logit_bias: request.logit_bias.clone(),
                         user: request.user.clone(),
                         n: request.n,
+                        dimensions: None,
                     },
                     endpoint: custom_endpoint,
                 })
@@ -269,6 +304,7 @@ impl Provider {
                         logit_bias: request.logit_bias.clone(),
                         user: request.user.clone(),
                         n: request.n,
+                        dimensions: None,
                     },
                     endpoint: custom_endpoint,
                 })
@@ -278,7 +314,7 @@ impl Provider {
 
     pub fn get_image_engine_for_model(
         model: &ModelMetadata,
-        request: &CreateImageRequest,
+        model_name: &str,
         credentials: Option<&Credentials>,
     ) -> Result<ImageGenerationEngineParams, GatewayError> {
         match model.inference_provider.provider {
@@ -295,7 +331,7 @@ impl Provider {
                         }
                         _ => None,
                     }),
-                    model_name: request.model.clone(),
+                    model_name: model_name.to_string(),
                     endpoint: custom_endpoint,
                 })
             }
@@ -304,7 +340,7 @@ impl Provider {
                     Credentials::ApiKey(key) => Some(key.clone()),
                     _ => None,
                 }),
-                model_name: request.model.clone(),
+                model_name: model_name.to_string(),
             }),
             InferenceModelProvider::Ollama => {
                 let mut custom_endpoint = None;
@@ -319,7 +355,7 @@ impl Provider {
                         }
                         _ => None,
                     }),
-                    model_name: request.model.clone(),
+                    model_name: model_name.to_string(),
                     endpoint: custom_endpoint,
                 })
             }
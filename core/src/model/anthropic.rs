@@ -6,13 +6,16 @@ use super::types::{
 };
 use super::{CredentialsIdent, ModelInstance};
 use crate::error::GatewayError;
+use crate::events;
+use crate::events::bodies::BodyRecordingMode;
 use crate::events::JsonValue;
 use crate::events::SPAN_ANTHROPIC;
-use crate::events::{self, RecordResult};
 use crate::model::error::AnthropicError;
 use crate::model::handler::handle_tool_call;
 use crate::model::types::LLMFirstToken;
-use crate::model::{async_trait, DEFAULT_MAX_RETRIES};
+use crate::model::{
+    async_trait, http_client, wait_retry_backoff, with_timeout, DEFAULT_MAX_RETRIES,
+};
 use crate::types::credentials::ApiKeyCredentials;
 use crate::types::engine::{AnthropicModelParams, ExecutionOptions, Prompt};
 use crate::types::gateway::CompletionModelUsage;
@@ -23,7 +26,7 @@ use crate::GatewayResult;
 use clust::messages::{
     Content, ContentBlock, ImageContentBlock, ImageContentSource, Message as ClustMessage,
     MessageChunk, MessagesRequestBody, MessagesRequestBuilder, StopReason, StreamError,
-    StreamOption, SystemPrompt, TextContentBlock, ToolDefinition, ToolResult,
+    StreamOption, SystemPrompt, TextContentBlock, ToolChoice, ToolDefinition, ToolResult,
     ToolResultContentBlock, ToolUse, ToolUseContentBlock, Usage,
 };
 use clust::Client;
@@ -56,6 +59,47 @@ fn custom_err(e: impl ToString) -> ModelError {
     ModelError::CustomError(e.to_string())
 }
 
+/// HTTP status of each error type Anthropic sends, for errors without a status line
+const ERROR_TYPE_STATUSES: [(&str, u16); 8] = [
+    ("invalid_request_error", 400),
+    ("authentication_error", 401),
+    ("permission_error", 403),
+    ("not_found_error", 404),
+    ("request_too_large", 413),
+    ("rate_limit_error", 429),
+    ("api_error", 500),
+    ("overloaded_error", 529),
+];
+
+/// clust doesn't expose the response status, it is read from the status line of the error
+/// or derived from the error type in its body
+fn error_status(message: &str) -> Option<u16> {
+    message
+        .split("API error: ")
+        .nth(1)
+        .and_then(|rest| rest.get(..3))
+        .and_then(|status| status.parse().ok())
+        .or_else(|| {
+            ERROR_TYPE_STATUSES
+                .iter()
+                .find(|(error_type, _)| message.contains(&format!("\"{error_type}\"")))
+                .map(|(_, status)| *status)
+        })
+}
+
+/// Classifies a failed Anthropic call by its status, so routers can fail over on it
+pub(crate) fn api_err(e: impl ToString) -> GatewayError {
+    let message = e.to_string();
+    match error_status(&message) {
+        Some(429) => GatewayError::RateLimited {
+            provider: SPAN_ANTHROPIC.to_string(),
+            retry_after: None,
+        },
+        Some(status) => GatewayError::provider_status(SPAN_ANTHROPIC, status, message),
+        None => custom_err(message).into(),
+    }
+}
+
 pub fn anthropic_client(
     credentials: Option<&ApiKeyCredentials>,
 ) -> Result<clust::Client, ModelError> {
@@ -64,7 +108,9 @@ pub fn anthropic_client(
     } else {
         std::env::var("LANGDB_ANTHROPIC_API_KEY").map_err(|_| AuthorizationError::InvalidApiKey)?
     };
-    let client = Client::from_api_key(clust::ApiKey::new(api_key));
+    let client = clust::ClientBuilder::new(clust::ApiKey::new(api_key))
+        .client(http_client(&ExecutionOptions::default())?)
+        .build();
     Ok(client)
 }
 
@@ -113,13 +159,15 @@ impl AnthropicModel {
         })
     }
 
+    /// Runs the tool calls of a response, one after another when `sequential`
     async fn handle_tool_calls(
         function_calls: impl Iterator<Item = &ToolUse>,
         tools: &HashMap<String, Box<dyn Tool>>,
         tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
+        sequential: bool,
     ) -> Vec<ClustMessage> {
-        futures::future::join_all(function_calls.map(|tool_use| {
+        let calls = function_calls.map(|tool_use| {
             let tags_value = tags.clone();
             async move {
                 let tool_call = Self::map_tool_call(tool_use);
@@ -141,8 +189,17 @@ impl AnthropicModel {
 
                 ClustMessage::user(result)
             }
-        }))
-        .await
+        });
+
+        if sequential {
+            let mut results = vec![];
+            for call in calls {
+                results.push(call.await);
+            }
+            results
+        } else {
+            futures::future::join_all(calls).await
+        }
     }
 
     fn build_request(
@@ -201,7 +258,13 @@ impl AnthropicModel {
                 tools.push(tool_definition(tool.deref()));
             }
 
-            builder.tools(tools)
+            let builder = builder.tools(tools);
+            match model_params.disable_parallel_tool_use {
+                Some(disable_parallel_tool_use) => builder.tool_choice(ToolChoice::Auto {
+                    disable_parallel_tool_use: Some(disable_parallel_tool_use),
+                }),
+                None => builder,
+            }
         } else {
             builder
         };
@@ -219,11 +282,12 @@ impl AnthropicModel {
         &self,
         stream: impl Stream<Item = Result<MessageChunk, StreamError>>,
         tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
-    ) -> GatewayResult<(StopReason, Vec<ToolUse>, Option<Usage>)> {
+    ) -> GatewayResult<(StopReason, Vec<ToolUse>, Option<Usage>, Option<String>)> {
         let mut tool_call_states: HashMap<u32, ToolUse> = HashMap::new();
         tokio::pin!(stream);
         let mut json_states: HashMap<u32, String> = HashMap::new();
         let mut input_tokens = 0;
+        let mut message_id = None;
         let mut first_response_received = false;
 
         loop {
@@ -245,6 +309,7 @@ impl AnthropicModel {
                                 &tracing::Span::current(),
                                 ModelEventType::LlmContent(LLMContentEvent {
                                     content: block.text,
+                                    index: 0,
                                 }),
                             )))
                             .await
@@ -255,6 +320,7 @@ impl AnthropicModel {
                                 &tracing::Span::current(),
                                 ModelEventType::LlmContent(LLMContentEvent {
                                     content: format!("thinking: {}", thinking.thinking),
+                                    index: 0,
                                 }),
                             )))
                             .await
@@ -271,6 +337,7 @@ impl AnthropicModel {
                                 &tracing::Span::current(),
                                 ModelEventType::LlmContent(LLMContentEvent {
                                     content: delta.text,
+                                    index: 0,
                                 }),
                             )))
                             .await
@@ -281,6 +348,7 @@ impl AnthropicModel {
                                 &tracing::Span::current(),
                                 ModelEventType::LlmContent(LLMContentEvent {
                                     content: delta.thinking,
+                                    index: 0,
                                 }),
                             )))
                             .await
@@ -300,6 +368,7 @@ impl AnthropicModel {
                     },
                     MessageChunk::MessageStart(start) => {
                         input_tokens = start.message.usage.input_tokens;
+                        message_id = Some(start.message.id);
                     }
 
                     MessageChunk::Ping(_) => {}
@@ -324,6 +393,7 @@ impl AnthropicModel {
                                 stop_reason,
                                 tool_call_states.values().cloned().collect(),
                                 usage,
+                                message_id,
                             ));
                         }
                     }
@@ -369,16 +439,16 @@ impl AnthropicModel {
 
         let response = async move {
             let result = self.client.create_a_message(request).await;
-            let _ = result
-                .as_ref()
-                .map(|response| serde_json::to_value(response).unwrap())
-                .as_ref()
-                .map(JsonValue)
-                .record();
-            let response = result.map_err(custom_err)?;
+            if let Err(e) = &result {
+                Span::current().record("error", e.to_string());
+            }
+            let response = result.map_err(api_err)?;
 
             let span = Span::current();
-            span.record("output", serde_json::to_string(&response)?);
+            span.record(
+                "output",
+                BodyRecordingMode::current().apply(&serde_json::to_string(&response)?),
+            );
 
             span.record(
                 "usage",
@@ -421,6 +491,9 @@ impl AnthropicModel {
                                 finish_reason: ModelFinishReason::Stop,
                                 tool_calls: vec![],
                                 credentials_ident: self.credentials_ident.clone(),
+                                logprobs: None,
+                                service_tier: None,
+                                response_id: Some(response.id.clone()),
                             }),
                         )))
                         .await
@@ -467,6 +540,9 @@ impl AnthropicModel {
                                 finish_reason: ModelFinishReason::Stop,
                                 tool_calls: vec![],
                                 credentials_ident: self.credentials_ident.clone(),
+                                logprobs: None,
+                                service_tier: None,
+                                response_id: Some(response.id.clone()),
                             }),
                         )))
                         .await
@@ -550,6 +626,9 @@ impl AnthropicModel {
                                 })
                                 .collect(),
                             credentials_ident: self.credentials_ident.clone(),
+                            logprobs: None,
+                            service_tier: None,
+                            response_id: Some(response.id.clone()),
                         }),
                     )))
                     .await
@@ -578,10 +657,15 @@ impl AnthropicModel {
                         ..Default::default()
                     }))
                 } else {
-                    let result_tool_calls =
-                        Self::handle_tool_calls(tool_runs.iter(), &self.tools, tx, tags.clone())
-                            .instrument(tools_span.clone())
-                            .await;
+                    let result_tool_calls = Self::handle_tool_calls(
+                        tool_runs.iter(),
+                        &self.tools,
+                        tx,
+                        tags.clone(),
+                        self.params.disable_parallel_tool_use == Some(true),
+                    )
+                    .instrument(tools_span.clone())
+                    .await;
                     messages.extend(result_tool_calls);
 
                     let conversation_messages = [input_messages, messages].concat();
@@ -607,11 +691,12 @@ impl AnthropicModel {
             .max_retries
             .unwrap_or(DEFAULT_MAX_RETRIES);
         while let Some((system_message, input_messages)) = calls.pop() {
-            let input = serde_json::to_string(&input_messages)?;
+            let input =
+                BodyRecordingMode::current().apply(&serde_json::to_string(&input_messages)?);
             let call_span = tracing::info_span!(
                 target: target!("chat"),
                 SPAN_ANTHROPIC,
-                input = input,
+                input = input.as_deref(),
                 output = field::Empty,
                 error = field::Empty,
                 ttft = field::Empty,
@@ -628,11 +713,16 @@ impl AnthropicModel {
                 .build_request(system_prompt.clone(), input_messages.clone(), false)
                 .map_err(custom_err)?;
 
-            call_span.record("system_prompt", format!("{system_prompt}"));
+            call_span.record(
+                "system_prompt",
+                BodyRecordingMode::current().apply(&system_prompt.to_string()),
+            );
 
-            match self
-                .execute_inner(call_span.clone(), request, tx, tags.clone())
-                .await
+            match with_timeout(
+                &self.execution_options,
+                self.execute_inner(call_span.clone(), request, tx, tags.clone()),
+            )
+            .await
             {
                 Ok(InnerExecutionResult::Finish(message)) => return Ok(message),
                 Ok(InnerExecutionResult::NextCall((system_prompt, messages))) => {
@@ -644,6 +734,7 @@ impl AnthropicModel {
                     if retries == 0 {
                         return Err(e);
                     } else {
+                        wait_retry_backoff(&self.execution_options).await;
                         calls.push((Some(system_prompt), input_messages));
                     }
                 }
@@ -666,11 +757,12 @@ impl AnthropicModel {
             .max_retries
             .unwrap_or(DEFAULT_MAX_RETRIES);
         while let Some((system_message, input_messages)) = calls.pop() {
-            let input = serde_json::to_string(&input_messages)?;
+            let input =
+                BodyRecordingMode::current().apply(&serde_json::to_string(&input_messages)?);
             let call_span = tracing::info_span!(
                 target: target!("chat"),
                 SPAN_ANTHROPIC,
-                input = input,
+                input = input.as_deref(),
                 output = field::Empty,
                 error = field::Empty,
                 ttft = field::Empty,
@@ -687,11 +779,16 @@ impl AnthropicModel {
                 .build_request(system_prompt.clone(), input_messages.clone(), true)
                 .map_err(custom_err)?;
 
-            call_span.record("system_prompt", format!("{system_prompt}"));
+            call_span.record(
+                "system_prompt",
+                BodyRecordingMode::current().apply(&system_prompt.to_string()),
+            );
 
-            match self
-                .execute_stream_inner(request, call_span.clone(), tx, tags.clone())
-                .await
+            match with_timeout(
+                &self.execution_options,
+                self.execute_stream_inner(request, call_span.clone(), tx, tags.clone()),
+            )
+            .await
             {
                 Ok(InnerExecutionResult::Finish(_)) => return Ok(()),
                 Ok(InnerExecutionResult::NextCall((system_prompt, messages))) => {
@@ -703,6 +800,7 @@ impl AnthropicModel {
                     if retries == 0 {
                         return Err(e);
                     } else {
+                        wait_retry_backoff(&self.execution_options).await;
                         calls.push((Some(system_prompt), input_messages));
                     }
                 }
@@ -769,8 +867,8 @@ impl AnthropicModel {
             .client
             .create_a_message_stream(request)
             .await
-            .map_err(custom_err)?;
-        let (stop_reason, tool_calls, usage) = self
+            .map_err(api_err)?;
+        let (stop_reason, tool_calls, usage, message_id) = self
             .process_stream(stream, tx)
             .instrument(span.clone())
             .await?;
@@ -798,6 +896,9 @@ impl AnthropicModel {
                     .iter()
                     .map(Self::map_tool_call)
                     .collect::<Result<Vec<ModelToolCall>, GatewayError>>()?,
+                logprobs: None,
+                service_tier: None,
+                response_id: message_id,
             }),
         )))
         .await
@@ -807,7 +908,10 @@ impl AnthropicModel {
             "stop_reason": trace_finish_reason,
             "tool_calls": tool_calls
         });
-        span.record("output", response.to_string());
+        span.record(
+            "output",
+            BodyRecordingMode::current().apply(&response.to_string()),
+        );
 
         match stop_reason {
             StopReason::EndTurn | StopReason::StopSequence => {
@@ -843,10 +947,15 @@ impl AnthropicModel {
                             .map(|t| ContentBlock::ToolUse(ToolUseContentBlock::new(t.clone())))
                             .collect(),
                     ))];
-                    let result_tool_calls =
-                        Self::handle_tool_calls(tool_calls.iter(), &self.tools, tx, tags.clone())
-                            .instrument(tools_span.clone())
-                            .await;
+                    let result_tool_calls = Self::handle_tool_calls(
+                        tool_calls.iter(),
+                        &self.tools,
+                        tx,
+                        tags.clone(),
+                        self.params.disable_parallel_tool_use == Some(true),
+                    )
+                    .instrument(tools_span.clone())
+                    .await;
                     messages.extend(result_tool_calls);
 
                     let conversation_messages = [input_messages, messages].concat();
@@ -963,10 +1072,15 @@ impl AnthropicModel {
             .map(|message| map_system_message(message.to_owned(), &input_variables));
 
         if system_message.is_none() {
-            system_message = previous_messages
+            // Anthropic takes a single system prompt, so all system messages are joined
+            let system_texts = previous_messages
                 .iter()
-                .find(|m| m.r#type == MessageType::SystemMessage)
-                .map(|message| SystemPrompt::new(message.content.clone().unwrap_or_default()));
+                .filter(|m| m.r#type == MessageType::SystemMessage)
+                .filter_map(|m| m.content.clone())
+                .collect::<Vec<_>>();
+            if !system_texts.is_empty() {
+                system_message = Some(SystemPrompt::new(system_texts.join("\n\n")));
+            }
         }
 
         let previous_messages = Self::map_previous_messages(previous_messages)?;
@@ -1057,3 +1171,181 @@ pub fn record_map_err(e: impl Into<GatewayError> + ToString, span: tracing::Span
     span.record("error", e.to_string());
     e.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorClass;
+
+    #[test]
+    fn test_rate_limit_becomes_rate_limited_error() {
+        let body = r#"{"type":"error","error":{"type":"rate_limit_error","message":"Number of requests has exceeded your rate limit"}}"#;
+        assert!(matches!(
+            api_err(format!("API error: 429 Too Many Requests {body}")),
+            GatewayError::RateLimited { provider, retry_after: None } if provider == SPAN_ANTHROPIC
+        ));
+    }
+
+    #[test]
+    fn test_errors_are_classified_by_status() {
+        let body =
+            r#"{"type":"error","error":{"type":"api_error","message":"Internal server error"}}"#;
+        assert_eq!(
+            api_err(format!("API error: 503 Service Unavailable {body}")).class(),
+            ErrorClass::ServerError
+        );
+        assert_eq!(
+            api_err(r#"{"type":"error","error":{"type":"overloaded_error"}}"#).class(),
+            ErrorClass::ServerError
+        );
+        let body = r#"{"type":"error","error":{"type":"invalid_request_error","message":"max_tokens: Field required"}}"#;
+        assert_eq!(
+            api_err(format!("API error: 400 Bad Request {body}")).class(),
+            ErrorClass::ClientError
+        );
+        assert_eq!(api_err("connection reset").class(), ErrorClass::Other);
+    }
+
+    #[test]
+    fn test_disable_parallel_tool_use_is_sent() {
+        let model = crate::models::ModelMetadata {
+            model: "claude-3-5-sonnet".to_string(),
+            model_provider: "anthropic".to_string(),
+            inference_provider: crate::models::InferenceProvider {
+                provider: crate::types::provider::InferenceModelProvider::Anthropic,
+                model_name: "claude-3-5-sonnet".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let tool: crate::types::gateway::ChatCompletionTool =
+            serde_json::from_value(serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Weather of a city",
+                    "parameters": {"type": "object", "properties": {}}
+                }
+            }))
+            .unwrap();
+        let payload = |request: serde_json::Value| {
+            let request: crate::types::gateway::ChatCompletionRequest =
+                serde_json::from_value(request).unwrap();
+            let engine = crate::llm_gateway::provider::Provider::get_completion_engine_for_model(
+                &model, &request, None, None, None,
+            )
+            .unwrap();
+            let crate::types::engine::CompletionEngineParams::Anthropic { params, .. } = engine
+            else {
+                panic!("Expected Anthropic params");
+            };
+            let model = AnthropicModel::new(
+                params,
+                ExecutionOptions::default(),
+                Some(&ApiKeyCredentials {
+                    api_key: "sk-ant-test".to_string(),
+                }),
+                Prompt::new("test".to_string(), String::new()),
+                HashMap::from([(
+                    "get_weather".to_string(),
+                    Box::new(crate::model::tools::GatewayTool { def: tool.clone() })
+                        as Box<dyn Tool>,
+                )]),
+            )
+            .unwrap();
+            let body = model
+                .build_request(SystemPrompt::new(""), vec![], false)
+                .unwrap();
+            serde_json::to_value(body).unwrap()
+        };
+
+        let body = payload(serde_json::json!({
+            "model": "anthropic/claude-3-5-sonnet",
+            "parallel_tool_calls": false
+        }));
+        assert_eq!(
+            body["tool_choice"],
+            serde_json::json!({"type": "auto", "disable_parallel_tool_use": true})
+        );
+
+        let body = payload(serde_json::json!({"model": "anthropic/claude-3-5-sonnet"}));
+        assert!(body.get("tool_choice").map_or(true, Value::is_null));
+    }
+
+    /// Tool logging when its calls start and end
+    struct LoggingTool(Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl Tool for LoggingTool {
+        fn name(&self) -> String {
+            "get_weather".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Logs its calls".to_string()
+        }
+
+        fn get_function_parameters(&self) -> Option<crate::types::gateway::FunctionParameters> {
+            None
+        }
+
+        async fn run(
+            &self,
+            input: HashMap<String, Value>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<Value> {
+            let city = input["city"].as_str().unwrap().to_string();
+            self.0.lock().unwrap().push(format!("start {city}"));
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.0.lock().unwrap().push(format!("end {city}"));
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_run_sequentially_without_parallel_tool_use() {
+        let tool_uses = [
+            ToolUse::new(
+                "call_1".to_string(),
+                "get_weather".to_string(),
+                serde_json::json!({"city": "Paris"}),
+            ),
+            ToolUse::new(
+                "call_2".to_string(),
+                "get_weather".to_string(),
+                serde_json::json!({"city": "Rome"}),
+            ),
+        ];
+        let run = |sequential: bool| {
+            let log = Arc::new(std::sync::Mutex::new(vec![]));
+            let tools = HashMap::from([(
+                "get_weather".to_string(),
+                Box::new(LoggingTool(log.clone())) as Box<dyn Tool>,
+            )]);
+            let tool_uses = &tool_uses;
+            async move {
+                let (tx, _rx) = tokio::sync::mpsc::channel(10);
+                let results = AnthropicModel::handle_tool_calls(
+                    tool_uses.iter(),
+                    &tools,
+                    &tx,
+                    HashMap::new(),
+                    sequential,
+                )
+                .await;
+                assert_eq!(results.len(), 2);
+                let log = log.lock().unwrap();
+                log.clone()
+            }
+        };
+
+        assert_eq!(
+            run(true).await,
+            vec!["start Paris", "end Paris", "start Rome", "end Rome"]
+        );
+        assert_eq!(
+            run(false).await[..2],
+            ["start Paris".to_string(), "start Rome".to_string()]
+        );
+    }
+}
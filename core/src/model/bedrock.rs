@@ -3,13 +3,15 @@ use super::types::{
     ModelToolCall,
 };
 use super::{CredentialsIdent, ModelInstance};
-use crate::error::GatewayError;
-use crate::events::{self, JsonValue, RecordResult, SPAN_BEDROCK};
+use crate::error::{ErrorClass, GatewayError};
+use crate::events::bodies::BodyRecordingMode;
+use crate::events::{self, JsonValue, SPAN_BEDROCK};
 use crate::model::error::BedrockError;
-use crate::model::handler::handle_tool_call;
+use crate::model::handler::{handle_tool_call, ToolCallError};
+use crate::model::tls;
 use crate::model::types::LLMFirstToken;
 use crate::model::Tool as LangdbTool;
-use crate::model::DEFAULT_MAX_RETRIES;
+use crate::model::{wait_retry_backoff, with_timeout, ToolCallIterations, DEFAULT_MAX_RETRIES};
 use crate::models::BedrockMetaCompletionModel;
 use crate::types::aws::{get_shared_config, get_user_shared_config};
 use crate::types::credentials::AwsCredentials;
@@ -18,7 +20,7 @@ use crate::types::gateway::{
     ChatCompletionContent, ChatCompletionMessage, CompletionModelUsage, ToolCall,
 };
 use crate::types::message::{MessageType, PromptMessage};
-use crate::types::provider::BedrockProvider;
+use crate::types::provider::{BedrockInferenceProfile, BedrockProvider};
 use crate::types::threads::InnerMessage;
 use crate::types::threads::Message as LMessage;
 use crate::GatewayResult;
@@ -35,6 +37,7 @@ use aws_sdk_bedrockruntime::types::{
     ToolResultStatus, ToolSpecification, ToolUseBlock,
 };
 use aws_sdk_bedrockruntime::Client;
+use aws_smithy_runtime_api::client::result::SdkError;
 use aws_smithy_types::{Blob, Document};
 use base64::Engine;
 use serde::de::IntoDeserializer;
@@ -69,6 +72,8 @@ fn build_err(e: impl ToString) -> ModelError {
 
 pub struct BedrockModel {
     pub client: Client,
+    /// Clients of the regions tried after `client`'s, in order
+    pub failover_clients: Vec<Client>,
     pub execution_options: ExecutionOptions,
     prompt: Prompt,
     params: BedrockModelParams,
@@ -84,23 +89,124 @@ pub struct BedrockToolCall {
     pub properties: Value,
 }
 
-pub async fn bedrock_client(credentials: Option<&AwsCredentials>) -> Result<Client, ModelError> {
-    let config = match credentials {
-        Some(creds) => get_user_shared_config(creds.clone()).await.load().await,
+pub async fn bedrock_client(
+    credentials: Option<&AwsCredentials>,
+    region: Option<&str>,
+) -> Result<Client, ModelError> {
+    if tls::provider_tls().is_some() {
+        return Err(ModelError::CustomError(
+            "Provider TLS policy can't be applied to Bedrock connections".to_string(),
+        ));
+    }
+    let mut loader = match credentials {
+        Some(creds) => get_user_shared_config(creds.clone()).await,
         None => {
             // TODO: read from env
-            get_shared_config(Some(aws_config::Region::new("us-east-1".to_string())))
-                .await
-                .load()
-                .await
+            get_shared_config(Some(aws_config::Region::new("us-east-1".to_string()))).await
         }
     };
-    let client = Client::new(&config);
+    if let Some(region) = region {
+        loader = loader.region(aws_config::Region::new(region.to_string()));
+    }
+    let client = Client::new(&loader.load().await);
     Ok(client)
 }
 
+/// Throttling and outages are local to a region, the next region may still serve the call
+fn bedrock_error(error: &GatewayError) -> Option<&BedrockError> {
+    match error {
+        GatewayError::ModelError(error) => match error.as_ref() {
+            ModelError::Bedrock(error) => Some(error),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_region_failure(error: &GatewayError) -> bool {
+    let Some(error) = bedrock_error(error) else {
+        return false;
+    };
+    match error {
+        BedrockError::ConverseError(e) => match e {
+            SdkError::DispatchFailure(_) | SdkError::TimeoutError(_) => true,
+            _ => e.as_service_error().is_some_and(|e| {
+                e.is_throttling_exception()
+                    || e.is_service_unavailable_exception()
+                    || e.is_internal_server_exception()
+            }),
+        },
+        BedrockError::ResponseError(e) => match e {
+            SdkError::DispatchFailure(_) | SdkError::TimeoutError(_) => true,
+            _ => e.as_service_error().is_some_and(|e| {
+                e.is_throttling_exception()
+                    || e.is_service_unavailable_exception()
+                    || e.is_internal_server_exception()
+            }),
+        },
+        _ => false,
+    }
+}
+
+fn sdk_error_class<E>(
+    error: &SdkError<E, aws_smithy_runtime_api::http::Response>,
+) -> Option<ErrorClass> {
+    match error {
+        SdkError::TimeoutError(_) => Some(ErrorClass::Timeout),
+        SdkError::DispatchFailure(_) => Some(ErrorClass::ServerError),
+        _ => error
+            .raw_response()
+            .map(|response| ErrorClass::from_status(response.status().as_u16())),
+    }
+}
+
+/// Reports the kind of failure a Bedrock request ended with, a request Bedrock kept
+/// throttling as a rate limit
+fn classified(error: GatewayError) -> GatewayError {
+    let (throttled, class) = match bedrock_error(&error) {
+        Some(BedrockError::ConverseError(e)) => (
+            e.as_service_error()
+                .is_some_and(|e| e.is_throttling_exception()),
+            sdk_error_class(e),
+        ),
+        Some(BedrockError::ResponseError(e)) => (
+            e.as_service_error()
+                .is_some_and(|e| e.is_throttling_exception()),
+            sdk_error_class(e),
+        ),
+        _ => (false, None),
+    };
+    if throttled {
+        return GatewayError::RateLimited {
+            provider: SPAN_BEDROCK.to_string(),
+            retry_after: None,
+        };
+    }
+    match class {
+        Some(class) => GatewayError::ProviderError {
+            provider: SPAN_BEDROCK.to_string(),
+            class,
+            message: error.to_string(),
+        },
+        None => error,
+    }
+}
+
 impl BedrockModel {
-    fn get_model_region(model_id: &str) -> Option<String> {
+    fn get_model_region(
+        provider_name: &str,
+        model_id: &str,
+        inference_profiles: Option<&HashMap<String, BedrockInferenceProfile>>,
+    ) -> Option<String> {
+        if let Some(profiles) = inference_profiles {
+            let profile = profiles
+                .get(&format!("{provider_name}.{model_id}"))
+                .or_else(|| profiles.get(model_id));
+            if let Some(profile) = profile {
+                return Some(profile.to_string());
+            }
+        }
+
         let us_models = [
             BedrockMetaCompletionModel::Llama318BInstruct.to_string(),
             BedrockMetaCompletionModel::Llama3170BInstruct.to_string(),
@@ -117,6 +223,20 @@ impl BedrockModel {
         }
     }
 
+    fn resolve_model_name(
+        model_id: &str,
+        provider: &BedrockProvider,
+        inference_profiles: Option<&HashMap<String, BedrockInferenceProfile>>,
+    ) -> String {
+        let provider_name = provider.to_string();
+        let model_id = replace_version(model_id);
+        match Self::get_model_region(&provider_name, &model_id, inference_profiles) {
+            Some(region) => format!("{region}.{provider_name}.{model_id}"),
+            None => format!("{provider_name}.{model_id}"),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         model_params: BedrockModelParams,
         execution_options: ExecutionOptions,
@@ -124,24 +244,24 @@ impl BedrockModel {
         prompt: Prompt,
         tools: HashMap<String, Box<dyn LangdbTool>>,
         provider: BedrockProvider,
+        inference_profiles: Option<&HashMap<String, BedrockInferenceProfile>>,
+        regions: &[String],
     ) -> Result<Self, ModelError> {
-        let client = bedrock_client(credentials).await?;
+        let client = bedrock_client(credentials, regions.first().map(String::as_str)).await?;
+        let mut failover_clients = vec![];
+        for region in regions.iter().skip(1) {
+            failover_clients.push(bedrock_client(credentials, Some(region)).await?);
+        }
 
         let model_id = model_params.model_id.clone().unwrap_or_default();
         let model_name = match credentials {
             Some(_) => model_id,
-            None => {
-                let provider_name = provider.to_string();
-                let model_id = replace_version(&model_id);
-                match Self::get_model_region(&model_id) {
-                    Some(region) => format!("{region}.{provider_name}.{model_id}"),
-                    None => format!("{provider_name}.{model_id}"),
-                }
-            }
+            None => Self::resolve_model_name(&model_id, &provider, inference_profiles),
         };
 
         Ok(Self {
             client,
+            failover_clients,
             execution_options,
             prompt,
             params: model_params,
@@ -327,12 +447,15 @@ impl BedrockModel {
                 let tool_call = Self::map_tool_call(tool)?;
                 let result = handle_tool_call(&tool_call, tools, tx, tags_value.clone()).await;
                 tracing::trace!("Result ({tool_use_id}): {result:?}");
-                let content = result.unwrap_or_else(|err| err.to_string());
+                let (content, status) = match result {
+                    Ok(content) => (content, ToolResultStatus::Success),
+                    Err(err) => (ToolCallError::from(&err).to_json(), ToolResultStatus::Error),
+                };
                 Ok(ContentBlock::ToolResult(
                     ToolResultBlock::builder()
                         .tool_use_id(tool_use_id.clone())
                         .content(ToolResultContentBlock::Text(content))
-                        .status(ToolResultStatus::Success)
+                        .status(status)
                         .build()
                         .unwrap(),
                 ))
@@ -385,8 +508,17 @@ impl BedrockModel {
         Ok(Some(config))
     }
 
+    /// Client of the `region`th configured region
+    fn region_client(&self, region: usize) -> &Client {
+        match region {
+            0 => &self.client,
+            region => &self.failover_clients[region - 1],
+        }
+    }
+
     pub fn build_request(
         &self,
+        region: usize,
         input_messages: &[Message],
         system_messages: &[SystemContentBlock],
     ) -> GatewayResult<ConverseFluentBuilder> {
@@ -399,18 +531,13 @@ impl BedrockModel {
             .build();
 
         Ok(self
-            .client
+            .region_client(region)
             .converse()
             .set_system(Some(system_messages.to_vec()))
             .set_tool_config(self.get_tools_config()?)
             .model_id(replace_version(&self.model_name))
             .set_messages(Some(input_messages.to_vec()))
-            .additional_model_request_fields(Document::deserialize(
-                model_params
-                    .additional_parameters
-                    .clone()
-                    .into_deserializer(),
-            )?)
+            .additional_model_request_fields(additional_model_request_fields(model_params)?)
             .set_inference_config(Some(inference_config)))
     }
 
@@ -427,11 +554,14 @@ impl BedrockModel {
             .execution_options
             .max_retries
             .unwrap_or(DEFAULT_MAX_RETRIES);
+        let mut tool_call_iterations = ToolCallIterations::new(&self.execution_options);
+        let mut region = 0;
         while let Some(input_messages) = calls.pop() {
             let input = serde_json::json!({
                 "initial_messages": format!("{input_messages:?}"),
                 "system_messages": format!("{system_messages:?}")
             });
+            let input = BodyRecordingMode::current().apply(&input.to_string());
             let span = tracing::info_span!(
                 target: target!("chat"),
                 SPAN_BEDROCK,
@@ -440,27 +570,46 @@ impl BedrockModel {
                 error = field::Empty,
                 usage = field::Empty,
                 cost = field::Empty,
-                input = JsonValue(&input).as_value(),
+                input = input.as_deref(),
                 tags = JsonValue(&serde_json::to_value(tags.clone()).unwrap_or_default()).as_value(),
                 retries_left = retries
             );
 
-            let builder = self.build_request(&input_messages, &system_messages)?;
-            let response = self
-                .execute_inner(builder, span.clone(), tx, tags.clone())
-                .await;
+            let builder = self.build_request(region, &input_messages, &system_messages)?;
+            let response = with_timeout(
+                &self.execution_options,
+                self.execute_inner(
+                    builder,
+                    span.clone(),
+                    tx,
+                    tags.clone(),
+                    &mut tool_call_iterations,
+                ),
+            )
+            .await;
 
             match response {
                 Ok(InnerExecutionResult::Finish(message)) => return Ok(message),
                 Ok(InnerExecutionResult::NextCall(messages)) => {
                     calls.push(messages);
                 }
+                Err(e) if ToolCallIterations::reached(&e) => {
+                    span.record("error", e.to_string());
+                    return Err(e);
+                }
+                Err(e) if region < self.failover_clients.len() && is_region_failure(&e) => {
+                    span.record("error", e.to_string());
+                    tracing::warn!("Bedrock region failed, retrying in the next one: {e}");
+                    region += 1;
+                    calls.push(input_messages);
+                }
                 Err(e) => {
                     retries -= 1;
                     span.record("error", e.to_string());
                     if retries == 0 {
-                        return Err(e);
+                        return Err(classified(e));
                     } else {
+                        wait_retry_backoff(&self.execution_options).await;
                         calls.push(input_messages);
                     }
                 }
@@ -475,6 +624,7 @@ impl BedrockModel {
         span: Span,
         tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
+        tool_call_iterations: &mut ToolCallIterations,
     ) -> GatewayResult<InnerExecutionResult> {
         let input_messages = builder.get_messages().clone().unwrap_or_default();
         tx.send(Some(ModelEvent::new(
@@ -490,16 +640,16 @@ impl BedrockModel {
 
         let response = async move {
             let result = builder.send().await;
-            let _ = result
-                .as_ref()
-                .map(|response| Value::String(format!("{response:?}")))
-                .as_ref()
-                .map(JsonValue)
-                .record();
+            if let Err(e) = &result {
+                Span::current().record("error", e.to_string());
+            }
             let response = result.map_err(|e| ModelError::Bedrock(Box::new(e.into())))?;
             let span = Span::current();
 
-            span.record("output", format!("{response:?}"));
+            span.record(
+                "output",
+                BodyRecordingMode::current().apply(&format!("{response:?}")),
+            );
             if let Some(ref usage) = response.usage {
                 span.record(
                     "usage",
@@ -546,6 +696,9 @@ impl BedrockModel {
                             finish_reason: ModelFinishReason::Stop,
                             tool_calls: vec![],
                             credentials_ident: self.credentials_ident.clone(),
+                            logprobs: None,
+                            service_tier: None,
+                            response_id: None,
                         }),
                     )))
                     .await
@@ -654,6 +807,9 @@ impl BedrockModel {
                                             .collect::<Result<Vec<ModelToolCall>, GatewayError>>(
                                         )?,
                                         credentials_ident: self.credentials_ident.clone(),
+                                        logprobs: None,
+                                        service_tier: None,
+                                        response_id: None,
                                     }),
                                 )))
                                 .await
@@ -666,6 +822,8 @@ impl BedrockModel {
                                     ..Default::default()
                                 }))
                             } else {
+                                tool_call_iterations
+                                    .next_round(|| last_assistant_text(&messages))?;
                                 let tools_message = Self::handle_tool_calls(
                                     tool_uses,
                                     &self.tools,
@@ -724,7 +882,10 @@ impl BedrockModel {
                         Some(ContentBlockDelta::Text(t)) => {
                             tx.send(Some(ModelEvent::new(
                                 &Span::current(),
-                                ModelEventType::LlmContent(LLMContentEvent { content: t }),
+                                ModelEventType::LlmContent(LLMContentEvent {
+                                    content: t,
+                                    index: 0,
+                                }),
                             )))
                             .await
                             .unwrap();
@@ -831,11 +992,14 @@ impl BedrockModel {
             .execution_options
             .max_retries
             .unwrap_or(DEFAULT_MAX_RETRIES);
+        let mut tool_call_iterations = ToolCallIterations::new(&self.execution_options);
+        let mut region = 0;
         while let Some(input_messages) = calls.pop() {
             let input = serde_json::json!({
                 "initial_messages": format!("{input_messages:?}"),
                 "system_messages": format!("{system_messages:?}")
             });
+            let input = BodyRecordingMode::current().apply(&input.to_string());
             let span = tracing::info_span!(
                 target: target!("chat"),
                 SPAN_BEDROCK,
@@ -844,34 +1008,54 @@ impl BedrockModel {
                 error = field::Empty,
                 usage = field::Empty,
                 cost = field::Empty,
-                input = JsonValue(&input).as_value(),
+                input = input.as_deref(),
                 tags = JsonValue(&serde_json::to_value(tags.clone()).unwrap_or_default()).as_value(),
                 retries_left = retries
             );
 
             let builder = self
-                .client
+                .region_client(region)
                 .converse_stream()
                 .model_id(replace_version(&self.model_name))
                 .set_system(Some(system_messages.clone()))
                 .set_tool_config(self.get_tools_config()?)
-                .set_messages(Some(input_messages.clone()));
-
-            let response = self
-                .execute_stream_inner(builder, span.clone(), tx, tags.clone())
-                .await;
+                .set_messages(Some(input_messages.clone()))
+                .additional_model_request_fields(additional_model_request_fields(&self.params)?);
+
+            let response = with_timeout(
+                &self.execution_options,
+                self.execute_stream_inner(
+                    builder,
+                    span.clone(),
+                    tx,
+                    tags.clone(),
+                    &mut tool_call_iterations,
+                ),
+            )
+            .await;
 
             match response {
                 Ok(InnerExecutionResult::Finish(_)) => return Ok(()),
                 Ok(InnerExecutionResult::NextCall(messages)) => {
                     calls.push(messages);
                 }
+                Err(e) if ToolCallIterations::reached(&e) => {
+                    span.record("error", e.to_string());
+                    return Err(e);
+                }
+                Err(e) if region < self.failover_clients.len() && is_region_failure(&e) => {
+                    span.record("error", e.to_string());
+                    tracing::warn!("Bedrock region failed, retrying in the next one: {e}");
+                    region += 1;
+                    calls.push(input_messages);
+                }
                 Err(e) => {
                     retries -= 1;
                     span.record("error", e.to_string());
                     if retries == 0 {
-                        return Err(e);
+                        return Err(classified(e));
                     } else {
+                        wait_retry_backoff(&self.execution_options).await;
                         calls.push(input_messages);
                     }
                 }
@@ -887,6 +1071,7 @@ impl BedrockModel {
         span: Span,
         tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
+        tool_call_iterations: &mut ToolCallIterations,
     ) -> GatewayResult<InnerExecutionResult> {
         let input_messages = builder.get_messages().clone().unwrap_or_default();
 
@@ -937,6 +1122,9 @@ impl BedrockModel {
                 finish_reason: trace_finish_reason.clone(),
                 tool_calls: tool_calls.clone(),
                 credentials_ident: self.credentials_ident.clone(),
+                logprobs: None,
+                service_tier: None,
+                response_id: None,
             }),
         )))
         .await
@@ -946,7 +1134,10 @@ impl BedrockModel {
             "stop_reason": format!("{stop_reason:?}"),
             "msg": format!("{msg:?}")
         });
-        span.record("output", response.to_string());
+        span.record(
+            "output",
+            BodyRecordingMode::current().apply(&response.to_string()),
+        );
         match stop_reason {
             StopReason::ToolUse => {
                 let Some((role, tool_uses)) = msg else {
@@ -977,6 +1168,8 @@ impl BedrockModel {
                     .build()
                     .map_err(build_err)?;
                 conversational_messages.push(message);
+                tool_call_iterations
+                    .next_round(|| last_assistant_text(&conversational_messages))?;
                 let result_tool_calls =
                     Self::handle_tool_calls(tool_uses, &self.tools, tx, tags.clone())
                         .instrument(tools_span.clone())
@@ -1097,6 +1290,21 @@ fn replace_version(model: &str) -> String {
         .to_string()
 }
 
+/// Text of the latest assistant message, returned as the partial result of a stopped agent loop
+fn last_assistant_text(messages: &[Message]) -> Option<String> {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role() == &ConversationRole::Assistant)
+        .map(|m| {
+            m.content()
+                .iter()
+                .filter_map(|c| c.as_text().ok().cloned())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+}
+
 fn map_converse_stream_error(
     e: aws_smithy_runtime_api::client::result::SdkError<
         aws_sdk_bedrockruntime::operation::converse_stream::ConverseStreamError,
@@ -1113,3 +1321,295 @@ fn map_converse_stream_error(
         _ => ModelError::Bedrock(Box::new(e.into())),
     }
 }
+
+/// Parameters outside the common inference configuration, sent as `additionalModelRequestFields`
+fn additional_model_request_fields(params: &BedrockModelParams) -> GatewayResult<Document> {
+    Ok(Document::deserialize(
+        params.additional_parameters.clone().into_deserializer(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FailingTool;
+
+    #[test]
+    fn test_configured_inference_profile() {
+        let profiles = HashMap::from([(
+            "anthropic.claude-3-5-sonnet-20240620-v1:0".to_string(),
+            BedrockInferenceProfile::Eu,
+        )]);
+
+        let model_name = BedrockModel::resolve_model_name(
+            "claude-3-5-sonnet-20240620-v1.0",
+            &BedrockProvider::Other("anthropic".to_string()),
+            Some(&profiles),
+        );
+        assert_eq!(model_name, "eu.anthropic.claude-3-5-sonnet-20240620-v1:0");
+
+        // Configured profile takes precedence over the builtin list
+        let profiles = HashMap::from([(
+            BedrockMetaCompletionModel::Llama3370BInstruct.to_string(),
+            BedrockInferenceProfile::Apac,
+        )]);
+        let model_name = BedrockModel::resolve_model_name(
+            &BedrockMetaCompletionModel::Llama3370BInstruct.to_string(),
+            &BedrockProvider::Meta,
+            Some(&profiles),
+        );
+        assert_eq!(
+            model_name,
+            format!(
+                "apac.meta.{}",
+                BedrockMetaCompletionModel::Llama3370BInstruct
+            )
+        );
+    }
+
+    #[test]
+    fn test_extra_body_is_forwarded() {
+        let model = crate::models::ModelMetadata {
+            model: "claude-3-5-sonnet".to_string(),
+            model_provider: "anthropic".to_string(),
+            inference_provider: crate::models::InferenceProvider {
+                provider: crate::types::provider::InferenceModelProvider::Bedrock,
+                model_name: "anthropic.claude-3-5-sonnet-20240620-v1:0".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let provider_specific: crate::types::gateway::ProviderSpecificRequest =
+            serde_json::from_value(serde_json::json!({"extra_body": {"top_k": 40}})).unwrap();
+
+        let engine = crate::llm_gateway::provider::Provider::get_completion_engine_for_model(
+            &model,
+            &Default::default(),
+            None,
+            Some(&provider_specific),
+            None,
+        )
+        .unwrap();
+        let crate::types::engine::CompletionEngineParams::Bedrock { params, .. } = engine else {
+            panic!("Expected Bedrock params");
+        };
+
+        assert_eq!(
+            additional_model_request_fields(&params).unwrap(),
+            Document::Object(HashMap::from([(
+                "top_k".to_string(),
+                Document::Number(aws_smithy_types::Number::PosInt(40))
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_unconfigured_inference_profile() {
+        let profiles = HashMap::new();
+
+        let model_name = BedrockModel::resolve_model_name(
+            "mistral-large-2402-v1.0",
+            &BedrockProvider::Mistral,
+            Some(&profiles),
+        );
+        assert_eq!(model_name, "mistral.mistral-large-2402-v1:0");
+
+        let model_name = BedrockModel::resolve_model_name(
+            &BedrockMetaCompletionModel::Llama318BInstruct.to_string(),
+            &BedrockProvider::Meta,
+            None,
+        );
+        assert_eq!(
+            model_name,
+            format!("us.meta.{}", BedrockMetaCompletionModel::Llama318BInstruct)
+        );
+    }
+
+    /// Answers a single request with `status` and `body`, returning the request line
+    async fn mock_region(
+        status: &'static str,
+        headers: &'static str,
+        body: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = vec![];
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&received).to_string();
+                if let Some(headers_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..headers_end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if received.len() >= headers_end + 4 + content_length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {status}\r\ncontent-type: application/json\r\n{headers}content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&received)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        });
+        (url, handle)
+    }
+
+    fn region_client(url: &str) -> Client {
+        let config = aws_sdk_bedrockruntime::Config::builder()
+            .behavior_version(aws_sdk_bedrockruntime::config::BehaviorVersion::latest())
+            .region(aws_sdk_bedrockruntime::config::Region::new("us-east-1"))
+            .credentials_provider(aws_sdk_bedrockruntime::config::Credentials::new(
+                "key", "secret", None, None, "test",
+            ))
+            .retry_config(aws_sdk_bedrockruntime::config::retry::RetryConfig::disabled())
+            .endpoint_url(url)
+            .build();
+        Client::from_conf(config)
+    }
+
+    fn model_in_regions(client: Client, failover_clients: Vec<Client>) -> BedrockModel {
+        BedrockModel {
+            client,
+            failover_clients,
+            execution_options: ExecutionOptions {
+                max_retries: Some(1),
+                ..Default::default()
+            },
+            prompt: Prompt::new("test".to_string(), String::new()),
+            params: BedrockModelParams {
+                model_id: Some("anthropic.claude-3-haiku-20240307-v1:0".to_string()),
+                max_tokens: None,
+                temperature: None,
+                top_p: None,
+                stop_sequences: None,
+                additional_parameters: HashMap::new(),
+            },
+            tools: Arc::new(HashMap::new()),
+            model_name: "anthropic.claude-3-haiku-20240307-v1:0".to_string(),
+            credentials_ident: CredentialsIdent::Own,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_throttled_region_fails_over_to_next() {
+        let (throttled_url, throttled) = mock_region(
+            "429 Too Many Requests",
+            "x-amzn-errortype: ThrottlingException\r\n",
+            r#"{"message":"Too many requests"}"#,
+        )
+        .await;
+        let (healthy_url, healthy) = mock_region(
+            "200 OK",
+            "",
+            r#"{"output":{"message":{"role":"assistant","content":[{"text":"Paris"}]}},"stopReason":"end_turn","usage":{"inputTokens":5,"outputTokens":1,"totalTokens":6},"metrics":{"latencyMs":10}}"#,
+        )
+        .await;
+
+        let model = model_in_regions(
+            region_client(&throttled_url),
+            vec![region_client(&healthy_url)],
+        );
+        let message = Message::builder()
+            .role(ConversationRole::User)
+            .content(ContentBlock::Text("Capital of France?".to_string()))
+            .build()
+            .unwrap();
+        let (tx, _rx) = tokio::sync::mpsc::channel(32);
+
+        let response = model
+            .execute(vec![message], vec![], &tx, HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.content,
+            Some(ChatCompletionContent::Text("Paris".to_string()))
+        );
+        assert!(throttled.await.unwrap().contains("/converse"));
+        assert!(healthy.await.unwrap().contains("/converse"));
+    }
+
+    #[tokio::test]
+    async fn test_throttling_becomes_rate_limited_error() {
+        let (throttled_url, _throttled) = mock_region(
+            "429 Too Many Requests",
+            "x-amzn-errortype: ThrottlingException\r\n",
+            r#"{"message":"Too many requests"}"#,
+        )
+        .await;
+
+        let model = model_in_regions(region_client(&throttled_url), vec![]);
+        let message = Message::builder()
+            .role(ConversationRole::User)
+            .content(ContentBlock::Text("Capital of France?".to_string()))
+            .build()
+            .unwrap();
+        let (tx, _rx) = tokio::sync::mpsc::channel(32);
+
+        let result = model
+            .execute(vec![message], vec![], &tx, HashMap::new())
+            .await;
+        assert!(matches!(
+            result,
+            Err(GatewayError::RateLimited { provider, retry_after: None }) if provider == SPAN_BEDROCK
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tool_error_is_structured() {
+        let tools: HashMap<String, Box<dyn LangdbTool>> =
+            HashMap::from([("get_stock".to_string(), Box::new(FailingTool) as _)]);
+        let tool_use = ToolUseBlock::builder()
+            .tool_use_id("call_1")
+            .name("get_stock")
+            .input(Document::Object(HashMap::new()))
+            .build()
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+
+        let message = BedrockModel::handle_tool_calls(vec![tool_use], &tools, &tx, HashMap::new())
+            .await
+            .unwrap();
+        drop(tx);
+
+        let ContentBlock::ToolResult(result) = &message.content()[0] else {
+            panic!("Expected a tool result");
+        };
+        assert_eq!(result.status(), Some(&ToolResultStatus::Error));
+        let ToolResultContentBlock::Text(content) = &result.content()[0] else {
+            panic!("Expected text content");
+        };
+        let content: Value = serde_json::from_str(content).unwrap();
+        assert_eq!(content["code"], "execution_failed");
+        assert_eq!(content["error"], "Custom Error: Quote service unavailable");
+
+        let mut tool_end = None;
+        while let Some(Some(event)) = rx.recv().await {
+            if let ModelEventType::ToolEnd(end) = event.event {
+                tool_end = Some(end);
+            }
+        }
+        let tool_end = tool_end.expect("ToolEnd should be emitted");
+        assert!(tool_end.is_error);
+        assert_eq!(
+            tool_end.error.as_deref(),
+            Some("Custom Error: Quote service unavailable")
+        );
+    }
+}
@@ -34,6 +34,12 @@ pub enum ModelError {
     #[error("Max retries reached")]
     MaxRetriesReached,
 
+    #[error("Max tool call iterations ({0}) reached, partial result: {1}")]
+    MaxToolCallIterationsReached(u32, String),
+
+    #[error("Model call timed out after {0} seconds")]
+    Timeout(u64),
+
     #[error("View should return a model name, {0}")]
     RoutingError(String),
 
@@ -55,6 +61,9 @@ pub enum ModelError {
     #[error("Model {0} not found")]
     ModelNotFound(String),
 
+    #[error("Model {0} not found, did you mean {1}?")]
+    ModelNotFoundWithSuggestion(String, String),
+
     #[error("Request failed: {0}")]
     RequestFailed(String),
 
@@ -1,4 +1,5 @@
-use crate::{error::GatewayError, GatewayResult};
+use crate::model::rate_limits::upstream_rate_limits;
+use crate::{error::GatewayError, events::SPAN_GEMINI, GatewayResult};
 
 use super::types::{
     CountTokensRequest, CountTokensResponse, GenerateContentRequest, GenerateContentResponse,
@@ -20,6 +21,8 @@ pub struct Client {
     api_key: String,
     /// Internal HTTP client.
     client: reqwest::Client,
+    /// Models endpoint the requests are sent to.
+    base_url: String,
 }
 
 enum Method {
@@ -27,20 +30,26 @@ enum Method {
     Get,
 }
 impl Client {
-    pub fn new(api_key: String) -> Self {
+    pub fn new(api_key: String, client: reqwest::Client) -> Self {
         Self {
             api_key,
-            client: reqwest::Client::new(),
+            client,
+            base_url: API_URL.to_string(),
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn with_base_url(self, base_url: String) -> Self {
+        Self { base_url, ..self }
+    }
+
     async fn make_request<T: serde::de::DeserializeOwned, P: Serialize>(
         &self,
         path: &str,
         payload: Option<P>,
         method: Method,
     ) -> GatewayResult<T> {
-        let url = format!("{API_URL}{path}?key={}", self.api_key);
+        let url = format!("{}{path}?key={}", self.base_url, self.api_key);
 
         let resp = match method {
             Method::Get => self.client.get(url),
@@ -57,8 +66,12 @@ impl Client {
             // .header("x-api-key", self.api_key.as_str())
             .await
             .map_err(|e| GatewayError::CustomError(e.to_string()))?;
+        upstream_rate_limits().record(SPAN_GEMINI, resp.headers());
 
         let status = resp.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(GatewayError::rate_limited(SPAN_GEMINI, resp.headers()));
+        }
         if !status.is_success() {
             let msg = resp.text().await?;
             let p = if let Some(p) = payload {
@@ -68,9 +81,11 @@ impl Client {
             };
             tracing::error!(target: "gemini", "{msg}. Payload: {p}");
 
-            return Err(GatewayError::CustomError(format!(
-                "Request failed with status: {status}"
-            )));
+            return Err(GatewayError::provider_status(
+                SPAN_GEMINI,
+                status.as_u16(),
+                format!("Request failed with status: {status}"),
+            ));
         }
 
         let text = resp.text().await?;
@@ -120,8 +135,8 @@ impl Client {
     ) -> GatewayResult<impl Stream<Item = Result<Option<GenerateContentResponse>, GatewayError>>>
     {
         let stream_url = format!(
-            "{API_URL}/{model_name}:streamGenerateContent?alt=sse&key={}",
-            self.api_key
+            "{}/{model_name}:streamGenerateContent?alt=sse&key={}",
+            self.base_url, self.api_key
         );
         tracing::debug!(target: "gemini", "Invoking model: {model_name} on {stream_url} with payload: {}", serde_json::to_string(&payload).unwrap());
         let request = self.client.post(&stream_url).json(&payload);
@@ -153,19 +168,34 @@ impl Client {
                     }
                     Some(Err(Error::StreamEnded)) => None,
                     Some(Err(e)) => {
+                        if let Error::InvalidStatusCode(_, r) = &e {
+                            upstream_rate_limits().record(SPAN_GEMINI, r.headers());
+                        }
                         let err_str = e.to_string();
                         let err_str = match e {
+                            reqwest_eventsource::Error::InvalidStatusCode(status, r)
+                                if status == StatusCode::TOO_MANY_REQUESTS =>
+                            {
+                                let error = GatewayError::rate_limited(SPAN_GEMINI, r.headers());
+                                return Some((Err(error), event_source));
+                            }
                             reqwest_eventsource::Error::InvalidStatusCode(_, r) => {
                                 let status = r.status();
                                 let error = r.text().await.unwrap_or(err_str);
 
                                 tracing::error!(target: "gemini", "Gemini error: {error}");
 
-                                if status == StatusCode::NOT_FOUND {
+                                let message = if status == StatusCode::NOT_FOUND {
                                     "Gemini model not found".to_string()
                                 } else {
                                     error
-                                }
+                                };
+                                let error = GatewayError::provider_status(
+                                    SPAN_GEMINI,
+                                    status.as_u16(),
+                                    message,
+                                );
+                                return Some((Err(error), event_source));
                             }
                             _ => err_str,
                         };
@@ -181,9 +211,14 @@ impl Client {
 
 #[cfg(test)]
 mod tests {
+    use super::Client;
+    use crate::error::GatewayError;
+    use crate::events::SPAN_GEMINI;
     use crate::model::gemini::types::FinishReason;
+    use crate::model::gemini::types::GenerateContentRequest;
     use crate::model::gemini::types::GenerateContentResponse;
     use crate::model::gemini::types::Part;
+    use crate::model::rate_limits::upstream_rate_limits;
     use crate::model::HashMap;
     use serde_json;
 
@@ -246,4 +281,94 @@ mod tests {
             FinishReason::Stop
         );
     }
+
+    /// Answers a single request with a 429 and returns the server url
+    async fn rate_limited_server() -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = vec![];
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&received).to_string();
+                if let Some(headers_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..headers_end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if received.len() >= headers_end + 4 + content_length || n == 0 {
+                        break;
+                    }
+                }
+            }
+            let body = r#"{"error":{"code":429,"status":"RESOURCE_EXHAUSTED"}}"#;
+            let response = format!(
+                "HTTP/1.1 429 Too Many Requests\r\ncontent-type: application/json\r\nretry-after: 12\r\nx-ratelimit-remaining-requests: 0\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    fn client(base_url: String) -> Client {
+        Client {
+            api_key: "test".to_string(),
+            client: reqwest::Client::new(),
+            base_url,
+        }
+    }
+
+    fn request() -> GenerateContentRequest {
+        GenerateContentRequest {
+            system_instruction: None,
+            contents: vec![],
+            generation_config: None,
+            tools: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_becomes_rate_limited_error() {
+        let result = client(rate_limited_server().await)
+            .invoke("gemini-1.5-flash", request())
+            .await;
+        assert!(matches!(
+            result,
+            Err(GatewayError::RateLimited { provider, retry_after: Some(12) }) if provider == "gemini"
+        ));
+        assert_eq!(
+            upstream_rate_limits().get(SPAN_GEMINI).unwrap()["x-ratelimit-remaining-requests"],
+            "0"
+        );
+
+        let stream = client(rate_limited_server().await)
+            .stream("gemini-1.5-flash", request())
+            .await
+            .unwrap();
+        tokio::pin!(stream);
+        let mut error = None;
+        while let Some(item) = tokio_stream::StreamExt::next(&mut stream).await {
+            if let Err(e) = item {
+                error = Some(e);
+                break;
+            }
+        }
+        assert!(matches!(
+            error,
+            Some(GatewayError::RateLimited {
+                retry_after: Some(12),
+                ..
+            })
+        ));
+    }
 }
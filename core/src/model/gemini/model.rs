@@ -7,24 +7,29 @@ use super::super::ModelInstance;
 use super::super::Tool;
 use super::client::Client;
 use super::types::{
-    Content, FinishReason, GenerateContentRequest, GenerateContentResponse, Part,
+    Content, FinishReason, GenerateContentRequest, GenerateContentResponse, LogprobsResult, Part,
     PartFunctionResponse, UsageMetadata,
 };
 use crate::error::GatewayError;
+use crate::events;
+use crate::events::bodies::BodyRecordingMode;
 use crate::events::JsonValue;
 use crate::events::SPAN_GEMINI;
-use crate::events::{self, RecordResult};
 use crate::model::error::AuthorizationError;
 use crate::model::gemini::types::{
-    FunctionDeclaration, GenerationConfig, PartWithThought, Role, Tools,
+    FunctionDeclaration, GenerationConfig, PartWithThought, Role, SystemInstruction, Tools,
 };
 use crate::model::handler::handle_tool_call;
 use crate::model::types::LLMFirstToken;
-use crate::model::{async_trait, CredentialsIdent, DEFAULT_MAX_RETRIES};
+use crate::model::{
+    async_trait, http_client, wait_retry_backoff, with_timeout, CredentialsIdent,
+    ToolCallIterations, DEFAULT_MAX_RETRIES,
+};
 use crate::types::credentials::ApiKeyCredentials;
 use crate::types::engine::{ExecutionOptions, GeminiModelParams, Prompt};
 use crate::types::gateway::{
-    ChatCompletionContent, ChatCompletionMessage, CompletionModelUsage, ToolCall,
+    ChatCompletionContent, ChatCompletionLogprobs, ChatCompletionMessage,
+    ChatCompletionTokenLogprob, ChatCompletionTopLogprob, CompletionModelUsage, ToolCall,
 };
 use crate::types::message::{MessageType, PromptMessage};
 use crate::types::threads::{AudioFormat, InnerMessage, Message, MessageContentPartOptions};
@@ -63,13 +68,16 @@ fn map_calls_to_tool_label(calls: &[(String, HashMap<String, Value>)]) -> String
         .join(",")
 }
 
-pub fn gemini_client(credentials: Option<&ApiKeyCredentials>) -> Result<Client, ModelError> {
+pub fn gemini_client(
+    credentials: Option<&ApiKeyCredentials>,
+    execution_options: &ExecutionOptions,
+) -> Result<Client, ModelError> {
     let api_key = if let Some(credentials) = credentials {
         credentials.api_key.clone()
     } else {
         std::env::var("LANGDB_GEMINI_API_KEY").map_err(|_| AuthorizationError::InvalidApiKey)?
     };
-    Ok(Client::new(api_key))
+    Ok(Client::new(api_key, http_client(execution_options)?))
 }
 
 enum InnerExecutionResult {
@@ -94,7 +102,7 @@ impl GeminiModel {
         prompt: Prompt,
         tools: HashMap<String, Box<dyn Tool>>,
     ) -> Result<Self, ModelError> {
-        let client = gemini_client(credentials)?;
+        let client = gemini_client(credentials, &execution_options)?;
         Ok(Self {
             params,
             execution_options,
@@ -129,7 +137,11 @@ impl GeminiModel {
         .await
     }
 
-    fn build_request(&self, messages: Vec<Content>) -> GatewayResult<GenerateContentRequest> {
+    fn build_request(
+        &self,
+        system_instruction: Option<SystemInstruction>,
+        messages: Vec<Content>,
+    ) -> GatewayResult<GenerateContentRequest> {
         let model_params = &self.params;
         let response_schema = match &model_params.response_format {
             Some(ResponseFormat::JsonSchema { json_schema }) => {
@@ -164,6 +176,7 @@ impl GeminiModel {
                 None
             },
             response_schema,
+            extra: model_params.extra_body.clone().unwrap_or_default(),
         };
 
         let tools = if self.tools.is_empty() {
@@ -194,6 +207,7 @@ impl GeminiModel {
         };
 
         let request = GenerateContentRequest {
+            system_instruction,
             contents: messages,
             generation_config: Some(config),
             tools,
@@ -237,6 +251,7 @@ impl GeminiModel {
                                                 &Span::current(),
                                                 ModelEventType::LlmContent(LLMContentEvent {
                                                     content: text.to_owned(),
+                                                    index: 0,
                                                 }),
                                             )))
                                             .await;
@@ -280,6 +295,7 @@ impl GeminiModel {
         span: Span,
         tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
+        tool_call_iterations: &mut ToolCallIterations,
     ) -> GatewayResult<InnerExecutionResult> {
         let model_name = self.params.model.as_ref().unwrap();
         let input_messages = call.contents.clone();
@@ -297,16 +313,16 @@ impl GeminiModel {
 
         let response = async move {
             let result = self.client.invoke(model_name, call).await;
-            let _ = result
-                .as_ref()
-                .map(|response| serde_json::to_value(response).unwrap())
-                .as_ref()
-                .map(JsonValue)
-                .record();
+            if let Err(e) = &result {
+                Span::current().record("error", e.to_string());
+            }
             let response = result.map_err(custom_err)?;
 
             let span = Span::current();
-            span.record("output", serde_json::to_string(&response)?);
+            span.record(
+                "output",
+                BodyRecordingMode::current().apply(&serde_json::to_string(&response)?),
+            );
             if let Some(ref usage) = response.usage_metadata {
                 span.record(
                     "usage",
@@ -320,6 +336,12 @@ impl GeminiModel {
         let mut finish_reason = None;
         let mut calls: Vec<(String, HashMap<String, Value>)> = vec![];
         let mut text = String::new();
+        // Only the first candidate is returned as a choice, so its logprobs are the ones kept
+        let logprobs = response
+            .candidates
+            .first()
+            .and_then(|candidate| candidate.logprobs_result.as_ref())
+            .map(Self::map_logprobs);
         for candidate in response.candidates {
             if let Some(reason) = candidate.finish_reason {
                 finish_reason = Some(reason);
@@ -387,6 +409,7 @@ impl GeminiModel {
                             input_tokens: u.prompt_token_count as u32,
                             output_tokens: (u.total_token_count - u.prompt_token_count) as u32,
                             total_tokens: u.total_token_count as u32,
+                            reasoning_tokens: u.thoughts_token_count.unwrap_or_default() as u32,
                             ..Default::default()
                         });
                     let finish_reason = ModelFinishReason::ToolCalls;
@@ -409,6 +432,9 @@ impl GeminiModel {
                                 })
                                 .collect::<Result<Vec<ModelToolCall>, GatewayError>>()?,
                             credentials_ident: self.credentials_ident.clone(),
+                            logprobs,
+                            service_tier: None,
+                            response_id: None,
                         }),
                     )))
                     .await
@@ -442,6 +468,7 @@ impl GeminiModel {
                     }));
                 }
             }
+            tool_call_iterations.next_round(|| Some(text.clone()))?;
             tools_span.follows_from(span.id());
             let tool_call_parts =
                 Self::handle_tool_calls(calls.iter(), &self.tools, tx, tags.clone())
@@ -466,6 +493,7 @@ impl GeminiModel {
                         input_tokens: u.prompt_token_count as u32,
                         output_tokens: (u.total_token_count - u.prompt_token_count) as u32,
                         total_tokens: u.total_token_count as u32,
+                        reasoning_tokens: u.thoughts_token_count.unwrap_or_default() as u32,
                         ..Default::default()
                     });
 
@@ -484,6 +512,9 @@ impl GeminiModel {
                         finish_reason: ModelFinishReason::Stop,
                         tool_calls: vec![],
                         credentials_ident: self.credentials_ident.clone(),
+                        logprobs,
+                        service_tier: None,
+                        response_id: None,
                     }),
                 )))
                 .await
@@ -505,6 +536,7 @@ impl GeminiModel {
 
     async fn execute(
         &self,
+        system_instruction: Option<SystemInstruction>,
         input_messages: Vec<Content>,
         tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
@@ -514,6 +546,7 @@ impl GeminiModel {
             .execution_options
             .max_retries
             .unwrap_or(DEFAULT_MAX_RETRIES);
+        let mut tool_call_iterations = ToolCallIterations::new(&self.execution_options);
         while let Some(call) = gemini_calls.pop() {
             let span = tracing::info_span!(
                 target: target!("chat"),
@@ -528,13 +561,23 @@ impl GeminiModel {
             );
 
             let result = {
-                let request = self.build_request(call.clone())?;
-
-                span.record("input", serde_json::to_string(&request)?);
-                span.record("request", serde_json::to_string(&request)?);
-
-                self.execute_inner(request, span.clone(), tx, tags.clone())
-                    .await
+                let request = self.build_request(system_instruction.clone(), call.clone())?;
+
+                let body = BodyRecordingMode::current().apply(&serde_json::to_string(&request)?);
+                span.record("input", body.as_deref());
+                span.record("request", body.as_deref());
+
+                with_timeout(
+                    &self.execution_options,
+                    self.execute_inner(
+                        request,
+                        span.clone(),
+                        tx,
+                        tags.clone(),
+                        &mut tool_call_iterations,
+                    ),
+                )
+                .await
             };
 
             match result.map_err(|e| record_map_err(e, span.clone())) {
@@ -543,12 +586,14 @@ impl GeminiModel {
                     gemini_calls.push(messages);
                     continue;
                 }
+                Err(e) if ToolCallIterations::reached(&e) => return Err(e),
                 Err(e) => {
                     retries -= 1;
                     span.record("error", e.to_string());
                     if retries == 0 {
                         return Err(e);
                     } else {
+                        wait_retry_backoff(&self.execution_options).await;
                         gemini_calls.push(call);
                     }
                 }
@@ -586,11 +631,41 @@ impl GeminiModel {
         }
     }
 
+    fn map_logprobs(result: &LogprobsResult) -> ChatCompletionLogprobs {
+        ChatCompletionLogprobs {
+            content: result
+                .chosen_candidates
+                .iter()
+                .enumerate()
+                .map(|(position, chosen)| ChatCompletionTokenLogprob {
+                    token: chosen.token.clone(),
+                    logprob: chosen.log_probability,
+                    bytes: Some(chosen.token.as_bytes().to_vec()),
+                    top_logprobs: result
+                        .top_candidates
+                        .get(position)
+                        .map(|top| {
+                            top.candidates
+                                .iter()
+                                .map(|c| ChatCompletionTopLogprob {
+                                    token: c.token.clone(),
+                                    logprob: c.log_probability,
+                                    bytes: Some(c.token.as_bytes().to_vec()),
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+
     fn map_usage(usage: Option<&UsageMetadata>) -> Option<CompletionModelUsage> {
         usage.map(|u| CompletionModelUsage {
             input_tokens: u.prompt_token_count as u32,
             output_tokens: (u.total_token_count - u.prompt_token_count) as u32,
             total_tokens: u.total_token_count as u32,
+            reasoning_tokens: u.thoughts_token_count.unwrap_or_default() as u32,
             ..Default::default()
         })
     }
@@ -609,6 +684,7 @@ impl GeminiModel {
         tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         call_span: Span,
         tags: HashMap<String, String>,
+        tool_call_iterations: &mut ToolCallIterations,
     ) -> GatewayResult<InnerExecutionResult> {
         let model_name = self.params.model.as_ref().unwrap();
         let input_messages = call.contents.clone();
@@ -655,6 +731,9 @@ impl GeminiModel {
                 finish_reason: trace_finish_reason.clone(),
                 tool_calls: tool_calls.iter().map(Self::map_tool_call).collect(),
                 credentials_ident: self.credentials_ident.clone(),
+                logprobs: None,
+                service_tier: None,
+                response_id: None,
             }),
         )))
         .await
@@ -664,7 +743,10 @@ impl GeminiModel {
             "finish_reason": finish_reason,
             "tool_calls": tool_calls
         });
-        call_span.record("output", response.to_string());
+        call_span.record(
+            "output",
+            BodyRecordingMode::current().apply(&response.to_string()),
+        );
         if !tool_calls.is_empty() {
             let mut call_messages = vec![];
             let mut tools = vec![];
@@ -705,6 +787,7 @@ impl GeminiModel {
                 }
             }
 
+            tool_call_iterations.next_round(|| last_model_text(&input_messages))?;
             tools_span.follows_from(call_span.id());
             let tool_call_parts =
                 Self::handle_tool_calls(tool_calls.iter(), &self.tools, &tx, tags.clone())
@@ -730,6 +813,7 @@ impl GeminiModel {
 
     async fn execute_stream(
         &self,
+        system_instruction: Option<SystemInstruction>,
         input_messages: Vec<Content>,
         tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
@@ -740,6 +824,7 @@ impl GeminiModel {
             .execution_options
             .max_retries
             .unwrap_or(DEFAULT_MAX_RETRIES);
+        let mut tool_call_iterations = ToolCallIterations::new(&self.execution_options);
         while let Some(call) = gemini_calls.pop() {
             let span = tracing::info_span!(
                 target: target!("chat"),
@@ -754,13 +839,23 @@ impl GeminiModel {
             );
 
             let result = {
-                let request = self.build_request(call.clone())?;
-
-                span.record("input", serde_json::to_string(&request)?);
-                span.record("request", serde_json::to_string(&request)?);
-
-                self.execute_stream_inner(request, tx.clone(), span.clone(), tags.clone())
-                    .await
+                let request = self.build_request(system_instruction.clone(), call.clone())?;
+
+                let body = BodyRecordingMode::current().apply(&serde_json::to_string(&request)?);
+                span.record("input", body.as_deref());
+                span.record("request", body.as_deref());
+
+                with_timeout(
+                    &self.execution_options,
+                    self.execute_stream_inner(
+                        request,
+                        tx.clone(),
+                        span.clone(),
+                        tags.clone(),
+                        &mut tool_call_iterations,
+                    ),
+                )
+                .await
             };
 
             match result.map_err(|e| record_map_err(e, span.clone())) {
@@ -769,12 +864,14 @@ impl GeminiModel {
                     gemini_calls.push(messages);
                     continue;
                 }
+                Err(e) if ToolCallIterations::reached(&e) => return Err(e),
                 Err(e) => {
                     retries -= 1;
                     span.record("error", e.to_string());
                     if retries == 0 {
                         return Err(e);
                     } else {
+                        wait_retry_backoff(&self.execution_options).await;
                         gemini_calls.push(call);
                     }
                 }
@@ -792,9 +889,8 @@ impl GeminiModel {
         for m in messages_dto.iter() {
             let request_message = {
                 match m.r#type {
-                    MessageType::SystemMessage => {
-                        Some(Content::user(m.content.clone().unwrap_or_default()))
-                    }
+                    // Sent as the system instruction by `construct_messages`
+                    MessageType::SystemMessage => None,
 
                     MessageType::AIMessage => {
                         if let Some(tool_calls) = &m.tool_calls {
@@ -876,9 +972,10 @@ impl ModelInstance for GeminiModel {
         previous_messages: Vec<Message>,
         tags: HashMap<String, String>,
     ) -> GatewayResult<ChatCompletionMessage> {
-        let conversational_messages =
+        let (system_instruction, conversational_messages) =
             self.construct_messages(input_variables, previous_messages)?;
-        self.execute(conversational_messages, &tx, tags).await
+        self.execute(system_instruction, conversational_messages, &tx, tags)
+            .await
     }
 
     async fn stream(
@@ -888,9 +985,10 @@ impl ModelInstance for GeminiModel {
         previous_messages: Vec<Message>,
         tags: HashMap<String, String>,
     ) -> GatewayResult<()> {
-        let conversational_messages =
+        let (system_instruction, conversational_messages) =
             self.construct_messages(input_variables, previous_messages)?;
-        self.execute_stream(conversational_messages, tx, tags).await
+        self.execute_stream(system_instruction, conversational_messages, tx, tags)
+            .await
     }
 }
 
@@ -899,17 +997,21 @@ impl GeminiModel {
         &self,
         input_variables: HashMap<String, Value>,
         previous_messages: Vec<Message>,
-    ) -> GatewayResult<Vec<Content>> {
+    ) -> GatewayResult<(Option<SystemInstruction>, Vec<Content>)> {
         let mut conversational_messages = vec![];
-        let system_message = self
+        let system_texts = self
             .prompt
             .messages
             .iter()
-            .find(|m| m.r#type == MessageType::SystemMessage)
-            .map(|message| map_chat_messages(message.to_owned(), &input_variables));
-        if let Some(system_message) = system_message {
-            conversational_messages.push(system_message?);
-        }
+            .filter(|m| m.r#type == MessageType::SystemMessage)
+            .map(|message| Prompt::render(message.msg.clone(), &input_variables))
+            .chain(
+                previous_messages
+                    .iter()
+                    .filter(|m| m.r#type == MessageType::SystemMessage)
+                    .filter_map(|m| m.content.clone()),
+            )
+            .collect();
         let previous_messages = Self::map_previous_messages(previous_messages)?;
         conversational_messages.extend(previous_messages);
         let human_message = self
@@ -922,7 +1024,8 @@ impl GeminiModel {
             conversational_messages.push(human_message?);
         }
 
-        Ok(conversational_messages)
+        let system_instruction = SystemInstruction::new(system_texts);
+        Ok((system_instruction, conversational_messages))
     }
 }
 
@@ -1007,6 +1110,24 @@ pub fn record_map_err(e: impl Into<GatewayError> + ToString, span: tracing::Span
     e.into()
 }
 
+/// Text of the latest model message, returned as the partial result of a stopped agent loop
+fn last_model_text(messages: &[Content]) -> Option<String> {
+    messages
+        .iter()
+        .rev()
+        .find(|m| matches!(m.role, Role::Model))
+        .map(|m| {
+            m.parts
+                .iter()
+                .filter_map(|p| match &p.part {
+                    Part::Text(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+}
+
 fn replace_refs_with_defs(schema: Value) -> Value {
     // If schema isn't an object, return as is
     if !schema.is_object() {
@@ -1195,3 +1316,242 @@ fn normalize_nullable_types(schema: Value) -> Value {
     normalize(&mut result);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_gateway::message_mapper::MessageMapper;
+    use crate::test_utils::{mock_server, mock_server_repeating, FailingTool};
+
+    fn gemini_model(
+        request: &crate::types::gateway::ChatCompletionRequest,
+        provider_specific: Option<&crate::types::gateway::ProviderSpecificRequest>,
+    ) -> GeminiModel {
+        let model = crate::models::ModelMetadata {
+            model: "gemini-2.0-flash".to_string(),
+            model_provider: "gemini".to_string(),
+            inference_provider: crate::models::InferenceProvider {
+                provider: crate::types::provider::InferenceModelProvider::Gemini,
+                model_name: "gemini-2.0-flash".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let engine = crate::llm_gateway::provider::Provider::get_completion_engine_for_model(
+            &model,
+            request,
+            None,
+            provider_specific,
+            None,
+        )
+        .unwrap();
+        let crate::types::engine::CompletionEngineParams::Gemini { params, .. } = engine else {
+            panic!("Expected Gemini params");
+        };
+        GeminiModel::new(
+            params,
+            ExecutionOptions::default(),
+            Some(&ApiKeyCredentials {
+                api_key: "test-key".to_string(),
+            }),
+            Prompt::new("test".to_string(), String::new()),
+            HashMap::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_extra_body_is_added_to_generation_config() {
+        let request = crate::types::gateway::ChatCompletionRequest {
+            temperature: Some(0.5),
+            ..Default::default()
+        };
+        let provider_specific: crate::types::gateway::ProviderSpecificRequest =
+            serde_json::from_value(serde_json::json!({
+                "extra_body": {"thinkingConfig": {"thinkingBudget": 0}}
+            }))
+            .unwrap();
+        let model = gemini_model(&request, Some(&provider_specific));
+
+        let request = model
+            .build_request(None, vec![Content::user("Hi".to_string())])
+            .unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            body["generation_config"]["thinkingConfig"]["thinkingBudget"],
+            0
+        );
+        assert_eq!(body["generation_config"]["temperature"], 0.5);
+    }
+
+    #[test]
+    fn test_system_messages_use_system_instruction() {
+        let model = gemini_model(&Default::default(), None);
+        let messages = [
+            ("system", "Be brief."),
+            ("user", "What is the capital of France?"),
+        ]
+        .into_iter()
+        .map(|(role, content)| {
+            MessageMapper::map_completions_message_to_langdb_message(
+                &ChatCompletionMessage::new_text(role.to_string(), content.to_string()),
+                "gemini-2.0-flash",
+                "user",
+            )
+            .unwrap()
+        })
+        .collect();
+
+        let (system_instruction, contents) =
+            model.construct_messages(HashMap::new(), messages).unwrap();
+        let request = model.build_request(system_instruction, contents).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(body["system_instruction"]["parts"][0]["text"], "Be brief.");
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 1);
+        assert_eq!(contents[0]["role"], "user");
+        assert_eq!(
+            contents[0]["parts"][0]["text"],
+            "What is the capital of France?"
+        );
+    }
+
+    #[test]
+    fn test_map_logprobs() {
+        let response: GenerateContentResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "Hello world"}]},
+                "finishReason": "STOP",
+                "logprobsResult": {
+                    "topCandidates": [
+                        {"candidates": [
+                            {"token": "Hello", "logProbability": -0.1},
+                            {"token": "Hi", "logProbability": -2.3}
+                        ]},
+                        {"candidates": [
+                            {"token": " world", "logProbability": -0.2}
+                        ]}
+                    ],
+                    "chosenCandidates": [
+                        {"token": "Hello", "logProbability": -0.1},
+                        {"token": " world", "logProbability": -0.2}
+                    ]
+                }
+            }],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 2, "totalTokenCount": 5}
+        }))
+        .unwrap();
+
+        let result = response.candidates[0].logprobs_result.as_ref().unwrap();
+        let mapped = GeminiModel::map_logprobs(result);
+        assert_eq!(mapped.content.len(), 2);
+        assert_eq!(mapped.content[0].token, "Hello");
+        assert_eq!(mapped.content[0].logprob, -0.1);
+        assert_eq!(mapped.content[0].top_logprobs.len(), 2);
+        assert_eq!(mapped.content[0].top_logprobs[1].token, "Hi");
+        assert_eq!(mapped.content[1].token, " world");
+        assert_eq!(mapped.content[1].bytes, Some(b" world".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_logprobs_of_first_candidate_are_attached() {
+        let logprobs_result = |token: &str, log_probability: f32| {
+            serde_json::json!({
+                "topCandidates": [{"candidates": [{"token": token, "logProbability": log_probability}]}],
+                "chosenCandidates": [{"token": token, "logProbability": log_probability}]
+            })
+        };
+        let body = serde_json::json!({
+            "candidates": [
+                {
+                    "content": {"role": "model", "parts": [{"text": "Paris"}]},
+                    "finishReason": "STOP",
+                    "logprobsResult": logprobs_result("Paris", -0.1)
+                },
+                {
+                    "content": {"role": "model", "parts": [{"text": "Lyon"}]},
+                    "finishReason": "STOP",
+                    "logprobsResult": logprobs_result("Lyon", -3.2)
+                }
+            ],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 2, "totalTokenCount": 5}
+        });
+
+        let request = crate::types::gateway::ChatCompletionRequest {
+            logprobs: Some(true),
+            top_logprobs: Some(1),
+            ..Default::default()
+        };
+        let mut model = gemini_model(&request, None);
+        let generation_config = serde_json::to_value(
+            model
+                .build_request(None, vec![Content::user("Hi".to_string())])
+                .unwrap(),
+        )
+        .unwrap()["generation_config"]
+            .clone();
+        assert_eq!(generation_config["responseLogprobs"], true);
+        assert_eq!(generation_config["logprobs"], 1);
+
+        let url = mock_server("application/json", body.to_string()).await;
+        model.client = model.client.clone().with_base_url(url);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        model
+            .invoke(HashMap::new(), tx, vec![], HashMap::new())
+            .await
+            .unwrap();
+
+        let mut logprobs = None;
+        while let Some(Some(event)) = rx.recv().await {
+            if let ModelEventType::LlmStop(finish) = event.event {
+                logprobs = finish.logprobs;
+                break;
+            }
+        }
+        let logprobs = logprobs.unwrap();
+        assert_eq!(logprobs.content.len(), 1);
+        assert_eq!(logprobs.content[0].token, "Paris");
+        assert_eq!(logprobs.content[0].logprob, -0.1);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_rounds_stop_at_cap() {
+        // Every response calls the tool again
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"functionCall": {"name": "get_stock", "args": {}}}]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 2, "totalTokenCount": 5}
+        });
+        let url = mock_server_repeating("application/json", body.to_string()).await;
+
+        let mut model = gemini_model(&Default::default(), None);
+        model.client = model.client.clone().with_base_url(url);
+        model.tools = Arc::new(HashMap::from([(
+            "get_stock".to_string(),
+            Box::new(FailingTool) as Box<dyn Tool>,
+        )]));
+        model.execution_options.max_tool_call_iterations = Some(2);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        let result = model
+            .invoke(HashMap::new(), tx, vec![], HashMap::new())
+            .await;
+        assert!(matches!(
+            result,
+            Err(GatewayError::ModelError(e))
+                if matches!(*e, ModelError::MaxToolCallIterationsReached(2, _))
+        ));
+
+        // The tool ran once for each allowed round and not in the round over the cap
+        let mut tool_calls = 0;
+        while let Some(Some(event)) = rx.recv().await {
+            if let ModelEventType::ToolStart(_) = event.event {
+                tool_calls += 1;
+            }
+        }
+        assert_eq!(tool_calls, 2);
+    }
+}
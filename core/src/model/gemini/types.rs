@@ -16,11 +16,35 @@ pub struct CountTokensResponse {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GenerateContentRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<SystemInstruction>,
     pub contents: Vec<Content>,
     pub generation_config: Option<GenerationConfig>,
     pub tools: Option<Vec<Tools>>,
 }
 
+/// System prompt, sent apart from the conversation since Gemini has no system role
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SystemInstruction {
+    pub parts: Vec<PartWithThought>,
+}
+
+impl SystemInstruction {
+    /// Instruction with a part per system message, `None` when there is no system text
+    pub fn new(texts: Vec<String>) -> Option<Self> {
+        let parts: Vec<PartWithThought> = texts
+            .into_iter()
+            .filter(|t| !t.is_empty())
+            .map(|t| Part::Text(t).into())
+            .collect();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(Self { parts })
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Tools {
     pub function_declarations: Option<Vec<FunctionDeclaration>>,
@@ -81,6 +105,8 @@ pub struct GenerationConfig {
     pub logprobs: Option<i32>,
     pub response_mime_type: Option<String>,
     pub response_schema: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -142,6 +168,34 @@ pub struct Candidate {
     pub citation_metadata: Option<CitationMetadata>,
     pub safety_ratings: Option<Vec<SafetyRating>>,
     pub finish_reason: Option<FinishReason>,
+    #[serde(default)]
+    pub logprobs_result: Option<LogprobsResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LogprobsResult {
+    #[serde(default)]
+    pub top_candidates: Vec<TopLogprobsCandidates>,
+    #[serde(default)]
+    pub chosen_candidates: Vec<LogprobsCandidate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TopLogprobsCandidates {
+    #[serde(default)]
+    pub candidates: Vec<LogprobsCandidate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LogprobsCandidate {
+    #[serde(default)]
+    pub token: String,
+    pub token_id: Option<i32>,
+    #[serde(default)]
+    pub log_probability: f32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -181,6 +235,7 @@ pub struct UsageMetadata {
     pub candidates_token_count: Option<i32>,
     pub prompt_token_count: i32,
     pub total_token_count: i32,
+    pub thoughts_token_count: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -7,22 +7,26 @@ use crate::{
 };
 
 use super::{
-    types::{ModelEvent, ModelEventType, ModelToolCall, ToolResultEvent, ToolStartEvent},
+    types::{
+        ModelEvent, ModelEventType, ModelToolCall, ToolEndEvent, ToolResultEvent, ToolStartEvent,
+    },
     Tool,
 };
 use opentelemetry::propagation::Injector;
+use serde::Serialize;
 use serde_json::Value;
 use tracing::Span;
+use tracing_futures::Instrument;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-// macro_rules! target {
-//     () => {
-//         "langdb::user_tracing::models"
-//     };
-//     ($subtgt:literal) => {
-//         concat!("langdb::user_tracing::models::", $subtgt)
-//     };
-// }
+macro_rules! target {
+    () => {
+        "langdb::user_tracing::models"
+    };
+    ($subtgt:literal) => {
+        concat!("langdb::user_tracing::models::", $subtgt)
+    };
+}
 
 pub(crate) struct LlmToolCallCarrier<'a> {
     properties: &'a mut HashMap<String, String>,
@@ -40,53 +44,83 @@ impl Injector for LlmToolCallCarrier<'_> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorCode {
+    ToolNotFound,
+    InvalidArguments,
+    ExecutionFailed,
+}
+
+/// Tool failure fed back to the model as the tool result so it can reason about it
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallError {
+    pub error: String,
+    pub code: ToolErrorCode,
+}
+
+impl From<&GatewayError> for ToolCallError {
+    fn from(error: &GatewayError) -> Self {
+        let code = match error {
+            GatewayError::ToolNotFound(_) => ToolErrorCode::ToolNotFound,
+            GatewayError::ParseError(_) => ToolErrorCode::InvalidArguments,
+            _ => ToolErrorCode::ExecutionFailed,
+        };
+        Self {
+            error: error.to_string(),
+            code,
+        }
+    }
+}
+
+impl ToolCallError {
+    /// Tool result content describing the failure, e.g. `{"error": "...", "code": "tool_not_found"}`
+    pub fn to_json(&self) -> String {
+        serde_json::json!(self).to_string()
+    }
+}
+
 pub(crate) async fn handle_tool_call(
     tool_use: &ModelToolCall,
     tools: &HashMap<String, Box<dyn Tool>>,
     tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
-    mut tags: HashMap<String, String>,
+    tags: HashMap<String, String>,
 ) -> GatewayResult<String> {
     let tool_name = tool_use.tool_name.clone();
     let arguments = tool_use.input.clone();
-    let arguments_value = serde_json::from_str::<HashMap<String, Value>>(&arguments)?;
-    // let span = tracing::info_span!(
-    //     target: target!("tool"),
-    //     crate::events::SPAN_TOOL,
-    //     tool_name = tool_name,
-    //     arguments = arguments.to_string(),
-    //     output = tracing::field::Empty,
-    //     error = tracing::field::Empty,
-    // );
-    let tool = tools
-        .get(&tool_name)
-        .ok_or(GatewayError::CustomError(format!(
-            "Tool Not Found {tool_name}"
-        )))?;
-
+    let span = tracing::info_span!(
+        target: target!("tool"),
+        crate::events::SPAN_TOOL,
+        tool_name = tool_name,
+        tool_id = tool_use.tool_id,
+        duration_ms = tracing::field::Empty,
+        error = tracing::field::Empty,
+    );
     async {
         tx.send(Some(ModelEvent::new(
             &Span::current(),
             ModelEventType::ToolStart(ToolStartEvent {
                 tool_id: tool_use.tool_id.clone(),
                 tool_name: tool_name.clone(),
-                input: arguments,
+                input: arguments.clone(),
             }),
         )))
         .await
         .map_err(|e| GatewayError::CustomError(e.to_string()))?;
-        let span_context = Span::current().context();
-        opentelemetry::global::get_text_map_propagator(|propagator| {
-            propagator.inject_context(&span_context, &mut LlmToolCallCarrier::new(&mut tags))
-        });
-
-        let result = tool.run(arguments_value, tags).await;
-        let _ = result.as_ref().map(JsonValue).record();
-        let result = result.map(|v| v.to_string());
+
+        let started_at = std::time::Instant::now();
+        let result = run_tool(&tool_name, &arguments, tools, tags).await;
+        let duration_ms = started_at.elapsed().as_millis() as u64;
+        let current_span = Span::current();
+        current_span.record("duration_ms", duration_ms);
+        if let Err(e) = &result {
+            current_span.record("error", e.to_string());
+        }
         tx.send(Some(ModelEvent::new(
             &Span::current(),
             ModelEventType::ToolResult(ToolResultEvent {
                 tool_id: tool_name.clone(),
-                tool_name,
+                tool_name: tool_name.clone(),
                 is_error: result.is_err(),
                 output: result
                     .as_ref()
@@ -96,8 +130,194 @@ pub(crate) async fn handle_tool_call(
         )))
         .await
         .map_err(|e| GatewayError::CustomError(e.to_string()))?;
+        tx.send(Some(ModelEvent::new(
+            &Span::current(),
+            ModelEventType::ToolEnd(ToolEndEvent {
+                tool_id: tool_use.tool_id.clone(),
+                tool_name,
+                result: result.as_ref().ok().cloned(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                is_error: result.is_err(),
+                duration_ms,
+            }),
+        )))
+        .await
+        .map_err(|e| GatewayError::CustomError(e.to_string()))?;
         result
     }
-    // .instrument(span.or_current())
+    .instrument(span)
     .await
 }
+
+async fn run_tool(
+    tool_name: &str,
+    arguments: &str,
+    tools: &HashMap<String, Box<dyn Tool>>,
+    mut tags: HashMap<String, String>,
+) -> GatewayResult<String> {
+    let arguments_value = serde_json::from_str::<HashMap<String, Value>>(arguments)?;
+    let tool = tools
+        .get(tool_name)
+        .ok_or_else(|| GatewayError::ToolNotFound(tool_name.to_string()))?;
+
+    let span_context = Span::current().context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&span_context, &mut LlmToolCallCarrier::new(&mut tags))
+    });
+
+    let result = tool.run(arguments_value, tags).await;
+    let _ = result.as_ref().map(JsonValue).record();
+    result.map(|v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{FailingTool, SpanFields};
+    use crate::types::gateway::FunctionParameters;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    struct WeatherTool;
+
+    #[async_trait::async_trait]
+    impl Tool for WeatherTool {
+        fn name(&self) -> String {
+            "get_weather".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Returns the weather".to_string()
+        }
+
+        fn get_function_parameters(&self) -> Option<FunctionParameters> {
+            None
+        }
+
+        async fn run(
+            &self,
+            input: HashMap<String, Value>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<Value> {
+            Ok(serde_json::json!({"city": input["city"], "temperature": 21}))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_end_follows_tool_start() {
+        let tools: HashMap<String, Box<dyn Tool>> = HashMap::from([(
+            "get_weather".to_string(),
+            Box::new(WeatherTool) as Box<dyn Tool>,
+        )]);
+        let tool_call = ModelToolCall {
+            tool_id: "call_1".to_string(),
+            tool_name: "get_weather".to_string(),
+            input: r#"{"city": "Paris"}"#.to_string(),
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+
+        let output = handle_tool_call(&tool_call, &tools, &tx, HashMap::new())
+            .await
+            .unwrap();
+        drop(tx);
+
+        let mut events = vec![];
+        while let Some(Some(event)) = rx.recv().await {
+            events.push(event.event);
+        }
+
+        let start = events
+            .iter()
+            .position(|e| matches!(e, ModelEventType::ToolStart(_)))
+            .expect("ToolStart should be emitted");
+        let end = events
+            .iter()
+            .position(|e| matches!(e, ModelEventType::ToolEnd(_)))
+            .expect("ToolEnd should be emitted");
+        assert!(start < end);
+
+        let (ModelEventType::ToolStart(start), ModelEventType::ToolEnd(end)) =
+            (&events[start], &events[end])
+        else {
+            unreachable!();
+        };
+        assert_eq!(start.tool_id, end.tool_id);
+        assert_eq!(start.tool_name, end.tool_name);
+        assert_eq!(end.result.as_deref(), Some(output.as_str()));
+        assert!(end.error.is_none());
+        assert!(!end.is_error);
+    }
+
+    #[tokio::test]
+    async fn test_tool_end_marks_errors() {
+        let tools: HashMap<String, Box<dyn Tool>> = HashMap::from([
+            (
+                "get_weather".to_string(),
+                Box::new(WeatherTool) as Box<dyn Tool>,
+            ),
+            (
+                "get_stock".to_string(),
+                Box::new(FailingTool) as Box<dyn Tool>,
+            ),
+        ]);
+        let cases = [
+            ("get_weather", "not json", ToolErrorCode::InvalidArguments),
+            ("get_time", "{}", ToolErrorCode::ToolNotFound),
+            ("get_stock", "{}", ToolErrorCode::ExecutionFailed),
+        ];
+
+        for (tool_name, input, code) in cases {
+            let tool_call = ModelToolCall {
+                tool_id: "call_1".to_string(),
+                tool_name: tool_name.to_string(),
+                input: input.to_string(),
+            };
+            let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+
+            let error = handle_tool_call(&tool_call, &tools, &tx, HashMap::new())
+                .await
+                .unwrap_err();
+            drop(tx);
+            assert_eq!(ToolCallError::from(&error).code, code);
+
+            let mut ends = vec![];
+            while let Some(Some(event)) = rx.recv().await {
+                if let ModelEventType::ToolEnd(end) = event.event {
+                    ends.push(end);
+                }
+            }
+            assert_eq!(ends.len(), 1);
+            assert!(ends[0].is_error);
+            assert_eq!(ends[0].error.as_deref(), Some(error.to_string().as_str()));
+            assert!(ends[0].result.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_span_records_duration() {
+        let fields = SpanFields::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+        let tools: HashMap<String, Box<dyn Tool>> = HashMap::from([(
+            "get_weather".to_string(),
+            Box::new(WeatherTool) as Box<dyn Tool>,
+        )]);
+        let tool_call = ModelToolCall {
+            tool_id: "call_1".to_string(),
+            tool_name: "get_weather".to_string(),
+            input: r#"{"city": "Paris"}"#.to_string(),
+        };
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+
+        handle_tool_call(&tool_call, &tools, &tx, HashMap::new())
+            .await
+            .unwrap();
+
+        let span = fields
+            .of(crate::events::SPAN_TOOL)
+            .expect("Tool span should be created");
+        assert_eq!(span["tool_name"], "get_weather");
+        assert_eq!(span["tool_id"], "call_1");
+        assert!(span["duration_ms"].parse::<u64>().is_ok());
+        assert!(!span.contains_key("error"));
+    }
+}
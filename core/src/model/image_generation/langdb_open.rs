@@ -3,7 +3,10 @@ use crate::model::error::ModelError;
 use crate::model::openai_spec_client::openai_spec_client;
 use crate::model::types::ModelEvent;
 use crate::types::credentials::ApiKeyCredentials;
-use crate::types::gateway::CreateImageRequest;
+use crate::types::engine::ExecutionOptions;
+use crate::types::gateway::{
+    CreateImageEditRequest, CreateImageRequest, CreateImageVariationRequest,
+};
 use crate::types::image::ImagesResponse;
 use crate::GatewayResult;
 use async_openai::config::OpenAIConfig;
@@ -24,8 +27,12 @@ impl OpenAISpecModel {
         endpoint: Option<&str>,
         provider_name: &str,
     ) -> Result<Self, ModelError> {
-        let client: Client<OpenAIConfig> =
-            openai_spec_client(credentials, endpoint, provider_name)?;
+        let client: Client<OpenAIConfig> = openai_spec_client(
+            credentials,
+            endpoint,
+            provider_name,
+            &ExecutionOptions::default(),
+        )?;
         let openai_model = OpenAIImageGeneration::new(credentials, Some(client), None)?;
 
         Ok(Self { openai_model })
@@ -42,4 +49,22 @@ impl ImageGenerationModelInstance for OpenAISpecModel {
     ) -> GatewayResult<ImagesResponse> {
         self.openai_model.create_new(request, tx, tags).await
     }
+
+    async fn edit(
+        &self,
+        request: &CreateImageEditRequest,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        self.openai_model.edit(request, tx, tags).await
+    }
+
+    async fn create_variation(
+        &self,
+        request: &CreateImageVariationRequest,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        self.openai_model.create_variation(request, tx, tags).await
+    }
 }
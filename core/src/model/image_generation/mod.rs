@@ -11,10 +11,14 @@ use tracing::info_span;
 use tracing_futures::Instrument;
 use valuable::Valuable;
 
+use crate::events::bodies::BodyRecordingMode;
 use crate::events::{JsonValue, RecordResult, SPAN_MODEL_CALL};
 use crate::model::types::ModelEventType;
 use crate::types::engine::{ImageGenerationEngineParams, ImageGenerationModelDefinition};
-use crate::types::gateway::{CostCalculator, CreateImageRequest, ImageGenerationModelUsage, Usage};
+use crate::types::gateway::{
+    CostCalculator, CreateImageEditRequest, CreateImageRequest, CreateImageVariationRequest,
+    ImageGenerationModelUsage, Usage,
+};
 use crate::types::image::ImagesResponse;
 use crate::GatewayResult;
 
@@ -34,6 +38,20 @@ pub trait ImageGenerationModelInstance: Sync + Send {
         tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
     ) -> GatewayResult<ImagesResponse>;
+
+    async fn edit(
+        &self,
+        request: &CreateImageEditRequest,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse>;
+
+    async fn create_variation(
+        &self,
+        request: &CreateImageVariationRequest,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse>;
 }
 
 fn initialize_image_generation_model_instance(
@@ -162,28 +180,32 @@ impl From<ImageGenerationModelDefinition> for TracedImageGenerationModelDefiniti
     }
 }
 
-#[async_trait::async_trait]
-impl<Inner: ImageGenerationModelInstance> ImageGenerationModelInstance
-    for TracedImageGenerationModel<Inner>
-{
-    async fn create_new(
+impl<Inner: ImageGenerationModelInstance> TracedImageGenerationModel<Inner> {
+    /// Wraps a call to the inner model in a model call span and records usage and cost
+    /// from its finish event
+    async fn traced_call<F, Fut>(
         &self,
-        request: &CreateImageRequest,
+        request_str: String,
         outer_tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
-    ) -> GatewayResult<ImagesResponse> {
+        call: F,
+    ) -> GatewayResult<ImagesResponse>
+    where
+        F: FnOnce(tokio::sync::mpsc::Sender<Option<ModelEvent>>, HashMap<String, String>) -> Fut,
+        Fut: std::future::Future<Output = GatewayResult<ImagesResponse>>,
+    {
         let traced_model: TracedImageGenerationModelDefinition = self.definition.clone().into();
         let credentials_ident = traced_model.get_credentials_owner();
         let model = traced_model.sanitize_json()?;
         let model_str = serde_json::to_string(&model)?;
         let model_name = self.definition.name.clone();
         let provider_name = self.definition.db_model.provider_name.clone();
-        let request_str = serde_json::to_string(request)?;
 
         let (tx, mut rx) = channel::<Option<ModelEvent>>(outer_tx.max_capacity());
+        let input = BodyRecordingMode::current().apply(&request_str);
         let span = info_span!(
             target: "langdb::user_tracing::models", SPAN_MODEL_CALL,
-            input = &request_str,
+            input = input.as_deref(),
             model = model_str,
             provider_name = provider_name,
             output = tracing::field::Empty,
@@ -229,14 +251,14 @@ impl<Inner: ImageGenerationModelInstance> ImageGenerationModelInstance
                         }
                     }
 
-                    outer_tx.send(Some(msg)).await.unwrap();
+                    let _ = outer_tx.send(Some(msg)).await;
                 }
             }
             .instrument(span.clone()),
         );
 
         async {
-            let result = self.inner.create_new(request, tx, tags).await;
+            let result = call(tx, tags).await;
             let _ = result.as_ref().map(|r| r.data.len()).record();
 
             result
@@ -245,3 +267,47 @@ impl<Inner: ImageGenerationModelInstance> ImageGenerationModelInstance
         .await
     }
 }
+
+#[async_trait::async_trait]
+impl<Inner: ImageGenerationModelInstance> ImageGenerationModelInstance
+    for TracedImageGenerationModel<Inner>
+{
+    async fn create_new(
+        &self,
+        request: &CreateImageRequest,
+        outer_tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        let request_str = serde_json::to_string(request)?;
+        self.traced_call(request_str, outer_tx, tags, |tx, tags| {
+            self.inner.create_new(request, tx, tags)
+        })
+        .await
+    }
+
+    async fn edit(
+        &self,
+        request: &CreateImageEditRequest,
+        outer_tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        let request_str = serde_json::to_string(request)?;
+        self.traced_call(request_str, outer_tx, tags, |tx, tags| {
+            self.inner.edit(request, tx, tags)
+        })
+        .await
+    }
+
+    async fn create_variation(
+        &self,
+        request: &CreateImageVariationRequest,
+        outer_tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        let request_str = serde_json::to_string(request)?;
+        self.traced_call(request_str, outer_tx, tags, |tx, tags| {
+            self.inner.create_variation(request, tx, tags)
+        })
+        .await
+    }
+}
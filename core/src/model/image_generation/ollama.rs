@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use tokio::sync::mpsc::Sender;
 use crate::model::image_generation::ImageGenerationModelInstance;
-use crate::types::gateway::CreateImageRequest;
+use crate::types::gateway::{CreateImageEditRequest, CreateImageRequest, CreateImageVariationRequest};
 use crate::types::image::ImagesResponse;
 use crate::model::types::ModelEvent;
 use crate::GatewayResult;
@@ -32,4 +32,22 @@ impl ImageGenerationModelInstance for OllamaImageGeneration {
         // Ollama 暂不支持 image generation
         unimplemented!("Ollama image generation is not supported yet")
     }
+
+    async fn edit(
+        &self,
+        _request: &CreateImageEditRequest,
+        _tx: Sender<Option<ModelEvent>>,
+        _tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        unimplemented!("Ollama image edits are not supported yet")
+    }
+
+    async fn create_variation(
+        &self,
+        _request: &CreateImageVariationRequest,
+        _tx: Sender<Option<ModelEvent>>,
+        _tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        unimplemented!("Ollama image variations are not supported yet")
+    }
 }
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use crate::events::bodies::BodyRecordingMode;
 use crate::events::SPAN_OPENAI;
 use crate::model::error::ModelError;
 use async_openai::config::Config;
@@ -9,13 +10,18 @@ use crate::model::types::ModelEventType;
 use crate::{
     error::GatewayError,
     model::{
+        http_client,
         openai::openai_client,
         types::{ImageGenerationFinishEvent, ModelEvent},
         CredentialsIdent,
     },
     types::{
         credentials::ApiKeyCredentials,
-        gateway::{CreateImageRequest, ImageQuality, ImageResponseFormat, ImageSize, ImageStyle},
+        engine::ExecutionOptions,
+        gateway::{
+            CreateImageEditRequest, CreateImageRequest, CreateImageVariationRequest, ImageFile,
+            ImageQuality, ImageResponseFormat, ImageSize, ImageStyle,
+        },
         image::ImagesResponse,
     },
     GatewayResult,
@@ -23,9 +29,10 @@ use crate::{
 
 use super::ImageGenerationModelInstance;
 use crate::model::JsonValue;
+use reqwest::multipart::{Form, Part};
 use secrecy::ExposeSecret;
 use serde::Deserialize;
-use tracing::field;
+use tracing::{field, Span};
 use valuable::Valuable;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -44,6 +51,7 @@ pub struct ApiError {
 #[derive(Clone)]
 pub struct OpenAIImageGeneration {
     client: Client<OpenAIConfig>,
+    http: reqwest::Client,
     credentials_ident: CredentialsIdent,
 }
 
@@ -57,7 +65,12 @@ impl OpenAIImageGeneration {
             credentials_ident: credentials
                 .map(|_c| CredentialsIdent::Own)
                 .unwrap_or(CredentialsIdent::Langdb),
-            client: client.unwrap_or(openai_client(credentials, endpoint)?),
+            client: client.unwrap_or(openai_client(
+                credentials,
+                endpoint,
+                &ExecutionOptions::default(),
+            )?),
+            http: http_client(&ExecutionOptions::default())?,
         })
     }
 
@@ -81,6 +94,47 @@ impl OpenAIImageGeneration {
         }
     }
 
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        let api_base = self.client.config().api_base().to_string();
+        let api_key: String = self.client.config().api_key().expose_secret().to_string();
+
+        self.http
+            .post(format!("{api_base}/{path}"))
+            .header("Authorization", format!("Bearer {api_key}"))
+    }
+
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        event: ImageGenerationFinishEvent,
+        call_span: &Span,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+    ) -> GatewayResult<ImagesResponse> {
+        let reqwest_result = request.send().await?;
+
+        if reqwest_result.status().is_success() {
+            let result = reqwest_result.json::<ImagesResponse>().await?;
+
+            tx.send(Some(ModelEvent::new(
+                call_span,
+                ModelEventType::ImageGenerationFinish(event),
+            )))
+            .await
+            .unwrap();
+
+            Ok(result)
+        } else {
+            let r: OpenAIReqwestError = reqwest_result.json().await.map_err(|e| {
+                call_span.record("error", e.to_string());
+                GatewayError::CustomError(format!("Failed to generate image: {e}"))
+            })?;
+            Err(GatewayError::CustomError(format!(
+                "Failed to generate image: {}",
+                r.error.message
+            )))
+        }
+    }
+
     fn map_size(
         &self,
         size: Option<&ImageSize>,
@@ -126,8 +180,8 @@ impl ImageGenerationModelInstance for OpenAIImageGeneration {
         tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
         tags: HashMap<String, String>,
     ) -> GatewayResult<ImagesResponse> {
-        let input = serde_json::to_string(request)?;
-        let call_span = tracing::info_span!(target: "langdb::user_tracing::models::openai::image_generation", SPAN_OPENAI, input = input, output = field::Empty, error = field::Empty, usage = field::Empty, ttft = field::Empty, tags = JsonValue(&serde_json::to_value(tags.clone()).unwrap_or_default()).as_value());
+        let input = BodyRecordingMode::current().apply(&serde_json::to_string(request)?);
+        let call_span = tracing::info_span!(target: "langdb::user_tracing::models::openai::image_generation", SPAN_OPENAI, input = input.as_deref(), output = field::Empty, error = field::Empty, usage = field::Empty, ttft = field::Empty, tags = JsonValue(&serde_json::to_value(tags.clone()).unwrap_or_default()).as_value());
 
         let size = self.map_size(request.size.as_ref());
 
@@ -158,45 +212,229 @@ impl ImageGenerationModelInstance for OpenAIImageGeneration {
             }),
         };
 
-        let api_base = self.client.config().api_base().to_string();
-        let api_key: String = self.client.config().api_key().expose_secret().to_string();
+        let event = self.generate_event(
+            &request.model,
+            request.quality.as_ref(),
+            request.size.as_ref(),
+            request.n.unwrap_or(1),
+            1,
+        );
+        let request = self.post("images/generations").json(&r);
 
-        let reqwest_client = reqwest::Client::new();
-        let reqwest_result = reqwest_client
-            .post(format!("{api_base}/images/generations"))
-            .header("Authorization", format!("Bearer {api_key}"))
-            .json(&r)
-            .send()
-            .await?;
+        self.send(request, event, &call_span, tx).await
+    }
 
-        if reqwest_result.status().is_success() {
-            let result = reqwest_result.json::<ImagesResponse>().await?;
+    async fn edit(
+        &self,
+        request: &CreateImageEditRequest,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        let input = BodyRecordingMode::current().apply(&serde_json::to_string(request)?);
+        let call_span = tracing::info_span!(target: "langdb::user_tracing::models::openai::image_generation", SPAN_OPENAI, input = input.as_deref(), output = field::Empty, error = field::Empty, usage = field::Empty, ttft = field::Empty, tags = JsonValue(&serde_json::to_value(tags.clone()).unwrap_or_default()).as_value());
 
-            let event = self.generate_event(
-                &request.model,
-                request.quality.as_ref(),
-                request.size.as_ref(),
-                request.n.unwrap_or(1),
-                1,
+        let mut form = Form::new()
+            .part("image", image_part(&request.image)?)
+            .text("prompt", request.prompt.clone());
+        if let Some(mask) = &request.mask {
+            form = form.part("mask", image_part(mask)?);
+        }
+        let form = with_image_options(
+            form,
+            &request.model,
+            request.n,
+            request.size.as_ref(),
+            request.response_format.as_ref(),
+            request.user.as_ref(),
+        );
+
+        let event = self.generate_event(
+            &request.model,
+            None,
+            request.size.as_ref(),
+            request.n.unwrap_or(1),
+            1,
+        );
+        let request = self.post("images/edits").multipart(form);
+
+        self.send(request, event, &call_span, tx).await
+    }
+
+    async fn create_variation(
+        &self,
+        request: &CreateImageVariationRequest,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+        tags: HashMap<String, String>,
+    ) -> GatewayResult<ImagesResponse> {
+        let input = BodyRecordingMode::current().apply(&serde_json::to_string(request)?);
+        let call_span = tracing::info_span!(target: "langdb::user_tracing::models::openai::image_generation", SPAN_OPENAI, input = input.as_deref(), output = field::Empty, error = field::Empty, usage = field::Empty, ttft = field::Empty, tags = JsonValue(&serde_json::to_value(tags.clone()).unwrap_or_default()).as_value());
+
+        let form = with_image_options(
+            Form::new().part("image", image_part(&request.image)?),
+            &request.model,
+            request.n,
+            request.size.as_ref(),
+            request.response_format.as_ref(),
+            request.user.as_ref(),
+        );
+
+        let event = self.generate_event(
+            &request.model,
+            None,
+            request.size.as_ref(),
+            request.n.unwrap_or(1),
+            1,
+        );
+        let request = self.post("images/variations").multipart(form);
+
+        self.send(request, event, &call_span, tx).await
+    }
+}
+
+fn image_part(file: &ImageFile) -> Result<Part, GatewayError> {
+    let mime = match file.filename.rsplit('.').next().map(|e| e.to_lowercase()) {
+        Some(ext) if ext == "jpg" || ext == "jpeg" => "image/jpeg",
+        Some(ext) if ext == "webp" => "image/webp",
+        _ => "image/png",
+    };
+
+    Part::bytes(file.content.clone())
+        .file_name(file.filename.clone())
+        .mime_str(mime)
+        .map_err(|e| GatewayError::CustomError(e.to_string()))
+}
+
+fn with_image_options(
+    mut form: Form,
+    model: &str,
+    n: Option<u8>,
+    size: Option<&ImageSize>,
+    response_format: Option<&ImageResponseFormat>,
+    user: Option<&String>,
+) -> Form {
+    form = form.text("model", model.to_string());
+    if let Some(n) = n {
+        form = form.text("n", n.to_string());
+    }
+    if let Some(size) = size {
+        form = form.text("size", size.to_string());
+    }
+    if let Some(response_format) = response_format {
+        let response_format = match response_format {
+            ImageResponseFormat::Url => "url",
+            ImageResponseFormat::B64Json => "b64_json",
+        };
+        form = form.text("response_format", response_format);
+    }
+    if let Some(user) = user {
+        form = form.text("user", user.clone());
+    }
+    form
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accepts a single request, returns it as text and answers with a fixed image response
+    async fn mock_server() -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = vec![];
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&received).to_string();
+                if let Some(headers_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..headers_end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if received.len() >= headers_end + 4 + content_length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+
+            let body = r#"{"created":1,"data":[{"url":"http://localhost/image.png"}]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
             );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&received).to_string()
+        });
 
-            tx.send(Some(ModelEvent::new(
-                &call_span,
-                ModelEventType::ImageGenerationFinish(event),
-            )))
-            .await
-            .unwrap();
+        (url, handle)
+    }
 
-            Ok(result)
-        } else {
-            let r: OpenAIReqwestError = reqwest_result.json().await.map_err(|e| {
-                call_span.record("error", e.to_string());
-                GatewayError::CustomError(format!("Failed to generate image: {e}"))
-            })?;
-            Err(GatewayError::CustomError(format!(
-                "Failed to generate image: {}",
-                r.error.message
-            )))
-        }
+    #[tokio::test]
+    async fn test_image_edit_request() {
+        let (url, server) = mock_server().await;
+        let model = OpenAIImageGeneration::new(
+            Some(&ApiKeyCredentials {
+                api_key: "test-key".to_string(),
+            }),
+            None,
+            Some(&url),
+        )
+        .unwrap();
+
+        let request = CreateImageEditRequest {
+            image: ImageFile {
+                filename: "image.png".to_string(),
+                content: vec![0x89, b'P', b'N', b'G'],
+            },
+            mask: Some(ImageFile {
+                filename: "mask.png".to_string(),
+                content: vec![0x89, b'P', b'N', b'G'],
+            }),
+            prompt: "Add a red hat".to_string(),
+            model: "dall-e-2".to_string(),
+            n: Some(1),
+            size: Some(ImageSize::Size256x256),
+            response_format: None,
+            user: None,
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let response = model.edit(&request, tx, HashMap::new()).await.unwrap();
+        assert_eq!(
+            response.data[0].url.as_deref(),
+            Some("http://localhost/image.png")
+        );
+
+        let received = server.await.unwrap();
+        assert!(received.starts_with("POST /images/edits "));
+        assert!(received.contains("authorization: Bearer test-key"));
+        assert!(received.contains("multipart/form-data; boundary="));
+        assert!(received.contains(r#"name="image"; filename="image.png""#));
+        assert!(received.contains(r#"name="mask"; filename="mask.png""#));
+        assert!(received.contains(r#"name="prompt""#));
+        assert!(received.contains("Add a red hat"));
+        assert!(received.contains(r#"name="size""#));
+        assert!(received.contains("256x256"));
+
+        let event = rx.recv().await.unwrap().unwrap();
+        assert!(matches!(
+            event.event,
+            ModelEventType::ImageGenerationFinish(ImageGenerationFinishEvent {
+                count_of_images: 1,
+                ..
+            })
+        ));
     }
 }
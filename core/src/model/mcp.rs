@@ -1,4 +1,7 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 
 use regex::Regex;
 use rmcp::model::{
@@ -49,10 +52,28 @@ pub enum McpServerError {
     #[error("No text content in tool {0} result")]
     NoTextInToolResult(String),
 
+    #[error("MCP server {0} is unavailable")]
+    ServerUnavailable(String),
+
     #[error("Join error: {0}")]
     JoinError(#[from] tokio::task::JoinError),
 }
 
+impl McpServerError {
+    /// Whether the server could not be reached, as opposed to answering with an error
+    pub fn is_connection_error(&self) -> bool {
+        match self {
+            McpServerError::ServiceError(e) => is_connection_error(e),
+            McpServerError::SseTransportError(_)
+            | McpServerError::ClientInitializeError(_)
+            | McpServerError::ClientStartError(_)
+            | McpServerError::ReqwestError(_)
+            | McpServerError::ServerUnavailable(_) => true,
+            _ => false,
+        }
+    }
+}
+
 impl From<rmcp::service::ClientInitializeError<std::io::Error>> for McpServerError {
     fn from(value: rmcp::service::ClientInitializeError<std::io::Error>) -> Self {
         McpServerError::ClientInitializeError(Box::new(value))
@@ -75,6 +96,145 @@ pub fn stdio() -> (tokio::io::Stdin, tokio::io::Stdout) {
     (tokio::io::stdin(), tokio::io::stdout())
 }
 
+type McpClient = RunningService<RoleClient, Box<dyn DynService<RoleClient>>>;
+
+/// How long a server that could not be reached is skipped before reconnecting
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+static CONNECTION_POOL: LazyLock<McpConnectionPool> =
+    LazyLock::new(|| McpConnectionPool::new(RECONNECT_INTERVAL));
+
+/// How long a server's tool listing is served without asking the server again
+const TOOLS_CACHE_TTL: Duration = Duration::from_secs(300);
+
+static TOOLS_CACHE: LazyLock<McpToolsCache> = LazyLock::new(|| McpToolsCache::new(TOOLS_CACHE_TTL));
+
+/// Keeps one connection per MCP server. Broken connections are dropped and
+/// re-established lazily on the next use, servers that fail to connect are skipped
+/// until `reconnect_interval` has passed.
+pub struct McpConnectionPool {
+    clients: tokio::sync::Mutex<HashMap<String, Arc<McpClient>>>,
+    down_since: parking_lot::Mutex<HashMap<String, Instant>>,
+    reconnect_interval: Duration,
+}
+
+impl McpConnectionPool {
+    pub fn new(reconnect_interval: Duration) -> Self {
+        Self {
+            clients: tokio::sync::Mutex::new(HashMap::new()),
+            down_since: parking_lot::Mutex::new(HashMap::new()),
+            reconnect_interval,
+        }
+    }
+
+    pub fn is_down(&self, definition: &McpDefinition) -> bool {
+        let mut down_since = self.down_since.lock();
+        let key = definition.r#type.key();
+        match down_since.get(&key) {
+            Some(since) if since.elapsed() < self.reconnect_interval => true,
+            Some(_) => {
+                down_since.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn mark_down(&self, definition: &McpDefinition) {
+        self.down_since
+            .lock()
+            .insert(definition.r#type.key(), Instant::now());
+    }
+
+    async fn client(&self, definition: &McpDefinition) -> Result<Arc<McpClient>, McpServerError> {
+        let key = definition.r#type.key();
+        if let Some(client) = self.clients.lock().await.get(&key) {
+            return Ok(client.clone());
+        }
+        if self.is_down(definition) {
+            return Err(McpServerError::ServerUnavailable(definition.server_name()));
+        }
+
+        match get_transport(definition).await {
+            Ok(client) => {
+                let client = Arc::new(client);
+                self.clients.lock().await.insert(key, client.clone());
+                Ok(client)
+            }
+            Err(e) => {
+                self.mark_down(definition);
+                Err(e)
+            }
+        }
+    }
+
+    async fn evict(&self, definition: &McpDefinition) {
+        self.clients.lock().await.remove(&definition.r#type.key());
+    }
+
+    /// Runs `f` on a pooled connection. A broken connection is dropped so the next call
+    /// reconnects, but `f` is not retried, so calls with side effects such as tool calls
+    /// run at most once.
+    pub async fn with_client<T, F, Fut>(
+        &self,
+        definition: &McpDefinition,
+        f: F,
+    ) -> Result<T, McpServerError>
+    where
+        F: FnOnce(Arc<McpClient>) -> Fut,
+        Fut: Future<Output = Result<T, ServiceError>>,
+    {
+        let client = self.client(definition).await?;
+        let result = f(client).await;
+        if let Err(e) = &result {
+            if is_connection_error(e) {
+                self.evict(definition).await;
+            }
+        }
+        result.map_err(McpServerError::from)
+    }
+
+    /// Runs `f` on a pooled connection. A broken connection is replaced by a fresh one
+    /// and `f` is retried once, so `f` has to be safe to repeat, like listing tools.
+    /// Errors returned by the server are not retried.
+    pub async fn with_client_retrying<T, F, Fut>(
+        &self,
+        definition: &McpDefinition,
+        f: F,
+    ) -> Result<T, McpServerError>
+    where
+        F: Fn(Arc<McpClient>) -> Fut,
+        Fut: Future<Output = Result<T, ServiceError>>,
+    {
+        let client = self.client(definition).await?;
+        match f(client).await {
+            Err(e) if is_connection_error(&e) => {
+                tracing::warn!(
+                    "MCP server {} connection failed, reconnecting: {e}",
+                    definition.server_name()
+                );
+                self.evict(definition).await;
+                let client = self.client(definition).await?;
+                f(client).await.map_err(|e| {
+                    if is_connection_error(&e) {
+                        self.mark_down(definition);
+                    }
+                    McpServerError::from(e)
+                })
+            }
+            result => result.map_err(McpServerError::from),
+        }
+    }
+}
+
+/// Whether `e` comes from a broken connection rather than from the server's answer
+fn is_connection_error(e: &ServiceError) -> bool {
+    matches!(
+        e,
+        ServiceError::TransportSend(_) | ServiceError::TransportClosed
+    )
+}
+
 pub async fn get_transport(
     definition: &McpDefinition,
 ) -> Result<RunningService<RoleClient, Box<dyn DynService<RoleClient>>>, McpServerError> {
@@ -117,73 +277,211 @@ pub async fn get_transport(
     }
 }
 
-pub async fn get_tools(definitions: &[McpDefinition]) -> Result<Vec<ServerTools>, McpServerError> {
-    let mut all_tools = Vec::new();
+struct CachedTools {
+    tools: Vec<rmcp::model::Tool>,
+    fetched_at: Instant,
+    refreshing: bool,
+}
 
-    for tool_def in definitions {
-        let mcp_server_name = tool_def.server_name();
-        let client = get_transport(tool_def).await?;
-        let tools = client.list_tools(Default::default()).await?;
-        client.cancel().await?;
-
-        let mut tools = tools.tools;
-        let total_tools = tools.len();
-
-        // Filter tools based on actions_filter if specified
-        match &tool_def.filter {
-            ToolsFilter::All => {
-                tracing::debug!("Loading all {} tools from {}", total_tools, mcp_server_name);
-            }
-            ToolsFilter::Selected(selected) => {
-                let before_count = tools.len();
-                tools.retain_mut(|tool| {
-                    let found = selected.iter().find(|t| {
-                        if tool.name == t.name {
-                            true
-                        } else if let Ok(name_regex) = Regex::new(&t.name) {
-                            debug!("Matching {} against pattern {}", tool.name, t.name);
-                            name_regex.is_match(&tool.name)
-                        } else {
-                            false
+/// Tool listings of MCP servers keyed by server config. Listings older than `ttl`
+/// are still served while a refresh runs in the background, listings of servers
+/// that fail to respond are dropped.
+pub struct McpToolsCache {
+    entries: Arc<parking_lot::Mutex<HashMap<String, CachedTools>>>,
+    ttl: Duration,
+}
+
+impl McpToolsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    fn key(definition: &McpDefinition) -> String {
+        definition.r#type.key()
+    }
+
+    pub fn invalidate(&self, definition: &McpDefinition) {
+        self.entries.lock().remove(&Self::key(definition));
+    }
+
+    fn store(
+        entries: &parking_lot::Mutex<HashMap<String, CachedTools>>,
+        key: String,
+        result: &Result<Vec<rmcp::model::Tool>, McpServerError>,
+    ) {
+        let mut entries = entries.lock();
+        match result {
+            Ok(tools) => {
+                entries.insert(
+                    key,
+                    CachedTools {
+                        tools: tools.clone(),
+                        fetched_at: Instant::now(),
+                        refreshing: false,
+                    },
+                );
+            }
+            Err(_) => {
+                entries.remove(&key);
+            }
+        }
+    }
+
+    /// Returns the cached tools of `definition`, listing them with `fetch` when missing
+    pub async fn get<F, Fut>(
+        &self,
+        definition: &McpDefinition,
+        fetch: F,
+    ) -> Result<Vec<rmcp::model::Tool>, McpServerError>
+    where
+        F: FnOnce(McpDefinition) -> Fut,
+        Fut: Future<Output = Result<Vec<rmcp::model::Tool>, McpServerError>> + Send + 'static,
+    {
+        let key = Self::key(definition);
+        let stale = {
+            let mut entries = self.entries.lock();
+            match entries.get_mut(&key) {
+                Some(entry) if entry.fetched_at.elapsed() < self.ttl => {
+                    return Ok(entry.tools.clone());
+                }
+                Some(entry) if !entry.refreshing => {
+                    entry.refreshing = true;
+                    Some((entry.tools.clone(), true))
+                }
+                Some(entry) => Some((entry.tools.clone(), false)),
+                None => None,
+            }
+        };
+
+        match stale {
+            Some((tools, spawn_refresh)) => {
+                if spawn_refresh {
+                    let entries = self.entries.clone();
+                    let refresh = fetch(definition.clone());
+                    let server_name = definition.server_name();
+                    tokio::spawn(async move {
+                        let result = refresh.await;
+                        if let Err(e) = &result {
+                            tracing::warn!(
+                                "Failed to refresh tools of MCP server {server_name}: {e}"
+                            );
                         }
+                        Self::store(&entries, key, &result);
                     });
-                    if let Some(Some(d)) = found.as_ref().map(|t| t.description.as_ref()) {
-                        tool.description = Some(d.clone().into());
+                }
+                Ok(tools)
+            }
+            None => {
+                let result = fetch(definition.clone()).await;
+                Self::store(&self.entries, key, &result);
+                result
+            }
+        }
+    }
+}
+
+fn filter_tools(definition: &McpDefinition, mut tools: Vec<rmcp::model::Tool>) -> Vec<McpTool> {
+    let mcp_server_name = definition.server_name();
+    let total_tools = tools.len();
+
+    // Filter tools based on actions_filter if specified
+    match &definition.filter {
+        ToolsFilter::All => {
+            tracing::debug!("Loading all {} tools from {}", total_tools, mcp_server_name);
+        }
+        ToolsFilter::Selected(selected) => {
+            let before_count = tools.len();
+            tools.retain_mut(|tool| {
+                let found = selected.iter().find(|t| {
+                    if tool.name == t.name {
+                        true
+                    } else if let Ok(name_regex) = Regex::new(&t.name) {
+                        debug!("Matching {} against pattern {}", tool.name, t.name);
+                        name_regex.is_match(&tool.name)
+                    } else {
+                        false
                     }
-                    found.is_some()
                 });
-                tracing::debug!(
-                    "Filtered tools for {}: {}/{} tools selected",
-                    mcp_server_name,
-                    tools.len(),
-                    before_count
-                );
-            }
+                if let Some(Some(d)) = found.as_ref().map(|t| t.description.as_ref()) {
+                    tool.description = Some(d.clone().into());
+                }
+                found.is_some()
+            });
+            tracing::debug!(
+                "Filtered tools for {}: {}/{} tools selected",
+                mcp_server_name,
+                tools.len(),
+                before_count
+            );
         }
+    }
 
-        let mcp_tools = tools
-            .into_iter()
-            .map(|t| McpTool(t, tool_def.clone()))
-            .collect();
+    tools
+        .into_iter()
+        .map(|t| McpTool(t, definition.clone()))
+        .collect()
+}
+
+/// Loads the tools of every server with `fetch`. Servers whose tools can't be
+/// fetched are skipped so the request can go on with the remaining tools.
+async fn collect_tools<F, Fut>(definitions: &[McpDefinition], fetch: F) -> Vec<ServerTools>
+where
+    F: Fn(McpDefinition) -> Fut,
+    Fut: Future<Output = Result<Vec<rmcp::model::Tool>, McpServerError>>,
+{
+    let mut all_tools = Vec::new();
+
+    for tool_def in definitions {
+        let tools = match fetch(tool_def.clone()).await {
+            Ok(tools) => tools,
+            Err(e) => {
+                tracing::warn!(
+                    "Skipping tools of MCP server {}: {e}",
+                    tool_def.server_name()
+                );
+                continue;
+            }
+        };
 
         all_tools.push(ServerTools {
-            tools: mcp_tools,
+            tools: filter_tools(tool_def, tools),
             definition: tool_def.clone(),
         });
     }
 
     tracing::debug!("Loaded {} tool definitions in total", all_tools.len());
-    Ok(all_tools)
+    all_tools
+}
+
+async fn list_tools(definition: &McpDefinition) -> Result<Vec<rmcp::model::Tool>, McpServerError> {
+    CONNECTION_POOL
+        .with_client_retrying(definition, |client| async move {
+            client
+                .list_tools(Default::default())
+                .await
+                .map(|result| result.tools)
+        })
+        .await
+}
+
+pub async fn get_tools(definitions: &[McpDefinition]) -> Result<Vec<ServerTools>, McpServerError> {
+    Ok(collect_tools(definitions, |definition| async move {
+        TOOLS_CACHE
+            .get(&definition, |definition| async move {
+                list_tools(&definition).await
+            })
+            .await
+    })
+    .await)
 }
 
 pub async fn get_raw_tools(
     definitions: &McpDefinition,
 ) -> Result<Vec<rmcp::model::Tool>, McpServerError> {
-    let client = get_transport(definitions).await?;
-    let tools = client.list_tools(Default::default()).await?;
-    client.cancel().await?;
-
-    Ok(tools.tools)
+    list_tools(definitions).await
 }
 
 pub async fn execute_mcp_tool(
@@ -203,8 +501,6 @@ pub async fn execute_mcp_tool(
     }
     let name = tool.name.clone();
 
-    let client = get_transport(def).await?;
-
     let mut args = serde_json::Map::new();
 
     for (key, value) in inputs {
@@ -228,8 +524,15 @@ pub async fn execute_mcp_tool(
         }
     }
 
-    let response = client.send_request(t).await?;
-    client.cancel().await?;
+    let response = CONNECTION_POOL
+        .with_client(def, |client| async move { client.send_request(t).await })
+        .await
+        .inspect_err(|e| {
+            // The server may come back with different tools, list them again next time
+            if e.is_connection_error() {
+                TOOLS_CACHE.invalidate(def);
+            }
+        })?;
 
     let response = match response {
         ServerResult::CallToolResult(result) => Ok(result),
@@ -251,3 +554,216 @@ pub async fn execute_mcp_tool(
     tracing::error!("Tool {name}: No text content in tool response", name = name);
     Err(McpServerError::NoTextInToolResult(name.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_server(server_url: &str) -> McpDefinition {
+        McpDefinition {
+            filter: ToolsFilter::All,
+            r#type: McpTransportType::Http {
+                server_url: server_url.to_string(),
+                headers: HashMap::new(),
+                env: None,
+            },
+        }
+    }
+
+    fn tool(name: &str) -> rmcp::model::Tool {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "description": name,
+            "inputSchema": {"type": "object"}
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_down_server_is_skipped() {
+        let definitions = [
+            http_server("http://127.0.0.1:1/mcp"),
+            http_server("http://localhost:3004/mcp"),
+        ];
+
+        let tools = collect_tools(&definitions, |definition| async move {
+            match definition.server_name().as_str() {
+                "http://localhost:3004/mcp" => Ok(vec![tool("ping"), tool("search")]),
+                name => Err(McpServerError::ServerUnavailable(name.to_string())),
+            }
+        })
+        .await;
+
+        assert_eq!(tools.len(), 1);
+        assert_eq!(
+            tools[0].definition.server_name(),
+            "http://localhost:3004/mcp"
+        );
+        let names: Vec<_> = tools[0]
+            .tools
+            .iter()
+            .map(|t| t.0.name.to_string())
+            .collect();
+        assert_eq!(names, vec!["ping", "search"]);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_server_is_marked_down() {
+        let pool = McpConnectionPool::new(Duration::from_secs(60));
+        let definition = http_server("http://127.0.0.1:1/mcp");
+        assert!(!pool.is_down(&definition));
+
+        let result = pool
+            .with_client_retrying(&definition, |client| async move {
+                client.list_tools(Default::default()).await
+            })
+            .await;
+        assert!(result.is_err());
+        assert!(pool.is_down(&definition));
+
+        // Requests during the reconnect interval fail fast without connecting
+        let result = pool
+            .with_client_retrying(&definition, |client| async move {
+                client.list_tools(Default::default()).await
+            })
+            .await;
+        assert!(matches!(result, Err(McpServerError::ServerUnavailable(_))));
+
+        // Once the interval has passed the server is tried again
+        let pool = McpConnectionPool::new(Duration::ZERO);
+        pool.mark_down(&definition);
+        assert!(!pool.is_down(&definition));
+    }
+
+    #[test]
+    fn test_pool_key_includes_headers_and_env() {
+        let with_config = |headers: &[(&str, &str)], env: Option<&[(&str, &str)]>| {
+            let to_map = |pairs: &[(&str, &str)]| {
+                pairs
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<HashMap<_, _>>()
+            };
+            McpTransportType::Http {
+                server_url: "http://localhost:3004/mcp".to_string(),
+                headers: to_map(headers),
+                env: env.map(to_map),
+            }
+            .key()
+        };
+
+        let tenant_a = with_config(&[("authorization", "Bearer a"), ("x-team", "search")], None);
+        assert_eq!(
+            tenant_a,
+            with_config(&[("x-team", "search"), ("authorization", "Bearer a")], None)
+        );
+        assert_ne!(
+            tenant_a,
+            with_config(&[("authorization", "Bearer b"), ("x-team", "search")], None)
+        );
+        assert_ne!(
+            with_config(&[], Some(&[("API_KEY", "a")])),
+            with_config(&[], Some(&[("API_KEY", "b")]))
+        );
+        assert_ne!(with_config(&[], None), with_config(&[], Some(&[])));
+    }
+
+    #[test]
+    fn test_only_connection_errors_are_retried() {
+        assert!(is_connection_error(&ServiceError::TransportClosed));
+        assert!(!is_connection_error(&ServiceError::UnexpectedResponse));
+    }
+
+    #[tokio::test]
+    async fn test_tools_are_cached_until_expiry() {
+        let cache = McpToolsCache::new(Duration::from_millis(200));
+        let definition = http_server("http://localhost:3004/mcp");
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let list = || {
+            let calls = calls.clone();
+            move |_definition: McpDefinition| async move {
+                calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(vec![tool("ping")])
+            }
+        };
+        let call_count = || calls.load(std::sync::atomic::Ordering::SeqCst);
+
+        for _ in 0..3 {
+            let tools = cache.get(&definition, list()).await.unwrap();
+            assert_eq!(tools.len(), 1);
+        }
+        assert_eq!(call_count(), 1);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        // The expired listing is still served while it is refreshed in the background
+        let tools = cache.get(&definition, list()).await.unwrap();
+        assert_eq!(tools.len(), 1);
+        for _ in 0..50 {
+            if call_count() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(call_count(), 2);
+
+        cache.get(&definition, list()).await.unwrap();
+        assert_eq!(call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_listing_is_not_cached() {
+        let cache = McpToolsCache::new(Duration::from_secs(60));
+        let definition = http_server("http://localhost:3004/mcp");
+
+        let result = cache
+            .get(&definition, |definition| async move {
+                Err(McpServerError::ServerUnavailable(definition.server_name()))
+            })
+            .await;
+        assert!(result.is_err());
+
+        let tools = cache
+            .get(&definition, |_| async move { Ok(vec![tool("ping")]) })
+            .await
+            .unwrap();
+        assert_eq!(tools.len(), 1);
+
+        cache.invalidate(&definition);
+        let tools = cache
+            .get(&definition, |_| async move { Ok(vec![]) })
+            .await
+            .unwrap();
+        assert!(tools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_connection_error_invalidates_cached_tools() {
+        let definition = http_server("http://127.0.0.1:1/invalidated");
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let list = || {
+            let calls = calls.clone();
+            move |_definition: McpDefinition| async move {
+                calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(vec![tool("ping")])
+            }
+        };
+
+        TOOLS_CACHE.get(&definition, list()).await.unwrap();
+        TOOLS_CACHE.get(&definition, list()).await.unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let result = execute_mcp_tool(&definition, &tool("ping"), HashMap::new(), None).await;
+        assert!(result.unwrap_err().is_connection_error());
+
+        TOOLS_CACHE.get(&definition, list()).await.unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_tools_tolerates_down_server() {
+        let tools = get_tools(&[http_server("http://127.0.0.1:1/mcp")])
+            .await
+            .unwrap();
+        assert!(tools.is_empty());
+    }
+}
@@ -1,5 +1,6 @@
-use crate::events::{JsonValue, RecordResult, SPAN_MODEL_CALL};
+use crate::events::{JsonValue, SPAN_MODEL_CALL};
 use crate::executor::context::ExecutorContext;
+use crate::handler::ModelEventWithDetails;
 use crate::model::bedrock::BedrockModel;
 use crate::model::cached::CachedModel;
 use crate::model::error::ModelError;
@@ -7,11 +8,11 @@ use crate::model::ollama::OllamaModel;
 use crate::model::ollama_api::OllamaApiModel;
 use crate::model::openai::OpenAIModel;
 use crate::model::proxy::OpenAISpecModel;
-use crate::types::engine::{CompletionEngineParams, CompletionModelParams};
-use crate::types::engine::{CompletionModelDefinition, ModelTools, ModelType};
+use crate::types::engine::{CompletionEngineParams, CompletionModelParams, ExecutionOptions};
+use crate::types::engine::{CompletionModelDefinition, Model, ModelTools, ModelType};
 use crate::types::gateway::{
-    ChatCompletionContent, ChatCompletionMessage, ContentType, Extra, GuardOrName,
-    GuardWithParameters, Usage,
+    ChatCompletionContent, ChatCompletionMessage, ContentType, CostCalculationResult, Extra,
+    GuardOrName, GuardWithParameters, Usage,
 };
 use crate::types::guardrails::service::GuardrailsEvaluator;
 use crate::types::guardrails::{GuardError, GuardResult, GuardStage};
@@ -21,7 +22,7 @@ use anthropic::AnthropicModel;
 use async_openai::config::OpenAIConfig;
 use async_openai::Client;
 use async_trait::async_trait;
-use futures::future::join;
+use futures::future::{join, AbortHandle, Abortable};
 use gemini::GeminiModel;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
@@ -30,7 +31,7 @@ use std::fmt::Display;
 use tokio::sync::mpsc::{self, channel};
 use tools::Tool;
 use tracing::{info_span, Instrument};
-use types::{ModelEvent, ModelEventType};
+use types::{CustomEvent, ModelEvent, ModelEventType, ModelFinishReason};
 use valuable::Valuable;
 pub mod handler;
 
@@ -48,6 +49,8 @@ pub mod openai;
 pub mod openai_spec_client;
 pub mod ollama_api;
 pub mod proxy;
+pub mod rate_limits;
+pub mod tls;
 pub mod tools;
 pub mod types;
 
@@ -79,6 +82,158 @@ pub trait ModelInstance: Sync + Send {
 
 pub const DEFAULT_MAX_RETRIES: i32 = 0;
 
+/// Runs a single provider call, failing once the configured timeout elapses
+pub async fn with_timeout<T>(
+    execution_options: &ExecutionOptions,
+    call: impl std::future::Future<Output = GatewayResult<T>>,
+) -> GatewayResult<T> {
+    match execution_options.timeout() {
+        Some(timeout) => tokio::time::timeout(timeout, call)
+            .await
+            .map_err(|_| ModelError::Timeout(timeout.as_secs()))?,
+        None => call.await,
+    }
+}
+
+/// HTTP client for provider calls with the connect and read timeouts of `execution_options`
+/// and the configured provider TLS policy
+pub fn http_client(execution_options: &ExecutionOptions) -> Result<reqwest::Client, ModelError> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(config) = tls::provider_tls() {
+        builder = tls::apply_tls(builder, config).map_err(ModelError::CustomError)?;
+    }
+    if let Some(timeout) = execution_options.connect_timeout() {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = execution_options.read_timeout() {
+        builder = builder.read_timeout(timeout);
+    }
+    builder
+        .build()
+        .map_err(|e| ModelError::CustomError(e.to_string()))
+}
+
+pub async fn wait_retry_backoff(execution_options: &ExecutionOptions) {
+    if let Some(backoff) = execution_options.retry_backoff() {
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// Counts tool call rounds of an agent loop, so a tool that keeps triggering
+/// new calls cannot loop forever. Rounds are not capped unless
+/// `max_tool_call_iterations` is set.
+pub struct ToolCallIterations {
+    max_iterations: Option<u32>,
+    iterations: u32,
+}
+
+impl ToolCallIterations {
+    pub fn new(execution_options: &ExecutionOptions) -> Self {
+        Self {
+            max_iterations: execution_options.max_tool_call_iterations,
+            iterations: 0,
+        }
+    }
+
+    /// Registers the next tool call round, before its tools run. Fails with the partial
+    /// result once the cap is exceeded
+    pub fn next_round(
+        &mut self,
+        partial_result: impl FnOnce() -> Option<String>,
+    ) -> Result<(), ModelError> {
+        self.iterations += 1;
+        match self.max_iterations {
+            Some(max_iterations) if self.iterations > max_iterations => {
+                Err(ModelError::MaxToolCallIterationsReached(
+                    max_iterations,
+                    partial_result().unwrap_or_default(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `error` ended the loop at the cap, which is final and not retried
+    pub fn reached(error: &crate::GatewayError) -> bool {
+        matches!(
+            error,
+            crate::GatewayError::ModelError(e)
+                if matches!(e.as_ref(), ModelError::MaxToolCallIterationsReached(..))
+        )
+    }
+}
+
+/// Whether a successful completion has neither text, media nor tool calls
+pub fn is_empty_completion(message: &ChatCompletionMessage) -> bool {
+    let has_content = match &message.content {
+        Some(ChatCompletionContent::Text(text)) => !text.trim().is_empty(),
+        Some(ChatCompletionContent::Content(parts)) => parts.iter().any(|p| match p.r#type {
+            ContentType::Text => p.text.as_ref().is_some_and(|t| !t.trim().is_empty()),
+            ContentType::ImageUrl | ContentType::InputAudio | ContentType::Audio => true,
+        }),
+        None => false,
+    };
+    let has_tool_calls = message.tool_calls.as_ref().is_some_and(|c| !c.is_empty());
+
+    !has_content && !has_tool_calls
+}
+
+/// Invokes `model`, calling it again up to `max_retries` times while it completes
+/// without content. Returns the result with the number of retries made.
+///
+/// Events of each call are held back until it is known whether the call is retried, only
+/// those of the returned call reach `tx`. Usage of discarded calls is not reported.
+pub async fn invoke_retrying_empty<M: ModelInstance + ?Sized>(
+    model: &M,
+    max_retries: u32,
+    input_vars: HashMap<String, Value>,
+    tx: mpsc::Sender<Option<ModelEvent>>,
+    previous_messages: Vec<Message>,
+    tags: HashMap<String, String>,
+) -> (GatewayResult<ChatCompletionMessage>, u32) {
+    let mut retries = 0;
+    loop {
+        // The last call is returned whatever it completes with
+        if retries == max_retries {
+            let result = model.invoke(input_vars, tx, previous_messages, tags).await;
+            return (result, retries);
+        }
+
+        let (attempt_tx, mut attempt_rx) = channel(tx.max_capacity());
+        let invoke = model.invoke(
+            input_vars.clone(),
+            attempt_tx,
+            previous_messages.clone(),
+            tags.clone(),
+        );
+        tokio::pin!(invoke);
+        // Drained while the call runs, so it never waits for room in the channel
+        let mut events = vec![];
+        let result = loop {
+            tokio::select! {
+                result = &mut invoke => break result,
+                Some(event) = attempt_rx.recv() => events.push(event),
+            }
+        };
+        while let Ok(event) = attempt_rx.try_recv() {
+            events.push(event);
+        }
+
+        match &result {
+            Ok(message) if is_empty_completion(message) => {
+                retries += 1;
+                tracing::warn!("Empty completion, retrying ({retries}/{max_retries})");
+            }
+            _ => {
+                for event in events {
+                    let _ = tx.send(event).await;
+                }
+                return (result, retries);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ResponseCacheState {
     #[serde(rename = "HIT")]
@@ -150,6 +305,15 @@ pub async fn init_completion_model_instance(
                 definition.prompt.clone(),
                 tools,
                 provider.clone(),
+                executor_context
+                    .bedrock_config
+                    .as_ref()
+                    .map(|c| &c.inference_profiles),
+                executor_context
+                    .bedrock_config
+                    .as_ref()
+                    .map(|c| c.regions.as_slice())
+                    .unwrap_or_default(),
             )
             .await?,
             definition,
@@ -280,7 +444,7 @@ pub async fn init_completion_model_instance(
                 execution_options.clone(),
                 credentials.clone(),
                 endpoint.map(|s| s.to_string()),
-            ),
+            )?,
             definition,
             executor_context: executor_context.clone(),
             router_span: router_span.clone(),
@@ -299,7 +463,7 @@ pub async fn init_completion_model_instance(
                 execution_options.clone(),
                 credentials.clone(),
                 endpoint.map(|s| s.to_string()),
-            ),
+            )?,
             definition,
             executor_context: executor_context.clone(),
             router_span: router_span.clone(),
@@ -348,8 +512,13 @@ struct TraceModelDefinition {
 }
 
 impl TraceModelDefinition {
-    pub fn sanitize_json(&self) -> GatewayResult<Value> {
+    /// Serializes the definition without credentials. With `fingerprint_credentials` a
+    /// `credentials_fingerprint` is added so the key used can be told apart from others.
+    pub fn sanitize_json(&self, fingerprint_credentials: bool) -> GatewayResult<Value> {
         let mut model = self.clone();
+        let fingerprint = fingerprint_credentials
+            .then(|| self.credentials_fingerprint())
+            .flatten();
 
         match &mut model.model_params.engine {
             CompletionEngineParams::OpenAi {
@@ -396,9 +565,28 @@ impl TraceModelDefinition {
                 credentials.take();
             }
         }
-        let model = serde_json::to_value(&model)?;
+        let mut model = serde_json::to_value(&model)?;
+        if let (Some(fingerprint), Value::Object(fields)) = (fingerprint, &mut model) {
+            fields.insert("credentials_fingerprint".into(), Value::String(fingerprint));
+        }
         Ok(model)
     }
+
+    fn credentials_fingerprint(&self) -> Option<String> {
+        match &self.model_params.engine {
+            CompletionEngineParams::Bedrock { credentials, .. } => {
+                credentials.as_ref().map(|c| c.fingerprint())
+            }
+            CompletionEngineParams::OpenAi { credentials, .. }
+            | CompletionEngineParams::Anthropic { credentials, .. }
+            | CompletionEngineParams::Gemini { credentials, .. }
+            | CompletionEngineParams::Proxy { credentials, .. }
+            | CompletionEngineParams::Ollama { credentials, .. }
+            | CompletionEngineParams::OllamaApi { credentials, .. } => {
+                credentials.as_ref().map(|c| c.fingerprint())
+            }
+        }
+    }
 }
 impl From<CompletionModelDefinition> for TraceModelDefinition {
     fn from(value: CompletionModelDefinition) -> Self {
@@ -421,6 +609,27 @@ impl<Inner: ModelInstance> TracedModel<Inner> {
         let str = serde_json::to_string(&json!(input_vars))?;
         Ok(str)
     }
+
+    /// Reports a provider rate limit to the callback handler, which counts them per model
+    fn report_rate_limit<T>(&self, result: &GatewayResult<T>) {
+        if let Err(crate::GatewayError::RateLimited {
+            provider,
+            retry_after,
+        }) = result
+        {
+            let event = CustomEvent::new(
+                RATE_LIMITED_EVENT.to_string(),
+                json!({"provider": provider, "retry_after": retry_after}),
+            );
+            let event = ModelEvent::new(&tracing::Span::current(), ModelEventType::Custom(event));
+            self.executor_context
+                .callbackhandler
+                .on_message(ModelEventWithDetails::new(
+                    event,
+                    Some(self.definition.db_model.clone()),
+                ));
+        }
+    }
 }
 
 #[async_trait]
@@ -434,10 +643,12 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
     ) -> GatewayResult<ChatCompletionMessage> {
         let credentials_ident = credentials_identifier(&self.definition.model_params);
         let traced_model: TraceModelDefinition = self.definition.clone().into();
-        let model = traced_model.sanitize_json()?;
+        let model = traced_model
+            .sanitize_json(self.executor_context.credential_fingerprinting.is_some())?;
         let model_str = serde_json::to_string(&model)?;
+        let body_recording = self.executor_context.body_recording;
         // TODO: Fix input creation properly
-        let input_str = self.clean_input_trace(&input_vars)?;
+        let input_str = body_recording.apply(&self.clean_input_trace(&input_vars)?);
         let model_name = self.definition.name.clone();
         let provider_name = self.definition.db_model.provider_name.clone();
         let (tx, mut rx) = channel::<Option<ModelEvent>>(outer_tx.max_capacity());
@@ -446,7 +657,7 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
             target: "langdb::user_tracing::models",
             parent: self.router_span.clone(),
             SPAN_MODEL_CALL,
-            input = &input_str,
+            input = input_str.as_deref(),
             model = model_str,
             provider_name = provider_name,
             model_name = model_name.clone(),
@@ -455,10 +666,17 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
             error = tracing::field::Empty,
             credentials_identifier = credentials_ident.to_string(),
             cost = tracing::field::Empty,
+            input_cost = tracing::field::Empty,
+            output_cost = tracing::field::Empty,
+            reasoning_cost = tracing::field::Empty,
+            tool_calls_cost = tracing::field::Empty,
+            cache_savings = tracing::field::Empty,
             usage = tracing::field::Empty,
+            response_id = tracing::field::Empty,
             ttft = tracing::field::Empty,
             tags = JsonValue(&serde_json::to_value(tags.clone())?).as_value(),
-            cache = tracing::field::Empty
+            cache = tracing::field::Empty,
+            empty_retries = tracing::field::Empty
         );
 
         if let Some(state) = &self.response_cache_state {
@@ -486,9 +704,14 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
                         }
                         ModelEventType::LlmStop(llmfinish_event) => {
                             let current_span = tracing::Span::current();
+                            if let Some(response_id) = &llmfinish_event.response_id {
+                                current_span.record("response_id", response_id.as_str());
+                            }
                             if let Some(output) = &llmfinish_event.output {
-                                current_span
-                                    .record("output", serde_json::to_string(output).unwrap());
+                                current_span.record(
+                                    "output",
+                                    body_recording.apply(&serde_json::to_string(output).unwrap()),
+                                );
                             }
                             if let Some(u) = &llmfinish_event.usage {
                                 match cost_calculator
@@ -500,8 +723,11 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
                                     .await
                                 {
                                     Ok(c) => {
-                                        current_span
-                                            .record("cost", serde_json::to_string(&c).unwrap());
+                                        record_cost(
+                                            &current_span,
+                                            c,
+                                            &llmfinish_event.finish_reason,
+                                        );
                                     }
                                     Err(e) => {
                                         tracing::error!(
@@ -539,28 +765,47 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
         );
 
         async {
-            let result = self
-                .inner
-                .invoke(input_vars, tx, previous_messages, tags)
+            let max_retries = self
+                .definition
+                .model_params
+                .engine
+                .execution_options()
+                .empty_completion_retries
+                .unwrap_or(0);
+            let (result, retries) = body_recording
+                .scope(invoke_retrying_empty(
+                    &self.inner,
+                    max_retries,
+                    input_vars,
+                    tx,
+                    previous_messages,
+                    tags,
+                ))
                 .await;
-            let _ = result
-                .as_ref()
-                .map(|r| match r.content.as_ref() {
-                    Some(content) => match content {
-                        ChatCompletionContent::Text(t) => t.to_string(),
-                        ChatCompletionContent::Content(b) => b
-                            .iter()
-                            .map(|a| match a.r#type {
-                                ContentType::Text => a.text.clone().unwrap_or_default(),
-                                ContentType::ImageUrl => "".to_string(),
-                                ContentType::InputAudio => "".to_string(),
-                            })
-                            .collect::<Vec<String>>()
-                            .join("\n"),
-                    },
-                    _ => "".to_string(),
-                })
-                .record();
+            let current_span = tracing::Span::current();
+            if retries > 0 {
+                current_span.record("empty_retries", retries);
+            }
+            let output = result.as_ref().map(|r| match r.content.as_ref() {
+                Some(content) => match content {
+                    ChatCompletionContent::Text(t) => t.to_string(),
+                    ChatCompletionContent::Content(b) => b
+                        .iter()
+                        .map(|a| match a.r#type {
+                            ContentType::Text => a.text.clone().unwrap_or_default(),
+                            ContentType::ImageUrl => "".to_string(),
+                            ContentType::InputAudio | ContentType::Audio => "".to_string(),
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                },
+                _ => "".to_string(),
+            });
+            match output {
+                Ok(output) => current_span.record("output", body_recording.apply(&output)),
+                Err(e) => current_span.record("error", e.to_string()),
+            };
+            self.report_rate_limit(&result);
 
             if let Ok(message) = &result {
                 apply_guardrails(
@@ -589,10 +834,12 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
     ) -> GatewayResult<()> {
         let credentials_ident = credentials_identifier(&self.definition.model_params);
         let traced_model: TraceModelDefinition = self.definition.clone().into();
-        let model = traced_model.sanitize_json()?;
+        let model = traced_model
+            .sanitize_json(self.executor_context.credential_fingerprinting.is_some())?;
         let model_str = serde_json::to_string(&model)?;
+        let body_recording = self.executor_context.body_recording;
         // TODO: Fix input creation properly
-        let input_str = self.clean_input_trace(&input_vars)?;
+        let input_str = body_recording.apply(&self.clean_input_trace(&input_vars)?);
 
         let model_name = self.definition.name.clone();
         let provider_name = self.definition.db_model.provider_name.clone();
@@ -602,7 +849,7 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
             target: "langdb::user_tracing::models",
             parent: self.router_span.clone(),
             SPAN_MODEL_CALL,
-            input = &input_str,
+            input = input_str.as_deref(),
             model = model_str,
             provider_name = provider_name,
             model_name = model_name.clone(),
@@ -611,7 +858,13 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
             error = tracing::field::Empty,
             credentials_identifier = credentials_ident.to_string(),
             cost = tracing::field::Empty,
+            input_cost = tracing::field::Empty,
+            output_cost = tracing::field::Empty,
+            reasoning_cost = tracing::field::Empty,
+            tool_calls_cost = tracing::field::Empty,
+            cache_savings = tracing::field::Empty,
             usage = tracing::field::Empty,
+            response_id = tracing::field::Empty,
             tags = JsonValue(&serde_json::to_value(tags.clone())?).as_value(),
             ttft = tracing::field::Empty,
             cache = tracing::field::Empty
@@ -635,11 +888,30 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
             let (tx, mut rx) = channel(outer_tx.max_capacity());
             let mut output = String::new();
             let mut start_time = None;
+            let (upstream_handle, upstream_registration) = AbortHandle::new_pair();
             let result = join(
-                self.inner
-                    .stream(input_vars, tx, previous_messages, tags.clone()),
+                Abortable::new(
+                    body_recording.scope(self.inner.stream(
+                        input_vars,
+                        tx,
+                        previous_messages,
+                        tags.clone(),
+                    )),
+                    upstream_registration,
+                ),
                 async {
-                    while let Some(Some(msg)) = rx.recv().await {
+                    loop {
+                        let msg = tokio::select! {
+                            msg = rx.recv() => msg,
+                            // The client went away while the provider or a tool was busy
+                            _ = outer_tx.closed() => {
+                                upstream_handle.abort();
+                                break;
+                            }
+                        };
+                        let Some(Some(msg)) = msg else {
+                            break;
+                        };
                         match &msg.event {
                             ModelEventType::LlmStart(_event) => {
                                 start_time = Some(msg.timestamp.timestamp_micros() as u64);
@@ -658,7 +930,13 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
                             }
                             ModelEventType::LlmStop(llmfinish_event) => {
                                 let s = tracing::Span::current();
-                                s.record("output", serde_json::to_string(&output).unwrap());
+                                if let Some(response_id) = &llmfinish_event.response_id {
+                                    s.record("response_id", response_id.as_str());
+                                }
+                                s.record(
+                                    "output",
+                                    body_recording.apply(&serde_json::to_string(&output).unwrap()),
+                                );
                                 if let Some(u) = &llmfinish_event.usage {
                                     let cost = cost_calculator
                                         .calculate_cost(
@@ -670,7 +948,7 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
 
                                     match cost {
                                         Ok(c) => {
-                                            s.record("cost", serde_json::to_string(&c).unwrap());
+                                            record_cost(&s, c, &llmfinish_event.finish_reason);
                                         }
                                         Err(e) => {
                                             tracing::error!("Error calculating cost: {:?}", e);
@@ -681,28 +959,76 @@ impl<Inner: ModelInstance> ModelInstance for TracedModel<Inner> {
                             }
                             _ => {}
                         }
-                        outer_tx.send(Some(msg)).await.unwrap();
+                        // The client went away, stop reading from the provider
+                        if outer_tx.send(Some(msg)).await.is_err() {
+                            upstream_handle.abort();
+                            break;
+                        }
                     }
                 },
             )
             .instrument(span.clone())
             .await
-            .0;
+            .0
+            .unwrap_or_else(|_| {
+                Err(crate::GatewayError::CustomError(
+                    "Client disconnected".to_string(),
+                ))
+            });
             let span = tracing::Span::current();
             span.record(
                 "tags",
                 JsonValue(&serde_json::to_value(tags.clone())?).as_value(),
             );
             match result {
-                Ok(()) => span.record("output", output),
+                Ok(()) => span.record("output", body_recording.apply(&output)),
                 Err(ref e) => span.record("error", tracing::field::display(e)),
             };
+            self.report_rate_limit(&result);
+
+            if result.is_ok() {
+                let message = ChatCompletionMessage::new_text("assistant".to_string(), output);
+                let extra = self.extra.clone();
+                let executor_context = self.executor_context.clone();
+                let db_model = self.definition.db_model.clone();
+                tokio::spawn(
+                    async move {
+                        observe_output_guardrails(
+                            &message,
+                            extra.as_ref(),
+                            &executor_context,
+                            Some(db_model),
+                        )
+                        .await
+                    }
+                    .instrument(span),
+                );
+            }
             result
         }
         .await
     }
 }
 
+/// Records `cost` on a model call span, with the parts of its breakdown as separate fields
+fn record_cost(
+    span: &tracing::Span,
+    mut cost: CostCalculationResult,
+    finish_reason: &ModelFinishReason,
+) {
+    if *finish_reason == ModelFinishReason::ToolCalls {
+        cost.breakdown = cost.breakdown.map(|breakdown| breakdown.with_tool_calls());
+    }
+    if let Some(breakdown) = &cost.breakdown {
+        span.record("input_cost", breakdown.input_cost);
+        span.record("output_cost", breakdown.output_cost);
+        span.record("reasoning_cost", breakdown.reasoning_cost);
+        span.record("tool_calls_cost", breakdown.tool_calls_cost);
+        span.record("cache_savings", breakdown.cache_savings);
+    }
+    span.record("cost", serde_json::to_string(&cost).unwrap());
+}
+
 pub fn credentials_identifier(model_params: &CompletionModelParams) -> CredentialsIdent {
     let langdb_creds = match &model_params.engine {
         CompletionEngineParams::Bedrock { credentials, .. } => credentials.is_none(),
@@ -772,7 +1098,16 @@ pub async fn apply_guardrails(
             | GuardResult::Text { passed, .. }
                 if !passed =>
             {
-                return Err(GuardError::GuardNotPassed(guard_id.clone(), result));
+                let blocked_content = blocked_content(
+                    messages,
+                    &guard_stage,
+                    executor_context.return_blocked_output,
+                );
+                return Err(GuardError::GuardNotPassed(
+                    guard_id.clone(),
+                    result,
+                    blocked_content,
+                ));
             }
             _ => {}
         }
@@ -780,3 +1115,597 @@ pub async fn apply_guardrails(
 
     Ok(())
 }
+
+/// Name of the custom event reporting the result of an observe guard
+pub const GUARD_RESULT_EVENT: &str = "guard_result";
+
+/// Name of the custom event reporting a provider rate limit
+pub const RATE_LIMITED_EVENT: &str = "rate_limited";
+
+/// Runs the observe guards of a streamed completion on its assembled output. The stream has
+/// already been delivered, so results are only reported through the callback handler.
+pub async fn observe_output_guardrails(
+    message: &ChatCompletionMessage,
+    extra: Option<&Extra>,
+    executor_context: &ExecutorContext,
+    db_model: Option<Model>,
+) {
+    let Some(Extra { guards, .. }) = extra else {
+        return;
+    };
+
+    let evaluator = executor_context.evaluator_service.as_ref().as_ref();
+    for guard in guards {
+        let (guard_id, parameters) = match guard {
+            GuardOrName::GuardId(guard_id) => (guard_id, None),
+            GuardOrName::GuardWithParameters(GuardWithParameters { id, parameters }) => {
+                (id, Some(parameters))
+            }
+        };
+
+        let result = evaluator
+            .observe(
+                std::slice::from_ref(message),
+                guard_id,
+                executor_context,
+                parameters,
+                &GuardStage::Output,
+            )
+            .await;
+
+        match result {
+            Ok(Some(result)) => {
+                let event = CustomEvent::new(
+                    GUARD_RESULT_EVENT.to_string(),
+                    json!({"guard_id": guard_id, "result": result}),
+                );
+                let event =
+                    ModelEvent::new(&tracing::Span::current(), ModelEventType::Custom(event));
+                executor_context
+                    .callbackhandler
+                    .on_message(ModelEventWithDetails::new(event, db_model.clone()));
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Observe guard {guard_id} failed: {e}"),
+        }
+    }
+}
+
+/// Text of output messages a guard blocked, if it may be returned to the client
+fn blocked_content(
+    messages: &[ChatCompletionMessage],
+    guard_stage: &GuardStage,
+    return_blocked_output: bool,
+) -> Option<String> {
+    if !return_blocked_output || guard_stage != &GuardStage::Output {
+        return None;
+    }
+
+    let content = messages
+        .iter()
+        .filter_map(|message| message.content.as_ref())
+        .map(|content| match content {
+            ChatCompletionContent::Text(text) => text.clone(),
+            ChatCompletionContent::Content(parts) => parts
+                .iter()
+                .filter_map(|part| part.text.clone())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::bodies::{BodyRecording, BodyRecordingMode};
+    use crate::handler::{AvailableModels, CallbackHandlerFn};
+    use crate::test_utils::{mock_server, SpanFields};
+    use crate::types::engine::OpenAiModelParams;
+    use crate::types::gateway::{CostCalculationResult, CostCalculator, CostCalculatorError};
+    use crate::types::message::MessageType;
+    use crate::types::threads::MessageContentType;
+    use actix_web::HttpRequest;
+    use std::sync::Arc;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_tool_call_iterations_unlimited_by_default() {
+        let mut iterations = ToolCallIterations::new(&ExecutionOptions::default());
+        for _ in 0..1000 {
+            assert!(iterations.next_round(|| None).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_tool_call_iterations_reached() {
+        let mut iterations = ToolCallIterations::new(&ExecutionOptions {
+            max_tool_call_iterations: Some(2),
+            ..Default::default()
+        });
+        assert!(iterations.next_round(|| None).is_ok());
+        assert!(iterations.next_round(|| None).is_ok());
+
+        let error = iterations
+            .next_round(|| Some("Partial".to_string()))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Max tool call iterations (2) reached, partial result: Partial"
+        );
+        assert!(ToolCallIterations::reached(&error.into()));
+        assert!(!ToolCallIterations::reached(
+            &ModelError::Timeout(30).into()
+        ));
+    }
+
+    fn traced_openai_model(api_key: &str) -> TraceModelDefinition {
+        TraceModelDefinition {
+            name: "gpt-4o-mini".to_string(),
+            provider_name: "openai".to_string(),
+            engine_name: "openai".to_string(),
+            prompt_name: None,
+            model_params: CompletionModelParams {
+                engine: CompletionEngineParams::OpenAi {
+                    params: Default::default(),
+                    execution_options: ExecutionOptions::default(),
+                    credentials: Some(crate::types::credentials::ApiKeyCredentials {
+                        api_key: api_key.to_string(),
+                    }),
+                    endpoint: None,
+                },
+                provider_name: "openai".to_string(),
+                prompt_name: None,
+            },
+            model_name: "gpt-4o-mini".to_string(),
+            tools: ModelTools::default(),
+            model_type: ModelType::Completions,
+        }
+    }
+
+    #[test]
+    fn test_sanitize_json_strips_credentials() {
+        let model = traced_openai_model("sk-secret-key");
+        let json = model.sanitize_json(false).unwrap();
+        let serialized = json.to_string();
+
+        assert!(!serialized.contains("sk-secret-key"));
+        assert!(json.get("credentials_fingerprint").is_none());
+        assert!(json["model_params"]["engine"]["OpenAi"]["credentials"].is_null());
+    }
+
+    #[test]
+    fn test_sanitize_json_adds_only_fingerprint() {
+        let model = traced_openai_model("sk-secret-key");
+        let json = model.sanitize_json(true).unwrap();
+        let serialized = json.to_string();
+
+        assert!(!serialized.contains("sk-secret-key"));
+        assert!(json["model_params"]["engine"]["OpenAi"]["credentials"].is_null());
+        assert_eq!(
+            json["credentials_fingerprint"],
+            crate::types::credentials::credential_fingerprint("sk-secret-key")
+        );
+
+        // Without credentials there is nothing to fingerprint
+        let mut model = model;
+        if let CompletionEngineParams::OpenAi { credentials, .. } = &mut model.model_params.engine {
+            credentials.take();
+        }
+        let json = model.sanitize_json(true).unwrap();
+        assert!(json.get("credentials_fingerprint").is_none());
+    }
+
+    #[test]
+    fn test_blocked_content() {
+        let messages = [ChatCompletionMessage::new_text(
+            "assistant".to_string(),
+            "Try Competitor A instead".to_string(),
+        )];
+
+        assert_eq!(
+            blocked_content(&messages, &GuardStage::Output, true).as_deref(),
+            Some("Try Competitor A instead")
+        );
+        assert!(blocked_content(&messages, &GuardStage::Output, false).is_none());
+        // Input is the client's own content and is never echoed back
+        assert!(blocked_content(&messages, &GuardStage::Input, true).is_none());
+    }
+
+    /// Model returning an empty completion on its first `empty_calls` calls
+    struct FlakyModel {
+        empty_calls: usize,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ModelInstance for FlakyModel {
+        async fn invoke(
+            &self,
+            _input_vars: HashMap<String, Value>,
+            tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<ChatCompletionMessage> {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let content = if call < self.empty_calls { "" } else { "Paris" };
+            let finish = types::LLMFinishEvent {
+                provider_name: "openai".to_string(),
+                model_name: "gpt-4o-mini".to_string(),
+                output: Some(content.to_string()),
+                usage: Some(crate::types::gateway::CompletionModelUsage {
+                    input_tokens: 9,
+                    output_tokens: 1,
+                    total_tokens: 10,
+                    ..Default::default()
+                }),
+                finish_reason: ModelFinishReason::Stop,
+                tool_calls: vec![],
+                credentials_ident: CredentialsIdent::Own,
+                logprobs: None,
+                service_tier: None,
+                response_id: None,
+            };
+            let event = ModelEvent::new(&tracing::Span::none(), ModelEventType::LlmStop(finish));
+            tx.send(Some(event)).await.unwrap();
+            Ok(ChatCompletionMessage::new_text(
+                "assistant".to_string(),
+                content.to_string(),
+            ))
+        }
+
+        async fn stream(
+            &self,
+            _input_vars: HashMap<String, Value>,
+            _tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<()> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_completion_is_retried() {
+        let model = FlakyModel {
+            empty_calls: 1,
+            calls: Default::default(),
+        };
+        let (tx, _rx) = channel(16);
+        let (result, retries) =
+            invoke_retrying_empty(&model, 2, HashMap::new(), tx, vec![], HashMap::new()).await;
+
+        assert_eq!(
+            result.unwrap().content,
+            Some(ChatCompletionContent::Text("Paris".to_string()))
+        );
+        assert_eq!(retries, 1);
+
+        // Without retries left the empty completion is returned
+        let model = FlakyModel {
+            empty_calls: 5,
+            calls: Default::default(),
+        };
+        let (tx, _rx) = channel(16);
+        let (result, retries) =
+            invoke_retrying_empty(&model, 2, HashMap::new(), tx, vec![], HashMap::new()).await;
+
+        assert!(is_empty_completion(&result.unwrap()));
+        assert_eq!(retries, 2);
+        assert_eq!(model.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_usage_of_retried_calls_is_not_reported() {
+        let mut model = traced_model(
+            FlakyModel {
+                empty_calls: 2,
+                calls: Default::default(),
+            },
+            actix_web::test::TestRequest::default().to_http_request(),
+        );
+        if let CompletionEngineParams::OpenAi {
+            execution_options, ..
+        } = &mut model.definition.model_params.engine
+        {
+            execution_options.empty_completion_retries = Some(2);
+        }
+
+        let (tx, mut rx) = channel(16);
+        let response = model
+            .invoke(HashMap::new(), tx, vec![], HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.content,
+            Some(ChatCompletionContent::Text("Paris".to_string()))
+        );
+        assert_eq!(
+            model.inner.calls.load(std::sync::atomic::Ordering::SeqCst),
+            3
+        );
+
+        let mut finished = vec![];
+        while let Some(Some(event)) = rx.recv().await {
+            if let ModelEventType::LlmStop(finish) = event.event {
+                finished.push(finish);
+            }
+        }
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].output.as_deref(), Some("Paris"));
+        assert_eq!(finished[0].usage.as_ref().unwrap().total_tokens, 10);
+    }
+
+    /// Streams content until its stream is dropped, ignoring a closed channel like
+    /// provider streams reading on regardless would
+    struct EndlessModel {
+        dropped: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    /// Sets its flag when the future holding it is dropped
+    struct DropFlag(Arc<std::sync::atomic::AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl ModelInstance for EndlessModel {
+        async fn invoke(
+            &self,
+            _input_vars: HashMap<String, Value>,
+            _tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<ChatCompletionMessage> {
+            unimplemented!()
+        }
+
+        async fn stream(
+            &self,
+            _input_vars: HashMap<String, Value>,
+            tx: mpsc::Sender<Option<ModelEvent>>,
+            _previous_messages: Vec<Message>,
+            _tags: HashMap<String, String>,
+        ) -> GatewayResult<()> {
+            let _flag = DropFlag(self.dropped.clone());
+            loop {
+                let event = ModelEvent::new(
+                    &tracing::Span::none(),
+                    ModelEventType::LlmContent(types::LLMContentEvent {
+                        content: "word ".to_string(),
+                        index: 0,
+                    }),
+                );
+                let _ = tx.send(Some(event)).await;
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+        }
+    }
+
+    /// `inner` traced as `openai/gpt-4o-mini` for `req`
+    fn traced_model<Inner: ModelInstance>(inner: Inner, req: HttpRequest) -> TracedModel<Inner> {
+        TracedModel {
+            inner,
+            definition: CompletionModelDefinition {
+                name: "openai/gpt-4o-mini".to_string(),
+                model_params: traced_openai_model("sk-test").model_params,
+                prompt: crate::types::engine::Prompt::empty(),
+                tools: ModelTools::default(),
+                db_model: Model {
+                    name: "openai/gpt-4o-mini".to_string(),
+                    description: None,
+                    provider_name: "openai".to_string(),
+                    prompt_name: None,
+                    model_params: HashMap::new(),
+                    tools: ModelTools::default(),
+                    model_type: ModelType::Completions,
+                    response_schema: None,
+                    credentials: None,
+                },
+            },
+            executor_context: ExecutorContext::new(
+                CallbackHandlerFn(None),
+                Arc::new(Box::new(NoCost)),
+                AvailableModels(vec![]),
+                &req,
+                Arc::new(Box::new(ToneObserver)),
+            )
+            .unwrap(),
+            router_span: tracing::Span::none(),
+            extra: None,
+            initial_messages: vec![],
+            response_cache_state: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_when_client_disconnects() {
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let model = traced_model(
+            EndlessModel {
+                dropped: dropped.clone(),
+            },
+            actix_web::test::TestRequest::default().to_http_request(),
+        );
+
+        let (tx, mut rx) = channel(4);
+        let handle = tokio::spawn(async move {
+            model
+                .stream(HashMap::new(), tx, vec![], HashMap::new())
+                .await
+        });
+        rx.recv().await.unwrap().unwrap();
+        rx.recv().await.unwrap().unwrap();
+        drop(rx);
+
+        let result = tokio::time::timeout(std::time::Duration::from_secs(1), handle)
+            .await
+            .expect("stream kept running after the client disconnected")
+            .expect("stream panicked");
+        assert!(result.is_err());
+        assert!(dropped.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_bodies_are_not_recorded_when_off() {
+        const PROMPT: &str = "What is the capital of France?";
+        let spans = SpanFields::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let body = json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Paris"},
+                "finish_reason": "stop"
+            }]
+        });
+        let url = mock_server("application/json", body.to_string()).await;
+        let inner = OpenAIModel::new(
+            OpenAiModelParams {
+                model: Some("gpt-4o-mini".to_string()),
+                ..Default::default()
+            },
+            Some(&crate::types::credentials::ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            ExecutionOptions::default(),
+            crate::types::engine::Prompt::new("test".to_string(), "You are helpful".to_string()),
+            HashMap::new(),
+            None,
+            Some(&url),
+        )
+        .unwrap();
+        let req = actix_web::test::TestRequest::default()
+            .app_data(BodyRecording {
+                mode: BodyRecordingMode::Off,
+                sample_rate: 1.0,
+            })
+            .to_http_request();
+        let message = Message {
+            model_name: "openai/gpt-4o-mini".to_string(),
+            thread_id: None,
+            user_id: "user".to_string(),
+            content_type: MessageContentType::Text,
+            content: Some(PROMPT.to_string()),
+            content_array: vec![],
+            r#type: MessageType::HumanMessage,
+            tool_call_id: None,
+            tool_calls: None,
+        };
+
+        let (tx, _rx) = channel(16);
+        let response = traced_model(inner, req)
+            .invoke(
+                HashMap::from([("question".to_string(), json!(PROMPT))]),
+                tx,
+                vec![message],
+                HashMap::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.content,
+            Some(ChatCompletionContent::Text("Paris".to_string()))
+        );
+
+        let spans = spans.all();
+        assert!(!spans.is_empty());
+        for fields in spans {
+            assert!(!fields.contains_key("input") && !fields.contains_key("output"));
+            assert!(
+                fields
+                    .values()
+                    .all(|v| !v.contains("France") && !v.contains("Paris")),
+                "Body recorded on {fields:?}"
+            );
+        }
+    }
+
+    struct NoCost;
+
+    #[async_trait]
+    impl CostCalculator for NoCost {
+        async fn calculate_cost(
+            &self,
+            _model_name: &str,
+            _provider_name: &str,
+            _usage: &Usage,
+        ) -> Result<CostCalculationResult, CostCalculatorError> {
+            Err(CostCalculatorError::ModelNotFound)
+        }
+    }
+
+    /// Observes `tone` as failing, and nothing else
+    struct ToneObserver;
+
+    #[async_trait]
+    impl GuardrailsEvaluator for ToneObserver {
+        async fn evaluate(
+            &self,
+            _messages: &[ChatCompletionMessage],
+            _guard_id: &str,
+            _executor_context: &ExecutorContext,
+            _parameters: Option<&Value>,
+            _guard_stage: &GuardStage,
+        ) -> Result<GuardResult, String> {
+            unimplemented!()
+        }
+
+        async fn observe(
+            &self,
+            messages: &[ChatCompletionMessage],
+            guard_id: &str,
+            _executor_context: &ExecutorContext,
+            _parameters: Option<&Value>,
+            guard_stage: &GuardStage,
+        ) -> Result<Option<GuardResult>, String> {
+            assert_eq!(guard_stage, &GuardStage::Output);
+            assert_eq!(
+                messages[0].content,
+                Some(ChatCompletionContent::Text("You are wrong".to_string()))
+            );
+            Ok((guard_id == "tone").then_some(GuardResult::Boolean {
+                passed: false,
+                confidence: None,
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observe_guard_result_is_reported_after_stream() {
+        let (events_tx, mut events_rx) = tokio::sync::broadcast::channel(16);
+        let executor_context = ExecutorContext::new(
+            CallbackHandlerFn(Some(events_tx)),
+            Arc::new(Box::new(NoCost)),
+            AvailableModels(vec![]),
+            &actix_web::test::TestRequest::default().to_http_request(),
+            Arc::new(Box::new(ToneObserver)),
+        )
+        .unwrap();
+        let extra: Extra = serde_json::from_value(json!({"guards": ["tone", "pii"]})).unwrap();
+        let output =
+            ChatCompletionMessage::new_text("assistant".to_string(), "You are wrong".to_string());
+
+        observe_output_guardrails(&output, Some(&extra), &executor_context, None).await;
+
+        let ModelEventType::Custom(event) = events_rx.recv().await.unwrap().event.event else {
+            panic!("Expected a custom event");
+        };
+        assert_eq!(event.name(), GUARD_RESULT_EVENT);
+        assert_eq!(
+            event.value(),
+            json!({"guard_id": "tone", "result": {"type": "boolean", "passed": false}})
+        );
+        // `pii` isn't an observe guard
+        assert!(events_rx.try_recv().is_err());
+    }
+}
@@ -1,6 +1,7 @@
+use crate::events::bodies::BodyRecordingMode;
 use crate::model::error::ModelError;
 use crate::model::types::{LLMFirstToken, ModelEvent, ModelEventType};
-use crate::model::ModelInstance;
+use crate::model::{http_client, ModelInstance};
 use crate::types::credentials::ApiKeyCredentials;
 use crate::types::engine::ExecutionOptions;
 use crate::types::engine::{OllamaModelParams, OllamaResponseFormat};
@@ -46,18 +47,18 @@ impl OllamaModel {
         execution_options: ExecutionOptions,
         credentials: Option<ApiKeyCredentials>,
         endpoint: Option<String>,
-    ) -> Self {
-        let client = Client::new();
+    ) -> Result<Self, ModelError> {
+        let client = http_client(&execution_options)?;
 
         tracing::debug!(target: "ollama_debug", "[OllamaModel::new] endpoint = {:?}", endpoint);
 
-        Self {
+        Ok(Self {
             client,
             credentials,
             execution_options,
             params,
             endpoint,
-        }
+        })
     }
 
     // Add a helper method to validate model name
@@ -122,6 +123,7 @@ impl OllamaModel {
                 &span,
                 ModelEventType::LlmContent(crate::model::types::LLMContentEvent {
                     content: error_msg.clone(),
+                    index: 0,
                 })
             ))); 
 
@@ -171,6 +173,7 @@ impl OllamaModel {
                 prompt_tokens_details: None,
                 completion_tokens_details: None,
                 is_cache_used: false,
+                reasoning_tokens: 0,
             })
         });
 
@@ -201,6 +204,7 @@ impl OllamaModel {
             prompt_tokens_details: None,
             completion_tokens_details: None,
             is_cache_used: false,
+            reasoning_tokens: 0,
         })
     }
 
@@ -292,10 +296,14 @@ impl OllamaModel {
 
     fn build_embedding_request(&self, input: &str, model_name: &str) -> serde_json::Value {
         // Format messages for OpenAI compatible Ollama API format
-        json!({
+        let mut request = json!({
             "input": input,
             "model": model_name,
-        })
+        });
+        if let Some(dimensions) = self.params.dimensions {
+            request["dimensions"] = json!(dimensions);
+        }
+        request
     }
 }
 
@@ -327,12 +335,13 @@ impl ModelInstance for OllamaModel {
         
         // Create a span specifically for this request - using target! pattern from openai.rs
         let input = serde_json::to_string(&messages).unwrap_or_default();
+        let recorded_input = BodyRecordingMode::current().apply(&input);
         let span = tracing::info_span!(
             target: target!("chat"),
             "model_call",
             provider = "ollama",
             model = model_name,
-            input = input,
+            input = recorded_input.as_deref(),
             output = field::Empty,
             error = field::Empty,
             usage = field::Empty,
@@ -368,7 +377,7 @@ impl ModelInstance for OllamaModel {
         
         // Record the response in the span
         let output_str = serde_json::to_string(&message).unwrap_or_default();
-        span.record("output", &output_str);
+        span.record("output", BodyRecordingMode::current().apply(&output_str));
         
         // 优先用 response usage 字段，没有则 fallback 到估算
         let usage = if let Some(u) = usage_from_response {
@@ -409,6 +418,9 @@ impl ModelInstance for OllamaModel {
                 finish_reason: crate::model::types::ModelFinishReason::Stop,
                 tool_calls: vec![],
                 credentials_ident,
+                logprobs: None,
+                service_tier: None,
+                response_id: None,
             })
         )));
 
@@ -424,12 +436,13 @@ impl ModelInstance for OllamaModel {
     ) -> GatewayResult<()> {
         let model_name = self.validate_model()?;
         let input = serde_json::to_string(&previous_messages).unwrap_or_default();
+        let recorded_input = BodyRecordingMode::current().apply(&input);
         let span = tracing::info_span!(
             target: target!("chat_stream"),
             "model_call_stream",
             provider = "ollama",
             model = model_name.clone(),
-            input = input,
+            input = recorded_input.as_deref(),
             error = field::Empty,
             tags = crate::events::JsonValue(&serde_json::to_value(tags.clone()).unwrap_or_default()).as_value()
         );
@@ -496,6 +509,9 @@ impl ModelInstance for OllamaModel {
                         finish_reason: crate::model::types::ModelFinishReason::ContentFilter,
                         tool_calls: vec![],
                         credentials_ident,
+                        logprobs: None,
+                        service_tier: None,
+                        response_id: None,
                     }),
                 )));
 
@@ -548,6 +564,7 @@ impl ModelInstance for OllamaModel {
                                                 ModelEventType::LlmContent(
                                                     crate::model::types::LLMContentEvent {
                                                         content: content.to_string(),
+                                                        index: 0,
                                                     },
                                                 ),
                                             )));
@@ -602,6 +619,9 @@ impl ModelInstance for OllamaModel {
                     finish_reason,
                     tool_calls: vec![],
                     credentials_ident,
+                    logprobs: None,
+                    service_tier: None,
+                    response_id: None,
                 }),
             )));
 
@@ -626,12 +646,13 @@ impl ModelInstance for OllamaModel {
                 ));
             }
         };
+        let recorded_input = BodyRecordingMode::current().apply(&input_str);
         let span = tracing::info_span!(
             target: target!("embed"),
             "model_embed",
             provider = "ollama",
             model = model_name,
-            input = input_str,
+            input = recorded_input.as_deref(),
             output = field::Empty,
             error = field::Empty,
         );
@@ -684,7 +705,7 @@ impl ModelInstance for OllamaModel {
         };
         // 8. 记录 output
         let output_str = serde_json::to_string(&result).unwrap_or_default();
-        span.record("output", &output_str);
+        span.record("output", BodyRecordingMode::current().apply(&output_str));
         Ok(result)
     }
 }
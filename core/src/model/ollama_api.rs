@@ -1,6 +1,7 @@
+use crate::events::bodies::BodyRecordingMode;
 use crate::model::error::ModelError;
 use crate::model::types::{ModelEvent, ModelEventType};
-use crate::model::ModelInstance;
+use crate::model::{http_client, ModelInstance};
 use crate::types::credentials::ApiKeyCredentials;
 use crate::types::engine::ExecutionOptions;
 use crate::types::engine::{OllamaModelParams, OllamaResponseFormat};
@@ -71,18 +72,18 @@ impl OllamaApiModel {
         execution_options: ExecutionOptions,
         credentials: Option<ApiKeyCredentials>,
         endpoint: Option<String>,
-    ) -> Self {
-        let client = Client::new();
+    ) -> Result<Self, ModelError> {
+        let client = http_client(&execution_options)?;
 
         tracing::debug!(target: "ollama_api_debug", "[OllamaApiModel::new] endpoint = {:?}", endpoint);
 
-        Self {
+        Ok(Self {
             client,
             credentials,
             execution_options,
             params,
             endpoint,
-        }
+        })
     }
     
     // Add a helper method to validate model name
@@ -147,6 +148,7 @@ impl OllamaApiModel {
                 &span,
                 ModelEventType::LlmContent(crate::model::types::LLMContentEvent {
                     content: error_msg.clone(),
+                    index: 0,
                 })
             ))).await
                 .map_err(|e| ModelError::CustomError(e.to_string()))?;
@@ -189,6 +191,7 @@ impl OllamaApiModel {
             prompt_tokens_details: None,
             completion_tokens_details: None,
             is_cache_used: false,
+            reasoning_tokens: 0,
         })
     }
 
@@ -445,12 +448,13 @@ impl ModelInstance for OllamaApiModel {
         
         // Create a span specifically for this request
         let input = serde_json::to_string(&messages).unwrap_or_default();
+        let recorded_input = BodyRecordingMode::current().apply(&input);
         let span = tracing::info_span!(
             target: target!("chat"),
             "model_call",
             provider = "ollama_api",
             model = model_name,
-            input = input,
+            input = recorded_input.as_deref(),
             output = field::Empty,
             error = field::Empty,
             usage = field::Empty,
@@ -492,7 +496,7 @@ impl ModelInstance for OllamaApiModel {
         
         // Record the response in the span
         let output_str = serde_json::to_string(&message).unwrap_or_default();
-        span.record("output", &output_str);
+        span.record("output", BodyRecordingMode::current().apply(&output_str));
         
         // Extract token counts from the response if available
         let api_response = serde_json::from_value::<OllamaApiResponse>(response.clone())
@@ -528,6 +532,9 @@ impl ModelInstance for OllamaApiModel {
                 finish_reason: crate::model::types::ModelFinishReason::Stop,
                 tool_calls: vec![],
                 credentials_ident,
+                logprobs: None,
+                service_tier: None,
+                response_id: None,
             })
         ))).await
             .map_err(|e| crate::error::GatewayError::CustomError(e.to_string()))?;
@@ -547,12 +554,13 @@ impl ModelInstance for OllamaApiModel {
         
         // Create a span specifically for this stream request
         let input = serde_json::to_string(&previous_messages).unwrap_or_default();
+        let recorded_input = BodyRecordingMode::current().apply(&input);
         let span = tracing::info_span!(
             target: target!("chat_stream"),
             "model_call_stream",
             provider = "ollama_api",
             model = model_name,
-            input = input,
+            input = recorded_input.as_deref(),
             error = field::Empty,
             tags = crate::events::JsonValue(&serde_json::to_value(tags.clone()).unwrap_or_default()).as_value()
         );
@@ -587,6 +595,9 @@ impl ModelInstance for OllamaApiModel {
                 } else {
                     crate::model::CredentialsIdent::Own
                 },
+                logprobs: None,
+                service_tier: None,
+                response_id: None,
             })
         ))).await
             .map_err(|e| crate::error::GatewayError::CustomError(e.to_string()))?;
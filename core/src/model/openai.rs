@@ -5,23 +5,36 @@ use super::types::{
     ModelToolCall,
 };
 use super::{CredentialsIdent, ModelInstance};
-use crate::error::GatewayError;
+use crate::error::{ErrorClass, GatewayError};
+use crate::events;
+use crate::events::bodies::BodyRecordingMode;
 use crate::events::JsonValue;
 use crate::events::SPAN_OPENAI;
-use crate::events::{self, RecordResult};
 use crate::model::handler::handle_tool_call;
+use crate::model::rate_limits::upstream_rate_limits;
 use crate::model::types::LLMFirstToken;
-use crate::model::{async_trait, DEFAULT_MAX_RETRIES};
+use crate::model::{
+    async_trait, http_client, wait_retry_backoff, with_timeout, DEFAULT_MAX_RETRIES,
+};
 use crate::types::credentials::ApiKeyCredentials;
 use crate::types::engine::{ExecutionOptions, OpenAiModelParams, Prompt};
-use crate::types::gateway::CompletionModelUsage;
-use crate::types::gateway::{ChatCompletionContent, ChatCompletionMessage, ToolCall};
+use crate::types::gateway::{
+    ChatCompletionContent, ChatCompletionLogprobs, ChatCompletionMessage,
+    ChatCompletionTokenLogprob, ChatCompletionTopLogprob, Content, ContentType, InputAudio,
+    ToolCall,
+};
+use crate::types::gateway::{CompletionModelUsage, CompletionTokensDetails};
 use crate::types::message::{MessageType, PromptMessage};
 use crate::types::threads::{InnerMessage, Message};
 use crate::GatewayResult;
 use async_openai::config::Config;
 use async_openai::config::{AzureConfig, OpenAIConfig};
 use async_openai::error::OpenAIError;
+use async_openai::types::ServiceTierResponse;
+use async_openai::types::{
+    ChatChoiceLogprobs, ChatCompletionRequestToolMessageArgs, CompletionUsage,
+};
+use async_openai::types::{ChatCompletionAudio, ChatCompletionResponseMessageAudio};
 use async_openai::types::{
     ChatCompletionMessageToolCall, ChatCompletionMessageToolCallChunk,
     ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
@@ -35,12 +48,13 @@ use async_openai::types::{
 use async_openai::types::{
     ChatCompletionRequestMessageContentPartImage, CreateChatCompletionStreamResponse, ImageUrl,
 };
-use async_openai::types::{ChatCompletionRequestToolMessageArgs, CompletionUsage};
 use async_openai::types::{ChatCompletionRequestUserMessageContent, ChatCompletionStreamOptions};
+use async_openai::types::{ChatCompletionResponseStream, CreateChatCompletionResponse};
 use async_openai::Client;
 use futures::Stream;
 use futures::StreamExt;
-use serde_json::Value;
+use reqwest_eventsource::{Event, EventSource};
+use serde_json::{Map, Value};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tracing::field;
@@ -66,6 +80,54 @@ fn custom_err(e: impl ToString) -> ModelError {
     ModelError::CustomError(e.to_string())
 }
 
+/// Rate limit error if OpenAI answered `e` with a 429. async-openai doesn't expose the
+/// response status, so it is recognised by the error code or the stream's status line.
+fn rate_limit_err(e: &OpenAIError) -> Option<GatewayError> {
+    let rate_limited = match e {
+        OpenAIError::ApiError(api_error) => {
+            api_error.code.as_deref() == Some("rate_limit_exceeded")
+        }
+        OpenAIError::StreamError(message) => message.contains("429 Too Many Requests"),
+        _ => false,
+    };
+    rate_limited.then(|| GatewayError::RateLimited {
+        provider: SPAN_OPENAI.to_string(),
+        retry_after: None,
+    })
+}
+
+/// Classified error for a failed OpenAI response, if its kind can be told from `e`
+fn provider_err(e: &OpenAIError) -> Option<GatewayError> {
+    if let Some(rate_limited) = rate_limit_err(e) {
+        return Some(rate_limited);
+    }
+    let class = match e {
+        OpenAIError::ApiError(api_error) => match api_error.r#type.as_deref() {
+            Some("server_error") => ErrorClass::ServerError,
+            Some(
+                "invalid_request_error"
+                | "authentication_error"
+                | "permission_error"
+                | "not_found_error"
+                | "insufficient_quota",
+            ) => ErrorClass::ClientError,
+            _ => return None,
+        },
+        OpenAIError::StreamError(message) => {
+            let status = message.split("Invalid status code: ").nth(1)?;
+            ErrorClass::from_status(status.get(..3)?.parse().ok()?)
+        }
+        OpenAIError::Reqwest(e) if e.is_timeout() => ErrorClass::Timeout,
+        OpenAIError::Reqwest(e) if e.is_connect() => ErrorClass::ServerError,
+        _ => return None,
+    };
+    Some(GatewayError::ProviderError {
+        provider: SPAN_OPENAI.to_string(),
+        class,
+        message: e.to_string(),
+    })
+}
+
 /// Parse an Azure OpenAI URL into AzureConfig
 /// Format: https://{resource-name}.openai.azure.com/openai/deployments/{deployment-id}/chat/completions?api-version={api-version}
 fn parse_azure_url(endpoint: &str, api_key: String) -> Result<AzureConfig, ModelError> {
@@ -115,6 +177,7 @@ pub fn is_azure_endpoint(endpoint: &str) -> bool {
 pub fn openai_client(
     credentials: Option<&ApiKeyCredentials>,
     endpoint: Option<&str>,
+    execution_options: &ExecutionOptions,
 ) -> Result<Client<OpenAIConfig>, ModelError> {
     let api_key = if let Some(credentials) = credentials {
         credentials.api_key.clone()
@@ -137,16 +200,17 @@ pub fn openai_client(
         config = config.with_api_base(endpoint);
     }
 
-    Ok(Client::with_config(config))
+    Ok(Client::with_config(config).with_http_client(http_client(execution_options)?))
 }
 
 /// Create an Azure OpenAI client from endpoint URL
 pub fn azure_openai_client(
     api_key: String,
     endpoint: &str,
+    execution_options: &ExecutionOptions,
 ) -> Result<Client<AzureConfig>, ModelError> {
     let azure_config = parse_azure_url(endpoint, api_key)?;
-    Ok(Client::with_config(azure_config))
+    Ok(Client::with_config(azure_config).with_http_client(http_client(execution_options)?))
 }
 
 #[derive(Clone)]
@@ -179,7 +243,7 @@ impl OpenAIModel<OpenAIConfig> {
             }
         }
 
-        let client = client.unwrap_or(openai_client(credentials, endpoint)?);
+        let client = client.unwrap_or(openai_client(credentials, endpoint, &execution_options)?);
 
         Ok(Self {
             params,
@@ -214,7 +278,7 @@ impl OpenAIModel<AzureConfig> {
                 std::env::var("LANGDB_OPENAI_API_KEY")
                     .map_err(|_| AuthorizationError::InvalidApiKey)?
             };
-            azure_openai_client(api_key, endpoint)?
+            azure_openai_client(api_key, endpoint, &execution_options)?
         } else {
             return Err(ModelError::CustomError(
                 "Azure OpenAI requires an endpoint URL".to_string(),
@@ -359,6 +423,27 @@ impl<C: Config> OpenAIModel<C> {
             builder.response_format(schema.clone());
         }
 
+        if let Some(service_tier) = &model_params.service_tier {
+            builder.service_tier(service_tier.clone());
+        }
+
+        if let Some(modalities) = &model_params.modalities {
+            builder.modalities(modalities.clone());
+        }
+
+        if let Some(audio) = &model_params.audio {
+            builder.audio(audio.clone());
+        }
+
+        if let Some(prediction) = &model_params.prediction {
+            builder.prediction(prediction.clone());
+        }
+
+        // Non-streaming responses only surface the first choice
+        if let (true, Some(n)) = (stream, model_params.n) {
+            builder.n(n.min(u8::MAX as u32) as u8);
+        }
+
         builder
             .model(model_params.model.as_ref().unwrap())
             .messages(messages)
@@ -367,6 +452,9 @@ impl<C: Config> OpenAIModel<C> {
             builder
                 .tools(chat_completion_tools)
                 .tool_choice(ChatCompletionToolChoiceOption::Auto);
+            if let Some(parallel_tool_calls) = model_params.parallel_tool_calls {
+                builder.parallel_tool_calls(parallel_tool_calls);
+            }
         }
 
         if stream {
@@ -378,6 +466,52 @@ impl<C: Config> OpenAIModel<C> {
         Ok(builder.build().map_err(custom_err)?)
     }
 
+    /// Chat completions request with `extra_body` merged over the typed fields
+    fn request_with_extra_body(
+        &self,
+        request: CreateChatCompletionRequest,
+        extra_body: &Map<String, Value>,
+    ) -> Result<reqwest::RequestBuilder, ModelError> {
+        let mut body = serde_json::to_value(request).map_err(custom_err)?;
+        if let Value::Object(fields) = &mut body {
+            fields.extend(extra_body.clone());
+        }
+
+        let config = self.client.config();
+        Ok(http_client(&self.execution_options)?
+            .post(config.url("/chat/completions"))
+            .query(&config.query())
+            .headers(config.headers())
+            .json(&body))
+    }
+
+    async fn create_with_extra_body(
+        &self,
+        request: CreateChatCompletionRequest,
+        extra_body: &Map<String, Value>,
+    ) -> GatewayResult<CreateChatCompletionResponse> {
+        let response = self
+            .request_with_extra_body(request, extra_body)?
+            .send()
+            .await
+            .map_err(custom_err)?;
+        upstream_rate_limits().record(SPAN_OPENAI, response.headers());
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(GatewayError::rate_limited(SPAN_OPENAI, response.headers()));
+        }
+        let body = response.text().await.map_err(custom_err)?;
+        if !status.is_success() {
+            return Err(GatewayError::provider_status(
+                SPAN_OPENAI,
+                status.as_u16(),
+                format!("{status}: {body}"),
+            ));
+        }
+
+        Ok(serde_json::from_str(&body).map_err(custom_err)?)
+    }
+
     async fn process_stream(
         &self,
         mut stream: impl Stream<Item = Result<CreateChatCompletionStreamResponse, OpenAIError>> + Unpin,
@@ -387,8 +521,11 @@ impl<C: Config> OpenAIModel<C> {
         FinishReason,
         Vec<ChatCompletionMessageToolCall>,
         Option<async_openai::types::CompletionUsage>,
+        String,
     )> {
         let mut tool_call_states: HashMap<u32, ChatCompletionMessageToolCall> = HashMap::new();
+        let choice_count = self.params.n.unwrap_or(1).max(1) as usize;
+        let mut finish_reasons: HashMap<u32, FinishReason> = HashMap::new();
         while let Some(result) = stream.next().await {
             match result {
                 Ok(mut response) => {
@@ -401,49 +538,56 @@ impl<C: Config> OpenAIModel<C> {
                         .await
                         .map_err(|e| GatewayError::CustomError(e.to_string()))?;
                     }
-                    if response.choices.is_empty() {
-                        continue;
-                    }
-                    let chat_choice = response.choices.remove(0);
-                    if let Some(tool_calls) = chat_choice.delta.tool_calls {
-                        for tool_call in tool_calls.into_iter() {
-                            let ChatCompletionMessageToolCallChunk {
-                                index,
-                                id,
-                                function: Some(FunctionCallStream { name, arguments }),
-                                ..
-                            } = tool_call
-                            else {
-                                continue;
-                            };
-                            let state = tool_call_states.entry(index).or_insert_with(|| {
-                                ChatCompletionMessageToolCall {
-                                    id: id.unwrap(),
-                                    r#type: ChatCompletionToolType::Function,
-                                    function: FunctionCall {
-                                        name: name.unwrap(),
-                                        arguments: Default::default(),
-                                    },
+                    // Chunks of several choices interleave, each tagged with its index
+                    for chat_choice in std::mem::take(&mut response.choices) {
+                        // Tool calls are only followed for the first choice
+                        if let (0, Some(tool_calls)) =
+                            (chat_choice.index, chat_choice.delta.tool_calls)
+                        {
+                            for tool_call in tool_calls.into_iter() {
+                                let ChatCompletionMessageToolCallChunk {
+                                    index,
+                                    id,
+                                    function: Some(FunctionCallStream { name, arguments }),
+                                    ..
+                                } = tool_call
+                                else {
+                                    continue;
+                                };
+                                let state = tool_call_states.entry(index).or_insert_with(|| {
+                                    ChatCompletionMessageToolCall {
+                                        id: id.unwrap(),
+                                        r#type: ChatCompletionToolType::Function,
+                                        function: FunctionCall {
+                                            name: name.unwrap(),
+                                            arguments: Default::default(),
+                                        },
+                                    }
+                                });
+                                if let Some(arguments) = arguments {
+                                    state.function.arguments.push_str(&arguments);
                                 }
-                            });
-                            if let Some(arguments) = arguments {
-                                state.function.arguments.push_str(&arguments);
                             }
                         }
-                    }
 
-                    if let Some(content) = &chat_choice.delta.content {
-                        let _ = tx
-                            .send(Some(ModelEvent::new(
-                                &Span::current(),
-                                ModelEventType::LlmContent(LLMContentEvent {
-                                    content: content.to_owned(),
-                                }),
-                            )))
-                            .await;
+                        if let Some(content) = &chat_choice.delta.content {
+                            let _ = tx
+                                .send(Some(ModelEvent::new(
+                                    &Span::current(),
+                                    ModelEventType::LlmContent(LLMContentEvent {
+                                        content: content.to_owned(),
+                                        index: chat_choice.index,
+                                    }),
+                                )))
+                                .await;
+                        }
+
+                        if let Some(reason) = chat_choice.finish_reason {
+                            finish_reasons.insert(chat_choice.index, reason);
+                        }
                     }
 
-                    if let Some(reason) = &chat_choice.finish_reason {
+                    if finish_reasons.len() >= choice_count {
                         // Collect last chunk. Some providers sends usage with last chunk instead of separate chunk
                         let mut usage = response.usage;
                         if let Some(Ok(response)) = stream.next().await {
@@ -451,12 +595,24 @@ impl<C: Config> OpenAIModel<C> {
                                 usage = Some(u);
                             }
                         }
-                        return Ok((*reason, tool_call_states.into_values().collect(), usage));
+                        let reason = finish_reasons
+                            .get(&0)
+                            .or_else(|| finish_reasons.values().next())
+                            .copied()
+                            .unwrap_or(FinishReason::Stop);
+                        return Ok((
+                            reason,
+                            tool_call_states.into_values().collect(),
+                            usage,
+                            response.id,
+                        ));
                     }
                 }
                 Err(err) => {
                     tracing::warn!("OpenAI API error: {err}");
-                    return Err(ModelError::OpenAIApi(err).into());
+                    return Err(
+                        provider_err(&err).unwrap_or_else(|| ModelError::OpenAIApi(err).into())
+                    );
                 }
             }
         }
@@ -484,17 +640,22 @@ impl<C: Config> OpenAIModel<C> {
         .map_err(|e| GatewayError::CustomError(e.to_string()))?;
 
         let response = async move {
-            let result = self.client.chat().create(call).await;
-            let _ = result
-                .as_ref()
-                .map(|response| serde_json::to_value(response).unwrap())
-                .as_ref()
-                .map(JsonValue)
-                .record();
-            let response = result.map_err(custom_err)?;
+            let result = match &self.params.extra_body {
+                Some(extra_body) => self.create_with_extra_body(call, extra_body).await,
+                None => self.client.chat().create(call).await.map_err(|e| {
+                    provider_err(&e).unwrap_or_else(|| custom_err(ModelError::OpenAIApi(e)).into())
+                }),
+            };
+            if let Err(e) = &result {
+                Span::current().record("error", e.to_string());
+            }
+            let response = result?;
 
             let span = Span::current();
-            span.record("output", serde_json::to_string(&response)?);
+            span.record(
+                "output",
+                BodyRecordingMode::current().apply(&serde_json::to_string(&response)?),
+            );
             if let Some(ref usage) = response.usage {
                 span.record(
                     "usage",
@@ -549,6 +710,9 @@ impl<C: Config> OpenAIModel<C> {
                             finish_reason,
                             tool_calls: tool_calls.iter().map(Self::map_tool_call).collect(),
                             credentials_ident: self.credentials_ident.clone(),
+                            logprobs: Self::map_logprobs(first_choice.logprobs.as_ref()),
+                            service_tier: Self::map_service_tier(response.service_tier.as_ref()),
+                            response_id: Some(response.id.clone()),
                         }),
                     )))
                     .await
@@ -604,7 +768,12 @@ impl<C: Config> OpenAIModel<C> {
                 let finish_reason = Self::map_finish_reason(
                     &finish_reason.expect("Finish reason is already checked"),
                 );
-                let message_content = first_choice.message.content;
+                let audio = first_choice.message.audio;
+                // The text of an audio response comes as the transcript of the audio
+                let message_content = first_choice
+                    .message
+                    .content
+                    .or_else(|| audio.as_ref().map(|a| a.transcript.clone()));
                 if let Some(content) = &message_content {
                     tx.send(Some(ModelEvent::new(
                         &span,
@@ -616,6 +785,9 @@ impl<C: Config> OpenAIModel<C> {
                             finish_reason,
                             tool_calls: vec![],
                             credentials_ident: self.credentials_ident.clone(),
+                            logprobs: Self::map_logprobs(first_choice.logprobs.as_ref()),
+                            service_tier: Self::map_service_tier(response.service_tier.as_ref()),
+                            response_id: Some(response.id.clone()),
                         }),
                     )))
                     .await
@@ -623,7 +795,11 @@ impl<C: Config> OpenAIModel<C> {
 
                     Ok(InnerExecutionResult::Finish(ChatCompletionMessage {
                         role: "assistant".to_string(),
-                        content: Some(ChatCompletionContent::Text(content.to_string())),
+                        content: Some(Self::map_content(
+                            content.to_string(),
+                            audio,
+                            self.params.audio.as_ref(),
+                        )),
                         ..Default::default()
                     }))
                 } else {
@@ -650,11 +826,11 @@ impl<C: Config> OpenAIModel<C> {
             .max_retries
             .unwrap_or(DEFAULT_MAX_RETRIES);
         while let Some(messages) = openai_calls.pop() {
-            let input = serde_json::to_string(&messages)?;
+            let input = BodyRecordingMode::current().apply(&serde_json::to_string(&messages)?);
             let span = tracing::info_span!(
                 target: target!("chat"),
                 SPAN_OPENAI,
-                input = input,
+                input = input.as_deref(),
                 output = field::Empty,
                 error = field::Empty,
                 usage = field::Empty,
@@ -663,9 +839,11 @@ impl<C: Config> OpenAIModel<C> {
                 retries_left = retries
             );
 
-            match self
-                .execute_inner(span.clone(), messages.clone(), tx, tags.clone())
-                .await
+            match with_timeout(
+                &self.execution_options,
+                self.execute_inner(span.clone(), messages.clone(), tx, tags.clone()),
+            )
+            .await
             {
                 Ok(InnerExecutionResult::Finish(message)) => return Ok(message),
                 Ok(InnerExecutionResult::NextCall(messages)) => {
@@ -677,6 +855,7 @@ impl<C: Config> OpenAIModel<C> {
                     if retries == 0 {
                         return Err(e);
                     } else {
+                        wait_retry_backoff(&self.execution_options).await;
                         openai_calls.push(messages);
                     }
                 }
@@ -706,11 +885,86 @@ impl<C: Config> OpenAIModel<C> {
             FinishReason::FunctionCall => ModelFinishReason::Other("FunctionCall".to_string()),
         }
     }
+    /// Content of a completion, with the audio the model generated, if any, next to its text
+    fn map_content(
+        text: String,
+        audio: Option<ChatCompletionResponseMessageAudio>,
+        requested: Option<&ChatCompletionAudio>,
+    ) -> ChatCompletionContent {
+        let Some(audio) = audio else {
+            return ChatCompletionContent::Text(text);
+        };
+        let format = requested
+            .and_then(|a| serde_json::to_value(&a.format).ok())
+            .and_then(|f| f.as_str().map(str::to_string))
+            .unwrap_or_default();
+        ChatCompletionContent::Content(vec![
+            Content {
+                r#type: ContentType::Text,
+                text: Some(text),
+                image_url: None,
+                audio: None,
+            },
+            Content {
+                r#type: ContentType::Audio,
+                text: None,
+                image_url: None,
+                audio: Some(InputAudio {
+                    data: audio.data,
+                    format,
+                }),
+            },
+        ])
+    }
+
+    fn map_service_tier(service_tier: Option<&ServiceTierResponse>) -> Option<String> {
+        service_tier
+            .and_then(|tier| serde_json::to_value(tier).ok())
+            .and_then(|tier| tier.as_str().map(str::to_string))
+    }
+
+    fn map_logprobs(logprobs: Option<&ChatChoiceLogprobs>) -> Option<ChatCompletionLogprobs> {
+        logprobs
+            .and_then(|l| l.content.as_ref())
+            .map(|content| ChatCompletionLogprobs {
+                content: content
+                    .iter()
+                    .map(|t| ChatCompletionTokenLogprob {
+                        token: t.token.clone(),
+                        logprob: t.logprob,
+                        bytes: t.bytes.clone(),
+                        top_logprobs: t
+                            .top_logprobs
+                            .iter()
+                            .map(|top| ChatCompletionTopLogprob {
+                                token: top.token.clone(),
+                                logprob: top.logprob,
+                                bytes: top.bytes.clone(),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            })
+    }
+
     fn map_usage(usage: Option<&CompletionUsage>) -> Option<CompletionModelUsage> {
         usage.map(|u| CompletionModelUsage {
             input_tokens: u.prompt_tokens,
             output_tokens: u.completion_tokens,
             total_tokens: u.total_tokens,
+            reasoning_tokens: u
+                .completion_tokens_details
+                .as_ref()
+                .and_then(|d| d.reasoning_tokens)
+                .unwrap_or_default(),
+            completion_tokens_details: u.completion_tokens_details.as_ref().map(|d| {
+                CompletionTokensDetails {
+                    accepted_prediction_tokens: d.accepted_prediction_tokens.unwrap_or_default(),
+                    audio_tokens: d.audio_tokens.unwrap_or_default(),
+                    reasoning_tokens: d.reasoning_tokens.unwrap_or_default(),
+                    rejected_prediction_tokens: d.rejected_prediction_tokens.unwrap_or_default(),
+                }
+            }),
             ..Default::default()
         })
     }
@@ -736,13 +990,20 @@ impl<C: Config> OpenAIModel<C> {
 
         let request = self.build_request(&input_messages, true)?;
 
-        let stream = self
-            .client
-            .chat()
-            .create_stream(request)
-            .await
-            .map_err(ModelError::OpenAIApi)?;
-        let (finish_reason, tool_calls, usage) = self
+        let stream = match &self.params.extra_body {
+            Some(extra_body) => {
+                let events = EventSource::new(self.request_with_extra_body(request, extra_body)?)
+                    .map_err(custom_err)?;
+                chat_completion_chunks(events)
+            }
+            None => self
+                .client
+                .chat()
+                .create_stream(request)
+                .await
+                .map_err(ModelError::OpenAIApi)?,
+        };
+        let (finish_reason, tool_calls, usage, response_id) = self
             .process_stream(stream, tx, first_response_received)
             .instrument(span.clone())
             .await?;
@@ -758,6 +1019,9 @@ impl<C: Config> OpenAIModel<C> {
                 finish_reason: trace_finish_reason.clone(),
                 tool_calls: tool_calls.iter().map(Self::map_tool_call).collect(),
                 credentials_ident: self.credentials_ident.clone(),
+                logprobs: None,
+                service_tier: None,
+                response_id: Some(response_id),
             }),
         )))
         .await
@@ -772,7 +1036,10 @@ impl<C: Config> OpenAIModel<C> {
             "finish_reason": trace_finish_reason,
             "tool_calls": tool_calls
         });
-        span.record("output", response.to_string());
+        span.record(
+            "output",
+            BodyRecordingMode::current().apply(&response.to_string()),
+        );
         match finish_reason {
             FinishReason::Stop => Ok(InnerExecutionResult::Finish(ChatCompletionMessage {
                 ..Default::default()
@@ -838,11 +1105,12 @@ impl<C: Config> OpenAIModel<C> {
             .max_retries
             .unwrap_or(DEFAULT_MAX_RETRIES);
         while let Some(input_messages) = openai_calls.pop() {
-            let input = serde_json::to_string(&input_messages)?;
+            let input =
+                BodyRecordingMode::current().apply(&serde_json::to_string(&input_messages)?);
             let span = tracing::info_span!(
                 target: target!("chat"),
                 SPAN_OPENAI,
-                input = input,
+                input = input.as_deref(),
                 output = field::Empty,
                 error = field::Empty,
                 usage = field::Empty,
@@ -853,15 +1121,17 @@ impl<C: Config> OpenAIModel<C> {
 
             let first_response_received = &mut false;
 
-            match self
-                .execute_stream_inner(
+            match with_timeout(
+                &self.execution_options,
+                self.execute_stream_inner(
                     span.clone(),
                     input_messages.clone(),
                     tx,
                     tags.clone(),
                     first_response_received,
-                )
-                .await
+                ),
+            )
+            .await
             {
                 Ok(InnerExecutionResult::Finish(_)) => {
                     break;
@@ -875,6 +1145,7 @@ impl<C: Config> OpenAIModel<C> {
                     if retries == 0 {
                         return Err(e);
                     } else {
+                        wait_retry_backoff(&self.execution_options).await;
                         openai_calls.push(input_messages);
                     }
                 }
@@ -1113,6 +1384,34 @@ fn construct_user_message(
     )
 }
 
+/// Chunks of a chat completion stream read until `[DONE]` or the first error
+fn chat_completion_chunks(events: EventSource) -> ChatCompletionResponseStream {
+    Box::pin(futures::stream::unfold(Some(events), |events| async move {
+        let mut events = events?;
+        loop {
+            let chunk = match events.next().await? {
+                Ok(Event::Open) => continue,
+                Ok(Event::Message(message)) if message.data == "[DONE]" => {
+                    events.close();
+                    return None;
+                }
+                Ok(Event::Message(message)) => {
+                    serde_json::from_str(&message.data).map_err(OpenAIError::JSONDeserialize)
+                }
+                Err(reqwest_eventsource::Error::StreamEnded) => return None,
+                Err(e) => {
+                    if let reqwest_eventsource::Error::InvalidStatusCode(_, response) = &e {
+                        upstream_rate_limits().record(SPAN_OPENAI, response.headers());
+                    }
+                    events.close();
+                    return Some((Err(OpenAIError::StreamError(e.to_string())), None));
+                }
+            };
+            return Some((chunk, Some(events)));
+        }
+    }))
+}
+
 pub fn record_map_err(e: impl Into<GatewayError> + ToString, span: tracing::Span) -> GatewayError {
     span.record("error", e.to_string());
     e.into()
@@ -1127,3 +1426,624 @@ fn map_tool_names_to_labels(tool_calls: &[ChatCompletionMessageToolCall]) -> Str
         .collect::<Vec<String>>()
         .join(",")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{mock_response, mock_server};
+
+    #[test]
+    fn test_extra_body_is_merged_into_request() {
+        let model = crate::models::ModelMetadata {
+            model: "gpt-4o-mini".to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: crate::models::InferenceProvider {
+                provider: crate::types::provider::InferenceModelProvider::OpenAI,
+                model_name: "gpt-4o-mini".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let provider_specific: crate::types::gateway::ProviderSpecificRequest =
+            serde_json::from_value(serde_json::json!({
+                "extra_body": {"reasoning_effort": "low", "store": true}
+            }))
+            .unwrap();
+
+        let engine = crate::llm_gateway::provider::Provider::get_completion_engine_for_model(
+            &model,
+            &Default::default(),
+            None,
+            Some(&provider_specific),
+            None,
+        )
+        .unwrap();
+        let crate::types::engine::CompletionEngineParams::OpenAi { params, .. } = engine else {
+            panic!("Expected OpenAI params");
+        };
+        let extra_body = params.extra_body.clone().unwrap();
+        let model = OpenAIModel::new(
+            params,
+            Some(&ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            ExecutionOptions::default(),
+            Prompt::new("test".to_string(), String::new()),
+            HashMap::new(),
+            None,
+            None,
+        )
+        .unwrap();
+
+        let request = model.build_request(&[], false).unwrap();
+        let request = model
+            .request_with_extra_body(request, &extra_body)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.url().path(), "/v1/chat/completions");
+        let body: Value =
+            serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body["model"], "gpt-4o-mini");
+        assert_eq!(body["reasoning_effort"], "low");
+        assert_eq!(body["store"], true);
+    }
+
+    #[test]
+    fn test_service_tier_is_forwarded_and_surfaced() {
+        let model = crate::models::ModelMetadata {
+            model: "gpt-4o-mini".to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: crate::models::InferenceProvider {
+                provider: crate::types::provider::InferenceModelProvider::OpenAI,
+                model_name: "gpt-4o-mini".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let request: crate::types::gateway::ChatCompletionRequest =
+            serde_json::from_value(serde_json::json!({
+                "model": "openai/gpt-4o-mini",
+                "service_tier": "auto"
+            }))
+            .unwrap();
+
+        let engine = crate::llm_gateway::provider::Provider::get_completion_engine_for_model(
+            &model, &request, None, None, None,
+        )
+        .unwrap();
+        let crate::types::engine::CompletionEngineParams::OpenAi { params, .. } = engine else {
+            panic!("Expected OpenAI params");
+        };
+        let model = OpenAIModel::new(
+            params,
+            Some(&ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            ExecutionOptions::default(),
+            Prompt::new("test".to_string(), String::new()),
+            HashMap::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        let body = serde_json::to_value(model.build_request(&[], false).unwrap()).unwrap();
+        assert_eq!(body["service_tier"], "auto");
+
+        let response: CreateChatCompletionResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o-mini",
+            "service_tier": "default",
+            "choices": []
+        }))
+        .unwrap();
+        assert_eq!(
+            OpenAIModel::<OpenAIConfig>::map_service_tier(response.service_tier.as_ref()),
+            Some("default".to_string())
+        );
+    }
+
+    #[test]
+    fn test_audio_output_is_forwarded_and_parsed() {
+        let model = crate::models::ModelMetadata {
+            model: "gpt-4o-audio-preview".to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: crate::models::InferenceProvider {
+                provider: crate::types::provider::InferenceModelProvider::OpenAI,
+                model_name: "gpt-4o-audio-preview".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let request: crate::types::gateway::ChatCompletionRequest =
+            serde_json::from_value(serde_json::json!({
+                "model": "openai/gpt-4o-audio-preview",
+                "modalities": ["text", "audio"],
+                "audio": {"voice": "alloy", "format": "wav"}
+            }))
+            .unwrap();
+
+        let engine = crate::llm_gateway::provider::Provider::get_completion_engine_for_model(
+            &model, &request, None, None, None,
+        )
+        .unwrap();
+        let crate::types::engine::CompletionEngineParams::OpenAi { params, .. } = engine else {
+            panic!("Expected OpenAI params");
+        };
+        let model = OpenAIModel::new(
+            params,
+            Some(&ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            ExecutionOptions::default(),
+            Prompt::new("test".to_string(), String::new()),
+            HashMap::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        let body = serde_json::to_value(model.build_request(&[], false).unwrap()).unwrap();
+        assert_eq!(body["modalities"], serde_json::json!(["text", "audio"]));
+        assert_eq!(body["audio"]["voice"], "alloy");
+        assert_eq!(body["audio"]["format"], "wav");
+
+        let response: CreateChatCompletionResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o-audio-preview",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "audio": {
+                        "id": "audio_1",
+                        "expires_at": 1700003600,
+                        "data": "UklGRg==",
+                        "transcript": "Paris"
+                    }
+                },
+                "finish_reason": "stop",
+                "logprobs": null
+            }]
+        }))
+        .unwrap();
+        let message = response.choices[0].message.clone();
+        let content = OpenAIModel::<OpenAIConfig>::map_content(
+            message.audio.as_ref().unwrap().transcript.clone(),
+            message.audio,
+            model.params.audio.as_ref(),
+        );
+        let ChatCompletionContent::Content(parts) = content else {
+            panic!("Expected content parts");
+        };
+        assert_eq!(parts[0].r#type, ContentType::Text);
+        assert_eq!(parts[0].text.as_deref(), Some("Paris"));
+        assert_eq!(parts[1].r#type, ContentType::Audio);
+        assert_eq!(
+            parts[1].audio,
+            Some(InputAudio {
+                data: "UklGRg==".to_string(),
+                format: "wav".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parallel_tool_calls_is_forwarded() {
+        let model = crate::models::ModelMetadata {
+            model: "gpt-4o-mini".to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: crate::models::InferenceProvider {
+                provider: crate::types::provider::InferenceModelProvider::OpenAI,
+                model_name: "gpt-4o-mini".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let request: crate::types::gateway::ChatCompletionRequest =
+            serde_json::from_value(serde_json::json!({
+                "model": "openai/gpt-4o-mini",
+                "parallel_tool_calls": false
+            }))
+            .unwrap();
+
+        let engine = crate::llm_gateway::provider::Provider::get_completion_engine_for_model(
+            &model, &request, None, None, None,
+        )
+        .unwrap();
+        let crate::types::engine::CompletionEngineParams::OpenAi { params, .. } = engine else {
+            panic!("Expected OpenAI params");
+        };
+        let tool: crate::types::gateway::ChatCompletionTool =
+            serde_json::from_value(serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Current weather of a city",
+                    "parameters": {"type": "object", "properties": {}}
+                }
+            }))
+            .unwrap();
+        let tools: HashMap<String, Box<dyn Tool>> = HashMap::from([(
+            "get_weather".to_string(),
+            Box::new(crate::model::tools::GatewayTool { def: tool }) as Box<dyn Tool>,
+        )]);
+        let model = OpenAIModel::new(
+            params,
+            Some(&ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            ExecutionOptions::default(),
+            Prompt::new("test".to_string(), String::new()),
+            tools,
+            None,
+            None,
+        )
+        .unwrap();
+        let body = serde_json::to_value(model.build_request(&[], false).unwrap()).unwrap();
+        assert_eq!(body["parallel_tool_calls"], false);
+    }
+
+    #[test]
+    fn test_map_logprobs() {
+        let logprobs: ChatChoiceLogprobs = serde_json::from_value(serde_json::json!({
+            "content": [{
+                "token": "Hello",
+                "logprob": -0.25,
+                "bytes": [72, 101, 108, 108, 111],
+                "top_logprobs": [
+                    {"token": "Hello", "logprob": -0.25, "bytes": [72, 101, 108, 108, 111]},
+                    {"token": "Hi", "logprob": -1.5, "bytes": [72, 105]}
+                ]
+            }],
+            "refusal": null
+        }))
+        .unwrap();
+
+        let mapped = OpenAIModel::<OpenAIConfig>::map_logprobs(Some(&logprobs)).unwrap();
+        assert_eq!(mapped.content.len(), 1);
+        assert_eq!(mapped.content[0].token, "Hello");
+        assert_eq!(mapped.content[0].logprob, -0.25);
+        assert_eq!(mapped.content[0].bytes, Some(b"Hello".to_vec()));
+        assert_eq!(mapped.content[0].top_logprobs.len(), 2);
+        assert_eq!(mapped.content[0].top_logprobs[1].token, "Hi");
+        assert_eq!(mapped.content[0].top_logprobs[1].logprob, -1.5);
+
+        assert!(OpenAIModel::<OpenAIConfig>::map_logprobs(None).is_none());
+    }
+
+    #[test]
+    fn test_map_usage_captures_reasoning_tokens() {
+        let usage: CompletionUsage = serde_json::from_value(serde_json::json!({
+            "prompt_tokens": 20,
+            "completion_tokens": 150,
+            "total_tokens": 170,
+            "completion_tokens_details": {"reasoning_tokens": 128}
+        }))
+        .unwrap();
+
+        let mapped = OpenAIModel::<OpenAIConfig>::map_usage(Some(&usage)).unwrap();
+        assert_eq!(mapped.output_tokens, 150);
+        assert_eq!(mapped.reasoning_tokens, 128);
+    }
+
+    #[test]
+    fn test_prediction_is_forwarded_and_counted() {
+        let model = crate::models::ModelMetadata {
+            model: "gpt-4o-mini".to_string(),
+            model_provider: "openai".to_string(),
+            inference_provider: crate::models::InferenceProvider {
+                provider: crate::types::provider::InferenceModelProvider::OpenAI,
+                model_name: "gpt-4o-mini".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        };
+        let request: crate::types::gateway::ChatCompletionRequest =
+            serde_json::from_value(serde_json::json!({
+                "model": "openai/gpt-4o-mini",
+                "prediction": {"type": "content", "content": "fn main() {}"}
+            }))
+            .unwrap();
+
+        let engine = crate::llm_gateway::provider::Provider::get_completion_engine_for_model(
+            &model, &request, None, None, None,
+        )
+        .unwrap();
+        let crate::types::engine::CompletionEngineParams::OpenAi { params, .. } = engine else {
+            panic!("Expected OpenAI params");
+        };
+        let model = OpenAIModel::new(
+            params,
+            Some(&ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            ExecutionOptions::default(),
+            Prompt::new("test".to_string(), String::new()),
+            HashMap::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        let body = serde_json::to_value(model.build_request(&[], false).unwrap()).unwrap();
+        assert_eq!(
+            body["prediction"],
+            serde_json::json!({"type": "content", "content": "fn main() {}"})
+        );
+
+        let usage: CompletionUsage = serde_json::from_value(serde_json::json!({
+            "prompt_tokens": 40,
+            "completion_tokens": 30,
+            "total_tokens": 70,
+            "completion_tokens_details": {
+                "accepted_prediction_tokens": 18,
+                "rejected_prediction_tokens": 4
+            }
+        }))
+        .unwrap();
+        let mapped = OpenAIModel::<OpenAIConfig>::map_usage(Some(&usage)).unwrap();
+        assert_eq!(
+            mapped.completion_tokens_details,
+            Some(CompletionTokensDetails {
+                accepted_prediction_tokens: 18,
+                rejected_prediction_tokens: 4,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout_fails_fast() {
+        // Non-routable address, connecting hangs until the connect timeout fires
+        let client = openai_client(
+            Some(&ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            Some("http://10.255.255.1/v1"),
+            &ExecutionOptions {
+                connect_timeout_ms: Some(200),
+                read_timeout_secs: Some(300),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let started = std::time::Instant::now();
+        assert!(client.models().list().await.is_err());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    fn model_with_endpoint(endpoint: &str) -> OpenAIModel<OpenAIConfig> {
+        OpenAIModel::new(
+            OpenAiModelParams {
+                model: Some("gpt-4o-mini".to_string()),
+                ..Default::default()
+            },
+            Some(&ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            ExecutionOptions::default(),
+            Prompt::new("test".to_string(), "You are helpful".to_string()),
+            HashMap::new(),
+            None,
+            Some(endpoint),
+        )
+        .unwrap()
+    }
+
+    async fn finish_event(
+        mut rx: tokio::sync::mpsc::Receiver<Option<ModelEvent>>,
+    ) -> LLMFinishEvent {
+        while let Some(Some(event)) = rx.recv().await {
+            if let ModelEventType::LlmStop(finish) = event.event {
+                return finish;
+            }
+        }
+        panic!("No finish event");
+    }
+
+    #[tokio::test]
+    async fn test_upstream_response_id_is_surfaced() {
+        let body = serde_json::json!({
+            "id": "chatcmpl-upstream",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Paris"},
+                "finish_reason": "stop"
+            }]
+        });
+        let url = mock_server("application/json", body.to_string()).await;
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        model_with_endpoint(&url)
+            .invoke(HashMap::new(), tx, vec![], HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            finish_event(rx).await.response_id.as_deref(),
+            Some("chatcmpl-upstream")
+        );
+
+        let chunk = serde_json::json!({
+            "id": "chatcmpl-streamed",
+            "object": "chat.completion.chunk",
+            "created": 1700000000,
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "delta": {"role": "assistant", "content": "Paris"},
+                "finish_reason": "stop"
+            }]
+        });
+        let url = mock_server(
+            "text/event-stream",
+            format!("data: {chunk}\n\ndata: [DONE]\n\n"),
+        )
+        .await;
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        model_with_endpoint(&url)
+            .stream(HashMap::new(), tx, vec![], HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            finish_event(rx).await.response_id.as_deref(),
+            Some("chatcmpl-streamed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_logprobs_are_requested_and_attached() {
+        let body = serde_json::json!({
+            "id": "chatcmpl-logprobs",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Paris"},
+                "finish_reason": "stop",
+                "logprobs": {
+                    "content": [{
+                        "token": "Paris",
+                        "logprob": -0.01,
+                        "bytes": [80, 97, 114, 105, 115],
+                        "top_logprobs": [
+                            {"token": "Paris", "logprob": -0.01, "bytes": [80, 97, 114, 105, 115]},
+                            {"token": "Lyon", "logprob": -4.6, "bytes": [76, 121, 111, 110]}
+                        ]
+                    }],
+                    "refusal": null
+                }
+            }]
+        });
+        let url = mock_server("application/json", body.to_string()).await;
+        let mut model = model_with_endpoint(&url);
+        model.params.logprobs = Some(true);
+        model.params.top_logprobs = Some(2);
+
+        let request = serde_json::to_value(model.build_request(&[], false).unwrap()).unwrap();
+        assert_eq!(request["logprobs"], true);
+        assert_eq!(request["top_logprobs"], 2);
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        model
+            .invoke(HashMap::new(), tx, vec![], HashMap::new())
+            .await
+            .unwrap();
+        let logprobs = finish_event(rx).await.logprobs.unwrap();
+        assert_eq!(logprobs.content.len(), 1);
+        assert_eq!(logprobs.content[0].token, "Paris");
+        assert_eq!(logprobs.content[0].top_logprobs[1].token, "Lyon");
+        assert_eq!(logprobs.content[0].top_logprobs[1].logprob, -4.6);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_becomes_rate_limited_error() {
+        let body = r#"{"error":{"message":"Rate limit reached","type":"requests","code":"rate_limit_exceeded"}}"#;
+        let too_many_requests = format!(
+            "HTTP/1.1 429 Too Many Requests\r\ncontent-type: application/json\r\nretry-after: 7\r\nx-ratelimit-remaining-requests: 0\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+
+        let url = mock_response(too_many_requests.clone()).await;
+        let mut model = model_with_endpoint(&url);
+        model.execution_options.max_retries = Some(1);
+        model.params.extra_body = Some(Map::new());
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let result = model
+            .invoke(HashMap::new(), tx, vec![], HashMap::new())
+            .await;
+        assert!(matches!(
+            result,
+            Err(GatewayError::RateLimited { provider, retry_after: Some(7) }) if provider == SPAN_OPENAI
+        ));
+        assert_eq!(
+            upstream_rate_limits().get(SPAN_OPENAI).unwrap()["x-ratelimit-remaining-requests"],
+            "0"
+        );
+
+        let url = mock_response(too_many_requests).await;
+        let mut model = model_with_endpoint(&url);
+        model.execution_options.max_retries = Some(1);
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let result = model
+            .stream(HashMap::new(), tx, vec![], HashMap::new())
+            .await;
+        assert!(matches!(result, Err(GatewayError::RateLimited { .. })));
+
+        let body: Value = serde_json::from_str(body).unwrap();
+        let error: async_openai::error::ApiError =
+            serde_json::from_value(body["error"].clone()).unwrap();
+        assert!(matches!(
+            rate_limit_err(&OpenAIError::ApiError(error)),
+            Some(GatewayError::RateLimited { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_interleaves_several_choices() {
+        let model = OpenAIModel::new(
+            OpenAiModelParams {
+                model: Some("gpt-4o-mini".to_string()),
+                n: Some(2),
+                ..Default::default()
+            },
+            Some(&ApiKeyCredentials {
+                api_key: "sk-test".to_string(),
+            }),
+            ExecutionOptions::default(),
+            Prompt::new("test".to_string(), String::new()),
+            HashMap::new(),
+            None,
+            None,
+        )
+        .unwrap();
+        let body = serde_json::to_value(model.build_request(&[], true).unwrap()).unwrap();
+        assert_eq!(body["n"], 2);
+
+        let chunk = |choices: Value| -> Result<CreateChatCompletionStreamResponse, OpenAIError> {
+            Ok(serde_json::from_value(serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion.chunk",
+                "created": 1700000000,
+                "model": "gpt-4o-mini",
+                "choices": choices
+            }))
+            .unwrap())
+        };
+        let stream = futures::stream::iter(vec![
+            chunk(serde_json::json!([{"index": 0, "delta": {"content": "Paris"}}])),
+            chunk(serde_json::json!([{"index": 1, "delta": {"content": "Lyon"}}])),
+            chunk(serde_json::json!([{"index": 1, "delta": {}, "finish_reason": "stop"}])),
+            chunk(serde_json::json!([{"index": 0, "delta": {"content": "!"}}])),
+            chunk(serde_json::json!([{"index": 0, "delta": {}, "finish_reason": "length"}])),
+        ]);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (finish_reason, ..) = model.process_stream(stream, &tx, &mut false).await.unwrap();
+        drop(tx);
+        // The first choice decides how the completion finished
+        assert_eq!(finish_reason, FinishReason::Length);
+
+        let mut contents = vec![];
+        while let Some(Some(event)) = rx.recv().await {
+            if let ModelEventType::LlmContent(content) = event.event {
+                contents.push((content.index, content.content));
+            }
+        }
+        assert_eq!(
+            contents,
+            [
+                (0, "Paris".to_string()),
+                (1, "Lyon".to_string()),
+                (0, "!".to_string())
+            ]
+        );
+    }
+}
@@ -1,13 +1,16 @@
 use async_openai::{config::OpenAIConfig, Client};
 
 use crate::types::credentials::ApiKeyCredentials;
+use crate::types::engine::ExecutionOptions;
 
 use super::error::ModelError;
+use super::http_client;
 
 pub fn openai_spec_client(
     credentials: Option<&ApiKeyCredentials>,
     endpoint: Option<&str>,
     provider_name: &str,
+    execution_options: &ExecutionOptions,
 ) -> Result<async_openai::Client<async_openai::config::OpenAIConfig>, ModelError> {
     let mut config = OpenAIConfig::new();
 
@@ -32,5 +35,5 @@ pub fn openai_spec_client(
 
     config = config.with_api_base(api_base);
 
-    Ok(Client::with_config(config))
+    Ok(Client::with_config(config).with_http_client(http_client(execution_options)?))
 }
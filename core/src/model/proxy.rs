@@ -1,10 +1,11 @@
 use super::error::ModelError;
 use super::openai_spec_client::openai_spec_client;
 use super::tools::Tool;
-use super::types::ModelEvent;
+use super::types::{ModelEvent, ModelEventType};
 use super::ModelInstance;
 use crate::model::async_trait;
 use crate::model::openai::OpenAIModel;
+use crate::pricing::estimate::estimate_completion_usage;
 use crate::types::credentials::ApiKeyCredentials;
 use crate::types::engine::ExecutionOptions;
 use crate::types::engine::OpenAiModelParams;
@@ -16,6 +17,7 @@ use async_openai::config::OpenAIConfig;
 use async_openai::Client;
 use serde_json::Value;
 use std::collections::HashMap;
+use tokio::sync::mpsc;
 use tracing::Span;
 use tracing_futures::Instrument;
 
@@ -45,7 +47,7 @@ impl OpenAISpecModel {
         }
 
         let client: Client<OpenAIConfig> =
-            openai_spec_client(credentials, endpoint, provider_name)?;
+            openai_spec_client(credentials, endpoint, provider_name, &execution_options)?;
         let openai_model = OpenAIModel::new(
             params,
             credentials,
@@ -60,6 +62,41 @@ impl OpenAISpecModel {
     }
 }
 
+/// Forwards the events of a proxied call to `tx`, filling in an estimated usage when the
+/// backend did not report one so the call can still be costed
+async fn estimate_missing_usage(
+    mut rx: mpsc::Receiver<Option<ModelEvent>>,
+    tx: mpsc::Sender<Option<ModelEvent>>,
+) {
+    let mut input = String::new();
+    let mut output = String::new();
+    while let Some(mut event) = rx.recv().await {
+        if let Some(event) = &mut event {
+            match &mut event.event {
+                ModelEventType::LlmStart(start) => {
+                    input = start.input.clone();
+                    output.clear();
+                }
+                ModelEventType::LlmContent(content) => output.push_str(&content.content),
+                ModelEventType::LlmStop(stop) if stop.usage.is_none() => {
+                    let mut generated = stop.output.clone().unwrap_or_else(|| output.clone());
+                    for tool_call in &stop.tool_calls {
+                        generated.push_str(&tool_call.tool_name);
+                        generated.push_str(&tool_call.input);
+                    }
+                    tracing::debug!("Backend did not report usage, estimating it");
+                    stop.usage = Some(estimate_completion_usage(&input, &generated));
+                }
+                _ => {}
+            }
+        }
+
+        if tx.send(event).await.is_err() {
+            break;
+        }
+    }
+}
+
 #[async_trait]
 impl ModelInstance for OpenAISpecModel {
     async fn invoke(
@@ -70,10 +107,14 @@ impl ModelInstance for OpenAISpecModel {
         tags: HashMap<String, String>,
     ) -> GatewayResult<ChatCompletionMessage> {
         let span = Span::current();
-        self.openai_model
-            .invoke(input_variables, tx, previous_messages, tags)
-            .instrument(span.clone())
-            .await
+        let (inner_tx, inner_rx) = mpsc::channel(tx.max_capacity());
+        let (result, _) = tokio::join!(
+            self.openai_model
+                .invoke(input_variables, inner_tx, previous_messages, tags)
+                .instrument(span.clone()),
+            estimate_missing_usage(inner_rx, tx)
+        );
+        result
     }
 
     async fn stream(
@@ -84,9 +125,13 @@ impl ModelInstance for OpenAISpecModel {
         tags: HashMap<String, String>,
     ) -> GatewayResult<()> {
         let span = Span::current();
-        self.openai_model
-            .stream(input_variables, tx, previous_messages, tags)
-            .instrument(span.clone())
-            .await
+        let (inner_tx, inner_rx) = mpsc::channel(tx.max_capacity());
+        let (result, _) = tokio::join!(
+            self.openai_model
+                .stream(input_variables, inner_tx, previous_messages, tags)
+                .instrument(span.clone()),
+            estimate_missing_usage(inner_rx, tx)
+        );
+        result
     }
 }
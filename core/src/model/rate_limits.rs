@@ -0,0 +1,70 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::LazyLock;
+
+use parking_lot::RwLock;
+use reqwest::header::HeaderMap;
+
+/// Prefix of the rate-limit headers captured from provider responses
+pub const RATE_LIMIT_HEADER_PREFIX: &str = "x-ratelimit-";
+
+static UPSTREAM_RATE_LIMITS: LazyLock<UpstreamRateLimits> =
+    LazyLock::new(UpstreamRateLimits::default);
+
+/// Latest rate-limit headers seen per provider
+#[derive(Debug, Default)]
+pub struct UpstreamRateLimits {
+    headers: RwLock<HashMap<String, BTreeMap<String, String>>>,
+}
+
+impl UpstreamRateLimits {
+    pub fn get(&self, provider: &str) -> Option<BTreeMap<String, String>> {
+        self.headers.read().get(provider).cloned()
+    }
+
+    pub fn all(&self) -> HashMap<String, BTreeMap<String, String>> {
+        self.headers.read().clone()
+    }
+
+    /// Keeps the rate-limit headers of a `provider` response, responses without any are ignored
+    pub fn record(&self, provider: &str, headers: &HeaderMap) {
+        let captured = headers
+            .iter()
+            .filter(|(name, _)| name.as_str().starts_with(RATE_LIMIT_HEADER_PREFIX))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect::<BTreeMap<_, _>>();
+        if captured.is_empty() {
+            return;
+        }
+
+        tracing::debug!("Rate limits for {provider}: {captured:?}");
+        self.headers.write().insert(provider.to_string(), captured);
+    }
+}
+
+/// Rate limits recorded by the provider clients
+pub fn upstream_rate_limits() -> &'static UpstreamRateLimits {
+    &UPSTREAM_RATE_LIMITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_rate_limit_headers_are_recorded() {
+        let rate_limits = UpstreamRateLimits::default();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-requests", "59".parse().unwrap());
+        headers.insert("openai-organization", "org-123".parse().unwrap());
+        rate_limits.record("openai", &headers);
+
+        let recorded = rate_limits.get("openai").unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded["x-ratelimit-remaining-requests"], "59");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        rate_limits.record("openai", &headers);
+        assert_eq!(rate_limits.get("openai").unwrap(), recorded);
+    }
+}
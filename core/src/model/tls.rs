@@ -0,0 +1,312 @@
+use std::sync::{Arc, OnceLock};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, SignatureScheme, SupportedProtocolVersion};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+static PROVIDER_TLS: OnceLock<ProviderTlsConfig> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// TLS policy of the connections made to model providers
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderTlsConfig {
+    /// Lowest TLS version a provider may negotiate
+    #[serde(default)]
+    pub min_version: Option<TlsVersion>,
+    /// SHA-256 fingerprints of the only provider certificates accepted, hex encoded with
+    /// optional colons. Pinned certificates are trusted without checking their issuer.
+    #[serde(default)]
+    pub pinned_certificates: Vec<String>,
+}
+
+impl ProviderTlsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.fingerprints().map(|_| ())
+    }
+
+    fn fingerprints(&self) -> Result<Vec<String>, String> {
+        self.pinned_certificates
+            .iter()
+            .map(|pin| {
+                let fingerprint = pin.replace(':', "").to_lowercase();
+                if fingerprint.len() == 64 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+                    Ok(fingerprint)
+                } else {
+                    Err(format!("invalid SHA-256 certificate fingerprint {pin}"))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Sets the TLS policy applied to every provider HTTP client built afterwards. Only the
+/// first call has an effect.
+pub fn set_provider_tls(config: ProviderTlsConfig) {
+    if PROVIDER_TLS.set(config).is_err() {
+        tracing::warn!("Provider TLS policy is already set, ignoring the new one");
+    }
+}
+
+pub fn provider_tls() -> Option<&'static ProviderTlsConfig> {
+    PROVIDER_TLS.get()
+}
+
+/// Applies `config` to `builder`. With pinned certificates the connection is rejected
+/// unless the server presents one of them.
+pub fn apply_tls(
+    builder: reqwest::ClientBuilder,
+    config: &ProviderTlsConfig,
+) -> Result<reqwest::ClientBuilder, String> {
+    let fingerprints = config.fingerprints()?;
+    if fingerprints.is_empty() {
+        return Ok(match config.min_version {
+            Some(TlsVersion::Tls12) => builder.min_tls_version(reqwest::tls::Version::TLS_1_2),
+            Some(TlsVersion::Tls13) => builder.min_tls_version(reqwest::tls::Version::TLS_1_3),
+            None => builder,
+        });
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let versions = match config.min_version {
+        Some(TlsVersion::Tls13) => vec![&rustls::version::TLS13],
+        _ => rustls::ALL_VERSIONS.to_vec(),
+    };
+    let tls = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&versions)
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier {
+            fingerprints,
+            provider,
+        }))
+        .with_no_client_auth();
+    Ok(builder.use_preconfigured_tls(tls))
+}
+
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    fingerprints: Vec<String>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let digest = Sha256::digest(end_entity.as_ref());
+        let fingerprint: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+        if self.fingerprints.contains(&fingerprint) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            tracing::warn!("Rejected provider certificate {fingerprint}, it is not pinned");
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelInstance;
+    use base64::Engine;
+    use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+    use std::io::{Read, Write};
+
+    const CERT: &str = "MIIBnDCCAUGgAwIBAgIUIYzgfETrGH7TNj+QBGZMXd+9jM0wCgYIKoZIzj0EAwIwFDESMBAGA1UEAwwJbG9jYWxob3N0MCAXDTI2MTAxNjAyNTY0NFoYDzIxMjYwOTIyMDI1NjQ0WjAUMRIwEAYDVQQDDAlsb2NhbGhvc3QwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAARfsulA4JfoWF5lHgK0mta+FVEN2aJCOfDJtPF6Vu+AcU+mJGLlx4ZkAj+dVLFaoBqJUps6m0PwLXFXU6qBpnzHo28wbTAdBgNVHQ4EFgQUKLP9BMm16VM2nYtpgvNax0u0XjwwHwYDVR0jBBgwFoAUKLP9BMm16VM2nYtpgvNax0u0XjwwDwYDVR0TAQH/BAUwAwEB/zAaBgNVHREEEzARgglsb2NhbGhvc3SHBH8AAAEwCgYIKoZIzj0EAwIDSQAwRgIhANhzthwi3VFxx+VJN1kWRMy4eg5A0aQtfeRz/hi6PqjoAiEAu5IMr+7R1vIhFMmjObvzQC4DlZmG90W+nzEuazYrGi4=";
+    const KEY: &str = "MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQgRWaDrXBcEZd4/r3MgBawKxB75PjoOcvvRqYw+v7SNxqhRANCAARfsulA4JfoWF5lHgK0mta+FVEN2aJCOfDJtPF6Vu+AcU+mJGLlx4ZkAj+dVLFaoBqJUps6m0PwLXFXU6qBpnzH";
+
+    fn decode(der: &str) -> Vec<u8> {
+        base64::engine::general_purpose::STANDARD
+            .decode(der)
+            .unwrap()
+    }
+
+    /// Serves a single HTTPS request with the self-signed test certificate, answering `body`
+    fn tls_server(versions: &[&'static SupportedProtocolVersion], body: &'static str) -> String {
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_protocol_versions(versions)
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![CertificateDer::from(decode(CERT))],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(decode(KEY))),
+        )
+        .unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "https://127.0.0.1:{}",
+            listener.local_addr().unwrap().port()
+        );
+        std::thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let connection = rustls::ServerConnection::new(Arc::new(config)).unwrap();
+            let mut stream = rustls::StreamOwned::new(connection, socket);
+            let mut received = vec![];
+            let mut buf = [0u8; 1024];
+            while !received.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => received.extend_from_slice(&buf[..n]),
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes());
+            let _ = stream.flush();
+        });
+        url
+    }
+
+    fn client(config: &ProviderTlsConfig) -> reqwest::Client {
+        apply_tls(reqwest::Client::builder(), config)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn fingerprint() -> String {
+        Sha256::digest(decode(CERT))
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    #[tokio::test]
+    async fn test_pinned_certificate_is_accepted() {
+        let url = tls_server(rustls::ALL_VERSIONS, "ok");
+        let config = ProviderTlsConfig {
+            min_version: Some(TlsVersion::Tls12),
+            pinned_certificates: vec![fingerprint()],
+        };
+        let response = client(&config).get(&url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_certificate_not_pinned_is_rejected() {
+        let url = tls_server(rustls::ALL_VERSIONS, "ok");
+        let config = ProviderTlsConfig {
+            min_version: None,
+            pinned_certificates: vec!["ab".repeat(32)],
+        };
+        assert!(client(&config).get(&url).send().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_older_tls_version_is_rejected() {
+        let url = tls_server(&[&rustls::version::TLS12], "ok");
+        let config = ProviderTlsConfig {
+            min_version: Some(TlsVersion::Tls13),
+            pinned_certificates: vec![fingerprint()],
+        };
+        assert!(client(&config).get(&url).send().await.is_err());
+    }
+
+    /// Sets the process wide policy, no other test may set it
+    #[tokio::test]
+    async fn test_policy_applies_to_provider_clients() {
+        set_provider_tls(ProviderTlsConfig {
+            min_version: Some(TlsVersion::Tls13),
+            pinned_certificates: vec![fingerprint()],
+        });
+        let embedding = r#"{"object":"list","data":[{"object":"embedding","embedding":[0.5],"index":0}],"usage":{"prompt_tokens":1,"total_tokens":1}}"#;
+        let model = |endpoint: String| {
+            crate::model::ollama::OllamaModel::new(
+                crate::types::engine::OllamaModelParams {
+                    model: Some("nomic-embed-text".to_string()),
+                    ..Default::default()
+                },
+                Default::default(),
+                None,
+                Some(endpoint),
+            )
+            .unwrap()
+        };
+        let input = || async_openai::types::EmbeddingInput::String("Paris".to_string());
+
+        // The self-signed certificate is only trusted because it is pinned
+        let url = tls_server(&[&rustls::version::TLS13], embedding);
+        let response = model(url).embed(input()).await.unwrap();
+        assert_eq!(response.data[0].embedding, vec![0.5]);
+
+        let url = tls_server(&[&rustls::version::TLS12], embedding);
+        assert!(model(url).embed(input()).await.is_err());
+
+        assert!(crate::model::bedrock::bedrock_client(None, None)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_invalid_fingerprint() {
+        let config = ProviderTlsConfig {
+            min_version: None,
+            pinned_certificates: vec!["sha256:abc".to_string()],
+        };
+        assert!(config.validate().is_err());
+        assert!(ProviderTlsConfig {
+            min_version: None,
+            pinned_certificates: vec![fingerprint()],
+        }
+        .validate()
+        .is_ok());
+    }
+}
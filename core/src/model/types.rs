@@ -1,4 +1,6 @@
-use crate::types::gateway::{CompletionModelUsage, ImageSize};
+use crate::types::gateway::{
+    ChatCompletionLogprobs, ChatCompletionResponse, CompletionModelUsage, ImageSize,
+};
 use chrono::{DateTime, Utc};
 use opentelemetry::trace::TraceContextExt;
 use serde::{Deserialize, Serialize};
@@ -45,8 +47,11 @@ pub enum ModelEventType {
     LlmFirstToken(LLMFirstToken),
     LlmContent(LLMContentEvent),
     LlmStop(LLMFinishEvent),
+    /// The complete response of a streamed completion, assembled once the stream ends
+    LlmResponse(Box<ChatCompletionResponse>),
     ToolStart(ToolStartEvent),
     ToolResult(ToolResultEvent),
+    ToolEnd(ToolEndEvent),
     ImageGenerationFinish(ImageGenerationFinishEvent),
     Custom(CustomEvent),
 }
@@ -59,8 +64,10 @@ impl ModelEventType {
             ModelEventType::LlmStart(_) => "llm_start",
             ModelEventType::LlmContent(_) => "llm_content",
             ModelEventType::LlmStop(_) => "llm_stop",
+            ModelEventType::LlmResponse(_) => "llm_response",
             ModelEventType::ToolStart(_) => "tool_start",
             ModelEventType::ToolResult(_) => "tool_result",
+            ModelEventType::ToolEnd(_) => "tool_end",
             ModelEventType::ImageGenerationFinish(_) => "image_generation_finish",
             ModelEventType::LlmFirstToken(_) => "llm_first_token",
             ModelEventType::Custom(_) => "custom",
@@ -90,6 +97,9 @@ impl ModelEvent {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LLMContentEvent {
     pub content: String,
+    /// Choice the content belongs to when several are generated
+    #[serde(default)]
+    pub index: u32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -114,6 +124,14 @@ pub struct LLMFinishEvent {
     pub finish_reason: ModelFinishReason,
     pub tool_calls: Vec<ModelToolCall>,
     pub credentials_ident: CredentialsIdent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<ChatCompletionLogprobs>,
+    /// Processing tier the provider served the request with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+    /// Id the provider assigned to the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -166,6 +184,17 @@ pub struct ToolResultEvent {
     pub output: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolEndEvent {
+    pub tool_id: String,
+    pub tool_name: String,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub is_error: bool,
+    pub duration_ms: u64,
+}
+
 pub struct ModelToolResult {
     pub tool_id: String,
     pub tool_name: String,
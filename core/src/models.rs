@@ -2,6 +2,7 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
+use crate::types::engine::ExecutionOptions;
 use crate::types::provider::{CompletionModelPrice, InferenceModelProvider, ModelPrice};
 
 use std::str::FromStr;
@@ -273,6 +274,51 @@ pub struct ModelMetadata {
     pub parameters: Option<serde_json::Value>,
     #[serde(default)]
     pub virtual_model_id: Option<String>,
+    /// Default retries, timeout and backoff for this model. Request values take precedence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_options: Option<ExecutionOptions>,
+    /// JSON schema chat completions of this model are constrained to and validated against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
+    /// Whether tool calls are supported, assumed when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supports_tools: Option<bool>,
+    /// Whether image inputs are supported, assumed when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supports_vision: Option<bool>,
+    /// Whether `response_format` JSON modes are supported, assumed when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supports_json: Option<bool>,
+    /// Overrides `limits.max_context_size`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_context: Option<u32>,
+    /// Stop sequences added to every chat completion of this model
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    /// Embedding size requested when an embeddings request doesn't set `dimensions`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u16>,
+}
+
+impl ModelMetadata {
+    // `capabilities` and `input_formats` are incomplete for most models, so only an
+    // explicit `false` rules a feature out
+    pub fn supports_tools(&self) -> bool {
+        self.supports_tools.unwrap_or(true)
+    }
+
+    pub fn supports_vision(&self) -> bool {
+        self.supports_vision.unwrap_or(true)
+    }
+
+    pub fn supports_json(&self) -> bool {
+        self.supports_json.unwrap_or(true)
+    }
+
+    /// Context window in tokens, `0` when unknown
+    pub fn max_context(&self) -> u32 {
+        self.max_context.unwrap_or(self.limits.max_context_size)
+    }
 }
 
 impl Default for ModelMetadata {
@@ -288,6 +334,7 @@ impl Default for ModelMetadata {
             price: ModelPrice::Completion(CompletionModelPrice {
                 per_input_token: 0.0,
                 per_output_token: 0.0,
+                per_reasoning_token: None,
                 valid_from: None,
             }),
             input_formats: Vec::new(),
@@ -298,6 +345,14 @@ impl Default for ModelMetadata {
             description: "".to_string(),
             parameters: None,
             virtual_model_id: None,
+            execution_options: None,
+            response_schema: None,
+            supports_tools: None,
+            supports_vision: None,
+            supports_json: None,
+            max_context: None,
+            stop_sequences: Vec::new(),
+            dimensions: None,
         }
     }
 }
@@ -0,0 +1,229 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::GatewayResult;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceCompression {
+    #[default]
+    None,
+    Gzip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceBatchConfig {
+    /// Number of spans written per batch
+    #[serde(default = "default_batch_size")]
+    pub size: usize,
+    /// Partial batches are written after this interval
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Batches written concurrently, further flushes wait for one of them to finish
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+    /// Compression applied to a batch before it is handed to the transport
+    #[serde(default)]
+    pub compression: TraceCompression,
+    /// Retries of a failed batch insert
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled on every further retry
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// File that batches are appended to once all retries failed
+    #[serde(default)]
+    pub dead_letter_path: Option<String>,
+}
+
+const MAX_BATCH_SIZE: usize = 100_000;
+const MIN_FLUSH_INTERVAL_MS: u64 = 10;
+const MAX_FLUSH_INTERVAL_MS: u64 = 600_000;
+
+fn default_batch_size() -> usize {
+    1000
+}
+
+fn default_flush_interval_ms() -> u64 {
+    1000
+}
+
+fn default_max_in_flight() -> usize {
+    1
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+impl Default for TraceBatchConfig {
+    fn default() -> Self {
+        Self {
+            size: default_batch_size(),
+            flush_interval_ms: default_flush_interval_ms(),
+            max_in_flight: default_max_in_flight(),
+            compression: TraceCompression::default(),
+            max_retries: default_max_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+            dead_letter_path: None,
+        }
+    }
+}
+
+impl TraceBatchConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_BATCH_SIZE).contains(&self.size) {
+            return Err(format!(
+                "traces batch size must be between 1 and {MAX_BATCH_SIZE}, got {}",
+                self.size
+            ));
+        }
+        if !(MIN_FLUSH_INTERVAL_MS..=MAX_FLUSH_INTERVAL_MS).contains(&self.flush_interval_ms) {
+            return Err(format!(
+                "traces flush_interval_ms must be between {MIN_FLUSH_INTERVAL_MS} and {MAX_FLUSH_INTERVAL_MS}, got {}",
+                self.flush_interval_ms
+            ));
+        }
+        if self.max_in_flight == 0 {
+            return Err("traces max_in_flight must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_millis(self.flush_interval_ms.max(1))
+    }
+
+    pub fn retry_backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.retry_backoff_ms.saturating_mul(1 << retry.min(16)))
+    }
+}
+
+/// Rows of a span batch, gzip batches hold the rows encoded as `JSONCompactEachRow`,
+/// one JSON array per line
+#[derive(Debug, Clone)]
+pub enum SpanBatch {
+    Rows(Vec<Vec<Value>>),
+    Gzip { rows: usize, payload: Vec<u8> },
+}
+
+impl SpanBatch {
+    pub fn encode(rows: Vec<Vec<Value>>, compression: TraceCompression) -> GatewayResult<Self> {
+        match compression {
+            TraceCompression::None => Ok(Self::Rows(rows)),
+            TraceCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                for row in &rows {
+                    serde_json::to_writer(&mut encoder, row)?;
+                    encoder.write_all(b"\n")?;
+                }
+                Ok(Self::Gzip {
+                    rows: rows.len(),
+                    payload: encoder.finish()?,
+                })
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Rows(rows) => rows.len(),
+            Self::Gzip { rows, .. } => *rows,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn into_rows(self) -> GatewayResult<Vec<Vec<Value>>> {
+        match self {
+            Self::Rows(rows) => Ok(rows),
+            Self::Gzip { payload, .. } => {
+                let mut json = String::new();
+                GzDecoder::new(payload.as_slice()).read_to_string(&mut json)?;
+                Ok(json
+                    .lines()
+                    .map(serde_json::from_str)
+                    .collect::<Result<_, _>>()?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows() -> Vec<Vec<Value>> {
+        (0..50)
+            .map(|i| vec![json!(i), json!("openai"), json!({"input": "a".repeat(100)})])
+            .collect()
+    }
+
+    #[test]
+    fn test_gzip_roundtrip() {
+        let batch = SpanBatch::encode(rows(), TraceCompression::Gzip).unwrap();
+        let SpanBatch::Gzip { payload, .. } = &batch else {
+            panic!("Expected gzip batch");
+        };
+        assert!(payload.len() < serde_json::to_vec(&rows()).unwrap().len());
+        let mut text = String::new();
+        GzDecoder::new(payload.as_slice())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(
+            text.lines().next().unwrap(),
+            serde_json::to_string(&rows()[0]).unwrap()
+        );
+        assert_eq!(batch.len(), 50);
+        assert_eq!(batch.into_rows().unwrap(), rows());
+    }
+
+    #[test]
+    fn test_batch_config_bounds() {
+        assert!(TraceBatchConfig::default().validate().is_ok());
+
+        for config in [
+            TraceBatchConfig {
+                size: 0,
+                ..Default::default()
+            },
+            TraceBatchConfig {
+                size: MAX_BATCH_SIZE + 1,
+                ..Default::default()
+            },
+            TraceBatchConfig {
+                flush_interval_ms: 0,
+                ..Default::default()
+            },
+            TraceBatchConfig {
+                flush_interval_ms: MAX_FLUSH_INTERVAL_MS + 1,
+                ..Default::default()
+            },
+            TraceBatchConfig {
+                max_in_flight: 0,
+                ..Default::default()
+            },
+        ] {
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+    }
+
+    #[test]
+    fn test_uncompressed_batch() {
+        let batch = SpanBatch::encode(rows(), TraceCompression::None).unwrap();
+        assert!(matches!(batch, SpanBatch::Rows(_)));
+        assert_eq!(batch.into_rows().unwrap(), rows());
+    }
+}
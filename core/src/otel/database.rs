@@ -1,4 +1,5 @@
 use crate::database::DatabaseTransport;
+use crate::otel::batch::SpanBatch;
 use crate::otel::SpanWriterTransport;
 use crate::GatewayError;
 use crate::GatewayResult;
@@ -27,4 +28,20 @@ impl SpanWriterTransport for DatabaseSpanWritter {
             .await
             .map_err(|e| GatewayError::CustomError(e.to_string()))
     }
+
+    async fn insert_batch(
+        &self,
+        table_name: &str,
+        columns: &[&str],
+        batch: SpanBatch,
+    ) -> GatewayResult<String> {
+        match batch {
+            SpanBatch::Rows(rows) => self.insert_values(table_name, columns, rows).await,
+            SpanBatch::Gzip { payload, .. } => self
+                .transport
+                .insert_gzip(table_name, columns, payload)
+                .await
+                .map_err(|e| GatewayError::CustomError(e.to_string())),
+        }
+    }
 }
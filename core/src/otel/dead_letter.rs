@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::otel::SpanWriterTransport;
+use crate::{GatewayError, GatewayResult};
+
+/// Appends batches that could not be written as JSON lines of `{"table", "columns", "rows"}`
+pub struct FileDeadLetterTransport {
+    path: PathBuf,
+}
+
+impl FileDeadLetterTransport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait::async_trait]
+impl SpanWriterTransport for FileDeadLetterTransport {
+    async fn insert_values(
+        &self,
+        table_name: &str,
+        columns: &[&str],
+        body: Vec<Vec<Value>>,
+    ) -> GatewayResult<String> {
+        let mut line = serde_json::to_vec(&json!({
+            "table": table_name,
+            "columns": columns,
+            "rows": body,
+        }))?;
+        line.push(b'\n');
+
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?
+                .write_all(&line)
+        })
+        .await
+        .map_err(|e| GatewayError::CustomError(e.to_string()))??;
+
+        Ok("".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_appends_batches() {
+        let path = std::env::temp_dir().join(format!("spans-{}.jsonl", uuid::Uuid::new_v4()));
+        let transport = FileDeadLetterTransport::new(&path);
+
+        for i in 0..2 {
+            transport
+                .insert_values("langdb.traces", &["span_id"], vec![vec![json!(i)]])
+                .await
+                .unwrap();
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines = content
+            .lines()
+            .map(|l| serde_json::from_str::<Value>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["table"], "langdb.traces");
+        assert_eq!(lines[1]["columns"], json!(["span_id"]));
+        assert_eq!(lines[1]["rows"], json!([[1]]));
+    }
+}
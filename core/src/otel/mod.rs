@@ -1,10 +1,13 @@
+pub mod batch;
 #[cfg(feature = "database")]
 pub mod database;
+pub mod dead_letter;
 
+use crate::otel::batch::{SpanBatch, TraceBatchConfig};
+use crate::otel::dead_letter::FileDeadLetterTransport;
 use crate::types::GatewayTenant;
 use std::collections::HashMap;
 use std::str::FromStr;
-use std::time::Duration;
 use std::{future::Ready, sync::Arc};
 
 use crate::GatewayResult;
@@ -32,10 +35,10 @@ use opentelemetry_proto::tonic::{
     trace::v1::span as otel_span,
 };
 use opentelemetry_sdk::propagation::TraceContextPropagator;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::Value;
 use tokio::select;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, Semaphore};
 use tonic::metadata::MetadataMap;
 use uuid::Uuid;
 
@@ -75,17 +78,116 @@ pub trait SpanWriterTransport: Send + Sync {
         columns: &[&str],
         body: Vec<Vec<Value>>,
     ) -> GatewayResult<String>;
+
+    /// Inserts a possibly compressed batch. Transports that cannot send compressed
+    /// payloads decode the batch and insert the rows.
+    async fn insert_batch(
+        &self,
+        table_name: &str,
+        columns: &[&str],
+        batch: SpanBatch,
+    ) -> GatewayResult<String> {
+        self.insert_values(table_name, columns, batch.into_rows()?)
+            .await
+    }
+}
+
+const TRACES_TABLE: &str = "langdb.traces";
+
+/// Span attributes that may hold large payloads
+const LARGE_ATTRIBUTES: [&str; 4] = ["input", "output", "request", "response"];
+const TRUNCATION_MARKER: &str = "...";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TraceStorageConfig {
+    /// Maximum length of input/output attributes, longer values are truncated
+    #[serde(default)]
+    pub max_attribute_length: Option<usize>,
+    /// Store the full value of truncated attributes in the `attribute_blobs` column
+    #[serde(default)]
+    pub blob_column: bool,
+    #[serde(default)]
+    pub batch: TraceBatchConfig,
+}
+
+impl TraceStorageConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.batch.validate()
+    }
+
+    /// Truncates large attributes and returns the original values of truncated ones
+    pub fn compact_attributes(
+        &self,
+        attributes: &mut serde_json::Map<String, Value>,
+    ) -> serde_json::Map<String, Value> {
+        let mut blobs = serde_json::Map::new();
+        let Some(max_length) = self.max_attribute_length else {
+            return blobs;
+        };
+
+        for key in LARGE_ATTRIBUTES {
+            let Some(Value::String(value)) = attributes.get(key) else {
+                continue;
+            };
+            if value.chars().count() <= max_length {
+                continue;
+            }
+
+            let truncated = format!(
+                "{}{TRUNCATION_MARKER}",
+                value.chars().take(max_length).collect::<String>()
+            );
+            if let Some(original) = attributes.insert(key.to_string(), truncated.into()) {
+                blobs.insert(key.to_string(), original);
+            }
+        }
+
+        if !blobs.is_empty() {
+            attributes.insert("truncated".to_string(), true.into());
+        }
+        blobs
+    }
 }
 
 pub(crate) struct SpanWriter {
-    pub(crate) transport: Box<dyn SpanWriterTransport>,
+    pub(crate) transport: Arc<dyn SpanWriterTransport>,
     pub(crate) receiver: tokio::sync::mpsc::Receiver<Span>,
     pub(crate) buf: Vec<Vec<Value>>,
     pub(crate) trace_senders: Arc<TraceMap>,
     pub(crate) finished_traces: Vec<TraceId>,
+    pub(crate) storage_config: TraceStorageConfig,
+    pub(crate) in_flight: Arc<Semaphore>,
+    pub(crate) dead_letter: Option<Arc<dyn SpanWriterTransport>>,
 }
 
 impl SpanWriter {
+    pub(crate) fn new(
+        transport: Arc<dyn SpanWriterTransport>,
+        receiver: mpsc::Receiver<Span>,
+        trace_senders: Arc<TraceMap>,
+        storage_config: TraceStorageConfig,
+    ) -> Self {
+        let dead_letter = storage_config.batch.dead_letter_path.as_ref().map(|path| {
+            Arc::new(FileDeadLetterTransport::new(path)) as Arc<dyn SpanWriterTransport>
+        });
+        Self {
+            transport,
+            receiver,
+            buf: Default::default(),
+            trace_senders,
+            finished_traces: Default::default(),
+            in_flight: Arc::new(Semaphore::new(storage_config.batch.max_in_flight.max(1))),
+            dead_letter,
+            storage_config,
+        }
+    }
+
+    /// Sends batches that failed all retries to `transport` instead of the configured file
+    pub(crate) fn with_dead_letter(mut self, transport: Arc<dyn SpanWriterTransport>) -> Self {
+        self.dead_letter = Some(transport);
+        self
+    }
+
     pub(crate) fn process(&mut self, span: Span) {
         let Span {
             trace_id,
@@ -96,7 +198,7 @@ impl SpanWriter {
             start_time_unix_nano,
             end_time_unix_nano,
             kind: span_kind,
-            attributes,
+            mut attributes,
             tenant_id,
             project_id,
             thread_id,
@@ -106,7 +208,8 @@ impl SpanWriter {
         if parent_span_id.is_none() {
             self.finished_traces.push(trace_id);
         }
-        self.buf.push(vec![
+        let blobs = self.storage_config.compact_attributes(&mut attributes);
+        let mut row = vec![
             trace_id_uuid(trace_id).to_string().into(),
             parent_trace_id.map_or(Value::Null, |trace_id| {
                 trace_id_uuid(trace_id).to_string().into()
@@ -137,49 +240,79 @@ impl SpanWriter {
             thread_id.into(),
             tags.into(),
             run_id.into(),
-        ]);
+        ];
+        if self.storage_config.blob_column {
+            row.push(blobs.into());
+        }
+        self.buf.push(row);
     }
 
     pub(crate) async fn flush(&mut self) {
         if self.buf.is_empty() {
             return;
         }
-        let result = self
-            .transport
-            .insert_values(
-                "langdb.traces",
-                &[
-                    "trace_id",
-                    "parent_trace_id",
-                    "span_id",
-                    "parent_span_id",
-                    "operation_name",
-                    "start_time_us",
-                    "finish_time_us",
-                    "finish_date",
-                    "kind",
-                    "attribute",
-                    "tenant_id",
-                    "project_id",
-                    "thread_id",
-                    "tags",
-                    "run_id",
-                ],
-                self.buf.clone(),
-            )
-            .await;
-        if let Err(e) = result {
-            tracing::error!("{e}");
-        }
-        // Once we've written the full trace, we can safely drop the sender
-        for trace_id in self.finished_traces.drain(..) {
-            self.trace_senders.remove(&trace_id);
+        let mut columns = vec![
+            "trace_id",
+            "parent_trace_id",
+            "span_id",
+            "parent_span_id",
+            "operation_name",
+            "start_time_us",
+            "finish_time_us",
+            "finish_date",
+            "kind",
+            "attribute",
+            "tenant_id",
+            "project_id",
+            "thread_id",
+            "tags",
+            "run_id",
+        ];
+        if self.storage_config.blob_column {
+            columns.push("attribute_blobs");
         }
-        self.buf.clear();
+
+        // Waits while `max_in_flight` batches are being written
+        let Ok(permit) = self.in_flight.clone().acquire_owned().await else {
+            return;
+        };
+        let rows = std::mem::take(&mut self.buf);
+        let finished_traces = std::mem::take(&mut self.finished_traces);
+        let transport = self.transport.clone();
+        let dead_letter = self.dead_letter.clone();
+        let trace_senders = self.trace_senders.clone();
+        let batch_config = self.storage_config.batch.clone();
+
+        tokio::spawn(async move {
+            match SpanBatch::encode(rows, batch_config.compression) {
+                Ok(batch) => {
+                    write_batch(
+                        transport.as_ref(),
+                        dead_letter.as_deref(),
+                        &batch_config,
+                        &columns,
+                        batch,
+                    )
+                    .await
+                }
+                Err(e) => tracing::error!("{e}"),
+            }
+            // Once we've written the full trace, we can safely drop the sender
+            for trace_id in finished_traces {
+                trace_senders.remove(&trace_id);
+            }
+            drop(permit);
+        });
+    }
+
+    /// Waits for all in flight batches to be written
+    pub(crate) async fn drain(&self) {
+        let permits = self.storage_config.batch.max_in_flight.max(1) as u32;
+        let _ = self.in_flight.acquire_many(permits).await;
     }
 
     pub(crate) async fn run(mut self) {
-        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        let mut interval = tokio::time::interval(self.storage_config.batch.flush_interval());
         loop {
             select! {
                 span = self.receiver.recv() => {
@@ -187,7 +320,7 @@ impl SpanWriter {
                         break;
                     };
                     self.process(span);
-                    if self.buf.len() > 1000 {
+                    if self.buf.len() >= self.storage_config.batch.size {
                         self.flush().await
                     }
                 }
@@ -199,6 +332,46 @@ impl SpanWriter {
         while let Some(span) = self.receiver.recv().await {
             self.process(span);
         }
+        self.flush().await;
+        self.drain().await;
+    }
+}
+
+/// Inserts a batch, retrying with backoff and handing it to the dead letter transport
+/// once all retries failed
+async fn write_batch(
+    transport: &dyn SpanWriterTransport,
+    dead_letter: Option<&dyn SpanWriterTransport>,
+    config: &TraceBatchConfig,
+    columns: &[&str],
+    batch: SpanBatch,
+) {
+    let mut retry = 0;
+    loop {
+        match transport
+            .insert_batch(TRACES_TABLE, columns, batch.clone())
+            .await
+        {
+            Ok(_) => return,
+            Err(e) if retry < config.max_retries => {
+                tracing::warn!("Failed to write {} spans, retrying: {e}", batch.len());
+                tokio::time::sleep(config.retry_backoff(retry)).await;
+                retry += 1;
+            }
+            Err(e) => {
+                tracing::error!("Failed to write {} spans: {e}", batch.len());
+                break;
+            }
+        }
+    }
+
+    match dead_letter {
+        Some(dead_letter) => {
+            if let Err(e) = dead_letter.insert_batch(TRACES_TABLE, columns, batch).await {
+                tracing::error!("Failed to write spans to dead letter transport: {e}");
+            }
+        }
+        None => tracing::error!("No dead letter transport configured, spans are dropped"),
     }
 }
 
@@ -216,15 +389,15 @@ impl TraceServiceImpl {
         project_trace_senders: Arc<ProjectTraceMap>,
         transport: Box<dyn SpanWriterTransport>,
         tenant_resolver: Box<dyn TraceTenantResolver>,
+        storage_config: TraceStorageConfig,
     ) -> Self {
         let (sender, receiver) = mpsc::channel(1000);
-        let writer = SpanWriter {
-            trace_senders: Arc::clone(&listener_senders),
-            transport,
+        let writer = SpanWriter::new(
+            Arc::from(transport),
             receiver,
-            finished_traces: Default::default(),
-            buf: Default::default(),
-        };
+            Arc::clone(&listener_senders),
+            storage_config,
+        );
         tokio::spawn(writer.run());
         Self {
             listener_senders,
@@ -612,3 +785,322 @@ impl Extractor for HeaderExtractor<'_> {
         self.0.keys().map(|header| header.as_str()).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn attributes() -> serde_json::Map<String, Value> {
+        json!({
+            "input": "a".repeat(20),
+            "output": "short",
+            "model_name": "b".repeat(20),
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    #[test]
+    fn test_truncate_attributes() {
+        let config = TraceStorageConfig {
+            max_attribute_length: Some(10),
+            blob_column: false,
+            ..Default::default()
+        };
+        let mut attributes = attributes();
+        let blobs = config.compact_attributes(&mut attributes);
+
+        assert_eq!(attributes["input"], json!(format!("{}...", "a".repeat(10))));
+        assert_eq!(attributes["output"], json!("short"));
+        assert_eq!(attributes["model_name"], json!("b".repeat(20)));
+        assert_eq!(attributes["truncated"], json!(true));
+        assert_eq!(blobs["input"], json!("a".repeat(20)));
+        assert_eq!(blobs.len(), 1);
+    }
+
+    #[test]
+    fn test_attributes_within_limit() {
+        let config = TraceStorageConfig {
+            max_attribute_length: Some(20),
+            blob_column: true,
+            ..Default::default()
+        };
+        let mut attributes = attributes();
+        let blobs = config.compact_attributes(&mut attributes);
+
+        assert_eq!(attributes, self::attributes());
+        assert!(!attributes.contains_key("truncated"));
+        assert!(blobs.is_empty());
+
+        let mut attributes = self::attributes();
+        TraceStorageConfig::default().compact_attributes(&mut attributes);
+        assert_eq!(attributes, self::attributes());
+    }
+
+    #[derive(Default)]
+    struct RecordedBatches {
+        sizes: std::sync::Mutex<Vec<usize>>,
+        started: std::sync::atomic::AtomicUsize,
+        in_flight: std::sync::atomic::AtomicUsize,
+        max_in_flight: std::sync::atomic::AtomicUsize,
+        /// Number of inserts that fail before inserts succeed
+        failures: std::sync::atomic::AtomicUsize,
+    }
+
+    struct RecordingTransport {
+        recorded: Arc<RecordedBatches>,
+        delay: std::time::Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl SpanWriterTransport for RecordingTransport {
+        async fn insert_values(
+            &self,
+            _table_name: &str,
+            _columns: &[&str],
+            body: Vec<Vec<Value>>,
+        ) -> GatewayResult<String> {
+            use std::sync::atomic::Ordering;
+
+            let recorded = &self.recorded;
+            recorded.started.fetch_add(1, Ordering::SeqCst);
+            let failing = recorded
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |f| f.checked_sub(1))
+                .is_ok();
+            if failing {
+                return Err(crate::GatewayError::CustomError(
+                    "Insert failed".to_string(),
+                ));
+            }
+            let current = recorded.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            recorded.max_in_flight.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            recorded.sizes.lock().unwrap().push(body.len());
+            recorded.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok("".to_string())
+        }
+    }
+
+    fn span(i: u64) -> Span {
+        Span {
+            trace_id: TraceId::from_bytes(1u128.to_be_bytes()),
+            parent_trace_id: None,
+            span_id: SpanId::from_bytes((i + 1).to_be_bytes()),
+            parent_span_id: Some(SpanId::from_bytes(1000u64.to_be_bytes())),
+            operation_name: "test".to_string(),
+            kind: SpanKind::Internal,
+            start_time_unix_nano: 1_000_000,
+            end_time_unix_nano: 2_000_000,
+            attributes: Default::default(),
+            tenant_id: None,
+            project_id: None,
+            thread_id: None,
+            tags: Default::default(),
+            run_id: None,
+        }
+    }
+
+    fn start_writer(
+        batch: TraceBatchConfig,
+        delay_ms: u64,
+    ) -> (
+        mpsc::Sender<Span>,
+        tokio::task::JoinHandle<()>,
+        Arc<RecordedBatches>,
+    ) {
+        let recorded = Arc::new(RecordedBatches::default());
+        let transport = RecordingTransport {
+            recorded: recorded.clone(),
+            delay: std::time::Duration::from_millis(delay_ms),
+        };
+        let (sender, receiver) = mpsc::channel(100);
+        let writer = SpanWriter::new(
+            Arc::new(transport),
+            receiver,
+            Default::default(),
+            TraceStorageConfig {
+                batch,
+                ..Default::default()
+            },
+        );
+        (sender, tokio::spawn(writer.run()), recorded)
+    }
+
+    #[tokio::test]
+    async fn test_flush_at_batch_size() {
+        let (sender, handle, recorded) = start_writer(
+            TraceBatchConfig {
+                size: 3,
+                flush_interval_ms: 60_000,
+                max_in_flight: 4,
+                ..Default::default()
+            },
+            0,
+        );
+        for i in 0..7 {
+            sender.send(span(i)).await.unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(*recorded.sizes.lock().unwrap(), vec![3, 3]);
+
+        // Remaining spans are written when the writer shuts down
+        drop(sender);
+        handle.await.unwrap();
+        assert_eq!(*recorded.sizes.lock().unwrap(), vec![3, 3, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_flush_at_interval() {
+        let (sender, _handle, recorded) = start_writer(
+            TraceBatchConfig {
+                size: 100,
+                flush_interval_ms: 50,
+                ..Default::default()
+            },
+            0,
+        );
+        sender.send(span(0)).await.unwrap();
+        sender.send(span(1)).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(*recorded.sizes.lock().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_flush_at_configured_size_and_interval() {
+        let (sender, _handle, recorded) = start_writer(
+            TraceBatchConfig {
+                size: 2,
+                flush_interval_ms: 150,
+                ..Default::default()
+            },
+            0,
+        );
+        // The first tick flushes nothing, spans sent after it wait for the size or next tick
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        for i in 0..3 {
+            sender.send(span(i)).await.unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(*recorded.sizes.lock().unwrap(), vec![2]);
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(*recorded.sizes.lock().unwrap(), vec![2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_max_in_flight_backpressure() {
+        use std::sync::atomic::Ordering;
+
+        let (sender, handle, recorded) = start_writer(
+            TraceBatchConfig {
+                size: 1,
+                flush_interval_ms: 60_000,
+                max_in_flight: 2,
+                ..Default::default()
+            },
+            100,
+        );
+        for i in 0..6 {
+            sender.send(span(i)).await.unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        // Two batches are being written, the rest wait for a free slot
+        assert_eq!(recorded.started.load(Ordering::SeqCst), 2);
+        assert!(recorded.sizes.lock().unwrap().is_empty());
+
+        drop(sender);
+        handle.await.unwrap();
+        assert_eq!(*recorded.sizes.lock().unwrap(), vec![1; 6]);
+        assert_eq!(recorded.max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_batch_reaches_transport() {
+        let (sender, handle, recorded) = start_writer(
+            TraceBatchConfig {
+                size: 2,
+                compression: batch::TraceCompression::Gzip,
+                ..Default::default()
+            },
+            0,
+        );
+        for i in 0..4 {
+            sender.send(span(i)).await.unwrap();
+        }
+        drop(sender);
+        handle.await.unwrap();
+        assert_eq!(*recorded.sizes.lock().unwrap(), vec![2, 2]);
+    }
+
+    fn failing_writer(
+        failures: usize,
+    ) -> (
+        mpsc::Sender<Span>,
+        tokio::task::JoinHandle<()>,
+        Arc<RecordedBatches>,
+        Arc<RecordedBatches>,
+    ) {
+        let recorded = Arc::new(RecordedBatches {
+            failures: failures.into(),
+            ..Default::default()
+        });
+        let dead_letter = Arc::new(RecordedBatches::default());
+        let (sender, receiver) = mpsc::channel(100);
+        let writer = SpanWriter::new(
+            Arc::new(RecordingTransport {
+                recorded: recorded.clone(),
+                delay: Default::default(),
+            }),
+            receiver,
+            Default::default(),
+            TraceStorageConfig {
+                batch: TraceBatchConfig {
+                    size: 2,
+                    max_retries: 3,
+                    retry_backoff_ms: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .with_dead_letter(Arc::new(RecordingTransport {
+            recorded: dead_letter.clone(),
+            delay: Default::default(),
+        }));
+        (sender, tokio::spawn(writer.run()), recorded, dead_letter)
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed_insert() {
+        use std::sync::atomic::Ordering;
+
+        let (sender, handle, recorded, dead_letter) = failing_writer(2);
+        sender.send(span(0)).await.unwrap();
+        sender.send(span(1)).await.unwrap();
+        drop(sender);
+        handle.await.unwrap();
+
+        assert_eq!(recorded.started.load(Ordering::SeqCst), 3);
+        assert_eq!(*recorded.sizes.lock().unwrap(), vec![2]);
+        assert!(dead_letter.sizes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_after_retries() {
+        use std::sync::atomic::Ordering;
+
+        let (sender, handle, recorded, dead_letter) = failing_writer(usize::MAX);
+        sender.send(span(0)).await.unwrap();
+        sender.send(span(1)).await.unwrap();
+        drop(sender);
+        handle.await.unwrap();
+
+        // The first attempt and 3 retries
+        assert_eq!(recorded.started.load(Ordering::SeqCst), 4);
+        assert!(recorded.sizes.lock().unwrap().is_empty());
+        assert_eq!(*dead_letter.sizes.lock().unwrap(), vec![2]);
+    }
+}
@@ -1,6 +1,7 @@
+use crate::pricing::currency::USD;
 use crate::types::{
     gateway::{
-        CompletionModelUsage, CostCalculationResult, ImageCostCalculationResult,
+        CompletionModelUsage, CostBreakdown, CostCalculationResult, ImageCostCalculationResult,
         ImageGenerationModelUsage,
     },
     provider::ImageGenerationPrice,
@@ -25,11 +26,13 @@ pub fn calculate_image_price(
             per_input_token: 0.0,
             per_output_token: 0.0,
             is_cache_used: false,
+            currency: USD.to_string(),
             per_image_cost: Some(ImageCostCalculationResult::TypePrice {
                 size: size.clone(),
                 quality: usage.quality.clone(),
                 per_image: type_price,
             }),
+            breakdown: None,
         }
     } else if let Some(cost) = p.mp_price {
         let total_mp = (usage.size.0 as f64 * usage.size.1 as f64 * usage.images_count as f64)
@@ -40,7 +43,9 @@ pub fn calculate_image_price(
             per_input_token: 0.0,
             per_output_token: 0.0,
             is_cache_used: false,
+            currency: USD.to_string(),
             per_image_cost: Some(ImageCostCalculationResult::MPPrice(cost)),
+            breakdown: None,
         }
     } else {
         tracing::warn!("Image model pricing are not set");
@@ -50,29 +55,157 @@ pub fn calculate_image_price(
             per_input_token: 0.0,
             per_output_token: 0.0,
             is_cache_used: false,
+            currency: USD.to_string(),
             per_image_cost: Some(ImageCostCalculationResult::SingleImagePrice(price)),
+            breakdown: None,
         }
     }
 }
 
 pub fn calculate_tokens_cost(
+    usage: &CompletionModelUsage,
+    cost_per_input_token: f64,
+    cost_per_output_token: f64,
+) -> CostCalculationResult {
+    calculate_tokens_cost_with_reasoning(usage, cost_per_input_token, cost_per_output_token, None)
+}
+
+/// Like `calculate_tokens_cost`, with reasoning tokens billed at `cost_per_reasoning_token`
+/// instead of the output rate when it is set
+pub fn calculate_tokens_cost_with_reasoning(
     usage: &CompletionModelUsage,
     mut cost_per_input_token: f64,
     mut cost_per_output_token: f64,
+    cost_per_reasoning_token: Option<f64>,
 ) -> CostCalculationResult {
+    let mut cost_per_reasoning_token = cost_per_reasoning_token.unwrap_or(cost_per_output_token);
+    let uncached_cost = tokens_cost(
+        usage,
+        cost_per_input_token,
+        cost_per_output_token,
+        Some(cost_per_reasoning_token),
+    );
     if usage.is_cache_used {
         cost_per_input_token /= 100.0;
         cost_per_output_token /= 100.0;
+        cost_per_reasoning_token /= 100.0;
     }
 
     let input_cost = cost_per_input_token * usage.input_tokens as f64 * 1e-6;
-    let output_cost = cost_per_output_token * usage.output_tokens as f64 * 1e-6;
+    let output_cost = cost_per_output_token * visible_output_tokens(usage) as f64 * 1e-6;
+    let reasoning_cost = cost_per_reasoning_token * usage.reasoning_tokens as f64 * 1e-6;
+    let cost = input_cost + output_cost + reasoning_cost;
 
     CostCalculationResult {
-        cost: input_cost + output_cost,
+        cost,
         per_input_token: cost_per_input_token,
         per_output_token: cost_per_output_token,
         per_image_cost: None,
         is_cache_used: usage.is_cache_used,
+        currency: USD.to_string(),
+        breakdown: Some(CostBreakdown {
+            input_cost,
+            output_cost,
+            reasoning_cost,
+            tool_calls_cost: 0.0,
+            cache_savings: uncached_cost - cost,
+        }),
+    }
+}
+
+/// Cost of `usage` at the given prices per million tokens, without any cache discount
+pub fn tokens_cost(
+    usage: &CompletionModelUsage,
+    cost_per_input_token: f64,
+    cost_per_output_token: f64,
+    cost_per_reasoning_token: Option<f64>,
+) -> f64 {
+    let cost_per_reasoning_token = cost_per_reasoning_token.unwrap_or(cost_per_output_token);
+    (cost_per_input_token * usage.input_tokens as f64
+        + cost_per_output_token * visible_output_tokens(usage) as f64
+        + cost_per_reasoning_token * usage.reasoning_tokens as f64)
+        * 1e-6
+}
+
+/// Providers count reasoning tokens as part of the output
+fn visible_output_tokens(usage: &CompletionModelUsage) -> u32 {
+    usage.output_tokens.saturating_sub(usage.reasoning_tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(is_cache_used: bool) -> CompletionModelUsage {
+        CompletionModelUsage {
+            input_tokens: 2_000_000,
+            output_tokens: 500_000,
+            total_tokens: 2_500_000,
+            prompt_tokens_details: None,
+            completion_tokens_details: None,
+            is_cache_used,
+            reasoning_tokens: 0,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_breakdown_sums_to_total() {
+        let result = calculate_tokens_cost(&usage(false), 2.5, 10.0);
+        let breakdown = result.breakdown.unwrap();
+        assert_close(breakdown.input_cost, 5.0);
+        assert_close(breakdown.output_cost, 5.0);
+        assert_close(breakdown.reasoning_cost, 0.0);
+        assert_close(breakdown.tool_calls_cost, 0.0);
+        assert_close(breakdown.cache_savings, 0.0);
+        assert_close(breakdown.total(), result.cost);
+
+        // Output of a completion calling tools moves to the tool calls, the total stays
+        let breakdown = breakdown.with_tool_calls();
+        assert_close(breakdown.output_cost, 0.0);
+        assert_close(breakdown.tool_calls_cost, 5.0);
+        assert_close(breakdown.total(), result.cost);
+    }
+
+    #[test]
+    fn test_cache_reads_are_priced_lower() {
+        let uncached = calculate_tokens_cost(&usage(false), 2.5, 10.0);
+        let cached = calculate_tokens_cost(&usage(true), 2.5, 10.0);
+        assert!(cached.cost < uncached.cost);
+
+        let breakdown = cached.breakdown.unwrap();
+        assert_close(breakdown.input_cost, 0.05);
+        assert_close(breakdown.output_cost, 0.05);
+        assert_close(breakdown.total(), cached.cost);
+        assert_close(breakdown.cache_savings, uncached.cost - cached.cost);
+    }
+
+    #[test]
+    fn test_reasoning_tokens_are_priced_separately() {
+        let usage = CompletionModelUsage {
+            reasoning_tokens: 200_000,
+            ..usage(false)
+        };
+
+        // Without a reasoning rate they cost as much as any output token
+        let result = calculate_tokens_cost(&usage, 2.5, 10.0);
+        let breakdown = result.breakdown.unwrap();
+        assert_close(breakdown.output_cost, 3.0);
+        assert_close(breakdown.reasoning_cost, 2.0);
+        assert_close(result.cost, 10.0);
+
+        let result = calculate_tokens_cost_with_reasoning(&usage, 2.5, 10.0, Some(4.0));
+        let breakdown = result.breakdown.unwrap();
+        assert_close(breakdown.input_cost, 5.0);
+        assert_close(breakdown.output_cost, 3.0);
+        assert_close(breakdown.reasoning_cost, 0.8);
+        assert_close(breakdown.total(), result.cost);
+        assert_close(result.cost, 8.8);
     }
 }
@@ -0,0 +1,374 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::handler::DollarUsage;
+use crate::types::gateway::{
+    CostBreakdown, CostCalculationResult, CostCalculator, CostCalculatorError,
+    ImageCostCalculationResult, Usage,
+};
+
+/// Currency costs are calculated in
+pub const USD: &str = "USD";
+
+fn default_refresh_interval_secs() -> u64 {
+    3600
+}
+
+#[derive(Debug, Error)]
+pub enum CurrencyError {
+    #[error("Failed to fetch exchange rate: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("Exchange rate for {0} not found in response")]
+    RateNotFound(String),
+}
+
+/// Reports costs in `code` instead of USD
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyConfig {
+    /// Currency code, e.g. `EUR`
+    pub code: String,
+    /// Units of `code` per USD. Used until the first refresh from `rate_url` succeeds.
+    #[serde(default)]
+    pub rate: Option<f64>,
+    /// Endpoint returning either `{"rate": 0.92}` or `{"rates": {"EUR": 0.92}}`
+    #[serde(default)]
+    pub rate_url: Option<String>,
+    #[serde(default = "default_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+}
+
+impl CurrencyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rate.is_none() && self.rate_url.is_none() {
+            return Err(format!(
+                "currency {} needs either a rate or a rate_url",
+                self.code
+            ));
+        }
+        match self.rate {
+            Some(rate) if !(rate.is_finite() && rate > 0.0) => {
+                Err(format!("currency rate must be positive, got {rate}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Converts USD amounts into the configured currency. Amounts stay in USD while no
+/// exchange rate is known.
+#[derive(Debug, Clone)]
+pub struct CurrencyConverter {
+    code: String,
+    rate: Arc<RwLock<Option<f64>>>,
+}
+
+impl Default for CurrencyConverter {
+    fn default() -> Self {
+        Self::usd()
+    }
+}
+
+impl CurrencyConverter {
+    pub fn usd() -> Self {
+        Self {
+            code: USD.to_string(),
+            rate: Arc::new(RwLock::new(Some(1.0))),
+        }
+    }
+
+    pub fn new(config: &CurrencyConfig) -> Self {
+        Self {
+            code: config.code.to_uppercase(),
+            rate: Arc::new(RwLock::new(config.rate)),
+        }
+    }
+
+    pub fn set_rate(&self, rate: f64) {
+        *self.rate.write() = Some(rate);
+    }
+
+    fn rate(&self) -> Option<f64> {
+        *self.rate.read()
+    }
+
+    pub fn currency(&self) -> &str {
+        match self.rate() {
+            Some(_) => &self.code,
+            None => USD,
+        }
+    }
+
+    pub fn convert(&self, usd: f64) -> f64 {
+        usd * self.rate().unwrap_or(1.0)
+    }
+
+    pub fn convert_cost(&self, result: CostCalculationResult) -> CostCalculationResult {
+        let per_image_cost = result.per_image_cost.map(|cost| match cost {
+            ImageCostCalculationResult::SingleImagePrice(price) => {
+                ImageCostCalculationResult::SingleImagePrice(self.convert(price))
+            }
+            ImageCostCalculationResult::MPPrice(price) => {
+                ImageCostCalculationResult::MPPrice(self.convert(price))
+            }
+            ImageCostCalculationResult::TypePrice {
+                size,
+                quality,
+                per_image,
+            } => ImageCostCalculationResult::TypePrice {
+                size,
+                quality,
+                per_image: self.convert(per_image),
+            },
+        });
+
+        CostCalculationResult {
+            cost: self.convert(result.cost),
+            per_input_token: self.convert(result.per_input_token),
+            per_output_token: self.convert(result.per_output_token),
+            per_image_cost,
+            is_cache_used: result.is_cache_used,
+            currency: self.currency().to_string(),
+            breakdown: result.breakdown.map(|breakdown| CostBreakdown {
+                input_cost: self.convert(breakdown.input_cost),
+                output_cost: self.convert(breakdown.output_cost),
+                reasoning_cost: self.convert(breakdown.reasoning_cost),
+                tool_calls_cost: self.convert(breakdown.tool_calls_cost),
+                cache_savings: self.convert(breakdown.cache_savings),
+            }),
+        }
+    }
+
+    pub fn convert_usage(&self, usage: DollarUsage) -> DollarUsage {
+        DollarUsage {
+            daily: self.convert(usage.daily),
+            daily_limit: usage.daily_limit.map(|l| self.convert(l)),
+            monthly: self.convert(usage.monthly),
+            monthly_limit: usage.monthly_limit.map(|l| self.convert(l)),
+            total: self.convert(usage.total),
+            total_limit: usage.total_limit.map(|l| self.convert(l)),
+            currency: self.currency().to_string(),
+        }
+    }
+
+    fn parse_rate(&self, response: &Value) -> Result<f64, CurrencyError> {
+        response
+            .get("rates")
+            .and_then(|rates| rates.get(&self.code))
+            .or_else(|| response.get("rate"))
+            .and_then(Value::as_f64)
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .ok_or_else(|| CurrencyError::RateNotFound(self.code.clone()))
+    }
+
+    pub async fn refresh(&self, client: &reqwest::Client, url: &str) -> Result<f64, CurrencyError> {
+        let response: Value = client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let rate = self.parse_rate(&response)?;
+        self.set_rate(rate);
+        Ok(rate)
+    }
+
+    /// Refreshes the rate from `rate_url` every `refresh_interval_secs`
+    pub fn spawn_refresh(&self, config: &CurrencyConfig) {
+        let Some(url) = config.rate_url.clone() else {
+            return;
+        };
+        let converter = self.clone();
+        let interval = Duration::from_secs(config.refresh_interval_secs.max(1));
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match converter.refresh(&client, &url).await {
+                    Ok(rate) => {
+                        tracing::debug!("{} exchange rate refreshed: {rate}", converter.code)
+                    }
+                    Err(e) => tracing::warn!("Failed to refresh exchange rate: {e}"),
+                }
+            }
+        });
+    }
+}
+
+/// Reports the costs of `inner` in the converter's currency
+pub struct CurrencyCostCalculator<C: CostCalculator> {
+    inner: C,
+    converter: CurrencyConverter,
+}
+
+impl<C: CostCalculator> CurrencyCostCalculator<C> {
+    pub fn new(inner: C, converter: CurrencyConverter) -> Self {
+        Self { inner, converter }
+    }
+}
+
+#[async_trait::async_trait]
+impl<C: CostCalculator> CostCalculator for CurrencyCostCalculator<C> {
+    async fn calculate_cost(
+        &self,
+        model_name: &str,
+        provider_name: &str,
+        usage: &Usage,
+    ) -> Result<CostCalculationResult, CostCalculatorError> {
+        self.inner
+            .calculate_cost(model_name, provider_name, usage)
+            .await
+            .map(|result| self.converter.convert_cost(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::calculator::calculate_tokens_cost;
+    use crate::types::gateway::CompletionModelUsage;
+    use serde_json::json;
+
+    struct FixedCostCalculator;
+
+    #[async_trait::async_trait]
+    impl CostCalculator for FixedCostCalculator {
+        async fn calculate_cost(
+            &self,
+            _model_name: &str,
+            _provider_name: &str,
+            usage: &Usage,
+        ) -> Result<CostCalculationResult, CostCalculatorError> {
+            match usage {
+                Usage::CompletionModelUsage(usage) => Ok(calculate_tokens_cost(usage, 2.0, 4.0)),
+                _ => Err(CostCalculatorError::ModelNotFound),
+            }
+        }
+    }
+
+    fn eur(rate: Option<f64>) -> CurrencyConfig {
+        CurrencyConfig {
+            code: "eur".to_string(),
+            rate,
+            rate_url: Some("http://localhost/rates".to_string()),
+            refresh_interval_secs: 60,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cost_is_converted() {
+        let calculator = CurrencyCostCalculator::new(
+            FixedCostCalculator,
+            CurrencyConverter::new(&eur(Some(0.5))),
+        );
+        let usage = Usage::CompletionModelUsage(CompletionModelUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 1_000_000,
+            total_tokens: 2_000_000,
+            ..Default::default()
+        });
+
+        let result = calculator
+            .calculate_cost("gpt-4o", "openai", &usage)
+            .await
+            .unwrap();
+        assert_close(result.cost, 3.0);
+        assert_close(result.per_input_token, 1.0);
+        assert_close(result.per_output_token, 2.0);
+        assert_eq!(result.currency, "EUR");
+
+        let serialized = serde_json::to_value(&result).unwrap();
+        assert_eq!(serialized["currency"], "EUR");
+    }
+
+    #[tokio::test]
+    async fn test_usd_is_default() {
+        let usage = Usage::CompletionModelUsage(CompletionModelUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 1_000_000,
+            total_tokens: 2_000_000,
+            ..Default::default()
+        });
+        let result = FixedCostCalculator
+            .calculate_cost("gpt-4o", "openai", &usage)
+            .await
+            .unwrap();
+        assert_eq!(result.currency, USD);
+
+        let converter = CurrencyConverter::default();
+        let result = converter.convert_cost(result);
+        assert_close(result.cost, 6.0);
+        assert_eq!(result.currency, USD);
+
+        // Without a rate amounts stay in USD until the first refresh
+        let converter = CurrencyConverter::new(&eur(None));
+        assert_eq!(converter.currency(), USD);
+        assert_close(converter.convert(6.0), 6.0);
+        converter.set_rate(0.9);
+        assert_eq!(converter.currency(), "EUR");
+        assert_close(converter.convert(6.0), 5.4);
+    }
+
+    #[test]
+    fn test_usage_is_converted() {
+        let converter = CurrencyConverter::new(&eur(Some(2.0)));
+        let usage = converter.convert_usage(DollarUsage {
+            daily: 1.0,
+            daily_limit: Some(10.0),
+            monthly: 5.0,
+            monthly_limit: None,
+            total: 7.5,
+            total_limit: Some(100.0),
+            currency: USD.to_string(),
+        });
+
+        assert_close(usage.daily, 2.0);
+        assert_eq!(usage.daily_limit, Some(20.0));
+        assert_close(usage.monthly, 10.0);
+        assert_eq!(usage.monthly_limit, None);
+        assert_close(usage.total, 15.0);
+        assert_eq!(usage.total_limit, Some(200.0));
+        assert_eq!(usage.currency, "EUR");
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        let converter = CurrencyConverter::new(&eur(None));
+        assert_eq!(
+            converter
+                .parse_rate(&json!({"rates": {"EUR": 0.92, "GBP": 0.79}}))
+                .unwrap(),
+            0.92
+        );
+        assert_eq!(converter.parse_rate(&json!({"rate": 0.91})).unwrap(), 0.91);
+        assert!(converter
+            .parse_rate(&json!({"rates": {"GBP": 0.79}}))
+            .is_err());
+        assert!(converter.parse_rate(&json!({"rate": -1.0})).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(eur(Some(0.9)).validate().is_ok());
+        assert!(eur(None).validate().is_ok());
+        assert!(eur(Some(0.0)).validate().is_err());
+        let config = CurrencyConfig {
+            rate_url: None,
+            ..eur(None)
+        };
+        assert!(config.validate().is_err());
+    }
+}
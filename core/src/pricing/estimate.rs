@@ -0,0 +1,247 @@
+use crate::models::ModelMetadata;
+use crate::pricing::currency::CurrencyConverter;
+use crate::types::gateway::{
+    ChatCompletionRequest, CompletionModelUsage, CostCalculator, CostCalculatorError, Usage,
+};
+use crate::GatewayApiError;
+
+/// Rejects single requests whose projected cost exceeds this amount in USD, like the
+/// other cost limits
+#[derive(Debug, Clone, Copy)]
+pub struct RequestCostCeiling(pub f64);
+
+/// Rough token count of `text`, about four characters per token
+pub(crate) fn estimate_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(4) as u32
+}
+
+/// Projects the usage of a request before it is sent. Output is assumed to use all of
+/// `max_tokens` for every choice, requests without `max_tokens` only account for input.
+pub fn estimate_usage(request: &ChatCompletionRequest) -> CompletionModelUsage {
+    let prompt = serde_json::to_string(&request.messages).unwrap_or_default();
+    let tools = request
+        .tools
+        .as_ref()
+        .and_then(|tools| serde_json::to_string(tools).ok())
+        .unwrap_or_default();
+    let input_tokens = estimate_tokens(&prompt) + estimate_tokens(&tools);
+    let output_tokens = request
+        .max_tokens
+        .unwrap_or(0)
+        .saturating_mul(request.n.unwrap_or(1).max(1));
+
+    CompletionModelUsage {
+        input_tokens,
+        output_tokens,
+        total_tokens: input_tokens.saturating_add(output_tokens),
+        ..Default::default()
+    }
+}
+
+/// Usage of a completion whose provider did not report it, estimated from the text sent
+/// and received
+pub fn estimate_completion_usage(input: &str, output: &str) -> CompletionModelUsage {
+    let input_tokens = estimate_tokens(input);
+    let output_tokens = estimate_tokens(output);
+
+    CompletionModelUsage {
+        input_tokens,
+        output_tokens,
+        total_tokens: input_tokens.saturating_add(output_tokens),
+        ..Default::default()
+    }
+}
+
+/// Fails when the estimated cost of `request` on `model` is above `ceiling`. `calculator`
+/// reports costs in the currency of `currency`, so the ceiling is converted into it before
+/// comparing. Models without a price are not checked.
+pub async fn check_request_cost(
+    calculator: &dyn CostCalculator,
+    model: &ModelMetadata,
+    request: &ChatCompletionRequest,
+    ceiling: RequestCostCeiling,
+    currency: &CurrencyConverter,
+) -> Result<(), GatewayApiError> {
+    let usage = Usage::CompletionModelUsage(estimate_usage(request));
+    let estimated = match calculator
+        .calculate_cost(
+            &model.inference_provider.model_name,
+            &model.inference_provider.provider.to_string(),
+            &usage,
+        )
+        .await
+    {
+        Ok(result) => result.cost,
+        Err(CostCalculatorError::ModelNotFound) => {
+            tracing::warn!(
+                "No price for {}, skipping the per request cost check",
+                model.model
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let limit = currency.convert(ceiling.0);
+    if estimated > limit {
+        return Err(GatewayApiError::RequestCostLimit { estimated, limit });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::InferenceProvider;
+    use crate::pricing::calculator::calculate_tokens_cost;
+    use crate::pricing::currency::{CurrencyConfig, CurrencyCostCalculator};
+    use crate::types::gateway::{ChatCompletionMessage, CostCalculationResult};
+    use crate::types::provider::InferenceModelProvider;
+
+    /// $10 per million input tokens and $30 per million output tokens
+    struct ExpensiveModel;
+
+    #[async_trait::async_trait]
+    impl CostCalculator for ExpensiveModel {
+        async fn calculate_cost(
+            &self,
+            model_name: &str,
+            provider_name: &str,
+            usage: &Usage,
+        ) -> Result<CostCalculationResult, CostCalculatorError> {
+            match usage {
+                Usage::CompletionModelUsage(usage)
+                    if model_name == "gpt-4" && provider_name == "openai" =>
+                {
+                    Ok(calculate_tokens_cost(usage, 10.0, 30.0))
+                }
+                _ => Err(CostCalculatorError::ModelNotFound),
+            }
+        }
+    }
+
+    fn model() -> ModelMetadata {
+        ModelMetadata {
+            model: "gpt-4".to_string(),
+            inference_provider: InferenceProvider {
+                provider: InferenceModelProvider::OpenAI,
+                model_name: "gpt-4".to_string(),
+                endpoint: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn request(max_tokens: Option<u32>) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: "openai/gpt-4".to_string(),
+            messages: vec![ChatCompletionMessage::new_text(
+                "user".to_string(),
+                "Hello!".to_string(),
+            )],
+            max_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_estimate_usage() {
+        let mut two_choices = request(Some(100));
+        two_choices.n = Some(2);
+        let usage = estimate_usage(&two_choices);
+        assert!(usage.input_tokens > 0);
+        assert_eq!(usage.output_tokens, 200);
+        assert_eq!(usage.total_tokens, usage.input_tokens + 200);
+
+        let usage = estimate_usage(&request(None));
+        assert_eq!(usage.output_tokens, 0);
+    }
+
+    #[tokio::test]
+    async fn test_large_request_is_rejected() {
+        // 100k output tokens cost about $3
+        let result = check_request_cost(
+            &ExpensiveModel,
+            &model(),
+            &request(Some(100_000)),
+            RequestCostCeiling(1.0),
+            &CurrencyConverter::usd(),
+        )
+        .await;
+
+        match result {
+            Err(GatewayApiError::RequestCostLimit { estimated, limit }) => {
+                assert!(estimated > 3.0);
+                assert_eq!(limit, 1.0);
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_small_request_proceeds() {
+        check_request_cost(
+            &ExpensiveModel,
+            &model(),
+            &request(Some(100)),
+            RequestCostCeiling(1.0),
+            &CurrencyConverter::usd(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unpriced_model_is_not_checked() {
+        let mut unpriced = model();
+        unpriced.inference_provider.model_name = "llama3".to_string();
+        check_request_cost(
+            &ExpensiveModel,
+            &unpriced,
+            &request(Some(100_000)),
+            RequestCostCeiling(1.0),
+            &CurrencyConverter::usd(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ceiling_is_compared_in_reported_currency() {
+        let currency = CurrencyConverter::new(&CurrencyConfig {
+            code: "JPY".to_string(),
+            rate: Some(150.0),
+            rate_url: None,
+            refresh_interval_secs: 3600,
+        });
+        let calculator = CurrencyCostCalculator::new(ExpensiveModel, currency.clone());
+
+        // 10k output tokens cost $0.30, below the $1 ceiling but above 1 JPY
+        check_request_cost(
+            &calculator,
+            &model(),
+            &request(Some(10_000)),
+            RequestCostCeiling(1.0),
+            &currency,
+        )
+        .await
+        .unwrap();
+
+        let result = check_request_cost(
+            &calculator,
+            &model(),
+            &request(Some(100_000)),
+            RequestCostCeiling(1.0),
+            &currency,
+        )
+        .await;
+        match result {
+            Err(GatewayApiError::RequestCostLimit { estimated, limit }) => {
+                assert!(estimated > 450.0);
+                assert_eq!(limit, 150.0);
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+    }
+}
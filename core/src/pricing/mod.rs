@@ -1 +1,3 @@
 pub mod calculator;
+pub mod currency;
+pub mod estimate;
@@ -1,16 +1,27 @@
-use crate::events::SPAN_OPENAI;
+use crate::error::GatewayError;
+use crate::events::bodies::BodyRecordingMode;
+use crate::events::{JsonValue, SPAN_OPENAI};
 use crate::model::error::ModelError;
 use crate::model::openai::openai_client;
-use crate::model::types::ModelEvent;
-use crate::model::CredentialsIdent;
+use crate::model::types::{
+    LLMContentEvent, LLMFinishEvent, LLMFirstToken, LLMStartEvent, ModelEvent, ModelEventType,
+    ModelFinishReason,
+};
+use crate::model::{http_client, CredentialsIdent};
 use crate::types::credentials::ApiKeyCredentials;
+use crate::types::engine::ExecutionOptions;
+use crate::types::gateway::CompletionModelUsage;
 use crate::GatewayResult;
-use async_openai::config::OpenAIConfig;
+use async_openai::config::{Config, OpenAIConfig};
 use async_openai::types::responses::CreateResponse;
 use async_openai::types::responses::Response;
 use async_openai::Client;
+use futures::StreamExt;
+use reqwest_eventsource::{Event, EventSource};
+use serde::Deserialize;
 use tracing::Instrument;
 use tracing::{field, Span};
+use valuable::Valuable;
 macro_rules! target {
     () => {
         "langdb::user_tracing::models::openai"
@@ -27,12 +38,95 @@ pub trait Responses: Sync + Send {
         input_text: CreateResponse,
         tx: Option<tokio::sync::mpsc::Sender<Option<ModelEvent>>>,
     ) -> GatewayResult<Response>;
+
+    /// Streams the response, sending an `LlmContent` event per output text delta and
+    /// an `LlmStop` with the usage once the response completes
+    async fn stream(
+        &self,
+        input_text: CreateResponse,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+    ) -> GatewayResult<()>;
+}
+
+/// Subset of the Responses API stream events needed to render the output incrementally
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum ResponseStreamEvent {
+    #[serde(rename = "response.output_text.delta")]
+    OutputTextDelta { delta: String },
+    #[serde(rename = "response.completed")]
+    Completed { response: StreamedResponse },
+    #[serde(rename = "response.incomplete")]
+    Incomplete { response: StreamedResponse },
+    #[serde(rename = "response.failed")]
+    Failed { response: StreamedResponse },
+    #[serde(rename = "error")]
+    Error { message: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamedResponse {
+    #[serde(default)]
+    usage: Option<StreamedUsage>,
+    #[serde(default)]
+    incomplete_details: Option<IncompleteDetails>,
+    #[serde(default)]
+    error: Option<StreamedError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamedUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+    total_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct IncompleteDetails {
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamedError {
+    message: String,
+}
+
+impl StreamedResponse {
+    fn usage(&self) -> Option<CompletionModelUsage> {
+        self.usage.as_ref().map(|u| CompletionModelUsage {
+            input_tokens: u.input_tokens,
+            output_tokens: u.output_tokens,
+            total_tokens: u.total_tokens,
+            ..Default::default()
+        })
+    }
+
+    fn finish_reason(&self) -> ModelFinishReason {
+        match self.incomplete_details.as_ref().map(|d| d.reason.as_str()) {
+            None => ModelFinishReason::Stop,
+            Some("max_output_tokens") => ModelFinishReason::Length,
+            Some("content_filter") => ModelFinishReason::ContentFilter,
+            Some(reason) => ModelFinishReason::Other(reason.to_string()),
+        }
+    }
+}
+
+async fn send_event(
+    tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+    span: &Span,
+    event_type: ModelEventType,
+) -> GatewayResult<()> {
+    tx.send(Some(ModelEvent::new(span, event_type)))
+        .await
+        .map_err(|e| GatewayError::CustomError(e.to_string()))
 }
 
 #[derive(Clone)]
 pub struct OpenAIResponses {
     client: Client<OpenAIConfig>,
-    #[allow(dead_code)]
+    http: reqwest::Client,
     credentials_ident: CredentialsIdent,
 }
 
@@ -41,7 +135,7 @@ impl OpenAIResponses {
         credentials: Option<&ApiKeyCredentials>,
         endpoint: Option<&str>,
     ) -> Result<Self, ModelError> {
-        let client = openai_client(credentials, endpoint)?;
+        let client = openai_client(credentials, endpoint, &ExecutionOptions::default())?;
 
         let credentials_ident = credentials
             .map(|_c| CredentialsIdent::Own)
@@ -49,6 +143,7 @@ impl OpenAIResponses {
 
         Ok(Self {
             client,
+            http: http_client(&ExecutionOptions::default())?,
             credentials_ident,
         })
     }
@@ -63,6 +158,116 @@ impl OpenAIResponses {
 
         Ok(response)
     }
+
+    async fn execute_stream(
+        &self,
+        input: CreateResponse,
+        span: Span,
+        tx: &tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+    ) -> GatewayResult<()> {
+        let mut body = serde_json::to_value(&input)?;
+        body["stream"] = serde_json::Value::Bool(true);
+        let model_name = body["model"].as_str().unwrap_or_default().to_string();
+
+        send_event(
+            tx,
+            &span,
+            ModelEventType::LlmStart(LLMStartEvent {
+                provider_name: SPAN_OPENAI.to_string(),
+                model_name: model_name.clone(),
+                input: serde_json::to_string(&body["input"])?,
+            }),
+        )
+        .await?;
+
+        let config = self.client.config();
+        let request = self
+            .http
+            .post(config.url("/responses"))
+            .headers(config.headers())
+            .json(&body);
+        let mut event_source =
+            EventSource::new(request).map_err(|e| GatewayError::CustomError(e.to_string()))?;
+
+        let mut output = String::new();
+        let mut first_token_received = false;
+        while let Some(event) = event_source.next().await {
+            let message = match event {
+                Ok(Event::Open) => continue,
+                Ok(Event::Message(message)) => message,
+                Err(reqwest_eventsource::Error::StreamEnded) => break,
+                Err(e) => {
+                    event_source.close();
+                    return Err(GatewayError::CustomError(e.to_string()));
+                }
+            };
+
+            match serde_json::from_str::<ResponseStreamEvent>(&message.data)? {
+                ResponseStreamEvent::OutputTextDelta { delta } => {
+                    if !first_token_received {
+                        first_token_received = true;
+                        send_event(tx, &span, ModelEventType::LlmFirstToken(LLMFirstToken {}))
+                            .await?;
+                    }
+                    output.push_str(&delta);
+                    send_event(
+                        tx,
+                        &span,
+                        ModelEventType::LlmContent(LLMContentEvent {
+                            content: delta,
+                            index: 0,
+                        }),
+                    )
+                    .await?;
+                }
+                ResponseStreamEvent::Completed { response }
+                | ResponseStreamEvent::Incomplete { response } => {
+                    event_source.close();
+                    let usage = response.usage();
+                    span.record("output", BodyRecordingMode::current().apply(&output));
+                    if let Some(usage) = &usage {
+                        span.record("usage", JsonValue(&serde_json::to_value(usage)?).as_value());
+                    }
+                    return send_event(
+                        tx,
+                        &span,
+                        ModelEventType::LlmStop(LLMFinishEvent {
+                            provider_name: SPAN_OPENAI.to_string(),
+                            model_name,
+                            output: Some(output),
+                            usage,
+                            finish_reason: response.finish_reason(),
+                            tool_calls: vec![],
+                            credentials_ident: self.credentials_ident.clone(),
+                            logprobs: None,
+                            service_tier: None,
+                            response_id: None,
+                        }),
+                    )
+                    .await;
+                }
+                ResponseStreamEvent::Failed { response } => {
+                    event_source.close();
+                    let message = response
+                        .error
+                        .map(|e| e.message)
+                        .unwrap_or_else(|| "Response failed".to_string());
+                    span.record("error", &message);
+                    return Err(GatewayError::CustomError(message));
+                }
+                ResponseStreamEvent::Error { message } => {
+                    event_source.close();
+                    span.record("error", &message);
+                    return Err(GatewayError::CustomError(message));
+                }
+                ResponseStreamEvent::Other => {}
+            }
+        }
+
+        Err(GatewayError::CustomError(
+            "Response stream ended before completion".to_string(),
+        ))
+    }
 }
 
 impl Responses for OpenAIResponses {
@@ -71,11 +276,94 @@ impl Responses for OpenAIResponses {
         input_text: CreateResponse,
         tx: Option<tokio::sync::mpsc::Sender<Option<ModelEvent>>>,
     ) -> GatewayResult<Response> {
-        let input = serde_json::to_string(&input_text)?;
-        let call_span = tracing::info_span!(target: target!("responses"), SPAN_OPENAI, input = input, output = field::Empty, ttft = field::Empty, error = field::Empty, usage = field::Empty);
+        let input = BodyRecordingMode::current().apply(&serde_json::to_string(&input_text)?);
+        let call_span = tracing::info_span!(target: target!("responses"), SPAN_OPENAI, input = input.as_deref(), output = field::Empty, ttft = field::Empty, error = field::Empty, usage = field::Empty);
 
         self.execute(input_text, call_span.clone(), tx.as_ref())
             .instrument(call_span.clone())
             .await
     }
+    async fn stream(
+        &self,
+        input_text: CreateResponse,
+        tx: tokio::sync::mpsc::Sender<Option<ModelEvent>>,
+    ) -> GatewayResult<()> {
+        let input = BodyRecordingMode::current().apply(&serde_json::to_string(&input_text)?);
+        let call_span = tracing::info_span!(target: target!("responses"), SPAN_OPENAI, input = input.as_deref(), output = field::Empty, ttft = field::Empty, error = field::Empty, usage = field::Empty);
+
+        self.execute_stream(input_text, call_span.clone(), &tx)
+            .instrument(call_span.clone())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    const EVENTS: [&str; 5] = [
+        r#"{"type":"response.created","response":{"id":"resp_1","status":"in_progress"}}"#,
+        r#"{"type":"response.output_text.delta","item_id":"msg_1","output_index":0,"content_index":0,"delta":"Hello"}"#,
+        r#"{"type":"response.output_text.delta","item_id":"msg_1","output_index":0,"content_index":0,"delta":" world"}"#,
+        r#"{"type":"response.output_text.done","item_id":"msg_1","output_index":0,"content_index":0,"text":"Hello world"}"#,
+        r#"{"type":"response.completed","response":{"id":"resp_1","status":"completed","usage":{"input_tokens":5,"output_tokens":2,"total_tokens":7}}}"#,
+    ];
+
+    async fn mock_responses(body: web::Json<serde_json::Value>) -> HttpResponse {
+        assert_eq!(body["stream"], true);
+        let events: String = EVENTS
+            .iter()
+            .map(|event| format!("data: {event}\n\n"))
+            .collect();
+        HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .body(events)
+    }
+
+    #[actix_web::test]
+    async fn test_stream_emits_deltas() {
+        let server =
+            HttpServer::new(|| App::new().route("/v1/responses", web::post().to(mock_responses)))
+                .bind("127.0.0.1:0")
+                .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        let client = OpenAIResponses::new(
+            Some(&ApiKeyCredentials {
+                api_key: "test".to_string(),
+            }),
+            Some(&format!("http://{addr}/v1")),
+        )
+        .unwrap();
+        let request: CreateResponse = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o-mini",
+            "input": "Say hello"
+        }))
+        .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+        client.stream(request, tx).await.unwrap();
+
+        let mut deltas = vec![];
+        let mut stop = None;
+        while let Some(Some(event)) = rx.recv().await {
+            match event.event {
+                ModelEventType::LlmContent(content) => deltas.push(content.content),
+                ModelEventType::LlmStop(finish) => stop = Some(finish),
+                _ => {}
+            }
+        }
+
+        assert_eq!(deltas, vec!["Hello", " world"]);
+        let stop = stop.expect("LlmStop is emitted");
+        assert_eq!(stop.output.as_deref(), Some("Hello world"));
+        assert_eq!(stop.model_name, "gpt-4o-mini");
+        let usage = stop.usage.unwrap();
+        assert_eq!(usage.input_tokens, 5);
+        assert_eq!(usage.output_tokens, 2);
+        assert_eq!(usage.total_tokens, 7);
+        assert!(matches!(stop.finish_reason, ModelFinishReason::Stop));
+    }
 }
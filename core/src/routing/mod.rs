@@ -1,6 +1,6 @@
 // use crate::routing::strategy::script::ScriptError;
 // use crate::routing::strategy::script::ScriptStrategy;
-use crate::types::gateway::ChatCompletionRequest;
+use crate::types::gateway::{ChatCompletionRequest, ChatCompletionRequestWithTools, DynamicRouter};
 use crate::{handler::AvailableModels, usage::ProviderMetrics};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
@@ -32,6 +32,18 @@ pub enum RouterError {
 
     #[error("Target by index not found: {0}")]
     TargetByIndexNotFound(usize),
+
+    #[error("Invalid weights: {0}")]
+    InvalidWeights(String),
+
+    #[error("Router not found: {0}")]
+    UnknownRouter(String),
+
+    #[error("Route target not found: {0}")]
+    UnknownTarget(String),
+
+    #[error("Router {0} resolved no targets")]
+    NoTargets(String),
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
@@ -63,6 +75,10 @@ pub enum RoutingStrategy {
         targets_percentages: Vec<f64>,
     },
     Random,
+    /// Picks a target with a probability proportional to its integer weight
+    Weighted {
+        weights: Vec<u32>,
+    },
     // Script {
     //     script: String,
     //     // js function. Context is passed in parameters
@@ -79,6 +95,7 @@ impl Display for RoutingStrategy {
             RoutingStrategy::Fallback => write!(f, "Fallback"),
             RoutingStrategy::Percentage { .. } => write!(f, "Percentage"),
             RoutingStrategy::Random => write!(f, "Random"),
+            RoutingStrategy::Weighted { .. } => write!(f, "Weighted"),
             RoutingStrategy::Optimized { .. } => write!(f, "Optimized"),
         }
     }
@@ -103,6 +120,118 @@ pub enum TargetOrRouterName {
     Target(Target),
 }
 
+/// Header selecting one of the configured routers by name
+pub const ROUTER_HEADER: &str = "x-router";
+
+/// Header pinning a request to one target of its router
+pub const ROUTE_TARGET_HEADER: &str = "x-route-target";
+
+/// Routers applied to requests for a plain model, keyed by the requested model name
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
+pub struct DefaultRouters(pub HashMap<String, DynamicRouter<RoutingStrategy>>);
+
+impl DefaultRouters {
+    /// Wraps `request` with the default router of its model unless it already sets a
+    /// router. Returns whether a router was applied.
+    pub fn apply(&self, request: &mut ChatCompletionRequestWithTools<RoutingStrategy>) -> bool {
+        if request.router.is_some() {
+            return false;
+        }
+
+        let model = request.request.model.to_lowercase();
+        match self.0.iter().find(|(name, _)| name.to_lowercase() == model) {
+            Some((name, router)) => {
+                let mut router = router.clone();
+                router.name.get_or_insert_with(|| format!("default/{name}"));
+                request.router = Some(router);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Router configured for model `name` or with `name` as its name
+    pub fn find(&self, name: &str) -> Option<DynamicRouter<RoutingStrategy>> {
+        let lowercase = name.to_lowercase();
+        self.0.iter().find_map(|(model, router)| {
+            let matches = model.to_lowercase() == lowercase || router.name.as_deref() == Some(name);
+            matches.then(|| {
+                let mut router = router.clone();
+                router
+                    .name
+                    .get_or_insert_with(|| format!("default/{model}"));
+                router
+            })
+        })
+    }
+}
+
+/// Applies the `x-router` and `x-route-target` overrides. `router` replaces the request's
+/// router with a configured one. `target` pins the request to the target of its router
+/// whose `name` or `model` matches, whatever the router's strategy.
+pub fn apply_route_overrides(
+    request: &mut ChatCompletionRequestWithTools<RoutingStrategy>,
+    routers: Option<&DefaultRouters>,
+    router: Option<&str>,
+    target: Option<&str>,
+) -> Result<(), RouterError> {
+    if let Some(name) = router {
+        let router = routers
+            .and_then(|routers| routers.find(name))
+            .ok_or_else(|| RouterError::UnknownRouter(name.to_string()))?;
+        request.router = Some(router);
+    }
+
+    if let Some(name) = target {
+        let router = request
+            .router
+            .as_mut()
+            .ok_or_else(|| RouterError::UnknownTarget(name.to_string()))?;
+        let pinned = router
+            .targets
+            .iter()
+            .find(|t| {
+                ["name", "model"]
+                    .iter()
+                    .any(|key| t.get(*key).and_then(|v| v.as_str()) == Some(name))
+            })
+            .cloned()
+            .ok_or_else(|| RouterError::UnknownTarget(name.to_string()))?;
+        router.strategy = RoutingStrategy::Fallback;
+        router.targets = vec![pinned];
+    }
+
+    Ok(())
+}
+
+/// Index of the target picked for `weights`, which must have one entry per target
+fn pick_weighted(weights: &[u32], targets: usize) -> Result<usize, RouterError> {
+    use rand::Rng;
+
+    if weights.len() != targets {
+        return Err(RouterError::InvalidWeights(format!(
+            "{} weights for {targets} targets",
+            weights.len()
+        )));
+    }
+    let total: u64 = weights.iter().map(|w| *w as u64).sum();
+    if total == 0 {
+        return Err(RouterError::InvalidWeights(
+            "at least one weight must be positive".to_string(),
+        ));
+    }
+
+    let mut remaining = rand::thread_rng().gen_range(0..total);
+    for (idx, weight) in weights.iter().enumerate() {
+        let weight = *weight as u64;
+        if remaining < weight {
+            return Ok(idx);
+        }
+        remaining -= weight;
+    }
+    Err(RouterError::TargetByIndexNotFound(weights.len()))
+}
+
 #[async_trait::async_trait]
 pub trait RouteStrategy {
     async fn route(
@@ -158,6 +287,10 @@ impl RouteStrategy for LlmRouter {
 
                 Ok(vec![target])
             }
+            RoutingStrategy::Weighted { weights } => {
+                let idx = pick_weighted(weights, self.targets.len())?;
+                Ok(vec![self.targets[idx].clone()])
+            }
             // RoutingStrategy::Script { script } => {
             //     let result =
             //         ScriptStrategy::run(script, &request, &headers, available_models, &metrics)?;
@@ -198,6 +331,233 @@ mod tests {
 
     use super::*;
 
+    #[tokio::test]
+    async fn test_default_router_applies_to_bare_model() {
+        let routers: DefaultRouters = serde_json::from_value(serde_json::json!({
+            "openai/gpt-4o": {
+                "type": "fallback",
+                "targets": [
+                    { "model": "openai/gpt-4o" },
+                    { "model": "azure/gpt-4o" }
+                ]
+            }
+        }))
+        .unwrap();
+
+        let mut request = ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: "openai/gpt-4o".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(routers.apply(&mut request));
+
+        let router = request.router.expect("default router applied");
+        assert_eq!(router.name.as_deref(), Some("default/openai/gpt-4o"));
+        let llm_router = LlmRouter {
+            name: router.name.unwrap(),
+            strategy: router.strategy,
+            targets: router.targets,
+            metrics_duration: None,
+        };
+        let targets = llm_router
+            .route(
+                request.request,
+                &AvailableModels(vec![]),
+                HashMap::new(),
+                BTreeMap::new(),
+            )
+            .await
+            .unwrap();
+        let models: Vec<_> = targets
+            .iter()
+            .map(|t| t["model"].as_str().unwrap())
+            .collect();
+        assert_eq!(models, vec!["openai/gpt-4o", "azure/gpt-4o"]);
+    }
+
+    #[test]
+    fn test_default_router_skips_other_requests() {
+        let routers = DefaultRouters(HashMap::from([(
+            "openai/gpt-4o".to_string(),
+            DynamicRouter {
+                strategy: RoutingStrategy::Fallback,
+                targets: vec![],
+                name: None,
+                failover_on: None,
+            },
+        )]));
+
+        let mut other_model = ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: "openai/gpt-4o-mini".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(!routers.apply(&mut other_model));
+        assert!(other_model.router.is_none());
+
+        let mut explicit_router = ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: "openai/gpt-4o".to_string(),
+                ..Default::default()
+            },
+            router: Some(DynamicRouter {
+                strategy: RoutingStrategy::Random,
+                targets: vec![],
+                name: Some("mine".to_string()),
+                failover_on: None,
+            }),
+            ..Default::default()
+        };
+        assert!(!routers.apply(&mut explicit_router));
+        assert_eq!(
+            explicit_router.router.unwrap().name.as_deref(),
+            Some("mine")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route_target_header_pins_target() {
+        let routers: DefaultRouters = serde_json::from_value(serde_json::json!({
+            "openai/gpt-4o": {
+                "name": "canary",
+                "type": "weighted",
+                "weights": [1, 0],
+                "targets": [
+                    { "model": "openai/gpt-4o" },
+                    { "model": "openai/gpt-4o", "name": "gpt-4o-canary", "temperature": 0.2 }
+                ]
+            }
+        }))
+        .unwrap();
+
+        for _ in 0..20 {
+            let mut request = ChatCompletionRequestWithTools {
+                request: ChatCompletionRequest {
+                    model: "openai/gpt-4o-mini".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            apply_route_overrides(
+                &mut request,
+                Some(&routers),
+                Some("canary"),
+                Some("gpt-4o-canary"),
+            )
+            .unwrap();
+
+            let router = request.router.unwrap();
+            let llm_router = LlmRouter {
+                name: router.name.unwrap(),
+                strategy: router.strategy,
+                targets: router.targets,
+                metrics_duration: None,
+            };
+            let targets = llm_router
+                .route(
+                    request.request,
+                    &AvailableModels(vec![]),
+                    HashMap::new(),
+                    BTreeMap::new(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(targets.len(), 1);
+            assert_eq!(targets[0]["name"], "gpt-4o-canary");
+        }
+    }
+
+    #[test]
+    fn test_unknown_route_overrides_are_rejected() {
+        let routers = DefaultRouters(HashMap::from([(
+            "openai/gpt-4o".to_string(),
+            DynamicRouter {
+                strategy: RoutingStrategy::Random,
+                targets: vec![HashMap::from([(
+                    "model".to_string(),
+                    serde_json::json!("openai/gpt-4o"),
+                )])],
+                name: None,
+                failover_on: None,
+            },
+        )]));
+        let mut request = ChatCompletionRequestWithTools::default();
+
+        assert!(matches!(
+            apply_route_overrides(&mut request, Some(&routers), Some("missing"), None),
+            Err(RouterError::UnknownRouter(_))
+        ));
+        assert!(matches!(
+            apply_route_overrides(&mut request, Some(&routers), None, Some("openai/gpt-4o")),
+            Err(RouterError::UnknownTarget(_))
+        ));
+
+        apply_route_overrides(
+            &mut request,
+            Some(&routers),
+            Some("openai/gpt-4o"),
+            Some("openai/gpt-4o"),
+        )
+        .unwrap();
+        let router = request.router.unwrap();
+        assert_eq!(router.name.as_deref(), Some("default/openai/gpt-4o"));
+        assert!(matches!(router.strategy, RoutingStrategy::Fallback));
+    }
+
+    #[tokio::test]
+    async fn test_weighted_distribution() {
+        let router: LlmRouter = serde_json::from_value(serde_json::json!({
+            "name": "weighted",
+            "type": "weighted",
+            "weights": [1, 3, 0],
+            "targets": [
+                { "model": "openai/gpt-4o" },
+                { "model": "openai/gpt-4o-mini" },
+                { "model": "openai/o1-mini" }
+            ]
+        }))
+        .unwrap();
+
+        let iterations = 10_000;
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..iterations {
+            let targets = router
+                .route(
+                    ChatCompletionRequest::default(),
+                    &AvailableModels(vec![]),
+                    HashMap::new(),
+                    BTreeMap::new(),
+                )
+                .await
+                .unwrap();
+            let model = targets[0]["model"].as_str().unwrap().to_string();
+            *counts.entry(model).or_default() += 1;
+        }
+
+        let share =
+            |model: &str| counts.get(model).copied().unwrap_or(0) as f64 / iterations as f64;
+        assert!((share("openai/gpt-4o") - 0.25).abs() < 0.03);
+        assert!((share("openai/gpt-4o-mini") - 0.75).abs() < 0.03);
+        assert_eq!(share("openai/o1-mini"), 0.0);
+    }
+
+    #[test]
+    fn test_weights_must_match_targets() {
+        assert!(matches!(
+            pick_weighted(&[1, 2], 3),
+            Err(RouterError::InvalidWeights(_))
+        ));
+        assert!(matches!(
+            pick_weighted(&[0, 0], 2),
+            Err(RouterError::InvalidWeights(_))
+        ));
+        assert_eq!(pick_weighted(&[0, 5], 2).unwrap(), 1);
+    }
+
     #[test]
     fn test_serialize() {
         let router = LlmRouter {
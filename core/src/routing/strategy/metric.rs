@@ -134,6 +134,7 @@ mod tests {
             llm_usage: Some(0.05),
             tps: Some(0.1),
             error_rate: Some(0.01),
+            rate_limited: None,
         };
 
         ModelMetrics {
@@ -0,0 +1,249 @@
+//! Fixtures shared by tests across modules and, with the `test-utils` feature, crates
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::executor::context::ExecutorContext;
+use crate::model::tools::Tool;
+use crate::models::{InferenceProvider, ModelMetadata};
+use crate::pricing::calculator::calculate_tokens_cost;
+use crate::types::gateway::{
+    ChatCompletionMessage, CostCalculationResult, CostCalculator, CostCalculatorError,
+    FunctionParameters, Usage,
+};
+use crate::types::guardrails::service::GuardrailsEvaluator;
+use crate::types::guardrails::{GuardResult, GuardStage};
+use crate::types::provider::InferenceModelProvider;
+use crate::{GatewayError, GatewayResult};
+
+/// Answers a single request with `body` and returns the server url
+pub async fn mock_server(content_type: &'static str, body: String) -> String {
+    mock_response(ok_response(content_type, &body)).await
+}
+
+/// Answers every request with `body` and returns the server url
+pub async fn mock_server_repeating(content_type: &'static str, body: String) -> String {
+    let response = ok_response(content_type, &body);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            tokio::spawn(answer(socket, response.clone()));
+        }
+    });
+    url
+}
+
+/// Answers a single request with the raw HTTP `response` and returns the server url
+pub async fn mock_response(response: String) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        answer(socket, response).await;
+    });
+    url
+}
+
+/// Answers a single request with `body` and returns the server url together with the
+/// raw bytes of the received request
+pub async fn recording_server(
+    content_type: &'static str,
+    body: String,
+) -> (String, tokio::sync::oneshot::Receiver<Vec<u8>>) {
+    let response = ok_response(content_type, &body);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let _ = tx.send(answer(socket, response).await);
+    });
+    (url, rx)
+}
+
+fn ok_response(content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Reads the whole request from `socket`, writes `response` back and returns the request
+async fn answer(mut socket: TcpStream, response: String) -> Vec<u8> {
+    let mut received = vec![];
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        received.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&received).to_string();
+        if let Some(headers_end) = text.find("\r\n\r\n") {
+            let content_length = text[..headers_end]
+                .lines()
+                .find_map(|l| {
+                    l.to_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            if received.len() >= headers_end + 4 + content_length || n == 0 {
+                break;
+            }
+        }
+    }
+    socket.write_all(response.as_bytes()).await.unwrap();
+    received
+}
+
+/// Metadata of the `provider` model `name`
+pub fn model(provider: InferenceModelProvider, name: &str) -> ModelMetadata {
+    ModelMetadata {
+        model: name.to_string(),
+        model_provider: provider.to_string(),
+        inference_provider: InferenceProvider {
+            provider,
+            model_name: name.to_string(),
+            endpoint: None,
+        },
+        ..Default::default()
+    }
+}
+
+/// `get_stock` tool whose calls always fail
+pub struct FailingTool;
+
+#[async_trait::async_trait]
+impl Tool for FailingTool {
+    fn name(&self) -> String {
+        "get_stock".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Always fails".to_string()
+    }
+
+    fn get_function_parameters(&self) -> Option<FunctionParameters> {
+        None
+    }
+
+    async fn run(
+        &self,
+        _input: HashMap<String, Value>,
+        _tags: HashMap<String, String>,
+    ) -> GatewayResult<Value> {
+        Err(GatewayError::CustomError(
+            "Quote service unavailable".to_string(),
+        ))
+    }
+}
+
+/// Prices every completion at zero
+pub struct FreeModels;
+
+#[async_trait::async_trait]
+impl CostCalculator for FreeModels {
+    async fn calculate_cost(
+        &self,
+        _model_name: &str,
+        _provider_name: &str,
+        usage: &Usage,
+    ) -> Result<CostCalculationResult, CostCalculatorError> {
+        match usage {
+            Usage::CompletionModelUsage(usage) => Ok(calculate_tokens_cost(usage, 0.0, 0.0)),
+            _ => Err(CostCalculatorError::ModelNotFound),
+        }
+    }
+}
+
+/// Evaluator without any guards configured
+pub struct NoGuards;
+
+#[async_trait::async_trait]
+impl GuardrailsEvaluator for NoGuards {
+    async fn evaluate(
+        &self,
+        _messages: &[ChatCompletionMessage],
+        _guard_id: &str,
+        _executor_context: &ExecutorContext,
+        _parameters: Option<&serde_json::Value>,
+        _guard_stage: &GuardStage,
+    ) -> Result<GuardResult, String> {
+        Err("No guards configured".to_string())
+    }
+}
+
+/// Layer recording the name and fields of every span, values recorded as strings are kept
+/// as is and the rest in their `Debug` form
+#[derive(Clone, Default)]
+pub struct SpanFields(Arc<Mutex<Vec<(u64, String, HashMap<String, String>)>>>);
+
+impl SpanFields {
+    /// Fields of the spans named `name`, in the order they were created
+    pub fn named(&self, name: &str) -> Vec<HashMap<String, String>> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, span_name, _)| span_name == name)
+            .map(|(_, _, fields)| fields.clone())
+            .collect()
+    }
+
+    /// Fields of the first span named `name`
+    pub fn of(&self, name: &str) -> Option<HashMap<String, String>> {
+        self.named(name).into_iter().next()
+    }
+
+    /// Fields of every span, in the order they were created
+    pub fn all(&self) -> Vec<HashMap<String, String>> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, _, fields)| fields.clone())
+            .collect()
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for SpanFields {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+        let mut fields = HashMap::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        self.0
+            .lock()
+            .unwrap()
+            .push((id.into_u64(), attrs.metadata().name().to_string(), fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        // Ids of closed spans are reused, the latest span with the id is the live one
+        let mut spans = self.0.lock().unwrap();
+        if let Some((_, _, fields)) = spans
+            .iter_mut()
+            .rev()
+            .find(|(span_id, _, _)| *span_id == id.into_u64())
+        {
+            values.record(&mut FieldVisitor(fields));
+        }
+    }
+}
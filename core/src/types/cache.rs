@@ -5,6 +5,9 @@ pub struct ResponseCacheOptions {
     pub expiration_time: Option<u32>,
     #[serde(flatten)]
     pub adapter: ResponseCacheAdapter,
+    /// Skip the cache lookup and don't store the completion
+    #[serde(default)]
+    pub bypass: bool,
 }
 
 impl Default for ResponseCacheOptions {
@@ -12,6 +15,7 @@ impl Default for ResponseCacheOptions {
         Self {
             expiration_time: Some(24 * 60 * 60),
             adapter: ResponseCacheAdapter::Exact,
+            bypass: false,
         }
     }
 }
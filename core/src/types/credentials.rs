@@ -3,6 +3,23 @@ use std::collections::HashMap;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Must be set to `true` alongside the `unsafe_log_credentials` config flag before
+/// credential fingerprints are recorded on model spans
+pub const UNSAFE_LOG_CREDENTIALS_ENV: &str = "LANGDB_UNSAFE_LOG_CREDENTIALS";
+
+/// Present in app data when model spans should carry a fingerprint of the credentials
+/// used for the call. Only meant for debugging auth failures locally.
+#[derive(Debug, Clone, Copy)]
+pub struct CredentialFingerprinting;
+
+/// Short sha256 prefix identifying a secret without revealing it
+pub fn credential_fingerprint(secret: &str) -> String {
+    let digest = Sha256::digest(secret.as_bytes());
+    let prefix: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256:{prefix}")
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(untagged)]
@@ -31,6 +48,12 @@ pub struct ApiKeyCredentials {
     pub api_key: String,
 }
 
+impl ApiKeyCredentials {
+    pub fn fingerprint(&self) -> String {
+        credential_fingerprint(&self.api_key)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct AwsCredentials {
@@ -40,9 +63,17 @@ pub struct AwsCredentials {
     pub region: Option<String>,
 }
 
+impl AwsCredentials {
+    pub fn fingerprint(&self) -> String {
+        credential_fingerprint(&format!("{}:{}", self.access_key, self.access_secret))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::types::credentials::{ApiKeyCredentials, Credentials};
+    use crate::types::credentials::{
+        credential_fingerprint, ApiKeyCredentials, AwsCredentials, Credentials,
+    };
 
     #[test]
     fn test_serialization() {
@@ -61,4 +92,25 @@ mod tests {
         let deserialized: Credentials = serde_json::from_str(&serialized).unwrap();
         assert_eq!(credentials, deserialized);
     }
+
+    #[test]
+    fn test_fingerprint_does_not_contain_secret() {
+        let credentials = ApiKeyCredentials {
+            api_key: "sk-very-secret-key".to_string(),
+        };
+        let fingerprint = credentials.fingerprint();
+        assert!(fingerprint.starts_with("sha256:"));
+        assert_eq!(fingerprint.len(), "sha256:".len() + 16);
+        assert!(!fingerprint.contains("sk-very-secret-key"));
+        assert_eq!(fingerprint, credential_fingerprint("sk-very-secret-key"));
+        assert_ne!(fingerprint, credential_fingerprint("sk-other-key"));
+
+        let aws = AwsCredentials {
+            access_key: "AKIA123".to_string(),
+            access_secret: "aws-secret".to_string(),
+            region: None,
+        };
+        assert!(!aws.fingerprint().contains("aws-secret"));
+        assert_ne!(aws.fingerprint(), credential_fingerprint("aws-secret"));
+    }
 }
@@ -2,7 +2,9 @@ use std::borrow::Cow;
 use std::{collections::HashMap, fmt::Display, ops::Deref, str::FromStr};
 
 use crate::types::json::JsonStringCond;
-use async_openai::types::ResponseFormat;
+use async_openai::types::{
+    ChatCompletionAudio, ChatCompletionModalities, PredictionContent, ResponseFormat, ServiceTier,
+};
 use clust::messages as claude;
 use minijinja::Environment;
 use serde::de::IntoDeserializer;
@@ -181,6 +183,54 @@ impl CompletionModelDefinition {
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
 pub struct ExecutionOptions {
     pub max_retries: Option<i32>,
+    /// Maximum number of tool call rounds before the agent loop is stopped, unlimited when unset
+    pub max_tool_call_iterations: Option<u32>,
+    /// Timeout of a single provider call in seconds
+    pub timeout_secs: Option<u64>,
+    /// Delay between retries in milliseconds
+    pub retry_backoff_ms: Option<u64>,
+    /// Timeout of establishing the connection to the provider in milliseconds
+    pub connect_timeout_ms: Option<u64>,
+    /// Longest wait for the next bytes of a provider response in seconds
+    pub read_timeout_secs: Option<u64>,
+    /// Retries of non-streaming completions that succeed without text or tool calls
+    pub empty_completion_retries: Option<u32>,
+}
+
+impl ExecutionOptions {
+    pub fn timeout(&self) -> Option<std::time::Duration> {
+        self.timeout_secs.map(std::time::Duration::from_secs)
+    }
+
+    pub fn retry_backoff(&self) -> Option<std::time::Duration> {
+        self.retry_backoff_ms.map(std::time::Duration::from_millis)
+    }
+
+    pub fn connect_timeout(&self) -> Option<std::time::Duration> {
+        self.connect_timeout_ms
+            .map(std::time::Duration::from_millis)
+    }
+
+    pub fn read_timeout(&self) -> Option<std::time::Duration> {
+        self.read_timeout_secs.map(std::time::Duration::from_secs)
+    }
+
+    /// Values set on `self` take precedence over `defaults`
+    pub fn or(self, defaults: ExecutionOptions) -> Self {
+        Self {
+            max_retries: self.max_retries.or(defaults.max_retries),
+            max_tool_call_iterations: self
+                .max_tool_call_iterations
+                .or(defaults.max_tool_call_iterations),
+            timeout_secs: self.timeout_secs.or(defaults.timeout_secs),
+            retry_backoff_ms: self.retry_backoff_ms.or(defaults.retry_backoff_ms),
+            connect_timeout_ms: self.connect_timeout_ms.or(defaults.connect_timeout_ms),
+            read_timeout_secs: self.read_timeout_secs.or(defaults.read_timeout_secs),
+            empty_completion_retries: self
+                .empty_completion_retries
+                .or(defaults.empty_completion_retries),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -424,6 +474,32 @@ impl CompletionEngineParams {
         }
     }
 
+    pub fn execution_options(&self) -> &ExecutionOptions {
+        match self {
+            Self::OpenAi {
+                execution_options, ..
+            }
+            | Self::Bedrock {
+                execution_options, ..
+            }
+            | Self::Anthropic {
+                execution_options, ..
+            }
+            | Self::Gemini {
+                execution_options, ..
+            }
+            | Self::Ollama {
+                execution_options, ..
+            }
+            | Self::OllamaApi {
+                execution_options, ..
+            }
+            | Self::Proxy {
+                execution_options, ..
+            } => execution_options,
+        }
+    }
+
     pub fn provider_name(&self) -> &str {
         match self {
             Self::OpenAi { .. } => "openai",
@@ -539,6 +615,30 @@ pub struct OpenAiModelParams {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub seed: Option<i64>,
 
+    /// Processing tier of the request, trading latency for cost
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<ServiceTier>,
+
+    /// Output types to generate, e.g. text and audio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Vec<ChatCompletionModalities>>,
+
+    /// Voice and format of the audio output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<ChatCompletionAudio>,
+
+    /// Expected content of the completion, for predicted outputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prediction: Option<PredictionContent>,
+
+    /// How many choices to generate, only streamed responses return more than one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+
+    /// Whether the model may call several tools in one turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+
     /// Up to 4 sequences where the API will stop generating further tokens.
     #[serde_as(as = "Option<OneOrMany<_>>")]
     #[serde(alias = "stop_sequences")]
@@ -566,6 +666,10 @@ pub struct OpenAiModelParams {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response_format: Option<ResponseFormat>,
+
+    /// Fields merged into the request body as is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_body: Option<serde_json::Map<String, Value>>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -590,6 +694,8 @@ pub struct OllamaModelParams {
     pub logit_bias: Option<std::collections::HashMap<String, serde_json::Value>>,
     pub user: Option<String>,
     pub n: Option<u32>,
+    /// Size of the embeddings, for models that can shorten them
+    pub dimensions: Option<u16>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Validate)]
@@ -828,6 +934,11 @@ pub struct AnthropicModelParams {
     pub top_k: Option<claude::TopK>,
 
     pub thinking: Option<claude::Thinking>,
+
+    /// Whether the model is kept to at most one tool call per turn, sent as
+    /// `tool_choice.disable_parallel_tool_use`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_parallel_tool_use: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -847,6 +958,9 @@ pub struct GeminiModelParams {
     pub response_logprobs: Option<bool>,
     pub logprobs: Option<i32>,
     pub response_format: Option<ResponseFormat>,
+    /// Fields added to `generationConfig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_body: Option<serde_json::Map<String, Value>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -1,10 +1,11 @@
+use crate::error::ErrorClass;
 use crate::model::tools::Tool;
 use crate::types::cache::ResponseCacheOptions;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
 use thiserror::Error;
 
 pub use async_openai::types::ResponseFormat as OpenaiResponseFormat;
@@ -37,10 +38,24 @@ pub struct ChatCompletionRequest {
     pub logit_bias: Option<HashMap<String, serde_json::Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
+    /// Key-value pairs recorded as tags on the traces of the request, e.g. `conversation_id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response_format: Option<async_openai::types::ResponseFormat>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<async_openai::types::ServiceTier>,
+    /// Output types to generate, `["text", "audio"]` to get a spoken response as well
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Vec<async_openai::types::ChatCompletionModalities>>,
+    /// Voice and format of the audio output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<async_openai::types::ChatCompletionAudio>,
+    /// Expected content of the completion, speeding up responses that mostly repeat it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prediction: Option<async_openai::types::PredictionContent>,
     // Keeping functions for backward compatibility
     #[serde(skip_serializing_if = "Option::is_none")]
     pub functions: Option<Vec<ChatCompletionFunction>>,
@@ -52,7 +67,13 @@ pub struct ChatCompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
 }
 
 impl ChatCompletionRequest {
@@ -87,6 +108,15 @@ pub struct Extra {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub variables: Option<HashMap<String, serde_json::Value>>,
+
+    /// Try to fix malformed JSON in the completion content before returning it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub repair_json: bool,
+
+    /// Abort streamed structured outputs as soon as they can no longer match the response
+    /// schema
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub validate_stream: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -115,11 +145,16 @@ pub struct ChatCompletionRequestWithTools<T> {
     pub request: ChatCompletionRequest,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mcp_servers: Option<Vec<McpDefinition>>,
+    /// Names of MCP server tools that are not offered to the model for this request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_tools: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub router: Option<DynamicRouter<T>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_retries: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tool_call_iterations: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub extra: Option<Extra>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fallbacks: Option<Vec<ModelNameOrTarget>>,
@@ -135,6 +170,13 @@ pub struct ProviderSpecificRequest {
     pub thinking: Option<Thinking>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub top_k: Option<u32>,
+    /// Have Anthropic models call at most one tool per turn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_parallel_tool_use: Option<bool>,
+    /// Fields merged into the provider request: the OpenAI request body, Bedrock
+    /// `additionalModelRequestFields` or Gemini `generationConfig`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_body: Option<serde_json::Map<String, Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,6 +200,9 @@ pub struct DynamicRouter<T> {
     pub targets: Vec<HashMap<String, serde_json::Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Error classes that move the request on to the next target. Any error does when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failover_on: Option<Vec<ErrorClass>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -205,16 +250,40 @@ pub enum McpTransportType {
 }
 
 impl McpTransportType {
+    /// Identifies a server connection. Servers sharing a URL but sent different headers
+    /// or env get separate keys.
     pub fn key(&self) -> String {
         match self {
-            McpTransportType::Sse { server_url, .. } => format!("sse:{server_url}"),
-            McpTransportType::Ws { server_url, .. } => format!("ws:{server_url}"),
+            McpTransportType::Sse {
+                server_url,
+                headers,
+                env,
+            } => format!("sse:{server_url}#{:016x}", config_hash(headers, env)),
+            McpTransportType::Ws {
+                server_url,
+                headers,
+                env,
+            } => format!("ws:{server_url}#{:016x}", config_hash(headers, env)),
             McpTransportType::InMemory { name, .. } => format!("in-memory:{name}"),
-            McpTransportType::Http { server_url, .. } => format!("http:{server_url}"),
+            McpTransportType::Http {
+                server_url,
+                headers,
+                env,
+            } => format!("http:{server_url}#{:016x}", config_hash(headers, env)),
         }
     }
 }
 
+/// Hash of a server's headers and env that does not depend on map order
+fn config_hash(headers: &HashMap<String, String>, env: &Option<HashMap<String, String>>) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    headers.iter().collect::<BTreeMap<_, _>>().hash(&mut hasher);
+    env.as_ref()
+        .map(|env| env.iter().collect::<BTreeMap<_, _>>())
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
 fn default_in_memory_name() -> String {
     "langdb".to_string()
 }
@@ -294,6 +363,8 @@ pub enum ContentType {
     Text,
     ImageUrl,
     InputAudio,
+    /// Audio generated by the model
+    Audio,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
@@ -383,6 +454,8 @@ pub struct ChatCompletionResponse {
     pub usage: ChatCompletionUsage,
     #[serde(skip_serializing)]
     pub is_cache_used: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -390,6 +463,30 @@ pub struct ChatCompletionChoice {
     pub index: i32,
     pub message: ChatCompletionMessage,
     pub finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub logprobs: Option<ChatCompletionLogprobs>,
+}
+
+/// Token log probabilities in the OpenAI format, regardless of the provider that produced them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ChatCompletionLogprobs {
+    pub content: Vec<ChatCompletionTokenLogprob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatCompletionTokenLogprob {
+    pub token: String,
+    pub logprob: f32,
+    pub bytes: Option<Vec<u8>>,
+    pub top_logprobs: Vec<ChatCompletionTopLogprob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatCompletionTopLogprob {
+    pub token: String,
+    pub logprob: f32,
+    pub bytes: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -406,6 +503,20 @@ pub struct ChatModel {
     pub object: String,
     pub created: i64,
     pub owned_by: String,
+    /// Maximum number of input and output tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ChatModelPricing>,
+}
+
+/// Token prices of a model, per million tokens
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatModelPricing {
+    pub input_per_1m_tokens: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_per_1m_tokens: Option<f64>,
+    pub currency: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -469,6 +580,9 @@ pub struct ChatCompletionDelta {
     pub content: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// Choice the delta belongs to, sent as the index of the chunk's choice
+    #[serde(skip)]
+    pub index: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -484,6 +598,9 @@ pub struct CompletionModelUsage {
     pub prompt_tokens_details: Option<PromptTokensDetails>,
     pub completion_tokens_details: Option<CompletionTokensDetails>,
     pub is_cache_used: bool,
+    /// Part of `output_tokens` the model spent reasoning, as reported by the provider
+    #[serde(default)]
+    pub reasoning_tokens: u32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -500,12 +617,14 @@ pub struct PromptTokensDetails {
     audio_tokens: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct CompletionTokensDetails {
-    accepted_prediction_tokens: u32,
-    audio_tokens: u32,
-    reasoning_tokens: u32,
-    rejected_prediction_tokens: u32,
+    /// Tokens of the predicted output that appeared in the completion
+    pub accepted_prediction_tokens: u32,
+    pub audio_tokens: u32,
+    pub reasoning_tokens: u32,
+    /// Tokens of the predicted output that did not appear in the completion, billed as output
+    pub rejected_prediction_tokens: u32,
 }
 
 #[derive(Error, Debug)]
@@ -525,6 +644,40 @@ pub struct CostCalculationResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub per_image_cost: Option<ImageCostCalculationResult>,
     pub is_cache_used: bool,
+    pub currency: String,
+    /// Where the cost of a token priced completion comes from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<CostBreakdown>,
+}
+
+/// Parts of a completion's cost. `input_cost`, `output_cost`, `reasoning_cost` and
+/// `tool_calls_cost` add up to the total, `cache_savings` is what the completion would have
+/// cost on top without the cache.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct CostBreakdown {
+    pub input_cost: f64,
+    /// Cost of the visible output tokens
+    pub output_cost: f64,
+    /// Cost of the output tokens spent reasoning
+    pub reasoning_cost: f64,
+    /// Cost of the output tokens spent on tool calls
+    pub tool_calls_cost: f64,
+    pub cache_savings: f64,
+}
+
+impl CostBreakdown {
+    pub fn total(&self) -> f64 {
+        self.input_cost + self.output_cost + self.reasoning_cost + self.tool_calls_cost
+    }
+
+    /// Attributes the output of a completion that finished with tool calls to them
+    pub fn with_tool_calls(self) -> Self {
+        Self {
+            output_cost: 0.0,
+            tool_calls_cost: self.tool_calls_cost + self.output_cost,
+            ..self
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -579,15 +732,38 @@ pub struct CreateEmbeddingResponse {
     pub data: Vec<EmbeddingData>,
     pub model: String,
     pub usage: EmbeddingUsage,
+    /// Inputs of the request that couldn't be embedded, the others are still in `data`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<EmbeddingInputError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingInputError {
+    pub index: u32,
+    pub error: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingData {
     pub object: String,
-    pub embedding: Vec<f32>,
+    pub embedding: EmbeddingVector,
     pub index: u32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum EmbeddingVector {
+    Float(Vec<f32>),
+    /// Little-endian f32 values, base64 encoded
+    Base64(String),
+}
+
+impl From<Vec<f32>> for EmbeddingVector {
+    fn from(value: Vec<f32>) -> Self {
+        EmbeddingVector::Float(value)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingUsage {
     pub prompt_tokens: u32,
@@ -621,6 +797,45 @@ pub struct CreateImageRequest {
     pub user: Option<String>,
 }
 
+/// Image uploaded with a multipart request. The content is not serialized into traces.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ImageFile {
+    pub filename: String,
+    #[serde(skip)]
+    pub content: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateImageEditRequest {
+    pub image: ImageFile,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mask: Option<ImageFile>,
+    pub prompt: String,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<ImageSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ImageResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateImageVariationRequest {
+    pub image: ImageFile,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<ImageSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ImageResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ImageQuality {
     #[serde(rename = "standard")]
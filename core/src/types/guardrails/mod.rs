@@ -32,17 +32,27 @@ pub enum GuardError {
     #[error("Output guardrails not supported in streaming")]
     OutputGuardrailsNotSupportedInStreaming,
 
+    /// Guard id, its result and, when enabled, the output that was blocked
     #[error("Guard '{0}' not passed")]
-    GuardNotPassed(String, GuardResult),
+    GuardNotPassed(String, GuardResult, Option<String>),
 }
 
+/// Marks that output blocked by a guard is returned with the error
+#[derive(Debug, Clone, Copy)]
+pub struct ReturnBlockedOutput;
+
+/// Marks that a completion a guard blocked is returned with a `content_filter` finish reason
+/// instead of an error
+#[derive(Debug, Clone, Copy)]
+pub struct BlockedAsContentFilter;
+
 impl ResponseError for GuardError {
     fn status_code(&self) -> http::StatusCode {
         match self {
             GuardError::GuardNotFound(_) => http::StatusCode::NOT_FOUND,
             GuardError::GuardEvaluationError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
             GuardError::OutputGuardrailsNotSupportedInStreaming => http::StatusCode::BAD_REQUEST,
-            GuardError::GuardNotPassed(_, _) => {
+            GuardError::GuardNotPassed(..) => {
                 crate::types::http::status::GuardValidationFailed::status_code()
             }
         }
@@ -65,12 +75,13 @@ impl ResponseError for GuardError {
                     "error": "Output guardrails not supported in streaming"
                 }))
             }
-            GuardError::GuardNotPassed(guard_id, result) => {
+            GuardError::GuardNotPassed(guard_id, result, blocked_content) => {
                 let details = serde_json::to_value(result).ok();
                 let guard_error = GuardValidationError {
                     message: format!("Guard '{guard_id}' not passed"),
                     guard_id: guard_id.clone(),
                     details,
+                    blocked_content: blocked_content.clone(),
                 };
                 guard_error.error_response()
             }
@@ -173,6 +184,23 @@ pub enum Guard {
         #[serde(flatten)]
         config: GuardConfig,
     },
+    /// Language guard that validates the detected language of the text
+    Language {
+        #[serde(flatten)]
+        config: GuardConfig,
+        /// ISO 639-3 codes or English names of the allowed languages
+        allowed: Vec<String>,
+    },
+    /// Prompt injection guard that scores user messages with heuristics and optionally
+    /// an LLM judge
+    PromptInjection {
+        #[serde(flatten)]
+        config: GuardConfig,
+        /// Also ask an LLM judge when the heuristics find nothing
+        #[serde(default)]
+        judge: bool,
+        model: Option<GuardModel>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -225,6 +253,8 @@ impl Guard {
             Guard::WordCount { config } => &config.stage,
             Guard::Regex { config, .. } => &config.stage,
             Guard::Partner { config, .. } => &config.stage,
+            Guard::Language { config, .. } => &config.stage,
+            Guard::PromptInjection { config, .. } => &config.stage,
         }
     }
 
@@ -237,6 +267,8 @@ impl Guard {
             Guard::Regex { config, .. } => &config.action,
             Guard::WordCount { config } => &config.action,
             Guard::Partner { config, .. } => &config.action,
+            Guard::Language { config, .. } => &config.action,
+            Guard::PromptInjection { config, .. } => &config.action,
         }
     }
 
@@ -249,6 +281,8 @@ impl Guard {
             Guard::Regex { config, .. } => &config.id,
             Guard::WordCount { config } => &config.id,
             Guard::Partner { config, .. } => &config.id,
+            Guard::Language { config, .. } => &config.id,
+            Guard::PromptInjection { config, .. } => &config.id,
         }
     }
 
@@ -261,6 +295,8 @@ impl Guard {
             Guard::Regex { config, .. } => &config.name,
             Guard::WordCount { config } => &config.name,
             Guard::Partner { config, .. } => &config.name,
+            Guard::Language { config, .. } => &config.name,
+            Guard::PromptInjection { config, .. } => &config.name,
         }
     }
     pub fn parameters(&self) -> Option<&Value> {
@@ -271,6 +307,8 @@ impl Guard {
             Guard::Regex { config, .. } => config.user_defined_parameters.as_ref(),
             Guard::WordCount { config } => config.user_defined_parameters.as_ref(),
             Guard::Partner { config, .. } => config.user_defined_parameters.as_ref(),
+            Guard::Language { config, .. } => config.user_defined_parameters.as_ref(),
+            Guard::PromptInjection { config, .. } => config.user_defined_parameters.as_ref(),
         }
     }
     pub fn set_parameters(&mut self, parameters: Value) {
@@ -281,6 +319,10 @@ impl Guard {
             Guard::Regex { config, .. } => config.user_defined_parameters = Some(parameters),
             Guard::WordCount { config } => config.user_defined_parameters = Some(parameters),
             Guard::Partner { config, .. } => config.user_defined_parameters = Some(parameters),
+            Guard::Language { config, .. } => config.user_defined_parameters = Some(parameters),
+            Guard::PromptInjection { config, .. } => {
+                config.user_defined_parameters = Some(parameters)
+            }
         }
     }
 
@@ -292,6 +334,8 @@ impl Guard {
             Guard::Regex { config, .. } => &config.template_id,
             Guard::WordCount { config } => &config.template_id,
             Guard::Partner { config, .. } => &config.template_id,
+            Guard::Language { config, .. } => &config.template_id,
+            Guard::PromptInjection { config, .. } => &config.template_id,
         }
     }
 
@@ -303,6 +347,51 @@ impl Guard {
             Guard::Regex { .. } => "regex".to_string(),
             Guard::WordCount { .. } => "word_count".to_string(),
             Guard::Partner { .. } => "partner".to_string(),
+            Guard::Language { .. } => "language".to_string(),
+            Guard::PromptInjection { .. } => "prompt_injection".to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn error_body(error: GuardError) -> Value {
+        let response = error.error_response();
+        assert_eq!(
+            response.status(),
+            crate::types::http::status::GuardValidationFailed::status_code()
+        );
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_blocked_content_in_response() {
+        let result = GuardResult::Boolean {
+            passed: false,
+            confidence: None,
+        };
+
+        let body = error_body(GuardError::GuardNotPassed(
+            "competitor-1".to_string(),
+            result.clone(),
+            Some("Try Competitor A instead".to_string()),
+        ))
+        .await;
+        assert_eq!(body["guard_id"], "competitor-1");
+        assert_eq!(body["blocked_content"], "Try Competitor A instead");
+
+        let body = error_body(GuardError::GuardNotPassed(
+            "competitor-1".to_string(),
+            result,
+            None,
+        ))
+        .await;
+        assert_eq!(body["guard_id"], "competitor-1");
+        assert!(body.get("blocked_content").is_none());
+    }
+}
@@ -15,4 +15,17 @@ pub trait GuardrailsEvaluator: Send + Sync {
         parameters: Option<&serde_json::Value>,
         guard_stage: &GuardStage,
     ) -> Result<GuardResult, String>;
+
+    /// Result of `guard_id` when it is an observe guard for `guard_stage`. Observe guards
+    /// never block, so their results are only reported
+    async fn observe(
+        &self,
+        _messages: &[ChatCompletionMessage],
+        _guard_id: &str,
+        _executor_context: &ExecutorContext,
+        _parameters: Option<&serde_json::Value>,
+        _guard_stage: &GuardStage,
+    ) -> Result<Option<GuardResult>, String> {
+        Ok(None)
+    }
 }
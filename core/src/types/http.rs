@@ -59,6 +59,9 @@ pub mod response {
         pub guard_id: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         pub details: Option<serde_json::Value>,
+        /// Output the guard blocked, only set when `return_blocked_output` is enabled
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub blocked_content: Option<String>,
     }
 
     impl std::fmt::Display for GuardValidationError {
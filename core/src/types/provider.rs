@@ -44,6 +44,37 @@ impl From<String> for BedrockProvider {
     }
 }
 
+/// Geography prefix of a Bedrock cross-region inference profile
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BedrockInferenceProfile {
+    Us,
+    Eu,
+    Apac,
+}
+
+impl Display for BedrockInferenceProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BedrockInferenceProfile::Us => write!(f, "us"),
+            BedrockInferenceProfile::Eu => write!(f, "eu"),
+            BedrockInferenceProfile::Apac => write!(f, "apac"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct BedrockConfig {
+    /// Inference profile per model id, e.g. `meta.llama3-1-8b-instruct-v1:0: eu`.
+    /// Keys may be given with or without the provider prefix.
+    #[serde(default)]
+    pub inference_profiles: HashMap<String, BedrockInferenceProfile>,
+    /// Regions tried in order, a call throttled or failing in one region is retried in
+    /// the next. The credentials' region is used when empty.
+    #[serde(default)]
+    pub regions: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase", into = "String", from = "String")]
 pub enum InferenceModelProvider {
@@ -56,6 +87,34 @@ pub enum InferenceModelProvider {
     Proxy(String),
 }
 
+impl InferenceModelProvider {
+    /// Whether the provider's model implementation can stream completions
+    pub fn supports_streaming(&self) -> bool {
+        !matches!(self, InferenceModelProvider::OllamaApi)
+    }
+
+    /// Whether the provider accepts `parallel_tool_calls`, Anthropic as
+    /// `disable_parallel_tool_use`. Other providers have it emulated by keeping only the
+    /// first tool call of a response
+    pub fn supports_parallel_tool_calls(&self) -> bool {
+        matches!(
+            self,
+            InferenceModelProvider::OpenAI
+                | InferenceModelProvider::Proxy(_)
+                | InferenceModelProvider::Anthropic
+        )
+    }
+
+    /// Most stop sequences a request to the provider may carry, `None` when unlimited
+    pub fn max_stop_sequences(&self) -> Option<usize> {
+        match self {
+            InferenceModelProvider::OpenAI | InferenceModelProvider::Proxy(_) => Some(4),
+            InferenceModelProvider::Gemini => Some(5),
+            _ => None,
+        }
+    }
+}
+
 impl From<String> for InferenceModelProvider {
     fn from(value: String) -> Self {
         match value.to_lowercase().as_str() {
@@ -115,6 +174,9 @@ pub struct EmbeddingModelPrice {
 pub struct CompletionModelPrice {
     pub per_input_token: f64,
     pub per_output_token: f64,
+    /// Rate for reasoning tokens. Without it they are billed as output tokens.
+    #[serde(default)]
+    pub per_reasoning_token: Option<f64>,
     pub valid_from: Option<NaiveDate>,
 }
 
@@ -1,5 +1,6 @@
-use chrono::{Months, Utc};
+use chrono::{DateTime, Months, Utc};
 use parking_lot::RwLock;
+use rolling::RollingWindows;
 use serde::Serialize;
 use std::collections::BTreeMap;
 use std::fmt::Display;
@@ -9,6 +10,8 @@ use std::sync::Arc;
 use chrono::Datelike;
 use chrono::Timelike;
 
+pub mod rolling;
+
 pub fn get_hour_key(company_id: &str, key: &str) -> String {
     let hour = Utc::now().naive_utc().format("%Y-%m-%d-%H");
     format!("{company_id}:{key}:{hour}")
@@ -111,6 +114,8 @@ pub struct Metrics {
     pub llm_usage: Option<f64>,
     pub tps: Option<f64>,
     pub error_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limited: Option<f64>,
 }
 
 #[derive(Debug, Default, Serialize, Clone)]
@@ -130,18 +135,66 @@ pub struct ProviderMetrics {
     pub models: BTreeMap<String, ModelMetrics>,
 }
 
+impl Metrics {
+    fn set(&mut self, metric_type: &str, value: Option<f64>) {
+        match metric_type {
+            "requests" => self.requests = value,
+            "input_tokens" => self.input_tokens = value,
+            "output_tokens" => self.output_tokens = value,
+            "total_tokens" => self.total_tokens = value,
+            "latency" => self.latency = value,
+            "ttft" => self.ttft = value,
+            "rate_limited" => self.rate_limited = value,
+            "llm_usage" => self.llm_usage = value,
+            _ => {}
+        }
+    }
+}
+
+/// Splits a `provider:model:metric` counter key
+fn split_counter_key(key: &str) -> Option<(&str, &str, &str)> {
+    if key.starts_with("default:") {
+        return None;
+    }
+    let mut parts = key.split(':');
+    Some((parts.next()?, parts.next()?, parts.next()?))
+}
+
 #[derive(Default, Clone)]
 pub struct InMemoryStorage {
     counters: Arc<RwLock<BTreeMap<String, AtomicU64>>>,
+    /// Recent increments of the total counters, backing the `last_15_minutes` and
+    /// `last_hour` metrics
+    windows: RollingWindows,
 }
 
 impl InMemoryStorage {
     pub fn new() -> Self {
         Self {
             counters: Arc::new(RwLock::new(BTreeMap::new())),
+            windows: RollingWindows::default(),
         }
     }
 
+    /// Drops increments older than the longest metrics window every minute, so models
+    /// that stopped receiving requests don't keep them
+    pub fn spawn_window_pruning(&self) -> tokio::task::JoinHandle<()> {
+        let windows = self.windows.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                windows.prune(Utc::now());
+            }
+        })
+    }
+
+    /// Adds `value` to the rolling windows of `key` at time `at`
+    pub fn record_at(&self, identifier: &str, key: &str, value: f64, at: DateTime<Utc>) {
+        self.windows
+            .record(&format!("{identifier}:{key}"), value, at);
+    }
+
     pub async fn increment_and_get_value(
         &self,
         refresh_rate: &LimitPeriod,
@@ -163,6 +216,11 @@ impl InMemoryStorage {
         let new_bits = new_value.to_bits();
         counter.store(new_bits, Ordering::SeqCst);
 
+        // Every increment updates the total counter, so the windows record it only once
+        if matches!(refresh_rate, LimitPeriod::Total) {
+            self.record_at(identifier, key, incr_by, Utc::now());
+        }
+
         // If there's an expiry period, spawn a task to remove the counter after that time
         if let Some(expire_seconds) = refresh_rate.get_seconds_until_refresh() {
             let counters: Arc<RwLock<BTreeMap<String, AtomicU64>>> = Arc::clone(&self.counters);
@@ -190,53 +248,136 @@ impl InMemoryStorage {
             .map(|counter| f64::from_bits(counter.load(Ordering::SeqCst)))
     }
 
+    /// Removes every counter of `model` at `provider` and returns how many were removed
+    pub fn reset_model(&self, provider: &str, model: &str) -> usize {
+        let prefix = format!("{provider}:{model}:");
+        self.windows.remove_prefix(&prefix);
+        let mut counters = self.counters.write();
+        let before = counters.len();
+        counters.retain(|key, _| !key.starts_with(&prefix));
+        before - counters.len()
+    }
+
     pub async fn get_all_counters(&self) -> BTreeMap<String, ProviderMetrics> {
-        let counters = self.counters.read();
+        self.counters_at(Utc::now())
+    }
+
+    /// Metrics of every model, with the rolling windows ending at `now`
+    pub fn counters_at(&self, now: DateTime<Utc>) -> BTreeMap<String, ProviderMetrics> {
         let mut providers_metrics: BTreeMap<String, ProviderMetrics> = BTreeMap::new();
 
-        for (key, value) in counters.iter() {
-            if key.starts_with("default:") {
+        for (key, value) in self.counters.read().iter() {
+            // Only `total` counters are reported, the others back rate and cost limits
+            if key.split(':').nth(3) != Some("total") {
                 continue;
             }
-
-            let parts: Vec<&str> = key.split(':').collect();
-            if parts.len() < 2 {
+            let Some((provider, model, metric_type)) = split_counter_key(key) else {
                 continue;
-            }
+            };
 
-            let provider = parts[0].to_string();
-            let model = parts[1].to_string();
+            let v = Some(f64::from_bits(value.load(Ordering::SeqCst)));
+            providers_metrics
+                .entry(provider.to_string())
+                .or_default()
+                .models
+                .entry(model.to_string())
+                .or_default()
+                .metrics
+                .total
+                .set(metric_type, v);
+        }
 
-            if parts.len() <= 2 {
+        for (key, sums) in self.windows.sums(now) {
+            let Some((provider, model, metric_type)) = split_counter_key(&key) else {
                 continue;
-            }
-            let metric_type = parts[2];
+            };
 
-            let provider_metrics = providers_metrics.entry(provider).or_default();
-            let model_metrics = provider_metrics.models.entry(model).or_default();
+            let metrics = &mut providers_metrics
+                .entry(provider.to_string())
+                .or_default()
+                .models
+                .entry(model.to_string())
+                .or_default()
+                .metrics;
+            metrics
+                .last_15_minutes
+                .set(metric_type, sums.last_15_minutes);
+            metrics.last_hour.set(metric_type, sums.last_hour);
+        }
 
-            // Extract time period if present (parts[3] if it exists)
-            let period = parts.get(3).map(|&s| s.to_string());
+        providers_metrics
+    }
+}
 
-            let metrics = match period {
-                Some(p) if p == "total" => &mut model_metrics.metrics.total,
-                _ => continue,
-            };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
 
-            let v = Some(f64::from_bits(value.load(Ordering::SeqCst)));
+    fn model_metrics(storage: &InMemoryStorage, now: DateTime<Utc>) -> TimeMetrics {
+        storage.counters_at(now)["openai"].models["gpt-4o"]
+            .metrics
+            .clone()
+    }
 
-            match metric_type {
-                "requests" => metrics.requests = v,
-                "input_tokens" => metrics.input_tokens = v,
-                "output_tokens" => metrics.output_tokens = v,
-                "total_tokens" => metrics.total_tokens = v,
-                "latency" => metrics.latency = v,
-                "ttft" => metrics.ttft = v,
-                "llm_usage" => model_metrics.metrics.total.llm_usage = v,
-                _ => {}
-            }
+    #[test]
+    fn test_rolling_windows_only_count_recent_events() {
+        let storage = InMemoryStorage::new();
+        let now = Utc::now();
+        for (minutes_ago, requests) in [(2, 1.0), (10, 2.0), (30, 4.0), (90, 8.0)] {
+            storage.record_at(
+                "openai:gpt-4o",
+                "requests",
+                requests,
+                now - Duration::minutes(minutes_ago),
+            );
         }
+        storage.record_at("openai:gpt-4o", "input_tokens", 100.0, now);
+
+        let metrics = model_metrics(&storage, now);
+        assert_eq!(metrics.last_15_minutes.requests, Some(3.0));
+        assert_eq!(metrics.last_hour.requests, Some(7.0));
+        assert_eq!(metrics.last_15_minutes.input_tokens, Some(100.0));
+
+        // Half an hour later only the events of the last hour are left
+        let metrics = model_metrics(&storage, now + Duration::minutes(30));
+        assert_eq!(metrics.last_15_minutes.requests, None);
+        assert_eq!(metrics.last_hour.requests, Some(3.0));
+        assert_eq!(metrics.last_hour.input_tokens, Some(100.0));
+    }
 
-        providers_metrics
+    #[tokio::test]
+    async fn test_increments_fill_windows_and_total() {
+        let storage = InMemoryStorage::new();
+        for period in [LimitPeriod::Hour, LimitPeriod::Total] {
+            storage
+                .increment_and_get_value(&period, "openai:gpt-4o", "requests", 1.0)
+                .await;
+        }
+        storage.record_at(
+            "openai:gpt-4o",
+            "requests",
+            5.0,
+            Utc::now() - Duration::hours(2),
+        );
+
+        let metrics = model_metrics(&storage, Utc::now());
+        assert_eq!(metrics.total.requests, Some(1.0));
+        // Increments of the other periods aren't counted twice
+        assert_eq!(metrics.last_15_minutes.requests, Some(1.0));
+        assert_eq!(metrics.last_hour.requests, Some(1.0));
+    }
+
+    #[test]
+    fn test_pruning_drops_expired_buckets() {
+        let storage = InMemoryStorage::new();
+        let now = Utc::now();
+        storage.record_at("openai:gpt-4o", "requests", 1.0, now - Duration::hours(2));
+        storage.record_at("anthropic:claude-3-5-sonnet", "requests", 1.0, now);
+
+        storage.windows.prune(now);
+        let sums = storage.windows.sums(now - Duration::hours(2));
+        assert!(!sums.contains_key("openai:gpt-4o:requests"));
+        assert!(storage.counters_at(now).contains_key("anthropic"));
     }
 }
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+
+/// Window lengths in minutes. Buckets older than the longest one are dropped by `prune`.
+const LAST_HOUR: i64 = 60;
+const LAST_15_MINUTES: i64 = 15;
+
+/// Per minute sums of counter increments, keyed by counter. Increments drop out of a window
+/// once they are older than it.
+#[derive(Default, Clone)]
+pub struct RollingWindows {
+    buckets: Arc<RwLock<BTreeMap<String, BTreeMap<i64, f64>>>>,
+}
+
+/// Sums of a counter over the last 15 minutes and the last hour
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowSums {
+    pub last_15_minutes: Option<f64>,
+    pub last_hour: Option<f64>,
+}
+
+fn minute(at: DateTime<Utc>) -> i64 {
+    at.timestamp().div_euclid(60)
+}
+
+impl RollingWindows {
+    pub fn record(&self, key: &str, value: f64, at: DateTime<Utc>) {
+        let mut buckets = self.buckets.write();
+        *buckets
+            .entry(key.to_string())
+            .or_default()
+            .entry(minute(at))
+            .or_default() += value;
+    }
+
+    /// Sums of every counter with increments in the last hour before `now`
+    pub fn sums(&self, now: DateTime<Utc>) -> BTreeMap<String, WindowSums> {
+        let current = minute(now);
+        let sum = |counter: &BTreeMap<i64, f64>, minutes: i64| {
+            let mut values = counter
+                .range(current - minutes + 1..=current)
+                .map(|(_, value)| *value)
+                .peekable();
+            values.peek().is_some().then(|| values.sum::<f64>())
+        };
+
+        self.buckets
+            .read()
+            .iter()
+            .filter_map(|(key, counter)| {
+                let last_hour = sum(counter, LAST_HOUR)?;
+                Some((
+                    key.clone(),
+                    WindowSums {
+                        last_15_minutes: sum(counter, LAST_15_MINUTES),
+                        last_hour: Some(last_hour),
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Drops the buckets that have left every window
+    pub fn prune(&self, now: DateTime<Utc>) {
+        let oldest = minute(now) - LAST_HOUR + 1;
+        let mut buckets = self.buckets.write();
+        for counter in buckets.values_mut() {
+            *counter = counter.split_off(&oldest);
+        }
+        buckets.retain(|_, counter| !counter.is_empty());
+    }
+
+    /// Removes the counters starting with `prefix`
+    pub fn remove_prefix(&self, prefix: &str) {
+        self.buckets
+            .write()
+            .retain(|key, _| !key.starts_with(prefix));
+    }
+}
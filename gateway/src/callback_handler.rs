@@ -5,11 +5,14 @@ use tokio::sync::Mutex;
 use chrono::{DateTime, Utc};
 use langdb_core::usage::InMemoryStorage;
 use langdb_core::{
-    handler::CallbackHandlerFn, model::types::ModelEventType,
+    handler::CallbackHandlerFn, model::types::ModelEventType, model::RATE_LIMITED_EVENT,
     types::gateway::ImageGenerationModelUsage,
 };
 
-use crate::{cost::GatewayCostCalculator, usage::update_usage};
+use crate::{
+    cost::GatewayCostCalculator,
+    usage::{update_rate_limits, update_usage},
+};
 
 pub fn init_callback_handler(
     storage: Arc<Mutex<InMemoryStorage>>,
@@ -119,6 +122,16 @@ pub fn init_callback_handler(
                                 };
                             }
                         }
+                        ModelEventType::Custom(event) if event.name() == RATE_LIMITED_EVENT => {
+                            if let Some(model) = &model_event.model {
+                                update_rate_limits(
+                                    storage.clone(),
+                                    &model.name,
+                                    &model.provider_name,
+                                )
+                                .await;
+                            }
+                        }
                         ModelEventType::ImageGenerationFinish(finish_event) => {
                             if let Some(model) = &model_event.model {
                                 let model_name = finish_event.model_name.clone();
@@ -42,6 +42,10 @@ pub struct ServeArgs {
     #[arg(long, value_name = "LIMIT")]
     pub cost_total: Option<f64>,
 
+    /// Maximum estimated cost of a single request (e.g., 0.5)
+    #[arg(long, value_name = "LIMIT")]
+    pub cost_per_request: Option<f64>,
+
     /// Maximum number of API calls per hour (e.g., 1000)
     #[arg(long, value_name = "LIMIT")]
     pub rate_hourly: Option<u64>,
@@ -1,13 +1,31 @@
 use crate::cli;
 use crate::session::Credentials;
+use crate::tracing::TracingConfig;
+use langdb_core::executor::chat_completion::merge_messages::MergeConsecutiveMessages;
+use langdb_core::executor::chat_completion::prompt_limit::PromptLimit;
+use langdb_core::executor::chat_completion::response_cache::ResponseCacheConfig;
+use langdb_core::executor::chat_completion::stream_wrapper::KeepAliveConfig;
+use langdb_core::executor::chat_completion::tool_limit::ToolLimit;
+use langdb_core::executor::chat_completion::EventChannelCapacity;
+use langdb_core::executor::provider_keys::ProviderCredentials;
 use langdb_core::executor::ProvidersConfig;
+use langdb_core::handler::admin::AdminConfig;
+use langdb_core::handler::middleware::auth::BearerKeyAuth;
 use langdb_core::handler::middleware::rate_limit::RateLimiting;
+use langdb_core::handler::middleware::request_id::RequestIdMiddleware;
+use langdb_core::handler::middleware::response_headers::ResponseHeadersConfig;
+use langdb_core::handler::model_access::{ApiKeysConfig, TenantModels};
+use langdb_core::model::tls::ProviderTlsConfig;
+use langdb_core::otel::TraceStorageConfig;
+use langdb_core::pricing::currency::CurrencyConfig;
+use langdb_core::routing::DefaultRouters;
 use langdb_core::types::credentials::ApiKeyCredentials;
-use langdb_core::types::guardrails::Guard;
+use langdb_core::types::guardrails::{Guard, GuardModel};
+use langdb_core::types::provider::BedrockConfig;
 use minijinja::Environment;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -16,6 +34,8 @@ pub enum ConfigError {
     ParseError(#[from] serde_yaml::Error),
     #[error("Failed to read template in config. Error: {0}")]
     ReadError(#[from] minijinja::Error),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,6 +43,25 @@ pub struct HttpConfig {
     pub host: String,
     pub port: u16,
     pub cors_allowed_origins: Vec<String>,
+    #[serde(default)]
+    pub sse_keepalive: Option<KeepAliveConfig>,
+    /// Header the request id is read from and echoed in, `x-request-id` by default
+    #[serde(default)]
+    pub request_id_header: Option<String>,
+    /// Headers removed from responses before they are returned to clients
+    #[serde(default)]
+    pub response_headers: Option<ResponseHeadersConfig>,
+}
+
+impl HttpConfig {
+    pub fn request_id_middleware(&self) -> Result<RequestIdMiddleware, ConfigError> {
+        match &self.request_id_header {
+            Some(header) => RequestIdMiddleware::new(header).map_err(|e| {
+                ConfigError::InvalidConfig(format!("invalid request_id_header {header}: {e}"))
+            }),
+            None => Ok(RequestIdMiddleware::default()),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Default)]
@@ -45,6 +84,70 @@ pub struct Config {
     pub providers: Option<ProvidersConfig>,
     #[serde(default)]
     pub guards: Option<HashMap<String, Guard>>,
+    /// Judge model and prompts of LLM judge guards whose template has none of its own
+    #[serde(default)]
+    pub guard_judge: Option<GuardModel>,
+    #[serde(default)]
+    pub bedrock: Option<BedrockConfig>,
+    #[serde(default)]
+    pub api_keys: Option<ApiKeysConfig>,
+    #[serde(default)]
+    pub traces: Option<TraceStorageConfig>,
+    #[serde(default)]
+    pub tracing: Option<TracingConfig>,
+    /// YAML or JSON file with per provider and model prices replacing the built-in ones
+    #[serde(default)]
+    pub pricing_overrides: Option<PathBuf>,
+    /// Reports costs in another currency instead of USD
+    #[serde(default)]
+    pub currency: Option<CurrencyConfig>,
+    /// Rejects chat completions without a `user` instead of generating a random one
+    #[serde(default)]
+    pub require_user: bool,
+    /// Routers wrapping requests for a plain model name, keyed by model
+    #[serde(default)]
+    pub default_routers: Option<DefaultRouters>,
+    /// Returns the output an output guard blocked alongside the guard error
+    #[serde(default)]
+    pub return_blocked_output: bool,
+    /// Returns completions a guard blocked with a `content_filter` finish reason
+    /// instead of an error
+    #[serde(default)]
+    pub blocked_as_content_filter: bool,
+    /// Serves streaming requests to providers that can't stream with a single chunk
+    #[serde(default)]
+    pub stream_fallback: bool,
+    /// Strips markdown code fences and surrounding whitespace from structured outputs
+    #[serde(default)]
+    pub strip_code_fences: bool,
+    /// Enables the `/admin` endpoints for the configured key
+    #[serde(default)]
+    pub admin: Option<AdminConfig>,
+    /// Per provider switch for merging consecutive messages of the same role, on by
+    /// default for providers that reject them
+    #[serde(default)]
+    pub merge_consecutive_messages: Option<MergeConsecutiveMessages>,
+    /// Only accepts `/v1` requests carrying one of these keys
+    #[serde(default)]
+    pub auth: Option<BearerKeyAuth>,
+    /// Models each tenant may see and use, keyed by tenant name
+    #[serde(default)]
+    pub tenant_models: Option<TenantModels>,
+    /// Caps the number of tools, request and MCP ones combined, sent with a request
+    #[serde(default)]
+    pub tool_limit: Option<ToolLimit>,
+    /// Caps the size of prompts, for every model or per model
+    #[serde(default)]
+    pub prompt_limit: Option<PromptLimit>,
+    /// Model events buffered per request before the provider stream is throttled
+    #[serde(default)]
+    pub event_channel_capacity: Option<EventChannelCapacity>,
+    /// Expiration and size limits of the response cache
+    #[serde(default)]
+    pub response_cache: Option<ResponseCacheConfig>,
+    /// Minimum TLS version and pinned certificates of the connections to providers
+    #[serde(default)]
+    pub provider_tls: Option<ProviderTlsConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -52,6 +155,9 @@ pub struct CostControl {
     pub daily: Option<f64>,
     pub monthly: Option<f64>,
     pub total: Option<f64>,
+    /// Rejects single requests whose estimated cost is above this amount
+    #[serde(default)]
+    pub per_request: Option<f64>,
 }
 
 impl Default for HttpConfig {
@@ -60,6 +166,9 @@ impl Default for HttpConfig {
             host: "0.0.0.0".to_string(),
             port: 8080,
             cors_allowed_origins: vec!["*".to_string()],
+            sse_keepalive: None,
+            request_id_header: None,
+            response_headers: None,
         }
     }
 }
@@ -85,7 +194,9 @@ impl Config {
         match std::fs::read_to_string(config_path) {
             Ok(content) => {
                 let content = replace_env_vars(content)?;
-                Ok(serde_yaml::from_str(&content)?)
+                let config: Self = serde_yaml::from_str(&content)?;
+                config.validate()?;
+                Ok(config)
             }
             Err(e) => {
                 tracing::warn!("Failed to read config: {}. Using default config.", e);
@@ -94,6 +205,61 @@ impl Config {
         }
     }
 
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(tracing) = &self.tracing {
+            tracing.validate().map_err(ConfigError::InvalidConfig)?;
+        }
+        if let Some(currency) = &self.currency {
+            currency.validate().map_err(ConfigError::InvalidConfig)?;
+        }
+        if let Some(traces) = &self.traces {
+            traces.validate().map_err(ConfigError::InvalidConfig)?;
+        }
+        if let Some(sse_keepalive) = &self.http.sse_keepalive {
+            sse_keepalive
+                .validate()
+                .map_err(ConfigError::InvalidConfig)?;
+        }
+        if let Some(provider_tls) = &self.provider_tls {
+            provider_tls
+                .validate()
+                .map_err(ConfigError::InvalidConfig)?;
+            // Bedrock connections are made by the AWS SDK, which the policy can't reach
+            let bedrock_configured = self.bedrock.is_some()
+                || self
+                    .providers
+                    .as_ref()
+                    .is_some_and(|p| p.0.contains_key("bedrock"));
+            if bedrock_configured {
+                return Err(ConfigError::InvalidConfig(
+                    "provider_tls can't be applied to Bedrock, remove it or the Bedrock configuration"
+                        .to_string(),
+                ));
+            }
+        }
+        if self.event_channel_capacity == Some(EventChannelCapacity(0)) {
+            return Err(ConfigError::InvalidConfig(
+                "event_channel_capacity must be at least 1".to_string(),
+            ));
+        }
+        if self.response_cache.is_some_and(|c| c.max_entries == 0) {
+            return Err(ConfigError::InvalidConfig(
+                "response_cache.max_entries must be at least 1".to_string(),
+            ));
+        }
+        for (provider, credentials) in self.providers.iter().flat_map(|p| &p.0) {
+            if let ProviderCredentials::Rotating(keys) = credentials {
+                if keys.api_keys.iter().all(|k| k.weight == 0) {
+                    return Err(ConfigError::InvalidConfig(format!(
+                        "providers.{provider}.api_keys must list at least one key with a weight above 0"
+                    )));
+                }
+            }
+        }
+        self.http.request_id_middleware()?;
+        Ok(())
+    }
+
     pub fn apply_cli_overrides(mut self, cli_opts: &cli::Commands) -> Self {
         if let cli::Commands::Serve(args) = cli_opts {
             // Apply REST config overrides
@@ -124,6 +290,9 @@ impl Config {
             if let Some(total) = args.cost_total {
                 cost_control.total = Some(total);
             }
+            if let Some(per_request) = args.cost_per_request {
+                cost_control.per_request = Some(per_request);
+            }
             self.cost_control = Some(cost_control);
 
             // Apply rate limit overrides
@@ -160,14 +329,14 @@ pub fn load_langdb_proxy_config(config: Option<ProvidersConfig>) -> Option<Provi
             if !providers_config.0.contains_key("langdb_proxy") {
                 providers_config.0.insert(
                     "langdb_proxy".to_string(),
-                    ApiKeyCredentials { api_key: key },
+                    ApiKeyCredentials { api_key: key }.into(),
                 );
             }
             Some(providers_config)
         } else {
             Some(ProvidersConfig(HashMap::from([(
                 "langdb_proxy".to_string(),
-                ApiKeyCredentials { api_key: key },
+                ApiKeyCredentials { api_key: key }.into(),
             )])))
         }
     } else {
@@ -1,15 +1,79 @@
 use langdb_core::{
     models::ModelMetadata,
-    pricing::calculator::{calculate_image_price, calculate_tokens_cost},
+    pricing::calculator::{
+        calculate_image_price, calculate_tokens_cost_with_reasoning, tokens_cost,
+    },
     types::{
-        gateway::{CostCalculationResult, CostCalculator, CostCalculatorError, Usage},
+        gateway::{
+            CompletionModelUsage, CostCalculationResult, CostCalculator, CostCalculatorError, Usage,
+        },
         provider::ModelPrice,
     },
 };
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PricingOverridesError {
+    #[error("Failed to read pricing overrides: {0}")]
+    ReadError(#[from] std::io::Error),
+    #[error("Failed to parse pricing overrides: {0}")]
+    ParseError(#[from] serde_yaml::Error),
+}
+
+/// Rates per million tokens that replace the built-in price of a model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceOverride {
+    pub per_input_token: f64,
+    #[serde(default)]
+    pub per_output_token: f64,
+    /// Rate for input tokens served from cache. Without it cached usage is discounted
+    /// the same way as built-in prices.
+    #[serde(default)]
+    pub per_cached_input_token: Option<f64>,
+    /// Rate for reasoning tokens. Without it they are billed as output tokens.
+    #[serde(default)]
+    pub per_reasoning_token: Option<f64>,
+}
+
+/// Price overrides keyed by provider and then model name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PricingOverrides(HashMap<String, HashMap<String, PriceOverride>>);
+
+impl PricingOverrides {
+    /// Loads overrides from a YAML or JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PricingOverridesError> {
+        tracing::info!(
+            "Loading pricing overrides from: {}",
+            path.as_ref().display()
+        );
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_yaml::from_str(&content)?)
+    }
+
+    fn get(&self, model_name: &str, provider_name: &str) -> Option<&PriceOverride> {
+        self.0.get(provider_name).and_then(|models| {
+            models
+                .iter()
+                .find(|(model, _)| model.to_lowercase() == model_name.to_lowercase())
+                .map(|(_, price)| price)
+        })
+    }
+}
+
+struct Prices {
+    models: Vec<ModelMetadata>,
+    overrides: PricingOverrides,
+}
 
+/// Prices models from their metadata and the pricing overrides. Clones share the prices, so
+/// a [`GatewayCostCalculator::reload`] is seen by all of them.
 #[derive(Clone)]
 pub struct GatewayCostCalculator {
-    models: Vec<ModelMetadata>,
+    prices: Arc<RwLock<Arc<Prices>>>,
     default_image_cost: f64,
     default_input_cost: f64,
     default_output_cost: f64,
@@ -18,12 +82,66 @@ pub struct GatewayCostCalculator {
 impl GatewayCostCalculator {
     pub fn new(models: Vec<ModelMetadata>) -> Self {
         Self {
-            models,
+            prices: Arc::new(RwLock::new(Arc::new(Prices {
+                models,
+                overrides: PricingOverrides::default(),
+            }))),
             default_image_cost: 0.0,
             default_input_cost: 0.0,
             default_output_cost: 0.0,
         }
     }
+
+    pub fn with_overrides(self, overrides: PricingOverrides) -> Self {
+        let models = self.prices().models.clone();
+        self.reload(models, overrides);
+        self
+    }
+
+    /// Replaces the models and overrides prices are taken from
+    pub fn reload(&self, models: Vec<ModelMetadata>, overrides: PricingOverrides) {
+        *self.prices.write().unwrap() = Arc::new(Prices { models, overrides });
+    }
+
+    fn prices(&self) -> Arc<Prices> {
+        self.prices.read().unwrap().clone()
+    }
+}
+
+fn calculate_override_cost(
+    price: &PriceOverride,
+    usage: &CompletionModelUsage,
+) -> CostCalculationResult {
+    match price.per_cached_input_token {
+        Some(per_cached_input_token) if usage.is_cache_used => {
+            let mut result = calculate_tokens_cost_with_reasoning(
+                &CompletionModelUsage {
+                    is_cache_used: false,
+                    ..usage.clone()
+                },
+                per_cached_input_token,
+                price.per_output_token,
+                price.per_reasoning_token,
+            );
+            result.is_cache_used = true;
+            let uncached_cost = tokens_cost(
+                usage,
+                price.per_input_token,
+                price.per_output_token,
+                price.per_reasoning_token,
+            );
+            if let Some(breakdown) = &mut result.breakdown {
+                breakdown.cache_savings = uncached_cost - result.cost;
+            }
+            result
+        }
+        _ => calculate_tokens_cost_with_reasoning(
+            usage,
+            price.per_input_token,
+            price.per_output_token,
+            price.per_reasoning_token,
+        ),
+    }
 }
 
 #[async_trait::async_trait]
@@ -41,7 +159,14 @@ impl CostCalculator for GatewayCostCalculator {
                 model_name
             };
 
-        let model = self.models.iter().find(|m| {
+        let prices = self.prices();
+        if let (Some(price), Usage::CompletionModelUsage(usage)) =
+            (prices.overrides.get(model_name, provider_name), usage)
+        {
+            return Ok(calculate_override_cost(price, usage));
+        }
+
+        let model = prices.models.iter().find(|m| {
             (m.model.to_lowercase() == model_name.to_lowercase()
                 || m.inference_provider.model_name.to_string().to_lowercase()
                     == model_name.to_lowercase())
@@ -61,10 +186,12 @@ impl CostCalculator for GatewayCostCalculator {
                     }
                 }
                 langdb_core::types::gateway::Usage::CompletionModelUsage(usage) => {
-                    let (input_price, output_price) = match price {
+                    let (input_price, output_price, reasoning_price) = match price {
                         Some(p) => match p {
-                            ModelPrice::Completion(c) => (c.per_input_token, c.per_output_token),
-                            ModelPrice::Embedding(c) => (c.per_input_token, 0.0),
+                            ModelPrice::Completion(c) => {
+                                (c.per_input_token, c.per_output_token, c.per_reasoning_token)
+                            }
+                            ModelPrice::Embedding(c) => (c.per_input_token, 0.0, None),
                             ModelPrice::ImageGeneration(_) => {
                                 return Err(CostCalculatorError::CalculationError(
                                     "Model pricing not supported".to_string(),
@@ -73,10 +200,15 @@ impl CostCalculator for GatewayCostCalculator {
                         },
                         None => {
                             tracing::error!("Model not found: {model_name} - {provider_name}");
-                            (self.default_input_cost, self.default_output_cost)
+                            (self.default_input_cost, self.default_output_cost, None)
                         }
                     };
-                    Ok(calculate_tokens_cost(usage, input_price, output_price))
+                    Ok(calculate_tokens_cost_with_reasoning(
+                        usage,
+                        input_price,
+                        output_price,
+                        reasoning_price,
+                    ))
                 }
             }
         } else {
@@ -85,3 +217,120 @@ impl CostCalculator for GatewayCostCalculator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use langdb_core::models::InferenceProvider;
+    use langdb_core::types::provider::{CompletionModelPrice, InferenceModelProvider};
+
+    fn usage(is_cache_used: bool) -> Usage {
+        Usage::CompletionModelUsage(CompletionModelUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 1_000_000,
+            total_tokens: 2_000_000,
+            prompt_tokens_details: None,
+            completion_tokens_details: None,
+            is_cache_used,
+            reasoning_tokens: 0,
+        })
+    }
+
+    fn built_in_models() -> Vec<ModelMetadata> {
+        vec![ModelMetadata {
+            model: "gpt-4o".to_string(),
+            inference_provider: InferenceProvider {
+                provider: InferenceModelProvider::OpenAI,
+                model_name: "gpt-4o".to_string(),
+                endpoint: None,
+            },
+            price: ModelPrice::Completion(CompletionModelPrice {
+                per_input_token: 5.0,
+                per_output_token: 15.0,
+                per_reasoning_token: None,
+                valid_from: None,
+            }),
+            ..Default::default()
+        }]
+    }
+
+    fn assert_cost(result: &CostCalculationResult, expected: f64) {
+        assert!(
+            (result.cost - expected).abs() < 1e-9,
+            "expected {expected}, got {}",
+            result.cost
+        );
+    }
+
+    fn write_overrides(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[actix_web::test]
+    async fn test_override_replaces_built_in_price() {
+        let path = write_overrides(
+            "pricing.yaml",
+            r#"
+openai:
+  GPT-4o:
+    per_input_token: 2.5
+    per_output_token: 10.0
+    per_cached_input_token: 1.25
+"#,
+        );
+        let overrides = PricingOverrides::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let built_in = GatewayCostCalculator::new(built_in_models());
+        let result = built_in
+            .calculate_cost("gpt-4o", "openai", &usage(false))
+            .await
+            .unwrap();
+        assert_cost(&result, 20.0);
+
+        let calculator = built_in.with_overrides(overrides);
+        let result = calculator
+            .calculate_cost("openai/gpt-4o", "openai", &usage(false))
+            .await
+            .unwrap();
+        assert_cost(&result, 12.5);
+        assert_eq!(result.per_input_token, 2.5);
+        assert_eq!(result.per_output_token, 10.0);
+
+        let result = calculator
+            .calculate_cost("gpt-4o", "openai", &usage(true))
+            .await
+            .unwrap();
+        assert_cost(&result, 11.25);
+        assert!(result.is_cache_used);
+        let breakdown = result.breakdown.unwrap();
+        assert!((breakdown.total() - result.cost).abs() < 1e-9);
+        assert!((breakdown.cache_savings - 1.25).abs() < 1e-9);
+    }
+
+    #[actix_web::test]
+    async fn test_override_adds_unknown_model() {
+        let path = write_overrides(
+            "pricing.json",
+            r#"{"anthropic": {"claude-new": {"per_input_token": 1.0, "per_output_token": 2.0}}}"#,
+        );
+        let overrides = PricingOverrides::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let calculator = GatewayCostCalculator::new(built_in_models()).with_overrides(overrides);
+        let result = calculator
+            .calculate_cost("claude-new", "anthropic", &usage(false))
+            .await
+            .unwrap();
+        assert_cost(&result, 3.0);
+
+        // Models without overrides keep their built-in price
+        let result = calculator
+            .calculate_cost("gpt-4o", "openai", &usage(false))
+            .await
+            .unwrap();
+        assert_cost(&result, 20.0);
+    }
+}
@@ -1,78 +1,140 @@
 use std::collections::HashMap;
 
-use langdb_core::executor::chat_completion::resolve_model_instance;
+use langdb_core::executor::chat_completion::routed_executor::RoutedExecutor;
 use langdb_core::executor::context::ExecutorContext;
-use langdb_core::model::ModelInstance;
+use langdb_core::executor::embeddings::handle_embeddings_invoke;
+use langdb_core::handler::find_model_by_full_name;
 use langdb_core::routing::RoutingStrategy;
-use langdb_core::types::engine::ModelTools;
 use langdb_core::types::gateway::ChatCompletionMessage;
 use langdb_core::types::gateway::ChatCompletionRequest;
 use langdb_core::types::gateway::ChatCompletionRequestWithTools;
-use langdb_core::types::gateway::DynamicRouter;
+use langdb_core::types::gateway::{CreateEmbeddingRequest, EmbeddingVector, Input};
 use langdb_core::types::guardrails::evaluator::Evaluator;
 use langdb_core::types::guardrails::service::GuardrailsEvaluator;
 use langdb_core::types::guardrails::Guard;
 use langdb_core::types::guardrails::GuardAction;
+use langdb_core::types::guardrails::GuardModel;
 use langdb_core::types::guardrails::GuardResult;
 use langdb_core::types::guardrails::GuardStage;
 use langdb_core::types::guardrails::GuardTemplate;
 use langdb_guardrails::guards::config::load_guard_templates;
-use langdb_guardrails::guards::llm_judge::GuardModelInstanceFactory;
+use langdb_guardrails::guards::llm_judge::GuardExecutor;
 use langdb_guardrails::guards::partner::PartnerEvaluator;
 use langdb_guardrails::guards::partners::openai::OpenaiGuardrailPartner;
 use langdb_guardrails::guards::traced::TracedGuard;
 use langdb_guardrails::guards::DatasetEvaluator;
 use langdb_guardrails::guards::FileDatasetLoader;
+use langdb_guardrails::guards::GuardEmbedder;
+use langdb_guardrails::guards::LanguageEvaluator;
 use langdb_guardrails::guards::LlmJudgeEvaluator;
+use langdb_guardrails::guards::PromptInjectionEvaluator;
 use langdb_guardrails::guards::RegexEvaluator;
 use langdb_guardrails::guards::SchemaEvaluator;
 use langdb_guardrails::guards::WordCountEvaluator;
 use serde_json::{Map, Value};
-use tracing::Span;
 
-pub struct GuardModelFactory {
+/// Runs LLM judge calls through the gateway's executor
+pub struct GatewayGuardExecutor {
     executor_context: ExecutorContext,
 }
 
-impl GuardModelFactory {
-    pub fn new(executor_context: ExecutorContext) -> Self {
+impl GatewayGuardExecutor {
+    pub fn new(mut executor_context: ExecutorContext) -> Self {
+        // Judge calls are made by the gateway, not on behalf of a user
+        executor_context.require_user = false;
         Self { executor_context }
     }
 }
 
 #[async_trait::async_trait]
-impl GuardModelInstanceFactory for GuardModelFactory {
-    async fn init(&self, name: &str) -> Box<dyn ModelInstance> {
-        let request = ChatCompletionRequestWithTools {
+impl GuardExecutor for GatewayGuardExecutor {
+    async fn complete(
+        &self,
+        model: &str,
+        messages: Vec<ChatCompletionMessage>,
+    ) -> Result<ChatCompletionMessage, String> {
+        let request = ChatCompletionRequestWithTools::<RoutingStrategy> {
             request: ChatCompletionRequest {
-                model: name.to_string(),
+                model: model.to_string(),
+                messages,
                 ..Default::default()
             },
-            router: None::<DynamicRouter<RoutingStrategy>>,
             ..Default::default()
         };
 
-        let resolved = resolve_model_instance(
-            &self.executor_context,
-            &request,
-            HashMap::new(),
-            ModelTools(vec![]),
-            Span::current(),
-            None,
-            Vec::new(),
-            None,
-            None,
+        let response = RoutedExecutor::new(request)
+            .complete(&self.executor_context, None)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message)
+            .ok_or_else(|| "Judge model returned no choices".to_string())
+    }
+}
+
+/// Runs dataset guard embeddings through the gateway's embeddings executor, with the
+/// credentials and endpoints configured for the embedding model
+pub struct GatewayGuardEmbedder {
+    executor_context: ExecutorContext,
+}
+
+impl GatewayGuardEmbedder {
+    pub fn new(executor_context: ExecutorContext) -> Self {
+        Self { executor_context }
+    }
+}
+
+#[async_trait::async_trait]
+impl GuardEmbedder for GatewayGuardEmbedder {
+    async fn embed(&self, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        let llm_model = find_model_by_full_name(model, &self.executor_context.provided_models)
+            .map_err(|e| e.to_string())?;
+        let request = CreateEmbeddingRequest {
+            model: model.to_string(),
+            input: Input::Array(input),
+            user: None,
+            dimensions: None,
+            encoding_format: Default::default(),
+        };
+
+        let response = handle_embeddings_invoke(
+            request,
+            &self.executor_context.callbackhandler,
+            &llm_model,
+            self.executor_context.key_credentials.as_ref(),
+            self.executor_context.providers_config.as_ref(),
+            self.executor_context.tags.clone(),
         )
         .await
-        .expect("Failed to resolve model instance");
+        .map_err(|e| e.to_string())?;
 
-        resolved.model_instance
+        // Every example needs its embedding
+        if let Some(error) = response.errors.first() {
+            return Err(format!(
+                "Failed to embed input {}: {}",
+                error.index, error.error
+            ));
+        }
+
+        let mut data = response.data;
+        data.sort_by_key(|data| data.index);
+        data.into_iter()
+            .map(|data| match data.embedding {
+                EmbeddingVector::Float(embedding) => Ok(embedding),
+                EmbeddingVector::Base64(_) => Err("Expected float embeddings".to_string()),
+            })
+            .collect()
     }
 }
 
 pub struct GuardrailsService {
     guards: HashMap<String, Guard>,
     templates: HashMap<String, GuardTemplate>,
+    default_judge: Option<GuardModel>,
 }
 
 // Implement Send + Sync since all fields are Send + Sync
@@ -81,7 +143,17 @@ unsafe impl Send for GuardrailsService {}
 impl GuardrailsService {
     pub fn new(guards: HashMap<String, Guard>) -> Self {
         let templates = load_guard_templates().unwrap_or_default();
-        Self { guards, templates }
+        Self {
+            guards,
+            templates,
+            default_judge: None,
+        }
+    }
+
+    /// Replaces the judge of LLM judge guards whose template has none
+    pub fn with_default_judge(mut self, default_judge: Option<GuardModel>) -> Self {
+        self.default_judge = default_judge;
+        self
     }
 
     fn get_evaluator(
@@ -92,17 +164,27 @@ impl GuardrailsService {
         let evaluator = match &guard {
             Guard::Schema { .. } => Box::new(SchemaEvaluator {}) as Box<dyn Evaluator>,
             Guard::LlmJudge { .. } => {
-                let factory = GuardModelFactory::new(executor_context.clone());
-                let evaluator = LlmJudgeEvaluator::new(
-                    Box::new(factory) as Box<dyn GuardModelInstanceFactory + Send + Sync>
-                );
-                Box::new(evaluator) as Box<dyn Evaluator>
+                let executor = GatewayGuardExecutor::new(executor_context.clone());
+                let mut judge = LlmJudgeEvaluator::new(Box::new(executor));
+                if let Some(default_judge) = &self.default_judge {
+                    judge = judge.with_default_model(default_judge.clone());
+                }
+                Box::new(judge) as Box<dyn Evaluator>
             }
             Guard::Dataset { .. } => Box::new(DatasetEvaluator {
                 loader: Box::new(FileDatasetLoader {}),
+                embedder: Some(Box::new(GatewayGuardEmbedder::new(
+                    executor_context.clone(),
+                ))),
             }) as Box<dyn Evaluator>,
             Guard::Regex { .. } => Box::new(RegexEvaluator {}) as Box<dyn Evaluator>,
             Guard::WordCount { .. } => Box::new(WordCountEvaluator {}) as Box<dyn Evaluator>,
+            Guard::Language { .. } => Box::new(LanguageEvaluator {}) as Box<dyn Evaluator>,
+            Guard::PromptInjection { .. } => {
+                let executor = GatewayGuardExecutor::new(executor_context.clone());
+                Box::new(PromptInjectionEvaluator::new(Some(Box::new(executor))))
+                    as Box<dyn Evaluator>
+            }
             Guard::Partner { .. } => Box::new(PartnerEvaluator::new(Box::new(
                 OpenaiGuardrailPartner::new(None).map_err(|e| e.to_string())?,
             ))) as Box<dyn Evaluator>,
@@ -112,16 +194,16 @@ impl GuardrailsService {
     }
 }
 
-#[async_trait::async_trait]
-impl GuardrailsEvaluator for GuardrailsService {
-    async fn evaluate(
+impl GuardrailsService {
+    /// Evaluates `guard_id` along with its action, `None` when it doesn't apply to `stage`
+    async fn evaluate_guard(
         &self,
         messages: &[ChatCompletionMessage],
         guard_id: &str,
         executor_context: &ExecutorContext,
         parameters: Option<&serde_json::Value>,
         stage: &GuardStage,
-    ) -> Result<GuardResult, String> {
+    ) -> Result<Option<(GuardAction, GuardResult)>, String> {
         let mut guard = self
             .guards
             .get(guard_id)
@@ -129,10 +211,7 @@ impl GuardrailsEvaluator for GuardrailsService {
             .cloned()?;
 
         if stage != guard.stage() {
-            return Ok(GuardResult::Boolean {
-                passed: true,
-                confidence: None,
-            });
+            return Ok(None);
         }
 
         let template = self
@@ -186,12 +265,332 @@ impl GuardrailsEvaluator for GuardrailsService {
         let evaluator = self.get_evaluator(&guard, executor_context)?;
         let result = evaluator.evaluate(messages, &guard).await?;
 
-        match guard.action() {
-            GuardAction::Validate => Ok(result),
-            GuardAction::Observe => Ok(GuardResult::Boolean {
+        Ok(Some((guard.action().clone(), result)))
+    }
+}
+
+#[async_trait::async_trait]
+impl GuardrailsEvaluator for GuardrailsService {
+    async fn evaluate(
+        &self,
+        messages: &[ChatCompletionMessage],
+        guard_id: &str,
+        executor_context: &ExecutorContext,
+        parameters: Option<&serde_json::Value>,
+        stage: &GuardStage,
+    ) -> Result<GuardResult, String> {
+        let result = self
+            .evaluate_guard(messages, guard_id, executor_context, parameters, stage)
+            .await?;
+
+        match result {
+            Some((GuardAction::Validate, result)) => Ok(result),
+            Some((GuardAction::Observe, _)) | None => Ok(GuardResult::Boolean {
                 passed: true,
                 confidence: None,
             }),
         }
     }
+
+    async fn observe(
+        &self,
+        messages: &[ChatCompletionMessage],
+        guard_id: &str,
+        executor_context: &ExecutorContext,
+        parameters: Option<&serde_json::Value>,
+        stage: &GuardStage,
+    ) -> Result<Option<GuardResult>, String> {
+        let observes = self
+            .guards
+            .get(guard_id)
+            .is_some_and(|guard| guard.action() == &GuardAction::Observe);
+        if !observes {
+            return Ok(None);
+        }
+
+        let result = self
+            .evaluate_guard(messages, guard_id, executor_context, parameters, stage)
+            .await?;
+
+        Ok(match result {
+            Some((GuardAction::Observe, result)) => Some(result),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{web, App, HttpMessage, HttpResponse, HttpServer};
+    use langdb_core::events::SPAN_MODEL_CALL;
+    use langdb_core::handler::AvailableModels;
+    use langdb_core::models::ModelMetadata;
+    use langdb_core::test_utils::SpanFields;
+    use langdb_core::types::credentials::{ApiKeyCredentials, Credentials};
+    use langdb_core::types::gateway::ChatCompletionContent;
+    use langdb_core::usage::InMemoryStorage;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::callback_handler::init_callback_handler;
+    use crate::cost::GatewayCostCalculator;
+
+    async fn judge_completion() -> HttpResponse {
+        HttpResponse::Ok().json(serde_json::json!({
+            "id": "chatcmpl-judge",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "{\"passed\":true}"},
+                "finish_reason": "stop",
+                "logprobs": null
+            }],
+            "usage": {"prompt_tokens": 20, "completion_tokens": 5, "total_tokens": 25}
+        }))
+    }
+
+    fn judge_model(endpoint: &str) -> ModelMetadata {
+        serde_yaml::from_str(&format!(
+            r#"
+model: gpt-4o-mini
+model_provider: openai
+inference_provider:
+  provider: openai
+  model_name: gpt-4o-mini
+  endpoint: {endpoint}
+price:
+  per_input_token: 0.15
+  per_output_token: 0.6
+  valid_from: null
+input_formats:
+- text
+output_formats:
+- text
+capabilities: []
+type: completions
+limits:
+  max_context_size: 128000
+description: Judge model
+"#
+        ))
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_judge_call_goes_through_executor() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = HttpServer::new(|| {
+            App::new().route("/v1/chat/completions", web::post().to(judge_completion))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        let server_handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let models = vec![judge_model(&endpoint)];
+        let storage = Arc::new(tokio::sync::Mutex::new(InMemoryStorage::new()));
+        let callback_handler =
+            init_callback_handler(storage.clone(), GatewayCostCalculator::new(models.clone()));
+
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        req.extensions_mut()
+            .insert(Credentials::ApiKey(ApiKeyCredentials {
+                api_key: "test-key".to_string(),
+            }));
+        let executor_context = ExecutorContext::new(
+            callback_handler,
+            Arc::new(Box::new(GatewayCostCalculator::new(models.clone()))),
+            AvailableModels(models),
+            &req,
+            Arc::new(Box::new(GuardrailsService::new(HashMap::new()))),
+        )
+        .unwrap();
+
+        let spans = SpanFields::default();
+        let subscriber = tracing_subscriber::registry().with(spans.clone());
+        let _guard = ::tracing::subscriber::set_default(subscriber);
+
+        let response = GatewayGuardExecutor::new(executor_context)
+            .complete(
+                "openai/gpt-4o-mini",
+                vec![ChatCompletionMessage::new_text(
+                    "user".to_string(),
+                    "Is this toxic?".to_string(),
+                )],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.content,
+            Some(ChatCompletionContent::Text("{\"passed\":true}".to_string()))
+        );
+        assert!(spans.of(SPAN_MODEL_CALL).is_some());
+
+        // Usage is recorded by the callback handler in the background
+        let mut metrics = BTreeMap::new();
+        for _ in 0..50 {
+            metrics = storage.lock().await.get_all_counters().await;
+            if !metrics.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let openai = metrics
+            .get("openai")
+            .expect("judge call recorded in metrics");
+        assert_eq!(openai.models.len(), 1);
+
+        server_handle.stop(false).await;
+    }
+
+    /// Embeds texts about refunds along one axis and everything else along another,
+    /// recording the model and key of every request
+    async fn embeddings(
+        body: web::Json<Value>,
+        req: actix_web::HttpRequest,
+        requests: web::Data<Mutex<Vec<(String, String)>>>,
+    ) -> HttpResponse {
+        requests.lock().unwrap().push((
+            body["model"].as_str().unwrap_or_default().to_string(),
+            req.headers()
+                .get("authorization")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string(),
+        ));
+        let data = body["input"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let embedding = if text.as_str().unwrap().contains("refund") {
+                    [1.0, 0.0]
+                } else {
+                    [0.0, 1.0]
+                };
+                serde_json::json!({"object": "embedding", "embedding": embedding, "index": index})
+            })
+            .collect::<Vec<_>>();
+        HttpResponse::Ok().json(serde_json::json!({
+            "object": "list",
+            "data": data,
+            "model": body["model"],
+            "usage": {"prompt_tokens": 8, "total_tokens": 8}
+        }))
+    }
+
+    fn embedding_model(endpoint: &str) -> ModelMetadata {
+        serde_yaml::from_str(&format!(
+            r#"
+model: bge-small
+model_provider: vllm
+inference_provider:
+  provider: vllm
+  model_name: bge-small
+  endpoint: {endpoint}
+price:
+  per_input_token: 0.01
+  valid_from: null
+input_formats:
+- text
+output_formats:
+- text
+capabilities: []
+type: embeddings
+limits:
+  max_context_size: 512
+description: Self-hosted embedding model
+"#
+        ))
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_dataset_guard_uses_configured_embedding_model() {
+        let requests = web::Data::new(Mutex::new(Vec::<(String, String)>::new()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = HttpServer::new({
+            let requests = requests.clone();
+            move || {
+                App::new()
+                    .app_data(requests.clone())
+                    .route("/v1/embeddings", web::post().to(embeddings))
+            }
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        let server_handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let models = vec![embedding_model(&endpoint)];
+        let storage = Arc::new(tokio::sync::Mutex::new(InMemoryStorage::new()));
+        let callback_handler =
+            init_callback_handler(storage, GatewayCostCalculator::new(models.clone()));
+        let req = actix_web::test::TestRequest::default().to_http_request();
+        req.extensions_mut()
+            .insert(Credentials::ApiKey(ApiKeyCredentials {
+                api_key: "vllm-key".to_string(),
+            }));
+
+        let guard: Guard = serde_json::from_value(serde_json::json!({
+            "type": "dataset",
+            "id": "no-refunds",
+            "name": "No refunds",
+            "template_id": "compliance-company-policy",
+            "stage": "input",
+            "action": "validate",
+            "embedding_model": "vllm/bge-small",
+            "threshold": 0.9,
+            "dataset": {"examples": [
+                {"text": "Can I get my money back", "label": false, "embedding": [1.0, 0.0]},
+                {"text": "What are your opening hours", "label": true, "embedding": null}
+            ]},
+            "schema": {}
+        }))
+        .unwrap();
+        let service = GuardrailsService::new(HashMap::from([("no-refunds".to_string(), guard)]));
+        let executor_context = ExecutorContext::new(
+            callback_handler,
+            Arc::new(Box::new(GatewayCostCalculator::new(models.clone()))),
+            AvailableModels(models),
+            &req,
+            Arc::new(Box::new(GuardrailsService::new(HashMap::new()))),
+        )
+        .unwrap();
+
+        let result = service
+            .evaluate(
+                &[ChatCompletionMessage::new_text(
+                    "user".to_string(),
+                    "I want a refund for my order".to_string(),
+                )],
+                "no-refunds",
+                &executor_context,
+                None,
+                &GuardStage::Input,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(result, GuardResult::Boolean { passed: false, .. }));
+
+        // The message and the example without an embedding are embedded in one call
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![("bge-small".to_string(), "Bearer vllm-key".to_string())]
+        );
+
+        server_handle.stop(false).await;
+    }
 }
@@ -1,10 +1,11 @@
 use crate::callback_handler::init_callback_handler;
-use crate::config::{load_langdb_proxy_config, Config};
-use crate::cost::GatewayCostCalculator;
-use crate::guardrails::GuardrailsService;
+use crate::cli::ServeArgs;
+use crate::config::{load_langdb_proxy_config, Config, ConfigError};
+use crate::cost::{GatewayCostCalculator, PricingOverrides, PricingOverridesError};
 use crate::limit::GatewayLimitChecker;
 use crate::middleware::trace_logger::TraceLogger;
 use crate::otel::DummyTraceWritterTransport;
+use crate::reload::{snapshot, ConfigReloader};
 use actix_cors::Cors;
 use actix_web::Scope as ActixScope;
 use actix_web::{
@@ -16,25 +17,46 @@ use actix_web::{
 use futures::{future::try_join, Future, TryFutureExt};
 use langdb_core::database::clickhouse::ClickhouseHttp;
 use langdb_core::database::DatabaseTransportClone;
+use langdb_core::events::bodies::BodyRecording;
+use langdb_core::executor::chat_completion::code_fences::StripCodeFences;
+use langdb_core::executor::chat_completion::merge_messages::MergeConsecutiveMessages;
+use langdb_core::executor::chat_completion::prompt_limit::PromptLimit;
+use langdb_core::executor::chat_completion::response_cache::ResponseCache;
+use langdb_core::executor::chat_completion::stream_wrapper::KeepAliveConfig;
+use langdb_core::executor::chat_completion::tool_limit::ToolLimit;
+use langdb_core::executor::chat_completion::transforms::TransformPipeline;
+use langdb_core::executor::chat_completion::{EventChannelCapacity, RequireUser, StreamFallback};
 use langdb_core::executor::ProvidersConfig;
+use langdb_core::handler::admin::{get_metrics, get_rate_limits, reset_model_metrics, AdminConfig};
 use langdb_core::handler::chat::create_chat_completion;
 use langdb_core::handler::embedding::embeddings_handler;
-use langdb_core::handler::image::create_image;
+use langdb_core::handler::image::{create_image, create_image_edit, create_image_variation};
+use langdb_core::handler::middleware::auth::{AllowAll, AuthMiddleware, AuthProvider};
+use langdb_core::handler::middleware::header_tags::HeaderTagsMiddleware;
 use langdb_core::handler::middleware::rate_limit::{RateLimitMiddleware, RateLimiting};
+use langdb_core::handler::middleware::request_id::RequestIdMiddleware;
+use langdb_core::handler::middleware::response_headers::ResponseHeadersMiddleware;
+use langdb_core::handler::model_access::{ApiKeysConfig, TenantModels};
 use langdb_core::handler::models::list_gateway_models;
-use langdb_core::handler::{AvailableModels, CallbackHandlerFn, LimitCheckWrapper};
+use langdb_core::handler::reload::ReloadableState;
+use langdb_core::handler::usage::get_usage;
+use langdb_core::handler::{CallbackHandlerFn, LimitCheckWrapper};
+use langdb_core::model::tls::set_provider_tls;
 use langdb_core::models::ModelMetadata;
 use langdb_core::otel::database::DatabaseSpanWritter;
 use langdb_core::otel::DummyTraceTenantResolver;
 use langdb_core::otel::ProjectTraceMap;
 use langdb_core::otel::SpanWriterTransport;
 use langdb_core::otel::{TraceMap, TraceServiceImpl, TraceServiceServer};
+use langdb_core::pricing::currency::{CurrencyConverter, CurrencyCostCalculator};
+use langdb_core::pricing::estimate::RequestCostCeiling;
+use langdb_core::types::credentials::CredentialFingerprinting;
 use langdb_core::types::gateway::CostCalculator;
-use langdb_core::types::guardrails::service::GuardrailsEvaluator;
-use langdb_core::types::guardrails::Guard;
+use langdb_core::types::guardrails::{BlockedAsContentFilter, ReturnBlockedOutput};
+use langdb_core::types::provider::BedrockConfig;
 use langdb_core::usage::InMemoryStorage;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::signal;
@@ -55,16 +77,63 @@ pub enum ServerError {
     Tonic(#[from] tonic::transport::Error),
     #[error(transparent)]
     AddrParseError(#[from] std::net::AddrParseError),
+    #[error(transparent)]
+    PricingOverrides(#[from] PricingOverridesError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
 }
 
 #[derive(Clone, Debug)]
 pub struct ApiServer {
     config: Config,
+    config_path: Option<PathBuf>,
+    cli_overrides: Option<ServeArgs>,
+    auth_provider: Option<Arc<dyn AuthProvider>>,
+    transforms: TransformPipeline,
 }
 
 impl ApiServer {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            config_path: None,
+            cli_overrides: None,
+            auth_provider: None,
+            transforms: TransformPipeline::default(),
+        }
+    }
+
+    /// Authenticates `/v1` requests with `auth_provider` instead of the configured `auth`
+    pub fn with_auth_provider(mut self, auth_provider: Arc<dyn AuthProvider>) -> Self {
+        self.auth_provider = Some(auth_provider);
+        self
+    }
+
+    /// Runs `transforms` on chat completion requests and responses
+    pub fn with_transforms(mut self, transforms: TransformPipeline) -> Self {
+        self.transforms = transforms;
+        self
+    }
+
+    /// The injected provider, else bearer keys from the config, else allow-all
+    fn auth_provider(&self) -> Arc<dyn AuthProvider> {
+        match (&self.auth_provider, &self.config.auth) {
+            (Some(provider), _) => provider.clone(),
+            (None, Some(auth)) => Arc::new(auth.clone()),
+            (None, None) => Arc::new(AllowAll),
+        }
+    }
+
+    /// Reloads models, routers and guards from `config_path` on SIGHUP
+    pub fn with_config_path(mut self, config_path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(config_path.into());
+        self
+    }
+
+    /// CLI options applied over the config file again on every reload
+    pub fn with_cli_overrides(mut self, args: ServeArgs) -> Self {
+        self.cli_overrides = Some(args);
+        self
     }
 
     pub fn print_useful_info(&self) {
@@ -110,12 +179,75 @@ impl ApiServer {
         models: Vec<ModelMetadata>,
         storage: Option<Arc<Mutex<InMemoryStorage>>>,
     ) -> Result<impl Future<Output = Result<(), ServerError>>, ServerError> {
+        if let Some(provider_tls) = &self.config.provider_tls {
+            set_provider_tls(provider_tls.clone());
+        }
         let trace_senders = Arc::new(TraceMap::new());
         let trace_senders_inner = Arc::clone(&trace_senders);
         let server_config = self.clone();
 
-        let cost_calculator = GatewayCostCalculator::new(models.clone());
+        let pricing_overrides = match &self.config.pricing_overrides {
+            Some(path) => PricingOverrides::load(path)?,
+            None => PricingOverrides::default(),
+        };
+        let cost_calculator =
+            GatewayCostCalculator::new(models.clone()).with_overrides(pricing_overrides);
+        let currency = match &self.config.currency {
+            Some(config) => {
+                let converter = CurrencyConverter::new(config);
+                converter.spawn_refresh(config);
+                converter
+            }
+            None => CurrencyConverter::usd(),
+        };
+        let request_cost_ceiling = self
+            .config
+            .cost_control
+            .as_ref()
+            .and_then(|cost_control| cost_control.per_request)
+            .map(RequestCostCeiling);
+        let request_id = self.config.http.request_id_middleware()?;
+        let header_tags = self
+            .config
+            .tracing
+            .as_ref()
+            .map(|tracing| tracing.header_tags_middleware())
+            .transpose()
+            .map_err(ConfigError::InvalidConfig)?
+            .unwrap_or_default();
+        let response_headers = ResponseHeadersMiddleware::new(
+            self.config
+                .http
+                .response_headers
+                .clone()
+                .unwrap_or_default(),
+        );
+        let credential_fingerprinting = self
+            .config
+            .tracing
+            .as_ref()
+            .and_then(|tracing| tracing.credential_fingerprinting());
+        let body_recording = self
+            .config
+            .tracing
+            .as_ref()
+            .and_then(|tracing| tracing.bodies.clone());
+        let reloadable_state = ReloadableState::new(snapshot(&self.config, models));
+        if let Some(config_path) = &self.config_path {
+            let mut reloader = ConfigReloader::new(
+                config_path,
+                reloadable_state.clone(),
+                cost_calculator.clone(),
+            );
+            if let Some(args) = &self.cli_overrides {
+                reloader = reloader.with_cli_overrides(args.clone());
+            }
+            reloader.spawn_on_sighup();
+        }
+        let response_cache = ResponseCache::new(&self.config.response_cache.unwrap_or_default());
+        let auth = AuthMiddleware::new(self.auth_provider());
         let callback = if let Some(storage) = &storage {
+            storage.lock().await.spawn_window_pruning();
             init_callback_handler(storage.clone(), cost_calculator.clone())
         } else {
             CallbackHandlerFn(None)
@@ -143,13 +275,36 @@ impl ApiServer {
                 cors,
                 storage.clone(),
                 trace_senders_inner.clone(),
-                models.clone(),
-                server_config.config.guards.clone(),
+                reloadable_state.clone(),
                 callback.clone(),
                 cost_calculator.clone(),
                 limit_checker.clone(),
                 server_config.config.rate_limit.clone(),
                 providers_config,
+                server_config.config.bedrock.clone(),
+                server_config.config.http.sse_keepalive.clone(),
+                server_config.config.api_keys.clone(),
+                server_config.config.tenant_models.clone(),
+                credential_fingerprinting,
+                currency.clone(),
+                request_cost_ceiling,
+                server_config.config.require_user,
+                server_config.config.return_blocked_output,
+                server_config.config.blocked_as_content_filter,
+                server_config.config.stream_fallback,
+                server_config.config.strip_code_fences,
+                request_id.clone(),
+                header_tags.clone(),
+                response_headers.clone(),
+                server_config.config.admin.clone(),
+                body_recording.clone(),
+                response_cache.clone(),
+                server_config.config.merge_consecutive_messages.clone(),
+                auth.clone(),
+                server_config.config.tool_limit,
+                server_config.config.prompt_limit.clone(),
+                server_config.transforms.clone(),
+                server_config.config.event_channel_capacity,
             )
         })
         .bind((self.config.http.host.as_str(), self.config.http.port))?
@@ -169,6 +324,7 @@ impl ApiServer {
             Arc::new(ProjectTraceMap::new()),
             writer,
             Box::new(DummyTraceTenantResolver),
+            server_config.config.traces.unwrap_or_default(),
         ));
         let tonic_server = tonic::transport::Server::builder()
             .add_service(trace_service)
@@ -189,13 +345,36 @@ impl ApiServer {
         cors: Cors,
         in_memory_storage: Option<Arc<Mutex<InMemoryStorage>>>,
         trace_senders: Arc<TraceMap>,
-        models: Vec<ModelMetadata>,
-        guards: Option<HashMap<String, Guard>>,
+        reloadable_state: ReloadableState,
         callback: CallbackHandlerFn,
         cost_calculator: GatewayCostCalculator,
         limit_checker: Option<LimitCheckWrapper>,
         rate_limit: Option<RateLimiting>,
         providers: Option<ProvidersConfig>,
+        bedrock: Option<BedrockConfig>,
+        sse_keepalive: Option<KeepAliveConfig>,
+        api_keys: Option<ApiKeysConfig>,
+        tenant_models: Option<TenantModels>,
+        credential_fingerprinting: Option<CredentialFingerprinting>,
+        currency: CurrencyConverter,
+        request_cost_ceiling: Option<RequestCostCeiling>,
+        require_user: bool,
+        return_blocked_output: bool,
+        blocked_as_content_filter: bool,
+        stream_fallback: bool,
+        strip_code_fences: bool,
+        request_id: RequestIdMiddleware,
+        header_tags: HeaderTagsMiddleware,
+        response_headers: ResponseHeadersMiddleware,
+        admin: Option<AdminConfig>,
+        body_recording: Option<BodyRecording>,
+        response_cache: ResponseCache,
+        merge_consecutive_messages: Option<MergeConsecutiveMessages>,
+        auth: AuthMiddleware,
+        tool_limit: Option<ToolLimit>,
+        prompt_limit: Option<PromptLimit>,
+        transforms: TransformPipeline,
+        event_channel_capacity: Option<EventChannelCapacity>,
     ) -> App<
         impl ServiceFactory<
             ServiceRequest,
@@ -208,7 +387,9 @@ impl ApiServer {
         let app = App::new();
 
         let mut service = Self::attach_gateway_routes(web::scope("/v1"));
+        let mut admin_service = Self::admin_routes(web::scope("/admin"));
         if let Some(in_memory_storage) = in_memory_storage {
+            admin_service = admin_service.app_data(in_memory_storage.clone());
             service = service.app_data(in_memory_storage);
         }
 
@@ -216,23 +397,97 @@ impl ApiServer {
             service = service.app_data(providers.clone());
         }
 
-        let guardrails_service = Box::new(GuardrailsService::new(guards.unwrap_or_default()))
-            as Box<dyn GuardrailsEvaluator>;
+        if let Some(bedrock) = bedrock {
+            service = service.app_data(bedrock);
+        }
+
+        if let Some(sse_keepalive) = sse_keepalive {
+            service = service.app_data(sse_keepalive);
+        }
+
+        if let Some(api_keys) = api_keys {
+            service = service.app_data(api_keys);
+        }
+
+        if let Some(tenant_models) = tenant_models {
+            service = service.app_data(tenant_models);
+        }
+
+        if let Some(credential_fingerprinting) = credential_fingerprinting {
+            service = service.app_data(credential_fingerprinting);
+        }
+
+        if let Some(request_cost_ceiling) = request_cost_ceiling {
+            service = service.app_data(request_cost_ceiling);
+        }
+
+        if require_user {
+            service = service.app_data(RequireUser);
+        }
+
+        if return_blocked_output {
+            service = service.app_data(ReturnBlockedOutput);
+        }
+
+        if blocked_as_content_filter {
+            service = service.app_data(BlockedAsContentFilter);
+        }
+
+        if stream_fallback {
+            service = service.app_data(StreamFallback);
+        }
+
+        if strip_code_fences {
+            service = service.app_data(StripCodeFences);
+        }
+
+        if let Some(admin) = admin {
+            admin_service = admin_service.app_data(admin);
+        }
+
+        if let Some(body_recording) = body_recording {
+            service = service.app_data(body_recording);
+        }
+
+        if let Some(merge_consecutive_messages) = merge_consecutive_messages {
+            service = service.app_data(merge_consecutive_messages);
+        }
+
+        if let Some(tool_limit) = tool_limit {
+            service = service.app_data(tool_limit);
+        }
+
+        if let Some(prompt_limit) = prompt_limit {
+            service = service.app_data(prompt_limit);
+        }
+
+        if let Some(event_channel_capacity) = event_channel_capacity {
+            service = service.app_data(event_channel_capacity);
+        }
+
         app.wrap(TraceLogger)
             .service(
                 service
                     .app_data(limit_checker)
                     .app_data(Data::new(callback))
                     .app_data(web::Data::from(trace_senders.clone()))
-                    .app_data(Data::new(AvailableModels(models)))
-                    .app_data(Data::new(
-                        Box::new(cost_calculator) as Box<dyn CostCalculator>
-                    ))
+                    .app_data(reloadable_state)
+                    .app_data(Data::new(Box::new(CurrencyCostCalculator::new(
+                        cost_calculator,
+                        currency.clone(),
+                    )) as Box<dyn CostCalculator>))
+                    .app_data(currency)
                     .app_data(rate_limit)
-                    .app_data(Data::new(guardrails_service))
-                    .wrap(RateLimitMiddleware),
+                    .app_data(response_cache)
+                    .app_data(transforms)
+                    .wrap(RateLimitMiddleware)
+                    .wrap(auth),
             )
+            .service(admin_service)
+            .wrap(header_tags)
+            .wrap(response_headers)
             .wrap(cors)
+            .wrap(request_id)
     }
 
     fn get_cors(cors: CorsOptions) -> Cors {
@@ -249,7 +504,21 @@ impl ApiServer {
         scope
             .route("/chat/completions", web::post().to(create_chat_completion))
             .route("/models", web::get().to(list_gateway_models))
+            .route("/usage", web::get().to(get_usage))
             .route("/embeddings", web::post().to(embeddings_handler))
             .route("/images/generations", web::post().to(create_image))
+            .route("/images/edits", web::post().to(create_image_edit))
+            .route("/images/variations", web::post().to(create_image_variation))
+    }
+
+    /// Admin endpoints, authorized by the admin key alone rather than the gateway auth
+    fn admin_routes(scope: ActixScope) -> ActixScope {
+        scope
+            .route("/metrics", web::get().to(get_metrics))
+            .route("/rate_limits", web::get().to(get_rate_limits))
+            .route(
+                "/metrics/{provider}/{model}",
+                web::delete().to(reset_model_metrics),
+            )
     }
 }
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use langdb_core::{
     handler::{DollarUsage, LimitCheck},
+    pricing::currency::USD,
     usage::{InMemoryStorage, LimitPeriod},
 };
 use tokio::sync::Mutex;
@@ -48,6 +49,7 @@ impl GatewayLimitChecker {
             monthly_limit: self.cost_control.monthly,
             total: total_usage.unwrap_or(0.0),
             total_limit: self.cost_control.total,
+            currency: USD.to_string(),
         })
     }
 }
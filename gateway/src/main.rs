@@ -17,6 +17,7 @@ mod http;
 mod limit;
 mod middleware;
 mod otel;
+mod reload;
 mod run;
 mod session;
 mod tracing;
@@ -76,7 +77,7 @@ async fn main() -> Result<(), CliError> {
     {
         cli::Commands::Login => session::login().await,
         cli::Commands::Update { force } => {
-            tracing::init_tracing();
+            tracing::init_tracing(None)?;
             println!("Updating models{}...", if force { " (forced)" } else { "" });
             // No support of force update yet, always load models from a prepared model file
             // to update it to true after backend api is ready, TODO
@@ -85,7 +86,7 @@ async fn main() -> Result<(), CliError> {
             Ok(())
         }
         cli::Commands::List => {
-            tracing::init_tracing();
+            tracing::init_tracing(None)?;
             println!("Available models:");
             let models = load_models(false).await?;
             run::table::pretty_print_models(models);
@@ -105,8 +106,10 @@ async fn main() -> Result<(), CliError> {
                     tokio::spawn(async move { Tui::spawn_counter_loop(storage, counters).await });
 
                 let config = Config::load(&cli.config)?;
-                let config = config.apply_cli_overrides(&cli::Commands::Serve(serve_args));
-                let api_server = ApiServer::new(config);
+                let config = config.apply_cli_overrides(&cli::Commands::Serve(serve_args.clone()));
+                let api_server = ApiServer::new(config)
+                    .with_config_path(&cli.config)
+                    .with_cli_overrides(serve_args);
                 let models = load_models(false).await?;
                 let server_handle = tokio::spawn(async move {
                     match api_server.start(models, Some(storage_clone)).await {
@@ -148,11 +151,12 @@ async fn main() -> Result<(), CliError> {
                     }
                 }
             } else {
-                tracing::init_tracing();
-
                 let config = Config::load(&cli.config)?;
-                let config = config.apply_cli_overrides(&cli::Commands::Serve(serve_args));
-                let api_server = ApiServer::new(config);
+                let config = config.apply_cli_overrides(&cli::Commands::Serve(serve_args.clone()));
+                tracing::init_tracing(config.tracing.as_ref())?;
+                let api_server = ApiServer::new(config)
+                    .with_config_path(&cli.config)
+                    .with_cli_overrides(serve_args);
                 let models = load_models(false).await?;
                 let server_handle = tokio::spawn(async move {
                     let storage = Arc::new(Mutex::new(InMemoryStorage::new()));
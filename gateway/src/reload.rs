@@ -0,0 +1,300 @@
+use crate::cli::{Commands, ServeArgs};
+use crate::config::{Config, ConfigError};
+use crate::cost::{GatewayCostCalculator, PricingOverrides, PricingOverridesError};
+use crate::guardrails::GuardrailsService;
+use crate::run::models::{load_models, ModelsLoadError};
+use langdb_core::handler::reload::{ReloadableSnapshot, ReloadableState};
+use langdb_core::handler::AvailableModels;
+use langdb_core::models::ModelMetadata;
+use langdb_core::types::guardrails::service::GuardrailsEvaluator;
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ReloadError {
+    #[error("Config file {0} not found")]
+    MissingConfig(PathBuf),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Models(#[from] ModelsLoadError),
+    #[error(transparent)]
+    PricingOverrides(#[from] PricingOverridesError),
+}
+
+/// Models, routers and guards of `config` that can be swapped without a restart
+pub fn snapshot(config: &Config, models: Vec<ModelMetadata>) -> ReloadableSnapshot {
+    let evaluator = Box::new(
+        GuardrailsService::new(config.guards.clone().unwrap_or_default())
+            .with_default_judge(config.guard_judge.clone()),
+    ) as Box<dyn GuardrailsEvaluator>;
+
+    ReloadableSnapshot {
+        models: AvailableModels(models),
+        default_routers: config.default_routers.clone(),
+        evaluator: Arc::new(evaluator),
+    }
+}
+
+/// Re-reads the config file and swaps the models, routers, guards and prices in use
+#[derive(Clone)]
+pub struct ConfigReloader {
+    config_path: PathBuf,
+    state: ReloadableState,
+    cost_calculator: GatewayCostCalculator,
+    cli_overrides: Option<ServeArgs>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config_path: impl Into<PathBuf>,
+        state: ReloadableState,
+        cost_calculator: GatewayCostCalculator,
+    ) -> Self {
+        Self {
+            config_path: config_path.into(),
+            state,
+            cost_calculator,
+            cli_overrides: None,
+        }
+    }
+
+    /// CLI options applied over the reloaded config, as they were at startup
+    pub fn with_cli_overrides(mut self, args: ServeArgs) -> Self {
+        self.cli_overrides = Some(args);
+        self
+    }
+
+    pub async fn reload(&self) -> Result<(), ReloadError> {
+        let models = load_models(false).await?;
+        self.reload_with_models(models)
+    }
+
+    fn reload_with_models(&self, models: Vec<ModelMetadata>) -> Result<(), ReloadError> {
+        // A missing file falls back to the default config at startup, on reload it would
+        // silently drop every guard and router
+        if !self.config_path.exists() {
+            return Err(ReloadError::MissingConfig(self.config_path.clone()));
+        }
+
+        let mut config = Config::load(&self.config_path)?;
+        if let Some(args) = &self.cli_overrides {
+            config = config.apply_cli_overrides(&Commands::Serve(args.clone()));
+        }
+        let pricing_overrides = match &config.pricing_overrides {
+            Some(path) => PricingOverrides::load(path)?,
+            None => PricingOverrides::default(),
+        };
+        let snapshot = snapshot(&config, models.clone());
+        tracing::info!(
+            "Reloaded config from {}: {} models, {} default routers",
+            self.config_path.display(),
+            snapshot.models.0.len(),
+            snapshot.default_routers.as_ref().map_or(0, |r| r.0.len())
+        );
+        self.cost_calculator.reload(models, pricing_overrides);
+        self.state.swap(snapshot);
+        Ok(())
+    }
+
+    /// Reloads the config every time the process receives SIGHUP. The handler is installed
+    /// before this returns, so a SIGHUP sent afterwards no longer terminates the process.
+    pub fn spawn_on_sighup(self) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(e) => {
+                    tracing::error!("Failed to listen for SIGHUP: {e}");
+                    return;
+                }
+            };
+
+            tokio::spawn(async move {
+                while hangup.recv().await.is_some() {
+                    if let Err(e) = self.reload().await {
+                        tracing::error!("Failed to reload config: {e}");
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+    use langdb_core::handler::models::list_gateway_models;
+    use langdb_core::handler::reload::current_default_routers;
+    use langdb_core::types::gateway::{
+        CompletionModelUsage, CostCalculator, CostCalculatorError, Usage,
+    };
+    use langdb_core::types::provider::ModelPrice;
+
+    fn model(name: &str) -> ModelMetadata {
+        serde_yaml::from_str(&format!(
+            r#"
+model: {name}
+model_provider: openai
+inference_provider:
+  provider: openai
+  model_name: {name}
+  endpoint: null
+price:
+  per_input_token: 1.0
+  per_output_token: 2.0
+  valid_from: null
+input_formats:
+- text
+output_formats:
+- text
+capabilities: []
+type: completions
+limits:
+  max_context_size: 128000
+description: {name}
+"#
+        ))
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_reload_swaps_models_and_routers() {
+        let config_path =
+            std::env::temp_dir().join(format!("ai-gateway-reload-{}.yaml", std::process::id()));
+        std::fs::write(&config_path, "{}").unwrap();
+
+        let state = ReloadableState::new(snapshot(
+            &Config::load(&config_path).unwrap(),
+            vec![model("gpt-4o")],
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .route("/models", web::get().to(list_gateway_models)),
+        )
+        .await;
+
+        let body =
+            test::call_and_read_body(&app, test::TestRequest::get().uri("/models").to_request())
+                .await;
+        assert!(!String::from_utf8_lossy(&body).contains("gpt-4o-mini"));
+
+        std::fs::write(
+            &config_path,
+            r#"
+default_routers:
+  openai/gpt-4o-mini:
+    type: fallback
+    targets:
+      - model: openai/gpt-4o-mini
+      - model: openai/gpt-4o
+"#,
+        )
+        .unwrap();
+        let reloader = ConfigReloader::new(
+            &config_path,
+            state.clone(),
+            GatewayCostCalculator::new(vec![]),
+        );
+        reloader
+            .reload_with_models(vec![model("gpt-4o"), model("gpt-4o-mini")])
+            .unwrap();
+
+        let body =
+            test::call_and_read_body(&app, test::TestRequest::get().uri("/models").to_request())
+                .await;
+        assert!(String::from_utf8_lossy(&body).contains("gpt-4o-mini"));
+
+        let req = test::TestRequest::default()
+            .app_data(state.clone())
+            .to_http_request();
+        let routers = current_default_routers(&req).unwrap();
+        assert!(routers.0.contains_key("openai/gpt-4o-mini"));
+
+        std::fs::remove_file(&config_path).unwrap();
+        assert!(matches!(
+            reloader.reload_with_models(vec![]),
+            Err(ReloadError::MissingConfig(_))
+        ));
+        assert_eq!(state.current().models.0.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[actix_web::test]
+    async fn test_sighup_reloads_models_and_prices() {
+        let config_path =
+            std::env::temp_dir().join(format!("ai-gateway-sighup-{}.yaml", std::process::id()));
+        let pricing_path = std::env::temp_dir().join(format!(
+            "ai-gateway-sighup-pricing-{}.yaml",
+            std::process::id()
+        ));
+        std::fs::write(
+            &pricing_path,
+            "openai:\n  private-model:\n    per_input_token: 1.0\n    per_output_token: 2.0\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &config_path,
+            format!("pricing_overrides: {}\n", pricing_path.display()),
+        )
+        .unwrap();
+
+        let state = ReloadableState::new(snapshot(&Config::load(&config_path).unwrap(), vec![]));
+        let cost_calculator = GatewayCostCalculator::new(vec![]);
+        ConfigReloader::new(&config_path, state.clone(), cost_calculator.clone())
+            .with_cli_overrides(ServeArgs::default())
+            .spawn_on_sighup();
+
+        let models = load_models(false).await.unwrap();
+        let usage = Usage::CompletionModelUsage(CompletionModelUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 1_000_000,
+            total_tokens: 2_000_000,
+            ..Default::default()
+        });
+        let model = models
+            .iter()
+            .find(|m| matches!(m.price, ModelPrice::Completion(_)))
+            .unwrap();
+        let provider = model.inference_provider.provider.to_string();
+        assert!(matches!(
+            cost_calculator
+                .calculate_cost(&model.model, &provider, &usage)
+                .await,
+            Err(CostCalculatorError::ModelNotFound)
+        ));
+
+        let status = std::process::Command::new("kill")
+            .args(["-HUP", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let reloaded = async {
+            while state.current().models.0.len() != models.len() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), reloaded)
+            .await
+            .unwrap();
+
+        assert!(cost_calculator
+            .calculate_cost(&model.model, &provider, &usage)
+            .await
+            .is_ok());
+        let result = cost_calculator
+            .calculate_cost("private-model", "openai", &usage)
+            .await
+            .unwrap();
+        assert!((result.cost - 3.0).abs() < 1e-9);
+
+        std::fs::remove_file(&config_path).unwrap();
+        std::fs::remove_file(&pricing_path).unwrap();
+    }
+}
@@ -98,3 +98,72 @@ pub fn get_models_path() -> Result<String, std::io::Error> {
     }
     Ok(include_str!("../../models.yaml").to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use langdb_core::executor::chat_completion::resolve_execution_options;
+    use langdb_core::types::engine::ExecutionOptions;
+    use langdb_core::types::gateway::{ChatCompletionRequest, ChatCompletionRequestWithTools};
+
+    const MODELS_YAML: &str = r#"
+- model: gpt-4o-mini
+  model_provider: openai
+  inference_provider:
+    provider: openai
+    model_name: gpt-4o-mini
+    endpoint: null
+  price:
+    per_input_token: 0.15
+    per_output_token: 0.6
+    valid_from: null
+  input_formats:
+  - text
+  output_formats:
+  - text
+  capabilities: []
+  type: completions
+  limits:
+    max_context_size: 128000
+  description: Small model
+  execution_options:
+    max_retries: 3
+    timeout_secs: 30
+    retry_backoff_ms: 500
+"#;
+
+    fn request(max_retries: Option<i32>) -> ChatCompletionRequestWithTools<()> {
+        ChatCompletionRequestWithTools {
+            request: ChatCompletionRequest {
+                model: "openai/gpt-4o-mini".to_string(),
+                ..Default::default()
+            },
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_per_model_execution_options() {
+        let models: Vec<ModelMetadata> = serde_yaml::from_str(MODELS_YAML).unwrap();
+
+        assert_eq!(
+            resolve_execution_options(&request(None), &models[0]),
+            ExecutionOptions {
+                max_retries: Some(3),
+                max_tool_call_iterations: None,
+                timeout_secs: Some(30),
+                retry_backoff_ms: Some(500),
+                connect_timeout_ms: None,
+                read_timeout_secs: None,
+                empty_completion_retries: None,
+            }
+        );
+
+        // Request values take precedence
+        assert_eq!(
+            resolve_execution_options(&request(Some(1)), &models[0]).max_retries,
+            Some(1)
+        );
+    }
+}
@@ -1,41 +1,212 @@
-use langdb_core::events::{self, BaggageSpanProcessor};
+use crate::config::ConfigError;
+use langdb_core::events::bodies::BodyRecording;
+use langdb_core::events::{self, BaggageSpanProcessor, CorrelatedFormat, DEFAULT_BAGGAGE_KEYS};
+use langdb_core::handler::middleware::header_tags::HeaderTagsMiddleware;
+use langdb_core::types::credentials::{CredentialFingerprinting, UNSAFE_LOG_CREDENTIALS_ENV};
 use opentelemetry::trace::TracerProvider as _;
-use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_otlp::{WithExportConfig, WithTonicConfig};
+use opentelemetry_sdk::trace::{BatchSpanProcessor, SdkTracerProvider, SpanProcessor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Layer, Registry};
 
-pub fn init_tracing() {
+/// An OTLP (gRPC) exporter spans are sent to
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct OtlpExporterConfig {
+    /// Collector endpoint, defaults to `OTEL_EXPORTER_OTLP_ENDPOINT` or `http://localhost:4317`
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Metadata sent with every export, e.g. vendor API keys
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TracingConfig {
+    /// Spans are exported to every exporter. Without exporters spans go to the default
+    /// OTLP endpoint, which is the gateway's own trace writer.
+    #[serde(default)]
+    pub exporters: Vec<OtlpExporterConfig>,
+    /// Baggage entries copied onto span attributes in addition to the `langdb.*` defaults
+    #[serde(default)]
+    pub baggage_keys: Vec<String>,
+    /// Request headers whose values are recorded on spans, keyed by header name, with the
+    /// attribute each one is recorded as
+    #[serde(default)]
+    pub header_tags: HashMap<String, String>,
+    /// Adds a fingerprint of the credentials used to model call spans. Local debugging
+    /// only, and ignored unless `LANGDB_UNSAFE_LOG_CREDENTIALS=true` is also set.
+    #[serde(default)]
+    pub unsafe_log_credentials: bool,
+    /// Whether request and response bodies are recorded in full, hashed or not at all
+    #[serde(default)]
+    pub bodies: Option<BodyRecording>,
+}
+
+impl TracingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(bodies) = &self.bodies {
+            bodies.validate()?;
+        }
+        self.header_tags_middleware()?;
+        self.exporters
+            .iter()
+            .try_for_each(OtlpExporterConfig::validate)?;
+        self.baggage_keys
+            .iter()
+            .chain(self.header_tags.values())
+            .try_for_each(|key| events::validate_baggage_key(key))
+    }
+
+    pub fn header_tags_middleware(&self) -> Result<HeaderTagsMiddleware, String> {
+        HeaderTagsMiddleware::new(&self.header_tags)
+            .map_err(|e| format!("invalid header in header_tags: {e}"))
+    }
+
+    /// Returns the fingerprinting marker when both the config flag and the env var are set
+    pub fn credential_fingerprinting(&self) -> Option<CredentialFingerprinting> {
+        let env_enabled = std::env::var(UNSAFE_LOG_CREDENTIALS_ENV).is_ok_and(|v| v == "true");
+        credential_fingerprinting(self.unsafe_log_credentials, env_enabled)
+    }
+
+    fn baggage_keys(&self) -> Vec<String> {
+        let mut keys = DEFAULT_BAGGAGE_KEYS.map(String::from).to_vec();
+        for key in self.baggage_keys.iter().chain(self.header_tags.values()) {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+        keys
+    }
+
+    fn span_processors(&self) -> Result<Vec<BatchSpanProcessor>, String> {
+        let exporters = if self.exporters.is_empty() {
+            vec![OtlpExporterConfig::default()]
+        } else {
+            self.exporters.clone()
+        };
+        exporters
+            .iter()
+            .map(|config| Ok(BatchSpanProcessor::builder(config.build_exporter()?).build()))
+            .collect()
+    }
+}
+
+fn credential_fingerprinting(
+    config_enabled: bool,
+    env_enabled: bool,
+) -> Option<CredentialFingerprinting> {
+    match (config_enabled, env_enabled) {
+        (true, true) => {
+            tracing::warn!(
+                "UNSAFE: unsafe_log_credentials is enabled, credential fingerprints will be \
+                 recorded on model spans. Never enable this in production."
+            );
+            Some(CredentialFingerprinting)
+        }
+        (true, false) => {
+            tracing::warn!(
+                "unsafe_log_credentials is set but ignored because {UNSAFE_LOG_CREDENTIALS_ENV} \
+                 is not true"
+            );
+            None
+        }
+        (false, _) => None,
+    }
+}
+
+impl OtlpExporterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.metadata()?;
+        if let Some(endpoint) = &self.endpoint {
+            let uri = endpoint
+                .parse::<tonic::transport::Uri>()
+                .map_err(|e| format!("invalid OTLP exporter endpoint {endpoint}: {e}"))?;
+            if !matches!(uri.scheme_str(), Some("http" | "https")) || uri.host().is_none() {
+                return Err(format!(
+                    "OTLP exporter endpoint {endpoint} must be an http(s) URL with a host"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn metadata(&self) -> Result<tonic::metadata::MetadataMap, String> {
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        for (key, value) in &self.headers {
+            let key = tonic::metadata::MetadataKey::from_bytes(key.as_bytes())
+                .map_err(|e| format!("invalid OTLP exporter header {key}: {e}"))?;
+            let value = value
+                .parse()
+                .map_err(|e| format!("invalid value of OTLP exporter header {key}: {e}"))?;
+            metadata.insert(key, value);
+        }
+        Ok(metadata)
+    }
+
+    fn build_exporter(&self) -> Result<opentelemetry_otlp::SpanExporter, String> {
+        let mut builder = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_metadata(self.metadata()?);
+        if let Some(endpoint) = &self.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(timeout_secs) = self.timeout_secs {
+            builder = builder.with_timeout(Duration::from_secs(timeout_secs));
+        }
+        builder
+            .build()
+            .map_err(|e| format!("failed to build OTLP exporter: {e}"))
+    }
+}
+
+/// Builds a tracer provider that copies `baggage_keys` onto spans and hands every span
+/// to each of `processors`
+pub fn tracer_provider<P: SpanProcessor + 'static>(
+    processors: Vec<P>,
+    baggage_keys: Vec<String>,
+) -> SdkTracerProvider {
+    let baggage = BaggageSpanProcessor::new(baggage_keys);
+    let mut builder = SdkTracerProvider::builder().with_span_processor(baggage);
+    for processor in processors {
+        builder = builder.with_span_processor(processor);
+    }
+    builder
+        .with_id_generator(events::UuidIdGenerator::default())
+        .build()
+}
+
+pub fn init_tracing(config: Option<&TracingConfig>) -> Result<(), ConfigError> {
     let log_level = std::env::var("RUST_LOG").unwrap_or("info".to_string());
     let env_filter = EnvFilter::new(log_level).add_directive("actix_server=off".parse().unwrap());
     let color = std::env::var("ANSI_OUTPUT").map_or(true, |v| v == "true");
 
     // tracing syntax ->
-    let builder = tracing_subscriber::fmt::layer()
+    let format = tracing_subscriber::fmt::format()
         .compact()
         .with_line_number(false)
         .with_file(false)
         .with_thread_ids(false)
         .with_thread_names(false)
         .with_target(false)
+        .with_ansi(color);
+    let builder = tracing_subscriber::fmt::layer()
         .with_ansi(color)
+        .event_format(CorrelatedFormat::new(format))
         .with_filter(env_filter);
 
-    let otlp_exporter = opentelemetry_otlp::SpanExporter::builder()
-        .with_tonic()
-        .build()
-        .unwrap();
-    let provider = SdkTracerProvider::builder()
-        .with_span_processor(BaggageSpanProcessor::new([
-            "langdb.parent_trace_id",
-            "langdb.run_id",
-            "langdb.label",
-        ]))
-        .with_batch_exporter(otlp_exporter)
-        .with_id_generator(events::UuidIdGenerator::default())
-        .build();
+    let config = config.cloned().unwrap_or_default();
+    let processors = config
+        .span_processors()
+        .map_err(ConfigError::InvalidConfig)?;
+    let provider = tracer_provider(processors, config.baggage_keys());
     let tracer = provider.tracer("langdb-ai-gateway");
     opentelemetry::global::set_tracer_provider(provider);
 
@@ -45,6 +216,7 @@ pub fn init_tracing() {
         .with(otel_layer)
         .try_init()
         .expect("initialized subscriber successfully");
+    Ok(())
 }
 
 pub fn init_tui_tracing(sender: Sender<String>) {
@@ -100,3 +272,244 @@ impl std::io::Write for LogWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Tracer, TracerProvider as _};
+    use opentelemetry_sdk::error::OTelSdkResult;
+    use opentelemetry_sdk::trace::SpanData;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default)]
+    struct RecordingProcessor {
+        spans: Arc<Mutex<Vec<SpanData>>>,
+    }
+
+    impl SpanProcessor for RecordingProcessor {
+        fn on_start(
+            &self,
+            _span: &mut opentelemetry_sdk::trace::Span,
+            _cx: &opentelemetry::Context,
+        ) {
+        }
+
+        fn on_end(&self, span: SpanData) {
+            self.spans.lock().unwrap().push(span);
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+
+        fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_exporters() {
+        let config: TracingConfig = serde_yaml::from_str(
+            r#"
+exporters:
+  - endpoint: http://localhost:4317
+  - endpoint: https://otlp.vendor.example:4317
+    headers:
+      x-api-key: secret
+    timeout_secs: 5
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.exporters.len(), 2);
+        assert_eq!(
+            config.exporters[1].headers.get("x-api-key"),
+            Some(&"secret".to_string())
+        );
+        assert_eq!(config.exporters[1].timeout_secs, Some(5));
+        assert!(TracingConfig::default().exporters.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_exporters_are_rejected() {
+        for exporter in [
+            OtlpExporterConfig {
+                endpoint: Some("localhost:4317".to_string()),
+                ..Default::default()
+            },
+            OtlpExporterConfig {
+                endpoint: Some("http://".to_string()),
+                ..Default::default()
+            },
+            OtlpExporterConfig {
+                headers: HashMap::from([("x api key".to_string(), "secret".to_string())]),
+                ..Default::default()
+            },
+            OtlpExporterConfig {
+                headers: HashMap::from([("x-api-key".to_string(), "line\nbreak".to_string())]),
+                ..Default::default()
+            },
+        ] {
+            let config = TracingConfig {
+                exporters: vec![exporter],
+                ..Default::default()
+            };
+            assert!(config.validate().is_err());
+        }
+
+        let config = TracingConfig {
+            exporters: vec![OtlpExporterConfig {
+                endpoint: Some("https://otlp.vendor.example:4317".to_string()),
+                headers: HashMap::from([("x-api-key".to_string(), "secret".to_string())]),
+                timeout_secs: None,
+            }],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_spans_reach_every_processor() {
+        let processors = (0..2)
+            .map(|_| RecordingProcessor::default())
+            .collect::<Vec<_>>();
+        let recorded = processors
+            .iter()
+            .map(|p| p.spans.clone())
+            .collect::<Vec<_>>();
+
+        let provider = tracer_provider(processors, vec![]);
+        let tracer = provider.tracer("test");
+        tracer.in_span("api_invoke", |_| {});
+        provider.force_flush().unwrap();
+
+        for spans in recorded {
+            let names = spans
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|s| s.name.to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(names, vec!["api_invoke".to_string()]);
+        }
+    }
+
+    #[test]
+    fn test_custom_baggage_keys() {
+        use opentelemetry::baggage::BaggageExt;
+        use opentelemetry::{Context, KeyValue};
+
+        let config: TracingConfig = serde_yaml::from_str(
+            r#"
+baggage_keys:
+  - tenant.region
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let processor = RecordingProcessor::default();
+        let spans = processor.spans.clone();
+        let provider = tracer_provider(vec![processor], config.baggage_keys());
+        let tracer = provider.tracer("test");
+
+        let cx = Context::current().with_baggage(vec![
+            KeyValue::new("tenant.region", "eu-west-1"),
+            KeyValue::new("langdb.run_id", "run-1"),
+            KeyValue::new("internal.secret", "hidden"),
+        ]);
+        tracer.start_with_context("api_invoke", &cx).end();
+        provider.force_flush().unwrap();
+
+        let spans = spans.lock().unwrap();
+        let attributes = spans[0]
+            .attributes
+            .iter()
+            .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(attributes.get("tenant.region").unwrap(), "eu-west-1");
+        assert_eq!(attributes.get("langdb.run_id").unwrap(), "run-1");
+        assert!(!attributes.contains_key("internal.secret"));
+    }
+
+    #[actix_web::test]
+    async fn test_header_tags_become_span_attributes() {
+        use actix_web::{test, web, App, HttpResponse};
+        use opentelemetry::trace::Tracer;
+
+        let config: TracingConfig = serde_yaml::from_str(
+            r#"
+header_tags:
+  x-env: env
+  x-team: team
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let processor = RecordingProcessor::default();
+        let spans = processor.spans.clone();
+        let provider = tracer_provider(vec![processor], config.baggage_keys());
+        let tracer = provider.tracer("test");
+        let app = test::init_service(
+            App::new()
+                .wrap(config.header_tags_middleware().unwrap())
+                .route(
+                    "/",
+                    web::get().to(move || {
+                        let tracer = tracer.clone();
+                        async move {
+                            tracer.start("api_invoke").end();
+                            HttpResponse::Ok()
+                        }
+                    }),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("x-env", "staging"))
+            .insert_header(("x-other", "hidden"))
+            .to_request();
+        test::call_service(&app, req).await;
+        provider.force_flush().unwrap();
+
+        let spans = spans.lock().unwrap();
+        let attributes = spans[0]
+            .attributes
+            .iter()
+            .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(attributes.get("env").unwrap(), "staging");
+        assert!(!attributes.contains_key("team"));
+        assert!(!attributes.contains_key("x-other"));
+    }
+
+    #[test]
+    fn test_invalid_baggage_keys() {
+        for key in ["", "tenant region", "tenant,region", "région"] {
+            let config = TracingConfig {
+                baggage_keys: vec![key.to_string()],
+                ..Default::default()
+            };
+            assert!(config.validate().is_err(), "{key:?} should be rejected");
+        }
+
+        let config = TracingConfig {
+            header_tags: HashMap::from([("x env".to_string(), "env".to_string())]),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_credential_fingerprinting_requires_env() {
+        assert!(TracingConfig::default()
+            .credential_fingerprinting()
+            .is_none());
+        assert!(credential_fingerprinting(false, true).is_none());
+        assert!(credential_fingerprinting(true, false).is_none());
+        assert!(credential_fingerprinting(true, true).is_some());
+    }
+}
@@ -21,6 +21,7 @@ pub const TOTAL_TOKENS: &str = "total_tokens";
 pub const REQUESTS: &str = "requests";
 pub const REQUESTS_DURATION: &str = "requests_duration";
 pub const TTFT: &str = "ttft";
+pub const RATE_LIMITED: &str = "rate_limited";
 
 pub(crate) async fn update_usage(
     storage: Arc<Mutex<InMemoryStorage>>,
@@ -97,3 +98,25 @@ pub(crate) async fn update_usage(
 
     Ok(())
 }
+
+/// Counts a request `provider_name` rejected with a rate limit
+pub(crate) async fn update_rate_limits(
+    storage: Arc<Mutex<InMemoryStorage>>,
+    model_name: &str,
+    provider_name: &str,
+) {
+    let identifier = format!("{provider_name}:{model_name}");
+    for p in &[
+        LimitPeriod::Hour,
+        LimitPeriod::Day,
+        LimitPeriod::Month,
+        LimitPeriod::Total,
+    ] {
+        let v = storage
+            .lock()
+            .await
+            .increment_and_get_value(p, &identifier, RATE_LIMITED, 1.0)
+            .await;
+        tracing::debug!(target:"gateway::usage", "{p} rate limits: {v}");
+    }
+}
@@ -4,8 +4,93 @@ use langdb_core::types::guardrails::{
     evaluator::Evaluator, DatasetLoader, Guard, GuardExample, GuardResult,
 };
 
+/// Handle for embedding guard inputs. The gateway implements it on top of its embeddings
+/// executor so dataset guards can use any configured embedding model.
+#[async_trait::async_trait]
+pub trait GuardEmbedder: Send + Sync {
+    async fn embed(&self, model: &str, input: Vec<String>) -> Result<Vec<Vec<f32>>, String>;
+}
+
 pub struct DatasetEvaluator {
     pub loader: Box<dyn DatasetLoader + Send + Sync>,
+    /// Compares texts by embedding similarity, word overlap is used without it
+    pub embedder: Option<Box<dyn GuardEmbedder>>,
+}
+
+impl DatasetEvaluator {
+    /// Similarity of `text` to each of `examples`
+    async fn scores(
+        &self,
+        embedding_model: &str,
+        text: &str,
+        examples: &[GuardExample],
+    ) -> Result<Vec<f64>, String> {
+        let Some(embedder) = &self.embedder else {
+            return Ok(examples
+                .iter()
+                .map(|example| simple_similarity(&example.text, text))
+                .collect());
+        };
+
+        // Examples carrying an embedding aren't embedded again
+        let mut input = vec![text.to_string()];
+        input.extend(
+            examples
+                .iter()
+                .filter(|example| example.embedding.is_none())
+                .map(|example| example.text.clone()),
+        );
+        let mut embeddings = embedder.embed(embedding_model, input).await?.into_iter();
+        let text_embedding = embeddings
+            .next()
+            .ok_or("Embedding model returned no embeddings")?;
+
+        examples
+            .iter()
+            .map(|example| {
+                let embedding = match &example.embedding {
+                    Some(embedding) => embedding.clone(),
+                    None => embeddings
+                        .next()
+                        .ok_or("Embedding model returned too few embeddings")?,
+                };
+                Ok(cosine_similarity(&embedding, &text_embedding))
+            })
+            .collect()
+    }
+
+    async fn classify(
+        &self,
+        embedding_model: &str,
+        threshold: f64,
+        text: &str,
+        examples: &[GuardExample],
+    ) -> Result<GuardResult, String> {
+        let scores = self.scores(embedding_model, text, examples).await?;
+
+        let mut best_match = None;
+        let mut best_score = 0.0;
+        for (example, score) in examples.iter().zip(scores) {
+            if score > best_score {
+                best_score = score;
+                best_match = Some(example);
+            }
+        }
+
+        if best_score >= threshold {
+            if let Some(example) = best_match {
+                return Ok(GuardResult::Boolean {
+                    passed: example.label,
+                    confidence: Some(best_score),
+                });
+            }
+        }
+
+        Ok(GuardResult::Boolean {
+            passed: true,
+            confidence: Some(1.0 - best_score),
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -16,67 +101,24 @@ impl Evaluator for DatasetEvaluator {
         guard: &Guard,
     ) -> Result<GuardResult, String> {
         if let Guard::Dataset {
-            threshold, dataset, ..
+            embedding_model,
+            threshold,
+            dataset,
+            ..
         } = &guard
         {
             let text = self.messages_to_text(messages)?;
             match dataset {
                 langdb_core::types::guardrails::DatasetSource::Examples { examples } => {
-                    // Simple similarity check (in a real implementation, this would use embeddings)
-                    let mut best_match = None;
-                    let mut best_score = 0.0;
-
-                    for example in examples {
-                        let score = simple_similarity(&example.text, &text);
-                        if score > best_score {
-                            best_score = score;
-                            best_match = Some(example);
-                        }
-                    }
-
-                    if best_score >= *threshold {
-                        if let Some(example) = best_match {
-                            return Ok(GuardResult::Boolean {
-                                passed: example.label,
-                                confidence: Some(best_score),
-                            });
-                        }
-                    }
-
-                    Ok(GuardResult::Boolean {
-                        passed: true,
-                        confidence: Some(1.0 - best_score),
-                    })
+                    self.classify(embedding_model, *threshold, &text, examples)
+                        .await
                 }
                 langdb_core::types::guardrails::DatasetSource::Source { source } => {
                     // Load dataset from source
                     match self.loader.load(source).await {
                         Ok(examples) => {
-                            // Simple similarity check
-                            let mut best_match = None;
-                            let mut best_score = 0.0;
-
-                            for example in &examples {
-                                let score = simple_similarity(&example.text, &text);
-                                if score > best_score {
-                                    best_score = score;
-                                    best_match = Some(example);
-                                }
-                            }
-
-                            if best_score >= *threshold {
-                                if let Some(example) = best_match {
-                                    return Ok(GuardResult::Boolean {
-                                        passed: example.label,
-                                        confidence: Some(best_score),
-                                    });
-                                }
-                            }
-
-                            Ok(GuardResult::Boolean {
-                                passed: true,
-                                confidence: Some(1.0 - best_score),
-                            })
+                            self.classify(embedding_model, *threshold, &text, &examples)
+                                .await
                         }
                         Err(e) => Err(format!("Error loading dataset: {e}")),
                     }
@@ -91,7 +133,18 @@ impl Evaluator for DatasetEvaluator {
     }
 }
 
-// Simple similarity function (in a real implementation, this would use embeddings)
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(a, b)| *a as f64 * *b as f64).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+// Word overlap similarity, used when no embedder is configured
 fn simple_similarity(a: &str, b: &str) -> f64 {
     let a_words: Vec<&str> = a.split_whitespace().collect();
     let b_words: Vec<&str> = b.split_whitespace().collect();
@@ -0,0 +1,61 @@
+use langdb_core::types::gateway::ChatCompletionMessage;
+use langdb_core::types::guardrails::{evaluator::Evaluator, Guard, GuardResult, GuardStage};
+use whatlang::Lang;
+
+/// Sentences shorter than this are too short to detect reliably and are skipped
+const MIN_SENTENCE_WORDS: usize = 3;
+
+/// Language evaluator that checks the detected language of every sentence of the text
+/// against an allowlist
+pub struct LanguageEvaluator;
+
+fn is_allowed(lang: Lang, allowed: &[String]) -> bool {
+    allowed
+        .iter()
+        .any(|a| a.eq_ignore_ascii_case(lang.code()) || a.eq_ignore_ascii_case(lang.eng_name()))
+}
+
+/// Splits text into sentences long enough to detect. Falls back to the whole text when
+/// no sentence is.
+fn sentences(text: &str) -> Vec<&str> {
+    let sentences = text
+        .split(['.', '!', '?', '\n'])
+        .map(str::trim)
+        .filter(|s| s.split_whitespace().count() >= MIN_SENTENCE_WORDS)
+        .collect::<Vec<_>>();
+
+    if sentences.is_empty() {
+        vec![text]
+    } else {
+        sentences
+    }
+}
+
+#[async_trait::async_trait]
+impl Evaluator for LanguageEvaluator {
+    async fn evaluate(
+        &self,
+        messages: &[ChatCompletionMessage],
+        guard: &Guard,
+    ) -> Result<GuardResult, String> {
+        let text = self.messages_to_text(messages)?;
+        if let Guard::Language { config, allowed } = guard {
+            if config.stage != GuardStage::Input {
+                return Err("Language guard can only be applied at the input stage".to_string());
+            }
+
+            let mut passed = true;
+            let mut confidence: Option<f64> = None;
+            for info in sentences(&text).into_iter().filter_map(whatlang::detect) {
+                passed &= is_allowed(info.lang(), allowed);
+                confidence =
+                    Some(confidence.map_or(info.confidence(), |c| c.min(info.confidence())));
+            }
+
+            // Text without a detectable language, such as numbers or code, passes
+            Ok(GuardResult::Boolean { passed, confidence })
+        } else {
+            Err("Invalid guard type for LanguageEvaluator".to_string())
+        }
+    }
+}
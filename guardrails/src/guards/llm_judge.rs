@@ -1,41 +1,47 @@
 use langdb_core::types::guardrails::GuardModel;
 use langdb_core::types::guardrails::{evaluator::Evaluator, Guard, GuardResult};
 
-use langdb_core::{
-    error::GatewayError,
-    llm_gateway::message_mapper::MessageMapper,
-    model::ModelInstance,
-    types::{
-        gateway::{ChatCompletionContent, ChatCompletionMessage, ContentType},
-        threads::Message,
-    },
-};
+use langdb_core::types::gateway::{ChatCompletionContent, ChatCompletionMessage, ContentType};
 use serde_json::Value;
 use std::collections::HashMap;
-use tokio::sync::mpsc;
 
-use super::config::{default_suffix, load_prompts_from_yaml};
+use super::config::{default_judge_model, default_suffix, load_prompts_from_yaml};
 
+/// Handle for running judge completions. The gateway implements it on top of its own
+/// executor so judge calls are routed, traced and billed like any other request.
 #[async_trait::async_trait]
-pub trait GuardModelInstanceFactory: Send + Sync {
-    async fn init(&self, name: &str) -> Box<dyn ModelInstance>;
+pub trait GuardExecutor: Send + Sync {
+    async fn complete(
+        &self,
+        model: &str,
+        messages: Vec<ChatCompletionMessage>,
+    ) -> Result<ChatCompletionMessage, String>;
 }
 
 pub struct LlmJudgeEvaluator {
-    // We'll use this to create model instances for evaluation
-    pub model_factory: Box<dyn GuardModelInstanceFactory + Send + Sync>,
+    pub executor: Box<dyn GuardExecutor>,
     pub models: HashMap<String, GuardModel>,
+    /// Judge of guards that neither set a model nor use a template with one. Its system
+    /// prompt is also used for guard models without one.
+    pub default_model: GuardModel,
 }
 
 impl LlmJudgeEvaluator {
-    pub fn new(model_factory: Box<dyn GuardModelInstanceFactory + Send + Sync>) -> Self {
+    pub fn new(executor: Box<dyn GuardExecutor>) -> Self {
         let models = include_str!("./config/models.yaml");
         let models = load_prompts_from_yaml(models).unwrap();
         Self {
-            model_factory,
+            executor,
             models,
+            default_model: default_judge_model(),
         }
     }
+
+    /// Replaces the built-in default judge
+    pub fn with_default_model(mut self, default_model: GuardModel) -> Self {
+        self.default_model = default_model;
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -51,17 +57,10 @@ impl Evaluator for LlmJudgeEvaluator {
             ..
         } = &guard
         {
-            // Create a model instance
-
-            let model = match (guard_model, self.models.get(config.template_id.as_str())) {
-                (Some(guard_model), _) => guard_model,
-                (None, Some(model)) => model,
-                _ => {
-                    return Err(format!("Model not found in guard: {}", config.template_id));
-                }
-            };
-
-            let model_instance = self.model_factory.init(&model.model).await;
+            let model = guard_model
+                .as_ref()
+                .or_else(|| self.models.get(config.template_id.as_str()))
+                .unwrap_or(&self.default_model);
 
             let input_vars: HashMap<String, Value> = match guard.parameters() {
                 Some(metadata) => match serde_json::from_value(metadata.clone()) {
@@ -72,11 +71,12 @@ impl Evaluator for LlmJudgeEvaluator {
                 },
                 None => HashMap::new(),
             };
-            // Create a channel for model events
-            let (tx, _rx) = mpsc::channel(10);
-
             let mut guard_messages = vec![];
-            if let Some(system_prompt) = &model.system_prompt {
+            if let Some(system_prompt) = model
+                .system_prompt
+                .as_ref()
+                .or(self.default_model.system_prompt.as_ref())
+            {
                 guard_messages.push(ChatCompletionMessage {
                     role: "system".to_string(),
                     content: Some(ChatCompletionContent::Text(system_prompt.clone())),
@@ -84,7 +84,13 @@ impl Evaluator for LlmJudgeEvaluator {
                 });
             }
 
-            let mut user_prompt_template = model.user_prompt_template.clone();
+            let mut user_prompt_template = model
+                .user_prompt_template
+                .replace("{{guard_name}}", &config.name)
+                .replace(
+                    "{{guard_description}}",
+                    config.description.as_deref().unwrap_or_default(),
+                );
 
             for var in input_vars.keys() {
                 user_prompt_template = user_prompt_template
@@ -104,31 +110,16 @@ impl Evaluator for LlmJudgeEvaluator {
                 ..Default::default()
             });
 
-            let guard_messages = guard_messages
-                .iter()
-                .map(|message| {
-                    MessageMapper::map_completions_message_to_langdb_message(
-                        message,
-                        &model.model,
-                        "judge",
-                    )
-                })
-                .collect::<Result<Vec<Message>, GatewayError>>()
-                .map_err(|e| e.to_string())?;
-
             // Call the model
-            let result = model_instance
-                .invoke(input_vars, tx, guard_messages, HashMap::new())
-                .await;
+            let result = self.executor.complete(&model.model, guard_messages).await;
 
             match result {
                 Ok(response) => {
                     // Extract the response content
                     let content = extract_text_content(&response)?;
 
-                    // Try to parse as JSON
-                    match serde_json::from_str::<Value>(&content) {
-                        Ok(json) => {
+                    match parse_judge_output(&content) {
+                        Some(json) => {
                             let params = match &guard.parameters() {
                                 Some(m) => m,
                                 None => &serde_json::Value::Null,
@@ -136,14 +127,14 @@ impl Evaluator for LlmJudgeEvaluator {
                             // Use the parameters to determine how to interpret the response
                             Ok(interpret_json_response(json, params))
                         }
-                        Err(_) => {
-                            // If it's not JSON, just return the text
-                            Ok(GuardResult::Text {
-                                text: content,
-                                passed: true,
+                        // A judge may answer with just its verdict
+                        None => match verdict(&Value::String(content.clone())) {
+                            Some(passed) => Ok(GuardResult::Boolean {
+                                passed,
                                 confidence: None,
-                            })
-                        }
+                            }),
+                            None => Err(format!("Malformed LLM judge output: {content}")),
+                        },
                     }
                 }
                 Err(err) => Err(format!("LLM evaluation failed: {err}")),
@@ -177,6 +168,38 @@ fn extract_text_content(response: &ChatCompletionMessage) -> Result<String, Stri
     }
 }
 
+/// Finds the JSON object in a judge's answer, which models often wrap in a code block or
+/// surround with explanations
+fn parse_judge_output(content: &str) -> Option<Value> {
+    let content = content.trim();
+    let json = serde_json::from_str::<Value>(content).ok().or_else(|| {
+        let start = content.find('{')?;
+        let end = content.rfind('}')?;
+        serde_json::from_str(content.get(start..=end)?).ok()
+    })?;
+    json.is_object().then_some(json)
+}
+
+/// Pass or fail verdict, given as a boolean or as a word like "pass" or "fail"
+fn verdict(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(passed) => Some(*passed),
+        Value::String(text) => {
+            match text
+                .trim()
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+                .as_str()
+            {
+                "pass" | "passed" | "true" | "yes" => Some(true),
+                "fail" | "failed" | "false" | "no" => Some(false),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 // Interpret JSON response based on parameters
 fn interpret_json_response(json: Value, parameters: &Value) -> GuardResult {
     tracing::info!(
@@ -185,7 +208,7 @@ fn interpret_json_response(json: Value, parameters: &Value) -> GuardResult {
         parameters
     );
     // Check for common result fields first
-    if let Some(passed) = json.get("passed").and_then(|v| v.as_bool()) {
+    if let Some(passed) = json.get("passed").and_then(verdict) {
         let confidence = json.get("confidence").and_then(|v| v.as_f64());
         let details = json
             .get("details")
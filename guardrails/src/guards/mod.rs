@@ -1,8 +1,10 @@
 pub mod config;
 pub mod dataset;
+pub mod language;
 pub mod llm_judge;
 pub mod partner;
 pub mod partners;
+pub mod prompt_injection;
 pub mod regex;
 pub mod schema;
 pub mod traced;
@@ -12,8 +14,10 @@ pub mod word_count;
 pub mod tests;
 
 // Re-export evaluators
-pub use dataset::{DatasetEvaluator, FileDatasetLoader};
+pub use dataset::{DatasetEvaluator, FileDatasetLoader, GuardEmbedder};
+pub use language::LanguageEvaluator;
 pub use llm_judge::LlmJudgeEvaluator;
+pub use prompt_injection::PromptInjectionEvaluator;
 pub use regex::RegexEvaluator;
 pub use schema::SchemaEvaluator;
 pub use word_count::WordCountEvaluator;
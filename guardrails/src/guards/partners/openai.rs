@@ -63,7 +63,7 @@ impl GuardPartner for OpenaiGuardrailPartner {
                                             .url,
                                     },
                                 }),
-                                ContentType::InputAudio => Err(
+                                ContentType::InputAudio | ContentType::Audio => Err(
                                     GuardPartnerError::InputTypeNotSupported("audio".to_string()),
                                 ),
                             })
@@ -0,0 +1,173 @@
+use langdb_core::types::gateway::ChatCompletionMessage;
+use langdb_core::types::guardrails::{evaluator::Evaluator, Guard, GuardResult, GuardStage};
+use regex::Regex;
+
+use super::llm_judge::{GuardExecutor, LlmJudgeEvaluator};
+
+lazy_static::lazy_static! {
+    /// Common injection patterns with the likelihood that a message matching them is an
+    /// injection attempt
+    static ref PATTERNS: Vec<(&'static str, Regex, f64)> = [
+        (
+            "instruction override",
+            r"(?i)\b(ignore|disregard|forget|skip)\b.{0,20}\b(previous|prior|above|earlier|preceding|all)\b.{0,20}\b(instructions?|prompts?|rules|directions|context)\b",
+            0.9,
+        ),
+        (
+            "guideline bypass",
+            r"(?i)\b(ignore|disregard|bypass|forget)\b.{0,10}\byour\b.{0,20}\b(guidelines|rules|training|restrictions|safety)\b",
+            0.8,
+        ),
+        (
+            "prompt extraction",
+            r"(?i)\b(reveal|show|print|repeat|output|tell me)\b.{0,20}\b(your|the)\b.{0,10}\b(system (prompt|message)|prompt|instructions)\b",
+            0.7,
+        ),
+        (
+            "role override",
+            r"(?i)\b(you are now|from now on,? you|pretend (to be|you are)|act as an? (unrestricted|unfiltered|different))\b",
+            0.6,
+        ),
+        (
+            "jailbreak mode",
+            r"(?i)\b(developer mode|jailbreak|do anything now|DAN mode)\b",
+            0.7,
+        ),
+        (
+            "role markers",
+            r"(?im)(^\s*(system|assistant)\s*:|<\|im_start\|>|\[/?INST\]|^#+\s*system\b)",
+            0.6,
+        ),
+    ]
+    .into_iter()
+    .map(|(name, pattern, weight)| (name, Regex::new(pattern).unwrap(), weight))
+    .collect();
+}
+
+/// Prompt injection evaluator. Scores user messages with heuristics and, for guards with
+/// `judge` set, falls back to an LLM judge when the heuristics find nothing.
+pub struct PromptInjectionEvaluator {
+    judge: Option<LlmJudgeEvaluator>,
+}
+
+impl PromptInjectionEvaluator {
+    pub fn new(executor: Option<Box<dyn GuardExecutor>>) -> Self {
+        Self {
+            judge: executor.map(LlmJudgeEvaluator::new),
+        }
+    }
+}
+
+/// Names of the patterns matched by `text` and the combined injection score
+pub fn heuristic_score(text: &str, extra_patterns: &[String]) -> (Vec<String>, f64) {
+    let mut matched = vec![];
+    let mut clean = 1.0;
+
+    for (name, regex, weight) in PATTERNS.iter() {
+        if regex.is_match(text) {
+            matched.push(name.to_string());
+            clean *= 1.0 - weight;
+        }
+    }
+
+    let lowercase = text.to_lowercase();
+    for pattern in extra_patterns {
+        if lowercase.contains(&pattern.to_lowercase()) {
+            matched.push(format!("\"{pattern}\""));
+            clean *= 1.0 - 0.8;
+        }
+    }
+
+    (matched, 1.0 - clean)
+}
+
+/// Injection score of a judge result, whose confidence is in its decision
+fn judge_score(result: &GuardResult) -> f64 {
+    match result {
+        GuardResult::Boolean { passed, confidence }
+        | GuardResult::Text {
+            passed, confidence, ..
+        } => match (passed, confidence) {
+            (true, Some(c)) => 1.0 - c,
+            (false, Some(c)) => *c,
+            (true, None) => 0.0,
+            (false, None) => 1.0,
+        },
+        GuardResult::Json { passed, .. } => {
+            if *passed {
+                0.0
+            } else {
+                1.0
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Evaluator for PromptInjectionEvaluator {
+    async fn evaluate(
+        &self,
+        messages: &[ChatCompletionMessage],
+        guard: &Guard,
+    ) -> Result<GuardResult, String> {
+        if let Guard::PromptInjection {
+            config,
+            judge,
+            model,
+        } = guard
+        {
+            if config.stage != GuardStage::Input {
+                return Err(
+                    "Prompt injection guard can only be applied at the input stage".to_string(),
+                );
+            }
+
+            let parameters = guard.parameters();
+            let threshold = parameters
+                .and_then(|p| p["threshold"].as_f64())
+                .unwrap_or(0.7);
+            let extra_patterns: Vec<String> = parameters
+                .and_then(|p| serde_json::from_value(p["detection_patterns"].clone()).ok())
+                .unwrap_or_default();
+
+            let text = messages
+                .iter()
+                .filter(|m| m.role == "user")
+                .filter_map(|m| m.content.as_ref()?.as_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let (mut matched, mut score) = heuristic_score(&text, &extra_patterns);
+
+            if *judge && score < threshold {
+                let judge_evaluator = self
+                    .judge
+                    .as_ref()
+                    .ok_or("LLM judge is not available for prompt injection guard")?;
+                let judge_guard = Guard::LlmJudge {
+                    config: config.clone(),
+                    model: model.clone(),
+                };
+                let result = judge_evaluator.evaluate(messages, &judge_guard).await?;
+                let from_judge = judge_score(&result);
+                if from_judge > score {
+                    matched.push("llm judge".to_string());
+                    score = from_judge;
+                }
+            }
+
+            let text = if matched.is_empty() {
+                "No prompt injection patterns found".to_string()
+            } else {
+                format!("Prompt injection patterns found: {}", matched.join(", "))
+            };
+
+            Ok(GuardResult::Text {
+                text,
+                passed: score < threshold,
+                confidence: Some(score),
+            })
+        } else {
+            Err("Invalid guard type for PromptInjectionEvaluator".to_string())
+        }
+    }
+}
@@ -25,3 +25,6 @@ ALTER TABLE langdb.traces ADD INDEX idx_thread_id thread_id TYPE bloom_filter GR
 
 -- Add composite index for tenant_id, project_id, and operation_name
 ALTER TABLE langdb.traces ADD INDEX idx_tenant_project_op (tenant_id, project_id, operation_name) TYPE bloom_filter GRANULARITY 4;
+
+-- Full values of truncated attributes, used when `traces.blob_column` is enabled
+ALTER TABLE langdb.traces ADD COLUMN IF NOT EXISTS attribute_blobs Map(String, String);
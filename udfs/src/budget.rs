@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{InvokeError, Result};
+
+/// Rough token estimate used before a request is dispatched (~4 characters per token)
+pub fn estimate_tokens(values: &[String]) -> usize {
+    let chars = values.iter().map(|v| v.chars().count()).sum::<usize>();
+    chars.div_ceil(4).max(1)
+}
+
+/// Token budget shared by all lines of a UDF invocation.
+/// Tokens are reserved before dispatch and settled against the reported usage afterwards.
+#[derive(Debug, Default)]
+pub struct TokenBudget {
+    used: AtomicUsize,
+}
+
+impl TokenBudget {
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// Reserves `tokens` unless the budget would exceed `max_tokens`
+    pub fn reserve(&self, tokens: usize, max_tokens: usize) -> Result<()> {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                (used + tokens <= max_tokens).then_some(used + tokens)
+            })
+            .map(|_| ())
+            .map_err(|used| {
+                InvokeError::CustomError(format!(
+                    "Estimated total tokens: {} exceeds max tokens: {max_tokens}",
+                    used + tokens
+                ))
+            })
+    }
+
+    /// Replaces a reservation with the actual usage
+    pub fn settle(&self, reserved: usize, actual: usize) {
+        if actual >= reserved {
+            self.used.fetch_add(actual - reserved, Ordering::SeqCst);
+        } else {
+            self.release(reserved - actual);
+        }
+    }
+
+    /// Returns a reservation of a request that did not complete
+    pub fn release(&self, reserved: usize) {
+        self.used.fetch_sub(reserved, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(&[]), 1);
+        assert_eq!(estimate_tokens(&["abcd".to_string()]), 1);
+        assert_eq!(
+            estimate_tokens(&["abcde".to_string(), "abc".to_string()]),
+            2
+        );
+    }
+
+    #[test]
+    fn test_reserve_and_settle() {
+        let budget = TokenBudget::default();
+        budget.reserve(4, 10).unwrap();
+        budget.reserve(4, 10).unwrap();
+        assert!(budget.reserve(4, 10).is_err());
+        assert_eq!(budget.used(), 8);
+
+        budget.settle(4, 2);
+        budget.release(4);
+        assert_eq!(budget.used(), 2);
+        budget.reserve(8, 10).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_reservations() {
+        let budget = Arc::new(TokenBudget::default());
+        let handles = (0..16)
+            .map(|_| {
+                let budget = budget.clone();
+                std::thread::spawn(move || {
+                    (0..100).filter(|_| budget.reserve(3, 100).is_ok()).count()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let granted = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .sum::<usize>();
+        assert_eq!(granted, 33);
+        assert_eq!(budget.used(), 99);
+    }
+}
@@ -1,5 +1,6 @@
 use thiserror::Error;
 use tokio::task::JoinError;
+pub mod budget;
 pub mod completions;
 pub mod embed;
 mod tracing;
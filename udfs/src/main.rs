@@ -1,10 +1,12 @@
-use serde_json::Value;
-use std::sync::atomic::AtomicUsize;
+use clap::Parser;
+use serde_json::{json, Value};
+use std::future::Future;
 use std::sync::Arc;
 use tokio::io::AsyncBufReadExt;
 use tokio::io::{stderr, stdin, stdout, AsyncWriteExt, BufReader};
 use tokio::sync::Mutex;
 use tracing::{self, debug};
+use udfs::budget::{estimate_tokens, TokenBudget};
 use udfs::completions::completions;
 use udfs::embed::embed;
 use udfs::init_tracing;
@@ -13,37 +15,171 @@ use udfs::InvokeError;
 
 use udfs::FunctionConfig;
 
-const PARALLEL: usize = 100;
 const MAX_RETRIES: u32 = 3;
 const BACKOFF_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Parser)]
+#[command(about = "LangDB UDFs for ClickHouse")]
+struct UdfArgs {
+    /// Function to execute: completions or embed
+    udf: String,
+
+    #[command(flatten)]
+    processor: ProcessorConfig,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+struct ProcessorConfig {
+    /// Maximum number of lines processed concurrently
+    #[arg(long, env = "LANGDB_UDF_PARALLEL", default_value_t = 100, value_parser = parse_positive)]
+    parallel: usize,
+
+    /// Number of finished lines awaited before spawning more
+    #[arg(long, env = "LANGDB_UDF_BATCH_SIZE", default_value_t = 10, value_parser = parse_positive)]
+    batch_size: usize,
+
+    /// Delay between batches in milliseconds
+    #[arg(long, env = "LANGDB_UDF_BATCH_DELAY_MS", default_value_t = 100)]
+    batch_delay_ms: u64,
+
+    /// Write per-line errors to stdout and keep processing instead of aborting
+    #[arg(long, env = "LANGDB_UDF_CONTINUE_ON_ERROR")]
+    continue_on_error: bool,
+
+    /// Write results as soon as they complete, tagged with their line index, instead of in input order
+    #[arg(long, env = "LANGDB_UDF_UNORDERED")]
+    unordered: bool,
+}
+
+fn parse_positive(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be greater than 0".to_string()),
+        Ok(v) => Ok(v),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Result of a line task: the value to write in input order, or `None` if the task already wrote it
+type LineResult = Result<Option<Value>, InvokeError>;
+
+/// Lines spawned in order and awaited in batches once `parallel` are in flight
+struct OrderedTasks<W> {
+    config: ProcessorConfig,
+    writer: Arc<Mutex<W>>,
+    futures: Vec<(u64, tokio::task::JoinHandle<LineResult>)>,
+}
+
+impl<W> OrderedTasks<W>
+where
+    W: AsyncWriteExt + std::marker::Unpin,
+{
+    fn new(config: ProcessorConfig, writer: Arc<Mutex<W>>) -> Self {
+        Self {
+            futures: Vec::with_capacity(config.parallel),
+            config,
+            writer,
+        }
+    }
+
+    async fn spawn<F>(&mut self, line_number: u64, future: F) -> Result<(), InvokeError>
+    where
+        F: Future<Output = LineResult> + Send + 'static,
+    {
+        self.futures.push((line_number, tokio::spawn(future)));
+
+        // Process results when we hit the parallel limit
+        if self.futures.len() >= self.config.parallel {
+            self.process_batch().await?;
+        }
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<(), InvokeError> {
+        // Process any remaining futures
+        while !self.futures.is_empty() {
+            self.process_batch().await?;
+        }
+        Ok(())
+    }
+
+    async fn process_batch(&mut self) -> Result<(), InvokeError> {
+        use tokio::time::{sleep, Duration};
+        // Sort by line number to maintain order
+        self.futures.sort_by_key(|(num, _)| *num);
+
+        // Process futures in small batches with delay
+        let batch_size = std::cmp::min(self.config.batch_size, self.futures.len());
+        let batch: Vec<_> = self.futures.drain(0..batch_size).collect();
+
+        for (_, future) in batch {
+            match future.await {
+                Ok(result) => {
+                    if let Some(value) = result? {
+                        write_with_retries(&self.writer, value).await?;
+                    }
+                }
+                Err(e) => return Err(InvokeError::from(e)),
+            }
+        }
+
+        // Add small delay between batches
+        sleep(Duration::from_millis(self.config.batch_delay_ms)).await;
+
+        Ok(())
+    }
+}
+
 async fn process_line(
     config: &FunctionConfig,
     values: &mut std::slice::Iter<'_, String>,
-    writer: Arc<Mutex<impl AsyncWriteExt + Send + Unpin>>,
-    tokens: Arc<AtomicUsize>,
-) -> Result<(), InvokeError> {
+    budget: Arc<TokenBudget>,
+) -> Result<Value, InvokeError> {
     debug!(
         "Parsing CLI arguments: {:?}",
         std::env::args().collect::<Vec<_>>()
     );
 
+    // Reserve the estimated input tokens so lines that would exceed the budget are not dispatched
+    let max_tokens = config.max_tokens();
+    let reserved = match max_tokens {
+        Some(max_tokens) => {
+            let estimate = estimate_tokens(values.as_slice());
+            budget.reserve(estimate, max_tokens)?;
+            estimate
+        }
+        None => 0,
+    };
+
     let val = match &config {
         FunctionConfig::Completion(config) => completions(values, config).await,
         FunctionConfig::Embedding(config) => embed(values, config).await,
-    }?;
+    };
+    let val = match val {
+        Ok(val) => val,
+        Err(e) => {
+            budget.release(reserved);
+            return Err(e);
+        }
+    };
 
-    let max_tokens = config.max_tokens();
+    budget.settle(reserved, val.usage.total_tokens);
     if let Some(max_tokens) = max_tokens {
-        let val = tokens.fetch_add(val.usage.total_tokens, std::sync::atomic::Ordering::Relaxed);
-        if val > max_tokens {
+        let used = budget.used();
+        if used > max_tokens {
             return Err(InvokeError::CustomError(format!(
-                "Total tokens: {val} exceeds max tokens: {max_tokens}"
+                "Total tokens: {used} exceeds max tokens: {max_tokens}"
             )));
         }
     }
 
-    let response = val.response;
-    let values: Vec<Value> = vec![response];
+    Ok(val.response)
+}
+
+async fn write_with_retries<W>(writer: &Mutex<W>, value: Value) -> Result<(), InvokeError>
+where
+    W: AsyncWriteExt + std::marker::Unpin,
+{
+    let values: Vec<Value> = vec![value];
     let mut writer = writer.lock().await;
     let mut retries = 0;
 
@@ -65,7 +201,12 @@ async fn process_line(
     }
 }
 
-async fn execute_udf<R, W>(udf: &str, mut reader: R, writer: W) -> Result<(), InvokeError>
+async fn execute_udf<R, W>(
+    udf: &str,
+    processor: ProcessorConfig,
+    mut reader: R,
+    writer: W,
+) -> Result<(), InvokeError>
 where
     R: tokio::io::AsyncBufRead + std::marker::Unpin,
     W: AsyncWriteExt + std::marker::Unpin + Send + 'static,
@@ -73,9 +214,10 @@ where
     let writer = Arc::new(Mutex::new(writer));
     let mut line = String::new();
 
-    let tokens = Arc::new(AtomicUsize::new(0));
-    // Create a buffer to store futures with their order
-    let mut futures = Vec::with_capacity(PARALLEL);
+    let budget = Arc::new(TokenBudget::default());
+    let continue_on_error = processor.continue_on_error;
+    let unordered = processor.unordered;
+    let mut tasks = OrderedTasks::new(processor, writer.clone());
     let mut line_number = 0u64;
 
     loop {
@@ -87,74 +229,56 @@ where
                     continue;
                 }
 
-                let line_clone = line.trim().to_string();
+                let parsed = parse_line(udf, line.trim());
                 let writer_clone = writer.clone();
+                let budget_clone = budget.clone();
 
-                let tokens_clone = tokens.clone();
-
-                let values: Vec<String> = serde_json::from_str(line_clone.as_str())?;
-                let config = values.first().cloned().ok_or_else(|| {
-                    InvokeError::CustomError("No configuration provided".to_string())
-                })?;
-                let config = parse_function_config(udf, &config)?;
-                let remaining_values = values[1..].to_vec();
-
-                futures.push((
-                    line_number,
-                    tokio::spawn(async move {
-                        process_line(
-                            &config,
-                            &mut remaining_values.iter(),
-                            writer_clone,
-                            tokens_clone,
-                        )
-                        .await
-                    }),
-                ));
+                tasks
+                    .spawn(line_number, async move {
+                        let result = match parsed {
+                            Ok((config, remaining_values)) => {
+                                process_line(&config, &mut remaining_values.iter(), budget_clone)
+                                    .await
+                            }
+                            Err(e) => Err(e),
+                        };
 
-                line_number += 1;
+                        let value = match result {
+                            Ok(value) if unordered => {
+                                json!({ "line": line_number, "result": value })
+                            }
+                            Ok(value) => value,
+                            Err(e) if continue_on_error => {
+                                json!({ "error": e.to_string(), "line": line_number })
+                            }
+                            Err(e) => return Err(e),
+                        };
 
-                // Process results when we hit the parallel limit or on last line
-                if futures.len() >= PARALLEL {
-                    process_ordered_futures(&mut futures).await?;
-                }
+                        if unordered {
+                            write_with_retries(&writer_clone, value).await?;
+                            Ok(None)
+                        } else {
+                            Ok(Some(value))
+                        }
+                    })
+                    .await?;
+
+                line_number += 1;
             }
             Err(e) => return Err(InvokeError::from(e)),
         }
     }
 
-    // Process any remaining futures
-    while !futures.is_empty() {
-        process_ordered_futures(&mut futures).await?;
-    }
-
-    Ok(())
+    tasks.finish().await
 }
 
-async fn process_ordered_futures(
-    futures: &mut Vec<(u64, tokio::task::JoinHandle<Result<(), InvokeError>>)>,
-) -> Result<(), InvokeError> {
-    use tokio::time::{sleep, Duration};
-    const BATCH_SIZE: usize = 10;
-    const BATCH_DELAY_MS: u64 = 100;
-    // Sort by line number to maintain order
-    futures.sort_by_key(|(num, _)| *num);
-
-    // Process futures in small batches with delay
-    let batch_size = std::cmp::min(BATCH_SIZE, futures.len());
-    let batch: Vec<_> = futures.drain(0..batch_size).collect();
-
-    for (_, future) in batch {
-        match future.await {
-            Ok(result) => result?,
-            Err(e) => return Err(InvokeError::from(e)),
-        }
-    }
-
-    // Add small delay between batches
-    sleep(Duration::from_millis(BATCH_DELAY_MS)).await;
-
-    Ok(())
+fn parse_line(udf: &str, line: &str) -> Result<(FunctionConfig, Vec<String>), InvokeError> {
+    let values: Vec<String> = serde_json::from_str(line)?;
+    let config = values
+        .first()
+        .ok_or_else(|| InvokeError::CustomError("No configuration provided".to_string()))?;
+    let config = parse_function_config(udf, config)?;
+    Ok((config, values[1..].to_vec()))
 }
 
 #[tokio::main]
@@ -168,13 +292,13 @@ async fn main() -> Result<(), InvokeError> {
     let stdout = stdout();
     let mut stderr = stderr();
     let reader = BufReader::new(stdin);
-    let args: Vec<String> = std::env::args().collect();
-    let udf_str = &args[1].trim().to_string();
+    let args = UdfArgs::parse();
+    let udf_str = args.udf.trim();
 
     // Wrap stdout in a BufWriter to make it cloneable
     let writer = tokio::io::BufWriter::new(stdout);
 
-    let res = execute_udf(udf_str, reader, writer).await;
+    let res = execute_udf(udf_str, args.processor, reader, writer).await;
     if let Err(e) = res {
         stderr.write_all(format!("{e}").as_bytes()).await?;
         stderr.flush().await?;
@@ -191,3 +315,272 @@ where
     writer.flush().await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_default_args() {
+        let args = UdfArgs::try_parse_from(["langdb_udf", "completions"]).unwrap();
+        assert_eq!(args.udf, "completions");
+        assert_eq!(args.processor.parallel, 100);
+        assert_eq!(args.processor.batch_size, 10);
+        assert_eq!(args.processor.batch_delay_ms, 100);
+        assert!(!args.processor.continue_on_error);
+        assert!(!args.processor.unordered);
+    }
+
+    #[test]
+    fn test_processor_args() {
+        let args = UdfArgs::try_parse_from([
+            "langdb_udf",
+            "embed",
+            "--parallel",
+            "8",
+            "--batch-size",
+            "4",
+            "--batch-delay-ms",
+            "0",
+        ])
+        .unwrap();
+        assert_eq!(args.udf, "embed");
+        assert_eq!(args.processor.parallel, 8);
+        assert_eq!(args.processor.batch_size, 4);
+        assert_eq!(args.processor.batch_delay_ms, 0);
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        assert!(UdfArgs::try_parse_from(["langdb_udf", "embed", "--parallel", "0"]).is_err());
+        assert!(UdfArgs::try_parse_from(["langdb_udf", "embed", "--batch-size", "-1"]).is_err());
+        assert!(UdfArgs::try_parse_from(["langdb_udf", "embed", "--parallel", "many"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_respects_parallelism() {
+        let config = ProcessorConfig {
+            parallel: 5,
+            batch_size: 2,
+            batch_delay_ms: 0,
+            continue_on_error: false,
+            unordered: false,
+        };
+        let mut tasks = OrderedTasks::new(config, Arc::new(Mutex::new(tokio::io::sink())));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        for line_number in 0..30 {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            tasks
+                .spawn(line_number, async move {
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok(None)
+                })
+                .await
+                .unwrap();
+        }
+        tasks.finish().await.unwrap();
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 5);
+        assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+    }
+
+    /// Serves every request with the input length as embedding, reporting `total_tokens`.
+    /// Shorter inputs respond slower so that lines complete out of order.
+    async fn mock_embeddings_server(total_tokens: usize) -> String {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut received = vec![];
+                    let mut buf = [0u8; 4096];
+                    loop {
+                        let n = socket.read(&mut buf).await.unwrap_or(0);
+                        received.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&received).to_string();
+                        let complete = text.find("\r\n\r\n").is_some_and(|end| {
+                            let content_length = text[..end]
+                                .lines()
+                                .find_map(|l| {
+                                    l.to_lowercase()
+                                        .strip_prefix("content-length:")
+                                        .and_then(|v| v.trim().parse::<usize>().ok())
+                                })
+                                .unwrap_or(0);
+                            received.len() >= end + 4 + content_length
+                        });
+                        if complete || n == 0 {
+                            break;
+                        }
+                    }
+
+                    let text = String::from_utf8_lossy(&received).to_string();
+                    let request = text
+                        .find("\r\n\r\n")
+                        .and_then(|end| serde_json::from_str::<Value>(&text[end + 4..]).ok())
+                        .unwrap_or_default();
+                    let length = request["input"].as_str().map_or(0, |i| i.len());
+                    tokio::time::sleep(tokio::time::Duration::from_millis(
+                        200 / length.max(1) as u64,
+                    ))
+                    .await;
+
+                    let body = json!({
+                        "object": "list",
+                        "data": [{"object": "embedding", "embedding": [length], "index": 0}],
+                        "model": "text-embedding-3-small",
+                        "usage": {"prompt_tokens": total_tokens, "total_tokens": total_tokens}
+                    })
+                    .to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        url
+    }
+
+    async fn run_embed(processor: ProcessorConfig, input: &str) -> Vec<Value> {
+        use tokio::io::AsyncReadExt;
+
+        let (writer, mut output) = tokio::io::duplex(64 * 1024);
+        execute_udf("embed", processor, input.as_bytes(), writer)
+            .await
+            .unwrap();
+
+        let mut text = String::new();
+        output.read_to_string(&mut text).await.unwrap();
+        serde_json::Deserializer::from_str(&text)
+            .into_iter::<Value>()
+            .map(|v| v.unwrap()[0].clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_continue_on_error() {
+        let api_base = mock_embeddings_server(1).await;
+        let config = json!({ "api_base": api_base }).to_string();
+        let valid = serde_json::to_string(&vec![config.clone(), "hello".to_string()]).unwrap();
+        let missing_input = serde_json::to_string(&vec![config]).unwrap();
+        let input = format!("{valid}\nnot json\n{missing_input}\n{valid}\n");
+
+        let processor = ProcessorConfig {
+            parallel: 1,
+            batch_size: 1,
+            batch_delay_ms: 0,
+            continue_on_error: true,
+            unordered: false,
+        };
+        let rows = run_embed(processor, &input).await;
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0], json!([5.0]));
+        assert_eq!(rows[1]["line"], 1);
+        assert!(rows[1]["error"].is_string());
+        assert_eq!(rows[2]["line"], 2);
+        assert_eq!(rows[2]["error"], "No input provided");
+        assert_eq!(rows[3], json!([5.0]));
+    }
+
+    #[tokio::test]
+    async fn test_abort_on_error() {
+        let processor = ProcessorConfig {
+            parallel: 1,
+            batch_size: 1,
+            batch_delay_ms: 0,
+            continue_on_error: false,
+            unordered: false,
+        };
+        let (writer, _output) = tokio::io::duplex(1024);
+        let result = execute_udf("embed", processor, "not json\n".as_bytes(), writer).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_lines_stay_within_max_tokens() {
+        // Each line is estimated at 2 tokens and the mock reports 2 tokens of usage
+        let api_base = mock_embeddings_server(2).await;
+        let config = json!({ "api_base": api_base, "max_tokens": 5 }).to_string();
+        let line = serde_json::to_string(&vec![config, "aaaaaaaa".to_string()]).unwrap();
+        let input = format!("{line}\n").repeat(10);
+
+        let processor = ProcessorConfig {
+            parallel: 10,
+            batch_size: 10,
+            batch_delay_ms: 0,
+            continue_on_error: true,
+            unordered: false,
+        };
+        let rows = run_embed(processor, &input).await;
+
+        let completed = rows.iter().filter(|r| r.is_array()).count();
+        assert_eq!(rows.len(), 10);
+        assert_eq!(completed, 2);
+        assert!(completed * 2 <= 5);
+    }
+
+    fn lines_of_increasing_length(api_base: &str) -> String {
+        let config = json!({ "api_base": api_base }).to_string();
+        (1..=6)
+            .map(|length| {
+                let line = vec![config.clone(), "a".repeat(length)];
+                format!("{}\n", serde_json::to_string(&line).unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_ordered_output() {
+        let api_base = mock_embeddings_server(1).await;
+        let processor = ProcessorConfig {
+            parallel: 6,
+            batch_size: 6,
+            batch_delay_ms: 0,
+            continue_on_error: false,
+            unordered: false,
+        };
+        let rows = run_embed(processor, &lines_of_increasing_length(&api_base)).await;
+
+        let expected = (1..=6).map(|l| json!([l as f64])).collect::<Vec<_>>();
+        assert_eq!(rows, expected);
+    }
+
+    #[tokio::test]
+    async fn test_unordered_output() {
+        let api_base = mock_embeddings_server(1).await;
+        let processor = ProcessorConfig {
+            parallel: 6,
+            batch_size: 6,
+            batch_delay_ms: 0,
+            continue_on_error: false,
+            unordered: true,
+        };
+        let rows = run_embed(processor, &lines_of_increasing_length(&api_base)).await;
+
+        assert_eq!(rows.len(), 6);
+        for row in &rows {
+            let line = row["line"].as_u64().unwrap();
+            assert_eq!(row["result"], json!([(line + 1) as f64]));
+        }
+        let mut lines = rows
+            .iter()
+            .map(|r| r["line"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(lines, (0..6).collect::<Vec<_>>());
+    }
+}